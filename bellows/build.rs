@@ -1,16 +1,21 @@
 //! Build script for bellows.
 //!
-//! Copies the kernel binary into `OUT_DIR` so it can be embedded
-//! via `include_bytes!` without polluting the source tree.
+//! With the `embed_kernel` feature, copies the kernel binary into
+//! `OUT_DIR` so it can be embedded via `include_bytes!` without polluting
+//! the source tree.  Without it bellows reads the kernel from the boot
+//! volume and there is nothing to do.
 //!
-//! The caller (flasks) sets `KERNEL_BIN_PATH` to the absolute path
-//! of the kernel EFI binary before invoking `cargo build`.
+//! Set `KERNEL_BIN_PATH` to the absolute path of the kernel EFI binary
+//! before invoking `cargo build --features embed_kernel`.
 
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 
 fn main() {
+    if env::var_os("CARGO_FEATURE_EMBED_KERNEL").is_none() {
+        return;
+    }
     println!("cargo:rerun-if-env-changed=KERNEL_BIN_PATH");
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
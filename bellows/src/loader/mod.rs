@@ -1,5 +1,6 @@
 use core::ffi::c_void;
 
+use petroleum::common::boot_params::BootParams;
 use petroleum::common::{BellowsError, EfiBootServices, EfiMemoryType, EfiStatus, EfiSystemTable};
 
 // Module declarations for separated functionality
@@ -17,6 +18,16 @@ const KERNEL_ARGS_PAGES: usize = 256;
 // Standard 4 KiB page size
 const PAGE_SIZE_4K: u64 = 4096;
 
+// InitAndJumpArgs, KernelArgs and BootParams share the first page of the
+// KernelArgs block; the L4 table starts on the next one.
+const _: () = assert!(
+    core::mem::size_of::<petroleum::page_table::InitAndJumpArgs>()
+        + core::mem::size_of::<petroleum::assembly::KernelArgs>()
+        + core::mem::size_of::<BootParams>()
+        + 32
+        <= PAGE_SIZE_4K as usize
+);
+
 /// Exits boot services and jumps to the kernel's entry point.
 /// This function is the final step of the bootloader.
 pub fn exit_boot_services_and_jump(
@@ -25,6 +36,8 @@ pub fn exit_boot_services_and_jump(
     kernel_phys_start: x86_64::PhysAddr,
     kernel_entry_phys: u64,
     _entry: extern "efiapi" fn(usize, *mut EfiSystemTable, *mut c_void, usize) -> !,
+    cmdline: &str,
+    initrd: Option<&'static [u8]>,
 ) -> petroleum::common::Result<!> {
     // Immediate debug prints on entry to pinpoint exact hang location
     #[cfg(feature = "debug_loader")]
@@ -293,6 +306,16 @@ pub fn exit_boot_services_and_jump(
         &mut frame_allocator,
         &memory_map_descriptors,
     );
+    // The initrd sits in loader-data pages, which the allocator counts as
+    // free; keep the kernel's page tables from being built on top of it.
+    if let Some(initrd) = initrd {
+        frame_allocator
+            .reserve_frames(
+                initrd.as_ptr() as u64,
+                initrd.len().div_ceil(PAGE_SIZE_4K as usize),
+            )
+            .map_err(|_| BellowsError::InvalidState("Failed to reserve the initrd."))?;
+    }
 
     // Calculate kernel entry virtual address (higher half)
     let kernel_entry_virt =
@@ -314,6 +337,11 @@ pub fn exit_boot_services_and_jump(
         + core::mem::size_of::<petroleum::page_table::InitAndJumpArgs>() as u64;
     // Align to 16 bytes
     let kernel_args_phys_aligned = (kernel_args_phys + 15) & !15;
+    // BootParams follow KernelArgs, still below the L4 table page.
+    let boot_params_phys = (kernel_args_phys_aligned
+        + core::mem::size_of::<petroleum::assembly::KernelArgs>() as u64
+        + 15)
+        & !15;
 
     let fb_addr;
     let fb_width;
@@ -321,10 +349,8 @@ pub fn exit_boot_services_and_jump(
     let fb_bpp;
     let fb_stride;
     let fb_pixel_format;
-    if let Some(config) = petroleum::FULLERENE_FRAMEBUFFER_CONFIG
-        .get()
-        .and_then(|mutex| *mutex.lock())
-    {
+    let fb_config = petroleum::framebuffer::get_config();
+    if let Some(config) = fb_config {
         fb_addr = config.address as u64;
         fb_width = config.width;
         fb_height = config.height;
@@ -340,6 +366,11 @@ pub fn exit_boot_services_and_jump(
         fb_pixel_format = 0;
     }
 
+    let mut boot_params = BootParams::new(cmdline, fb_config);
+    if let Some(initrd) = initrd {
+        boot_params = boot_params.with_initrd(initrd.as_ptr() as u64, initrd.len() as u64);
+    }
+
     unsafe {
         let kernel_args_ptr = kernel_args_phys_aligned as *mut petroleum::assembly::KernelArgs;
         core::ptr::write_volatile(
@@ -358,8 +389,10 @@ pub fn exit_boot_services_and_jump(
                 fb_bpp,
                 fb_stride,
                 fb_pixel_format,
+                boot_params: boot_params_phys,
             },
         );
+        core::ptr::write_volatile(boot_params_phys as *mut BootParams, boot_params);
 
         // Map KernelArgs address down to page boundary for identity mapping.
         // The actual KernelArgs pointer will be reconstructed by the kernel using arg1 + offset.
@@ -409,7 +442,7 @@ pub fn exit_boot_services_and_jump(
     }
 }
 
-/// Load EFI PE image using petroleum PE module
+/// Load the kernel image, choosing the PE or ELF64 loader by its magic.
 pub fn load_efi_image(
     st: &petroleum::common::EfiSystemTable,
     file: &[u8],
@@ -419,5 +452,9 @@ pub fn load_efi_image(
     u64,
     extern "efiapi" fn(usize, *mut petroleum::common::EfiSystemTable, *mut c_void, usize) -> !,
 )> {
-    petroleum::page_table::pe::load_efi_image(st, file, phys_offset)
+    if petroleum::page_table::elf::is_elf(file) {
+        petroleum::page_table::elf::load_elf_image(st, file, phys_offset)
+    } else {
+        petroleum::page_table::pe::load_efi_image(st, file, phys_offset)
+    }
 }
@@ -9,12 +9,18 @@ extern crate alloc;
 petroleum::define_panic_handler!();
 petroleum::define_alloc_error_handler!();
 
+#[cfg(feature = "embed_kernel")]
 static KERNEL_BINARY: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/kernel.bin"));
 
 mod loader;
 
+use core::ffi::c_void;
 use loader::{exit_boot_services_and_jump, init_heap, load_efi_image};
-use petroleum::common::EfiSystemTable;
+use petroleum::common::boot_params::{BOOT_CMDLINE_MAX, cmdline_from_load_options};
+use petroleum::common::{
+    EFI_LOADED_IMAGE_PROTOCOL_GUID, EfiBootServices, EfiLoadedImageProtocol, EfiStatus,
+    EfiSystemTable,
+};
 use petroleum::graphics::boot_screen::{BootFramebuffer, KERNEL_STAGE_COUNT};
 
 #[unsafe(no_mangle)]
@@ -61,14 +67,22 @@ pub unsafe extern "efiapi" fn efi_main(
             Some(config)
         }
         None => {
-            petroleum::bootloader_log!("No directly addressable GOP mode; continuing headless.");
+            petroleum::bootloader_log!(
+                "No directly addressable GOP mode or ramfb; continuing headless."
+            );
             None
         }
     };
     petroleum::bootloader_log!("Graphics initialization complete.");
 
-    let efi_image_file = KERNEL_BINARY;
-    let efi_image_size = KERNEL_BINARY.len();
+    let efi_image_file = match kernel_image(bs, image_handle) {
+        Ok(image) => image,
+        Err(err) => {
+            petroleum::println!("Failed to read the kernel: {:?}", err);
+            panic!("Failed to read the kernel.");
+        }
+    };
+    let efi_image_size = efi_image_file.len();
     petroleum::bootloader_log!("Bellows: Kernel file size check: {} bytes", efi_image_size);
     if efi_image_size == 0 {
         panic!("Kernel file is empty.");
@@ -95,12 +109,16 @@ pub unsafe extern "efiapi" fn efi_main(
         }
     };
     petroleum::println!("Bellows: EFI image loaded.");
-    petroleum::println!("Bellows: Kernel loaded from embedded binary.");
+    release_kernel_image(bs, efi_image_file);
+    let initrd = initrd_image(bs, image_handle);
     if let Some(config) = boot_framebuffer.and_then(BootFramebuffer::from_config) {
         unsafe {
             config.draw_stage(0, KERNEL_STAGE_COUNT, b"ENTERING KERNEL");
         }
     }
+    let mut cmdline_buf = [0u8; BOOT_CMDLINE_MAX];
+    let cmdline = boot_cmdline(bs, image_handle, &mut cmdline_buf);
+    petroleum::bootloader_log!("Kernel command line: \"{}\"", cmdline);
     petroleum::println!("Exiting boot services and jumping to kernel...");
     petroleum::println!("Bellows: About to exit boot services and jump to kernel.");
     match exit_boot_services_and_jump(
@@ -109,6 +127,8 @@ pub unsafe extern "efiapi" fn efi_main(
         kernel_phys_start,
         kernel_entry_phys,
         entry,
+        cmdline,
+        initrd,
     ) {
         Ok(_) => unreachable!(),
         Err(err) => {
@@ -117,3 +137,95 @@ pub unsafe extern "efiapi" fn efi_main(
         }
     }
 }
+
+/// The kernel image, embedded in bellows at build time.
+#[cfg(feature = "embed_kernel")]
+fn kernel_image(
+    _bs: &EfiBootServices,
+    _image_handle: usize,
+) -> petroleum::common::Result<&'static [u8]> {
+    petroleum::println!("Bellows: Kernel loaded from embedded binary.");
+    Ok(KERNEL_BINARY)
+}
+
+/// The kernel image, read from `\EFI\BOOT\KERNEL.EFI` on the volume bellows
+/// was loaded from into loader-data pages.
+#[cfg(not(feature = "embed_kernel"))]
+fn kernel_image(
+    bs: &EfiBootServices,
+    image_handle: usize,
+) -> petroleum::common::Result<&'static [u8]> {
+    use petroleum::filesystem::{
+        kernel_path_utf16, open_boot_volume, open_file, read_file_to_memory,
+    };
+    let root = open_boot_volume(bs, image_handle)?;
+    let file = open_file(&root, &kernel_path_utf16())?;
+    let (phys_addr, size) = read_file_to_memory(bs, &file)?;
+    petroleum::println!("Bellows: Kernel loaded from the boot volume.");
+    // Boot services memory is identity mapped.
+    Ok(unsafe { core::slice::from_raw_parts(phys_addr as *const u8, size) })
+}
+
+/// Give back the pages [`kernel_image`] read the kernel into, once its
+/// sections have been copied out.
+fn release_kernel_image(bs: &EfiBootServices, image: &'static [u8]) {
+    if cfg!(not(feature = "embed_kernel")) {
+        (bs.free_pages)(image.as_ptr() as usize, image.len().div_ceil(4096));
+    }
+}
+
+/// The initrd archive, read from `\EFI\BOOT\INITRD.CPIO` on the boot
+/// volume into loader-data pages, or `None` if the volume has none.
+fn initrd_image(bs: &EfiBootServices, image_handle: usize) -> Option<&'static [u8]> {
+    use petroleum::filesystem::{
+        initrd_path_utf16, open_boot_volume, open_file, read_file_to_memory,
+    };
+    let root = open_boot_volume(bs, image_handle).ok()?;
+    let Ok(file) = open_file(&root, &initrd_path_utf16()) else {
+        petroleum::println!("Bellows: No initrd on the boot volume.");
+        return None;
+    };
+    match read_file_to_memory(bs, &file) {
+        Ok((phys_addr, size)) => {
+            petroleum::println!("Bellows: Initrd loaded. Size: {}", size);
+            // Boot services memory is identity mapped.
+            Some(unsafe { core::slice::from_raw_parts(phys_addr as *const u8, size) })
+        }
+        Err(err) => {
+            petroleum::println!("Bellows: Failed to read the initrd: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Kernel command line: the image's UEFI load options if they hold one,
+/// otherwise the line embedded at build time through `FULLERENE_CMDLINE`.
+fn boot_cmdline<'a>(
+    bs: &EfiBootServices,
+    image_handle: usize,
+    buf: &'a mut [u8; BOOT_CMDLINE_MAX],
+) -> &'a str {
+    let mut interface: *mut c_void = core::ptr::null_mut();
+    let status = (bs.handle_protocol)(
+        image_handle,
+        EFI_LOADED_IMAGE_PROTOCOL_GUID.as_ptr(),
+        &mut interface,
+    );
+    if EfiStatus::from(status) == EfiStatus::Success && !interface.is_null() {
+        let image = unsafe { &*(interface as *const EfiLoadedImageProtocol) };
+        if !image.load_options.is_null() && image.load_options_size >= 2 {
+            let units = unsafe {
+                core::slice::from_raw_parts(
+                    image.load_options as *const u16,
+                    image.load_options_size as usize / 2,
+                )
+            };
+            let len = cmdline_from_load_options(units, buf);
+            if len > 0 {
+                // Load options are reduced to printable ASCII.
+                return core::str::from_utf8(&buf[..len]).unwrap_or("");
+            }
+        }
+    }
+    option_env!("FULLERENE_CMDLINE").unwrap_or("")
+}
@@ -79,6 +79,20 @@ pub fn dispatch_with_services(
     dispatch_inner(commands, terminal, line, Some(services))
 }
 
+/// Run an already parsed pipeline.
+///
+/// When `pipeline.stdout` is set, the last stage's output is captured like
+/// an intermediate stage's and left for the caller to collect with
+/// [`Terminal::take_stdout`]; writing it to the file is up to the caller.
+pub fn dispatch_pipeline_with_services(
+    commands: &[&dyn Command],
+    terminal: &mut dyn Terminal,
+    pipeline: &Pipeline,
+    services: &dyn Any,
+) -> bool {
+    run_pipeline(commands, terminal, pipeline, Some(services))
+}
+
 fn dispatch_inner(
     commands: &[&dyn Command],
     terminal: &mut dyn Terminal,
@@ -89,13 +103,24 @@ fn dispatch_inner(
     if trimmed.is_empty() {
         return true;
     }
+    run_pipeline(commands, terminal, &Pipeline::parse(trimmed), services)
+}
 
-    let pipeline = Pipeline::parse(trimmed);
+fn run_pipeline(
+    commands: &[&dyn Command],
+    terminal: &mut dyn Terminal,
+    pipeline: &Pipeline,
+    services: Option<&dyn Any>,
+) -> bool {
     if pipeline.commands.is_empty() {
         return true;
     }
+    let redirected = pipeline.stdout.is_some();
 
     if pipeline.commands.len() == 1 && pipeline.commands[0].name == "help" {
+        if redirected {
+            terminal.arm_pipe_stdout();
+        }
         list_commands(commands, terminal);
         return true;
     }
@@ -121,7 +146,7 @@ fn dispatch_inner(
             terminal.set_stdin(input);
         }
 
-        if !is_last {
+        if !is_last || redirected {
             terminal.arm_pipe_stdout();
         }
 
@@ -280,6 +305,21 @@ mod tests {
         assert!(terminal.pipe_stdin.is_none());
     }
 
+    #[test]
+    fn redirected_output_is_left_for_the_caller() {
+        let commands: &[&dyn Command] = &[&EMIT, &CONSUME];
+        let mut terminal = FakeTerminal::default();
+        let pipeline = Pipeline::parse("emit | consume > out.txt");
+        assert!(dispatch_pipeline_with_services(
+            commands,
+            &mut terminal,
+            &pipeline,
+            &()
+        ));
+        assert!(terminal.output.is_empty());
+        assert_eq!(terminal.take_stdout().as_deref(), Some("pipeline data"));
+    }
+
     #[test]
     fn command_can_stop_dispatch() {
         let commands: &[&dyn Command] = &[&STOP];
@@ -6,16 +6,23 @@ use alloc::vec::Vec;
 #[derive(Debug, Clone)]
 pub struct Pipeline {
     pub commands: Vec<ParsedCommand>,
+    /// Where `> file` or `>> file` at the end of the line sends the last
+    /// stage's output.
+    pub stdout: Option<Redirect>,
 }
 
 impl Pipeline {
     pub fn parse(line: &str) -> Self {
+        let (line, stdout) = match line.split_once('>') {
+            Some((line, target)) => (line, Some(Redirect::parse(target))),
+            None => (line, None),
+        };
         let commands: Vec<ParsedCommand> = line
             .split('|')
             .map(|s| ParsedCommand::parse(s.trim()))
             .filter(|c| !c.name.is_empty())
             .collect();
-        Self { commands }
+        Self { commands, stdout }
     }
 
     pub fn is_simple(&self) -> bool {
@@ -31,7 +38,48 @@ impl fmt::Display for Pipeline {
             }
             write!(f, "{}", cmd)?;
         }
-        Ok(())
+        match &self.stdout {
+            Some(redirect) => write!(f, " {redirect}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An output redirection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub path: String,
+    /// `>>`: add to the end of the file instead of replacing it.
+    pub append: bool,
+}
+
+impl Redirect {
+    /// Parse what follows the first `>` of a line.
+    fn parse(target: &str) -> Self {
+        let (append, path) = match target.strip_prefix('>') {
+            Some(path) => (true, path),
+            None => (false, target),
+        };
+        Self {
+            path: path.trim().to_string(),
+            append,
+        }
+    }
+
+    /// Whether the target is a single path, rather than missing or followed
+    /// by more words, pipes or redirections.
+    pub fn is_valid(&self) -> bool {
+        !self.path.is_empty()
+            && !self
+                .path
+                .contains(|c: char| c.is_whitespace() || c == '|' || c == '>')
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.append { ">>" } else { ">" };
+        write!(f, "{operator} {}", self.path)
     }
 }
 
@@ -118,6 +166,27 @@ mod tests {
         assert_eq!(pipeline.commands[0].args[0], "foo");
     }
 
+    #[test]
+    fn test_parse_redirect() {
+        let pipeline = Pipeline::parse("echo hello > /ram/test.txt");
+        assert_eq!(pipeline.commands[0].args, ["hello"]);
+        let redirect = pipeline.stdout.unwrap();
+        assert_eq!(redirect.path, "/ram/test.txt");
+        assert!(!redirect.append && redirect.is_valid());
+
+        let pipeline = Pipeline::parse("ls | grep txt>>log");
+        assert_eq!(pipeline.commands.len(), 2);
+        assert_eq!(pipeline.commands[1].args, ["txt"]);
+        assert_eq!(pipeline.to_string(), "ls | grep txt >> log");
+        assert!(pipeline.stdout.unwrap().append);
+
+        assert!(Pipeline::parse("echo hello").stdout.is_none());
+        assert!(!Pipeline::parse("echo >").stdout.unwrap().is_valid());
+        assert!(!Pipeline::parse("echo > a b").stdout.unwrap().is_valid());
+        assert!(!Pipeline::parse("echo > a | cat").stdout.unwrap().is_valid());
+        assert!(!Pipeline::parse("echo >>> a").stdout.unwrap().is_valid());
+    }
+
     #[test]
     fn test_parse_without_args() {
         let pipeline = Pipeline::parse("ls");
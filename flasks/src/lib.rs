@@ -1,4 +1,352 @@
+use std::fmt;
+use std::io::{self, Read, Write};
 use std::path::Path;
+use std::process::ExitStatus;
+use std::str::FromStr;
+
+/// Added by the kernel to its exit code before writing it to the
+/// `isa-debug-exit` port, which makes QEMU exit with `(value << 1) | 1`.
+const DEBUG_EXIT_BASE: i32 = 0x10;
+/// Largest exit code the kernel reports (QEMU status 255).
+const MAX_GUEST_EXIT_CODE: i32 = 0x6F;
+
+/// `flasks --test` exit code when QEMU runs past the timeout, as for
+/// `timeout(1)`.
+pub const TIMED_OUT_EXIT_CODE: i32 = 124;
+/// `flasks --test` exit code when QEMU ends without a guest exit code:
+/// QEMU failed, was killed, or the guest reset or powered off some other
+/// way.
+pub const NO_GUEST_EXIT_CODE: i32 = 125;
+
+/// How a QEMU run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QemuOutcome {
+    /// The kernel shut down through `isa-debug-exit` with this exit code,
+    /// 0 for success.
+    GuestExit(u8),
+    /// QEMU exited with a status that does not carry a guest exit code.
+    QemuExit(i32),
+    /// QEMU was killed by a signal.
+    Signalled,
+    /// QEMU ran past the timeout and was killed.
+    TimedOut,
+}
+
+impl QemuOutcome {
+    pub fn from_status(status: ExitStatus) -> Self {
+        status.code().map_or(Self::Signalled, Self::from_code)
+    }
+
+    pub fn from_code(code: i32) -> Self {
+        let value = code >> 1;
+        if code & 1 == 1
+            && (DEBUG_EXIT_BASE..=DEBUG_EXIT_BASE + MAX_GUEST_EXIT_CODE).contains(&value)
+        {
+            Self::GuestExit((value - DEBUG_EXIT_BASE) as u8)
+        } else {
+            Self::QemuExit(code)
+        }
+    }
+
+    /// Whether an interactive run ended well: the guest shut down with
+    /// exit code 0, or QEMU was closed normally.
+    pub fn is_clean(self) -> bool {
+        matches!(self, Self::GuestExit(0) | Self::QemuExit(0))
+    }
+
+    /// Exit code for `flasks --test`: the guest's own exit code, or
+    /// [`TIMED_OUT_EXIT_CODE`] / [`NO_GUEST_EXIT_CODE`].
+    pub fn test_exit_code(self) -> i32 {
+        match self {
+            Self::GuestExit(code) => i32::from(code),
+            Self::TimedOut => TIMED_OUT_EXIT_CODE,
+            Self::QemuExit(_) | Self::Signalled => NO_GUEST_EXIT_CODE,
+        }
+    }
+}
+
+/// A display resolution given as `WxH`, e.g. `1024x768`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    /// What virtio-gpu is given when no `--resolution` is passed.
+    pub const DEFAULT: Self = Self {
+        width: 1024,
+        height: 768,
+    };
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected WIDTHxHEIGHT such as 1024x768, got `{}`", value);
+        let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+        let width: u32 = width.parse().map_err(|_| invalid())?;
+        let height: u32 = height.parse().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        Ok(Self { width, height })
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Copy `input` to both `terminal` and `log` until it ends, flushing each
+/// chunk so that serial output shows up as the guest writes it.
+///
+/// A terminal that goes away (a closed pipe, say) stops only the terminal
+/// copy; the log keeps everything.  Errors writing the log are returned.
+pub fn tee(mut input: impl Read, mut terminal: impl Write, mut log: impl Write) -> io::Result<()> {
+    let mut buf = [0u8; 4096];
+    let mut terminal_ok = true;
+    loop {
+        let len = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        if terminal_ok {
+            terminal_ok = terminal
+                .write_all(&buf[..len])
+                .and_then(|()| terminal.flush())
+                .is_ok();
+        }
+        log.write_all(&buf[..len])?;
+        log.flush()?;
+    }
+    Ok(())
+}
+
+/// Write the directory tree under `root` to `out` as a CPIO `newc`
+/// archive, the format the kernel mounts its initrd from.
+///
+/// Entries are named relative to `root` and written in sorted order with
+/// zero timestamps and owners, so the same tree always gives the same
+/// archive.  Anything other than directories and regular files is skipped.
+pub fn write_cpio(root: &Path, mut out: impl Write) -> io::Result<()> {
+    let mut ino = 0;
+    write_cpio_dir(root, "", &mut out, &mut ino)?;
+    write_cpio_entry(&mut out, 0, "TRAILER!!!", 0, &[])
+}
+
+fn write_cpio_dir(dir: &Path, prefix: &str, out: &mut impl Write, ino: &mut u32) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Some(name) = entry
+            .file_name()
+            .to_str()
+            .map(|name| format!("{prefix}{name}"))
+        else {
+            continue;
+        };
+        let file_type = entry.file_type()?;
+        *ino += 1;
+        if file_type.is_dir() {
+            write_cpio_entry(out, *ino, &name, 0o040755, &[])?;
+            write_cpio_dir(&entry.path(), &format!("{name}/"), out, ino)?;
+        } else if file_type.is_file() {
+            write_cpio_entry(out, *ino, &name, 0o100644, &std::fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_cpio_entry(
+    out: &mut impl Write,
+    ino: u32,
+    name: &str,
+    mode: u32,
+    body: &[u8],
+) -> io::Result<()> {
+    let len = u32::try_from(body.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large for cpio"))?;
+    let nlink = if mode & 0o040000 != 0 { 2 } else { 1 };
+    let namesize = name.len() + 1;
+    write!(
+        out,
+        "070701{ino:08x}{mode:08x}{:08x}{:08x}{nlink:08x}{:08x}{len:08x}{:08x}{:08x}{:08x}{:08x}{namesize:08x}{:08x}",
+        0, 0, 0, 0, 0, 0, 0, 0
+    )?;
+    out.write_all(name.as_bytes())?;
+    // The header is 110 bytes; name and body are each padded to 4 bytes.
+    out.write_all(&[0; 4][..1 + (4 - (110 + namesize) % 4) % 4])?;
+    out.write_all(body)?;
+    out.write_all(&[0; 3][..(4 - body.len() % 4) % 4])
+}
+
+/// A function in the kernel image: `len` bytes from RVA `start`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub start: u32,
+    pub len: u32,
+    pub name: String,
+}
+
+/// Name of the PE section the kernel reserves for its symbol table.
+pub const SYMBOL_SECTION: &str = ".ksyms";
+const SYMBOL_MAGIC: &[u8; 4] = b"KSYM";
+
+/// The functions in an `lld-link /MAP` file, sorted by address and with
+/// demangled names that leave out the hash.
+///
+/// The map gives no sizes, so each function is taken to run up to the next
+/// symbol in its section, the last one to the end of the section.  Symbols
+/// in data sections are left out, as are all but the first of several at
+/// the same address.
+pub fn parse_linker_map(map: &str) -> Vec<Symbol> {
+    let hex = |field: &str| u64::from_str_radix(field.trim_end_matches('H'), 16).ok();
+    // "section:offset", e.g. "0001:00000120".
+    let location = |field: &str| {
+        let (section, offset) = field.split_once(':')?;
+        Some((hex(section)?, hex(offset)?))
+    };
+
+    let mut preferred_base = 0;
+    let mut code_section_ends = std::collections::BTreeMap::new();
+    // (section, offset, rva, name)
+    let mut found = Vec::new();
+    for line in map.lines() {
+        if let Some(base) = line.trim().strip_prefix("Preferred load address is ") {
+            preferred_base = hex(base).unwrap_or(0);
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some((section, offset)) = fields.first().copied().and_then(location) else {
+            continue;
+        };
+        match fields[1..] {
+            // A section contribution: " 0001:00000000 00012345H .text CODE".
+            [len, _, "CODE"] if len.ends_with('H') => {
+                let end = offset + hex(len).unwrap_or(0);
+                let section_end = code_section_ends.entry(section).or_insert(end);
+                *section_end = (*section_end).max(end);
+            }
+            // A symbol: " 0001:00000120  name  0000000140001120 [f] [i] object".
+            [name, address, ..] => {
+                if let Some(rva) =
+                    hex(address).and_then(|address| address.checked_sub(preferred_base))
+                {
+                    found.push((section, offset, rva, name));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found.retain(|(section, ..)| code_section_ends.contains_key(section));
+    found.sort_by_key(|&(_, _, rva, _)| rva);
+    found.dedup_by_key(|&mut (_, _, rva, _)| rva);
+    let mut symbols = Vec::new();
+    for (i, &(section, offset, rva, name)) in found.iter().enumerate() {
+        let section_end = (rva + code_section_ends[&section]).saturating_sub(offset);
+        let end = match found.get(i + 1) {
+            Some(&(next_section, _, next_rva, _)) if next_section == section => {
+                next_rva.min(section_end)
+            }
+            _ => section_end,
+        };
+        let (Ok(start), Ok(len)) = (u32::try_from(rva), u32::try_from(end.saturating_sub(rva)))
+        else {
+            continue;
+        };
+        if len > 0 {
+            symbols.push(Symbol {
+                start,
+                len,
+                name: format!("{:#}", rustc_demangle::demangle(name)),
+            });
+        }
+    }
+    symbols
+}
+
+/// Write `symbols` into the [`SYMBOL_SECTION`] of the PE image `image`, in
+/// the layout `fullerene-kernel/src/symbols.rs` reads.
+///
+/// Fails if the image has no such section, if the section does not start
+/// with the empty table the kernel links in, or if the table does not fit.
+pub fn write_symbol_table(image: &mut [u8], symbols: &[Symbol]) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let (rva, file_offset, size) = find_pe_section(image, SYMBOL_SECTION)
+        .ok_or_else(|| invalid(format!("no {SYMBOL_SECTION} section in the kernel image")))?;
+    let section = image
+        .get_mut(file_offset..file_offset + size)
+        .ok_or_else(|| invalid(format!("{SYMBOL_SECTION} runs past the end of the image")))?;
+    if !section.starts_with(SYMBOL_MAGIC) {
+        return Err(invalid(format!("{SYMBOL_SECTION} holds no symbol table")));
+    }
+
+    let mut table = SYMBOL_MAGIC.to_vec();
+    table.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    table.extend_from_slice(&rva.to_le_bytes());
+    table.extend_from_slice(&[0; 4]);
+    let mut names = Vec::new();
+    for symbol in symbols {
+        for field in [
+            symbol.start,
+            symbol.len,
+            names.len() as u32,
+            symbol.name.len() as u32,
+        ] {
+            table.extend_from_slice(&field.to_le_bytes());
+        }
+        names.extend_from_slice(symbol.name.as_bytes());
+    }
+    table.extend_from_slice(&names);
+    if table.len() > section.len() {
+        return Err(invalid(format!(
+            "the symbol table needs {} bytes but {SYMBOL_SECTION} holds {}; \
+             raise symbols::CAPACITY in the kernel",
+            table.len(),
+            section.len()
+        )));
+    }
+    section[..table.len()].copy_from_slice(&table);
+    section[table.len()..].fill(0);
+    Ok(())
+}
+
+/// The RVA, file offset and size of the section `name` of the PE image
+/// `image`.
+fn find_pe_section(image: &[u8], name: &str) -> Option<(u32, usize, usize)> {
+    let u16_at = |at: usize| Some(u16::from_le_bytes(image.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(image.get(at..at + 4)?.try_into().ok()?));
+
+    let pe = u32_at(0x3C)? as usize;
+    if image.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let sections = u16_at(pe + 6)? as usize;
+    let first = pe + 24 + u16_at(pe + 20)? as usize;
+    (0..sections).find_map(|i| {
+        let header = first + i * 40;
+        let raw_name = image.get(header..header + 8)?;
+        let len = raw_name.iter().position(|&b| b == 0).unwrap_or(8);
+        if &raw_name[..len] != name.as_bytes() {
+            return None;
+        }
+        // Past the smaller of the virtual and raw sizes is either not
+        // loaded or not in the file.
+        let size = u32_at(header + 8)?.min(u32_at(header + 16)?);
+        Some((
+            u32_at(header + 12)?,
+            u32_at(header + 20)? as usize,
+            size as usize,
+        ))
+    })
+}
 
 /// Finds the path to `libpthread.so.0` in common locations.
 ///
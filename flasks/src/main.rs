@@ -1,7 +1,15 @@
 // fullerene/flasks/src/main.rs
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
+use flasks::{QemuOutcome, Resolution};
 use isobemak::{BootInfo, IsoImage, IsoImageFile, UefiBootInfo, build_iso};
-use std::{env, io, path::PathBuf, process::Command};
+use std::{
+    env,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
 
 use env_logger;
 
@@ -15,31 +23,92 @@ struct Args {
     #[arg(long)]
     headless: bool,
 
-    /// Timeout for QEMU execution in seconds
+    /// Kill QEMU if it is still running after this many seconds
     #[arg(long)]
     timeout: Option<u64>,
 
+    /// Boot the kernel with `test` on its command line and exit with the
+    /// code it shuts down with: 0 on success, 124 on timeout, 125 if QEMU
+    /// ends without one. Implies --headless; --timeout defaults to 300
+    #[arg(long)]
+    test: bool,
+
     /// Build fullerene.iso and exit without launching QEMU
     #[arg(long)]
     iso_only: bool,
 
-    /// VGA device type: virtio-gpu, std, qxl, cirrus, none (default: virtio-gpu)
-    #[arg(long, default_value = "virtio-gpu")]
-    vga: String,
+    /// VGA device; petroleum finds a GOP framebuffer or falls back
+    /// differently on each
+    #[arg(long, value_enum, default_value_t = Vga::VirtioGpu)]
+    vga: Vga,
 
     /// Display backend: gtk, sdl, none, curses (default: gtk when not headless)
     #[arg(long)]
     display: Option<String>,
 
-    /// Screen resolution in WxH format (e.g., 1024x768). Only effective with virtio-gpu/qxl
-    #[arg(long, default_value = "1024x768")]
-    resolution: String,
+    /// Screen resolution in WxH format (e.g., 1024x768), also passed to the
+    /// kernel as `resolution=WxH`. Not supported by cirrus, ramfb or none
+    /// [default: 1024x768 with virtio-gpu]
+    #[arg(long)]
+    resolution: Option<Resolution>,
+
+    /// Number of virtual CPUs; the kernel starts the extra ones and parks them
+    #[arg(long, default_value_t = 1)]
+    smp: u32,
+
+    /// Build the kernel with its `debug_symbols` feature and patch its
+    /// symbol table in, so panic backtraces name functions
+    #[arg(long)]
+    debug_symbols: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Vga {
+    #[value(alias = "virtio")]
+    VirtioGpu,
+    Std,
+    Qxl,
+    Cirrus,
+    /// QEMU's `ramfb`, which bellows sets up when there is no GOP
+    Ramfb,
+    None,
+}
+
+impl Args {
+    /// The resolution asked of the display device, if it takes one.
+    fn resolution(&self) -> Option<Resolution> {
+        match self.vga {
+            Vga::VirtioGpu => Some(self.resolution.unwrap_or(Resolution::DEFAULT)),
+            Vga::Std | Vga::Qxl => self.resolution,
+            Vga::Cirrus | Vga::Ramfb | Vga::None => None,
+        }
+    }
+
+    /// Options flasks adds to the kernel command line.
+    fn kernel_cmdline_options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some(resolution) = self.resolution() {
+            options.push(format!("resolution={}", resolution));
+        }
+        if self.test {
+            options.push("test".to_string());
+        }
+        options
+    }
 }
 
 fn main() -> io::Result<()> {
     // Initialize env_logger - it will respect RUST_LOG environment variable for filtering
     env_logger::init();
     let args = Args::parse();
+    if args.resolution.is_some() && args.resolution().is_none() {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--resolution cannot be used with --vga cirrus, ramfb or none",
+            )
+            .exit();
+    }
     let workspace_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .parent()
         .expect("Failed to get workspace root")
@@ -51,13 +120,29 @@ fn main() -> io::Result<()> {
     }
 
     if args.iso_only {
-        let iso_path = create_iso(&workspace_root)?;
+        let iso_path = create_iso(
+            &workspace_root,
+            &args.kernel_cmdline_options(),
+            args.debug_symbols,
+        )?;
         println!("ISO rebuilt at {}", iso_path.display());
         return Ok(());
     }
 
-    run_qemu(&workspace_root, &args)?;
-    Ok(())
+    let outcome = run_qemu(&workspace_root, &args)?;
+    if args.test {
+        let code = outcome.test_exit_code();
+        match outcome {
+            QemuOutcome::GuestExit(0) => log::info!("Test run passed"),
+            outcome => log::error!("Test run failed: {:?} (exit code {})", outcome, code),
+        }
+        std::process::exit(code);
+    }
+    match outcome {
+        QemuOutcome::TimedOut => Err(io::Error::other("QEMU execution timed out")),
+        outcome if !outcome.is_clean() => Err(io::Error::other("QEMU execution failed")),
+        _ => Ok(()),
+    }
 }
 
 fn setup_ovmf(workspace_root: &PathBuf) -> io::Result<()> {
@@ -131,6 +216,25 @@ fn build_uefi_package(
     Ok(())
 }
 
+/// Copy the kernel at `kernel_path` with the symbols from the linker map
+/// its `debug_symbols` build leaves in `target_dir` patched into its
+/// `.ksyms` section, and return the copy's path.  The cargo output itself
+/// is left alone.
+fn add_symbol_table(target_dir: &Path, kernel_path: &Path) -> io::Result<PathBuf> {
+    let map = std::fs::read_to_string(target_dir.join("fullerene-kernel.map"))?;
+    let symbols = flasks::parse_linker_map(&map);
+    let mut image = std::fs::read(kernel_path)?;
+    flasks::write_symbol_table(&mut image, &symbols)?;
+    let patched_path = target_dir.join("fullerene-kernel-symbols.efi");
+    std::fs::write(&patched_path, image)?;
+    log::info!(
+        "Kernel with {} symbols at {}",
+        symbols.len(),
+        patched_path.display()
+    );
+    Ok(patched_path)
+}
+
 fn grub_cfg_content(_kernel_path: &PathBuf) -> String {
     // The ISO image always copies the kernel to /EFI/BOOT/KERNEL.EFI,
     // so the GRUB configuration must match this fixed path.
@@ -144,15 +248,40 @@ menuentry "Fullerene OS" {
     .to_string()
 }
 
-fn create_iso(workspace_root: &PathBuf) -> io::Result<PathBuf> {
+/// QEMU's serial output is copied here as well as to the terminal, next to
+/// the `-D` log in `qemu_log.txt`.
+const SERIAL_LOG_PATH: &str = "qemu_serial.log";
+
+/// Workspace directory packed into the initrd, which the kernel mounts at
+/// `/init`.  Without it the ISO carries no initrd.
+const INITRD_DIR: &str = "initrd";
+
+/// Seconds `--test` waits for the kernel to shut down when no --timeout is
+/// given.
+const DEFAULT_TEST_TIMEOUT_SECS: u64 = 300;
+
+/// Build the kernel and bellows and pack them, with an initrd of the
+/// workspace's `initrd/` directory if there is one, into `fullerene.iso`.
+/// Bellows passes `cmdline_options` on the kernel command line after
+/// anything from `FULLERENE_CMDLINE`.  With `debug_symbols`, the kernel
+/// carries its symbol table.
+fn create_iso(
+    workspace_root: &PathBuf,
+    cmdline_options: &[String],
+    debug_symbols: bool,
+) -> io::Result<PathBuf> {
     // --- 1. Build fullerene-kernel (no_std) ---
-    build_uefi_package(workspace_root, "fullerene-kernel", None)?;
+    let features = debug_symbols.then_some("debug_symbols");
+    build_uefi_package(workspace_root, "fullerene-kernel", features)?;
 
     let target_dir = workspace_root
         .join("target")
         .join("x86_64-unknown-uefi")
         .join("debug");
-    let kernel_path = target_dir.join("fullerene-kernel.efi");
+    let mut kernel_path = target_dir.join("fullerene-kernel.efi");
+    if debug_symbols {
+        kernel_path = add_symbol_table(&target_dir, &kernel_path)?;
+    }
     log::info!(
         "Kernel EFI at {} (size: {})",
         kernel_path.display(),
@@ -160,13 +289,23 @@ fn create_iso(workspace_root: &PathBuf) -> io::Result<PathBuf> {
     );
 
     // --- 2. Build bellows (no_std) ---
-    // Pass kernel path via environment variable so build.rs can copy
-    // it into OUT_DIR.  No source‑tree pollution.
+    // Bellows reads KERNEL.EFI from the ISO's ESP at boot, so it only
+    // rebuilds when its own sources or the command line change.
     let bellows_path = target_dir.join("bellows.efi");
 
-    let status = Command::new("cargo")
-        .current_dir(workspace_root)
-        .env("KERNEL_BIN_PATH", &kernel_path)
+    let mut bellows_build = Command::new("cargo");
+    bellows_build.current_dir(workspace_root);
+    if !cmdline_options.is_empty() {
+        let mut cmdline = env::var("FULLERENE_CMDLINE").unwrap_or_default();
+        for option in cmdline_options {
+            if !cmdline.is_empty() {
+                cmdline.push(' ');
+            }
+            cmdline.push_str(option);
+        }
+        bellows_build.env("FULLERENE_CMDLINE", cmdline);
+    }
+    let status = bellows_build
         .args([
             "+nightly",
             "build",
@@ -186,7 +325,24 @@ fn create_iso(workspace_root: &PathBuf) -> io::Result<PathBuf> {
         return Err(io::Error::other("bellows build failed"));
     }
 
-    // --- 3. Create ISO using isobemak ---
+    // --- 3. Pack the initrd ---
+    // Bellows reads it from the ESP next to KERNEL.EFI, if it is there.
+    let mut additional_efi_boot_files = Vec::new();
+    let initrd_dir = workspace_root.join(INITRD_DIR);
+    if initrd_dir.is_dir() {
+        let initrd_path = target_dir.join("INITRD.CPIO");
+        let mut initrd = io::BufWriter::new(File::create(&initrd_path)?);
+        flasks::write_cpio(&initrd_dir, &mut initrd)?;
+        io::Write::flush(&mut initrd)?;
+        log::info!(
+            "Initrd at {} (size: {})",
+            initrd_path.display(),
+            initrd_path.metadata()?.len()
+        );
+        additional_efi_boot_files.push(initrd_path);
+    }
+
+    // --- 4. Create ISO using isobemak ---
     let iso_path = workspace_root.join("fullerene.iso");
 
     let image = IsoImage {
@@ -205,7 +361,7 @@ fn create_iso(workspace_root: &PathBuf) -> io::Result<PathBuf> {
                 boot_image: bellows_path.clone(),
                 kernel_image: kernel_path.clone(),
                 destination_in_iso: "EFI/BOOT/BOOTX64.EFI".to_string(),
-                additional_efi_boot_files: Vec::new(),
+                additional_efi_boot_files,
                 grub_cfg_content: Some(grub_cfg_content(&kernel_path)),
             }),
         },
@@ -219,8 +375,10 @@ fn create_iso(workspace_root: &PathBuf) -> io::Result<PathBuf> {
 
 fn create_iso_and_setup(
     workspace_root: &PathBuf,
+    cmdline_options: &[String],
+    debug_symbols: bool,
 ) -> io::Result<(PathBuf, PathBuf, PathBuf, tempfile::NamedTempFile)> {
-    let iso_path = create_iso(workspace_root)?;
+    let iso_path = create_iso(workspace_root, cmdline_options, debug_symbols)?;
 
     let ovmf_fd_path = workspace_root
         .join("flasks")
@@ -242,12 +400,15 @@ fn create_iso_and_setup(
     Ok((iso_path, ovmf_fd_path, ovmf_vars_fd_path, temp_ovmf_vars_fd))
 }
 
-fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
+fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<QemuOutcome> {
     log::info!("Starting QEMU...");
-    let (iso_path, ovmf_fd_path, ovmf_vars_fd_path, temp_ovmf_vars_fd) =
-        create_iso_and_setup(&workspace_root)?;
+    let (iso_path, ovmf_fd_path, ovmf_vars_fd_path, temp_ovmf_vars_fd) = create_iso_and_setup(
+        &workspace_root,
+        &args.kernel_cmdline_options(),
+        args.debug_symbols,
+    )?;
 
-    // --- 4. Run QEMU with the created ISO ---
+    // --- 5. Run QEMU with the created ISO ---
 
     let ovmf_fd_drive = format!(
         "if=pflash,format=raw,unit=0,readonly=on,file={}",
@@ -267,52 +428,42 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
         "-cpu".to_string(),
         "qemu64,+smap,+invtsc".to_string(),
         "-smp".to_string(),
-        "1".to_string(),
+        args.smp.max(1).to_string(),
         "-M".to_string(),
         "q35,usb=off,pcspk-audiodev=speaker".to_string(),
     ];
 
     // --- VGA device (dynamic) ---
-    match args.vga.as_str() {
-        "virtio-gpu" => {
-            qemu_args.push("-vga".to_string());
+    let resolution = args.resolution();
+    qemu_args.push("-vga".to_string());
+    match (args.vga, resolution) {
+        (Vga::VirtioGpu, Some(Resolution { width, height })) => {
             qemu_args.push("none".to_string());
-            // Parse resolution
-            let res_parts: Vec<&str> = args.resolution.split('x').collect();
-            let (w, h) = if res_parts.len() == 2 {
-                (res_parts[0], res_parts[1])
-            } else {
-                ("1024", "768")
-            };
             qemu_args.push("-device".to_string());
             qemu_args.push(format!(
                 "virtio-gpu-pci,disable-legacy=on,disable-modern=off,xres={},yres={}",
-                w, h
+                width, height
             ));
         }
-        "std" => {
-            qemu_args.push("-vga".to_string());
-            qemu_args.push("std".to_string());
-        }
-        "qxl" => {
-            qemu_args.push("-vga".to_string());
-            qemu_args.push("qxl".to_string());
-        }
-        "cirrus" => {
-            qemu_args.push("-vga".to_string());
-            qemu_args.push("cirrus".to_string());
+        (Vga::Std, Some(Resolution { width, height })) => {
+            qemu_args.push("none".to_string());
+            qemu_args.push("-device".to_string());
+            qemu_args.push(format!("VGA,edid=on,xres={},yres={}", width, height));
         }
-        "none" => {
-            qemu_args.push("-vga".to_string());
+        (Vga::Qxl, Some(Resolution { width, height })) => {
             qemu_args.push("none".to_string());
+            qemu_args.push("-device".to_string());
+            qemu_args.push(format!("qxl-vga,xres={},yres={}", width, height));
         }
-        other => {
-            log::warn!("Unknown VGA type '{}', falling back to virtio-gpu", other);
-            qemu_args.push("-vga".to_string());
+        (Vga::Std, None) => qemu_args.push("std".to_string()),
+        (Vga::Qxl, None) => qemu_args.push("qxl".to_string()),
+        (Vga::Cirrus, _) => qemu_args.push("cirrus".to_string()),
+        (Vga::Ramfb, _) => {
             qemu_args.push("none".to_string());
             qemu_args.push("-device".to_string());
-            qemu_args.push("virtio-gpu-pci,disable-legacy=on,disable-modern=off".to_string());
+            qemu_args.push("ramfb".to_string());
         }
+        (Vga::None, _) | (Vga::VirtioGpu, None) => qemu_args.push("none".to_string()),
     }
 
     // --- Display backend (dynamic) ---
@@ -322,7 +473,11 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
     let display = args
         .display
         .as_deref()
-        .unwrap_or(if args.headless { "none" } else { "sdl" });
+        .unwrap_or(if args.headless || args.test {
+            "none"
+        } else {
+            "sdl"
+        });
     qemu_args.push("-display".to_string());
     match display {
         "gtk" => {
@@ -360,6 +515,8 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
         iso_path_str
     ));
 
+    // Test runs usually have no sound server to connect to.
+    let audio_driver = if args.test { "none" } else { "pa" };
     qemu_args.extend([
         "-no-reboot".to_string(),
         "-no-shutdown".to_string(),
@@ -371,10 +528,13 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
         "menu=on,order=d".to_string(),
         // ── PC Speaker audio (audiodev for PulseAudio) ───
         "-audiodev".to_string(),
-        "pa,id=speaker,out.mixing-engine=off".to_string(),
+        format!("{},id=speaker,out.mixing-engine=off", audio_driver),
         // ── HD Audio device (Intel HDA) ───
         "-audiodev".to_string(),
-        "pa,id=hda,timer-period=1000,out.mixing-engine=off".to_string(),
+        format!(
+            "{},id=hda,timer-period=1000,out.mixing-engine=off",
+            audio_driver
+        ),
         "-device".to_string(),
         "intel-hda,debug=0".to_string(),
         "-device".to_string(),
@@ -383,8 +543,6 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
 
     qemu_cmd.args(&qemu_args);
 
-    // Keep the temporary file alive until QEMU exits
-    let _temp_ovmf_vars_fd_holder = temp_ovmf_vars_fd;
     // LD_PRELOAD is a workaround for specific QEMU/libpthread versions.
     // It can be overridden by setting the FULLERENE_QEMU_LD_PRELOAD environment variable.
     let ld_preload_path = env::var("FULLERENE_QEMU_LD_PRELOAD").unwrap_or_else(|_| {
@@ -392,43 +550,85 @@ fn run_qemu(workspace_root: &PathBuf, args: &Args) -> io::Result<()> {
     });
     qemu_cmd.env("LD_PRELOAD", ld_preload_path);
 
-    let mut child = qemu_cmd.spawn()?;
+    // The curses display draws on the terminal itself, so its output
+    // cannot go through a pipe.
+    let serial_log = if display == "curses" {
+        None
+    } else {
+        qemu_cmd.stdout(Stdio::piped());
+        Some(File::create(SERIAL_LOG_PATH)?)
+    };
 
-    if let Some(timeout_secs) = args.timeout {
-        let timeout_duration = std::time::Duration::from_secs(timeout_secs);
-        let timeout_handle = std::thread::spawn(move || {
-            std::thread::sleep(timeout_duration);
-        });
+    // Declared after the OVMF vars file so that QEMU is killed before the
+    // file is deleted, however this function returns.
+    let mut qemu = QemuProcess(qemu_cmd.spawn()?);
+    let serial_tee = match (qemu.0.stdout.take(), serial_log) {
+        (Some(serial), Some(log)) => Some(
+            std::thread::Builder::new()
+                .name("serial-tee".to_string())
+                .spawn(move || flasks::tee(serial, io::stdout(), log))?,
+        ),
+        _ => None,
+    };
+
+    let timeout = args
+        .timeout
+        .or(args.test.then_some(DEFAULT_TEST_TIMEOUT_SECS))
+        .map(Duration::from_secs);
+    let outcome = qemu.wait(timeout)?;
+
+    // QEMU's end of the pipe is closed now, so the tee has reached the end.
+    if let Some(serial_tee) = serial_tee {
+        match serial_tee.join() {
+            Ok(Ok(())) => log::info!("Serial output saved to {}", SERIAL_LOG_PATH),
+            Ok(Err(error)) => log::warn!("Failed to write {}: {}", SERIAL_LOG_PATH, error),
+            Err(_) => log::warn!("Serial tee thread panicked"),
+        }
+    }
+    drop(temp_ovmf_vars_fd);
+    Ok(outcome)
+}
 
-        // We need to wait for either the child to exit or the timeout thread to finish
-        // Since we can't easily "select" on a process, we'll poll the child
+/// A running QEMU, killed if it is still running when dropped so that an
+/// early return or a panic in flasks does not leave it behind.
+struct QemuProcess(Child);
+
+impl QemuProcess {
+    /// Wait for QEMU to exit, killing it once `timeout` has passed.
+    fn wait(&mut self, timeout: Option<Duration>) -> io::Result<QemuOutcome> {
+        let Some(timeout) = timeout else {
+            return self.0.wait().map(QemuOutcome::from_status);
+        };
+        let deadline = Instant::now() + timeout;
+        // There is no portable way to wait on a process with a timeout, so
+        // poll it.
         loop {
-            match child.try_wait()? {
-                Some(status) => {
-                    if !status.success() {
-                        return Err(io::Error::other("QEMU execution failed"));
-                    }
-                    return Ok(());
-                }
-                None => {
-                    if timeout_handle.is_finished() {
-                        log::warn!(
-                            "QEMU timed out after {} seconds. Killing process...",
-                            timeout_secs
-                        );
-                        child.kill()?;
-                        return Err(io::Error::other("QEMU execution timed out"));
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                }
+            if let Some(status) = self.0.try_wait()? {
+                return Ok(QemuOutcome::from_status(status));
             }
+            if Instant::now() >= deadline {
+                log::warn!(
+                    "QEMU timed out after {} seconds. Killing process...",
+                    timeout.as_secs()
+                );
+                self.kill();
+                return Ok(QemuOutcome::TimedOut);
+            }
+            std::thread::sleep(Duration::from_millis(100));
         }
-    } else {
-        let qemu_status = child.wait()?;
-        if !qemu_status.success() {
-            return Err(io::Error::other("QEMU execution failed"));
+    }
+
+    /// Kill QEMU if it is still running and reap it.
+    fn kill(&mut self) {
+        if let Ok(None) = self.0.try_wait() {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
         }
     }
+}
 
-    Ok(())
+impl Drop for QemuProcess {
+    fn drop(&mut self) {
+        self.kill();
+    }
 }
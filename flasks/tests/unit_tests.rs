@@ -43,4 +43,196 @@ mod tests {
         }
         // If it returns None, that's a valid outcome on systems where the lib isn't in a standard path.
     }
+
+    #[test]
+    fn test_qemu_exit_status_decoding() {
+        use flasks::QemuOutcome;
+        // The kernel writes 0x10 + code to isa-debug-exit.
+        assert_eq!(QemuOutcome::from_code(33), QemuOutcome::GuestExit(0));
+        assert_eq!(QemuOutcome::from_code(35), QemuOutcome::GuestExit(1));
+        assert_eq!(QemuOutcome::from_code(255), QemuOutcome::GuestExit(111));
+        // QEMU's own statuses, including a debug-exit write of 0.
+        assert_eq!(QemuOutcome::from_code(0), QemuOutcome::QemuExit(0));
+        assert_eq!(QemuOutcome::from_code(1), QemuOutcome::QemuExit(1));
+        assert_eq!(QemuOutcome::from_code(34), QemuOutcome::QemuExit(34));
+
+        assert!(QemuOutcome::GuestExit(0).is_clean());
+        assert!(!QemuOutcome::GuestExit(3).is_clean());
+        assert_eq!(QemuOutcome::GuestExit(3).test_exit_code(), 3);
+        assert_eq!(
+            QemuOutcome::TimedOut.test_exit_code(),
+            flasks::TIMED_OUT_EXIT_CODE
+        );
+        assert_eq!(
+            QemuOutcome::QemuExit(0).test_exit_code(),
+            flasks::NO_GUEST_EXIT_CODE
+        );
+    }
+
+    #[test]
+    fn test_tee_keeps_logging_after_the_terminal_fails() {
+        struct ClosedAfter(usize);
+        impl std::io::Write for ClosedAfter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(std::io::ErrorKind::BrokenPipe.into());
+                }
+                self.0 -= 1;
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let serial: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let mut terminal = Vec::new();
+        let mut log = Vec::new();
+        flasks::tee(serial.as_slice(), &mut terminal, &mut log).unwrap();
+        assert_eq!(terminal, serial);
+        assert_eq!(log, serial);
+
+        let mut log = Vec::new();
+        flasks::tee(serial.as_slice(), ClosedAfter(1), &mut log).unwrap();
+        assert_eq!(log, serial);
+    }
+
+    #[test]
+    fn test_resolution_parsing() {
+        use flasks::Resolution;
+        let resolution: Resolution = "1280x720".parse().unwrap();
+        assert_eq!(
+            resolution,
+            Resolution {
+                width: 1280,
+                height: 720
+            }
+        );
+        assert_eq!(resolution.to_string(), "1280x720");
+        for invalid in ["1280", "1280x", "x720", "0x720", "1280x-1", "1280*720"] {
+            assert!(invalid.parse::<Resolution>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cpio_archive_of_a_directory() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("etc")).unwrap();
+        std::fs::write(root.path().join("etc").join("motd"), b"welcome\n").unwrap();
+        std::fs::write(root.path().join("README"), b"hi").unwrap();
+
+        let mut archive = Vec::new();
+        flasks::write_cpio(root.path(), &mut archive).unwrap();
+        assert_eq!(archive.len() % 4, 0);
+
+        // Walk the headers: name size at 94, file size at 54, both hex.
+        let field = |at: usize| {
+            usize::from_str_radix(std::str::from_utf8(&archive[at..at + 8]).unwrap(), 16).unwrap()
+        };
+        let mut names = Vec::new();
+        let mut offset = 0;
+        loop {
+            assert_eq!(&archive[offset..offset + 6], b"070701");
+            let namesize = field(offset + 94);
+            let filesize = field(offset + 54);
+            let name =
+                std::str::from_utf8(&archive[offset + 110..offset + 110 + namesize - 1]).unwrap();
+            let body = (offset + 110 + namesize).next_multiple_of(4);
+            names.push((name.to_string(), archive[body..body + filesize].to_vec()));
+            offset = (body + filesize).next_multiple_of(4);
+            if name == "TRAILER!!!" {
+                break;
+            }
+        }
+        assert_eq!(offset, archive.len());
+        assert_eq!(
+            names,
+            [
+                ("README".to_string(), b"hi".to_vec()),
+                ("etc".to_string(), Vec::new()),
+                ("etc/motd".to_string(), b"welcome\n".to_vec()),
+                ("TRAILER!!!".to_string(), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linker_map_symbols() {
+        let map = "\
+ fullerene-kernel
+
+ Preferred load address is 0000000140000000
+
+ Start         Length     Name                   Class
+ 0001:00000000 00000100H .text                   CODE
+ 0002:00000000 00000010H .rdata                  DATA
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+
+ 0000:00000000       __ImageBase                0000000140000000     <linker-defined>
+ 0001:00000000       efi_main                   0000000140001000     main.o
+ 0001:00000040       _RNvNtCs1234_9petroleum6serial5print 0000000140001040 f   petroleum.o
+ 0002:00000000       _RNvCs1234_9petroleum5TABLE 0000000140002000     petroleum.o
+
+ entry point at         0001:00000000
+
+ Static symbols
+
+ 0001:00000040       alias_of_print             0000000140001040 f   petroleum.o
+ 0001:000000f0       _ZN4core9panicking5panic17h0123456789abcdefE 00000001400010f0 f   core.o
+";
+        let symbols = flasks::parse_linker_map(map);
+        let symbol = |start, len, name: &str| flasks::Symbol {
+            start,
+            len,
+            name: name.to_string(),
+        };
+        assert_eq!(
+            symbols,
+            [
+                symbol(0x1000, 0x40, "efi_main"),
+                symbol(0x1040, 0xB0, "petroleum::serial::print"),
+                symbol(0x10F0, 0x10, "core::panicking::panic"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_symbol_table_patching() {
+        // A PE image whose second section is .ksyms: 64 bytes at RVA
+        // 0x3000, file offset 0x200.
+        let mut image = vec![0u8; 0x240];
+        image[0x3C] = 0x40;
+        image[0x40..0x44].copy_from_slice(b"PE\0\0");
+        image[0x46] = 2;
+        let ksyms = 0x40 + 24 + 40;
+        image[ksyms..ksyms + 6].copy_from_slice(b".ksyms");
+        for (at, value) in [(8, 0x40u32), (12, 0x3000), (16, 0x40), (20, 0x200)] {
+            image[ksyms + at..ksyms + at + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        image[0x200..0x204].copy_from_slice(b"KSYM");
+
+        let symbols = [flasks::Symbol {
+            start: 0x1000,
+            len: 0x40,
+            name: "main".to_string(),
+        }];
+        flasks::write_symbol_table(&mut image, &symbols).unwrap();
+        let table = &image[0x200..0x224];
+        assert_eq!(&table[..4], b"KSYM");
+        assert_eq!(table[4..8], 1u32.to_le_bytes());
+        assert_eq!(table[8..12], 0x3000u32.to_le_bytes());
+        assert_eq!(table[16..20], 0x1000u32.to_le_bytes());
+        assert_eq!(table[20..24], 0x40u32.to_le_bytes());
+        assert_eq!(table[24..28], 0u32.to_le_bytes());
+        assert_eq!(table[28..32], 4u32.to_le_bytes());
+        assert_eq!(&table[32..36], b"main");
+
+        // Too many symbols for the section.
+        let many = vec![symbols[0].clone(); 4];
+        assert!(flasks::write_symbol_table(&mut image, &many).is_err());
+        // An image without the section.
+        image[ksyms..ksyms + 6].copy_from_slice(b".data\0");
+        assert!(flasks::write_symbol_table(&mut image, &symbols).is_err());
+    }
 }
@@ -23,22 +23,45 @@ pub enum SyscallNumber {
     Open = 5,
     Close = 6,
     Wait = 7,
+    WaitPid = 8,
     GetPid = 20,
     GetProcessName = 21,
     Yield = 22,
     Spawn = 23,
+    SetPriority = 24,
+    Exec = 25,
+    ListProcesses = 26,
+    SetName = 27,
+    Kill = 28,
+    TakeSignals = 29,
     MapMemory = 30,
     UnmapMemory = 31,
     ProtectMemory = 32,
     QueryMemory = 33,
+    Brk = 34,
+    Mmap = 35,
+    Munmap = 36,
+    ShmCreate = 37,
+    ShmAttach = 38,
+    ShmDetach = 39,
     CreateEvent = 40,
     WaitEvent = 41,
     SignalEvent = 42,
     SubscribeEvent = 43,
+    FutexWait = 44,
+    FutexWake = 45,
+    MemInfo = 46,
+    ReadKlog = 47,
+    Dup = 48,
+    Dup2 = 49,
     CreateThread = 50,
     JoinThread = 51,
     DetachThread = 52,
     ExitThread = 53,
+    SemCreate = 54,
+    SemWait = 55,
+    SemPost = 56,
+    SemDestroy = 57,
     CreateWindow = 60,
     DestroyWindow = 61,
     ResizeWindow = 62,
@@ -58,20 +81,26 @@ pub enum SyscallNumber {
     TimerCreate = 101,
     Sleep = 102,
     Uptime = 103,
+    SleepTicks = 104,
+    GetTimeOfDay = 105,
+    ClockMonotonic = 106,
+    Shutdown = 110,
+    Reboot = 111,
 }
 
 impl SyscallNumber {
     all_syscall! {
-        AbiQuery, Exit, Fork, Read, Write, Open, Close, Wait,
-        GetPid, GetProcessName, Yield, Spawn,
-        MapMemory, UnmapMemory, ProtectMemory, QueryMemory,
-        CreateEvent, WaitEvent, SignalEvent, SubscribeEvent,
-        CreateThread, JoinThread, DetachThread, ExitThread,
+        AbiQuery, Exit, Fork, Read, Write, Open, Close, Wait, WaitPid,
+        GetPid, GetProcessName, Yield, Spawn, SetPriority, Exec, ListProcesses, SetName, Kill, TakeSignals,
+        MapMemory, UnmapMemory, ProtectMemory, QueryMemory, Brk, Mmap, Munmap, ShmCreate, ShmAttach, ShmDetach,
+        CreateEvent, WaitEvent, SignalEvent, SubscribeEvent, FutexWait, FutexWake, MemInfo, ReadKlog, Dup, Dup2,
+        CreateThread, JoinThread, DetachThread, ExitThread, SemCreate, SemWait, SemPost, SemDestroy,
         CreateWindow, DestroyWindow, ResizeWindow, PresentWindow, GetWindowEvent,
         EnumerateDevices, OpenDevice, DeviceIoctl,
         ChannelCreate, ChannelSend, ChannelRecv, PipeCreate,
         HandleTransfer, HandleDuplicate, HandleRevoke,
-        ClockGetTime, TimerCreate, Sleep, Uptime,
+        ClockGetTime, TimerCreate, Sleep, Uptime, SleepTicks, GetTimeOfDay, ClockMonotonic,
+        Shutdown, Reboot,
     }
 
     #[inline]
@@ -86,17 +115,23 @@ impl TryFrom<u64> for SyscallNumber {
         macro_rules! match_num { ($($n:ident => $v:ident),* $(,)?) => { match value { $(syscall_numbers::$n => Ok(Self::$v),)* _ => Err(()) } }; }
         match_num! {
             ABI_QUERY => AbiQuery, EXIT => Exit, FORK => Fork, READ => Read, WRITE => Write,
-            OPEN => Open, CLOSE => Close, WAIT => Wait, GETPID => GetPid, GET_PROCESS_NAME => GetProcessName,
-            YIELD => Yield, SPAWN => Spawn, MAP_MEMORY => MapMemory, UNMAP_MEMORY => UnmapMemory,
-            PROTECT_MEMORY => ProtectMemory, QUERY_MEMORY => QueryMemory,
+            OPEN => Open, CLOSE => Close, WAIT => Wait, WAITPID => WaitPid, GETPID => GetPid, GET_PROCESS_NAME => GetProcessName,
+            YIELD => Yield, SPAWN => Spawn, SET_PRIORITY => SetPriority, EXEC => Exec, LIST_PROCESSES => ListProcesses, SET_NAME => SetName, KILL => Kill, TAKE_SIGNALS => TakeSignals, MAP_MEMORY => MapMemory, UNMAP_MEMORY => UnmapMemory,
+            PROTECT_MEMORY => ProtectMemory, QUERY_MEMORY => QueryMemory, BRK => Brk,
+            MMAP => Mmap, MUNMAP => Munmap, SHM_CREATE => ShmCreate, SHM_ATTACH => ShmAttach, SHM_DETACH => ShmDetach,
             CREATE_EVENT => CreateEvent, WAIT_EVENT => WaitEvent, SIGNAL_EVENT => SignalEvent, SUBSCRIBE_EVENT => SubscribeEvent,
+            FUTEX_WAIT => FutexWait, FUTEX_WAKE => FutexWake, MEM_INFO => MemInfo, READ_KLOG => ReadKlog,
+            DUP => Dup, DUP2 => Dup2,
             CREATE_THREAD => CreateThread, JOIN_THREAD => JoinThread, DETACH_THREAD => DetachThread, EXIT_THREAD => ExitThread,
+            SEM_CREATE => SemCreate, SEM_WAIT => SemWait, SEM_POST => SemPost, SEM_DESTROY => SemDestroy,
             CREATE_WINDOW => CreateWindow, DESTROY_WINDOW => DestroyWindow, RESIZE_WINDOW => ResizeWindow,
             PRESENT_WINDOW => PresentWindow, GET_WINDOW_EVENT => GetWindowEvent,
             ENUMERATE_DEVICES => EnumerateDevices, OPEN_DEVICE => OpenDevice, DEVICE_IOCTL => DeviceIoctl,
             CHANNEL_CREATE => ChannelCreate, CHANNEL_SEND => ChannelSend, CHANNEL_RECV => ChannelRecv, PIPE_CREATE => PipeCreate,
             HANDLE_TRANSFER => HandleTransfer, HANDLE_DUPLICATE => HandleDuplicate, HANDLE_REVOKE => HandleRevoke,
             CLOCK_GETTIME => ClockGetTime, TIMER_CREATE => TimerCreate, SLEEP => Sleep, UPTIME => Uptime,
+            SLEEP_TICKS => SleepTicks, GET_TIME_OF_DAY => GetTimeOfDay, CLOCK_MONOTONIC => ClockMonotonic,
+            SHUTDOWN => Shutdown, REBOOT => Reboot,
         }
     }
 }
@@ -107,16 +142,25 @@ pub mod syscall_numbers {
     sc! {
         ABI_QUERY = AbiQuery, ABI_VERSION = AbiQuery,
         EXIT = Exit, FORK = Fork, READ = Read, WRITE = Write, OPEN = Open, CLOSE = Close, WAIT = Wait,
+        WAITPID = WaitPid,
         GETPID = GetPid, GET_PROCESS_NAME = GetProcessName, YIELD = Yield, SPAWN = Spawn,
+        SET_PRIORITY = SetPriority, EXEC = Exec, LIST_PROCESSES = ListProcesses,
+        SET_NAME = SetName, KILL = Kill, TAKE_SIGNALS = TakeSignals,
         MAP_MEMORY = MapMemory, UNMAP_MEMORY = UnmapMemory, PROTECT_MEMORY = ProtectMemory, QUERY_MEMORY = QueryMemory,
+        BRK = Brk, MMAP = Mmap, MUNMAP = Munmap, SHM_CREATE = ShmCreate, SHM_ATTACH = ShmAttach,
+        SHM_DETACH = ShmDetach,
         CREATE_EVENT = CreateEvent, WAIT_EVENT = WaitEvent, SIGNAL_EVENT = SignalEvent, SUBSCRIBE_EVENT = SubscribeEvent,
+        FUTEX_WAIT = FutexWait, FUTEX_WAKE = FutexWake, MEM_INFO = MemInfo, READ_KLOG = ReadKlog, DUP = Dup, DUP2 = Dup2,
         CREATE_THREAD = CreateThread, JOIN_THREAD = JoinThread, DETACH_THREAD = DetachThread, EXIT_THREAD = ExitThread,
+        SEM_CREATE = SemCreate, SEM_WAIT = SemWait, SEM_POST = SemPost, SEM_DESTROY = SemDestroy,
         CREATE_WINDOW = CreateWindow, DESTROY_WINDOW = DestroyWindow, RESIZE_WINDOW = ResizeWindow,
         PRESENT_WINDOW = PresentWindow, GET_WINDOW_EVENT = GetWindowEvent,
         ENUMERATE_DEVICES = EnumerateDevices, OPEN_DEVICE = OpenDevice, DEVICE_IOCTL = DeviceIoctl,
         CHANNEL_CREATE = ChannelCreate, CHANNEL_SEND = ChannelSend, CHANNEL_RECV = ChannelRecv, PIPE_CREATE = PipeCreate,
         HANDLE_TRANSFER = HandleTransfer, HANDLE_DUPLICATE = HandleDuplicate, HANDLE_REVOKE = HandleRevoke,
         CLOCK_GETTIME = ClockGetTime, TIMER_CREATE = TimerCreate, SLEEP = Sleep, UPTIME = Uptime,
+        SLEEP_TICKS = SleepTicks, GET_TIME_OF_DAY = GetTimeOfDay, CLOCK_MONOTONIC = ClockMonotonic,
+        SHUTDOWN = Shutdown, REBOOT = Reboot,
     }
 }
 
@@ -141,6 +185,7 @@ pub enum SyscallErrorCode {
     IsADirectory = 21,
     InvalidArgument = 22,
     NoSpace = 28,
+    BrokenPipe = 32,
     DirectoryNotEmpty = 39,
     Overflow = 75,
     NotSupported = 95,
@@ -153,7 +198,7 @@ impl SyscallErrorCode {
     all_error! {
         InvalidSyscall, FileNotFound, NoSuchProcess, Io, BadFileDescriptor, Again, OutOfMemory,
         PermissionDenied, AddressFault, Busy, AlreadyExists, NoSuchDevice,
-        NotADirectory, IsADirectory, InvalidArgument, NoSpace, DirectoryNotEmpty,
+        NotADirectory, IsADirectory, InvalidArgument, NoSpace, BrokenPipe, DirectoryNotEmpty,
         Overflow, NotSupported, BadHandle, TimedOut, WouldBlock,
     }
 
@@ -171,7 +216,7 @@ impl TryFrom<i64> for SyscallErrorCode {
             1 => InvalidSyscall, 2 => FileNotFound, 3 => NoSuchProcess, 5 => Io, 9 => BadFileDescriptor,
             11 => Again, 12 => OutOfMemory, 13 => PermissionDenied, 14 => AddressFault, 16 => Busy,
             17 => AlreadyExists, 19 => NoSuchDevice, 20 => NotADirectory, 21 => IsADirectory, 22 => InvalidArgument,
-            28 => NoSpace, 39 => DirectoryNotEmpty, 75 => Overflow, 95 => NotSupported, 104 => BadHandle,
+            28 => NoSpace, 32 => BrokenPipe, 39 => DirectoryNotEmpty, 75 => Overflow, 95 => NotSupported, 104 => BadHandle,
             110 => TimedOut, 140 => WouldBlock,
         }
     }
@@ -185,11 +230,25 @@ pub mod syscall_errors {
         IO_ERROR = Io, BAD_FILE_DESCRIPTOR = BadFileDescriptor, AGAIN = Again, OUT_OF_MEMORY = OutOfMemory,
         PERMISSION_DENIED = PermissionDenied, ADDRESS_FAULT = AddressFault, BUSY = Busy, ALREADY_EXISTS = AlreadyExists,
         NO_SUCH_DEVICE = NoSuchDevice, NOT_A_DIRECTORY = NotADirectory, IS_A_DIRECTORY = IsADirectory,
-        INVALID_ARGUMENT = InvalidArgument, NO_SPACE = NoSpace, DIRECTORY_NOT_EMPTY = DirectoryNotEmpty,
+        INVALID_ARGUMENT = InvalidArgument, NO_SPACE = NoSpace, BROKEN_PIPE = BrokenPipe,
+        DIRECTORY_NOT_EMPTY = DirectoryNotEmpty,
         OVERFLOW = Overflow, NOT_SUPPORTED = NotSupported, BAD_HANDLE = BadHandle, TIMED_OUT = TimedOut, WOULD_BLOCK = WouldBlock,
     }
 }
 
+/// Page protection bits taken by `Mmap` and `MapMemory`.
+pub mod memory_protection {
+    pub const READ: u64 = 1;
+    pub const WRITE: u64 = 2;
+    pub const EXEC: u64 = 4;
+}
+
+/// Flags taken by `Read` in its fourth argument.
+pub mod read_flags {
+    /// Fail with `WouldBlock` instead of waiting for console input.
+    pub const NONBLOCK: u64 = 1;
+}
+
 /// Semantic version of the native syscall ABI.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
@@ -381,6 +440,31 @@ impl MemoryInfo {
     }
 }
 
+/// System-wide physical memory totals returned by `mem_info`, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct MemInfo {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+    /// Largest physically contiguous free block; well below `free` when free
+    /// memory is fragmented.
+    pub largest_free_run: u64,
+}
+
+impl MemInfo {
+    pub const BYTE_SIZE: usize = 32;
+
+    pub fn to_ne_bytes(self) -> [u8; Self::BYTE_SIZE] {
+        let mut bytes = [0; Self::BYTE_SIZE];
+        bytes[0..8].copy_from_slice(&self.total.to_ne_bytes());
+        bytes[8..16].copy_from_slice(&self.used.to_ne_bytes());
+        bytes[16..24].copy_from_slice(&self.free.to_ne_bytes());
+        bytes[24..32].copy_from_slice(&self.largest_free_run.to_ne_bytes());
+        bytes
+    }
+}
+
 /// Time value returned by `clock_gettime`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
@@ -423,6 +507,64 @@ impl DeviceInfo {
     }
 }
 
+/// Signals accepted by `kill`; `take_signals` reports pending ones as
+/// `1 << signal` bits.
+pub mod signals {
+    /// Terminates the target at once; it cannot be caught.
+    pub const SIGKILL: u64 = 9;
+    /// Asks the target to exit; it stays pending until the target checks.
+    pub const SIGTERM: u64 = 15;
+    /// Exit code reported for a process terminated by `SIGKILL`.
+    pub const KILLED_EXIT_CODE: i32 = 128 + SIGKILL as i32;
+}
+
+/// Values of [`ProcessInfo::state`].
+pub mod process_states {
+    pub const READY: u32 = 0;
+    pub const RUNNING: u32 = 1;
+    pub const BLOCKED: u32 = 2;
+    pub const ZOMBIE: u32 = 3;
+    pub const TERMINATED: u32 = 4;
+}
+
+/// One process record returned by `list_processes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct ProcessInfo {
+    pub pid: u64,
+    /// One of the [`process_states`] values.
+    pub state: u32,
+    pub priority: u32,
+    /// CPU time used so far, in 1 ms ticks.
+    pub cpu_ticks: u64,
+    /// Process name, NUL-padded and truncated to fit.
+    pub name: [u8; 32],
+}
+
+impl ProcessInfo {
+    pub const BYTE_SIZE: usize = 56;
+
+    /// The name up to its first NUL.
+    pub fn name_bytes(&self) -> &[u8] {
+        let len = self
+            .name
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(self.name.len());
+        &self.name[..len]
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; Self::BYTE_SIZE] {
+        let mut bytes = [0; Self::BYTE_SIZE];
+        bytes[0..8].copy_from_slice(&self.pid.to_ne_bytes());
+        bytes[8..12].copy_from_slice(&self.state.to_ne_bytes());
+        bytes[12..16].copy_from_slice(&self.priority.to_ne_bytes());
+        bytes[16..24].copy_from_slice(&self.cpu_ticks.to_ne_bytes());
+        bytes[24..56].copy_from_slice(&self.name);
+        bytes
+    }
+}
+
 /// Fixed-size window event record returned by `get_window_event`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
@@ -467,6 +609,8 @@ const _: () = {
     assert!(core::mem::align_of::<TimeSpec>() == 8);
     assert!(core::mem::size_of::<DeviceInfo>() == DeviceInfo::BYTE_SIZE);
     assert!(core::mem::align_of::<DeviceInfo>() == 4);
+    assert!(core::mem::size_of::<ProcessInfo>() == ProcessInfo::BYTE_SIZE);
+    assert!(core::mem::align_of::<ProcessInfo>() == 8);
     assert!(core::mem::size_of::<WindowEvent>() == WindowEvent::BYTE_SIZE);
     assert!(WindowEvent::MIN_BYTE_SIZE <= WindowEvent::BYTE_SIZE);
     assert!(core::mem::align_of::<WindowEvent>() == 8);
@@ -523,4 +667,45 @@ mod tests {
         );
         assert!(info.capabilities.contains(Capability::NativeSyscall));
     }
+
+    #[test]
+    fn process_info_serialization_matches_repr_c_layout() {
+        let mut info = ProcessInfo {
+            pid: 7,
+            state: process_states::BLOCKED,
+            priority: 16,
+            cpu_ticks: 1234,
+            name: [0; 32],
+        };
+        info.name[..5].copy_from_slice(b"shell");
+        assert_eq!(info.name_bytes(), b"shell");
+        let bytes = info.to_ne_bytes();
+        assert_eq!(u64::from_ne_bytes(bytes[0..8].try_into().unwrap()), 7);
+        assert_eq!(
+            u32::from_ne_bytes(bytes[8..12].try_into().unwrap()),
+            process_states::BLOCKED
+        );
+        assert_eq!(u64::from_ne_bytes(bytes[16..24].try_into().unwrap()), 1234);
+        assert_eq!(&bytes[24..29], b"shell");
+    }
+
+    #[test]
+    fn mem_info_serialization_matches_repr_c_layout() {
+        assert_eq!(core::mem::size_of::<MemInfo>(), MemInfo::BYTE_SIZE);
+        let info = MemInfo {
+            total: 1 << 30,
+            used: 3 << 20,
+            free: (1 << 30) - (3 << 20),
+            largest_free_run: 1 << 29,
+        };
+        let bytes = info.to_ne_bytes();
+        assert_eq!(
+            u64::from_ne_bytes(bytes[8..16].try_into().unwrap()),
+            3 << 20
+        );
+        assert_eq!(
+            u64::from_ne_bytes(bytes[24..32].try_into().unwrap()),
+            1 << 29
+        );
+    }
 }
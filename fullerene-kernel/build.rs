@@ -12,6 +12,41 @@ fn main() {
     println!("cargo::rustc-check-cfg=cfg(have_ports_cpio)");
     println!("cargo:rerun-if-env-changed=FULLERENE_BUILD_PORTS");
 
+    // ── Linker map for the debug_symbols table ──────────────────
+    // flasks --debug-symbols reads the function symbols from the map and
+    // patches them into the image's .ksyms section.  It looks for the map
+    // next to the image, in the profile directory three levels above
+    // OUT_DIR.
+    if env::var_os("CARGO_FEATURE_DEBUG_SYMBOLS").is_some()
+        && env::var("TARGET").is_ok_and(|target| target.ends_with("-uefi"))
+    {
+        if let Some(profile_dir) = out_dir.ancestors().nth(3) {
+            let map = profile_dir.join("fullerene-kernel.map");
+            println!("cargo:rustc-link-arg-bins=/MAP:{}", map.display());
+        }
+    }
+
+    // ── Page-aligned sections ────────────────────────────────────
+    // The loader maps the image page by page with its sections'
+    // permissions.  A page shared by code and data could only be mapped
+    // read-only-executable or writable-and-executable, so every section
+    // starts on a page of its own.
+    if env::var("TARGET").is_ok_and(|target| target.ends_with("-uefi")) {
+        println!("cargo:rustc-link-arg-bins=/ALIGN:4096");
+    }
+
+    // ── Multiboot2 image layout ─────────────────────────────────
+    // A Multiboot2 loader copies the ELF segments to their physical
+    // addresses and only looks for the header in the first 32 KiB, so the
+    // ELF build links at a fixed low address with the header first.
+    if env::var_os("CARGO_FEATURE_MULTIBOOT2").is_some()
+        && env::var("TARGET").is_ok_and(|target| target.ends_with("-none"))
+    {
+        let script = manifest_dir.join("multiboot2.ld");
+        println!("cargo:rerun-if-changed={}", script.display());
+        println!("cargo:rustc-link-arg-bins=-T{}", script.display());
+    }
+
     // ── Propagate .driverignore cfg flags from Nitrogen ──────────
     let nitrogen_dir = manifest_dir.parent().unwrap().join("nitrogen");
     let ignore_path = nitrogen_dir.join(".driverignore");
@@ -1,6 +1,13 @@
 #[cfg(all(not(target_os = "uefi"), not(test)))]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn _start() -> ! {
+    kernel_main()
+}
+
+/// Boot of the ELF kernel, entered from `_start` or, with the `multiboot2`
+/// feature, once the Multiboot2 boot information has been published.
+#[cfg(all(not(target_os = "uefi"), not(test)))]
+pub(crate) fn kernel_main() -> ! {
     crate::init::init_common(x86_64::VirtAddr::new(0));
     log::info!("Entering _start (BIOS mode)...");
 
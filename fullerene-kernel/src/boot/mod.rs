@@ -1,4 +1,5 @@
 pub mod bios_entry;
+pub mod multiboot2;
 pub mod paging;
 pub mod uefi_entry;
 pub mod uefi_init;
@@ -9,3 +10,57 @@ use core::sync::atomic::AtomicU64;
 /// RSDP physical address discovered from the UEFI Configuration Table.
 /// 0 = not yet discovered or not available.
 pub static UEFI_RSDP_ADDRESS: AtomicU64 = AtomicU64::new(0);
+
+use petroleum::common::boot_params::{BOOT_CMDLINE_MAX, BootParams};
+
+/// Command line copied out of the bootloader's [`BootParams`] before the
+/// page holding them is reclaimed.
+static CMDLINE: spin::Once<([u8; BOOT_CMDLINE_MAX], usize)> = spin::Once::new();
+
+/// Record the command line from `params`.  Only the first call has an
+/// effect; parameters with a bad magic leave the command line empty.
+pub(crate) fn set_cmdline(params: &BootParams) {
+    if let Some(cmdline) = params.cmdline() {
+        CMDLINE.call_once(|| {
+            let mut bytes = [0u8; BOOT_CMDLINE_MAX];
+            bytes[..cmdline.len()].copy_from_slice(cmdline.as_bytes());
+            (bytes, cmdline.len())
+        });
+    }
+}
+
+/// The kernel command line passed by the bootloader, or `""` if there was
+/// none (BIOS boot or an older bellows).
+pub fn cmdline() -> &'static str {
+    CMDLINE
+        .get()
+        .and_then(|(bytes, len)| core::str::from_utf8(&bytes[..*len]).ok())
+        .unwrap_or("")
+}
+
+/// Value of `key=value` on the command line, `""` for a bare `key` flag.
+pub fn cmdline_param(key: &str) -> Option<&'static str> {
+    petroleum::common::boot_params::cmdline_param(cmdline(), key)
+}
+
+/// Physical address and length of the initrd bellows loaded.
+static INITRD: spin::Once<(u64, u64)> = spin::Once::new();
+
+/// Record the initrd range from `params`, if the bootloader loaded one.
+pub(crate) fn set_initrd(params: &BootParams) {
+    if let Some(range) = params.initrd() {
+        INITRD.call_once(|| range);
+    }
+}
+
+/// The initrd archive, read through the physical memory mapping, or `None`
+/// if the bootloader did not load one.
+///
+/// Its loader-data pages are never handed out by the kernel's frame
+/// allocator, which only takes conventional memory, so the slice stays
+/// valid for the life of the kernel.
+pub fn initrd() -> Option<&'static [u8]> {
+    let &(base, len) = INITRD.get()?;
+    let offset = petroleum::common::memory::get_physical_memory_offset() as u64;
+    Some(unsafe { core::slice::from_raw_parts((offset + base) as *const u8, len as usize) })
+}
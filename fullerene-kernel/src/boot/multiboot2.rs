@@ -0,0 +1,247 @@
+//! Multiboot2 boot path (`multiboot2` feature, ELF build only).
+//!
+//! With the feature, `cargo build --target x86_64-unknown-none` links the
+//! kernel by `multiboot2.ld`: [`MULTIBOOT2_HEADER`] first, so GRUB's
+//! `multiboot2` command recognises the image, and `multiboot2_start` as
+//! the ELF entry.  The loader enters it in 32-bit protected mode with
+//! paging off; the stub below identity maps the first 4 GiB with 2 MiB
+//! pages, switches to long mode and calls [`multiboot2_main`].
+//!
+//! [`publish`] then fills the same globals the UEFI entry fills from
+//! bellows' `KernelArgs`: the command line, the RSDP address, the boot
+//! framebuffer and `MEMORY_MAP`, with the kernel image and the boot
+//! information carved out of conventional memory.  From there the boot
+//! continues exactly as the ELF `_start` does, so nothing after it needs
+//! to know which loader ran.
+//!
+//! QEMU's `-kernel` only loads Multiboot 1 images; under QEMU, boot the
+//! ELF through GRUB.
+#![cfg(all(feature = "multiboot2", not(target_os = "uefi"), not(test)))]
+
+use core::sync::atomic::Ordering;
+use petroleum::common::EfiMemoryType;
+use petroleum::common::multiboot2::{BOOTLOADER_MAGIC, BootInformation, Header, split_around};
+use petroleum::page_table::MemoryMapDescriptor;
+use petroleum::page_table::memory_map::EfiMemoryDescriptor;
+
+/// Loaders only search the first 32 KiB of the image for the header;
+/// `multiboot2.ld` links its section first.
+#[used]
+#[unsafe(link_section = ".multiboot2")]
+pub static MULTIBOOT2_HEADER: Header = Header::new(0, 0, 32);
+
+/// Stack `multiboot2_main` runs on, in `.bss`.
+const BOOT_STACK_SIZE: usize = 64 * 1024;
+
+core::arch::global_asm!(
+    ".pushsection .text.multiboot2, \"ax\"",
+    ".global multiboot2_start",
+    ".code32",
+    "multiboot2_start:",
+    "cli",
+    "cld",
+    // EAX holds the loader magic and EBX the boot information; both stay
+    // in EDI and ESI, the first two arguments of multiboot2_main.
+    "mov edi, eax",
+    "mov esi, ebx",
+    "mov esp, offset multiboot2_stack_top",
+    // PML4[0] -> PDPT, PDPT[0..4] -> the four page directories.
+    "mov eax, offset multiboot2_pdpt",
+    "or eax, 0x3",
+    "mov dword ptr [multiboot2_pml4], eax",
+    "xor ecx, ecx",
+    "2:",
+    "mov eax, ecx",
+    "shl eax, 12",
+    "add eax, offset multiboot2_pd",
+    "or eax, 0x3",
+    "mov dword ptr [multiboot2_pdpt + ecx * 8], eax",
+    "inc ecx",
+    "cmp ecx, 4",
+    "jne 2b",
+    // 2048 present, writable 2 MiB pages: physical 0..4 GiB.
+    "xor ecx, ecx",
+    "3:",
+    "mov eax, ecx",
+    "shl eax, 21",
+    "or eax, 0x83",
+    "mov dword ptr [multiboot2_pd + ecx * 8], eax",
+    "inc ecx",
+    "cmp ecx, 2048",
+    "jne 3b",
+    // PAE, the page table, EFER.LME, then paging.
+    "mov eax, cr4",
+    "or eax, 0x20",
+    "mov cr4, eax",
+    "mov eax, offset multiboot2_pml4",
+    "mov cr3, eax",
+    "mov ecx, 0xC0000080",
+    "rdmsr",
+    "or eax, 0x100",
+    "wrmsr",
+    "mov eax, cr0",
+    "or eax, 0x80000001",
+    "mov cr0, eax",
+    "lgdt [multiboot2_gdtr]",
+    "jmp fword ptr [multiboot2_long_ptr]",
+    ".code64",
+    "multiboot2_long:",
+    "xor eax, eax",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    // The upper halves of RDI and RSI are undefined after the mode switch.
+    "mov edi, edi",
+    "mov esi, esi",
+    "mov rsp, offset multiboot2_stack_top",
+    "call {main}",
+    "4:",
+    "cli",
+    "hlt",
+    "jmp 4b",
+    ".balign 8",
+    "multiboot2_gdt:",
+    ".quad 0",
+    ".quad 0x00AF9A000000FFFF", // 0x08: 64-bit code
+    "multiboot2_gdtr:",
+    ".word 15",
+    ".long multiboot2_gdt",
+    "multiboot2_long_ptr:",
+    ".long multiboot2_long",
+    ".word 0x08",
+    ".popsection",
+    ".pushsection .bss.multiboot2, \"aw\", @nobits",
+    ".balign 4096",
+    "multiboot2_pml4:",
+    ".skip 4096",
+    "multiboot2_pdpt:",
+    ".skip 4096",
+    "multiboot2_pd:",
+    ".skip 4 * 4096",
+    "multiboot2_stack:",
+    ".skip {stack_size}",
+    "multiboot2_stack_top:",
+    ".popsection",
+    main = sym multiboot2_main,
+    stack_size = const BOOT_STACK_SIZE,
+);
+
+unsafe extern "C" {
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+}
+
+/// Most memory map entries kept: each Multiboot2 region can become up to
+/// five descriptors once the image and boot information are carved out.
+const MAX_REGIONS: usize = 256;
+
+const EMPTY_DESCRIPTOR: EfiMemoryDescriptor = EfiMemoryDescriptor {
+    type_: EfiMemoryType::EfiReservedMemoryType,
+    padding: 0,
+    physical_start: 0,
+    virtual_start: 0,
+    number_of_pages: 0,
+    attribute: 0,
+};
+
+/// The Multiboot2 memory map in UEFI layout; `MEMORY_MAP` points into it.
+static mut DESCRIPTORS: [EfiMemoryDescriptor; MAX_REGIONS] = [EMPTY_DESCRIPTOR; MAX_REGIONS];
+
+/// Why the boot information could not be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiboot2Error {
+    /// `eax` did not hold [`BOOTLOADER_MAGIC`]: not a Multiboot2 loader.
+    BadMagic,
+    /// The boot information structure is truncated.
+    BadInformation,
+    /// The loader passed no memory map, or one with more regions than
+    /// [`MAX_REGIONS`] descriptors hold.
+    BadMemoryMap,
+}
+
+/// Long-mode entry from `multiboot2_start`, on the boot stack with the
+/// first 4 GiB identity mapped.
+extern "C" fn multiboot2_main(magic: u32, info_phys: u64) -> ! {
+    if let Err(e) = unsafe { publish(magic, info_phys) } {
+        petroleum::serial::serial_log(format_args!("multiboot2: unusable boot: {:?}\n", e));
+        petroleum::halt_loop();
+    }
+    super::bios_entry::kernel_main()
+}
+
+/// Record the boot information at physical address `info_phys` in the
+/// kernel's boot globals.
+///
+/// # Safety
+///
+/// Must run once, on the boot CPU, before memory management starts and
+/// while `info_phys` is identity mapped.  The boot information has to stay
+/// in place until ACPI has been parsed, as the RSDP points into it; its
+/// pages are loader data, so the frame allocator leaves them alone.
+pub unsafe fn publish(magic: u32, info_phys: u64) -> Result<(), Multiboot2Error> {
+    if magic != BOOTLOADER_MAGIC {
+        return Err(Multiboot2Error::BadMagic);
+    }
+    let info = unsafe { BootInformation::from_ptr(info_phys as *const u8) }
+        .ok_or(Multiboot2Error::BadInformation)?;
+
+    let cmdline = info.cmdline().unwrap_or("");
+    crate::boot::set_cmdline(&petroleum::common::boot_params::BootParams::new(
+        cmdline, None,
+    ));
+
+    if let Some(offset) = info.rsdp_offset() {
+        crate::boot::UEFI_RSDP_ADDRESS.store(info_phys + offset as u64, Ordering::Relaxed);
+    }
+
+    if let Some(framebuffer) = info.framebuffer() {
+        // A second call finds the snapshot already taken; the first wins.
+        let _ = crate::graphics::discovery::store_boot_fb_params(
+            framebuffer.address,
+            framebuffer.width,
+            framebuffer.height,
+            framebuffer.stride,
+            framebuffer.bpp,
+            framebuffer.pixel_format as u32,
+        );
+    }
+
+    let image = (
+        &raw const __kernel_start as u64,
+        &raw const __kernel_end as u64,
+    );
+    let boot_info = (info_phys, info_phys + info.total_size() as u64);
+    let descriptors = unsafe { &mut *core::ptr::addr_of_mut!(DESCRIPTORS) };
+    let buffer = unsafe { &mut *core::ptr::addr_of_mut!(crate::heap::MEMORY_MAP_BUFFER) };
+    let capacity = MAX_REGIONS.min(buffer.len());
+    let mut count = 0;
+    for region in info
+        .memory_regions()
+        .filter_map(|region| region.to_efi_descriptor())
+    {
+        for piece in split_around(region, image.0, image.1).into_iter().flatten() {
+            for piece in split_around(piece, boot_info.0, boot_info.1)
+                .into_iter()
+                .flatten()
+            {
+                if count == capacity {
+                    return Err(Multiboot2Error::BadMemoryMap);
+                }
+                descriptors[count] = piece;
+                buffer[count] = MemoryMapDescriptor::new(
+                    (&raw const descriptors[count]).cast(),
+                    size_of::<EfiMemoryDescriptor>(),
+                );
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        return Err(Multiboot2Error::BadMemoryMap);
+    }
+    let buffer: &'static [MemoryMapDescriptor] = buffer;
+    *crate::heap::MEMORY_MAP.lock() = Some(&buffer[..count]);
+    Ok(())
+}
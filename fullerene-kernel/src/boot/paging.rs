@@ -120,7 +120,7 @@ pub fn bootstrap_memory(
                 kernel_phys_aligned,
                 kernel_virt_aligned,
                 256 * 1024,
-                PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
                 "kernel_area",
             )
             .expect("Failed to map kernel area");
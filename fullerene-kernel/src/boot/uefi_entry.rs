@@ -76,6 +76,16 @@ pub unsafe extern "sysv64" fn efi_main_real_logic(
         b"DEBUG: [uefi_entry] FB params stored in .data globals\n",
     );
 
+    // BootParams sit in the same page as KernelArgs, so they are reachable
+    // through whatever mapping args_ptr already uses.
+    if args.boot_params != 0 {
+        let params_addr = (args_ptr as u64 & !0xFFF) | (args.boot_params & 0xFFF);
+        let params =
+            unsafe { &*(params_addr as *const petroleum::common::boot_params::BootParams) };
+        crate::boot::set_cmdline(params);
+        crate::boot::set_initrd(params);
+    }
+
     let system_table_virt = (args.system_table as u64
         + petroleum::page_table::constants::HIGHER_HALF_OFFSET.as_u64())
         as *mut EfiSystemTable;
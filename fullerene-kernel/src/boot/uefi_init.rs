@@ -164,13 +164,10 @@ impl UefiInitContext {
     #[cfg(target_os = "uefi")]
     pub fn map_mmio() -> usize {
         let phys_offset = petroleum::common::memory::get_physical_memory_offset() as u64;
-        let lapic_virt = 0xfee00000u64 + phys_offset;
+        let lapic_virt = crate::interrupts::apic::local_apic_base_phys() + phys_offset;
         crate::interrupts::apic::preinit_apic_controller(lapic_virt);
 
-        if let Some(config) = petroleum::FULLERENE_FRAMEBUFFER_CONFIG
-            .get()
-            .and_then(|m| m.lock().clone())
-        {
+        if let Some(config) = petroleum::framebuffer::get_config() {
             petroleum::debug_log!(
                 "FB config: phys={:#x} {}x{}x{}\n",
                 config.address,
@@ -179,6 +179,45 @@ fn kernel_main_higher_half(
     crate::interrupts::apic::init_apic();
     log::info!("APIC initialized");
 
+    // Application processors are started and parked; they run no tasks.
+    crate::smp::start_application_processors();
+
+    // Every kernel mapping is in place; none may be writable and executable.
+    // `wx=warn` on the command line reports violations instead of halting.
+    // Without NX every writable page is executable, so W^X cannot hold.
+    if !petroleum::hardware::cpuid::features().nx {
+        log::warn!("CPU lacks NX; W^X is not enforced");
+    } else {
+        let wx_regions = crate::memory_management::audit_wx_mappings();
+        if crate::boot::cmdline_param("wx") == Some("warn") {
+            if wx_regions != 0 {
+                log::warn!("{} W^X violations in the kernel page table", wx_regions);
+            }
+        } else {
+            petroleum::kassert_eq!(wx_regions, 0, "W^X violations in the kernel page table");
+        }
+    }
+
+    // `flasks --test` also checks that SMAP really stops a stray kernel
+    // access to user memory, rather than trusting CR4.
+    if crate::boot::cmdline_param("test").is_some() && petroleum::common::memory::smap_enabled() {
+        match crate::memory_management::smap_faults_stray_user_reads() {
+            Ok(true) => log::info!("test: SMAP faults stray user reads"),
+            Ok(false) => panic!("SMAP let a stray kernel read of a user page through"),
+            Err(e) => panic!("SMAP self-test could not map its page: {:?}", e),
+        }
+    }
+
+    // It also overflows a kernel stack, which must end in the double-fault
+    // handler on its IST stack instead of a triple fault and a reset.
+    if crate::boot::cmdline_param("test").is_some() {
+        match crate::memory_management::stack_overflow_reaches_double_fault_handler() {
+            Ok(true) => log::info!("test: stack overflow reaches the double-fault handler"),
+            Ok(false) => panic!("a push into a stack guard page did not fault"),
+            Err(e) => panic!("double-fault self-test could not set up its stack: {:?}", e),
+        }
+    }
+
     // 2. Flush kernel log to VFS before entering scheduler
     log::info!("Flushing boot log...");
     debug_serial(b"Flushing boot log to VFS\n");
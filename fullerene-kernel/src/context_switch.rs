@@ -1,80 +1,143 @@
 //! Context switching implementation for Fullerene OS
 
+use crate::process::ProcessContext;
+use core::mem::offset_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+
+/// x87 control word after `FNINIT`: all exceptions masked, 64-bit precision.
+const DEFAULT_FCW: u16 = 0x037F;
+/// MXCSR at reset: all SSE exceptions masked, round to nearest.
+const DEFAULT_MXCSR: u32 = 0x1F80;
+
+/// Whether [`switch_context`] saves and restores the FPU/SSE state, which
+/// needs `FXSAVE`.  Cleared by [`init`] on CPUs without it.
+static FPU_SWITCH: AtomicBool = AtomicBool::new(true);
+
+/// x87/SSE register file in the 512-byte `FXSAVE` layout.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct FpuState([u8; 512]);
+
+impl FpuState {
+    pub fn fcw(&self) -> u16 {
+        u16::from_le_bytes([self.0[0], self.0[1]])
+    }
+
+    pub fn mxcsr(&self) -> u32 {
+        u32::from_le_bytes(self.0[24..28].try_into().unwrap())
+    }
+}
+
+impl Default for FpuState {
+    /// The state a new process starts with: every register zeroed and the
+    /// control words as `FNINIT` and reset leave them.
+    fn default() -> Self {
+        let mut area = [0; 512];
+        area[0..2].copy_from_slice(&DEFAULT_FCW.to_le_bytes());
+        area[24..28].copy_from_slice(&DEFAULT_MXCSR.to_le_bytes());
+        Self(area)
+    }
+}
+
+impl core::fmt::Debug for FpuState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FpuState")
+            .field("fcw", &format_args!("{:#06x}", self.fcw()))
+            .field("mxcsr", &format_args!("{:#010x}", self.mxcsr()))
+            .finish_non_exhaustive()
+    }
+}
+
 /// Save current process context and switch to next
+///
+/// The FPU/SSE registers are switched eagerly with `FXSAVE`/`FXRSTOR` on
+/// every call where the CPU has them (see [`init`]).  Lazy switching
+/// through CR0.TS would trap the first time the kernel itself touches an
+/// XMM register, which compiled Rust does freely.
 #[unsafe(naked)]
 pub extern "C" fn switch_context(
-    _old_context: Option<&mut crate::process::ProcessContext>,
-    _new_context: &crate::process::ProcessContext,
+    _old_context: Option<&mut ProcessContext>,
+    _new_context: &ProcessContext,
 ) {
     core::arch::naked_asm!(
         // Entry: rdi = old_context, rsi = new_context
         "test rdi, rdi",
         "jz 2f",
         // Save GPRs (regs[0..15])
-        "mov [rdi + 0], rax",
-        "mov [rdi + 8], rbx",
-        "mov [rdi + 16], rcx",
-        "mov [rdi + 24], rdx",
-        "mov [rdi + 32], rsi",
-        "mov [rdi + 40], rdi",
-        "mov [rdi + 48], rbp",
-        "mov [rdi + 56], rsp",
-        "mov [rdi + 64], r8",
-        "mov [rdi + 72], r9",
-        "mov [rdi + 80], r10",
-        "mov [rdi + 88], r11",
-        "mov [rdi + 96], r12",
-        "mov [rdi + 104], r13",
-        "mov [rdi + 112], r14",
-        "mov [rdi + 120], r15",
-        // Save RIP (at [rsp]), RFLAGS
+        "mov [rdi + {regs} + 0], rax",
+        "mov [rdi + {regs} + 8], rbx",
+        "mov [rdi + {regs} + 16], rcx",
+        "mov [rdi + {regs} + 24], rdx",
+        "mov [rdi + {regs} + 32], rsi",
+        "mov [rdi + {regs} + 40], rdi",
+        "mov [rdi + {regs} + 48], rbp",
+        "mov [rdi + {regs} + 64], r8",
+        "mov [rdi + {regs} + 72], r9",
+        "mov [rdi + {regs} + 80], r10",
+        "mov [rdi + {regs} + 88], r11",
+        "mov [rdi + {regs} + 96], r12",
+        "mov [rdi + {regs} + 104], r13",
+        "mov [rdi + {regs} + 112], r14",
+        "mov [rdi + {regs} + 120], r15",
+        // Resume at the return address with it popped, as `ret` would
         "mov rax, [rsp]",
-        "mov [rdi + 128], rax", // rip
+        "mov [rdi + {rip}], rax",
+        "lea rax, [rsp + 8]",
+        "mov [rdi + {regs} + 56], rax", // rsp
         "pushfq",
         "pop rax",
-        "mov [rdi + 136], rax", // rflags
+        "mov [rdi + {rflags}], rax",
         // Save Segments
-        "mov ax, cs; movzx rax, ax; mov [rdi + 144], rax",
-        "mov ax, ss; movzx rax, ax; mov [rdi + 152], rax",
-        "mov ax, ds; movzx rax, ax; mov [rdi + 160], rax",
-        "mov ax, es; movzx rax, ax; mov [rdi + 168], rax",
-        "mov ax, fs; movzx rax, ax; mov [rdi + 176], rax",
-        "mov ax, gs; movzx rax, ax; mov [rdi + 184], rax",
+        "mov ax, cs; movzx rax, ax; mov [rdi + {segments} + 0], rax",
+        "mov ax, ss; movzx rax, ax; mov [rdi + {segments} + 8], rax",
+        "mov ax, ds; movzx rax, ax; mov [rdi + {segments} + 16], rax",
+        "mov ax, es; movzx rax, ax; mov [rdi + {segments} + 24], rax",
+        "mov ax, fs; movzx rax, ax; mov [rdi + {segments} + 32], rax",
+        "mov ax, gs; movzx rax, ax; mov [rdi + {segments} + 40], rax",
+        // Save x87/SSE state
+        "cmp byte ptr [rip + {fpu_switch}], 0",
+        "je 2f",
+        "fxsave64 [rdi + {fpu}]",
         "2:",
         // Restore: rsi -> rbx (will use as base until last moment)
         "mov rbx, rsi",
+        "cmp byte ptr [rip + {fpu_switch}], 0",
+        "je 4f",
+        "fxrstor64 [rbx + {fpu}]",
+        "4:",
         // Push all values we need after GPR restore onto stack
         // This avoids callee-saved register aliasing with GPR restore
-        "mov rax, [rbx + 200]", // rax = is_user (push to stack)
+        "movzx rax, byte ptr [rbx + {is_user}]", // rax = is_user (push to stack)
         "push rax",
-        "mov rax, [rbx + 136]", // rax = rflags
+        "mov rax, [rbx + {rflags}]", // rax = rflags
         "push rax",
-        "mov rax, [rbx + 128]", // rax = rip
+        "mov rax, [rbx + {rip}]", // rax = rip
         "push rax",
-        "mov rax, [rbx + 152]", // rax = ss (for user mode)
+        "mov rax, [rbx + {segments} + 8]", // rax = ss (for user mode)
         "push rax",
-        "mov rax, [rbx + 144]", // rax = cs
+        "mov rax, [rbx + {segments} + 0]", // rax = cs
         "push rax",
-        "mov rax, [rbx + 56]", // rax = rsp (user or kernel)
+        "mov rax, [rbx + {regs} + 56]", // rax = rsp (user or kernel)
         "push rax",
         // Stack now: saved_rsp, saved_cs, saved_ss, saved_rip, saved_rflags, saved_is_user
         // Restore GPRs
-        "mov rax, [rbx + 0]",
-        "mov rcx, [rbx + 16]",
-        "mov rdx, [rbx + 24]",
-        "mov rsi, [rbx + 32]",
-        "mov rdi, [rbx + 40]",
-        "mov rbp, [rbx + 48]",
-        "mov r8,  [rbx + 64]",
-        "mov r9,  [rbx + 72]",
-        "mov r10, [rbx + 80]",
-        "mov r11, [rbx + 88]",
-        "mov r12, [rbx + 96]",
-        "mov r13, [rbx + 104]",
-        "mov r14, [rbx + 112]",
-        "mov r15, [rbx + 120]",
+        "mov rax, [rbx + {regs} + 0]",
+        "mov rcx, [rbx + {regs} + 16]",
+        "mov rdx, [rbx + {regs} + 24]",
+        "mov rsi, [rbx + {regs} + 32]",
+        "mov rdi, [rbx + {regs} + 40]",
+        "mov rbp, [rbx + {regs} + 48]",
+        "mov r8,  [rbx + {regs} + 64]",
+        "mov r9,  [rbx + {regs} + 72]",
+        "mov r10, [rbx + {regs} + 80]",
+        "mov r11, [rbx + {regs} + 88]",
+        "mov r12, [rbx + {regs} + 96]",
+        "mov r13, [rbx + {regs} + 104]",
+        "mov r14, [rbx + {regs} + 112]",
+        "mov r15, [rbx + {regs} + 120]",
         // Restore rbx last (dereference from rbx before clobbering)
-        "mov rbx, [rbx + 8]",
+        "mov rbx, [rbx + {regs} + 8]",
         // Pop saved context info (order reversed)
         "pop rax", // rax = saved_rsp (from rsp position)
         "pop rcx", // rcx = saved_cs
@@ -86,6 +149,12 @@ pub extern "C" fn switch_context(
         "test r10, r10",
         "jz 3f",
         // --- User mode switch via iretq ---
+        // A ring 3 target gets its own GS base back: swap the per-CPU block
+        // into KERNEL_GS_BASE, where the next kernel entry finds it.
+        "test cl, 3",
+        "jz 5f",
+        "swapgs",
+        "5:",
         // Stack: empty
         // Build iretq frame: ss, user_rsp, rflags, cs, rip
         "push rdx", // ss (from saved_ss)
@@ -103,21 +172,56 @@ pub extern "C" fn switch_context(
         "push r9",      // push rflags
         "popfq",        // restore rflags
         "jmp r8",       // jump to rip on the new stack
+        regs = const offset_of!(ProcessContext, regs),
+        rflags = const offset_of!(ProcessContext, rflags),
+        rip = const offset_of!(ProcessContext, rip),
+        segments = const offset_of!(ProcessContext, segments),
+        is_user = const offset_of!(ProcessContext, is_user),
+        fpu = const offset_of!(ProcessContext, fpu),
+        fpu_switch = sym FPU_SWITCH,
     );
 }
 
 /// Initialize context switching system
 ///
-/// This function sets up any global state needed for context switching.
-/// Currently empty but may be extended in the future.
+/// Enables the FPU and SSE for `FXSAVE`/`FXRSTOR`: CR0.EM and CR0.TS are
+/// cleared so x87 and SSE instructions execute instead of trapping, and
+/// CR4.OSFXSR/OSXMMEXCPT tell the CPU the kernel saves the SSE state and
+/// handles SIMD exceptions.  Application processors copy CR0 and CR4 from
+/// the bootstrap processor, so this must run before SMP bring-up.
+/// RSP0 is switched per process by the scheduler (`gdt::set_kernel_stack`).
+///
+/// A CPU without `FXSAVE` or SSE (which x86-64 guarantees, but a
+/// misconfigured hypervisor might hide) keeps running with the FPU state
+/// shared between processes instead of raising #UD on the first switch.
 pub fn init() {
-    // No global initialization needed for basic context switching
-    // Future: Set up Task State Segment, kernel stack pointers, etc.
+    let features = petroleum::hardware::cpuid::features();
+    if !features.fxsr || !features.sse {
+        log::warn!("CPU lacks FXSAVE or SSE; FPU state is not switched between processes");
+        FPU_SWITCH.store(false, Ordering::Relaxed);
+    }
+    unsafe {
+        Cr0::update(|cr0| {
+            cr0.remove(Cr0Flags::EMULATE_COPROCESSOR | Cr0Flags::TASK_SWITCHED);
+            cr0.insert(Cr0Flags::MONITOR_COPROCESSOR);
+        });
+        Cr4::update(|cr4| {
+            if features.fxsr {
+                cr4.insert(Cr4Flags::OSFXSR);
+            }
+            if features.sse {
+                cr4.insert(Cr4Flags::OSXMMEXCPT_ENABLE);
+            }
+        });
+        // Start from a clean x87 state rather than whatever firmware left.
+        core::arch::asm!("fninit", options(nomem, nostack));
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::process::ProcessContext;
+    use super::*;
+    use alloc::boxed::Box;
 
     #[test]
     fn test_context_default_values() {
@@ -130,4 +234,89 @@ mod tests {
         assert!(ctx.segments[0] > 0); // cs: Kernel code segment selector
         assert!(ctx.segments[1] > 0); // ss: Kernel data segment selector or user
     }
+
+    #[test]
+    fn fpu_area_is_aligned_for_fxsave() {
+        // FXSAVE faults on an area that is not 16-byte aligned.
+        assert_eq!(offset_of!(ProcessContext, fpu) % 16, 0);
+        assert_eq!(align_of::<ProcessContext>() % 16, 0);
+        assert_eq!(size_of::<FpuState>(), 512);
+
+        let ctx = ProcessContext::default();
+        assert_eq!(ctx.fpu.fcw(), DEFAULT_FCW);
+        assert_eq!(ctx.fpu.mxcsr(), DEFAULT_MXCSR);
+    }
+
+    /// Call `switch_context` with `value` in XMM0 and return what XMM0 holds
+    /// once something switches back.
+    fn switch_holding(value: f64, old: &mut ProcessContext, new: &ProcessContext) -> f64 {
+        let bits: u64;
+        unsafe {
+            core::arch::asm!(
+                "movq xmm0, {value}",
+                "call {switch}",
+                "movq rax, xmm0",
+                value = in(reg) value.to_bits(),
+                switch = sym switch_context,
+                out("rax") bits,
+                in("rdi") old,
+                in("rsi") new,
+                clobber_abi("C"),
+            );
+        }
+        f64::from_bits(bits)
+    }
+
+    fn mxcsr() -> u32 {
+        let mut value = 0u32;
+        unsafe { core::arch::asm!("stmxcsr [{}]", in(reg) &mut value) };
+        value
+    }
+
+    /// MXCSR rounding-control field and its round-toward-zero setting.
+    const ROUNDING: u32 = 0x6000;
+    const ROUND_TOWARD_ZERO: u32 = 0x6000;
+
+    static mut MAIN: *mut ProcessContext = core::ptr::null_mut();
+    static mut WORKER: *mut ProcessContext = core::ptr::null_mut();
+
+    extern "C" fn worker() {
+        // Round toward zero here only; the test's context keeps nearest.
+        let control = DEFAULT_MXCSR | ROUND_TOWARD_ZERO;
+        unsafe { core::arch::asm!("ldmxcsr [{}]", in(reg) &control) };
+        let mut value = 0.25;
+        loop {
+            let back = unsafe { switch_holding(value, &mut *WORKER, &*MAIN) };
+            assert_eq!(back, value);
+            assert_eq!(mxcsr() & ROUNDING, ROUND_TOWARD_ZERO);
+            value = value * 3.0 + 0.5;
+        }
+    }
+
+    /// Two contexts on the test thread's and a leaked stack take turns, each
+    /// keeping its own XMM0 and rounding mode across the switches.
+    #[test]
+    fn fpu_state_survives_switches_between_two_contexts() {
+        let stack = alloc::vec![0u128; 4096].leak();
+        let top = stack.as_mut_ptr_range().end as u64;
+        let mut regs = [0; 16];
+        // As if `worker` had been called: the return address slot is pushed.
+        regs[7] = top - 8;
+        unsafe {
+            MAIN = Box::leak(Box::new(ProcessContext::default()));
+            WORKER = Box::leak(Box::new(ProcessContext {
+                regs,
+                rip: worker as *const () as u64,
+                ..ProcessContext::default()
+            }));
+        }
+
+        let mut value = -1.5f64;
+        for _ in 0..100 {
+            let back = unsafe { switch_holding(value, &mut *MAIN, &*WORKER) };
+            assert_eq!(back, value);
+            assert_eq!(mxcsr() & ROUNDING, 0);
+            value = value * 2.0 - 1.0;
+        }
+    }
 }
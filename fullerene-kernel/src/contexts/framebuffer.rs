@@ -4,7 +4,8 @@ use core::fmt::Write;
 use nitrogen::virtio::gpu::VirtioGpu;
 use petroleum::common::EfiGraphicsPixelFormat;
 use petroleum::graphics::FramebufferGuard;
-use petroleum::graphics::color::FramebufferInfo;
+use petroleum::graphics::color::{FramebufferInfo, SimpleFramebufferConfig};
+use petroleum::graphics::fbconsole::FbConsole;
 use petroleum::graphics::framebuffer::UefiFramebufferWriter;
 use petroleum::graphics::framebuffer_mapper::{CacheMode, FramebufferMapper};
 use petroleum::graphics::text::VgaBuffer;
@@ -13,6 +14,9 @@ pub struct FramebufferContext {
     pub renderer: Option<UefiFramebufferWriter>,
     pub gpu: Option<Box<VirtioGpu>>,
     pub vga_console: Option<VgaBuffer>,
+    /// Text console on the GOP framebuffer; takes kernel text output in
+    /// place of the renderer when present.
+    pub fb_console: Option<FbConsole>,
     pub bpp: u32,
     pub fb_phys: u64,
     pub fb_width_px: u32,
@@ -27,6 +31,7 @@ impl FramebufferContext {
             renderer: None,
             gpu: None,
             vga_console: None,
+            fb_console: None,
             bpp: 32,
             fb_phys: 0,
             fb_width_px: 0,
@@ -187,6 +192,16 @@ impl FramebufferContext {
         };
         let writer = petroleum::graphics::framebuffer::FramebufferWriter::<u32>::new(info);
         self.renderer = Some(UefiFramebufferWriter::Uefi32(writer));
+        if self.bpp == 32 {
+            self.fb_console = FbConsole::new(SimpleFramebufferConfig {
+                base_addr: fb_va as usize,
+                width: self.fb_width_px as usize,
+                height: self.fb_height_px as usize,
+                stride: self.fb_stride_bytes as usize,
+                bytes_per_pixel: 4,
+                pixel_format: Some(self.fb_pixel_format),
+            });
+        }
         true
     }
     pub fn info(&self) -> Option<FramebufferInfo> {
@@ -230,6 +245,10 @@ impl FramebufferContext {
         FramebufferGuard::try_new(pixels, info.width, info.height, stride_pixels)
     }
     pub fn write_str(&mut self, s: &str) {
+        if let Some(ref mut c) = self.fb_console {
+            let _ = c.write_str(s);
+            return;
+        }
         if let Some(ref mut r) = self.renderer {
             let _ = r.write_str(s);
             return;
@@ -239,6 +258,10 @@ impl FramebufferContext {
         }
     }
     pub fn write_fmt(&mut self, args: core::fmt::Arguments) {
+        if let Some(ref mut c) = self.fb_console {
+            let _ = core::fmt::write(c, args);
+            return;
+        }
         if let Some(ref mut r) = self.renderer {
             let _ = core::fmt::write(r, args);
             return;
@@ -52,8 +52,9 @@ impl MemoryContext {
         &mut self,
         phys: u64,
         size: u64,
+        cache: petroleum::CacheMode,
     ) -> Result<u64, petroleum::MemoryError> {
-        self.vm_mut()?.map_framebuffer(phys, size, None)
+        self.vm_mut()?.map_framebuffer(phys, size, None, cache)
     }
 
     pub fn map_heap_vm(&mut self, phys: u64, size: u64) -> Result<u64, petroleum::MemoryError> {
@@ -219,7 +219,7 @@ impl VfsContext {
         Ok(removed)
     }
 
-    fn record_device_mount(&self, device: &str, mount_point: &str) {
+    pub(crate) fn record_device_mount(&self, device: &str, mount_point: &str) {
         let mut mounts = self.mounted_devices.lock();
         mounts.retain(|(source, path)| source != device && path != mount_point);
         mounts.push((String::from(device), String::from(mount_point)));
@@ -312,6 +312,16 @@ impl VfsContext {
         vfs.size_at(handle.mount_index, handle.local_fd)
     }
 
+    pub fn truncate(&self, fd: u32, len: u64) -> Result<(), FsError> {
+        let mut vfs = self.inner.lock();
+        let handle = self
+            .handle_table
+            .lock()
+            .find(fd)
+            .ok_or(FsError::InvalidFileDescriptor)?;
+        vfs.truncate_at(handle.mount_index, handle.local_fd, len)
+    }
+
     pub fn seek_from(&self, fd: u32, position: SeekFrom) -> Result<u64, FsError> {
         let mut vfs = self.inner.lock();
         let handle = self
@@ -383,6 +393,11 @@ impl VfsContext {
         self.inner.lock().exists(path)
     }
 
+    pub fn stat(&self, path: &str) -> Result<VNode, FsError> {
+        trace!("stat {}", path);
+        self.inner.lock().stat(path)
+    }
+
     /// Replace a regular file and persist the complete buffer, even when the
     /// backing filesystem accepts only a partial write per call.
     pub fn replace_file(&self, path: &str, data: &[u8]) -> Result<(), FsError> {
@@ -644,6 +659,10 @@ pub fn size(fd: u32) -> Result<u64, FsError> {
     with_vfs(|vfs| vfs.size(fd)).ok_or(FsError::PermissionDenied)?
 }
 
+pub fn truncate(fd: u32, len: u64) -> Result<(), FsError> {
+    with_vfs(|vfs| vfs.truncate(fd, len)).ok_or(FsError::PermissionDenied)?
+}
+
 pub fn seek_from(fd: u32, position: SeekFrom) -> Result<u64, FsError> {
     with_vfs(|vfs| vfs.seek_from(fd, position)).ok_or(FsError::PermissionDenied)?
 }
@@ -673,6 +692,10 @@ pub fn exists(path: &str) -> bool {
     with_vfs(|vfs| vfs.exists(path)).unwrap_or(false)
 }
 
+pub fn stat(path: &str) -> Result<VNode, FsError> {
+    with_vfs(|vfs| vfs.stat(path)).ok_or(FsError::PermissionDenied)?
+}
+
 /// Replace a complete file through the canonical VFS context.
 pub fn replace_file(path: &str, data: &[u8]) -> Result<(), FsError> {
     with_vfs(|vfs| vfs.replace_file(path, data)).ok_or(FsError::PermissionDenied)?
@@ -4,6 +4,7 @@
 //! concrete memory manager and page-table infrastructure.
 
 use nitrogen::{DriverContext, DriverContextError, PageFlags};
+use petroleum::CacheMode;
 use petroleum::initializer::FrameAllocator;
 use x86_64::structures::paging::PageTableFlags;
 
@@ -66,9 +67,13 @@ impl DriverContext for KernelDriverContext {
         if flags.writable {
             pte_flags |= PageTableFlags::WRITABLE;
         }
-        if flags.write_combining {
-            pte_flags |= PageTableFlags::WRITE_THROUGH;
+        // Pages mapped through a driver context are device memory.
+        pte_flags |= if flags.write_combining {
+            CacheMode::WriteCombining
+        } else {
+            CacheMode::Uncached
         }
+        .page_table_flags();
 
         m.safe_map_page(virt, phys, pte_flags)
             .map_err(|_| DriverContextError::MmioMappingFailed)
@@ -227,6 +227,101 @@ impl StorageDriver for SdCardStorageCtl {
     }
 }
 
+// -- AHCI SATA disks -------------------------------------------
+
+/// Name the first SATA disk is registered under in `/dev`.
+#[cfg(not(nitrogen_no_storage))]
+const SATA_DISK: &str = "sata0";
+
+#[cfg(not(nitrogen_no_storage))]
+pub struct AhciDriver;
+
+#[cfg(not(nitrogen_no_storage))]
+impl Driver for AhciDriver {
+    fn pci_class(&self) -> Option<(u8, u8)> {
+        Some((0x01, 0x06)) // SATA controller
+    }
+    fn probe(&self, _ctx: &dyn DriverContext, device: &PciDevice) -> DriverBox {
+        DriverBox::Storage(Box::new(AhciStorageCtl(device.clone())))
+    }
+}
+
+#[cfg(not(nitrogen_no_storage))]
+struct AhciStorageCtl(PciDevice);
+
+#[cfg(not(nitrogen_no_storage))]
+impl StorageDriver for AhciStorageCtl {
+    fn init(&mut self) -> Result<(), nitrogen::DriverError> {
+        nitrogen::storage::ahci::attach(&crate::driver_context_impl::KernelDriverContext, &self.0)?;
+        let Some(disk) = nitrogen::storage::ahci::disk_info() else {
+            log::info!("SATA: no disk attached");
+            return Ok(());
+        };
+        if !crate::devfs::block_device_exists(SATA_DISK) {
+            crate::klog_fmt!(
+                "SATA: registered /dev/{} ({}, {} sectors)\n",
+                SATA_DISK,
+                disk.model,
+                disk.total_sectors
+            );
+            crate::devfs::register_block_device(
+                SATA_DISK.into(),
+                Box::new(SataBlockDev {
+                    block_size: disk.sector_size,
+                    total_blocks: disk.total_sectors,
+                }),
+            );
+        }
+        Ok(())
+    }
+    fn read_blocks(
+        &self,
+        lba: u64,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<(), nitrogen::DriverError> {
+        let count = u16::try_from(count).map_err(|_| nitrogen::DriverError::InvalidArgument)?;
+        nitrogen::storage::ahci::read_sectors(lba, count, buf)
+    }
+    fn write_blocks(
+        &self,
+        _lba: u64,
+        _count: usize,
+        _buf: &[u8],
+    ) -> Result<(), nitrogen::DriverError> {
+        Err(nitrogen::DriverError::NotSupported)
+    }
+    fn block_size(&self) -> u32 {
+        nitrogen::storage::ahci::disk_info().map_or(0, |disk| disk.sector_size)
+    }
+    fn total_blocks(&self) -> u64 {
+        nitrogen::storage::ahci::disk_info().map_or(0, |disk| disk.total_sectors)
+    }
+}
+
+/// The first SATA disk, read-only.
+#[cfg(not(nitrogen_no_storage))]
+struct SataBlockDev {
+    block_size: u32,
+    total_blocks: u64,
+}
+
+#[cfg(not(nitrogen_no_storage))]
+impl BlockDevice for SataBlockDev {
+    fn read_sectors(&mut self, lba: u64, count: u16, buf: &mut [u8]) -> Result<(), BlockError> {
+        nitrogen::storage::ahci::read_sectors(lba, count, buf).map_err(|_| BlockError::Device)
+    }
+    fn write_sectors(&mut self, _lba: u64, _count: u16, _buf: &[u8]) -> Result<(), BlockError> {
+        Err(BlockError::Device)
+    }
+    fn sector_size(&self) -> u32 {
+        self.block_size
+    }
+    fn total_sectors(&self) -> u64 {
+        self.total_blocks
+    }
+}
+
 // ────────────────────────────────────────────────────────────
 //  Registry construction
 // ────────────────────────────────────────────────────────────
@@ -238,6 +333,8 @@ pub fn build_registry() -> DriverRegistry {
     let mut reg = reg;
     #[cfg(not(nitrogen_no_storage))]
     reg.register("sd_card", Box::new(SdCardDriver));
+    #[cfg(not(nitrogen_no_storage))]
+    reg.register("ahci", Box::new(AhciDriver));
     #[cfg(not(nitrogen_no_usb))]
     reg.register("usb_storage", Box::new(UsbStorageDriver::new()));
     // Future: virtio_gpu, iwlwifi, hda, …
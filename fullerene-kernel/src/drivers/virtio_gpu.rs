@@ -25,11 +25,8 @@ pub fn init() -> Option<(Box<VirtioGpu>, UefiFramebufferWriter)> {
     let mut result = nitrogen::virtio::gpu::init::init(&ctx)?;
 
     // 2. Framebuffer info
-    let fb_config = {
-        let opt = petroleum::FULLERENE_FRAMEBUFFER_CONFIG
-            .get()
-            .and_then(|m| m.lock().clone());
-        opt.unwrap_or(petroleum::common::FullereneFramebufferConfig {
+    let fb_config = petroleum::framebuffer::get_config().unwrap_or(
+        petroleum::common::FullereneFramebufferConfig {
             address: 0x40000000,
             width: 1024,
             height: 768,
@@ -37,8 +34,8 @@ pub fn init() -> Option<(Box<VirtioGpu>, UefiFramebufferWriter)> {
             pixel_format:
                 petroleum::common::EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor,
             bpp: 32,
-        })
-    };
+        },
+    );
     let fb_phys = fb_config.address;
     let fb_virt = fb_phys + off;
     let fb_byte_size = (fb_config.stride * fb_config.height * (fb_config.bpp / 8)) as u64;
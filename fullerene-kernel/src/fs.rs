@@ -1,8 +1,15 @@
+pub mod block;
+pub mod fat32;
+pub mod initrd;
+pub mod ramdisk;
+pub mod ramfs;
+pub mod vfs;
+
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 
-use crate::contexts::vfs;
+use crate::contexts::vfs as vfs_context;
 pub use genome::fs::{DirEntry, FsError, PackageEntry, parse_manifest};
 use genome::io::{FileReader, Read, Seek, SeekFrom};
 
@@ -14,11 +21,19 @@ fn basename(path: &str) -> &str {
 }
 
 fn is_dir(path: &str) -> bool {
-    vfs::readdir(path).is_ok()
+    vfs_context::readdir(path).is_ok()
 }
 
 pub fn init() {
-    vfs::init_vfs();
+    vfs_context::init_vfs();
+    match vfs::mount_boot_volume() {
+        Some(device) => log::info!(
+            "FAT32 volume on /dev/{} mounted at {}",
+            device,
+            vfs::BOOT_MOUNT
+        ),
+        None => log::info!("No FAT32 volume found for {}", vfs::BOOT_MOUNT),
+    }
     log::info!("File system initialized (VFS + tmpfs)");
 }
 
@@ -46,45 +61,70 @@ impl From<genome::vfs::FileDescriptor> for FileDesc {
 // ── Public file operations ────────────────────────────────────
 
 pub fn create_file(path: &str, data: &[u8]) -> Result<(), FsError> {
-    let fd_info = vfs::create(path)?;
+    let fd_info = vfs_context::create(path)?;
     if !data.is_empty() {
         let mut remaining = data;
         while !remaining.is_empty() {
-            match vfs::write(fd_info.fd, remaining) {
+            match vfs_context::write(fd_info.fd, remaining) {
                 Ok(0) => {
-                    let _ = vfs::close(fd_info.fd);
+                    let _ = vfs_context::close(fd_info.fd);
                     return Err(FsError::InvalidInput);
                 }
                 Ok(n) => remaining = &remaining[n..],
                 Err(e) => {
-                    let _ = vfs::close(fd_info.fd);
+                    let _ = vfs_context::close(fd_info.fd);
                     return Err(e);
                 }
             }
         }
     }
-    let _ = vfs::close(fd_info.fd);
+    let _ = vfs_context::close(fd_info.fd);
     Ok(())
 }
 
 pub fn create_dir(path: &str) -> Result<(), FsError> {
-    vfs::mkdir(path)
+    vfs_context::mkdir(path)
 }
 
 pub fn remove(path: &str) -> Result<(), FsError> {
-    vfs::unlink(path)
+    vfs_context::unlink(path)
 }
 
 pub fn open_file(path: &str) -> Result<FileDesc, FsError> {
-    vfs::open(path, 0).map(FileDesc::from)
+    vfs_context::open(path, 0).map(FileDesc::from)
+}
+
+/// Open `path` for writing, creating it if needed.  Without `append` the
+/// file is truncated; with it, writes go after the existing contents.
+pub fn open_for_output(path: &str, append: bool) -> Result<FileDesc, FsError> {
+    if is_dir(path) {
+        return Err(FsError::IsADirectory);
+    }
+    if !exists(path) {
+        create_file(path, &[])?;
+    }
+    let mut fd = FileDesc::from(vfs_context::open(path, crate::linux::O_WRONLY as u32)?);
+    // Truncate in place so anyone still reading the file sees it emptied.
+    let prepared = if append {
+        vfs_context::seek_from(fd.fd, SeekFrom::End(0)).map(|end| fd.offset = end)
+    } else {
+        truncate_file(&fd, 0)
+    };
+    match prepared {
+        Ok(()) => Ok(fd),
+        Err(error) => {
+            let _ = close_file(fd);
+            Err(error)
+        }
+    }
 }
 
 pub fn close_file(fd: FileDesc) -> Result<(), FsError> {
-    vfs::close(fd.fd)
+    vfs_context::close(fd.fd)
 }
 
 pub fn read_file(fd: &mut FileDesc, buffer: &mut [u8]) -> Result<usize, FsError> {
-    let n = vfs::read(fd.fd, buffer)?;
+    let n = vfs_context::read(fd.fd, buffer)?;
     fd.offset = fd
         .offset
         .checked_add(n as u64)
@@ -93,7 +133,7 @@ pub fn read_file(fd: &mut FileDesc, buffer: &mut [u8]) -> Result<usize, FsError>
 }
 
 pub fn write_file(fd: &mut FileDesc, data: &[u8]) -> Result<usize, FsError> {
-    let written = vfs::write(fd.fd, data)?;
+    let written = vfs_context::write(fd.fd, data)?;
     fd.offset = fd
         .offset
         .checked_add(written as u64)
@@ -102,17 +142,22 @@ pub fn write_file(fd: &mut FileDesc, data: &[u8]) -> Result<usize, FsError> {
 }
 
 pub fn seek_file(fd: &mut FileDesc, position: u64) -> Result<(), FsError> {
-    vfs::seek(fd.fd, position).map(|_| {
+    vfs_context::seek(fd.fd, position).map(|_| {
         fd.offset = position;
     })
 }
 
 pub fn file_position(fd: &FileDesc) -> Result<u64, FsError> {
-    vfs::position(fd.fd)
+    vfs_context::position(fd.fd)
+}
+
+/// Cut or zero-extend the open file to `len` bytes.
+pub fn truncate_file(fd: &FileDesc, len: u64) -> Result<(), FsError> {
+    vfs_context::truncate(fd.fd, len)
 }
 
 pub fn file_size_for_handle(fd: &FileDesc) -> Result<u64, FsError> {
-    vfs::size(fd.fd)
+    vfs_context::size(fd.fd)
 }
 
 impl Read for FileDesc {
@@ -141,7 +186,7 @@ impl FileReader for FileDesc {
 }
 
 pub fn list_dir(path: &str) -> Result<Vec<DirEntry>, FsError> {
-    vfs::readdir(path).map(|entries| {
+    vfs_context::readdir(path).map(|entries| {
         entries
             .into_iter()
             .map(|v| DirEntry {
@@ -154,15 +199,15 @@ pub fn list_dir(path: &str) -> Result<Vec<DirEntry>, FsError> {
 }
 
 pub fn exists(path: &str) -> bool {
-    vfs::exists(path)
+    vfs_context::exists(path)
 }
 
 pub fn working_directory() -> Result<String, FsError> {
-    vfs::working_directory()
+    vfs_context::working_directory()
 }
 
 pub fn change_directory(path: &str) -> Result<(), FsError> {
-    vfs::change_directory(path)
+    vfs_context::change_directory(path)
 }
 
 pub fn copy_file(src: &str, dst: &str) -> Result<(), FsError> {
@@ -0,0 +1,162 @@
+//! Bridge from kernel [`BlockDevice`]s to the filesystem drivers.
+//!
+//! The FAT/exFAT code in `genome` reads whole sectors through
+//! [`genome::block::BlockDevice`]; [`SectorAdapter`] presents any
+//! block-addressed device that way, one block per sector.  [`LeasedBlocks`]
+//! goes the other way, for `/dev` devices handed to kernel filesystems.
+
+use alloc::boxed::Box;
+
+use genome::block::BlockError;
+use petroleum::initializer::BlockDevice;
+use petroleum::{SystemError, SystemResult};
+use spin::Mutex;
+
+pub struct SectorAdapter<D>(pub D);
+
+impl<D: BlockDevice> SectorAdapter<D> {
+    /// Validate a `count`-sector transfer at `lba` against the device and
+    /// the caller's buffer, returning the block size.
+    fn check(&self, lba: u64, count: u16, buf_len: usize) -> Result<usize, BlockError> {
+        let block_size = self.0.block_size();
+        let required = block_size * count as usize;
+        if buf_len < required {
+            return Err(BlockError::BufferTooSmall {
+                required,
+                provided: buf_len,
+            });
+        }
+        match lba.checked_add(count as u64) {
+            Some(end) if end <= self.0.block_count() => Ok(block_size),
+            _ => Err(BlockError::LbaOverflow),
+        }
+    }
+}
+
+impl<D: BlockDevice> genome::block::BlockDevice for SectorAdapter<D> {
+    fn read_sectors(&mut self, lba: u64, count: u16, buf: &mut [u8]) -> Result<(), BlockError> {
+        let block_size = self.check(lba, count, buf.len())?;
+        for (i, chunk) in buf
+            .chunks_exact_mut(block_size)
+            .take(count as usize)
+            .enumerate()
+        {
+            self.0
+                .read_block(lba + i as u64, chunk)
+                .map_err(|_| BlockError::Device)?;
+        }
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, count: u16, buf: &[u8]) -> Result<(), BlockError> {
+        let block_size = self.check(lba, count, buf.len())?;
+        for (i, chunk) in buf
+            .chunks_exact(block_size)
+            .take(count as usize)
+            .enumerate()
+        {
+            self.0
+                .write_block(lba + i as u64, chunk)
+                .map_err(|_| BlockError::Device)?;
+        }
+        Ok(())
+    }
+
+    fn sector_size(&self) -> u32 {
+        self.0.block_size() as u32
+    }
+
+    fn total_sectors(&self) -> u64 {
+        self.0.block_count()
+    }
+}
+
+/// A block device leased from [`crate::devfs`], presented as a kernel
+/// [`BlockDevice`] with one block per sector.
+pub struct LeasedBlocks(Mutex<Box<dyn genome::block::BlockDevice>>);
+
+impl LeasedBlocks {
+    pub fn new(device: Box<dyn genome::block::BlockDevice>) -> Self {
+        Self(Mutex::new(device))
+    }
+}
+
+fn block_error(error: BlockError) -> SystemError {
+    match error {
+        BlockError::BufferTooSmall { .. } | BlockError::LbaOverflow => SystemError::InvalidArgument,
+        BlockError::Device | BlockError::SectorNotFound => SystemError::DeviceError,
+    }
+}
+
+impl BlockDevice for LeasedBlocks {
+    fn read_block(&self, lba: u64, buf: &mut [u8]) -> SystemResult<()> {
+        if lba >= self.block_count() {
+            return Err(SystemError::InvalidArgument);
+        }
+        self.0.lock().read_sectors(lba, 1, buf).map_err(block_error)
+    }
+
+    fn write_block(&self, lba: u64, buf: &[u8]) -> SystemResult<()> {
+        if lba >= self.block_count() {
+            return Err(SystemError::InvalidArgument);
+        }
+        self.0
+            .lock()
+            .write_sectors(lba, 1, buf)
+            .map_err(block_error)
+    }
+
+    fn block_size(&self) -> usize {
+        self.0.lock().sector_size() as usize
+    }
+
+    fn block_count(&self) -> u64 {
+        self.0.lock().total_sectors()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::ramdisk::RamDisk;
+    use genome::block::BlockDevice as _;
+
+    #[test]
+    fn sectors_map_onto_blocks() {
+        let mut dev = SectorAdapter(RamDisk::new("test_ramdisk", 512, 4).unwrap());
+        let data: alloc::vec::Vec<u8> = (0..1024).map(|i| i as u8).collect();
+        dev.write_sectors(2, 2, &data).unwrap();
+
+        let mut out = [0u8; 1024];
+        dev.read_sectors(2, 2, &mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+        assert_eq!(
+            dev.read_sectors(3, 2, &mut out),
+            Err(BlockError::LbaOverflow)
+        );
+        assert!(matches!(
+            dev.read_sectors(0, 3, &mut out),
+            Err(BlockError::BufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn leased_sectors_read_back_as_blocks() {
+        let disk = SectorAdapter(RamDisk::new("test_ramdisk", 512, 4).unwrap());
+        let dev = LeasedBlocks::new(Box::new(disk));
+        assert_eq!((dev.block_size(), dev.block_count()), (512, 4));
+        let data = [0x5Au8; 512];
+        dev.write_block(3, &data).unwrap();
+        let mut out = [0u8; 512];
+        dev.read_block(3, &mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(
+            dev.read_block(4, &mut out),
+            Err(SystemError::InvalidArgument)
+        );
+        assert_eq!(
+            dev.read_block(0, &mut out[..100]),
+            Err(SystemError::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Read-only FAT32 volumes over kernel block devices.
+//!
+//! [`Fat32::mount`] checks the boot sector itself (512-byte sectors and a
+//! FAT32 BIOS parameter block) and then hands the device to genome's FAT
+//! driver through [`SectorAdapter`], which walks the cluster chains.  Long
+//! file names are assembled there; an entry can be opened by either its long
+//! or its 8.3 name.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use genome::fat::FatFileSystem;
+use genome::fs::FsError;
+use genome::vfs::{FileDescriptor, FileSystem, FileSystemCapabilities, InodeType, VNode};
+use petroleum::initializer::BlockDevice;
+
+use super::block::SectorAdapter;
+
+/// The only sector size the reader accepts.
+pub const SECTOR_SIZE: usize = 512;
+
+/// BIOS parameter block fields needed to recognise a FAT32 volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bpb {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fat_count: u8,
+    pub total_sectors: u32,
+    pub sectors_per_fat: u32,
+    pub root_cluster: u32,
+}
+
+impl Bpb {
+    /// Parse and validate the boot sector of a FAT32 volume.
+    ///
+    /// Volumes with a sector size other than [`SECTOR_SIZE`] are
+    /// `NotSupported`; FAT12/16 layouts and malformed sectors are
+    /// `InvalidInput`.
+    pub fn parse(sector: &[u8]) -> Result<Self, FsError> {
+        if sector.len() < SECTOR_SIZE || sector[510..512] != [0x55, 0xAA] {
+            return Err(FsError::InvalidInput);
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([sector[offset], sector[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                sector[offset],
+                sector[offset + 1],
+                sector[offset + 2],
+                sector[offset + 3],
+            ])
+        };
+
+        let bpb = Self {
+            bytes_per_sector: u16_at(11),
+            sectors_per_cluster: sector[13],
+            reserved_sectors: u16_at(14),
+            fat_count: sector[16],
+            total_sectors: u32_at(32),
+            sectors_per_fat: u32_at(36),
+            root_cluster: u32_at(44),
+        };
+        if bpb.bytes_per_sector as usize != SECTOR_SIZE {
+            return Err(FsError::NotSupported);
+        }
+        // FAT12/16 have a fixed root directory and a 16-bit FAT size.
+        let root_entries = u16_at(17);
+        let sectors_per_fat_16 = u16_at(22);
+        if root_entries != 0
+            || sectors_per_fat_16 != 0
+            || bpb.sectors_per_fat == 0
+            || bpb.root_cluster < 2
+            || !bpb.sectors_per_cluster.is_power_of_two()
+            || bpb.reserved_sectors == 0
+            || bpb.fat_count == 0
+        {
+            return Err(FsError::InvalidInput);
+        }
+        Ok(bpb)
+    }
+}
+
+/// A mounted read-only FAT32 volume.
+pub struct Fat32 {
+    bpb: Bpb,
+    inner: FatFileSystem,
+}
+
+impl Fat32 {
+    /// Mount the FAT32 volume starting at block 0 of `device`.
+    pub fn mount<D: BlockDevice + 'static>(device: D) -> Result<Self, FsError> {
+        if device.block_size() != SECTOR_SIZE {
+            return Err(FsError::NotSupported);
+        }
+        let mut boot = [0u8; SECTOR_SIZE];
+        device.read_block(0, &mut boot).map_err(|_| FsError::Io)?;
+        let bpb = Bpb::parse(&boot)?;
+        if u64::from(bpb.total_sectors) > device.block_count() {
+            return Err(FsError::InvalidInput);
+        }
+        let inner = FatFileSystem::new(Box::new(SectorAdapter(device)))?;
+        Ok(Self { bpb, inner })
+    }
+
+    pub fn bpb(&self) -> &Bpb {
+        &self.bpb
+    }
+
+    /// Open a regular file for reading.
+    pub fn open(&mut self, path: &str) -> Result<FileDescriptor, FsError> {
+        self.inner.open(path, 0).ok_or(FsError::FileNotFound)
+    }
+
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<VNode>, FsError> {
+        self.inner.readdir(path)
+    }
+
+    /// Read from `file`'s current position, advancing it.
+    pub fn read(&mut self, file: &FileDescriptor, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.inner.read(file.fd, buf)
+    }
+}
+
+impl FileSystem for Fat32 {
+    fn capabilities(&self) -> FileSystemCapabilities {
+        FileSystemCapabilities::new(true, false, false, false, false)
+    }
+
+    fn open(&mut self, path: &str, flags: u32) -> Option<FileDescriptor> {
+        self.inner.open(path, flags)
+    }
+
+    fn read(&mut self, fd: u32, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.inner.read(fd, buf)
+    }
+
+    fn write(&mut self, _fd: u32, _data: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn close(&mut self, fd: u32) -> Result<(), FsError> {
+        self.inner.close(fd)
+    }
+
+    fn seek(&mut self, fd: u32, pos: u64) -> Result<(), FsError> {
+        self.inner.seek(fd, pos)
+    }
+
+    fn position(&mut self, fd: u32) -> Result<u64, FsError> {
+        self.inner.position(fd)
+    }
+
+    fn size(&mut self, fd: u32) -> Result<u64, FsError> {
+        self.inner.size(fd)
+    }
+
+    fn create(&mut self, _path: &str, _kind: InodeType) -> Option<u64> {
+        None
+    }
+
+    fn mkdir(&mut self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn readdir(&mut self, path: &str) -> Result<Vec<VNode>, FsError> {
+        self.inner.readdir(path)
+    }
+
+    fn exists(&mut self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::ramdisk::RamDisk;
+    use alloc::string::String;
+
+    const RESERVED: u32 = 32;
+    /// Just above the 65 525 clusters below which a volume counts as FAT16.
+    const CLUSTERS: u32 = 65_600;
+    const FAT_SECTORS: u32 = (CLUSTERS + 2).div_ceil(128);
+    const DATA_START: u32 = RESERVED + FAT_SECTORS;
+    const TOTAL: u32 = DATA_START + CLUSTERS;
+
+    const HELLO: &[u8] = b"hello, fat32\n";
+    const LONG: &[u8] = b"long name\n";
+    const LONG_NAME: &str = "Long file name.txt";
+    const LONG_SHORT: &[u8; 11] = b"LONGFI~1TXT";
+
+    fn put16(buf: &mut [u8], offset: usize, value: u16) {
+        buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put32(buf: &mut [u8], offset: usize, value: u32) {
+        buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn boot_sector(bytes_per_sector: u16) -> [u8; SECTOR_SIZE] {
+        let mut s = [0u8; SECTOR_SIZE];
+        s[..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        s[3..11].copy_from_slice(b"MSWIN4.1");
+        put16(&mut s, 11, bytes_per_sector);
+        s[13] = 1;
+        put16(&mut s, 14, RESERVED as u16);
+        s[16] = 1;
+        s[21] = 0xF8;
+        put16(&mut s, 24, 32);
+        put16(&mut s, 26, 64);
+        put32(&mut s, 32, TOTAL);
+        put32(&mut s, 36, FAT_SECTORS);
+        put32(&mut s, 44, 2);
+        put16(&mut s, 48, 1);
+        put16(&mut s, 50, 6);
+        s[64] = 0x80;
+        s[66] = 0x29;
+        put32(&mut s, 67, 0x1234_5678);
+        s[71..82].copy_from_slice(b"NO NAME    ");
+        s[82..90].copy_from_slice(b"FAT32   ");
+        s[510] = 0x55;
+        s[511] = 0xAA;
+        s
+    }
+
+    fn short_entry(name: &[u8; 11], cluster: u16, size: u32) -> [u8; 32] {
+        let mut e = [0u8; 32];
+        e[..11].copy_from_slice(name);
+        e[11] = 0x20;
+        put16(&mut e, 26, cluster);
+        put32(&mut e, 28, size);
+        e
+    }
+
+    /// Long-name entries for `name`, in on-disk order (last part first).
+    fn lfn_entries(name: &str, short: &[u8; 11]) -> Vec<[u8; 32]> {
+        let checksum = short
+            .iter()
+            .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c));
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        if !units.len().is_multiple_of(13) {
+            units.push(0);
+        }
+        while !units.len().is_multiple_of(13) {
+            units.push(0xFFFF);
+        }
+        let parts = units.len() / 13;
+        (0..parts)
+            .rev()
+            .map(|part| {
+                let mut e = [0u8; 32];
+                e[0] = (part as u8 + 1) | if part + 1 == parts { 0x40 } else { 0 };
+                e[11] = 0x0F;
+                e[13] = checksum;
+                let chars = &units[part * 13..part * 13 + 13];
+                let offsets = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+                for (&offset, &unit) in offsets.iter().zip(chars) {
+                    put16(&mut e, offset, unit);
+                }
+                e
+            })
+            .collect()
+    }
+
+    fn cluster_lba(cluster: u32) -> u64 {
+        (DATA_START + cluster - 2) as u64
+    }
+
+    fn fat32_image() -> RamDisk {
+        let disk = RamDisk::new("test_fat32", SECTOR_SIZE, TOTAL as u64).unwrap();
+        disk.write_block(0, &boot_sector(512)).unwrap();
+
+        let mut fsinfo = [0u8; SECTOR_SIZE];
+        put32(&mut fsinfo, 0, 0x4161_5252);
+        put32(&mut fsinfo, 484, 0x6141_7272);
+        put32(&mut fsinfo, 488, 0xFFFF_FFFF);
+        put32(&mut fsinfo, 492, 0xFFFF_FFFF);
+        put32(&mut fsinfo, 508, 0xAA55_0000);
+        disk.write_block(1, &fsinfo).unwrap();
+
+        // Media/clean markers, then end-of-chain for the root directory
+        // (cluster 2) and the two single-cluster files (3 and 4).
+        let mut fat = [0u8; SECTOR_SIZE];
+        for (i, entry) in [
+            0x0FFF_FFF8,
+            0x0FFF_FFFF,
+            0x0FFF_FFFF,
+            0x0FFF_FFFF,
+            0x0FFF_FFFF,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            put32(&mut fat, i * 4, entry);
+        }
+        disk.write_block(RESERVED as u64, &fat).unwrap();
+
+        let mut root = [0u8; SECTOR_SIZE];
+        let mut entries = Vec::new();
+        entries.push(short_entry(b"HELLO   TXT", 3, HELLO.len() as u32));
+        entries.extend(lfn_entries(LONG_NAME, LONG_SHORT));
+        entries.push(short_entry(LONG_SHORT, 4, LONG.len() as u32));
+        for (i, entry) in entries.iter().enumerate() {
+            root[i * 32..i * 32 + 32].copy_from_slice(entry);
+        }
+        disk.write_block(cluster_lba(2), &root).unwrap();
+
+        for (cluster, contents) in [(3, HELLO), (4, LONG)] {
+            let mut block = [0u8; SECTOR_SIZE];
+            block[..contents.len()].copy_from_slice(contents);
+            disk.write_block(cluster_lba(cluster), &block).unwrap();
+        }
+        disk
+    }
+
+    fn read_all(fs: &mut Fat32, path: &str) -> Vec<u8> {
+        let file = fs.open(path).unwrap();
+        let mut buf = [0u8; 64];
+        let n = fs.read(&file, &mut buf).unwrap();
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn mounts_image_and_lists_root() {
+        let mut fs = Fat32::mount(fat32_image()).unwrap();
+        assert_eq!(fs.bpb().root_cluster, 2);
+
+        let names: Vec<String> = fs
+            .read_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["HELLO.TXT", LONG_NAME]);
+
+        assert_eq!(read_all(&mut fs, "/hello.txt"), HELLO);
+        assert_eq!(read_all(&mut fs, "/LONGFI~1.TXT"), LONG);
+        assert_eq!(read_all(&mut fs, "/long file name.txt"), LONG);
+        assert!(FileSystem::mkdir(&mut fs, "/new").is_err());
+    }
+
+    #[test]
+    fn rejects_non_512_byte_sectors() {
+        assert_eq!(
+            Bpb::parse(&boot_sector(4096)).err(),
+            Some(FsError::NotSupported)
+        );
+        let disk = RamDisk::new("test_fat32_4k", 4096, 16).unwrap();
+        assert_eq!(Fat32::mount(disk).err(), Some(FsError::NotSupported));
+    }
+}
@@ -0,0 +1,348 @@
+//! Read-only filesystem over the initrd, mounted at `/init`.
+//!
+//! Bellows loads `\EFI\BOOT\INITRD.CPIO` from the boot volume and passes
+//! its physical range in the boot parameters.  [`Initrd::parse`] indexes the
+//! CPIO `newc` entries (see [`crate::initramfs`]) into a directory tree
+//! whose files borrow their contents from the archive, so nothing is
+//! copied.  Directories the archive leaves out are implied by the paths of
+//! the files inside them; symlinks and device nodes are skipped.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use genome::fs::FsError;
+use genome::vfs::{FileDescriptor, FileSystem, FileSystemCapabilities, InodeType, VNode};
+
+use crate::initramfs::parse_entry;
+
+/// Name of the entry that ends a `newc` archive.
+const TRAILER: &str = "TRAILER!!!";
+const MODE_TYPE: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+
+struct Node {
+    ino: u64,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    File(&'static [u8]),
+    Directory(BTreeMap<String, Node>),
+}
+
+impl Node {
+    fn size(&self) -> u64 {
+        match self.kind {
+            NodeKind::File(data) => data.len() as u64,
+            NodeKind::Directory(_) => 0,
+        }
+    }
+}
+
+/// An open descriptor; `data` is `None` for a directory.
+struct OpenFile {
+    data: Option<&'static [u8]>,
+    offset: u64,
+}
+
+pub struct Initrd {
+    root: Node,
+    next_ino: u64,
+    open: BTreeMap<u32, OpenFile>,
+    next_fd: u32,
+}
+
+impl Initrd {
+    /// Index the CPIO `newc` archive `archive`.
+    ///
+    /// Fails with [`FsError::InvalidInput`] if an entry is malformed or the
+    /// archive ends before its trailer.
+    pub fn parse(archive: &'static [u8]) -> Result<Self, FsError> {
+        let mut initrd = Self {
+            root: Node {
+                ino: 1,
+                kind: NodeKind::Directory(BTreeMap::new()),
+            },
+            next_ino: 2,
+            open: BTreeMap::new(),
+            next_fd: 0,
+        };
+        let mut offset = 0;
+        loop {
+            let (header, body_start, next) =
+                parse_entry(archive, offset).ok_or(FsError::InvalidInput)?;
+            if header.name == TRAILER {
+                return Ok(initrd);
+            }
+            let kind = match header.mode & MODE_TYPE {
+                MODE_DIRECTORY => Some(NodeKind::Directory(BTreeMap::new())),
+                // parse_entry has checked that the body is inside the archive.
+                MODE_FILE => Some(NodeKind::File(
+                    &archive[body_start..body_start + header.filesize as usize],
+                )),
+                _ => None,
+            };
+            match kind {
+                Some(kind) => initrd.insert(&header.name, kind),
+                None => log::debug!("initrd: {} skipped", header.name),
+            }
+            offset = next;
+        }
+    }
+
+    /// Add an archive entry at `path`, creating the directories above it.
+    /// Entries whose path escapes the archive or runs through a file are
+    /// dropped.
+    fn insert(&mut self, path: &str, kind: NodeKind) {
+        let components: Vec<&str> = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect();
+        let Some((name, parents)) = components.split_last() else {
+            return;
+        };
+        if components.contains(&"..") {
+            return;
+        }
+        let next_ino = &mut self.next_ino;
+        let mut allocate_ino = || {
+            *next_ino += 1;
+            *next_ino - 1
+        };
+        let mut dir = &mut self.root;
+        for &component in parents {
+            let NodeKind::Directory(children) = &mut dir.kind else {
+                return;
+            };
+            dir = children
+                .entry(String::from(component))
+                .or_insert_with(|| Node {
+                    ino: allocate_ino(),
+                    kind: NodeKind::Directory(BTreeMap::new()),
+                });
+        }
+        let NodeKind::Directory(children) = &mut dir.kind else {
+            return;
+        };
+        // A directory implied by an earlier file keeps its children.
+        if matches!(kind, NodeKind::Directory(_)) && children.contains_key(*name) {
+            return;
+        }
+        let ino = allocate_ino();
+        children.insert(String::from(*name), Node { ino, kind });
+    }
+
+    /// The node at `path`, relative to the mount point.
+    fn lookup(&self, path: &str) -> Option<&Node> {
+        let mut current = &self.root;
+        for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            let NodeKind::Directory(children) = &current.kind else {
+                return None;
+            };
+            current = children.get(component)?;
+        }
+        Some(current)
+    }
+
+    fn open_file(&mut self, fd: u32) -> Result<&mut OpenFile, FsError> {
+        self.open.get_mut(&fd).ok_or(FsError::InvalidFileDescriptor)
+    }
+}
+
+impl FileSystem for Initrd {
+    fn capabilities(&self) -> FileSystemCapabilities {
+        FileSystemCapabilities::new(true, false, false, false, false)
+    }
+
+    fn open(&mut self, path: &str, flags: u32) -> Option<FileDescriptor> {
+        let node = self.lookup(path)?;
+        let ino = node.ino;
+        let data = match node.kind {
+            NodeKind::File(data) => Some(data),
+            NodeKind::Directory(_) => None,
+        };
+        let fd = self.next_fd;
+        self.next_fd = self.next_fd.wrapping_add(1);
+        self.open.insert(fd, OpenFile { data, offset: 0 });
+        Some(FileDescriptor {
+            fd,
+            ino,
+            offset: 0,
+            flags,
+        })
+    }
+
+    fn read(&mut self, fd: u32, buf: &mut [u8]) -> Result<usize, FsError> {
+        let file = self.open_file(fd)?;
+        let data = file.data.ok_or(FsError::IsADirectory)?;
+        let offset = usize::try_from(file.offset).map_err(|_| FsError::InvalidSeek)?;
+        let available = data.get(offset..).unwrap_or_default();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        file.offset += n as u64;
+        Ok(n)
+    }
+
+    fn write(&mut self, _fd: u32, _data: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn close(&mut self, fd: u32) -> Result<(), FsError> {
+        self.open
+            .remove(&fd)
+            .map(drop)
+            .ok_or(FsError::InvalidFileDescriptor)
+    }
+
+    fn seek(&mut self, fd: u32, pos: u64) -> Result<(), FsError> {
+        self.open_file(fd)?.offset = pos;
+        Ok(())
+    }
+
+    fn position(&mut self, fd: u32) -> Result<u64, FsError> {
+        Ok(self.open_file(fd)?.offset)
+    }
+
+    fn size(&mut self, fd: u32) -> Result<u64, FsError> {
+        Ok(self.open_file(fd)?.data.map_or(0, |data| data.len() as u64))
+    }
+
+    fn create(&mut self, _path: &str, _kind: InodeType) -> Option<u64> {
+        None
+    }
+
+    fn mkdir(&mut self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn readdir(&mut self, path: &str) -> Result<Vec<VNode>, FsError> {
+        let node = self.lookup(path).ok_or(FsError::FileNotFound)?;
+        let NodeKind::Directory(children) = &node.kind else {
+            return Err(FsError::NotADirectory);
+        };
+        Ok(children
+            .iter()
+            .map(|(name, child)| VNode {
+                name: name.clone(),
+                size: child.size(),
+                is_dir: matches!(child.kind, NodeKind::Directory(_)),
+            })
+            .collect())
+    }
+
+    fn exists(&mut self, path: &str) -> bool {
+        self.lookup(path).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMLINK: u32 = 0o120000;
+
+    /// Append a `newc` entry to `archive`.
+    fn entry(archive: &mut Vec<u8>, name: &str, mode: u32, body: &[u8]) {
+        let fields = [
+            0,
+            mode,
+            0,
+            0,
+            1,
+            0,
+            body.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        archive.extend_from_slice(b"070701");
+        for field in fields {
+            archive.extend_from_slice(alloc::format!("{field:08x}").as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(body);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+
+    fn read_all(fs: &mut Initrd, path: &str) -> Vec<u8> {
+        let fd = fs.open(path, 0).unwrap().fd;
+        let mut buf = [0u8; 64];
+        let n = fs.read(fd, &mut buf).unwrap();
+        fs.close(fd).unwrap();
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn indexes_files_and_implied_directories() {
+        let mut archive = Vec::new();
+        entry(&mut archive, "./README", MODE_FILE | 0o644, b"read me\n");
+        entry(&mut archive, "bin/hello", MODE_FILE | 0o755, b"\x7fELF");
+        entry(&mut archive, "etc", MODE_DIRECTORY | 0o755, b"");
+        entry(&mut archive, "etc/motd", MODE_FILE | 0o644, b"welcome\n");
+        entry(&mut archive, "bin", MODE_DIRECTORY | 0o755, b"");
+        entry(&mut archive, "etc/link", SYMLINK | 0o777, b"motd");
+        entry(&mut archive, "../escape", MODE_FILE | 0o644, b"no");
+        entry(&mut archive, TRAILER, 0, b"");
+        let mut fs = Initrd::parse(Vec::leak(archive)).unwrap();
+
+        let root: Vec<(String, bool)> = fs
+            .readdir("/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.is_dir))
+            .collect();
+        assert_eq!(
+            root,
+            [
+                (String::from("README"), false),
+                (String::from("bin"), true),
+                (String::from("etc"), true),
+            ]
+        );
+        assert_eq!(read_all(&mut fs, "/etc/motd"), b"welcome\n");
+        assert_eq!(read_all(&mut fs, "bin/hello"), b"\x7fELF");
+        assert_eq!(fs.readdir("/bin").unwrap().len(), 1);
+        assert!(!fs.exists("/etc/link"));
+
+        let fd = fs.open("/README", 0).unwrap().fd;
+        assert_eq!(fs.size(fd), Ok(8));
+        assert_eq!(fs.write(fd, b"x"), Err(FsError::PermissionDenied));
+        let dir = fs.open("/etc", 0).unwrap().fd;
+        assert_eq!(fs.read(dir, &mut [0u8; 4]), Err(FsError::IsADirectory));
+        assert_eq!(fs.mkdir("/new"), Err(FsError::PermissionDenied));
+        assert!(fs.create("/new", InodeType::File).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_and_unterminated_archives() {
+        let mut archive = Vec::new();
+        entry(&mut archive, "motd", MODE_FILE | 0o644, b"welcome\n");
+        let unterminated = archive.clone();
+        entry(&mut archive, TRAILER, 0, b"");
+        let truncated = archive[..archive.len() - 8].to_vec();
+
+        assert!(Initrd::parse(Vec::leak(archive)).is_ok());
+        for bad in [
+            unterminated,
+            truncated,
+            b"not an archive".to_vec(),
+            Vec::new(),
+        ] {
+            assert_eq!(
+                Initrd::parse(Vec::leak(bad)).err(),
+                Some(FsError::InvalidInput)
+            );
+        }
+    }
+}
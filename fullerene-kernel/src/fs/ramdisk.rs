@@ -0,0 +1,212 @@
+//! Heap-backed RAM disk.
+//!
+//! A [`RamDisk`] is a fixed number of zero-filled blocks allocated at
+//! construction.  Clones share the same storage, so one handle can sit in
+//! the [`DeviceManager`](crate::hardware::device_manager::DeviceManager)
+//! while another is mounted through [`super::block::SectorAdapter`].
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use spin::Mutex;
+
+use petroleum::initializer::{BlockDevice, ErrorLogging, HardwareDevice, Initializable};
+use petroleum::{SystemError, SystemResult};
+
+#[derive(Clone)]
+pub struct RamDisk {
+    name: &'static str,
+    data: Arc<Mutex<Vec<u8>>>,
+    block_size: usize,
+    block_count: u64,
+    enabled: bool,
+}
+
+impl RamDisk {
+    /// Allocate `block_count` zeroed blocks of `block_size` bytes.
+    pub fn new(name: &'static str, block_size: usize, block_count: u64) -> SystemResult<Self> {
+        let len = usize::try_from(block_count)
+            .ok()
+            .and_then(|count| count.checked_mul(block_size))
+            .filter(|_| block_size > 0)
+            .ok_or(SystemError::InvalidArgument)?;
+        Ok(Self {
+            name,
+            data: Arc::new(Mutex::new(vec![0; len])),
+            block_size,
+            block_count,
+            enabled: false,
+        })
+    }
+
+    /// Byte range of block `lba`, if it exists and `buf_len` can hold it.
+    fn block_range(&self, lba: u64, buf_len: usize) -> SystemResult<Range<usize>> {
+        if lba >= self.block_count || buf_len < self.block_size {
+            return Err(SystemError::InvalidArgument);
+        }
+        let start = lba as usize * self.block_size;
+        Ok(start..start + self.block_size)
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, lba: u64, buf: &mut [u8]) -> SystemResult<()> {
+        let range = self.block_range(lba, buf.len())?;
+        buf[..self.block_size].copy_from_slice(&self.data.lock()[range]);
+        Ok(())
+    }
+
+    fn write_block(&self, lba: u64, buf: &[u8]) -> SystemResult<()> {
+        let range = self.block_range(lba, buf.len())?;
+        self.data.lock()[range].copy_from_slice(&buf[..self.block_size]);
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        self.block_count
+    }
+}
+
+impl Initializable for RamDisk {
+    fn init(&mut self) -> SystemResult<()> {
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn priority(&self) -> i32 {
+        50
+    }
+}
+
+impl ErrorLogging for RamDisk {
+    fn log_error(&self, error: &SystemError, context: &'static str) {
+        log::error!("{}: {}", context, error);
+    }
+
+    fn log_warning(&self, message: &'static str) {
+        log::warn!("{}", message);
+    }
+
+    fn log_info(&self, message: &'static str) {
+        log::info!("{}", message);
+    }
+
+    fn log_debug(&self, message: &'static str) {
+        log::debug!("{}", message);
+    }
+
+    fn log_trace(&self, message: &'static str) {
+        log::trace!("{}", message);
+    }
+}
+
+impl HardwareDevice for RamDisk {
+    fn device_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn device_type(&self) -> &'static str {
+        "Storage/RamDisk"
+    }
+
+    fn enable(&mut self) -> SystemResult<()> {
+        self.enabled = true;
+        Ok(())
+    }
+
+    fn disable(&mut self) -> SystemResult<()> {
+        self.enabled = false;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> SystemResult<()> {
+        self.data.lock().fill(0);
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Byte-addressed read; short at the end of the disk.
+    fn read(&mut self, address: usize, buffer: &mut [u8]) -> SystemResult<usize> {
+        let data = self.data.lock();
+        let src = data.get(address..).ok_or(SystemError::InvalidArgument)?;
+        let n = buffer.len().min(src.len());
+        buffer[..n].copy_from_slice(&src[..n]);
+        Ok(n)
+    }
+
+    /// Byte-addressed write; short at the end of the disk.
+    fn write(&mut self, address: usize, buffer: &[u8]) -> SystemResult<usize> {
+        let mut data = self.data.lock();
+        let dst = data
+            .get_mut(address..)
+            .ok_or(SystemError::InvalidArgument)?;
+        let n = buffer.len().min(dst.len());
+        dst[..n].copy_from_slice(&buffer[..n]);
+        Ok(n)
+    }
+}
+
+/// Create a RAM disk, register a handle with the global device manager and
+/// return another handle sharing the same storage.
+pub fn register_ramdisk(
+    name: &'static str,
+    block_size: usize,
+    block_count: u64,
+) -> SystemResult<RamDisk> {
+    let disk = RamDisk::new(name, block_size, block_count)?;
+    crate::hardware::device_manager::register_device(alloc::boxed::Box::new(disk.clone()))?;
+    Ok(disk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_read_back_what_was_written() {
+        let disk = RamDisk::new("test_ramdisk", 512, 8).unwrap();
+        let block = [0xA5u8; 512];
+        disk.write_block(7, &block).unwrap();
+
+        let mut out = [0u8; 512];
+        disk.read_block(7, &mut out).unwrap();
+        assert_eq!(out, block);
+        disk.read_block(6, &mut out).unwrap();
+        assert!(out.iter().all(|&b| b == 0));
+
+        // Clones share storage.
+        let mut handle = disk.clone();
+        let mut bytes = [0u8; 4];
+        assert_eq!(handle.read(7 * 512, &mut bytes), Ok(4));
+        assert_eq!(bytes, [0xA5; 4]);
+    }
+
+    #[test]
+    fn out_of_range_blocks_are_rejected() {
+        let disk = RamDisk::new("test_ramdisk", 512, 8).unwrap();
+        let mut buf = [0u8; 512];
+        assert_eq!(
+            disk.read_block(8, &mut buf),
+            Err(SystemError::InvalidArgument)
+        );
+        assert_eq!(
+            disk.write_block(u64::MAX, &buf),
+            Err(SystemError::InvalidArgument)
+        );
+        assert_eq!(
+            disk.read_block(0, &mut buf[..511]),
+            Err(SystemError::InvalidArgument)
+        );
+    }
+}
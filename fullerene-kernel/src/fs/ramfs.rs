@@ -0,0 +1,352 @@
+//! Writable in-memory filesystem for scratch files, mounted at `/ram`.
+//!
+//! Each inode sits behind its own [`Mutex`] and is shared through an
+//! [`Arc`]: directories hold their children by name and every open file
+//! holds the inode it was opened on.  Unlinking only removes the directory
+//! entry, so a file that is still open keeps its contents until the last
+//! descriptor on it is closed.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use genome::fs::FsError;
+use genome::vfs::{FileDescriptor, FileSystem, FileSystemCapabilities, InodeType, VNode};
+
+type NodeRef = Arc<Mutex<Node>>;
+
+struct Node {
+    ino: u64,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    File(Vec<u8>),
+    Directory(BTreeMap<String, NodeRef>),
+}
+
+impl Node {
+    fn size(&self) -> u64 {
+        match &self.kind {
+            NodeKind::File(data) => data.len() as u64,
+            NodeKind::Directory(_) => 0,
+        }
+    }
+}
+
+/// An open descriptor and the inode it keeps alive.
+struct OpenFile {
+    node: NodeRef,
+    offset: u64,
+}
+
+pub struct RamFs {
+    root: NodeRef,
+    next_ino: u64,
+    open: BTreeMap<u32, OpenFile>,
+    next_fd: u32,
+}
+
+impl RamFs {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(Mutex::new(Node {
+                ino: 1,
+                kind: NodeKind::Directory(BTreeMap::new()),
+            })),
+            next_ino: 2,
+            open: BTreeMap::new(),
+            next_fd: 0,
+        }
+    }
+
+    /// The inode at `path`, relative to the mount point.
+    fn lookup(&self, path: &str) -> Option<NodeRef> {
+        let mut ancestors = Vec::new();
+        let mut current = Arc::clone(&self.root);
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            match component {
+                "." => {}
+                ".." => current = ancestors.pop().unwrap_or_else(|| Arc::clone(&self.root)),
+                name => {
+                    let child = match &current.lock().kind {
+                        NodeKind::Directory(children) => Arc::clone(children.get(name)?),
+                        NodeKind::File(_) => return None,
+                    };
+                    ancestors.push(core::mem::replace(&mut current, child));
+                }
+            }
+        }
+        Some(current)
+    }
+
+    /// The directory that would hold `path` and the final component's name.
+    fn lookup_parent<'a>(&self, path: &'a str) -> Result<(NodeRef, &'a str), FsError> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FsError::InvalidPath);
+        }
+        let parent = self.lookup(parent).ok_or(FsError::FileNotFound)?;
+        Ok((parent, name))
+    }
+
+    fn open_file(&mut self, fd: u32) -> Result<&mut OpenFile, FsError> {
+        self.open.get_mut(&fd).ok_or(FsError::InvalidFileDescriptor)
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for RamFs {
+    fn capabilities(&self) -> FileSystemCapabilities {
+        FileSystemCapabilities::new(false, true, true, false, true)
+    }
+
+    fn open(&mut self, path: &str, flags: u32) -> Option<FileDescriptor> {
+        let node = self.lookup(path)?;
+        let ino = node.lock().ino;
+        let fd = self.next_fd;
+        self.next_fd = self.next_fd.wrapping_add(1);
+        self.open.insert(fd, OpenFile { node, offset: 0 });
+        Some(FileDescriptor {
+            fd,
+            ino,
+            offset: 0,
+            flags,
+        })
+    }
+
+    fn read(&mut self, fd: u32, buf: &mut [u8]) -> Result<usize, FsError> {
+        let file = self.open_file(fd)?;
+        let node = file.node.lock();
+        let NodeKind::File(data) = &node.kind else {
+            return Err(FsError::IsADirectory);
+        };
+        let offset = usize::try_from(file.offset).map_err(|_| FsError::InvalidSeek)?;
+        let available = data.get(offset..).unwrap_or_default();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        file.offset += n as u64;
+        Ok(n)
+    }
+
+    fn write(&mut self, fd: u32, data: &[u8]) -> Result<usize, FsError> {
+        let file = self.open_file(fd)?;
+        let mut node = file.node.lock();
+        let NodeKind::File(contents) = &mut node.kind else {
+            return Err(FsError::IsADirectory);
+        };
+        let start = usize::try_from(file.offset).map_err(|_| FsError::InvalidSeek)?;
+        let end = start.checked_add(data.len()).ok_or(FsError::InvalidInput)?;
+        // Writing past the end grows the file, zero-filling any gap.
+        if end > contents.len() {
+            contents
+                .try_reserve(end - contents.len())
+                .map_err(|_| FsError::DiskFull)?;
+            contents.resize(end, 0);
+        }
+        contents[start..end].copy_from_slice(data);
+        file.offset = end as u64;
+        Ok(data.len())
+    }
+
+    fn close(&mut self, fd: u32) -> Result<(), FsError> {
+        // Dropping the descriptor frees an unlinked inode once no other
+        // descriptor refers to it.
+        self.open
+            .remove(&fd)
+            .map(drop)
+            .ok_or(FsError::InvalidFileDescriptor)
+    }
+
+    fn seek(&mut self, fd: u32, pos: u64) -> Result<(), FsError> {
+        self.open_file(fd)?.offset = pos;
+        Ok(())
+    }
+
+    fn position(&mut self, fd: u32) -> Result<u64, FsError> {
+        Ok(self.open_file(fd)?.offset)
+    }
+
+    fn size(&mut self, fd: u32) -> Result<u64, FsError> {
+        Ok(self.open_file(fd)?.node.lock().size())
+    }
+
+    fn truncate(&mut self, fd: u32, len: u64) -> Result<(), FsError> {
+        let file = self.open_file(fd)?;
+        let mut node = file.node.lock();
+        let NodeKind::File(contents) = &mut node.kind else {
+            return Err(FsError::IsADirectory);
+        };
+        let len = usize::try_from(len).map_err(|_| FsError::InvalidInput)?;
+        if len > contents.len() {
+            contents
+                .try_reserve(len - contents.len())
+                .map_err(|_| FsError::DiskFull)?;
+        }
+        contents.resize(len, 0);
+        Ok(())
+    }
+
+    fn create(&mut self, path: &str, kind: InodeType) -> Option<u64> {
+        let kind = match kind {
+            InodeType::File => NodeKind::File(Vec::new()),
+            InodeType::Directory => NodeKind::Directory(BTreeMap::new()),
+            InodeType::Symlink => return None,
+        };
+        let (parent, name) = self.lookup_parent(path).ok()?;
+        let mut parent = parent.lock();
+        let NodeKind::Directory(children) = &mut parent.kind else {
+            return None;
+        };
+        if children.contains_key(name) {
+            return None;
+        }
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        children.insert(String::from(name), Arc::new(Mutex::new(Node { ino, kind })));
+        Some(ino)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<(), FsError> {
+        if path.trim_matches('/').is_empty() {
+            return Ok(());
+        }
+        let (parent, _) = self.lookup_parent(path)?;
+        if !matches!(parent.lock().kind, NodeKind::Directory(_)) {
+            return Err(FsError::NotADirectory);
+        }
+        self.create(path, InodeType::Directory)
+            .map(drop)
+            .ok_or(FsError::FileExists)
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<(), FsError> {
+        let (parent, name) = self.lookup_parent(path)?;
+        let mut parent = parent.lock();
+        let NodeKind::Directory(children) = &mut parent.kind else {
+            return Err(FsError::NotADirectory);
+        };
+        let child = children.get(name).ok_or(FsError::FileNotFound)?;
+        if let NodeKind::Directory(grandchildren) = &child.lock().kind
+            && !grandchildren.is_empty()
+        {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+        children.remove(name);
+        Ok(())
+    }
+
+    fn readdir(&mut self, path: &str) -> Result<Vec<VNode>, FsError> {
+        let node = self.lookup(path).ok_or(FsError::FileNotFound)?;
+        let node = node.lock();
+        let NodeKind::Directory(children) = &node.kind else {
+            return Err(FsError::NotADirectory);
+        };
+        Ok(children
+            .iter()
+            .map(|(name, child)| {
+                let child = child.lock();
+                VNode {
+                    name: name.clone(),
+                    size: child.size(),
+                    is_dir: matches!(child.kind, NodeKind::Directory(_)),
+                }
+            })
+            .collect())
+    }
+
+    fn exists(&mut self, path: &str) -> bool {
+        self.lookup(path).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_new(fs: &mut RamFs, path: &str, data: &[u8]) -> u32 {
+        fs.create(path, InodeType::File).unwrap();
+        let fd = fs.open(path, 0).unwrap().fd;
+        assert_eq!(fs.write(fd, data), Ok(data.len()));
+        fd
+    }
+
+    fn read_all(fs: &mut RamFs, path: &str) -> Vec<u8> {
+        let fd = fs.open(path, 0).unwrap().fd;
+        let mut buf = [0u8; 64];
+        let n = fs.read(fd, &mut buf).unwrap();
+        fs.close(fd).unwrap();
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn creates_files_in_nested_directories() {
+        let mut fs = RamFs::new();
+        fs.mkdir("/logs").unwrap();
+        let fd = write_new(&mut fs, "/logs/today.txt", b"hello");
+        fs.close(fd).unwrap();
+
+        assert_eq!(read_all(&mut fs, "logs/today.txt"), b"hello");
+        assert_eq!(read_all(&mut fs, "/logs/./../logs/today.txt"), b"hello");
+        let entries = fs.readdir("/logs").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].name.as_str(), entries[0].size),
+            ("today.txt", 5)
+        );
+        assert_eq!(fs.mkdir("/logs"), Err(FsError::FileExists));
+        assert_eq!(fs.mkdir("/missing/dir"), Err(FsError::FileNotFound));
+        assert!(fs.create("/logs/today.txt/x", InodeType::File).is_none());
+        assert_eq!(fs.unlink("/logs"), Err(FsError::DirectoryNotEmpty));
+    }
+
+    #[test]
+    fn writes_past_the_end_grow_the_file_and_truncate_shrinks_it() {
+        let mut fs = RamFs::new();
+        let fd = write_new(&mut fs, "/scratch", b"ab");
+        fs.seek(fd, 4).unwrap();
+        fs.write(fd, b"cd").unwrap();
+        assert_eq!(fs.size(fd), Ok(6));
+        assert_eq!(read_all(&mut fs, "/scratch"), b"ab\0\0cd");
+
+        fs.truncate(fd, 1).unwrap();
+        assert_eq!(read_all(&mut fs, "/scratch"), b"a");
+        // The descriptor's offset is left alone, so the next write leaves a
+        // hole again.
+        fs.write(fd, b"z").unwrap();
+        assert_eq!(read_all(&mut fs, "/scratch"), b"a\0\0\0\0\0z");
+    }
+
+    #[test]
+    fn unlinked_files_live_until_the_last_close() {
+        let mut fs = RamFs::new();
+        let writer = write_new(&mut fs, "/tmp.txt", b"kept");
+        let reader = fs.open("/tmp.txt", 0).unwrap().fd;
+        let node = Arc::downgrade(&fs.open[&reader].node);
+
+        fs.unlink("/tmp.txt").unwrap();
+        assert!(!fs.exists("/tmp.txt"));
+        assert!(fs.readdir("/").unwrap().is_empty());
+
+        let mut buf = [0u8; 8];
+        assert_eq!(fs.read(reader, &mut buf), Ok(4));
+        assert_eq!(&buf[..4], b"kept");
+        // A new file under the same name is a different inode.
+        let fd = write_new(&mut fs, "/tmp.txt", b"new");
+        fs.close(fd).unwrap();
+
+        fs.close(writer).unwrap();
+        assert!(node.upgrade().is_some());
+        fs.close(reader).unwrap();
+        assert!(node.upgrade().is_none());
+        assert_eq!(read_all(&mut fs, "/tmp.txt"), b"new");
+    }
+}
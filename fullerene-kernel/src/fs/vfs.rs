@@ -0,0 +1,66 @@
+//! Path-prefix mounts on the kernel VFS.
+//!
+//! Every shell and syscall path lookup goes through
+//! [`crate::contexts::vfs`], which normalizes `.`, `..` and trailing slashes
+//! once and then routes to the filesystem with the longest matching mount
+//! prefix.  This module is the entry point for attaching new filesystems to
+//! that namespace, e.g. the ramfs at `/ram` next to a FAT32 volume at `/boot`.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+
+use genome::fs::FsError;
+pub use genome::vfs::{FileSystem, VNode};
+use petroleum::initializer::BlockDevice;
+
+use super::block::LeasedBlocks;
+use super::fat32::Fat32;
+use crate::contexts::vfs;
+
+/// Mount `fs` at the absolute path `prefix`, creating the directory if it
+/// does not exist yet.  Fails with [`FsError::FileExists`] if something is
+/// already mounted there.
+pub fn mount(prefix: &str, fs: Box<dyn FileSystem>) -> Result<(), FsError> {
+    if !prefix.starts_with('/') {
+        return Err(FsError::InvalidPath);
+    }
+    if !vfs::exists(prefix) {
+        vfs::mkdir(prefix)?;
+    }
+    vfs::with_vfs(|ctx| ctx.mount(prefix, fs)).ok_or(FsError::PermissionDenied)?
+}
+
+/// Mount the FAT32 volume on `device` read-only at `prefix`.
+pub fn mount_fat32<D: BlockDevice + 'static>(prefix: &str, device: D) -> Result<(), FsError> {
+    mount(prefix, Box::new(Fat32::mount(device)?))
+}
+
+/// Where [`mount_boot_volume`] attaches the FAT32 volume.
+pub const BOOT_MOUNT: &str = "/boot";
+
+/// Mount the first registered block device that holds a FAT32 volume at
+/// [`BOOT_MOUNT`], returning its `/dev` name.  The device stays leased until
+/// it is unmounted; the others are handed back untouched.
+pub fn mount_boot_volume() -> Option<String> {
+    for name in crate::devfs::list_block_device_names() {
+        let Some(device) = crate::devfs::lease_block_device(&name) else {
+            continue;
+        };
+        match mount_fat32(BOOT_MOUNT, LeasedBlocks::new(device)) {
+            Ok(()) => {
+                vfs::with_vfs(|ctx| ctx.record_device_mount(&format!("/dev/{name}"), BOOT_MOUNT));
+                return Some(name);
+            }
+            Err(_) => {
+                crate::devfs::return_block_device_lease(&name);
+            }
+        }
+    }
+    None
+}
+
+/// Metadata for `path`, resolved against the working directory.
+pub fn stat(path: &str) -> Result<VNode, FsError> {
+    vfs::stat(path)
+}
@@ -2,7 +2,7 @@
 // single-threaded kernel initialization protected by GDT_INITIALIZED AtomicBool guard.
 // All accessor functions check initialization state before use.
 #![allow(static_mut_refs)]
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use petroleum::{debug_log_no_alloc, mem_debug};
 use x86_64::VirtAddr;
 use x86_64::instructions::tables::load_tss;
@@ -19,7 +19,8 @@ pub const NMI_IST_INDEX: u16 = 5;
 pub const MACHINE_CHECK_IST_INDEX: u16 = 6;
 
 pub const GDT_TSS_STACK_SIZE: usize = 4096 * 5;
-pub const GDT_TSS_STACK_COUNT: usize = 7;
+/// The seven IST stacks plus the dedicated RSP0 stack.
+pub const GDT_TSS_STACK_COUNT: usize = 8;
 pub const GDT_INIT_OVERHEAD: usize = GDT_TSS_STACK_COUNT * GDT_TSS_STACK_SIZE;
 
 #[allow(static_mut_refs)]
@@ -37,6 +38,9 @@ static mut USER_DATA_SELECTOR: Option<SegmentSelector> = None;
 #[allow(static_mut_refs)]
 static mut USER_CODE_SELECTOR: Option<SegmentSelector> = None;
 static GDT_INITIALIZED: AtomicBool = AtomicBool::new(false);
+/// Top of the dedicated RSP0 stack in the boot CPU's [`TssStacks`], used
+/// whenever the running process has no kernel stack of its own.
+static DEFAULT_KERNEL_STACK: AtomicU64 = AtomicU64::new(0);
 
 #[repr(align(4096))]
 struct EarlyGdtBuffer([u8; 0x20000]);
@@ -112,6 +116,33 @@ pub fn load() {
     }
 }
 
+/// Point RSP0 of the boot CPU's TSS at `top`, the stack that interrupts
+/// taken in ring 3 switch to.  A null `top` selects the dedicated stack from
+/// [`TssStacks`].
+///
+/// The scheduler calls this before resuming a user process, with that
+/// process's kernel stack.  Application processors keep the dedicated stack
+/// in their own TSS; they do not run processes.
+pub fn set_kernel_stack(top: VirtAddr) {
+    let top = if top.is_null() {
+        VirtAddr::new(DEFAULT_KERNEL_STACK.load(Ordering::Relaxed))
+    } else {
+        top
+    };
+    if top.is_null() {
+        return;
+    }
+    // The loaded TSS descriptor points into `TSS`, so the CPU sees the new
+    // value on its next privilege change.
+    unsafe {
+        if let Some(tss) = TSS.as_mut() {
+            tss.privilege_stack_table[0] = top;
+        }
+    }
+}
+
+/// Tops of the stacks a TSS hands out, carved from one contiguous region of
+/// [`GDT_TSS_STACK_COUNT`] stacks.
 pub struct TssStacks {
     pub double_fault: VirtAddr,
     pub timer: VirtAddr,
@@ -120,6 +151,9 @@ pub struct TssStacks {
     pub page_fault: VirtAddr,
     pub nmi: VirtAddr,
     pub machine_check: VirtAddr,
+    /// RSP0: where an interrupt or exception taken in ring 3 starts, unless
+    /// its gate names an IST stack.
+    pub privilege: VirtAddr,
 }
 
 impl TssStacks {
@@ -133,8 +167,23 @@ impl TssStacks {
             page_fault: VirtAddr::new(base.as_u64() + sz * 5),
             nmi: VirtAddr::new(base.as_u64() + sz * 6),
             machine_check: VirtAddr::new(base.as_u64() + sz * 7),
+            privilege: VirtAddr::new(base.as_u64() + sz * 8),
         }
     }
+
+    /// A TSS whose RSP0 and interrupt stack table point at these stacks.
+    pub fn tss(&self) -> TaskStateSegment {
+        let mut tss = TaskStateSegment::new();
+        tss.privilege_stack_table[0] = self.privilege;
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = self.double_fault;
+        tss.interrupt_stack_table[TIMER_IST_INDEX as usize] = self.timer;
+        tss.interrupt_stack_table[STACK_FAULT_IST_INDEX as usize] = self.stack_fault;
+        tss.interrupt_stack_table[GP_FAULT_IST_INDEX as usize] = self.gp_fault;
+        tss.interrupt_stack_table[PAGE_FAULT_IST_INDEX as usize] = self.page_fault;
+        tss.interrupt_stack_table[NMI_IST_INDEX as usize] = self.nmi;
+        tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] = self.machine_check;
+        tss
+    }
 }
 
 #[allow(static_mut_refs)]
@@ -190,16 +239,9 @@ pub fn init_with_stacks(stacks: TssStacks) {
     mem_debug!("GDT: Updating TSS stacks\n");
 
     unsafe {
-        let mut tss = TaskStateSegment::new();
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = stacks.double_fault;
-        tss.interrupt_stack_table[TIMER_IST_INDEX as usize] = stacks.timer;
-        tss.interrupt_stack_table[STACK_FAULT_IST_INDEX as usize] = stacks.stack_fault;
-        tss.interrupt_stack_table[GP_FAULT_IST_INDEX as usize] = stacks.gp_fault;
-        tss.interrupt_stack_table[PAGE_FAULT_IST_INDEX as usize] = stacks.page_fault;
-        tss.interrupt_stack_table[NMI_IST_INDEX as usize] = stacks.nmi;
-        tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] = stacks.machine_check;
-        TSS = Some(tss);
+        TSS = Some(stacks.tss());
     }
+    DEFAULT_KERNEL_STACK.store(stacks.privilege.as_u64(), Ordering::Relaxed);
 
     GDT_INITIALIZED.store(true, Ordering::SeqCst);
 }
@@ -213,19 +255,12 @@ pub fn init(heap_start: VirtAddr) -> VirtAddr {
     debug_log_no_alloc!("GDT: Initializing with heap at {}", heap_start.as_u64());
 
     let stacks = TssStacks::from_base(heap_start);
-    let new_heap_start = stacks.machine_check + GDT_TSS_STACK_SIZE as u64;
+    let new_heap_start = stacks.privilege;
 
     unsafe {
-        let mut tss = TaskStateSegment::new();
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = stacks.double_fault;
-        tss.interrupt_stack_table[TIMER_IST_INDEX as usize] = stacks.timer;
-        tss.interrupt_stack_table[STACK_FAULT_IST_INDEX as usize] = stacks.stack_fault;
-        tss.interrupt_stack_table[GP_FAULT_IST_INDEX as usize] = stacks.gp_fault;
-        tss.interrupt_stack_table[PAGE_FAULT_IST_INDEX as usize] = stacks.page_fault;
-        tss.interrupt_stack_table[NMI_IST_INDEX as usize] = stacks.nmi;
-        tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] = stacks.machine_check;
-        TSS = Some(tss);
+        TSS = Some(stacks.tss());
     }
+    DEFAULT_KERNEL_STACK.store(stacks.privilege.as_u64(), Ordering::Relaxed);
 
     #[cfg(not(target_os = "uefi"))]
     {
@@ -259,3 +294,31 @@ pub fn init(heap_start: VirtAddr) -> VirtAddr {
     GDT_INITIALIZED.store(true, Ordering::SeqCst);
     new_heap_start
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tss_stacks_tile_the_region() {
+        let base = VirtAddr::new(0x20_0000);
+        let tss = TssStacks::from_base(base).tss();
+        // Copy the tables out of the packed TSS before borrowing them.
+        let (ist, rsp) = (tss.interrupt_stack_table, tss.privilege_stack_table);
+        let mut tops: alloc::vec::Vec<u64> = ist[..7]
+            .iter()
+            .chain(&rsp[..1])
+            .map(|top| top.as_u64())
+            .collect();
+        tops.sort_unstable();
+        // One stack each, back to back, ending where the region ends.
+        let expected: alloc::vec::Vec<u64> = (1..=GDT_TSS_STACK_COUNT as u64)
+            .map(|i| base.as_u64() + i * GDT_TSS_STACK_SIZE as u64)
+            .collect();
+        assert_eq!(tops, expected);
+        assert_eq!(
+            *tops.last().unwrap(),
+            base.as_u64() + GDT_INIT_OVERHEAD as u64
+        );
+    }
+}
@@ -3,7 +3,7 @@
 //! Three probe strategies in priority order:
 //! 1. An immutable `.data`-section snapshot (stored by `efi_main_stage2`)
 //! 2. `KernelArgs` struct via the preserved virtual address
-//! 3. PCI BAR0 scan
+//! 3. PCI scan for a prefetchable VRAM BAR, verified by a write probe
 
 use petroleum::common::EfiGraphicsPixelFormat;
 use petroleum::graphics::boot_screen::BootFramebuffer;
@@ -150,6 +150,41 @@ pub fn direct_boot_framebuffer() -> Option<BootFramebuffer> {
     )
 }
 
+/// Mode assumed for a framebuffer found by [`FramebufferDiscovery::probe_pci`],
+/// which has no way to ask the device.
+const PCI_FALLBACK_WIDTH: u32 = 1280;
+const PCI_FALLBACK_HEIGHT: u32 = 800;
+
+/// Pattern written by [`probe_memory`]; its bits alternate so that neither
+/// an all-zeros nor an all-ones bus can echo it or its inverse.
+const PROBE_PATTERN: u32 = 0x5AA5_C33C;
+
+/// Whether `bar` looks like a VRAM aperture that can hold the fallback mode.
+fn is_plausible_framebuffer_bar(bar: &nitrogen::pci::PciBar) -> bool {
+    let needed = u64::from(PCI_FALLBACK_WIDTH) * u64::from(PCI_FALLBACK_HEIGHT) * 4;
+    !bar.is_io && bar.is_prefetchable && bar.address >= 0x10_0000 && u64::from(bar.size) >= needed
+}
+
+/// Check that `ptr` is backed by memory: it must read back [`PROBE_PATTERN`]
+/// and then its inverse.  The original value is restored whatever the
+/// outcome, so on a live framebuffer only one pixel flickers.
+///
+/// # Safety
+///
+/// `ptr` must be mapped, writable and aligned, and must not be a device
+/// register whose writes have side effects.
+unsafe fn probe_memory(ptr: *mut u32) -> bool {
+    unsafe {
+        let original = core::ptr::read_volatile(ptr);
+        core::ptr::write_volatile(ptr, PROBE_PATTERN);
+        let pattern_ok = core::ptr::read_volatile(ptr) == PROBE_PATTERN;
+        core::ptr::write_volatile(ptr, !PROBE_PATTERN);
+        let inverse_ok = core::ptr::read_volatile(ptr) == !PROBE_PATTERN;
+        core::ptr::write_volatile(ptr, original);
+        pattern_ok && inverse_ok
+    }
+}
+
 /// Discovery engine — tries each probe strategy in order.
 pub struct FramebufferDiscovery;
 
@@ -204,46 +239,65 @@ impl FramebufferDiscovery {
         })
     }
 
-    /// Probe PCI BAR0 for a VGA-compatible display controller.
+    /// Probe the BARs of VGA-compatible display controllers for a linear
+    /// framebuffer.
+    ///
+    /// Only prefetchable memory BARs large enough for the assumed mode are
+    /// considered, which is what VRAM apertures look like (BAR0 on Bochs/QEMU
+    /// VGA, BAR2 on Intel); register BARs are never touched.  Each candidate
+    /// must then pass [`probe_memory`] before it is reported.
     pub fn probe_pci(pci_devices: &[nitrogen::pci::PciDevice]) -> Option<FramebufferProbeResult> {
         petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[discovery] scanning PCI\n");
         for dev in pci_devices {
-            let vendor = nitrogen::pci::PciConfigSpace::read_config_word(dev.bus, dev.device, 0, 0);
-            if vendor == 0xFFFF || vendor == 0x0000 {
+            if dev.class_code != 0x03 || dev.subclass != 0x00 {
                 continue;
             }
-            let class =
-                nitrogen::pci::PciConfigSpace::read_config_byte(dev.bus, dev.device, 0, 0x0B);
-            let subclass =
-                nitrogen::pci::PciConfigSpace::read_config_byte(dev.bus, dev.device, 0, 0x0A);
-            if class == 0x03 && subclass == 0x00 {
-                // Intel GPUs (vendor 0x8086) use BAR2 for GTT aperture
-                // (physical framebuffer behind BAR2 offset + 0).  Other
-                // vendors use BAR0.
-                let bar_reg = if vendor == 0x8086 { 0x18 } else { 0x10 };
-                let bar = nitrogen::pci::PciConfigSpace::read_config_dword(
-                    dev.bus, dev.device, 0, bar_reg,
-                );
-                let fb_phys = if (bar & 0x6) == 0x4 {
-                    let bar_upper = nitrogen::pci::PciConfigSpace::read_config_dword(
-                        dev.bus,
-                        dev.device,
-                        0,
-                        bar_reg + 4,
-                    );
-                    ((bar_upper as u64) << 32) | ((bar & 0xFFFFFFF0) as u64)
-                } else {
-                    (bar & 0xFFFFFFF0) as u64
+            let mut index = 0;
+            while index < dev.max_bars() {
+                let Some(info) = dev.read_bar_info(index) else {
+                    index += 1;
+                    continue;
+                };
+                // The upper half of a 64-bit BAR is not a BAR of its own.
+                index += if info.is_64bit { 2 } else { 1 };
+                // Size only memory BARs that could be VRAM.
+                if info.is_io || !info.is_prefetchable {
+                    continue;
+                }
+                let Some(bar) = dev.get_bar_info(info.index) else {
+                    continue;
                 };
-                if fb_phys >= 0x100000 {
-                    return Some(FramebufferProbeResult {
-                        phys: fb_phys,
-                        width: 1280,
-                        height: 800,
-                        stride: 1280 * 4,
-                        pixel_format: EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor,
+                if !is_plausible_framebuffer_bar(&bar) {
+                    continue;
+                }
+                let direct_map_offset =
+                    petroleum::common::memory::get_physical_memory_offset() as u64;
+                let Some(va) = bar.address.checked_add(direct_map_offset) else {
+                    continue;
+                };
+                let mapped = x86_64::VirtAddr::try_new(va)
+                    .ok()
+                    .and_then(petroleum::common::memory::walk_page_table_for_flags)
+                    .is_some_and(|flags| {
+                        flags.contains(
+                            x86_64::structures::paging::PageTableFlags::PRESENT
+                                | x86_64::structures::paging::PageTableFlags::WRITABLE,
+                        )
                     });
+                if !mapped || !unsafe { probe_memory(va as *mut u32) } {
+                    petroleum::serial::serial_log(format_args!(
+                        "[discovery] BAR{} at 0x{:x} is not usable memory\n",
+                        bar.index, bar.address
+                    ));
+                    continue;
                 }
+                return Some(FramebufferProbeResult {
+                    phys: bar.address,
+                    width: PCI_FALLBACK_WIDTH,
+                    height: PCI_FALLBACK_HEIGHT,
+                    stride: PCI_FALLBACK_WIDTH * 4,
+                    pixel_format: EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor,
+                });
             }
         }
         None
@@ -259,7 +313,10 @@ impl FramebufferDiscovery {
 
 #[cfg(test)]
 mod tests {
-    use super::{BootFramebufferParams, BootSnapshotError, store_snapshot};
+    use super::{
+        BootFramebufferParams, BootSnapshotError, is_plausible_framebuffer_bar, probe_memory,
+        store_snapshot,
+    };
     use spin::Once;
 
     fn valid_params() -> BootFramebufferParams {
@@ -311,4 +368,41 @@ mod tests {
 
         assert!(params.probe_result().is_none());
     }
+
+    #[test]
+    fn pci_probe_only_considers_prefetchable_vram_bars() {
+        let vram = nitrogen::pci::PciBar {
+            index: 0,
+            address: 0xFD00_0000,
+            size: 16 << 20,
+            is_io: false,
+            is_64bit: false,
+            is_prefetchable: true,
+        };
+        assert!(is_plausible_framebuffer_bar(&vram));
+        for bar in [
+            nitrogen::pci::PciBar {
+                is_prefetchable: false,
+                ..vram
+            },
+            nitrogen::pci::PciBar {
+                is_io: true,
+                ..vram
+            },
+            nitrogen::pci::PciBar { size: 4096, ..vram },
+            nitrogen::pci::PciBar {
+                address: 0xA_0000,
+                ..vram
+            },
+        ] {
+            assert!(!is_plausible_framebuffer_bar(&bar), "{bar:?}");
+        }
+    }
+
+    #[test]
+    fn memory_probe_restores_the_original_value() {
+        let mut word = 0x1234_5678u32;
+        assert!(unsafe { probe_memory(&mut word) });
+        assert_eq!(word, 0x1234_5678);
+    }
 }
@@ -74,6 +74,26 @@ pub fn init_graphics() {
         );
     }
 
+    // `resolution=WxH` is what flasks asked QEMU's display device for;
+    // report when the firmware left the framebuffer in another mode.
+    if let Some((width, height)) = crate::boot::cmdline_param("resolution")
+        .and_then(petroleum::common::boot_params::parse_resolution)
+    {
+        match probe {
+            Some(ref p) if (p.width, p.height) != (width, height) => {
+                petroleum::serial::serial_log(format_args!(
+                    "[init_gfx] resolution {}x{} requested but the framebuffer is {}x{}\n",
+                    width, height, p.width, p.height
+                ));
+            }
+            Some(_) => {}
+            None => petroleum::serial::serial_log(format_args!(
+                "[init_gfx] resolution {}x{} requested but no framebuffer was found\n",
+                width, height
+            )),
+        }
+    }
+
     // ── Build renderer ──────────────────────────────────────────
     petroleum::write_serial_bytes(
         0x3F8,
@@ -88,6 +108,13 @@ pub fn init_graphics() {
         return;
     }
 
+    if crate::boot::cmdline_param("novga").is_some() {
+        petroleum::serial::serial_log(format_args!(
+            "[init_gfx] No GOP renderer and novga given; running headless.\n"
+        ));
+        return;
+    }
+
     // VGA text mode fallback.
     petroleum::serial::serial_log(format_args!(
         "[init_gfx] No GOP renderer available, falling back to VGA text mode.\n"
@@ -95,29 +122,43 @@ pub fn init_graphics() {
     let off = petroleum::common::memory::get_physical_memory_offset() as u64;
     let vga_phys = petroleum::page_table::constants::VGA_MEMORY_START;
     let vga_virt = vga_phys + off;
-    if let Some(mem) = crate::contexts::memory::get_memory().lock().as_mut() {
-        let _ = mem.map_page(
-            vga_virt as usize,
-            vga_phys as usize,
-            x86_64::structures::paging::PageTableFlags::NO_CACHE
-                | x86_64::structures::paging::PageTableFlags::PRESENT
-                | x86_64::structures::paging::PageTableFlags::WRITABLE
-                | x86_64::structures::paging::PageTableFlags::NO_EXECUTE,
-        );
-    } else {
-        let mut mm = crate::memory_management::get_memory_manager().lock();
-        let mm = mm.as_mut().unwrap();
-        let _ = mm.safe_map_page(
-            vga_virt as usize,
-            vga_phys as usize,
-            x86_64::structures::paging::PageTableFlags::NO_CACHE
-                | x86_64::structures::paging::PageTableFlags::PRESENT
-                | x86_64::structures::paging::PageTableFlags::WRITABLE
-                | x86_64::structures::paging::PageTableFlags::NO_EXECUTE,
-        );
+    // Map the whole text window: modes beyond 80x25 use more than a page.
+    for page in (0..petroleum::graphics::text::VGA_TEXT_WINDOW_BYTES as u64).step_by(4096) {
+        if let Some(mem) = crate::contexts::memory::get_memory().lock().as_mut() {
+            let _ = mem.map_page(
+                (vga_virt + page) as usize,
+                (vga_phys + page) as usize,
+                x86_64::structures::paging::PageTableFlags::NO_CACHE
+                    | x86_64::structures::paging::PageTableFlags::PRESENT
+                    | x86_64::structures::paging::PageTableFlags::WRITABLE
+                    | x86_64::structures::paging::PageTableFlags::NO_EXECUTE,
+            );
+        } else {
+            let mut mm = crate::memory_management::get_memory_manager().lock();
+            let mm = mm.as_mut().unwrap();
+            let _ = mm.safe_map_page(
+                (vga_virt + page) as usize,
+                (vga_phys + page) as usize,
+                x86_64::structures::paging::PageTableFlags::NO_CACHE
+                    | x86_64::structures::paging::PageTableFlags::PRESENT
+                    | x86_64::structures::paging::PageTableFlags::WRITABLE
+                    | x86_64::structures::paging::PageTableFlags::NO_EXECUTE,
+            );
+        }
     }
     let mut vga = petroleum::graphics::text::VgaBuffer::with_address(vga_virt as usize);
     vga.enable();
+    match petroleum::graphics::text::TextModeGeometry::detect() {
+        Some(geometry) if vga.reconfigure(geometry.rows, geometry.cols).is_ok() => {
+            petroleum::serial::serial_log(format_args!(
+                "[init_gfx] VGA text mode {}x{}, {}x{} font\n",
+                geometry.cols, geometry.rows, geometry.char_width, geometry.char_height
+            ));
+        }
+        _ => petroleum::serial::serial_log(format_args!(
+            "[init_gfx] VGA text mode unreadable, assuming 80x25\n"
+        )),
+    }
     petroleum::graphics::Console::clear(&mut vga);
     let _ = core::fmt::write(&mut vga, format_args!("fullerene kernel — VGA text mode\n"));
     with_kernel_mut(|k| k.framebuffer.vga_console = Some(vga));
@@ -126,11 +126,29 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
         crate::klog::write_bytes(msg.as_bytes());
     });
     let _ = petroleum::common::logging::init_global_logger();
-    log::set_max_level(log::LevelFilter::Info);
+    let level = crate::boot::cmdline_param("loglevel")
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(log::LevelFilter::Info);
+    petroleum::common::logging::set_max_level(level);
+    petroleum::common::logging::replay_early_log();
+    log::debug!("Kernel command line: {:?}", crate::boot::cmdline());
     let common_steps = [
         petroleum::init_step!("Interrupts", || {
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[init] Interrupts step start\n");
+            petroleum::serial::serial_log(format_args!(
+                "CPU features: {}\n",
+                petroleum::hardware::cpuid::features()
+            ));
+            // GS must point at the BSP's block before the first syscall.
+            crate::percpu::init_bsp();
             crate::interrupts::init();
+            crate::context_switch::init();
+            let protection = petroleum::common::memory::enable_user_access_protection();
+            petroleum::serial::serial_log(format_args!(
+                "SMEP {}, SMAP {}\n",
+                if protection.smep { "on" } else { "unavailable" },
+                if protection.smap { "on" } else { "unavailable" }
+            ));
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[init] Interrupts step done\n");
             crate::boot_stage!(BootStage::InterruptsReady);
             Ok(())
@@ -219,10 +237,33 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
                         "MADT: discovered {} processor entries",
                         madt.processors.len()
                     );
+                    log::info!(
+                        "MADT: Local APIC at {:#x}, {} I/O APIC(s), legacy IRQs on {:#x?}",
+                        madt.local_apic_base(),
+                        madt.io_apics.len(),
+                        madt.legacy_io_apic_base()
+                    );
+                    crate::interrupts::apic::set_madt_bases(&madt);
                     crate::smp::configure(madt);
                 } else {
                     log::warn!("MADT: processor topology unavailable; using BSP only");
                 }
+                match mgr.parse_fadt() {
+                    Some(fadt) => match mgr.parse_s5(&fadt) {
+                        Some(s5) => {
+                            log::info!(
+                                "FADT: PM1a_CNT={:#x} PM1b_CNT={:#x}, S5 sleep types {}/{}",
+                                fadt.pm1a_control,
+                                fadt.pm1b_control,
+                                s5.a,
+                                s5.b
+                            );
+                            crate::power::configure_acpi(crate::power::AcpiPowerOff { fadt, s5 });
+                        }
+                        None => log::warn!("DSDT: no \\_S5 package — ACPI power-off unavailable"),
+                    },
+                    None => log::warn!("FADT: table not found — ACPI power-off unavailable"),
+                }
                 if let Some(mcfg) = mgr.parse_mcfg() {
                     let phys_off = petroleum::common::memory::get_physical_memory_offset() as u64;
                     log::info!(
@@ -279,6 +320,35 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] devfs done\n");
             Ok(())
         }),
+        petroleum::init_step!("ramfs", || {
+            crate::fs::vfs::mount(
+                "/ram",
+                alloc::boxed::Box::new(crate::fs::ramfs::RamFs::new()),
+            )
+            .map_err(|_| petroleum::SystemError::DeviceError)?;
+            petroleum::serial::serial_log(format_args!("ramfs mounted at /ram\n"));
+            Ok(())
+        }),
+        petroleum::init_step!("initrd", || {
+            // Booting without an initrd, or with one that does not parse,
+            // leaves /init unmounted rather than failing the boot.
+            let Some(archive) = crate::boot::initrd() else {
+                petroleum::serial::serial_log(format_args!("initrd: none loaded\n"));
+                return Ok(());
+            };
+            match crate::fs::initrd::Initrd::parse(archive) {
+                Ok(initrd) => {
+                    crate::fs::vfs::mount("/init", alloc::boxed::Box::new(initrd))
+                        .map_err(|_| petroleum::SystemError::DeviceError)?;
+                    petroleum::serial::serial_log(format_args!(
+                        "initrd ({} bytes) mounted at /init\n",
+                        archive.len()
+                    ));
+                }
+                Err(err) => log::warn!("initrd: not a CPIO newc archive: {}", err),
+            }
+            Ok(())
+        }),
         petroleum::init_step!("device_probe", || {
             crate::boot_stage::draw_boot_label(b"DEVICE PROBE");
             crate::boot_stage::draw_step_hint(b"pci_scan");
@@ -370,6 +440,7 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
         petroleum::init_step!("PS2 Keyboard", || {
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] ps2_kbd start\n");
             nitrogen::ps2::keyboard::init_keyboard();
+            nitrogen::ps2::keyboard::detect_scancode_set();
             petroleum::serial::serial_log(format_args!("PS/2 keyboard initialised\n"));
             crate::boot_stage!(BootStage::InputReady);
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] ps2_kbd done\n");
@@ -418,6 +489,9 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
             crate::hardware::device_manager::init_device_manager()
                 .map_err(|_| petroleum::SystemError::DeviceError)?;
             petroleum::serial::serial_log(format_args!("Device manager initialised\n"));
+            if let Err(e) = crate::fs::ramdisk::register_ramdisk("ramdisk0", 512, 512) {
+                log::warn!("ramdisk0 not registered: {:?}", e);
+            }
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] device_mgr done\n");
             Ok(())
         }),
@@ -432,6 +506,24 @@ pub fn init_common(_physical_memory_offset: x86_64::VirtAddr) {
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] wifi done\n");
             Ok(())
         }),
+        petroleum::init_step!("TSC", || {
+            use petroleum::hardware::tsc;
+            match tsc::calibrate_tsc_hz() {
+                Some(hz) if tsc::is_invariant() => log::info!("TSC: {} kHz", hz / 1000),
+                Some(hz) => log::warn!(
+                    "TSC: {} kHz, not invariant; timings drift with CPU frequency",
+                    hz / 1000
+                ),
+                None if !petroleum::hardware::cpuid::features().tsc => {
+                    log::warn!("TSC: not supported by this CPU; timings are unavailable")
+                }
+                None => log::warn!(
+                    "TSC: PIT calibration failed, assuming {} MHz",
+                    tsc::FALLBACK_TSC_HZ / 1_000_000
+                ),
+            }
+            Ok(())
+        }),
         petroleum::init_step!("gui", || {
             petroleum::write_serial_bytes(0x3F8, 0x3FD, b"[step] gui start\n");
             crate::boot_stage::draw_boot_label(b"DESKTOP SERVICES");
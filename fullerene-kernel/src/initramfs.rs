@@ -47,14 +47,14 @@ fn align4(n: usize) -> usize {
 /// stored; the remaining fields are kept for future use (e.g. setting
 /// permissions).
 #[allow(dead_code)]
-struct CpioHeader {
-    name: String,
-    mode: u32,
+pub(crate) struct CpioHeader {
+    pub(crate) name: String,
+    pub(crate) mode: u32,
     uid: u32,
     gid: u32,
     nlink: u32,
     mtime: u64,
-    filesize: u64,
+    pub(crate) filesize: u64,
     devmajor: u32,
     devminor: u32,
     rdevmajor: u32,
@@ -64,7 +64,7 @@ struct CpioHeader {
 /// Parse a single CPIO entry header + body from `data` at `offset`.
 /// Returns `(header, body_offset, next_offset)` or `None` if the archive
 /// is malformed.
-fn parse_entry(data: &[u8], offset: usize) -> Option<(CpioHeader, usize, usize)> {
+pub(crate) fn parse_entry(data: &[u8], offset: usize) -> Option<(CpioHeader, usize, usize)> {
     if offset + 110 > data.len() {
         return None;
     }
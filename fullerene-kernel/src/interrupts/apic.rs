@@ -3,6 +3,7 @@
 //! This module provides APIC initialization and management functions.
 //! All unsafe volatile/port I/O is encapsulated in `nitrogen::apic_controller::ApicController`.
 
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use nitrogen::apic::{ApicFlags, ApicOffsets, IO_APIC_BASE};
 use nitrogen::apic_controller::ApicController;
 use nitrogen::mmio;
@@ -15,6 +16,38 @@ use x86_64::registers::model_specific::Msr;
 pub const TIMER_INTERRUPT_INDEX: u32 = 32;
 pub const KEYBOARD_INTERRUPT_INDEX: u32 = 33;
 pub const MOUSE_INTERRUPT_INDEX: u32 = 44;
+pub const SERIAL_INTERRUPT_INDEX: u32 = 36;
+
+/// ISA IRQ line of COM1.
+const COM1_IRQ: u8 = 4;
+
+/// Timer rate while processes are runnable.
+pub const ACTIVE_TIMER_HZ: u32 = 1000;
+/// Timer rate while only the idle loop has work.
+pub const IDLE_TIMER_HZ: u32 = 100;
+/// Upper bound for [`set_timer_hz`]; faster rates spend most of the CPU in
+/// the timer handler.
+pub const MAX_TIMER_HZ: u32 = 10_000;
+
+/// Divide-configuration value for divide-by-16.
+const TIMER_DIVIDE_BY_16: u32 = 0x3;
+/// Length of the calibration window.
+const CALIBRATION_MS: u32 = 10;
+/// Timer counts per millisecond assumed if calibration fails (100 MHz bus).
+const FALLBACK_TICKS_PER_MS: u32 = 6_250;
+
+/// Calibrated APIC timer counts per millisecond at divide-by-16.
+static TIMER_TICKS_PER_MS: AtomicU32 = AtomicU32::new(0);
+/// Current timer rate; 0 while the timer is stopped.
+static TIMER_HZ: AtomicU32 = AtomicU32::new(0);
+
+/// Architectural Local APIC base, used when nothing better is known.
+const DEFAULT_LAPIC_BASE: u64 = 0xFEE0_0000;
+
+/// Physical Local APIC base reported by the MADT; 0 until it is parsed.
+static MADT_LAPIC_BASE: AtomicU64 = AtomicU64::new(0);
+/// Physical base of the legacy-IRQ I/O APIC from the MADT; 0 until parsed.
+static MADT_IOAPIC_BASE: AtomicU64 = AtomicU64::new(0);
 
 /// Global APIC controller instance.
 ///
@@ -32,6 +65,52 @@ fn get_apic_base_phys() -> Option<u64> {
     }
 }
 
+/// Record the APIC bases firmware reports in the MADT.
+///
+/// Called once ACPI is parsed, which is after the early APIC setup but
+/// before [`init_apic`]; `init_apic` then rebuilds the controller if the
+/// firmware addresses differ from the defaults it was created with.
+pub fn set_madt_bases(madt: &nitrogen::acpi::madt::MadtInfo) {
+    MADT_LAPIC_BASE.store(madt.local_apic_base(), Ordering::Relaxed);
+    if let Some(io_apic) = madt.legacy_io_apic_base() {
+        MADT_IOAPIC_BASE.store(io_apic, Ordering::Relaxed);
+    }
+}
+
+/// Physical Local APIC base: from the MADT, else the IA32_APIC_BASE MSR,
+/// else the architectural default.
+pub fn local_apic_base_phys() -> u64 {
+    match MADT_LAPIC_BASE.load(Ordering::Relaxed) {
+        0 => get_apic_base_phys().unwrap_or(DEFAULT_LAPIC_BASE),
+        base => base,
+    }
+}
+
+/// Physical I/O APIC base: from the MADT, else the usual default.
+fn io_apic_base_phys() -> u64 {
+    match MADT_IOAPIC_BASE.load(Ordering::Relaxed) {
+        0 => IO_APIC_BASE,
+        base => base,
+    }
+}
+
+/// Build a controller for the current APIC bases, or `None` if the Local
+/// APIC base does not translate to a usable higher-half address.
+fn build_controller() -> Option<ApicController> {
+    let lapic_virt = phys_to_virt(local_apic_base_phys());
+    let ioapic_virt = phys_to_virt(io_apic_base_phys());
+    if lapic_virt < 0xFFFF_8000_0000_0000 || (lapic_virt & 0xFFF) != 0 {
+        petroleum::serial::serial_log(format_args!(
+            "ERROR: [apic] Invalid APIC base address {:#x} — MMIO mapping may be missing\n",
+            lapic_virt
+        ));
+        return None;
+    }
+    // SAFETY: Addresses validated above; physical memory, MMIO included,
+    // is mapped at the higher-half offset by the bootloader.
+    Some(unsafe { ApicController::new(lapic_virt, ioapic_virt) })
+}
+
 /// Compute the higher-half virtual address from a physical address.
 fn phys_to_virt(phys: u64) -> u64 {
     phys + petroleum::common::uefi::PHYSICAL_MEMORY_OFFSET_BASE as u64
@@ -48,7 +127,7 @@ pub fn preinit_apic_controller(lapic_virt: u64) {
         reset_mutex_lock(&APIC_CONTROLLER);
     }
 
-    let ioapic_virt = phys_to_virt(IO_APIC_BASE);
+    let ioapic_virt = phys_to_virt(io_apic_base_phys());
 
     // SAFETY: The caller guarantees that lapic_virt and ioapic_virt point
     // to valid, mapped MMIO regions in the higher half.
@@ -85,21 +164,9 @@ pub fn init_apic_hw_only() {
     // create one now using the MSR-discovered physical address.
     let mut guard = APIC_CONTROLLER.lock();
     if guard.is_none() {
-        let phys = get_apic_base_phys().unwrap_or(0xFEE00000);
-        let lapic_virt = phys_to_virt(phys);
-        let ioapic_virt = phys_to_virt(IO_APIC_BASE);
-
-        if lapic_virt >= 0xFFFF_8000_0000_0000 && (lapic_virt & 0xFFF) == 0 {
-            // SAFETY: Addresses validated above; MMIO regions are identity-mapped
-            // in the higher half by the bootloader.
-            let ctrl = unsafe { ApicController::new(lapic_virt, ioapic_virt) };
-            *guard = Some(ctrl);
-        } else {
-            petroleum::serial::serial_log(format_args!(
-                "[init_apic_hw_only] Invalid APIC base {:#x}, skipping\n",
-                lapic_virt
-            ));
-            return;
+        match build_controller() {
+            Some(ctrl) => *guard = Some(ctrl),
+            None => return,
         }
     }
 
@@ -125,22 +192,17 @@ pub fn init_apic_hw_only() {
 pub fn init_apic() {
     petroleum::serial::serial_log(format_args!("Initializing APIC...\n"));
 
-    // Ensure the controller exists (may have been created by preinit or hw_only).
+    // Ensure the controller exists (may have been created by preinit or
+    // hw_only) and uses the bases firmware reported since then.
     let mut guard = APIC_CONTROLLER.lock();
-    if guard.is_none() {
-        let phys = get_apic_base_phys().unwrap_or(0xFEE00000);
-        let lapic_virt = phys_to_virt(phys);
-        let ioapic_virt = phys_to_virt(IO_APIC_BASE);
-
-        if lapic_virt >= 0xFFFF_8000_0000_0000 && (lapic_virt & 0xFFF) == 0 {
-            let ctrl = unsafe { ApicController::new(lapic_virt, ioapic_virt) };
-            *guard = Some(ctrl);
-        } else {
-            petroleum::serial::serial_log(format_args!(
-                "ERROR: [init_apic] Invalid APIC base address {:#x} — MMIO mapping may be missing\n",
-                lapic_virt
-            ));
-            return;
+    let stale = guard.as_ref().is_none_or(|ctrl| {
+        ctrl.lapic_base() != phys_to_virt(local_apic_base_phys())
+            || ctrl.ioapic_base() != phys_to_virt(io_apic_base_phys())
+    });
+    if stale {
+        match build_controller() {
+            Some(ctrl) => *guard = Some(ctrl),
+            None => return,
         }
     }
 
@@ -153,27 +215,33 @@ pub fn init_apic() {
 
         petroleum::serial::serial_log(format_args!("APIC LVT entries masked.\n"));
 
-        // Configure timer: periodic, unmasked, divide-by-16, ~1ms initial count.
-        // Note: on real hardware the actual frequency depends on the bus clock;
-        // the scheduler hlt() loop is interrupt-driven so precise timing isn't
-        // critical — any periodic tick prevents the permanent hang.
+        // Configure timer: periodic, unmasked, divide-by-16, at the active
+        // rate measured against a reference clock.
+        let ticks_per_ms = calibrate_with(ctrl);
+        let initial_count = timer_initial_count(ticks_per_ms, ACTIVE_TIMER_HZ);
         ctrl.configure_timer(
             TIMER_INTERRUPT_INDEX,
             ApicFlags::TIMER_PERIODIC,
-            1_000_000,
-            0x3,
+            initial_count,
+            TIMER_DIVIDE_BY_16,
         );
+        TIMER_HZ.store(ACTIVE_TIMER_HZ, Ordering::Relaxed);
 
         petroleum::serial::serial_log(format_args!(
-            "APIC timer configured (periodic, div=16, initial_count=1000000).\n"
+            "APIC timer configured (periodic, div=16, {} Hz, initial_count={}).\n",
+            ACTIVE_TIMER_HZ, initial_count
         ));
 
         // Configure I/O APIC for legacy IRQs.
         ctrl.configure_legacy_irqs(KEYBOARD_INTERRUPT_INDEX as u8, MOUSE_INTERRUPT_INDEX as u8);
 
+        // COM1 receive (IRQ 4) drives the shell over the serial console.
+        ctrl.route_legacy_irq(COM1_IRQ, SERIAL_INTERRUPT_INDEX as u8);
+        petroleum::serial::com1_enable_receive_interrupt();
+
         petroleum::serial::serial_log(format_args!(
-            "I/O APIC legacy IRQs configured (keyboard={}, mouse={}).\n",
-            KEYBOARD_INTERRUPT_INDEX, MOUSE_INTERRUPT_INDEX
+            "I/O APIC legacy IRQs configured (keyboard={}, mouse={}, serial={}).\n",
+            KEYBOARD_INTERRUPT_INDEX, MOUSE_INTERRUPT_INDEX, SERIAL_INTERRUPT_INDEX
         ));
     }
 
@@ -181,6 +249,105 @@ pub fn init_apic() {
     setup_syscall();
 }
 
+// ── Timer rate ──────────────────────────────────────────────────
+
+/// Initial count that makes the timer fire `hz` times per second.
+///
+/// Never returns 0: writing 0 to the initial-count register stops the
+/// timer, which would leave the scheduler's `hlt` waiting forever.
+fn timer_initial_count(ticks_per_ms: u32, hz: u32) -> u32 {
+    let hz = u64::from(hz.clamp(1, MAX_TIMER_HZ));
+    (u64::from(ticks_per_ms) * 1000 / hz).clamp(1, u64::from(u32::MAX)) as u32
+}
+
+/// Measure the APIC timer against PIT channel 2, or the TSC if there is
+/// no PIT, and record the result.  The timer is left stopped.
+fn calibrate_with(ctrl: &ApicController) -> u32 {
+    let ticks_per_ms = instructions::interrupts::without_interrupts(|| {
+        ctrl.configure_timer(
+            TIMER_INTERRUPT_INDEX,
+            ApicFlags::TIMER_ONESHOT | ApicFlags::TIMER_MASKED,
+            u32::MAX,
+            TIMER_DIVIDE_BY_16,
+        );
+        let reference = if ApicController::pit_wait_us(CALIBRATION_MS * 1000) {
+            "PIT"
+        } else {
+            nitrogen::timing::delay_ms(u64::from(CALIBRATION_MS));
+            "TSC"
+        };
+        let elapsed = u32::MAX - ctrl.timer_current_count();
+        ctrl.lapic_write(ApicOffsets::TMRINITCNT, 0);
+
+        let ticks_per_ms = elapsed / CALIBRATION_MS;
+        petroleum::serial::serial_log(format_args!(
+            "APIC timer calibrated against {}: {} counts/ms at div=16\n",
+            reference, ticks_per_ms
+        ));
+        ticks_per_ms
+    });
+    let ticks_per_ms = if ticks_per_ms == 0 {
+        FALLBACK_TICKS_PER_MS
+    } else {
+        ticks_per_ms
+    };
+    TIMER_TICKS_PER_MS.store(ticks_per_ms, Ordering::Relaxed);
+    ticks_per_ms
+}
+
+/// Re-measure the APIC timer frequency and return the counts per
+/// millisecond at divide-by-16.
+///
+/// Stops the timer for the ~10 ms measurement and restarts it at the
+/// previous rate afterwards.
+pub fn calibrate() -> u32 {
+    // The timer handler takes the controller lock for its EOI.
+    instructions::interrupts::without_interrupts(|| {
+        let guard = APIC_CONTROLLER.lock();
+        let Some(ref ctrl) = *guard else {
+            return 0;
+        };
+        let lvt = ctrl.lapic_read(ApicOffsets::LVT_TIMER);
+        let ticks_per_ms = calibrate_with(ctrl);
+        let hz = TIMER_HZ.load(Ordering::Relaxed);
+        if hz != 0 {
+            ctrl.lapic_write(
+                ApicOffsets::TMRINITCNT,
+                timer_initial_count(ticks_per_ms, hz),
+            );
+            ctrl.lapic_write(ApicOffsets::LVT_TIMER, lvt);
+        }
+        ticks_per_ms
+    })
+}
+
+/// Run the periodic timer at `hz` interrupts per second, clamped to
+/// `1..=MAX_TIMER_HZ`.
+///
+/// Does nothing before [`init_apic`] has started the timer or if the rate
+/// is unchanged.
+pub fn set_timer_hz(hz: u32) {
+    let hz = hz.clamp(1, MAX_TIMER_HZ);
+    let ticks_per_ms = TIMER_TICKS_PER_MS.load(Ordering::Relaxed);
+    if ticks_per_ms == 0 || TIMER_HZ.load(Ordering::Relaxed) == hz {
+        return;
+    }
+    instructions::interrupts::without_interrupts(|| {
+        if let Some(ref ctrl) = *APIC_CONTROLLER.lock() {
+            ctrl.lapic_write(
+                ApicOffsets::TMRINITCNT,
+                timer_initial_count(ticks_per_ms, hz),
+            );
+            TIMER_HZ.store(hz, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Current timer rate in Hz, or 0 if the timer has not been started.
+pub fn timer_hz() -> u32 {
+    TIMER_HZ.load(Ordering::Relaxed)
+}
+
 // ── MMIO NMI watchdog timer switching ───────────────────────────
 
 const WATCHDOG_NMI_INITIAL_COUNT: u32 = 30_000_000; // ~4.8s at 100MHz bus /16 div
@@ -239,3 +406,22 @@ pub unsafe fn reset_apic_controller_lock() {
 pub fn register_mmio_watchdog() {
     mmio::register_watchdog_timer_callbacks(arm_watchdog_timer_impl, restore_watchdog_timer_impl);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_count_is_never_zero() {
+        assert_eq!(timer_initial_count(62_500, 1000), 62_500);
+        assert_eq!(timer_initial_count(62_500, 100), 625_000);
+        // A slow bus at a fast rate would round down to 0 and stop the timer.
+        assert_eq!(timer_initial_count(1, MAX_TIMER_HZ), 1);
+        assert_eq!(timer_initial_count(0, 1000), 1);
+        assert_eq!(
+            timer_initial_count(62_500, 0),
+            timer_initial_count(62_500, 1)
+        );
+        assert_eq!(timer_initial_count(u32::MAX, 1), u32::MAX);
+    }
+}
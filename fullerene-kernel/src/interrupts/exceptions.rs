@@ -1,7 +1,10 @@
 //! CPU exception handlers with recovery mechanism
 
+use crate::percpu::KernelGs;
 use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
 use x86_64::registers::control::Cr2;
+use x86_64::registers::rflags::RFlags;
 use x86_64::structures::idt::{InterruptStackFrame, InterruptStackFrameValue, PageFaultErrorCode};
 
 // ── Raw serial output (lock-free) ──────────────────────────────
@@ -10,12 +13,8 @@ struct RawSerialWriter;
 
 impl Write for RawSerialWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for &b in s.as_bytes() {
-            while unsafe { core::ptr::read_volatile(0x3FD as *const u8) } & 0x20 == 0 {
-                core::hint::spin_loop();
-            }
-            unsafe { core::ptr::write_volatile(0x3F8 as *mut u8, b) };
-        }
+        // COM1 through port I/O; the UART registers are not memory mapped.
+        petroleum::write_serial_bytes(0x3F8, 0x3FD, s.as_bytes());
         Ok(())
     }
 }
@@ -65,6 +64,49 @@ fn exception_name(vector: u8) -> &'static str {
     }
 }
 
+/// Selector error code pushed by #TS, #NP, #SS and #GP: bit 0 marks an
+/// event external to the program, bit 1 an IDT index, otherwise bit 2
+/// picks the LDT over the GDT; bits 3–15 are the descriptor index.
+struct SelectorErrorCode(u64);
+
+impl SelectorErrorCode {
+    /// The decoded form of `error_code` if `vector` pushes a selector code.
+    fn for_vector(vector: u8, error_code: u64) -> Option<Self> {
+        matches!(vector, 10..=13).then_some(Self(error_code))
+    }
+
+    fn is_external(&self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    fn table(&self) -> &'static str {
+        if self.0 & 2 != 0 {
+            "IDT"
+        } else if self.0 & 4 != 0 {
+            "LDT"
+        } else {
+            "GDT"
+        }
+    }
+
+    fn index(&self) -> u64 {
+        (self.0 >> 3) & 0x1FFF
+    }
+}
+
+impl core::fmt::Display for SelectorErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.0 & 0xFFFF == 0 {
+            return f.write_str("no selector");
+        }
+        write!(f, "{} index {}", self.table(), self.index())?;
+        if self.is_external() {
+            f.write_str(", external")?;
+        }
+        Ok(())
+    }
+}
+
 // ── Safe halt ──────────────────────────────────────────────────
 
 fn safe_halt() -> ! {
@@ -88,7 +130,7 @@ fn kernel_fault_halt(frame: &InterruptStackFrame, name: &str, extra: &str) -> !
     collector.capture();
     raw_log!("Backtrace:\n");
     for (i, entry) in collector.entries().iter().enumerate() {
-        raw_log!("  [{}] {:#x}\n", i, entry.ip);
+        raw_log!("  [{}] {}\n", i, crate::symbols::ReturnAddress(entry.ip));
     }
     safe_halt()
 }
@@ -124,6 +166,7 @@ fn terminate_and_recover(frame: &mut InterruptStackFrame, reason: &str) {
     }
     let pid = crate::process::ProcessId(current_pid as u64);
     crate::process::SCHEDULER.with_process(pid, |p| {
+        raw_log!("  process {} ({})\n", pid, p.name);
         p.state = crate::process::ProcessState::Terminated;
         p.exit_code = Some(1);
     });
@@ -151,6 +194,7 @@ macro_rules! define_no_err_handler {
     ($name:ident, $vector:expr) => {
         #[unsafe(no_mangle)]
         pub extern "x86-interrupt" fn $name(mut frame: InterruptStackFrame) {
+            let _gs = KernelGs::enter(&frame);
             let exc_name = exception_name($vector);
             if is_user_mode(&frame) {
                 raw_log!(
@@ -170,6 +214,7 @@ macro_rules! define_err_handler {
     ($name:ident, $vector:expr) => {
         #[unsafe(no_mangle)]
         pub extern "x86-interrupt" fn $name(mut frame: InterruptStackFrame, error_code: u64) {
+            let _gs = KernelGs::enter(&frame);
             let exc_name = exception_name($vector);
             if is_user_mode(&frame) {
                 raw_log!(
@@ -178,9 +223,15 @@ macro_rules! define_err_handler {
                     error_code,
                     frame.instruction_pointer.as_u64()
                 );
-                terminate_and_recover(&mut frame, exc_name);
             } else {
                 raw_log!("  Error code: {:#x}\n", error_code);
+            }
+            if let Some(selector) = SelectorErrorCode::for_vector($vector, error_code) {
+                raw_log!("  Selector: {}\n", selector);
+            }
+            if is_user_mode(&frame) {
+                terminate_and_recover(&mut frame, exc_name);
+            } else {
                 kernel_fault_halt(&frame, exc_name, "kernel exc");
             }
         }
@@ -192,6 +243,7 @@ define_no_err_handler!(debug_handler, 1);
 
 #[unsafe(no_mangle)]
 pub extern "x86-interrupt" fn nmi_handler(mut frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter_paranoid(&frame);
     if nitrogen::mmio::mmio_watchdog_armed() {
         raw_log!("NMI: MMIO watchdog expired — forcing recovery\n");
         nitrogen::mmio::mmio_watchdog_nmi_recovery();
@@ -233,43 +285,196 @@ define_err_handler!(security_exception_handler, 30);
 
 #[unsafe(no_mangle)]
 pub extern "x86-interrupt" fn machine_check_handler(frame: InterruptStackFrame) -> ! {
+    let _gs = KernelGs::enter_paranoid(&frame);
     kernel_fault_halt(&frame, "Machine Check", "");
 }
 
 #[unsafe(no_mangle)]
-pub extern "x86-interrupt" fn breakpoint_handler(_frame: InterruptStackFrame) {
+pub extern "x86-interrupt" fn breakpoint_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
     raw_log!("\nBREAKPOINT\n");
 }
 
 #[unsafe(no_mangle)]
 pub extern "x86-interrupt" fn double_fault_handler(
     frame: InterruptStackFrame,
-    _error_code: u64,
+    error_code: u64,
 ) -> ! {
+    // An overflow staged by `probe_stack_overflow` goes back to it.
+    let probe_stack = OVERFLOW_PROBE_STACK.load(Ordering::Relaxed);
+    if probe_stack != 0 && frame.stack_pointer.as_u64() == probe_stack {
+        unsafe { core::arch::asm!("jmp {}", sym overflow_probe_caught, options(noreturn)) }
+    }
+    let _gs = KernelGs::enter_paranoid(&frame);
+    // A double fault means the CPU could not deliver an earlier exception,
+    // most often because the stack it was pushing onto is unusable.  This
+    // handler runs on its own IST stack and only reports: the scheduler's
+    // locks may be held by the code that faulted, so it halts without
+    // touching them.
+    let cr2 = Cr2::read_raw();
+    let cr3 = x86_64::registers::control::Cr3::read_raw()
+        .0
+        .start_address()
+        .as_u64();
+    let rsp = frame.stack_pointer.as_u64();
+    let mode = if is_user_mode(&frame) {
+        "user"
+    } else {
+        "kernel"
+    };
     raw_log!(
-        "\n=== DOUBLE FAULT === RIP={:#x} RSP={:#x} CS={:#x}\n",
+        "\n=== DOUBLE FAULT ===\n  RIP={:#x} RSP={:#x} RFLAGS={:#x}\n  CS={:#x} SS={:#x} error={:#x}\n  CR2={:#x} CR3={:#x}\n  {} mode, pid {}\n",
         frame.instruction_pointer.as_u64(),
-        frame.stack_pointer.as_u64(),
-        frame.code_segment.0
+        rsp,
+        frame.cpu_flags.bits(),
+        frame.code_segment.0,
+        frame.stack_segment.0,
+        error_code,
+        cr2,
+        cr3,
+        mode,
+        crate::process::SCHEDULER.current_pid()
     );
-    if is_user_mode(&frame) {
-        let pid = crate::process::SCHEDULER.current_pid();
-        if pid != 0 {
-            crate::process::SCHEDULER.with_process(crate::process::ProcessId(pid as u64), |p| {
-                p.state = crate::process::ProcessState::Terminated;
-                p.exit_code = Some(1);
-            });
-            crate::process::SCHEDULER.cleanup();
-        }
+    if is_stack_overflow(cr2, rsp) {
+        raw_log!("  CR2 is just below RSP: stack overflow\n");
     }
     safe_halt()
 }
 
+/// Whether a fault at `fault_addr` looks like the stack at `rsp` running off
+/// its bottom, i.e. the address lies within a page below RSP.
+fn is_stack_overflow(fault_addr: u64, rsp: u64) -> bool {
+    fault_addr < rsp && rsp - fault_addr <= 4096
+}
+
+/// Why a kernel-mode fault on a present page at a user address happened:
+/// SMEP stops the kernel executing user pages, and SMAP stops it touching
+/// them with RFLAGS.AC clear, i.e. outside the user copy helpers.
+fn user_access_violation(
+    error_code: PageFaultErrorCode,
+    user_address: bool,
+    flags: RFlags,
+) -> Option<&'static str> {
+    if !user_address || !error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+        None
+    } else if error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+        Some("kernel executed a user page (SMEP)")
+    } else if !flags.contains(RFlags::ALIGNMENT_CHECK) {
+        Some("kernel accessed user memory outside a user copy (SMAP)")
+    } else {
+        None
+    }
+}
+
+// ── Fault probes ───────────────────────────────────────────────
+
+/// Error code of the page fault the running [`probe_read`] took.
+static PROBE_FAULT: AtomicU64 = AtomicU64::new(NO_PROBE_FAULT);
+const NO_PROBE_FAULT: u64 = u64::MAX;
+
+/// Read the byte at `addr` from the kernel, returning the error code of
+/// the page fault that stopped the read instead of halting on it.  Lets a
+/// self-test check that a protection really faults.
+pub fn probe_read(addr: u64) -> Result<u8, PageFaultErrorCode> {
+    PROBE_FAULT.store(NO_PROBE_FAULT, Ordering::Relaxed);
+    let byte = unsafe { probe_load(addr) };
+    match PROBE_FAULT.swap(NO_PROBE_FAULT, Ordering::Relaxed) {
+        NO_PROBE_FAULT => Ok(byte),
+        code => Err(PageFaultErrorCode::from_bits_truncate(code)),
+    }
+}
+
+/// The load [`probe_read`] makes.  A page fault on it resumes at
+/// [`probe_load_faulted`], which returns to the caller in its place.
+#[unsafe(naked)]
+unsafe extern "C" fn probe_load(addr: u64) -> u8 {
+    core::arch::naked_asm!("movzx eax, byte ptr [rdi]", "ret")
+}
+
+#[unsafe(naked)]
+extern "C" fn probe_load_faulted() -> u8 {
+    core::arch::naked_asm!("xor eax, eax", "ret")
+}
+
+/// Stack [`probe_stack_overflow`] is overflowing, or 0.
+static OVERFLOW_PROBE_STACK: AtomicU64 = AtomicU64::new(0);
+/// Stack pointer of the [`overflow_probe`] caller, to return on.
+static OVERFLOW_PROBE_RETURN_RSP: AtomicU64 = AtomicU64::new(0);
+
+/// Point the stack at `stack_bottom`, with an unmapped page just below it,
+/// push, and report whether that ended in the double-fault handler: the
+/// page fault cannot be delivered onto the same stack, so only the
+/// handler's IST stack keeps the machine from resetting.  The handler
+/// returns here instead of halting.  Call with interrupts off.
+pub fn probe_stack_overflow(stack_bottom: u64) -> bool {
+    OVERFLOW_PROBE_STACK.store(stack_bottom, Ordering::Relaxed);
+    let double_faulted = unsafe { overflow_probe(stack_bottom) };
+    OVERFLOW_PROBE_STACK.store(0, Ordering::Relaxed);
+    double_faulted
+}
+
+/// Switch to `stack` and push onto it; returns `false` if the push went
+/// through.  A double fault comes back through [`overflow_probe_caught`],
+/// which returns `true` for it with the registers saved here.
+#[unsafe(naked)]
+unsafe extern "C" fn overflow_probe(stack: u64) -> bool {
+    core::arch::naked_asm!(
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov [rip + {saved}], rsp",
+        "mov rsp, rdi",
+        "push rax",
+        "mov rsp, [rip + {saved}]",
+        "xor eax, eax",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "ret",
+        saved = sym OVERFLOW_PROBE_RETURN_RSP,
+    )
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn overflow_probe_caught() -> ! {
+    core::arch::naked_asm!(
+        "mov rsp, [rip + {saved}]",
+        "mov eax, 1",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "ret",
+        saved = sym OVERFLOW_PROBE_RETURN_RSP,
+    )
+}
+
 #[unsafe(no_mangle)]
 pub extern "x86-interrupt" fn page_fault_handler(
     mut frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
+    let _gs = KernelGs::enter(&frame);
+    if frame.instruction_pointer.as_u64() == probe_load as *const () as u64 {
+        PROBE_FAULT.store(error_code.bits(), Ordering::Relaxed);
+        let resume = InterruptStackFrameValue::new(
+            x86_64::VirtAddr::from_ptr(probe_load_faulted as *const ()),
+            frame.code_segment,
+            frame.cpu_flags,
+            frame.stack_pointer,
+            frame.stack_segment,
+        );
+        unsafe { frame.as_mut().write(resume) };
+        return;
+    }
     let fault_addr = match Cr2::read() {
         Ok(a) => a,
         Err(_) => {
@@ -287,6 +492,15 @@ pub extern "x86-interrupt" fn page_fault_handler(
     let is_write = error_code.intersects(PageFaultErrorCode::CAUSED_BY_WRITE);
     let is_user = error_code.intersects(PageFaultErrorCode::USER_MODE);
 
+    // Writes to copy-on-write pages are expected after fork and resolved
+    // silently, including kernel writes into user buffers during syscalls.
+    if is_present && is_write && petroleum::common::memory::is_user_address(fault_addr) {
+        let pml4 = x86_64::registers::control::Cr3::read().0.start_address();
+        if let Ok(true) = crate::memory_management::handle_cow_fault(pml4, fault_addr) {
+            return;
+        }
+    }
+
     raw_log!(
         "PF @ {:#x}: {} {} {}\n",
         fault_addr.as_u64(),
@@ -297,12 +511,71 @@ pub extern "x86-interrupt" fn page_fault_handler(
 
     if !is_user {
         raw_log!("  Fault addr: {:#x}\n", fault_addr.as_u64());
+        if let Some(cause) = user_access_violation(
+            error_code,
+            petroleum::common::memory::is_user_address(fault_addr),
+            frame.cpu_flags,
+        ) {
+            raw_log!("  {}\n", cause);
+        }
+        if is_stack_overflow(fault_addr.as_u64(), frame.stack_pointer.as_u64()) {
+            raw_log!("  CR2 is just below RSP: kernel stack overflow\n");
+        }
         kernel_fault_halt(&frame, "Page Fault", "kernel PF");
     } else {
-        if petroleum::common::memory::is_user_address(fault_addr) || is_present {
+        let pid = crate::process::SCHEDULER.current_pid();
+        if crate::memory_management::is_stack_guard_fault(pid, fault_addr.as_u64()) {
+            raw_log!("stack overflow in process {}\n", pid);
+            terminate_and_recover(&mut frame, "Stack overflow");
+        } else if petroleum::common::memory::is_user_address(fault_addr) || is_present {
             terminate_and_recover(&mut frame, "Page Fault(user)");
         } else {
             terminate_and_recover(&mut frame, "Page Fault(invalid addr)");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn decodes_selector_error_codes() {
+        // #GP loading selector 0x2B (GDT index 5, RPL 3).
+        assert_eq!(SelectorErrorCode(0x28).to_string(), "GDT index 5");
+        // Vector 0x80 through the IDT, raised by an external event.
+        assert_eq!(
+            SelectorErrorCode((0x80 << 3) | 0b011).to_string(),
+            "IDT index 128, external"
+        );
+        assert_eq!(SelectorErrorCode(0x0C).to_string(), "LDT index 1");
+        assert_eq!(SelectorErrorCode(0).to_string(), "no selector");
+        assert!(SelectorErrorCode::for_vector(13, 0).is_some());
+        assert!(SelectorErrorCode::for_vector(14, 0).is_none());
+    }
+
+    #[test]
+    fn kernel_faults_on_user_pages_are_attributed_to_smep_and_smap() {
+        let present = PageFaultErrorCode::PROTECTION_VIOLATION;
+        let fetch = present | PageFaultErrorCode::INSTRUCTION_FETCH;
+        let write = present | PageFaultErrorCode::CAUSED_BY_WRITE;
+        let open = RFlags::ALIGNMENT_CHECK;
+
+        assert_eq!(
+            user_access_violation(fetch, true, open),
+            Some("kernel executed a user page (SMEP)")
+        );
+        // A stray dereference of a user pointer, outside any user copy.
+        assert_eq!(
+            user_access_violation(write, true, RFlags::empty()),
+            Some("kernel accessed user memory outside a user copy (SMAP)")
+        );
+        assert_eq!(user_access_violation(write, true, open), None);
+        assert_eq!(
+            user_access_violation(PageFaultErrorCode::empty(), true, RFlags::empty()),
+            None
+        );
+        assert_eq!(user_access_violation(present, false, RFlags::empty()), None);
+    }
+}
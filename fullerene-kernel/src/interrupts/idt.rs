@@ -2,9 +2,12 @@
 //!
 //! This module provides IDT initialization and handler setup.
 
-use super::apic::{KEYBOARD_INTERRUPT_INDEX, MOUSE_INTERRUPT_INDEX, TIMER_INTERRUPT_INDEX};
+use super::apic::{
+    KEYBOARD_INTERRUPT_INDEX, MOUSE_INTERRUPT_INDEX, SERIAL_INTERRUPT_INDEX, TIMER_INTERRUPT_INDEX,
+};
 use super::exceptions::*;
-use super::input::{keyboard_handler, mouse_handler, timer_handler};
+use super::input::{keyboard_handler, mouse_handler, serial_handler, timer_handler};
+use super::irq;
 use crate::gdt::{
     DOUBLE_FAULT_IST_INDEX, GP_FAULT_IST_INDEX, MACHINE_CHECK_IST_INDEX, NMI_IST_INDEX,
     PAGE_FAULT_IST_INDEX, STACK_FAULT_IST_INDEX,
@@ -115,9 +118,18 @@ pub fn init() {
             .set_handler_fn(security_exception_handler);
 
         // ── Hardware interrupt handlers ──
+        // The timer rewrites the interrupted frame on watchdog recovery, so
+        // it keeps its own entry; every other IRQ goes through the stubs.
         idt[TIMER_INTERRUPT_INDEX as u8].set_handler_fn(timer_handler);
-        idt[KEYBOARD_INTERRUPT_INDEX as u8].set_handler_fn(keyboard_handler);
-        idt[MOUSE_INTERRUPT_INDEX as u8].set_handler_fn(mouse_handler);
+        irq::install_stubs(idt);
+        for (vector, handler) in [
+            (KEYBOARD_INTERRUPT_INDEX, keyboard_handler as fn()),
+            (MOUSE_INTERRUPT_INDEX, mouse_handler),
+            (SERIAL_INTERRUPT_INDEX, serial_handler),
+        ] {
+            irq::register_irq(irq::irq_for_vector(vector as u8), handler)
+                .expect("built-in IRQ claimed twice");
+        }
 
         // Set up scheduler trampoline address for exception recovery
         let trampoline_addr = x86_64::VirtAddr::new(
@@ -131,3 +143,11 @@ pub fn init() {
 
     mem_debug!("IDT: Initialized successfully\n");
 }
+
+/// Load the IDT built by [`init`] on the calling CPU.
+///
+/// Application processors share the BSP's handlers; only IDTR is per CPU.
+#[allow(static_mut_refs)]
+pub fn load() {
+    unsafe { IDT.load() };
+}
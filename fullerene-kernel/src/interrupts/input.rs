@@ -1,19 +1,23 @@
 //! Input device interrupt handlers
 //!
-//! This module handles keyboard and mouse interrupts.
+//! This module handles keyboard, mouse and serial (COM1) interrupts.  The
+//! timer keeps a dedicated IDT entry; the others are registered through
+//! [`super::irq::register_irq`].
 
 use super::apic::send_eoi;
+use core::sync::atomic::{AtomicBool, Ordering};
 use petroleum::port_read_u8;
+use spin::Mutex;
+use x86_64::PrivilegeLevel;
 use x86_64::structures::idt::{InterruptStackFrame, InterruptStackFrameValue};
 
-/// Macro to create input device interrupt handlers
+/// Macro to create input device IRQ handlers (EOI is sent by the
+/// dispatcher in [`super::irq`]).
 macro_rules! define_input_interrupt_handler {
     ($handler_name:ident, $port:expr, $process_input:expr) => {
-        #[unsafe(no_mangle)]
-        pub extern "x86-interrupt" fn $handler_name(_stack_frame: InterruptStackFrame) {
+        pub fn $handler_name() {
             let data = port_read_u8!($port);
             $process_input(data);
-            send_eoi();
         }
     };
 }
@@ -24,7 +28,9 @@ macro_rules! define_input_interrupt_handler {
 // PS/2 keyboard driver for scancode processing.  The driver handles
 // scancode-to-ASCII conversion, modifier keys, and input buffering.
 define_input_interrupt_handler!(keyboard_handler, 0x60, |scancode: u8| {
-    nitrogen::ps2::keyboard::handle_keyboard_scancode(scancode);
+    if nitrogen::ps2::keyboard::handle_keyboard_scancode(scancode) {
+        INPUT_EVENT.store(true, Ordering::Release);
+    }
 });
 
 // Mouse interrupt handler
@@ -36,17 +42,96 @@ define_input_interrupt_handler!(mouse_handler, 0x60, |byte: u8| {
     nitrogen::ps2::mouse::handle_mouse_data(byte);
 });
 
-/// Timer interrupt handler (no preemption - scheduler loop handles yielding)
-/// Also detects NMI MMIO watchdog recovery and redirects to the scheduler loop.
+pub use nitrogen::input_queue::InputQueue;
+
+/// Bytes received on COM1, waiting for the shell.  Filled by
+/// [`serial_handler`].
+pub static SERIAL_INPUT: InputQueue = InputQueue::new();
+
+/// Keeps the several readers of [`SERIAL_INPUT`] to one consumer at a
+/// time.  The handler never takes it, so it needs no interrupt masking.
+static SERIAL_READER: Mutex<()> = Mutex::new(());
+
+/// Set by the keyboard and serial handlers whenever they queue a byte for
+/// the console.
+static INPUT_EVENT: AtomicBool = AtomicBool::new(false);
+
+/// Whether keyboard or serial input has arrived since the last call, which
+/// clears the flag.  Lets the idle loop go straight round again rather than
+/// halt until the next timer tick while input is waiting.
+pub fn take_input_event() -> bool {
+    INPUT_EVENT.swap(false, Ordering::AcqRel)
+}
+
+/// COM1 receive interrupt handler (IRQ 4)
+///
+/// Drains every byte the UART holds so a FIFO burst costs one interrupt.
+/// Terminal conventions are mapped to what the keyboard driver produces:
+/// CR becomes LF and DEL becomes backspace.
+pub fn serial_handler() {
+    while let Some(byte) = petroleum::serial::com1_read_byte() {
+        let byte = match byte {
+            b'\r' => b'\n',
+            0x7F => 0x08,
+            other => other,
+        };
+        SERIAL_INPUT.push(byte);
+        INPUT_EVENT.store(true, Ordering::Release);
+    }
+}
+
+/// Pop the next byte received on COM1 without waiting.
+pub fn try_read_serial() -> Option<u8> {
+    let _reader = SERIAL_READER.lock();
+    SERIAL_INPUT.pop()
+}
+
+pub fn serial_input_available() -> bool {
+    !SERIAL_INPUT.is_empty()
+}
+
+/// Next console input byte, from the keyboard or else the serial line,
+/// without waiting.
+pub fn try_read_console() -> Option<u8> {
+    nitrogen::ps2::keyboard::try_read().or_else(try_read_serial)
+}
+
+pub fn console_input_available() -> bool {
+    nitrogen::ps2::keyboard::input_available() || serial_input_available()
+}
+
+/// Halt until the next interrupt unless console input is already waiting.
+///
+/// For readers that would otherwise spin on [`try_read_console`]: the
+/// input handlers wake the `hlt`, and so does the timer, which lets the
+/// caller yield to ready processes between waits.  Interrupts stay masked
+/// between the check and the halt, so a byte arriving in between is not
+/// missed.
+pub fn wait_for_console_input() {
+    x86_64::instructions::interrupts::disable();
+    if take_input_event() || console_input_available() {
+        x86_64::instructions::interrupts::enable();
+    } else {
+        x86_64::instructions::interrupts::enable_and_hlt();
+    }
+}
+
+/// Timer interrupt handler
+///
+/// Preempts a user process whose time slice has run out; kernel code is
+/// never preempted and yields on its own.  Also detects NMI MMIO watchdog
+/// recovery and redirects to the scheduler loop.
 #[unsafe(no_mangle)]
 pub extern "x86-interrupt" fn timer_handler(mut frame: InterruptStackFrame) {
-    // Increment global tick counter (lock-free atomic increment)
-    super::TICK_COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    let _gs = crate::percpu::KernelGs::enter(&frame);
+    // Advance the global tick counter (lock-free, milliseconds)
+    super::account_timer_tick();
+    crate::scheduler::watchdog_tick(&frame);
 
     if nitrogen::mmio::mmio_watchdog_recovery_triggered() {
-        petroleum::serial::serial_log(format_args!(
-            "[timer_handler] NMI recovery triggered — jumping to scheduler_loop\n"
-        ));
+        // Through the logger, which queues the line rather than splicing it
+        // into a record the interrupted code may be writing.
+        log::warn!("[timer_handler] NMI recovery triggered — jumping to scheduler_loop");
         let restart_fn = crate::scheduler_context::SCHEDULER.recovery_target();
         if let Some((rsp, rip)) = restart_fn {
             let new_frame = InterruptStackFrameValue::new(
@@ -67,4 +152,23 @@ pub extern "x86-interrupt" fn timer_handler(mut frame: InterruptStackFrame) {
     }
 
     send_eoi();
+
+    // Preempt only user code: see `SchedulerContext::preempt_current` for
+    // why the switch is safe from here.  The EOI has gone out first, as
+    // this handler may not return until the process runs again.
+    if frame.code_segment.rpl() == PrivilegeLevel::Ring3 {
+        crate::scheduler_context::SCHEDULER.preempt_current();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{INPUT_EVENT, Ordering, take_input_event};
+
+    #[test]
+    fn input_event_is_cleared_by_taking_it() {
+        INPUT_EVENT.store(true, Ordering::Release);
+        assert!(take_input_event());
+        assert!(!take_input_event());
+    }
 }
@@ -0,0 +1,129 @@
+//! Registered hardware interrupt handlers.
+//!
+//! IRQ `n` is delivered on IDT vector [`IRQ_VECTOR_BASE`]` + n`.  IRQs 0-15
+//! are the ISA lines routed through the I/O APIC, and IRQs 16-47 cover the
+//! MSI vector pool handed out by [`nitrogen::pci_msi::allocate_vector`].
+//! Every one of those vectors points at a generic stub that looks up the
+//! handler registered with [`register_irq`], calls it and sends the EOI, so
+//! a driver can claim an interrupt without touching the IDT.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+
+use super::apic::{TIMER_INTERRUPT_INDEX, send_eoi};
+
+/// IDT vector of IRQ 0.
+pub const IRQ_VECTOR_BASE: u8 = 32;
+/// ISA IRQs plus the MSI pool.
+pub const IRQ_COUNT: usize = 48;
+
+const _: () = assert!(
+    nitrogen::pci_msi::MSI_VECTOR_BASE as usize + nitrogen::pci_msi::MSI_VECTOR_COUNT as usize
+        <= IRQ_VECTOR_BASE as usize + IRQ_COUNT
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    /// The IRQ has no dispatch stub.
+    OutOfRange,
+    /// The vector is wired to a dedicated handler (the APIC timer).
+    Reserved,
+    /// Another handler already owns the IRQ.
+    AlreadyRegistered,
+}
+
+/// Handler addresses as `usize`; 0 means unclaimed.
+static HANDLERS: [AtomicUsize; IRQ_COUNT] = [const { AtomicUsize::new(0) }; IRQ_COUNT];
+
+/// Vector on which `irq` is delivered.
+pub const fn vector_for_irq(irq: u8) -> u8 {
+    IRQ_VECTOR_BASE + irq
+}
+
+/// IRQ number delivered on `vector`.
+pub const fn irq_for_vector(vector: u8) -> u8 {
+    vector - IRQ_VECTOR_BASE
+}
+
+/// Claim `irq` for `handler`.  The handler runs with interrupts disabled
+/// and must not send the EOI itself.
+pub fn register_irq(irq: u8, handler: fn()) -> Result<(), IrqError> {
+    let slot = HANDLERS.get(irq as usize).ok_or(IrqError::OutOfRange)?;
+    if vector_for_irq(irq) as u32 == TIMER_INTERRUPT_INDEX {
+        return Err(IrqError::Reserved);
+    }
+    slot.compare_exchange(0, handler as usize, Ordering::AcqRel, Ordering::Acquire)
+        .map(|_| ())
+        .map_err(|_| IrqError::AlreadyRegistered)
+}
+
+/// Release `irq` so another driver can claim it.
+pub fn unregister_irq(irq: u8) {
+    if let Some(slot) = HANDLERS.get(irq as usize) {
+        slot.store(0, Ordering::Release);
+    }
+}
+
+fn dispatch(irq: usize) {
+    let handler = HANDLERS[irq].load(Ordering::Acquire);
+    if handler != 0 {
+        // SAFETY: only `register_irq` stores non-zero values, and it only
+        // stores `fn()` pointers.
+        let handler: fn() = unsafe { core::mem::transmute(handler) };
+        handler();
+    }
+    send_eoi();
+}
+
+extern "x86-interrupt" fn irq_stub<const IRQ: usize>(frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&frame);
+    dispatch(IRQ);
+}
+
+macro_rules! irq_stubs {
+    ($($irq:literal)*) => {
+        [$(irq_stub::<$irq> as extern "x86-interrupt" fn(InterruptStackFrame)),*]
+    };
+}
+
+static STUBS: [extern "x86-interrupt" fn(InterruptStackFrame); IRQ_COUNT] = irq_stubs!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+    16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+    32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47
+);
+
+/// Point every IRQ vector except the timer's at its dispatch stub.
+pub(super) fn install_stubs(idt: &mut InterruptDescriptorTable) {
+    for (irq, stub) in STUBS.iter().enumerate() {
+        let vector = vector_for_irq(irq as u8);
+        if vector as u32 != TIMER_INTERRUPT_INDEX {
+            idt[vector].set_handler_fn(*stub);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler() {}
+
+    #[test]
+    fn irqs_cannot_be_claimed_twice() {
+        let irq = (IRQ_COUNT - 1) as u8;
+        assert_eq!(register_irq(irq, handler), Ok(()));
+        assert_eq!(register_irq(irq, handler), Err(IrqError::AlreadyRegistered));
+        unregister_irq(irq);
+        assert_eq!(register_irq(irq, handler), Ok(()));
+        unregister_irq(irq);
+
+        assert_eq!(
+            register_irq(irq_for_vector(TIMER_INTERRUPT_INDEX as u8), handler),
+            Err(IrqError::Reserved)
+        );
+        assert_eq!(
+            register_irq(IRQ_COUNT as u8, handler),
+            Err(IrqError::OutOfRange)
+        );
+    }
+}
@@ -8,14 +8,35 @@ pub mod apic;
 pub mod exceptions;
 pub mod idt;
 pub mod input;
+pub mod irq;
 pub mod syscall;
 
-use core::sync::atomic::AtomicU64;
+use core::sync::atomic::{AtomicU64, Ordering};
 use x86_64::instructions::interrupts;
 
-// Global tick counter for timing (lock-free atomic)
+// Global tick counter for timing (lock-free atomic).  Counts milliseconds of
+// timer time, so tick-based sleeps keep their length when the APIC timer
+// rate changes.  Only the BSP's timer advances it; every CPU counts its own
+// interrupts in `PerCpu::timer_ticks`.
 pub static TICK_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+/// Microseconds of timer time behind `TICK_COUNTER`.
+static TIMER_ELAPSED_US: AtomicU64 = AtomicU64::new(0);
+
+/// Advance the tick counter by one period of the APIC timer and charge the
+/// period to the running process.
+fn account_timer_tick() {
+    let cpu = crate::percpu::this_cpu();
+    cpu.timer_ticks.fetch_add(1, Ordering::Relaxed);
+    if !cpu.is_bsp() {
+        return;
+    }
+    let period_us = 1_000_000 / u64::from(apic::timer_hz().max(1));
+    let elapsed_us = TIMER_ELAPSED_US.fetch_add(period_us, Ordering::Relaxed) + period_us;
+    TICK_COUNTER.store(elapsed_us / 1000, Ordering::Relaxed);
+    crate::scheduler_context::SCHEDULER.charge_timer_tick(period_us);
+}
+
 // Re-export public functions and structures
 pub use exceptions::{
     alignment_check_handler, bound_range_exceeded_handler, breakpoint_handler,
@@ -27,7 +48,8 @@ pub use exceptions::{
     virtualization_handler, vmm_communication_exception_handler,
 };
 pub use idt::init;
-pub use input::{keyboard_handler, mouse_handler, timer_handler};
+pub use input::{keyboard_handler, mouse_handler, serial_handler, timer_handler};
+pub use irq::{IrqError, register_irq, unregister_irq};
 pub use syscall::setup_syscall;
 
 /// Wait for interrupt (actually halts the CPU instead of busy-waiting)
@@ -1,7 +1,15 @@
 //! System call mechanism
 //!
 //! This module implements the Fast System Call mechanism using SYSCALL/SYSRET instructions.
+//! The stack, the user RSP scratch slot and the frame pointer of a syscall
+//! live in the per-CPU block ([`crate::percpu`]), which GS points at.  A
+//! syscall runs on the calling process's own kernel stack, the one the TSS
+//! hands interrupts from ring 3, so a handler can block and be resumed
+//! while other processes make syscalls of their own.
 
+use crate::percpu::{PerCpu, this_cpu};
+use core::mem::offset_of;
+use core::sync::atomic::Ordering;
 use petroleum::mem_debug;
 use x86_64::VirtAddr;
 use x86_64::registers::model_specific::Msr;
@@ -10,12 +18,6 @@ use x86_64::registers::rflags::RFlags;
 /// Static kernel stack for syscall to prevent page fault vulnerabilities
 const SYSCALL_STACK_SIZE: usize = 4096;
 
-/// # Safety
-/// Written once during `init_syscall_stack()` (boot phase), then read‑only.
-/// Single‑core assumption: no concurrent readers.  Accessed from `syscall_entry`
-/// assembly which runs with interrupts disabled.
-static mut SYSCALL_STACK_PTR: u64 = 0;
-
 /// Kernel CR3 for syscall to access kernel heap
 ///
 /// # Safety
@@ -25,6 +27,76 @@ static mut SYSCALL_STACK_PTR: u64 = 0;
 #[unsafe(no_mangle)]
 pub static mut KERNEL_CR3_U64: u64 = 0;
 
+/// User register state saved by [`syscall_entry`] and restored on `sysretq`.
+///
+/// Field order mirrors the push sequence (lowest address first).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    /// User RFLAGS (from R11)
+    pub rflags: u64,
+    /// Return address (from RCX)
+    pub rip: u64,
+    /// User stack pointer
+    pub rsp: u64,
+}
+
+/// Snapshot of the user state of the syscall currently being handled.
+///
+/// Returns `None` until the running process makes a syscall.  Only
+/// meaningful when called from a handler reached through [`syscall_entry`];
+/// the frame stays valid across blocking, since every process has its own
+/// stack and frame.
+pub fn current_syscall_frame() -> Option<SyscallFrame> {
+    let ptr = this_cpu().syscall_frame.load(Ordering::Relaxed) as *const SyscallFrame;
+    if ptr.is_null() {
+        return None;
+    }
+    // SAFETY: set by `syscall_entry` to a fully written frame on the
+    // syscall stack, which stays live until this syscall returns.
+    Some(unsafe { ptr.read() })
+}
+
+/// Make the syscall in progress return into a new program image.
+///
+/// Rewrites the saved frame so that `sysretq` resumes at `rip` on `rsp`
+/// with interrupts enabled and zeroed callee-saved registers, and replaces
+/// the user CR3 that `syscall_entry` pushed just below the frame.
+///
+/// # Safety
+/// Must be called from a handler reached through [`syscall_entry`], and
+/// `cr3` must be a page table that maps `rip` and `rsp` for user mode.
+pub unsafe fn redirect_syscall_return(rip: u64, rsp: u64, cr3: u64) -> bool {
+    let ptr = this_cpu().syscall_frame.load(Ordering::Relaxed) as *mut SyscallFrame;
+    if ptr.is_null() {
+        return false;
+    }
+    let frame = SyscallFrame {
+        r15: 0,
+        r14: 0,
+        r13: 0,
+        r12: 0,
+        rbp: 0,
+        rbx: 0,
+        rflags: RFlags::INTERRUPT_FLAG.bits(),
+        rip,
+        rsp,
+    };
+    // SAFETY: the frame and the saved CR3 slot below it live on the
+    // syscall stack until this syscall returns.
+    unsafe {
+        ptr.write(frame);
+        (ptr as *mut u64).sub(1).write(cr3);
+    }
+    true
+}
+
 /// Set kernel CR3 for syscall switching
 pub fn set_kernel_cr3(cr3: u64) {
     unsafe {
@@ -32,7 +104,7 @@ pub fn set_kernel_cr3(cr3: u64) {
     }
 }
 
-/// Initialize syscall kernel stack
+/// Initialize the running CPU's syscall kernel stack
 pub fn init_syscall_stack() {
     mem_debug!("Syscall: init_syscall_stack start\n");
     use alloc::alloc::{Layout, alloc};
@@ -41,19 +113,51 @@ pub fn init_syscall_stack() {
     let ptr = unsafe { alloc(layout) };
     mem_debug!("Syscall: stack allocated\n");
     let stack_top = unsafe { ptr.add(SYSCALL_STACK_SIZE) };
-    unsafe {
-        SYSCALL_STACK_PTR = stack_top as u64;
-    }
+    let cpu = this_cpu();
+    cpu.default_syscall_stack
+        .store(stack_top as u64, Ordering::Relaxed);
+    cpu.syscall_stack_top
+        .store(stack_top as u64, Ordering::Relaxed);
     mem_debug!("Syscall: init_syscall_stack done\n");
 }
 
+/// Run this CPU's syscalls on `kernel_stack`, the kernel stack of the
+/// process about to run, with `frame` as its syscall in progress (0 for
+/// none).
+///
+/// Returns the frame of the process leaving the CPU, for it to get back
+/// when it is resumed.  A null `kernel_stack` selects the CPU's own syscall
+/// stack.
+pub fn switch_syscall_stack(kernel_stack: VirtAddr, frame: u64) -> u64 {
+    let cpu = this_cpu();
+    let top = if kernel_stack.is_null() {
+        cpu.default_syscall_stack.load(Ordering::Relaxed)
+    } else {
+        kernel_stack.as_u64()
+    };
+    cpu.syscall_stack_top.store(top, Ordering::Relaxed);
+    cpu.syscall_frame.swap(frame, Ordering::Relaxed)
+}
+
 /// System call entry point (naked function for manual assembly handling)
 #[unsafe(naked)]
 pub extern "C" fn syscall_entry() {
     core::arch::naked_asm!(
-        // Switch to kernel stack using swapgs
+        // Switch to this CPU's kernel stack, keeping the user RSP
         "swapgs",
-        "mov rsp, gs:0",
+        "mov qword ptr gs:[{user_rsp}], rsp",
+        "mov rsp, qword ptr gs:[{stack_top}]",
+        // Build the SyscallFrame. Entry: SYSCALL puts RIP in RCX, RFLAGS in R11
+        "push qword ptr gs:[{user_rsp}]",
+        "push rcx",
+        "push r11",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov qword ptr gs:[{frame}], rsp",
         // Save syscall number in RBX and switch CR3 to kernel page table
         "mov rbx, rax",
         "mov rax, cr3",
@@ -61,30 +165,48 @@ pub extern "C" fn syscall_entry() {
         "lea rax, [rip + KERNEL_CR3_U64]",
         "mov rax, [rax]",
         "mov cr3, rax",
-        // Entry: SYSCALL puts RIP in RCX, RFLAGS in R11
-        "push rcx",
-        "push r11",
+        // Keep the stack 16-byte aligned at the call
+        "sub rsp, 8",
         // Shuffle arguments: syscall ABI (rdi,rsi,rdx,r10,r8,r9)
         // to C ABI (rdi,rsi,rdx,rcx,r8,r9)
         "mov rcx, r10",
         "mov rdi, rbx",
         "push rsp",
         "call handle_syscall",
-        "add rsp, 8",
-        // Save return value before popping CR3
-        "mov r12, rax",
-        // Restore user CR3 and RFLAGS/RIP
+        "add rsp, 16",
+        // Restore the frame; the user CR3 is loaded last, once the kernel
+        // stack is no longer needed
+        "pop rdi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
         "pop r11",
         "pop rcx",
-        "pop rax",
-        "mov cr3, rax",
-        // Restore return value before returning to user
-        "mov rax, r12",
+        "pop rsp",
+        "mov cr3, rdi",
         "swapgs",
-        "sysretq"
+        "sysretq",
+        stack_top = const offset_of!(PerCpu, syscall_stack_top),
+        user_rsp = const offset_of!(PerCpu, syscall_user_rsp),
+        frame = const offset_of!(PerCpu, syscall_frame),
     );
 }
 
+/// STAR value for the given kernel and user code selectors.
+///
+/// SYSCALL loads CS from STAR[47:32] and SS from 8 above it; SYSRET loads SS
+/// from STAR[63:48] + 8 and CS from STAR[63:48] + 16, both with RPL 3.  The
+/// GDT therefore has to place user data directly below user code, and the
+/// upper field holds the selector 16 below user code rather than user code
+/// itself.
+const fn star_value(kernel_cs: u16, user_cs: u16) -> u64 {
+    let sysret_base = (user_cs & !3) - 16;
+    ((sysret_base as u64 | 3) << 48) | ((kernel_cs as u64) << 32)
+}
+
 /// Set up Fast System Call mechanism
 pub fn setup_syscall() {
     mem_debug!("Syscall: setup_syscall start\n");
@@ -107,32 +229,62 @@ pub fn setup_syscall() {
 
     // Set STAR MSR for CS/SS switching
     // Use fallback selectors if GDT not yet fully initialized
-    let user_cs = crate::gdt::user_code_selector_checked().0 as u64;
-    let kernel_cs = crate::gdt::code_selector_checked().0 as u64;
-    let star_value = (user_cs << 48) | (kernel_cs << 32);
+    let user_cs = crate::gdt::user_code_selector_checked().0;
+    let kernel_cs = crate::gdt::code_selector_checked().0;
     mem_debug!("Syscall: writing STAR\n");
     unsafe {
-        Msr::new(0xC0000081).write(star_value);
+        Msr::new(0xC0000081).write(star_value(kernel_cs, user_cs));
     }
     mem_debug!("Syscall: STAR written\n");
 
-    // Mask RFLAGS during syscall
+    // Mask RFLAGS during syscall.  AC too: user code can set it, and
+    // entering the kernel with it set would switch SMAP off.
     mem_debug!("Syscall: writing SFMASK\n");
     unsafe {
-        Msr::new(0xC0000084).write(RFlags::INTERRUPT_FLAG.bits() | RFlags::TRAP_FLAG.bits());
+        Msr::new(0xC0000084).write(
+            RFlags::INTERRUPT_FLAG.bits()
+                | RFlags::TRAP_FLAG.bits()
+                | RFlags::ALIGNMENT_CHECK.bits(),
+        );
     }
     mem_debug!("Syscall: SFMASK written\n");
 
-    // Set KERNEL_GS_BASE to point to the static variable holding the syscall kernel stack top.
-    use x86_64::registers::model_specific::KernelGsBase;
-    mem_debug!("Syscall: writing KernelGsBase\n");
-    KernelGsBase::write(VirtAddr::new(
-        &raw const SYSCALL_STACK_PTR as *const _ as u64,
-    ));
-    mem_debug!("Syscall: KernelGsBase written\n");
-
-    let stack_top_addr = unsafe { SYSCALL_STACK_PTR };
+    // `syscall_entry` finds its stack through GS, which `percpu::init_bsp`
+    // pointed at this CPU's block.
+    let stack_top_addr = this_cpu().syscall_stack_top.load(Ordering::Relaxed);
     petroleum::debug_log_no_alloc!("Syscall: initialized. LSTAR: {}", entry_addr);
     petroleum::debug_log_no_alloc!("Syscall: kernel stack: {}", stack_top_addr);
     mem_debug!("Syscall: setup_syscall done\n");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SyscallFrame, star_value};
+    use core::mem::{offset_of, size_of};
+    use x86_64::structures::tss::TaskStateSegment;
+
+    #[test]
+    fn syscall_frame_matches_entry_push_order() {
+        // `syscall_entry` pushes rsp, rcx, r11, rbx, rbp, r12..r15 in that order.
+        assert_eq!(size_of::<SyscallFrame>(), 9 * 8);
+        assert_eq!(offset_of!(SyscallFrame, r15), 0);
+        assert_eq!(offset_of!(SyscallFrame, rbx), 5 * 8);
+        assert_eq!(offset_of!(SyscallFrame, rflags), 6 * 8);
+        assert_eq!(offset_of!(SyscallFrame, rip), 7 * 8);
+        assert_eq!(offset_of!(SyscallFrame, rsp), 8 * 8);
+    }
+
+    #[test]
+    fn sysret_returns_with_the_gdt_user_selectors() {
+        let mut tss = TaskStateSegment::new();
+        let (_, kernel_cs, kernel_ds, _, user_ds, user_cs) =
+            unsafe { crate::gdt::build_gdt(&mut tss) };
+        let star = star_value(kernel_cs.0, user_cs.0);
+        let syscall_base = (star >> 32) as u16;
+        let sysret_base = (star >> 48) as u16;
+        assert_eq!(syscall_base, kernel_cs.0);
+        assert_eq!(syscall_base + 8, kernel_ds.0);
+        assert_eq!((sysret_base + 8) | 3, user_ds.0);
+        assert_eq!((sysret_base + 16) | 3, user_cs.0);
+    }
+}
@@ -12,18 +12,18 @@
 //! ```
 
 use core::fmt;
-use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Mutex;
 
 /// Maximum number of bytes in the ring buffer.
 const KLOG_CAPACITY: usize = 65536;
 
 /// A fixed-size byte ring buffer for kernel log lines.
-static KLOG_BUF: Mutex<KLogRing> = Mutex::new(KLogRing {
-    buf: [0u8; KLOG_CAPACITY],
-    head: 0,
-    len: 0,
-});
+///
+/// Writers only ever `try_lock` it.  A record that arrives while the lock
+/// is held — from a nested log call made while formatting another record,
+/// an interrupt handler, or another CPU — is left out of the buffer instead
+/// of spinning on a lock its own CPU may be holding.
+static KLOG_BUF: Mutex<KLogRing> = Mutex::new(KLogRing::new());
 
 struct KLogRing {
     buf: [u8; KLOG_CAPACITY],
@@ -31,11 +31,40 @@ struct KLogRing {
     len: usize,
 }
 
-/// Reentrancy guard: set to `true` while `write_fmt` or `write_bytes`
-/// is holding the `KLOG_BUF` lock.  Nested calls (e.g. a log message
-/// emitted while formatting another log message) are forwarded to the
-/// serial port instead of attempting to re-acquire the mutex.
-static IN_KLOG: AtomicBool = AtomicBool::new(false);
+impl KLogRing {
+    const fn new() -> Self {
+        Self {
+            buf: [0u8; KLOG_CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Append `bytes`, overwriting the oldest bytes once full.
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if self.len < KLOG_CAPACITY {
+                let idx = (self.head + self.len) % KLOG_CAPACITY;
+                self.buf[idx] = b;
+                self.len += 1;
+            } else {
+                self.buf[self.head] = b;
+                self.head = (self.head + 1) % KLOG_CAPACITY;
+            }
+        }
+    }
+
+    /// The newest `max` bytes, oldest first.
+    fn tail(&self, max: usize) -> alloc::vec::Vec<u8> {
+        let count = self.len.min(max);
+        let start = (self.head + self.len - count) % KLOG_CAPACITY;
+        let mut result = alloc::vec::Vec::with_capacity(count);
+        let first = count.min(KLOG_CAPACITY - start);
+        result.extend_from_slice(&self.buf[start..start + first]);
+        result.extend_from_slice(&self.buf[..count - first]);
+        result
+    }
+}
 
 /// Write a formatted message to the kernel log buffer.
 ///
@@ -44,62 +73,34 @@ static IN_KLOG: AtomicBool = AtomicBool::new(false);
 /// ```ignore
 /// klog_fmt!(format_args!("Sound: Hello {}\n", name));
 /// ```
+///
+/// If the buffer is busy the message goes to the serial port instead.
 pub fn write_fmt(args: fmt::Arguments<'_>) {
-    if IN_KLOG.swap(true, Ordering::Acquire) {
-        // Reentrant call — fall back to serial output to avoid deadlock.
+    let Some(mut ring) = KLOG_BUF.try_lock() else {
         petroleum::serial::serial_log(args);
         return;
-    }
-    let mut guard = KLOG_BUF.lock();
-    let ring = &mut *guard;
-    let mut writer = KLogWriter { ring, pos: 0 };
-    let _ = fmt::Write::write_fmt(&mut writer, args);
-    drop(guard);
-    IN_KLOG.store(false, Ordering::Release);
+    };
+    let _ = fmt::Write::write_fmt(&mut KLogWriter { ring: &mut ring }, args);
 }
 
 /// Write a raw byte slice to the kernel log buffer.
+///
+/// This is the logger's hook, which has already copied the record to the
+/// serial port, so the bytes are simply dropped if the buffer is busy.
 pub fn write_bytes(bytes: &[u8]) {
-    if IN_KLOG.swap(true, Ordering::Acquire) {
-        // Reentrant call — fall back to serial output.
-        petroleum::serial::serial_log(format_args!(
-            "{}",
-            core::str::from_utf8(bytes).unwrap_or("(binary)")
-        ));
-        return;
-    }
-    let mut guard = KLOG_BUF.lock();
-    let ring = &mut *guard;
-    for &b in bytes {
-        if ring.len < KLOG_CAPACITY {
-            let idx = (ring.head + ring.len) % KLOG_CAPACITY;
-            ring.buf[idx] = b;
-            ring.len += 1;
-        } else {
-            // Overwrite oldest byte
-            ring.buf[ring.head] = b;
-            ring.head = (ring.head + 1) % KLOG_CAPACITY;
-        }
+    if let Some(mut ring) = KLOG_BUF.try_lock() {
+        ring.push(bytes);
     }
-    drop(guard);
-    IN_KLOG.store(false, Ordering::Release);
 }
 
 /// Return the entire kernel log as an owned `Vec<u8>`.
 pub fn snapshot() -> alloc::vec::Vec<u8> {
-    let guard = KLOG_BUF.lock();
-    let ring = &*guard;
-    let mut result = alloc::vec::Vec::with_capacity(ring.len);
-    if ring.len > 0 {
-        let end = ring.head + ring.len;
-        if end <= KLOG_CAPACITY {
-            result.extend_from_slice(&ring.buf[ring.head..end]);
-        } else {
-            result.extend_from_slice(&ring.buf[ring.head..KLOG_CAPACITY]);
-            result.extend_from_slice(&ring.buf[0..(end % KLOG_CAPACITY)]);
-        }
-    }
-    result
+    tail(KLOG_CAPACITY)
+}
+
+/// Return the newest `max` bytes of the kernel log, oldest first.
+pub fn tail(max: usize) -> alloc::vec::Vec<u8> {
+    KLOG_BUF.lock().tail(max)
 }
 
 /// Write kernel log to a string-sink callback without heap allocation.
@@ -114,26 +115,12 @@ pub fn write_to<F>(mut emit: F)
 where
     F: FnMut(&str),
 {
-    // Copy ring buffer contents while lock is held, then drop the guard
-    // before calling emit_utf8_lossy, so terminal I/O runs without keeping
+    // Copy the ring buffer out first, so terminal I/O runs without keeping
     // KLOG_BUF locked.
-    let snapshot = {
-        let guard = KLOG_BUF.lock();
-        let ring = &*guard;
-        if ring.len == 0 {
-            return;
-        }
-
-        let mut buf = alloc::vec::Vec::with_capacity(ring.len);
-        let end = ring.head + ring.len;
-        if end <= KLOG_CAPACITY {
-            buf.extend_from_slice(&ring.buf[ring.head..end]);
-        } else {
-            buf.extend_from_slice(&ring.buf[ring.head..KLOG_CAPACITY]);
-            buf.extend_from_slice(&ring.buf[0..(end % KLOG_CAPACITY)]);
-        }
-        buf
-    };
+    let snapshot = snapshot();
+    if snapshot.is_empty() {
+        return;
+    }
 
     emit_utf8_lossy(&mut emit, &snapshot);
 }
@@ -179,22 +166,11 @@ pub fn len() -> usize {
 
 struct KLogWriter<'a> {
     ring: &'a mut KLogRing,
-    pos: usize,
 }
 
-impl<'a> fmt::Write for KLogWriter<'a> {
+impl fmt::Write for KLogWriter<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for &b in s.as_bytes() {
-            if self.ring.len < KLOG_CAPACITY {
-                let idx = (self.ring.head + self.ring.len) % KLOG_CAPACITY;
-                self.ring.buf[idx] = b;
-                self.ring.len += 1;
-            } else {
-                self.ring.buf[self.ring.head] = b;
-                self.ring.head = (self.ring.head + 1) % KLOG_CAPACITY;
-            }
-        }
-        self.pos += s.len();
+        self.ring.push(s.as_bytes());
         Ok(())
     }
 }
@@ -251,3 +227,25 @@ pub fn flush_to_vfs_safe() {
         let _ = flush_to_vfs();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_overwrites_oldest_bytes_when_full() {
+        let mut ring = alloc::boxed::Box::new(KLogRing::new());
+        ring.push(b"abc");
+        assert_eq!(ring.tail(usize::MAX), b"abc");
+        assert_eq!(ring.tail(2), b"bc");
+
+        let filler = alloc::vec![b'x'; KLOG_CAPACITY - 3];
+        ring.push(&filler);
+        ring.push(b"yz");
+        assert_eq!(ring.len, KLOG_CAPACITY);
+        let all = ring.tail(usize::MAX);
+        assert_eq!(&all[..2], b"cx");
+        assert_eq!(&all[all.len() - 3..], b"xyz");
+        assert_eq!(ring.tail(3), b"xyz");
+    }
+}
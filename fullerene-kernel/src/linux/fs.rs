@@ -21,7 +21,7 @@ pub fn sys_read(rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
         let count_min = count.min(512);
         // Single byte reads are most common for terminal input
         if count == 1 {
-            if let Some(ch) = nitrogen::ps2::keyboard::read_char() {
+            if let Some(ch) = nitrogen::ps2::keyboard::try_read() {
                 if unsafe { copy_to_user(buf, &[ch]) }.is_err() {
                     return errno_code(EFAULT);
                 }
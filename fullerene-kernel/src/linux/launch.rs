@@ -1,8 +1,6 @@
 // Linux binary launcher
 use crate::loader::LoadError;
 use crate::process::ProcessId;
-use alloc::boxed::Box;
-use alloc::string::ToString;
 
 /// Launch the built-in test binary ("Hello from Linux!") to verify ABI.
 pub fn launch_test_binary() -> Result<ProcessId, LoadError> {
@@ -11,14 +9,11 @@ pub fn launch_test_binary() -> Result<ProcessId, LoadError> {
 
 /// Launch a Linux ELF binary from the VFS at `path`.
 pub fn launch_linux_binary(path: &str) -> Result<ProcessId, LoadError> {
-    // General-purpose callers can provide arbitrary paths, so retain a stable
-    // process label for the process table.
-    let static_name: &'static str = Box::leak(path.to_string().into_boxed_str());
-    launch_linux_binary_named(path, static_name)
+    launch_linux_binary_named(path, path.rsplit('/').next().unwrap_or(path))
 }
 
-/// Launch a Linux ELF binary from the VFS with a caller-owned static label.
-pub fn launch_linux_binary_named(path: &str, name: &'static str) -> Result<ProcessId, LoadError> {
+/// Launch a Linux ELF binary from the VFS under the process name `name`.
+pub fn launch_linux_binary_named(path: &str, name: &str) -> Result<ProcessId, LoadError> {
     let data = match crate::fs::read_entire_file(path) {
         Ok(d) => d,
         Err(_) => return Err(LoadError::InvalidFormat),
@@ -27,7 +22,7 @@ pub fn launch_linux_binary_named(path: &str, name: &'static str) -> Result<Proce
 }
 
 /// Launch a Linux ELF binary from raw bytes.
-pub fn launch_linux_from_data(data: &[u8], name: &'static str) -> Result<ProcessId, LoadError> {
+pub fn launch_linux_from_data(data: &[u8], name: &str) -> Result<ProcessId, LoadError> {
     crate::loader::load_program_with_runtime(data, name, true)
 }
 
@@ -44,7 +39,8 @@ pub fn launch_busybox() -> Result<ProcessId, LoadError> {
     ];
 
     for path in &locations {
-        if crate::contexts::vfs::exists(path) {
+        // `/init` is also where the initrd is mounted.
+        if crate::fs::vfs::stat(path).is_ok_and(|node| !node.is_dir) {
             log::info!("Found BusyBox at {}", path);
             return launch_linux_binary(path);
         }
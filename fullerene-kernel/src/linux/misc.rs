@@ -19,11 +19,10 @@ pub fn sys_arch_prctl(rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
     let code = args[0] as i32;
     let addr = args[1];
 
-    // MSR addresses for FS.base and GS.base
+    // MSR address for FS.base
     const MSR_FS_BASE: u32 = 0xC0000100;
-    const MSR_GS_BASE: u32 = 0xC0000101;
 
-    if matches!(code, ARCH_SET_FS | ARCH_SET_GS)
+    if (code == ARCH_SET_FS || code == ARCH_SET_GS)
         && (addr != 0
             && x86_64::VirtAddr::try_new(addr)
                 .map(|address| !petroleum::is_user_address(address))
@@ -50,19 +49,16 @@ pub fn sys_arch_prctl(rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
             }
             0
         }
+        // The user GS base waits in the kernel GS base MSR while the kernel
+        // runs (see `crate::percpu`).
         ARCH_SET_GS => {
-            unsafe {
-                x86_64::registers::model_specific::Msr::new(MSR_GS_BASE).write(addr);
-            }
+            crate::percpu::set_user_gs_base(addr);
             0
         }
         ARCH_GET_GS => {
-            if addr != 0 {
-                let val =
-                    unsafe { x86_64::registers::model_specific::Msr::new(MSR_GS_BASE).read() };
-                if unsafe { copy_val_to_user(addr, &val) }.is_err() {
-                    return errno_code(EFAULT);
-                }
+            let val = crate::percpu::user_gs_base();
+            if addr != 0 && unsafe { copy_val_to_user(addr, &val) }.is_err() {
+                return errno_code(EFAULT);
             }
             0
         }
@@ -217,7 +213,7 @@ pub fn sys_getrlimit(_rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
 linux_stub!(sys_setrlimit, 0);
 
 pub fn sys_sched_yield(_rt: &mut LinuxRuntime, _args: &[u64; 6]) -> u64 {
-    crate::process::yield_current();
+    crate::process::yield_turn();
     0
 }
 
@@ -250,26 +246,21 @@ pub fn sys_sysinfo(_rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
 linux_stub!(sys_prctl, 0);
 
 pub fn sys_futex(_rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
-    let _uaddr = args[0];
+    let uaddr = args[0];
     let op = args[1] as i32;
-    let _val = args[2] as i32;
-    let _uaddr2 = args[3];
-    let _val3 = args[4] as i32;
+    let val = args[2] as u32;
 
     const FUTEX_WAIT: i32 = 0;
     const FUTEX_WAKE: i32 = 1;
 
-    match op & 0xf {
-        FUTEX_WAIT => {
-            // In a real impl, this would block. For now, just return 0.
-            0
-        }
-        FUTEX_WAKE => {
-            // Return number of woken threads (0 for now)
-            0
-        }
-        _ => errno_code(ENOSYS),
-    }
+    // The private flag only narrows the wait queue; timeouts are ignored.
+    let result = match op & 0xf {
+        FUTEX_WAIT => crate::syscall::futex::syscall_futex_wait(uaddr, val as u64),
+        FUTEX_WAKE => crate::syscall::futex::syscall_futex_wake(uaddr, val as u64),
+        _ => return errno_code(ENOSYS),
+    };
+    // Native error codes are Linux errno values.
+    result.unwrap_or_else(|error| errno_code(error as i32))
 }
 
 linux_stub_errno!(sys_statfs, ENOSYS);
@@ -3,7 +3,6 @@ extern crate alloc;
 use super::numbers::*;
 use super::runtime::{LinuxRuntime, copy_user_string, copy_val_to_user, errno_code};
 use crate::process::{self, ProcessContext, ProcessId};
-use alloc::boxed::Box;
 use alloc::vec::Vec;
 use petroleum::page_table::types::PageTableHelper;
 use x86_64::PhysAddr;
@@ -57,64 +56,39 @@ pub fn sys_clone(rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
     };
 
     // Get parent info
-    let (parent_pt, parent_ctx) = process::SCHEDULER
-        .with_process(current_pid, |p| (p.page_table_phys_addr, p.context.clone()))
-        .unwrap_or((PhysAddr::new(0), Box::new(ProcessContext::default())));
-
-    // Clone page table
-    let cloned_table = {
-        let mut mgr_guard = crate::memory_management::get_memory_manager().lock();
-        let mgr = match mgr_guard.as_mut() {
-            Some(m) => m,
-            None => return errno_code(ENOMEM),
-        };
-        let alloc = unsafe { petroleum::page_table::constants::get_frame_allocator_mut() };
-        match mgr.clone_page_table(parent_pt.as_u64() as usize, alloc) {
-            Ok(addr) => addr,
-            Err(_) => return errno_code(ENOMEM),
-        }
-    };
-
-    let cloned_frame = x86_64::structures::paging::PhysFrame::containing_address(
-        x86_64::PhysAddr::new(cloned_table as u64),
-    );
+    let (parent_pt, parent_ctx, parent_privileged) = process::SCHEDULER
+        .with_process(current_pid, |p| {
+            (p.page_table_phys_addr, p.context.clone(), p.privileged)
+        })
+        .unwrap_or((PhysAddr::new(0), ProcessContext::default().boxed(), false));
 
-    let mut child_pt =
-        petroleum::page_table::process::ProcessPageTable::new_with_frame(cloned_frame);
-    let _ = petroleum::initializer::Initializable::init(&mut child_pt);
+    // Copy the address space as fork does, so that each side owns a
+    // reference to every page and exiting frees only its own.
+    let mut child_pt = match crate::memory_management::fork_process_page_table(parent_pt) {
+        Ok(pt) => pt,
+        Err(_) => return errno_code(ENOMEM),
+    };
+    let Some(cloned_frame) = child_pt.pml4_frame else {
+        return errno_code(ENOMEM);
+    };
 
-    // Allocate kernel stack
-    let stack_layout = core::alloc::Layout::from_size_align(4096, 16).unwrap();
-    let stack_ptr =
-        petroleum::common::memory::allocate_layout(stack_layout).unwrap_or(core::ptr::null_mut());
-    if stack_ptr.is_null() {
+    // Allocate kernel stack, sized as exit frees it
+    let Ok((stack_ptr, kernel_stack_top)) = crate::syscall::process::alloc_kernel_stack() else {
+        crate::memory_management::deallocate_process_page_table(cloned_frame);
         return errno_code(ENOMEM);
-    }
-    let kernel_stack_top = x86_64::VirtAddr::new(stack_ptr as u64 + 4096);
+    };
 
     let child_pid = process::SCHEDULER.allocate_pid();
 
-    // Remove inherited VDSO mapping (parent may have one at VDSO_USER_BASE)
-    let _ = child_pt.unmap_page(petroleum::vdso::VDSO_USER_BASE as usize);
-
     // Create child VDSO page
     let child_vdso = {
-        let mut fa_lock = crate::heap::FRAME_ALLOCATOR.lock();
-        let fa = match fa_lock.as_mut() {
-            Some(f) => f,
-            None => {
-                drop(fa_lock);
-                unsafe { petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout) };
-                crate::memory_management::deallocate_process_page_table(cloned_frame);
-                return errno_code(ENOMEM);
-            }
-        };
-        let vdso = crate::vdso::create_vdso_page(&mut child_pt, fa, child_pid.0);
-        drop(fa_lock);
+        let vdso = petroleum::page_table::constants::with_frame_allocator(|fa| {
+            crate::vdso::create_vdso_page(&mut child_pt, fa, child_pid.0)
+        });
         match vdso {
             Ok(v) => Some(v),
             Err(_) => {
-                unsafe { petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout) };
+                crate::syscall::process::free_kernel_stack(stack_ptr);
                 crate::memory_management::deallocate_process_page_table(cloned_frame);
                 return errno_code(ENOMEM);
             }
@@ -123,20 +97,26 @@ pub fn sys_clone(rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
 
     let child_process = process::Process {
         id: child_pid,
-        name: "linux-child",
+        name: process::ProcessName::new("linux-child"),
         state: process::ProcessState::Ready,
+        wake_tick: None,
+        priority: process::DEFAULT_PRIORITY,
+        ready_since: crate::scheduler::get_system_tick(),
+        cpu_time_us: 0,
+        pending_signals: 0,
         context: {
             let mut ctx = parent_ctx.clone();
             // Child returns 0 from clone
             ctx.regs[0] = 0;
             ctx
         },
-        page_table_phys_addr: x86_64::PhysAddr::new(cloned_table as u64),
+        page_table_phys_addr: cloned_frame.start_address(),
         page_table: Some(alloc::boxed::Box::new(child_pt)),
         kernel_stack: kernel_stack_top,
         user_stack: x86_64::VirtAddr::new(0),
         entry_point: x86_64::VirtAddr::new(0),
         is_user: true,
+        privileged: parent_privileged,
         exit_code: None,
         parent_id: Some(current_pid),
         task_data: 0,
@@ -397,8 +377,7 @@ pub fn sys_wait4(_rt: &mut LinuxRuntime, args: &[u64; 6]) -> u64 {
         let mut found = None;
         process::SCHEDULER.with_list(|list| {
             for (id, p) in list.iter() {
-                if p.parent_id == Some(current_pid) && p.state == process::ProcessState::Terminated
-                {
+                if p.parent_id == Some(current_pid) && p.state == process::ProcessState::Zombie {
                     found = Some(*id);
                     break;
                 }
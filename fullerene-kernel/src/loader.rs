@@ -14,6 +14,7 @@
 use crate::process;
 use core::ptr;
 use goblin::elf::program_header::{PF_W, PF_X, PT_LOAD};
+use petroleum::page_table::constants::with_frame_allocator;
 use petroleum::page_table::process::ProcessPageTable;
 use petroleum::page_table::types::PageTableHelper;
 use x86_64::structures::paging::FrameAllocator;
@@ -22,17 +23,14 @@ pub const PROGRAM_LOAD_BASE: u64 = 0x400000; // 4MB base address for user progra
 
 /// Load a program from raw bytes and create a process for it using goblin.
 /// If `linux_abi` is true, attaches a LinuxRuntime for Linux ABI emulation.
-pub fn load_program(
-    image_data: &[u8],
-    name: &'static str,
-) -> Result<process::ProcessId, LoadError> {
+pub fn load_program(image_data: &[u8], name: &str) -> Result<process::ProcessId, LoadError> {
     load_program_inner(image_data, name, false)
 }
 
 /// Load a program, optionally with Linux ABI emulation.
 pub fn load_program_with_runtime(
     image_data: &[u8],
-    name: &'static str,
+    name: &str,
     is_linux: bool,
 ) -> Result<process::ProcessId, LoadError> {
     load_program_inner(image_data, name, is_linux)
@@ -40,7 +38,7 @@ pub fn load_program_with_runtime(
 
 fn load_program_inner(
     image_data: &[u8],
-    name: &'static str,
+    name: &str,
     is_linux: bool,
 ) -> Result<process::ProcessId, LoadError> {
     // Parse ELF using goblin
@@ -69,127 +67,314 @@ fn load_program_inner(
     }
 
     // Load program segments into the process page table.
-    //
-    // We write each PT_LOAD segment into the freshly allocated physical
-    // frame using `physical_to_virtual`, which gives us a kernel-visible
-    // pointer to the user-space page.  This avoids switching CR3 to the
-    // process page table during load and keeps the kernel's address space
-    // active.
     process::SCHEDULER
         .with_process(pid, |p| {
             let process_page_table = p.page_table.as_mut().ok_or(LoadError::InvalidFormat)?;
+            map_segments(process_page_table, &elf, image_data, 0)
+        })
+        .ok_or(LoadError::InvalidFormat)??;
 
-            for ph in &elf.program_headers {
-                if ph.p_type != PT_LOAD {
-                    continue;
-                }
-                let file_offset = ph.p_offset as usize;
-                let file_size = ph.p_filesz as usize;
-                let mem_size = ph.p_memsz as usize;
-                let vaddr = ph.p_vaddr as u64;
-
-                // Check file range with overflow protection
-                let file_end = file_offset
-                    .checked_add(file_size)
-                    .ok_or(LoadError::InvalidFormat)?;
-                if file_end > image_data.len() {
-                    return Err(LoadError::InvalidFormat);
-                }
-                if mem_size < file_size {
-                    return Err(LoadError::InvalidFormat);
-                }
-                // Check virtual address range with overflow protection
-                let vaddr_end = vaddr
-                    .checked_add(mem_size as u64)
-                    .ok_or(LoadError::InvalidFormat)?;
-                if mem_size == 0 {
-                    return Err(LoadError::InvalidFormat);
-                }
-                let start_addr = x86_64::VirtAddr::new(vaddr);
-                let end_addr = x86_64::VirtAddr::new(vaddr_end - 1);
-                if !petroleum::is_user_address(start_addr) || !petroleum::is_user_address(end_addr)
-                {
-                    return Err(LoadError::UnsupportedArchitecture);
-                }
-                let num_pages = petroleum::common::utils::calculate_pages(mem_size);
+    Ok(pid)
+}
 
-                // Check that the virtual address range is not already mapped.
-                for page_idx in 0..num_pages {
-                    let page_vaddr = x86_64::VirtAddr::new(
-                        petroleum::common::utils::calculate_offset_address(vaddr, page_idx),
-                    );
-                    let ppt: &ProcessPageTable = &**process_page_table;
-                    if PageTableHelper::translate_address(ppt, page_vaddr.as_u64() as usize).is_ok()
-                    {
-                        return Err(LoadError::AddressAlreadyMapped);
-                    }
-                }
+/// Map every PT_LOAD segment of `elf`, shifted by `bias`, into `page_table`.
+///
+/// We write each segment into the freshly allocated physical frame using
+/// `physical_to_virtual`, which gives us a kernel-visible pointer to the
+/// user-space page.  This avoids switching CR3 to the process page table
+/// during load and keeps the kernel's address space active.
+fn map_segments(
+    page_table: &mut ProcessPageTable,
+    elf: &goblin::elf::Elf,
+    image_data: &[u8],
+    bias: u64,
+) -> Result<(), LoadError> {
+    for ph in &elf.program_headers {
+        if ph.p_type != PT_LOAD {
+            continue;
+        }
+        let file_offset = ph.p_offset as usize;
+        let file_size = ph.p_filesz as usize;
+        let mem_size = ph.p_memsz as usize;
+        let vaddr = ph
+            .p_vaddr
+            .checked_add(bias)
+            .ok_or(LoadError::InvalidFormat)?;
+
+        // Check file range with overflow protection
+        let file_end = file_offset
+            .checked_add(file_size)
+            .ok_or(LoadError::InvalidFormat)?;
+        if file_end > image_data.len() {
+            return Err(LoadError::InvalidFormat);
+        }
+        if mem_size < file_size {
+            return Err(LoadError::InvalidFormat);
+        }
+        // Check virtual address range with overflow protection
+        let vaddr_end = vaddr
+            .checked_add(mem_size as u64)
+            .ok_or(LoadError::InvalidFormat)?;
+        if mem_size == 0 {
+            return Err(LoadError::InvalidFormat);
+        }
+        let start_addr = x86_64::VirtAddr::new(vaddr);
+        let end_addr = x86_64::VirtAddr::new(vaddr_end - 1);
+        if !petroleum::is_user_address(start_addr) || !petroleum::is_user_address(end_addr) {
+            return Err(LoadError::UnsupportedArchitecture);
+        }
+        let num_pages = petroleum::common::utils::calculate_pages(mem_size);
 
-                // For each page needed by the segment, allocate a physical frame,
-                // map it into the process page table, then write the segment data
-                // via the kernel's direct-mapped view of the frame.
-                use x86_64::structures::paging::PageTableFlags as X86Flags;
-                for page_idx in 0..num_pages {
-                    let page_vaddr = x86_64::VirtAddr::new(
-                        petroleum::common::utils::calculate_offset_address(vaddr, page_idx),
+        // Check that the virtual address range is not already mapped.
+        for page_idx in 0..num_pages {
+            let page_vaddr = x86_64::VirtAddr::new(
+                petroleum::common::utils::calculate_offset_address(vaddr, page_idx),
+            );
+            let ppt: &ProcessPageTable = page_table;
+            if PageTableHelper::translate_address(ppt, page_vaddr.as_u64() as usize).is_ok() {
+                return Err(LoadError::AddressAlreadyMapped);
+            }
+        }
+
+        // For each page needed by the segment, allocate a physical frame,
+        // map it into the process page table, then write the segment data
+        // via the kernel's direct-mapped view of the frame.
+        use x86_64::structures::paging::PageTableFlags as X86Flags;
+        for page_idx in 0..num_pages {
+            let page_vaddr = x86_64::VirtAddr::new(
+                petroleum::common::utils::calculate_offset_address(vaddr, page_idx),
+            );
+            let frame = with_frame_allocator(|allocator| allocator.allocate_frame())
+                .ok_or(LoadError::OutOfMemory)?;
+            let mut page_flags = X86Flags::PRESENT | X86Flags::USER_ACCESSIBLE;
+            if (ph.p_flags & PF_W) != 0 {
+                page_flags |= X86Flags::WRITABLE;
+            }
+            if (ph.p_flags & PF_X) == 0 {
+                page_flags |= X86Flags::NO_EXECUTE;
+            }
+            PageTableHelper::map_page(
+                page_table,
+                page_vaddr.as_u64() as usize,
+                frame.start_address().as_u64() as usize,
+                page_flags,
+                unsafe { petroleum::page_table::constants::get_frame_allocator_mut() },
+            )
+            .map_err(|_| LoadError::OutOfMemory)?;
+
+            // Write directly through the kernel's direct-mapped view of
+            // the physical frame.  This does NOT require a CR3 switch —
+            // the kernel's page table always maps all physical memory at
+            // `physical_memory_offset`.
+            let frame_phys = frame.start_address().as_u64() as usize;
+            let frame_vaddr = petroleum::common::memory::physical_to_virtual(frame_phys);
+            let page_offset = (page_idx * 4096) as u64;
+            unsafe {
+                if page_offset < file_size as u64 {
+                    let copy_len = ((file_size as u64) - page_offset).min(4096) as usize;
+                    let src_offset = (file_offset as u64 + page_offset) as usize;
+                    ptr::copy_nonoverlapping(
+                        image_data[src_offset..src_offset + copy_len].as_ptr(),
+                        frame_vaddr as *mut u8,
+                        copy_len,
                     );
-                    let frame = crate::heap::FRAME_ALLOCATOR
-                        .lock()
-                        .as_mut()
-                        .ok_or(LoadError::OutOfMemory)?
-                        .allocate_frame()
-                        .ok_or(LoadError::OutOfMemory)?;
-                    let mut page_flags = X86Flags::PRESENT | X86Flags::USER_ACCESSIBLE;
-                    if (ph.p_flags & PF_W) != 0 {
-                        page_flags |= X86Flags::WRITABLE;
-                    }
-                    if (ph.p_flags & PF_X) == 0 {
-                        page_flags |= X86Flags::NO_EXECUTE;
-                    }
-                    PageTableHelper::map_page(
-                        &mut **process_page_table,
-                        page_vaddr.as_u64() as usize,
-                        frame.start_address().as_u64() as usize,
-                        page_flags,
-                        unsafe { petroleum::page_table::constants::get_frame_allocator_mut() },
-                    )
-                    .map_err(|_| LoadError::OutOfMemory)?;
-
-                    // Write directly through the kernel's direct-mapped view of
-                    // the physical frame.  This does NOT require a CR3 switch —
-                    // the kernel's page table always maps all physical memory at
-                    // `physical_memory_offset`.
-                    let frame_phys = frame.start_address().as_u64() as usize;
-                    let frame_vaddr = petroleum::common::memory::physical_to_virtual(frame_phys);
-                    let page_offset = (page_idx * 4096) as u64;
-                    unsafe {
-                        if page_offset < file_size as u64 {
-                            let copy_len = ((file_size as u64) - page_offset).min(4096) as usize;
-                            let src_offset = (file_offset as u64 + page_offset) as usize;
-                            ptr::copy_nonoverlapping(
-                                image_data[src_offset..src_offset + copy_len].as_ptr(),
-                                frame_vaddr as *mut u8,
-                                copy_len,
-                            );
-                            if copy_len < 4096 {
-                                ptr::write_bytes(
-                                    (frame_vaddr as *mut u8).add(copy_len),
-                                    0,
-                                    4096 - copy_len,
-                                );
-                            }
-                        } else {
-                            // Zero-fill BSS page entirely.
-                            ptr::write_bytes(frame_vaddr as *mut u8, 0, 4096);
-                        }
+                    if copy_len < 4096 {
+                        ptr::write_bytes(
+                            (frame_vaddr as *mut u8).add(copy_len),
+                            0,
+                            4096 - copy_len,
+                        );
                     }
+                } else {
+                    // Zero-fill BSS page entirely.
+                    ptr::write_bytes(frame_vaddr as *mut u8, 0, 4096);
                 }
             }
-            Ok(())
-        })
-        .ok_or(LoadError::InvalidFormat)??;
+        }
+    }
+    Ok(())
+}
 
-    Ok(pid)
+/// Top of the initial user stack of an image started by [`exec_image`].
+pub const EXEC_STACK_TOP: u64 = 0x7fff_ffff_f000;
+/// Size of the initial user stack of an image started by [`exec_image`].
+pub const EXEC_STACK_SIZE: u64 = 64 * 1024;
+
+/// A program image loaded into a fresh address space, ready to replace the
+/// image of the calling process.
+pub struct ExecImage {
+    pub page_table: ProcessPageTable,
+    pub entry: x86_64::VirtAddr,
+    pub stack_pointer: x86_64::VirtAddr,
+}
+
+/// Whether `[start, start + len)` lies entirely in the lower (user) half.
+fn is_user_range(start: u64, len: u64) -> bool {
+    len != 0
+        && start
+            .checked_add(len - 1)
+            .and_then(|last| x86_64::VirtAddr::try_new(last).ok())
+            .is_some_and(petroleum::is_user_address)
+}
+
+/// Check that `elf` is a static x86-64 executable whose segments stay in
+/// user space, and return the bias its segments are loaded at.
+///
+/// ET_DYN images (static PIE) are placed at [`PROGRAM_LOAD_BASE`]; they are
+/// expected to apply their own relative relocations on startup.
+fn exec_load_bias(elf: &goblin::elf::Elf) -> Result<u64, LoadError> {
+    use goblin::elf::header::{EM_X86_64, ET_DYN, ET_EXEC};
+    use goblin::elf::program_header::PT_INTERP;
+
+    if !elf.is_64 || !elf.little_endian || elf.header.e_machine != EM_X86_64 {
+        return Err(LoadError::UnsupportedArchitecture);
+    }
+    let bias = match elf.header.e_type {
+        ET_EXEC => 0,
+        ET_DYN => PROGRAM_LOAD_BASE,
+        _ => return Err(LoadError::NotExecutable),
+    };
+    // Dynamically linked programs need an interpreter we do not provide.
+    if elf.program_headers.iter().any(|ph| ph.p_type == PT_INTERP) {
+        return Err(LoadError::NotExecutable);
+    }
+
+    let mut segments = elf
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .peekable();
+    if segments.peek().is_none() {
+        return Err(LoadError::InvalidFormat);
+    }
+    for ph in segments {
+        let start = ph
+            .p_vaddr
+            .checked_add(bias)
+            .ok_or(LoadError::KernelAddress)?;
+        if !is_user_range(start, ph.p_memsz) {
+            return Err(LoadError::KernelAddress);
+        }
+    }
+    Ok(bias)
+}
+
+/// Build the System V initial stack for `args` below `stack_top`.
+///
+/// Returns the bytes from the new stack pointer up to `stack_top` and the
+/// stack pointer itself: `argc`, the `argv` pointers and their terminating
+/// null, an empty `envp`, an empty auxiliary vector (`AT_NULL`), then the
+/// argument strings.  The stack pointer is 16-byte aligned.
+fn initial_stack(args: &[&[u8]], stack_top: u64) -> (alloc::vec::Vec<u8>, u64) {
+    let strings_len: u64 = args.iter().map(|arg| arg.len() as u64 + 1).sum();
+    let strings_base = stack_top - strings_len;
+    // argc, argv[..], NULL, envp NULL, AT_NULL type and value.
+    let words = args.len() as u64 + 5;
+    let mut stack_pointer = (strings_base & !0xF) - words * 8;
+    stack_pointer &= !0xF;
+
+    let mut stack = alloc::vec![0u8; (stack_top - stack_pointer) as usize];
+    let mut put_word = |index: u64, value: u64| {
+        let at = (index * 8) as usize;
+        stack[at..at + 8].copy_from_slice(&value.to_le_bytes());
+    };
+    put_word(0, args.len() as u64);
+    let mut string_addr = strings_base;
+    for (index, arg) in args.iter().enumerate() {
+        put_word(index as u64 + 1, string_addr);
+        string_addr += arg.len() as u64 + 1;
+    }
+
+    let mut at = (strings_base - stack_pointer) as usize;
+    for arg in args {
+        stack[at..at + arg.len()].copy_from_slice(arg);
+        at += arg.len() + 1;
+    }
+    (stack, stack_pointer)
+}
+
+/// Map a fresh [`EXEC_STACK_SIZE`] stack ending at [`EXEC_STACK_TOP`] and
+/// write the initial stack for `args` into it.  Returns the stack pointer.
+fn map_initial_stack(page_table: &mut ProcessPageTable, args: &[&[u8]]) -> Result<u64, LoadError> {
+    use x86_64::structures::paging::PageTableFlags as X86Flags;
+
+    let (contents, stack_pointer) = initial_stack(args, EXEC_STACK_TOP);
+    if contents.len() as u64 > EXEC_STACK_SIZE {
+        return Err(LoadError::InvalidFormat);
+    }
+    let stack_base = EXEC_STACK_TOP - EXEC_STACK_SIZE;
+    for page_idx in 0..EXEC_STACK_SIZE / 4096 {
+        let page_vaddr = petroleum::common::utils::calculate_offset_address(stack_base, page_idx);
+        let frame = with_frame_allocator(|allocator| allocator.allocate_frame())
+            .ok_or(LoadError::OutOfMemory)?;
+        PageTableHelper::map_page(
+            page_table,
+            page_vaddr as usize,
+            frame.start_address().as_u64() as usize,
+            X86Flags::PRESENT
+                | X86Flags::WRITABLE
+                | X86Flags::USER_ACCESSIBLE
+                | X86Flags::NO_EXECUTE,
+            unsafe { petroleum::page_table::constants::get_frame_allocator_mut() },
+        )
+        .map_err(|_| LoadError::MappingFailed)?;
+
+        // Zero the page, then copy in whatever part of the initial stack
+        // falls inside it.
+        let frame_vaddr =
+            petroleum::common::memory::physical_to_virtual(frame.start_address().as_u64() as usize);
+        let page_end = page_vaddr + 4096;
+        unsafe {
+            ptr::write_bytes(frame_vaddr as *mut u8, 0, 4096);
+            if page_end > stack_pointer {
+                let from = page_vaddr.max(stack_pointer);
+                let src = (from - stack_pointer) as usize;
+                let len = (page_end - from) as usize;
+                ptr::copy_nonoverlapping(
+                    contents[src..src + len].as_ptr(),
+                    (frame_vaddr as *mut u8).add((from - page_vaddr) as usize),
+                    len,
+                );
+            }
+        }
+    }
+    Ok(stack_pointer)
+}
+
+/// Load a static x86-64 ELF executable into a new address space for `exec`.
+///
+/// Segments are mapped with the permissions from their program-header
+/// flags and a stack holding `args` as `argv` is set up below
+/// [`EXEC_STACK_TOP`].  The caller installs the returned page table in
+/// place of the current one; on error nothing of the new image is left
+/// behind except its page-table frames.
+pub fn exec_image(image_data: &[u8], args: &[&[u8]]) -> Result<ExecImage, LoadError> {
+    let elf = goblin::elf::Elf::parse(image_data).map_err(|_| LoadError::InvalidFormat)?;
+    let bias = exec_load_bias(&elf)?;
+    let entry = elf
+        .header
+        .e_entry
+        .checked_add(bias)
+        .filter(|&entry| is_user_range(entry, 1))
+        .ok_or(LoadError::KernelAddress)?;
+
+    let mut page_table = crate::memory_management::create_process_page_table()?;
+    let stack_pointer = map_segments(&mut page_table, &elf, image_data, bias)
+        .and_then(|()| map_initial_stack(&mut page_table, args));
+    match stack_pointer {
+        Ok(stack_pointer) => Ok(ExecImage {
+            page_table,
+            entry: x86_64::VirtAddr::new(entry),
+            stack_pointer: x86_64::VirtAddr::new(stack_pointer),
+        }),
+        Err(error) => {
+            if let Some(pml4_frame) = page_table.pml4_frame() {
+                drop(page_table);
+                crate::memory_management::deallocate_process_page_table(pml4_frame);
+            }
+            Err(error)
+        }
+    }
 }
 
 /// Load error types
@@ -202,6 +387,8 @@ pub enum LoadError {
     MappingFailed,
     AddressAlreadyMapped,
     FileNotFound,
+    /// A segment or the entry point lies outside user space.
+    KernelAddress,
 }
 
 impl From<LoadError> for petroleum::common::logging::SystemError {
@@ -213,6 +400,7 @@ impl From<LoadError> for petroleum::common::logging::SystemError {
                 petroleum::common::logging::SystemError::MappingFailed
             }
             LoadError::FileNotFound => petroleum::common::logging::SystemError::FileNotFound,
+            LoadError::KernelAddress => petroleum::common::logging::SystemError::InvalidArgument,
             LoadError::MappingFailed => petroleum::common::logging::SystemError::MappingFailed,
             LoadError::NotExecutable | LoadError::UnsupportedArchitecture => {
                 petroleum::common::logging::SystemError::LoadFailed
@@ -263,4 +451,81 @@ mod tests {
         let invalid_data = [0u8; 64];
         assert!(load_program(&invalid_data, "test").is_err());
     }
+
+    /// A minimal ELF64 image with one PT_LOAD segment at `vaddr`.
+    fn elf_image(e_type: u16, machine: u16, vaddr: u64) -> alloc::vec::Vec<u8> {
+        let mut image = alloc::vec![0u8; 64 + 56];
+        image[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        image[16..18].copy_from_slice(&e_type.to_le_bytes());
+        image[18..20].copy_from_slice(&machine.to_le_bytes());
+        image[20..24].copy_from_slice(&1u32.to_le_bytes());
+        image[24..32].copy_from_slice(&vaddr.to_le_bytes());
+        image[32..40].copy_from_slice(&64u64.to_le_bytes());
+        image[52..54].copy_from_slice(&64u16.to_le_bytes());
+        image[54..56].copy_from_slice(&56u16.to_le_bytes());
+        image[56..58].copy_from_slice(&1u16.to_le_bytes());
+        let ph = &mut image[64..];
+        ph[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        ph[4..8].copy_from_slice(&5u32.to_le_bytes());
+        ph[16..24].copy_from_slice(&vaddr.to_le_bytes());
+        ph[32..40].copy_from_slice(&120u64.to_le_bytes());
+        ph[40..48].copy_from_slice(&0x1000u64.to_le_bytes());
+        ph[48..56].copy_from_slice(&0x1000u64.to_le_bytes());
+        image
+    }
+
+    fn load_bias(image: &[u8]) -> Result<u64, LoadError> {
+        exec_load_bias(&goblin::elf::Elf::parse(image).unwrap())
+    }
+
+    #[test]
+    fn exec_accepts_static_x86_64_executables() {
+        use goblin::elf::header::{EM_AARCH64, EM_X86_64, ET_DYN, ET_EXEC, ET_REL};
+
+        assert!(matches!(
+            load_bias(&elf_image(ET_EXEC, EM_X86_64, 0x401000)),
+            Ok(0)
+        ));
+        assert!(matches!(
+            load_bias(&elf_image(ET_DYN, EM_X86_64, 0)),
+            Ok(PROGRAM_LOAD_BASE)
+        ));
+        assert!(matches!(
+            load_bias(&elf_image(ET_REL, EM_X86_64, 0x401000)),
+            Err(LoadError::NotExecutable)
+        ));
+        assert!(matches!(
+            load_bias(&elf_image(ET_EXEC, EM_AARCH64, 0x401000)),
+            Err(LoadError::UnsupportedArchitecture)
+        ));
+        assert!(matches!(
+            load_bias(&elf_image(ET_EXEC, EM_X86_64, 0xffff_8000_0000_0000)),
+            Err(LoadError::KernelAddress)
+        ));
+        assert!(matches!(
+            load_bias(&elf_image(ET_EXEC, EM_X86_64, 0x7fff_ffff_f800)),
+            Err(LoadError::KernelAddress)
+        ));
+    }
+
+    #[test]
+    fn initial_stack_follows_the_system_v_layout() {
+        let top = 0x8000;
+        let (stack, sp) = initial_stack(&[b"prog", b"-v"], top);
+        let word =
+            |index: usize| u64::from_le_bytes(stack[index * 8..index * 8 + 8].try_into().unwrap());
+        let string_at = |addr: u64| {
+            let at = (addr - sp) as usize;
+            let len = stack[at..].iter().position(|&b| b == 0).unwrap();
+            &stack[at..at + len]
+        };
+
+        assert!(sp.is_multiple_of(16));
+        assert_eq!(stack.len() as u64, top - sp);
+        assert_eq!(word(0), 2);
+        assert_eq!(string_at(word(1)), b"prog");
+        assert_eq!(string_at(word(2)), b"-v");
+        // argv terminator, empty envp and AT_NULL.
+        assert_eq!([word(3), word(4), word(5), word(6)], [0; 4]);
+    }
 }
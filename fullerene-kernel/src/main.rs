@@ -130,9 +130,24 @@ mod panic_screen {
 }
 
 // ---- Custom panic handler (replaces petroleum::define_panic_handler!) ----
+#[cfg(all(any(target_os = "none", target_os = "uefi"), not(test)))]
+static PANICKING: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
 #[cfg(all(any(target_os = "none", target_os = "uefi"), not(test)))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
+    let regs = petroleum::debug::RegisterSnapshot::capture();
+    x86_64::instructions::interrupts::disable();
+
+    // A panic while reporting a panic only gets the message, so the report
+    // code cannot recurse into itself.
+    if PANICKING.swap(true, core::sync::atomic::Ordering::SeqCst) {
+        petroleum::serial::_print(format_args!("\n== NESTED PANIC ==\n  {}\n", info));
+        loop {
+            x86_64::instructions::hlt();
+        }
+    }
+
     crate::boot_stage::set_boot_stage(crate::boot_stage::BootStage::Panic);
 
     // ── Draw diagnostic to framebuffer / VGA ──
@@ -149,8 +164,26 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
         ));
     }
     petroleum::serial::_print(format_args!("  {}\n", info));
+    petroleum::serial::_print(format_args!("Registers:\n{}", regs));
+
+    let mut collector = petroleum::debug::BacktraceCollector::new();
+    collector.capture_from(regs.rbp, regs.rsp);
+    petroleum::serial::_print(format_args!("Backtrace:\n"));
+    for (i, entry) in collector.entries().iter().enumerate() {
+        petroleum::serial::_print(format_args!(
+            "  [{}] {}\n",
+            i,
+            crate::symbols::ReturnAddress(entry.ip)
+        ));
+    }
     petroleum::serial::_print(format_args!("==================================\n"));
 
+    // Under `flasks --test`, end the run now instead of waiting out the
+    // timeout.
+    if crate::boot::cmdline_param("test").is_some() {
+        crate::power::exit_qemu(crate::power::PANIC_EXIT_CODE);
+    }
+
     loop {
         x86_64::instructions::hlt();
     }
@@ -192,15 +225,19 @@ pub mod linux;
 pub mod loader;
 pub mod memory_management;
 pub mod metrics;
+pub mod percpu;
 pub mod ports;
+pub mod power;
 pub mod process;
 pub mod scheduler;
 pub mod scheduler_context;
 pub mod shell;
 pub mod slab;
 pub mod smp;
+pub mod symbols;
 pub mod syscall;
 pub mod task;
+pub mod tty;
 mod user_memory;
 pub mod vdso;
 
@@ -1,4 +1,7 @@
 use crate::memory_management::process_memory::ProcessMemoryManagerImpl;
+use crate::memory_management::virtual_ranges::{
+    KERNEL_DYNAMIC_END, KERNEL_DYNAMIC_START, VirtualRanges,
+};
 use petroleum::common::logging::{SystemError, SystemResult};
 use petroleum::graphics::framebuffer_mapper::{CacheMode, FramebufferMapper};
 use petroleum::initializer::{
@@ -24,6 +27,8 @@ pub struct UnifiedMemoryManager {
     pub(crate) process_managers: alloc::vec::Vec<Option<ProcessMemoryManagerImpl>>,
     pub(crate) current_process: usize,
     pub(crate) initialized: bool,
+    /// Free ranges of the kernel dynamic virtual window.
+    pub(crate) kernel_virtual: VirtualRanges,
 }
 
 impl UnifiedMemoryManager {
@@ -134,7 +139,11 @@ impl UnifiedMemoryManager {
                 virtual_addr,
                 size
             );
-            return Ok(());
+            // The direct map is built write-back for RAM; device registers
+            // read through it must not be cached.
+            return self
+                .page_table_manager
+                .set_cache_mode(virtual_addr, size, CacheMode::Uncached);
         }
         if !self.map_framebuffer_region(
             physical_addr as u64,
@@ -159,7 +168,7 @@ impl UnifiedMemoryManager {
         if !self.initialized {
             return false;
         }
-        let flags = Self::cache_flags(cache)
+        let flags = cache.page_table_flags()
             | PageFlags::PRESENT
             | PageFlags::WRITABLE
             | PageFlags::NO_EXECUTE;
@@ -183,20 +192,6 @@ impl UnifiedMemoryManager {
         true
     }
 
-    fn cache_flags(mode: CacheMode) -> PageFlags {
-        match mode {
-            // Uncached: PCD=1 (NO_CACHE) + PWT=0 → UC- (or UC if MTRR says UC)
-            CacheMode::Uncached => PageFlags::NO_CACHE,
-            // WriteCombining: PCD=0 + PWT=1 selects PAT slot 1.
-            // `configure_framebuffer_pat` programs PAT slot 1 to WC. Combined with MTRR UC on
-            // PCI MMIO frames, the effective type is WC — safe for framebuffer
-            // and won't #GP on InsydeH2O.
-            CacheMode::WriteCombining => PageFlags::WRITE_THROUGH,
-            // WriteBack: both PCD/PWT=0 → WB via PAT default
-            CacheMode::WriteBack => PageFlags::empty(),
-        }
-    }
-
     pub fn new() -> Self {
         Self {
             page_table_manager: ProcessPageTable::new(),
@@ -204,6 +199,7 @@ impl UnifiedMemoryManager {
             process_managers: alloc::vec::Vec::new(),
             current_process: 0,
             initialized: false,
+            kernel_virtual: VirtualRanges::new(KERNEL_DYNAMIC_START, KERNEL_DYNAMIC_END),
         }
     }
 
@@ -269,7 +265,9 @@ impl UnifiedMemoryManager {
                 let frame = x86_64::structures::paging::PhysFrame::<Size4KiB>::containing_address(
                     phys_addr_val,
                 );
-                let flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+                let flags = petroleum::page_table::supported_flags(
+                    PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE,
+                );
 
                 let mapper = self
                     .page_table_manager
@@ -325,12 +323,11 @@ impl FramebufferMapper for UnifiedMemoryManager {
             return None;
         }
         let pages = (size + 4095) / 4096;
-        let virt_base = crate::memory_management::kernel_space::find_free_virtual_address(
-            (pages * 4096) as u64,
-        )? as u64;
+        let virt_base = self.kernel_virtual.allocate(pages * 4096)? as u64;
         if self.map_framebuffer_region(phys_addr, virt_base, size, cache) {
             Some(virt_base)
         } else {
+            let _ = self.kernel_virtual.free(virt_base as usize, pages * 4096);
             None
         }
     }
@@ -341,6 +338,9 @@ impl FramebufferMapper for UnifiedMemoryManager {
             // Use non-freeing unmap for device-backed memory
             let _ = self.safe_unmap_page_no_free((virt_addr + (i * 4096) as u64) as usize);
         }
+        if (KERNEL_DYNAMIC_START..KERNEL_DYNAMIC_END).contains(&(virt_addr as usize)) {
+            let _ = self.kernel_virtual.free(virt_addr as usize, pages * 4096);
+        }
     }
 }
 
@@ -352,20 +352,29 @@ impl MemoryManager for UnifiedMemoryManager {
             return Err(SystemError::InternalError);
         }
         let page_size = self.page_size();
-        let total_virt_pages = count + 2;
-        let virtual_addr_base = crate::memory_management::kernel_space::find_free_virtual_address(
-            total_virt_pages as u64 * page_size as u64,
-        )
-        .ok_or(SystemError::MemOutOfMemory)?;
-
-        let frame_addr = unsafe { petroleum::page_table::constants::get_frame_allocator_mut() }
-            .allocate_contiguous_frames(count)? as usize;
+        // One unmapped guard page on either side of the data pages.
+        let total_virt_size = (count + 2) * page_size;
+        let virtual_addr_base = self
+            .kernel_virtual
+            .allocate(total_virt_size)
+            .ok_or(SystemError::MemOutOfMemory)?;
+
+        let frame_addr =
+            match unsafe { petroleum::page_table::constants::get_frame_allocator_mut() }
+                .allocate_contiguous_frames(count)
+            {
+                Ok(frame_addr) => frame_addr as usize,
+                Err(error) => {
+                    let _ = self.kernel_virtual.free(virtual_addr_base, total_virt_size);
+                    return Err(error);
+                }
+            };
         let data_virt_addr = virtual_addr_base + page_size;
         for i in 0..count {
             self.safe_map_page(
                 data_virt_addr + i * page_size,
                 frame_addr + i * page_size,
-                PageFlags::PRESENT | PageFlags::WRITABLE,
+                PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE,
             )?;
         }
         Ok(data_virt_addr)
@@ -388,6 +397,12 @@ impl MemoryManager for UnifiedMemoryManager {
         let _ = self
             .page_table_manager
             .unmap_page(address + (count * page_size));
+
+        let virtual_addr_base = address.saturating_sub(page_size);
+        if (KERNEL_DYNAMIC_START..KERNEL_DYNAMIC_END).contains(&virtual_addr_base) {
+            self.kernel_virtual
+                .free(virtual_addr_base, (count + 2) * page_size)?;
+        }
         Ok(())
     }
 
@@ -412,7 +427,7 @@ impl MemoryManager for UnifiedMemoryManager {
             self.safe_map_page(
                 virtual_addr + i * page_size,
                 physical_addr + i * page_size,
-                PageFlags::PRESENT | PageFlags::WRITABLE,
+                PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE,
             )?;
         }
         Ok(())
@@ -752,7 +767,7 @@ impl Initializable for UnifiedMemoryManager {
 
 impl ErrorLogging for UnifiedMemoryManager {
     fn log_error(&self, error: &SystemError, context: &'static str) {
-        log::error!("SystemError({}): {}", *error as u32, context);
+        log::error!("{}: {}", context, error);
     }
     fn log_warning(&self, message: &'static str) {
         log::warn!("{}", message);
@@ -16,10 +16,19 @@ pub mod convenience;
 pub mod kernel_space;
 pub mod manager;
 pub mod process_memory;
+pub mod virtual_ranges;
 
 pub use manager::UnifiedMemoryManager;
 pub use process_memory::*;
 
+/// Available PTE bit marking a user page that was writable before a fork
+/// and is now shared read-only until one side writes to it.
+pub const COW_FLAG: PageFlags = PageFlags::BIT_9;
+
+/// Available PTE bit marking a page of a shared-memory segment, which a
+/// fork leaves out of the child rather than sharing copy-on-write.
+pub const SHARED_FLAG: PageFlags = PageFlags::BIT_10;
+
 /// Configure the PAT MSR with the OS-defined memory type table.
 ///
 /// Corresponds to Linux `pat_bp_init()`.  Sets all eight PAT entries:
@@ -38,8 +47,7 @@ pub use process_memory::*;
 ///
 /// This is the same full PAT table Linux uses on modern CPUs.
 pub fn configure_framebuffer_pat() -> bool {
-    let pat_supported = core::arch::x86_64::__cpuid(1).edx & (1 << 16) != 0;
-    if !pat_supported {
+    if !petroleum::hardware::cpuid::features().pat {
         return false;
     }
     unsafe {
@@ -48,6 +56,37 @@ pub fn configure_framebuffer_pat() -> bool {
     true
 }
 
+/// W+X regions [`audit_wx_mappings`] logs one by one; its count covers the
+/// rest.
+const WX_AUDIT_LOG_LIMIT: usize = 16;
+
+/// Walk the running address space and log every region that is both
+/// writable and executable.  Returns how many there are.
+pub fn audit_wx_mappings() -> usize {
+    let offset =
+        x86_64::VirtAddr::new(petroleum::common::memory::get_physical_memory_offset() as u64);
+    let l4 = unsafe { petroleum::page_table::active_level_4_table(offset) };
+    let mut logged = 0;
+    let count = unsafe {
+        petroleum::page_table::kernel::wx::for_each_wx_region(l4, offset, |region| {
+            if logged < WX_AUDIT_LOG_LIMIT {
+                log::error!(
+                    "W^X: {:#x}..{:#x} is writable and executable",
+                    region.start.as_u64(),
+                    region.start.as_u64() + region.size
+                );
+                logged += 1;
+            }
+        })
+    };
+    if count == 0 {
+        log::info!("W^X: no writable and executable mappings");
+    } else {
+        log::error!("W^X: {} writable and executable regions", count);
+    }
+    count
+}
+
 // Memory management error types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocError {
@@ -150,15 +189,295 @@ pub fn create_process_page_table() -> SystemResult<ProcessPageTable> {
     Ok(page_table_manager)
 }
 
+/// Create the page table for a forked child of the process whose PML4 is
+/// `parent_pml4`.
+///
+/// Kernel mappings (PML4[256..512]) are shared as in
+/// [`create_process_page_table`].  Writable user pages are shared
+/// copy-on-write: both sides lose `WRITABLE`, gain [`COW_FLAG`], and the
+/// first write is resolved by [`handle_cow_fault`].  Read-only pages are
+/// still copied eagerly, since `mprotect` may later make them writable in
+/// place.  The VDSO page is skipped because each process maps its own, and
+/// shared-memory pages because attachments are not inherited.
+pub fn fork_process_page_table(parent_pml4: x86_64::PhysAddr) -> SystemResult<ProcessPageTable> {
+    let child = create_process_page_table()?;
+    let child_pml4 = child.pml4_frame.ok_or(SystemError::InternalError)?;
+
+    let mut manager_guard = get_memory_manager().lock();
+    let manager = manager_guard.as_mut().ok_or(SystemError::InternalError)?;
+    let mut allocated = alloc::vec::Vec::new();
+    // SAFETY: both PML4s are live page-table frames reachable through the
+    // physical memory offset, and the child's is freshly allocated.
+    let copied = unsafe {
+        copy_user_tables(
+            manager,
+            parent_pml4,
+            child_pml4.start_address(),
+            4,
+            0,
+            &mut allocated,
+        )
+    };
+    // The parent's writable entries were just downgraded in place.
+    x86_64::instructions::tlb::flush_all();
+    if let Err(e) = copied {
+        // The child's tables may point at frames just freed here, so only
+        // its PML4 is left to go.
+        for frame in allocated {
+            let _ = manager.free_frame(frame);
+        }
+        let _ = manager.free_frame(child_pml4.start_address().as_u64() as usize);
+        return Err(e);
+    }
+    Ok(child)
+}
+
+/// Copy the user mappings of the level-`level` table at `src` into the
+/// zeroed table at `dst`, duplicating child tables and read-only 4 KiB
+/// pages and sharing writable ones copy-on-write.  `base` is the virtual
+/// address covered by entry 0 of `src`; every frame allocated or shared is
+/// recorded in `allocated` so the caller can drop the reference to unwind.
+///
+/// # Safety
+/// `src` and `dst` must be distinct page-table frames reachable through
+/// the physical memory offset mapping.
+unsafe fn copy_user_tables(
+    manager: &mut UnifiedMemoryManager,
+    src: x86_64::PhysAddr,
+    dst: x86_64::PhysAddr,
+    level: u8,
+    base: u64,
+    allocated: &mut alloc::vec::Vec<usize>,
+) -> SystemResult<()> {
+    use petroleum::common::memory::physical_to_virtual;
+    use x86_64::structures::paging::PageTable;
+
+    let src_table = unsafe { &mut *(physical_to_virtual(src.as_u64() as usize) as *mut PageTable) };
+    let dst_table = unsafe { &mut *(physical_to_virtual(dst.as_u64() as usize) as *mut PageTable) };
+    // Only the lower half of the PML4 belongs to the process.
+    let entries = if level == 4 { 256 } else { 512 };
+
+    for i in 0..entries {
+        let entry = &mut src_table[i];
+        if !entry.flags().contains(PageFlags::PRESENT) {
+            continue;
+        }
+        let va = base | ((i as u64) << (12 + 9 * (level as u64 - 1)));
+        if level > 1 && entry.flags().contains(PageFlags::HUGE_PAGE) {
+            // User memory is mapped with 4 KiB pages only.
+            return Err(SystemError::NotImplemented);
+        }
+        if level == 1
+            && (va == petroleum::vdso::VDSO_USER_BASE || entry.flags().contains(SHARED_FLAG))
+        {
+            continue;
+        }
+        if level == 1 && entry.flags().intersects(PageFlags::WRITABLE | COW_FLAG) {
+            let flags = (entry.flags() - PageFlags::WRITABLE) | COW_FLAG;
+            manager
+                .frame_allocator_mut()
+                .inc_ref(entry.addr().as_u64())?;
+            allocated.push(entry.addr().as_u64() as usize);
+            entry.set_flags(flags);
+            dst_table[i].set_addr(entry.addr(), flags);
+            continue;
+        }
+
+        let frame = manager.allocate_frame()?;
+        allocated.push(frame);
+        let frame_virt = physical_to_virtual(frame);
+        if level == 1 {
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    physical_to_virtual(entry.addr().as_u64() as usize) as *const u8,
+                    frame_virt as *mut u8,
+                    4096,
+                );
+            }
+        } else {
+            unsafe {
+                core::ptr::write_bytes(frame_virt as *mut u8, 0, 4096);
+                copy_user_tables(
+                    manager,
+                    entry.addr(),
+                    x86_64::PhysAddr::new(frame as u64),
+                    level - 1,
+                    va,
+                    allocated,
+                )?;
+            }
+        }
+        dst_table[i].set_addr(x86_64::PhysAddr::new(frame as u64), entry.flags());
+    }
+    Ok(())
+}
+
+/// Physical address that `addr` maps to in the address space rooted at
+/// `pml4`, following huge pages; `None` if it is not mapped.
+pub fn translate_in(pml4: x86_64::PhysAddr, addr: x86_64::VirtAddr) -> Option<x86_64::PhysAddr> {
+    use petroleum::common::memory::physical_to_virtual;
+    use x86_64::structures::paging::PageTable;
+
+    let mut table_phys = pml4;
+    let indices = [
+        addr.p4_index(),
+        addr.p3_index(),
+        addr.p2_index(),
+        addr.p1_index(),
+    ];
+    for (depth, index) in indices.into_iter().enumerate() {
+        // SAFETY: every table on the walk is a present page-table frame,
+        // reachable through the physical memory offset.
+        let table =
+            unsafe { &*(physical_to_virtual(table_phys.as_u64() as usize) as *const PageTable) };
+        let entry = &table[index];
+        let flags = entry.flags();
+        if !flags.contains(PageFlags::PRESENT) {
+            return None;
+        }
+        // A leaf at depth 1 maps 1 GiB, at depth 2 2 MiB, at depth 3 4 KiB.
+        if depth == 3 || (depth > 0 && flags.contains(PageFlags::HUGE_PAGE)) {
+            let page_size = 1u64 << (12 + 9 * (3 - depth));
+            return Some(entry.addr() + (addr.as_u64() & (page_size - 1)));
+        }
+        table_phys = entry.addr();
+    }
+    None
+}
+
+/// Resolve a write fault at `addr` against a copy-on-write page in the
+/// address space rooted at `pml4`.
+///
+/// Returns `Ok(false)` if the page is not copy-on-write, so the caller can
+/// treat the fault as a genuine protection violation.  If this process
+/// holds the last reference the page is simply made writable again;
+/// otherwise its contents are copied into a fresh frame first.
+pub fn handle_cow_fault(pml4: x86_64::PhysAddr, addr: x86_64::VirtAddr) -> SystemResult<bool> {
+    use petroleum::common::memory::physical_to_virtual;
+    use x86_64::structures::paging::{PageTable, PhysFrame};
+
+    let mut table_phys = pml4;
+    let indices = [
+        addr.p4_index(),
+        addr.p3_index(),
+        addr.p2_index(),
+        addr.p1_index(),
+    ];
+    for (depth, index) in indices.into_iter().enumerate() {
+        // SAFETY: every table on the walk is a present page-table frame of
+        // the faulting process, reachable through the physical memory offset.
+        let table =
+            unsafe { &mut *(physical_to_virtual(table_phys.as_u64() as usize) as *mut PageTable) };
+        let entry = &mut table[index];
+        let flags = entry.flags();
+        if !flags.contains(PageFlags::PRESENT) || flags.contains(PageFlags::HUGE_PAGE) {
+            return Ok(false);
+        }
+        if depth < 3 {
+            table_phys = entry.addr();
+            continue;
+        }
+        if !flags.contains(COW_FLAG) {
+            return Ok(false);
+        }
+
+        // A kernel write that faults while the manager is held must not
+        // spin on it forever; report it as unhandled instead.
+        let mut manager_guard = MEMORY_MANAGER
+            .try_lock()
+            .ok_or(SystemError::InternalError)?;
+        let manager = manager_guard.as_mut().ok_or(SystemError::InternalError)?;
+        let old_frame = PhysFrame::containing_address(entry.addr());
+        let writable = (flags - COW_FLAG) | PageFlags::WRITABLE;
+        if manager.frame_allocator().frame_refcount(old_frame) > 1 {
+            let new_frame = manager.allocate_frame()?;
+            // SAFETY: both frames are distinct 4 KiB data frames.
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    physical_to_virtual(old_frame.start_address().as_u64() as usize) as *const u8,
+                    physical_to_virtual(new_frame) as *mut u8,
+                    4096,
+                );
+            }
+            manager.frame_allocator_mut().free_frame(old_frame);
+            entry.set_addr(x86_64::PhysAddr::new(new_frame as u64), writable);
+        } else {
+            entry.set_flags(writable);
+        }
+        x86_64::instructions::tlb::flush(addr.align_down(4096u64));
+        return Ok(true);
+    }
+    Ok(false)
+}
+
 /// Deallocate a process page table and free its frames
+///
+/// The user half (PML4[0..256]) is torn down: its page-table frames are
+/// freed, and every page it maps loses this address space's reference, so
+/// a page still shared copy-on-write survives until its last owner goes.
+/// Shared-memory pages are left to the shm attachments that hold their
+/// references, and the VDSO page to its [`crate::vdso::VdsoPageRef`].  The
+/// kernel half is shared by every process and stays.
 pub fn deallocate_process_page_table(pml4_frame: x86_64::structures::paging::PhysFrame) {
     if let Some(manager) = MEMORY_MANAGER.lock().as_mut() {
+        // SAFETY: the PML4 belongs to an address space that is no longer
+        // in use, reachable through the physical memory offset.
+        unsafe {
+            free_user_tables(pml4_frame.start_address(), 4, 0, &mut |frame| {
+                let _ = manager.free_frame(frame);
+            });
+        }
         let frame_addr = pml4_frame.start_address().as_u64() as usize;
         let _ = manager.free_frame(frame_addr);
         mem_debug!("Mem: Deallocated process page table\n");
     }
 }
 
+/// Pass to `free` every frame owned through the user mappings of the
+/// level-`level` table at `table`: the pages it maps and the tables below
+/// it, children before their parents, but not `table` itself.  `base` is
+/// the virtual address covered by entry 0.
+///
+/// A leaf that `mprotect(PROT_NONE)` made non-present still owns its page,
+/// which it keeps `USER_ACCESSIBLE` for.  User memory never uses huge
+/// pages, so one found here is not the process's and is left alone.
+///
+/// # Safety
+/// `table` must be a page-table frame reachable through the physical
+/// memory offset, and no CPU may be using the address space.
+unsafe fn free_user_tables(
+    table: x86_64::PhysAddr,
+    level: u8,
+    base: u64,
+    free: &mut impl FnMut(usize),
+) {
+    use petroleum::common::memory::physical_to_virtual;
+    use x86_64::structures::paging::PageTable;
+
+    let table = unsafe { &*(physical_to_virtual(table.as_u64() as usize) as *const PageTable) };
+    // Only the lower half of the PML4 belongs to the process.
+    let entries = if level == 4 { 256 } else { 512 };
+
+    for (i, entry) in table.iter().enumerate().take(entries) {
+        let flags = entry.flags();
+        let va = base | ((i as u64) << (12 + 9 * (level as u64 - 1)));
+        if level == 1 {
+            let owned = flags.intersects(PageFlags::PRESENT | PageFlags::USER_ACCESSIBLE)
+                && !flags.contains(SHARED_FLAG)
+                && va != petroleum::vdso::VDSO_USER_BASE;
+            if owned {
+                free(entry.addr().as_u64() as usize);
+            }
+            continue;
+        }
+        if !flags.contains(PageFlags::PRESENT) || flags.contains(PageFlags::HUGE_PAGE) {
+            continue;
+        }
+        unsafe { free_user_tables(entry.addr(), level - 1, va, free) };
+        free(entry.addr().as_u64() as usize);
+    }
+}
+
 /// Initialize the global memory manager
 pub fn init_memory_manager(
     memory_map: &[impl petroleum::page_table::types::MemoryDescriptorValidator],
@@ -183,6 +502,47 @@ pub fn get_memory_manager() -> &'static Mutex<Option<UnifiedMemoryManager>> {
     &MEMORY_MANAGER
 }
 
+/// Physical memory totals from the frame allocator.
+///
+/// `used` is derived from the one count of free frames, so `used + free ==
+/// total` always holds.  The allocator itself takes no lock, so a frame
+/// allocated while the bitmap is scanned can leave `largest_free_run` one
+/// allocation out of date.  `None` before the manager is initialized.
+pub fn mem_info() -> Option<fullerene_abi::MemInfo> {
+    use petroleum::page_table::FrameAllocatorExt;
+
+    if !MEMORY_MANAGER.lock().as_ref()?.is_initialized() {
+        return None;
+    }
+    let frames = unsafe { petroleum::page_table::constants::get_frame_allocator() };
+    let frame_size = frames.frame_size() as u64;
+    let total = frames.total_frames() as u64 * frame_size;
+    let free = frames.available_frames() as u64 * frame_size;
+    Some(fullerene_abi::MemInfo {
+        total,
+        used: total - free,
+        free,
+        largest_free_run: frames.largest_free_run() as u64 * frame_size,
+    })
+}
+
+/// Whether a fault at `address` hit a stack guard page of `process_id`.
+///
+/// Only called for faults taken in ring 3, so the process list can be
+/// locked; the process's memory lock is only tried, and a fault taken while
+/// it is held is reported as an ordinary fault.
+pub fn is_stack_guard_fault(process_id: usize, address: u64) -> bool {
+    crate::process::SCHEDULER
+        .with_process(crate::process::ProcessId(process_id as u64), |p| {
+            p.resources.memory.try_lock().is_some_and(|memory| {
+                memory
+                    .as_ref()
+                    .is_some_and(|m| m.stack_guard_for(address as usize).is_some())
+            })
+        })
+        .unwrap_or(false)
+}
+
 /// Map a user page for kernel access
 pub fn map_user_page(
     virtual_addr: usize,
@@ -200,6 +560,71 @@ pub fn map_user_page(
     }
 }
 
+/// Whether SMAP faults a kernel read of a present user page made outside a
+/// user copy, while the same read through a user copy goes through.  The
+/// page is mapped in a throwaway address space, which is switched to only
+/// for the two reads.  Meaningful only once SMAP is on.
+pub fn smap_faults_stray_user_reads() -> SystemResult<bool> {
+    use petroleum::common::memory::UserSlice;
+    use x86_64::structures::idt::PageFaultErrorCode;
+    use x86_64::structures::paging::FrameAllocator as _;
+
+    const SCRATCH_PAGE: usize = 0x40_0000;
+
+    let mut table = create_process_page_table()?;
+    let pml4_frame = table.pml4_frame().ok_or(SystemError::InternalError)?;
+    let mapped = petroleum::page_table::constants::with_frame_allocator(|allocator| {
+        allocator.allocate_frame()
+    })
+    .ok_or(SystemError::FrameAllocationFailed)
+    .and_then(|frame| {
+        table.map_page(
+            SCRATCH_PAGE,
+            frame.start_address().as_u64() as usize,
+            PageFlags::PRESENT | PageFlags::USER_ACCESSIBLE | PageFlags::NO_EXECUTE,
+            unsafe { petroleum::page_table::constants::get_frame_allocator_mut() },
+        )
+    });
+    if let Err(e) = mapped {
+        deallocate_process_page_table(pml4_frame);
+        return Err(e);
+    }
+
+    let (kernel_frame, _) = x86_64::registers::control::Cr3::read();
+    let (stray, copied) = x86_64::instructions::interrupts::without_interrupts(|| {
+        petroleum::safe_cr3_write!(pml4_frame);
+        let stray = crate::interrupts::exceptions::probe_read(SCRATCH_PAGE as u64);
+        let mut byte = [0u8];
+        let copied = UserSlice::new(SCRATCH_PAGE as *mut u8, byte.len(), false)
+            .and_then(|slice| unsafe { slice.copy_from_user(&mut byte) });
+        petroleum::safe_cr3_write!(kernel_frame);
+        (stray, copied)
+    });
+    deallocate_process_page_table(pml4_frame);
+
+    let faulted = stray.is_err_and(|code| code.contains(PageFaultErrorCode::PROTECTION_VIOLATION));
+    Ok(faulted && copied.is_ok())
+}
+
+/// Whether running a kernel stack into its guard page ends in the
+/// double-fault handler rather than resetting the machine.  The stack is a
+/// throwaway page from [`convenience::allocate_kernel_pages`], which leaves
+/// the page below unmapped.
+pub fn stack_overflow_reaches_double_fault_handler() -> SystemResult<bool> {
+    use crate::interrupts::exceptions::{probe_read, probe_stack_overflow};
+
+    let stack = convenience::allocate_kernel_pages(1)? as u64;
+    let result = x86_64::instructions::interrupts::without_interrupts(|| {
+        // A mapped guard would take the push and prove nothing.
+        if probe_read(stack - 1).is_ok() {
+            return Err(SystemError::InternalError);
+        }
+        Ok(probe_stack_overflow(stack))
+    });
+    convenience::free_kernel_pages(stack as usize, 1)?;
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -211,4 +636,63 @@ mod tests {
         assert_eq!(manager.priority(), 1000);
         assert!(!manager.is_initialized());
     }
+
+    #[test]
+    fn free_user_tables_frees_owned_pages_and_tables() {
+        use alloc::boxed::Box;
+        use alloc::vec::Vec;
+        use x86_64::PhysAddr;
+        use x86_64::structures::paging::PageTable;
+
+        // With no physical memory offset, a table's address is its own.
+        fn table() -> (PhysAddr, &'static mut PageTable) {
+            let table = Box::leak(Box::new(PageTable::new()));
+            (PhysAddr::new(&raw const *table as u64), table)
+        }
+        let user = PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::USER_ACCESSIBLE;
+        let (pml4, pml4_table) = table();
+        let (pdpt, pdpt_table) = table();
+        let (pd, pd_table) = table();
+        let (pt, pt_table) = table();
+        let (vdso_pdpt, vdso_pdpt_table) = table();
+        let (vdso_pd, vdso_pd_table) = table();
+        let (vdso_pt, vdso_pt_table) = table();
+        let (kernel, _) = table();
+        pml4_table[0].set_addr(pdpt, user);
+        pdpt_table[0].set_addr(pd, user);
+        pd_table[0].set_addr(pt, user);
+        let page = |n: u64| PhysAddr::new(0x1_0000_0000 + n * 0x1000);
+        pt_table[0].set_addr(page(0), user);
+        pt_table[1].set_addr(page(1), (user - PageFlags::WRITABLE) | COW_FLAG);
+        // Left to shm, not mapped, made PROT_NONE, and not 4 KiB.
+        pt_table[2].set_addr(page(2), user | SHARED_FLAG);
+        pt_table[3].set_addr(page(3), PageFlags::empty());
+        pt_table[4].set_addr(page(4), PageFlags::USER_ACCESSIBLE);
+        pd_table[1].set_addr(page(5), user | PageFlags::HUGE_PAGE);
+
+        let vdso = x86_64::VirtAddr::new(petroleum::vdso::VDSO_USER_BASE);
+        pml4_table[usize::from(vdso.p4_index())].set_addr(vdso_pdpt, user);
+        vdso_pdpt_table[usize::from(vdso.p3_index())].set_addr(vdso_pd, user);
+        vdso_pd_table[usize::from(vdso.p2_index())].set_addr(vdso_pt, user);
+        vdso_pt_table[usize::from(vdso.p1_index())].set_addr(page(6), user);
+        pml4_table[256].set_addr(kernel, PageFlags::PRESENT | PageFlags::WRITABLE);
+
+        let mut freed = Vec::new();
+        unsafe { free_user_tables(pml4, 4, 0, &mut |frame| freed.push(frame as u64)) };
+        freed.sort_unstable();
+        let mut expected = [
+            page(0),
+            page(1),
+            page(4),
+            pt,
+            pd,
+            pdpt,
+            vdso_pt,
+            vdso_pd,
+            vdso_pdpt,
+        ]
+        .map(PhysAddr::as_u64);
+        expected.sort_unstable();
+        assert_eq!(freed, expected);
+    }
 }
@@ -3,13 +3,26 @@ use petroleum::common::logging::{SystemError, SystemResult};
 use petroleum::page_table::PageTableHelper;
 use petroleum::page_table::process::ProcessPageTable;
 
+/// Size of the unmapped guard region reserved below every stack.
+pub const STACK_GUARD_SIZE: usize = 4096;
+/// Where the heap, and with it the program break, starts.
+pub const HEAP_START: usize = 0x4000_0000;
+/// Start of the region anonymous mappings are placed in.
+pub const MMAP_BASE: usize = 0x100_0000_0000;
+/// End (exclusive) of the anonymous mapping region, well below the stacks.
+pub const MMAP_END: usize = 0x7000_0000_0000;
+
 /// Process-specific memory manager implementation
 pub struct ProcessMemoryManagerImpl {
     process_id: usize,
     page_table: ProcessPageTable,
     heap_end: usize,
+    /// Exact program break; `heap_end` is this rounded up to a page.
+    program_break: usize,
     stack_start: usize,
     allocations: BTreeMap<usize, usize>, // address -> size mapping
+    stack_guards: BTreeMap<usize, usize>, // guard page address -> stack base
+    mmap_regions: BTreeMap<usize, usize>, // mapping address -> size
 }
 
 use crate::*;
@@ -20,9 +33,12 @@ impl ProcessMemoryManagerImpl {
         Self {
             process_id,
             page_table: ProcessPageTable::new(),
-            heap_end: 0x4000_0000,
+            heap_end: HEAP_START,
+            program_break: HEAP_START,
             stack_start: 0x7FFF_0000,
             allocations: BTreeMap::new(),
+            stack_guards: BTreeMap::new(),
+            mmap_regions: BTreeMap::new(),
         }
     }
 
@@ -76,10 +92,15 @@ impl ProcessMemoryManagerImpl {
     }
 
     /// Free memory from heap
+    ///
+    /// Freeing the topmost allocation gives its pages back to the heap.
     pub fn free_heap(&mut self, address: usize, size: usize) -> SystemResult<()> {
         if let Some(&alloc_size) = self.allocations.get(&address) {
             if alloc_size == size {
                 self.allocations.remove(&address);
+                if address + alloc_size == self.heap_end {
+                    self.heap_end = address;
+                }
                 return Ok(());
             }
         }
@@ -87,27 +108,161 @@ impl ProcessMemoryManagerImpl {
         Err(SystemError::InvalidArgument)
     }
 
+    /// Current program break
+    pub fn program_break(&self) -> usize {
+        self.program_break
+    }
+
+    /// Move the program break to `new_break`.
+    ///
+    /// Returns the page-aligned heap end before and after the move; the
+    /// caller maps the pages in between when the heap grew and unmaps them
+    /// when it shrank.  The break cannot drop below [`HEAP_START`], and
+    /// growth that would reach the guard page of the next stack up, or the
+    /// mapping region, fails with [`SystemError::MemOutOfMemory`].
+    pub fn set_program_break(&mut self, new_break: usize) -> SystemResult<(usize, usize)> {
+        if new_break < HEAP_START {
+            return Err(SystemError::InvalidArgument);
+        }
+        let new_end = new_break
+            .checked_add(4095)
+            .ok_or(SystemError::MemOutOfMemory)?
+            & !4095;
+        let old_end = self.heap_end;
+
+        if new_end > old_end {
+            if new_end > self.heap_limit() {
+                return Err(SystemError::MemOutOfMemory);
+            }
+            self.allocate_heap(new_end - old_end)?;
+        }
+        while self.heap_end > new_end {
+            let (&address, &size) = self
+                .allocations
+                .range(..self.heap_end)
+                .next_back()
+                .ok_or(SystemError::InternalError)?;
+            self.free_heap(address, size)?;
+            // Keep the part of a straddling allocation below the new end.
+            if address < new_end {
+                self.allocate_heap(new_end - address)?;
+            }
+        }
+
+        self.program_break = new_break;
+        Ok((old_end, new_end))
+    }
+
+    /// How far the heap may grow: up to the guard page of the lowest stack
+    /// above it, whether carved out here or adopted from the loader, and
+    /// never into the mapping region.
+    fn heap_limit(&self) -> usize {
+        self.stack_guards
+            .range(self.heap_end..)
+            .next()
+            .map_or(MMAP_BASE, |(&guard, _)| guard.min(MMAP_BASE))
+    }
+
+    /// Reserve `pages` pages of address space for an anonymous mapping.
+    ///
+    /// Takes the lowest gap between existing mappings that fits, so
+    /// released ranges are reused.
+    pub fn reserve_mmap(&mut self, pages: usize) -> SystemResult<usize> {
+        let size = pages
+            .checked_mul(4096)
+            .filter(|&size| size != 0)
+            .ok_or(SystemError::InvalidArgument)?;
+        let mut candidate = MMAP_BASE;
+        for (&address, &region_size) in &self.mmap_regions {
+            if address - candidate >= size {
+                break;
+            }
+            candidate = address + region_size;
+        }
+        if MMAP_END - candidate < size {
+            return Err(SystemError::MemOutOfMemory);
+        }
+        self.mmap_regions.insert(candidate, size);
+        Ok(candidate)
+    }
+
+    /// Release a mapping made by [`reserve_mmap`](Self::reserve_mmap).
+    /// Only whole mappings can be released.
+    pub fn release_mmap(&mut self, address: usize, pages: usize) -> SystemResult<()> {
+        match self.mmap_regions.get(&address) {
+            Some(&size) if Some(size) == pages.checked_mul(4096) => {
+                self.mmap_regions.remove(&address);
+                Ok(())
+            }
+            _ => Err(SystemError::InvalidArgument),
+        }
+    }
+
+    /// Whether any page of `pages` pages at `address` lies in a mapping
+    /// made by [`reserve_mmap`](Self::reserve_mmap).
+    pub fn overlaps_mmap(&self, address: usize, pages: usize) -> bool {
+        let end = address.saturating_add(pages.saturating_mul(4096));
+        self.mmap_regions
+            .range(..end)
+            .next_back()
+            .is_some_and(|(&start, &size)| start + size > address)
+    }
+
+    /// Copy the heap, stack and mapping bookkeeping for a forked child.
+    pub fn fork(&self, process_id: usize) -> Self {
+        Self {
+            process_id,
+            page_table: ProcessPageTable::new(),
+            heap_end: self.heap_end,
+            program_break: self.program_break,
+            stack_start: self.stack_start,
+            allocations: self.allocations.clone(),
+            stack_guards: self.stack_guards.clone(),
+            mmap_regions: self.mmap_regions.clone(),
+        }
+    }
+
     /// Allocate memory from stack
+    ///
+    /// A guard page is reserved directly below every stack and never mapped,
+    /// so running off the bottom of one stack faults instead of silently
+    /// writing into whatever was allocated before it.
     pub fn allocate_stack(&mut self, size: usize) -> SystemResult<usize> {
         let aligned_size = (size + 4095) & !(4095); // Page align
+        let reserved = aligned_size
+            .checked_add(STACK_GUARD_SIZE)
+            .ok_or(SystemError::MemOutOfMemory)?;
 
-        if self.stack_start < aligned_size {
+        // Stacks grow down towards the heap; neither may take the other's
+        // pages.
+        if self.stack_start < reserved || self.stack_start - reserved < self.heap_end {
             return Err(SystemError::MemOutOfMemory);
         }
 
-        self.stack_start -= aligned_size;
-        let address = self.stack_start;
+        self.stack_start -= reserved;
+        let guard = self.stack_start;
+        let address = guard + STACK_GUARD_SIZE;
 
         self.allocations.insert(address, aligned_size);
+        self.stack_guards.insert(guard, address);
 
         Ok(address)
     }
 
+    /// Record a stack that was mapped elsewhere, such as by the program
+    /// loader, so the page below it is treated as its guard.
+    pub fn adopt_stack(&mut self, address: usize, size: usize) {
+        self.allocations.insert(address, size);
+        self.stack_guards
+            .insert(address - STACK_GUARD_SIZE, address);
+    }
+
     /// Free memory from stack
     pub fn free_stack(&mut self, address: usize, size: usize) -> SystemResult<()> {
         if let Some(&alloc_size) = self.allocations.get(&address) {
             if alloc_size == size {
                 self.allocations.remove(&address);
+                self.stack_guards.remove(&(address - STACK_GUARD_SIZE));
                 return Ok(());
             }
         }
@@ -115,10 +270,139 @@ impl ProcessMemoryManagerImpl {
         Err(SystemError::InvalidArgument)
     }
 
+    /// Return the base of the stack whose guard page contains `address`.
+    pub fn stack_guard_for(&self, address: usize) -> Option<usize> {
+        self.stack_guards
+            .range(..=address)
+            .next_back()
+            .filter(|&(&guard, _)| address - guard < STACK_GUARD_SIZE)
+            .map(|(_, &stack)| stack)
+    }
+
     /// Cleanup process memory
     pub fn cleanup(&mut self) -> SystemResult<()> {
         self.allocations.clear();
+        self.stack_guards.clear();
         log::info!("Process memory cleaned up");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_stack_gets_its_own_guard_page() {
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        let first = pm.allocate_stack(0x2000).unwrap();
+        let second = pm.allocate_stack(0x2000).unwrap();
+
+        // The second stack sits below the first one's guard page.
+        assert_eq!(second + 0x2000, first - STACK_GUARD_SIZE);
+        assert_eq!(pm.stack_guard_for(first - 1), Some(first));
+        assert_eq!(pm.stack_guard_for(first - STACK_GUARD_SIZE), Some(first));
+        assert_eq!(pm.stack_guard_for(second - 8), Some(second));
+        assert_eq!(pm.stack_guard_for(second), None);
+        assert_eq!(pm.stack_guard_for(first + 0x1000), None);
+    }
+
+    #[test]
+    fn adopted_stack_is_guarded() {
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        pm.adopt_stack(0x7fff_ffff_0000, 0x10000);
+        assert_eq!(pm.stack_guard_for(0x7fff_fffe_fff8), Some(0x7fff_ffff_0000));
+        assert_eq!(pm.stack_guard_for(0x7fff_ffff_0000), None);
+    }
+
+    #[test]
+    fn program_break_grows_and_shrinks_by_pages() {
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        assert_eq!(pm.program_break(), HEAP_START);
+
+        assert_eq!(
+            pm.set_program_break(HEAP_START + 0x2800),
+            Ok((HEAP_START, HEAP_START + 0x3000))
+        );
+        assert_eq!(pm.program_break(), HEAP_START + 0x2800);
+        assert_eq!(
+            pm.set_program_break(HEAP_START + 0x1000),
+            Ok((HEAP_START + 0x3000, HEAP_START + 0x1000))
+        );
+        assert_eq!(
+            pm.set_program_break(HEAP_START + 0x2000),
+            Ok((HEAP_START + 0x1000, HEAP_START + 0x2000))
+        );
+
+        // Below the initial break, and into the stack region.
+        assert_eq!(
+            pm.set_program_break(HEAP_START - 1),
+            Err(SystemError::InvalidArgument)
+        );
+        let stack = pm.allocate_stack(0x1000).unwrap();
+        assert_eq!(
+            pm.set_program_break(stack - STACK_GUARD_SIZE + 1),
+            Err(SystemError::MemOutOfMemory)
+        );
+        assert_eq!(pm.program_break(), HEAP_START + 0x2000);
+        assert!(pm.set_program_break(stack - STACK_GUARD_SIZE).is_ok());
+    }
+
+    #[test]
+    fn program_break_is_limited_by_the_stacks_actually_there() {
+        // Nothing above the heap but the loader's stack near the top of
+        // the lower half: the heap may grow well past the stack region's
+        // bookkeeping start.
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        pm.adopt_stack(0x7fff_ffff_0000, 0x1_0000);
+        assert!(pm.set_program_break(0x9000_0000).is_ok());
+        // The stack region now lies inside the heap.
+        assert_eq!(pm.allocate_stack(0x1000), Err(SystemError::MemOutOfMemory));
+        assert_eq!(
+            pm.set_program_break(MMAP_BASE + 1),
+            Err(SystemError::MemOutOfMemory)
+        );
+
+        // A stack adopted just above the heap stops it at its guard page.
+        let mut pm = ProcessMemoryManagerImpl::new(2);
+        let stack = HEAP_START + 0x10_0000;
+        pm.adopt_stack(stack, 0x1000);
+        assert_eq!(
+            pm.set_program_break(stack - STACK_GUARD_SIZE + 1),
+            Err(SystemError::MemOutOfMemory)
+        );
+        assert!(pm.set_program_break(stack - STACK_GUARD_SIZE).is_ok());
+    }
+
+    #[test]
+    fn mmap_regions_do_not_overlap_and_are_reused() {
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        let first = pm.reserve_mmap(2).unwrap();
+        let second = pm.reserve_mmap(1).unwrap();
+        assert_eq!(first, MMAP_BASE);
+        assert_eq!(second, first + 0x2000);
+
+        // Partial releases are rejected; a freed gap is filled first fit.
+        assert_eq!(pm.release_mmap(first, 1), Err(SystemError::InvalidArgument));
+        assert!(pm.overlaps_mmap(first + 0x1000, 1));
+        assert!(!pm.overlaps_mmap(first - 0x1000, 1));
+        assert!(!pm.overlaps_mmap(second + 0x1000, 4));
+        pm.release_mmap(first, 2).unwrap();
+        assert_eq!(pm.reserve_mmap(3).unwrap(), second + 0x1000);
+        assert_eq!(pm.reserve_mmap(2).unwrap(), first);
+        assert_eq!(pm.reserve_mmap(0), Err(SystemError::InvalidArgument));
+
+        // Address spaces track their mappings independently.
+        let mut other = ProcessMemoryManagerImpl::new(2);
+        assert_eq!(other.reserve_mmap(1).unwrap(), MMAP_BASE);
+    }
+
+    #[test]
+    fn freeing_a_stack_releases_its_guard() {
+        let mut pm = ProcessMemoryManagerImpl::new(1);
+        let stack = pm.allocate_stack(0x1000).unwrap();
+        assert!(pm.stack_guard_for(stack - 1).is_some());
+        pm.free_stack(stack, 0x1000).unwrap();
+        assert_eq!(pm.stack_guard_for(stack - 1), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Free-range tracking for the kernel's dynamic virtual address window.
+//!
+//! `allocate_pages` and framebuffer mappings take their virtual addresses
+//! from this window.  The free ranges live in the [`UnifiedMemoryManager`]
+//! (and so behind `MEMORY_MANAGER`), and freed ranges are merged with their
+//! neighbours so they can be handed out again.
+//!
+//! [`UnifiedMemoryManager`]: super::UnifiedMemoryManager
+
+use alloc::collections::BTreeMap;
+use petroleum::common::logging::{SystemError, SystemResult};
+
+/// Start of the kernel dynamic allocation window, above the direct map.
+pub const KERNEL_DYNAMIC_START: usize = 0xFFFF_8800_0000_0000;
+/// End (exclusive) of the window; the page-table clone scratch area follows.
+pub const KERNEL_DYNAMIC_END: usize = 0xFFFF_9000_0000_0000;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Free virtual ranges, first fit.
+pub struct VirtualRanges {
+    free: BTreeMap<usize, usize>, // start -> end (exclusive)
+}
+
+impl VirtualRanges {
+    /// Track `[start, end)` as entirely free.
+    pub fn new(start: usize, end: usize) -> Self {
+        let mut free = BTreeMap::new();
+        if start < end {
+            free.insert(start, end);
+        }
+        Self { free }
+    }
+
+    /// Take the lowest free range of `size` bytes, rounded up to pages.
+    pub fn allocate(&mut self, size: usize) -> Option<usize> {
+        let size = size.checked_next_multiple_of(PAGE_SIZE)?.max(PAGE_SIZE);
+        let (&start, &end) = self
+            .free
+            .iter()
+            .find(|&(&start, &end)| end - start >= size)?;
+        self.free.remove(&start);
+        if end - start > size {
+            self.free.insert(start + size, end);
+        }
+        Some(start)
+    }
+
+    /// Return `[start, start + size)` to the pool.
+    ///
+    /// Fails with [`SystemError::InvalidArgument`] if any part of the range
+    /// is already free, e.g. on a double free.
+    pub fn free(&mut self, start: usize, size: usize) -> SystemResult<()> {
+        let size = size
+            .checked_next_multiple_of(PAGE_SIZE)
+            .ok_or(SystemError::InvalidArgument)?
+            .max(PAGE_SIZE);
+        let mut end = start
+            .checked_add(size)
+            .ok_or(SystemError::InvalidArgument)?;
+        let previous = self.free.range(..end).next_back().map(|(&s, &e)| (s, e));
+        if previous.is_some_and(|(_, previous_end)| previous_end > start) {
+            return Err(SystemError::InvalidArgument);
+        }
+
+        let mut start = start;
+        if let Some((previous_start, previous_end)) = previous
+            && previous_end == start
+        {
+            self.free.remove(&previous_start);
+            start = previous_start;
+        }
+        if let Some(next_end) = self.free.remove(&end) {
+            end = next_end;
+        }
+        self.free.insert(start, end);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: usize = 0x10_0000;
+
+    #[test]
+    fn freed_ranges_are_reused() {
+        let mut ranges = VirtualRanges::new(BASE, BASE + 0x10_000);
+        let first = ranges.allocate(0x3000).unwrap();
+        let second = ranges.allocate(0x1000).unwrap();
+        assert_eq!((first, second), (BASE, BASE + 0x3000));
+
+        ranges.free(first, 0x3000).unwrap();
+        assert_eq!(ranges.allocate(0x2000), Some(first));
+        assert_eq!(ranges.allocate(0x1000), Some(first + 0x2000));
+        assert_eq!(ranges.free(second, 0x1000), Ok(()));
+        assert_eq!(
+            ranges.free(second, 0x1000),
+            Err(SystemError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn neighbours_merge_into_one_range() {
+        let mut ranges = VirtualRanges::new(BASE, BASE + 0x4000);
+        let a = ranges.allocate(0x1000).unwrap();
+        let b = ranges.allocate(0x1000).unwrap();
+        let c = ranges.allocate(0x1000).unwrap();
+        ranges.free(a, 0x1000).unwrap();
+        ranges.free(c, 0x1000).unwrap();
+        // Only the merged range is big enough.
+        assert_eq!(ranges.allocate(0x3000), None);
+        ranges.free(b, 0x1000).unwrap();
+        assert_eq!(ranges.allocate(0x4000), Some(BASE));
+        assert_eq!(ranges.allocate(1), None);
+    }
+}
@@ -0,0 +1,253 @@
+//! Per-CPU data.
+//!
+//! Each processor owns a [`PerCpu`] block, registered by Local APIC ID.
+//! While the kernel runs, the GS base points at the block and the kernel GS
+//! base MSR holds the GS base of the user code it entered from; in ring 3
+//! the two are swapped, so nothing user code does to GS can reach the
+//! block.  `syscall_entry` swaps on entry and exit, and so does every
+//! interrupt and exception handler that interrupted ring 3, through
+//! [`KernelGs`].  The context switch carries the user GS base in the
+//! kernel GS base MSR from process to process.  [`this_cpu`] finds the
+//! block with a single GS-relative load.
+//!
+//! The BSP's block is a static, so it is usable before the heap and the
+//! APIC are: until [`init_bsp`] loads it, [`this_cpu`] returns it directly.
+//! An AP's block is allocated by the BSP before the AP starts and loaded by
+//! the AP first thing in its entry point.
+
+use alloc::boxed::Box;
+use core::mem::offset_of;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use x86_64::registers::model_specific::{GsBase, KernelGsBase};
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::{PrivilegeLevel, VirtAddr};
+
+/// xAPIC IDs are 8 bits wide.
+const MAX_APIC_IDS: usize = 256;
+
+/// State that belongs to one processor.
+///
+/// The first fields are read by `syscall_entry` through GS and must keep
+/// their offsets.
+#[repr(C, align(64))]
+pub struct PerCpu {
+    /// Top of the stack `syscall_entry` switches to: the running process's
+    /// kernel stack; 0 until the CPU takes syscalls.
+    pub(crate) syscall_stack_top: AtomicU64,
+    /// User RSP, live only between `swapgs` and the first push in
+    /// `syscall_entry`.
+    pub(crate) syscall_user_rsp: AtomicU64,
+    /// Address of the `SyscallFrame` of the syscall in progress.
+    pub(crate) syscall_frame: AtomicU64,
+    /// The block's own address, for [`this_cpu`].
+    self_ptr: AtomicU64,
+    apic_id: AtomicU32,
+    /// PID of the process running here; 0 for none.
+    pub current_process: AtomicUsize,
+    /// Timer interrupts taken by this CPU.
+    pub timer_ticks: AtomicU64,
+    /// This CPU's own syscall stack, used while the running process has no
+    /// kernel stack of its own.
+    pub(crate) default_syscall_stack: AtomicU64,
+}
+
+impl PerCpu {
+    const fn new(apic_id: u32) -> Self {
+        Self {
+            syscall_stack_top: AtomicU64::new(0),
+            syscall_user_rsp: AtomicU64::new(0),
+            syscall_frame: AtomicU64::new(0),
+            self_ptr: AtomicU64::new(0),
+            apic_id: AtomicU32::new(apic_id),
+            current_process: AtomicUsize::new(0),
+            timer_ticks: AtomicU64::new(0),
+            default_syscall_stack: AtomicU64::new(0),
+        }
+    }
+
+    pub fn apic_id(&self) -> u32 {
+        self.apic_id.load(Ordering::Relaxed)
+    }
+
+    /// Whether this is the bootstrap processor's block.
+    pub fn is_bsp(&self) -> bool {
+        core::ptr::eq(self, &BSP)
+    }
+}
+
+static BSP: PerCpu = PerCpu::new(0);
+/// Set once GS points at [`BSP`].
+static BSP_LOADED: AtomicBool = AtomicBool::new(false);
+static BY_APIC_ID: [AtomicPtr<PerCpu>; MAX_APIC_IDS] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_APIC_IDS];
+
+/// The running CPU's block.
+pub fn this_cpu() -> &'static PerCpu {
+    // Only the BSP runs before its block is loaded; an AP loads its own
+    // before anything can ask.
+    if !BSP_LOADED.load(Ordering::Acquire) {
+        return &BSP;
+    }
+    let block: *const PerCpu;
+    unsafe {
+        core::arch::asm!(
+            "mov {}, qword ptr gs:[{}]",
+            out(reg) block,
+            const offset_of!(PerCpu, self_ptr),
+            options(nostack, readonly, preserves_flags),
+        );
+        &*block
+    }
+}
+
+/// The block of the CPU with Local APIC ID `apic_id`, once registered.
+pub fn for_apic_id(apic_id: u32) -> Option<&'static PerCpu> {
+    let block = BY_APIC_ID.get(apic_id as usize)?.load(Ordering::Acquire);
+    unsafe { block.as_ref() }
+}
+
+fn register(block: &'static PerCpu) {
+    block
+        .self_ptr
+        .store(block as *const PerCpu as u64, Ordering::Relaxed);
+    if let Some(slot) = BY_APIC_ID.get(block.apic_id() as usize) {
+        slot.store(block as *const PerCpu as *mut PerCpu, Ordering::Release);
+    }
+}
+
+/// Point the GS base at `block`, leaving a null user GS base to swap in on
+/// the first return to ring 3.
+fn load(block: &'static PerCpu) {
+    GsBase::write(VirtAddr::from_ptr(block as *const PerCpu));
+    KernelGsBase::write(VirtAddr::zero());
+}
+
+/// GS base of the user code the kernel was entered from, which is parked in
+/// the kernel GS base MSR while the kernel runs.
+pub fn user_gs_base() -> u64 {
+    KernelGsBase::read().as_u64()
+}
+
+/// Park `base` as the user GS base, for the next return to ring 3.
+pub fn set_user_gs_base(base: u64) {
+    KernelGsBase::write(VirtAddr::new_truncate(base));
+}
+
+/// Puts this CPU's block in the GS base for the duration of an interrupt or
+/// exception handler, and the interrupted code's GS base back when dropped.
+///
+/// Taken first thing in every handler, before anything uses [`this_cpu`].
+/// Entering also closes the interrupted code's user-access window, see
+/// [`petroleum::common::memory::close_user_access`].
+/// A handler that rewrites its frame to resume in the kernel instead of
+/// ring 3 keeps the kernel GS base: the swap back only happens when the
+/// frame still returns to ring 3.
+pub struct KernelGs {
+    frame: *const InterruptStackFrame,
+    swapped: bool,
+    /// Restore whatever GS base was found, whichever mode is returned to.
+    paranoid: bool,
+}
+
+impl KernelGs {
+    /// For a handler entered through `frame`: swaps when it interrupted
+    /// ring 3, where the GS base belongs to user code.
+    #[inline(always)]
+    pub fn enter(frame: &InterruptStackFrame) -> Self {
+        let swapped = frame.code_segment.rpl() == PrivilegeLevel::Ring3;
+        if swapped {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+        petroleum::common::memory::close_user_access();
+        Self {
+            frame,
+            swapped,
+            paranoid: false,
+        }
+    }
+
+    /// For the NMI, machine check and double fault handlers, which can
+    /// interrupt the kernel between `syscall_entry`'s `swapgs` and the
+    /// switch of stacks, or just before `sysretq`, with the user GS base
+    /// loaded: swaps whenever the GS base is not this CPU's block, which is
+    /// looked up by APIC ID rather than through GS.
+    #[inline(always)]
+    pub fn enter_paranoid(frame: &InterruptStackFrame) -> Self {
+        let swapped = BSP_LOADED.load(Ordering::Acquire)
+            && for_apic_id(crate::smp::current_apic_id())
+                .is_some_and(|block| GsBase::read() != VirtAddr::from_ptr(block as *const PerCpu));
+        if swapped {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+        petroleum::common::memory::close_user_access();
+        Self {
+            frame,
+            swapped,
+            paranoid: true,
+        }
+    }
+}
+
+impl Drop for KernelGs {
+    #[inline(always)]
+    fn drop(&mut self) {
+        // SAFETY: the frame outlives the handler that holds the guard.
+        let to_user = unsafe { &*self.frame }.code_segment.rpl() == PrivilegeLevel::Ring3;
+        if self.swapped && (self.paranoid || to_user) {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+    }
+}
+
+/// Load the BSP's block on the BSP.  Needs neither the heap nor the APIC:
+/// the APIC ID comes from CPUID.  Loading the GS selector clears the base,
+/// so this must come after the GDT is loaded.
+pub fn init_bsp() {
+    BSP.apic_id
+        .store(crate::smp::current_apic_id(), Ordering::Relaxed);
+    register(&BSP);
+    load(&BSP);
+    BSP_LOADED.store(true, Ordering::Release);
+}
+
+/// Allocate and register the block of the AP with `apic_id`, for it to
+/// [`init_ap`] once it runs.  Never freed.
+pub fn allocate(apic_id: u32) -> &'static PerCpu {
+    let block: &'static PerCpu = Box::leak(Box::new(PerCpu::new(apic_id)));
+    register(block);
+    block
+}
+
+/// Load `block`, from [`allocate`], on the AP it was allocated for.
+pub fn init_ap(block: &'static PerCpu) {
+    load(block);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syscall_fields_keep_the_offsets_syscall_entry_uses() {
+        assert_eq!(offset_of!(PerCpu, syscall_stack_top), 0);
+        assert_eq!(offset_of!(PerCpu, syscall_user_rsp), 8);
+        assert_eq!(offset_of!(PerCpu, syscall_frame), 16);
+    }
+
+    #[test]
+    fn blocks_are_found_by_apic_id() {
+        // The BSP's block stands in until GS is loaded.
+        assert!(this_cpu().is_bsp());
+
+        let block = allocate(200);
+        assert!(core::ptr::eq(for_apic_id(200).unwrap(), block));
+        assert_eq!(block.apic_id(), 200);
+        assert!(!block.is_bsp());
+        assert_eq!(
+            block.self_ptr.load(Ordering::Relaxed),
+            block as *const PerCpu as u64
+        );
+        assert!(for_apic_id(201).is_none());
+        assert!(for_apic_id(4096).is_none());
+    }
+}
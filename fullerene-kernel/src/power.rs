@@ -0,0 +1,163 @@
+//! Powering the machine off and resetting it.
+//!
+//! [`shutdown`] first looks for QEMU's `isa-debug-exit` device and, when it
+//! is there, exits through it with an exit code for a test harness: QEMU
+//! ends with status `33 + 2 * code`.  flasks runs QEMU with `-no-shutdown`,
+//! under which an ACPI power-off only pauses the VM, so this is the path
+//! that lets a test run end with a status it can check.  Otherwise it
+//! enters ACPI S5 through the FADT's PM1 control registers.  [`reboot`]
+//! resets through the 0xCF9 reset control register and then the keyboard
+//! controller.  Both end in a triple fault if nothing else worked.
+
+use spin::Once;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+/// Port of flasks' `isa-debug-exit,iobase=0xf4` device.
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+/// Added to the exit code written to the debug-exit port, so that QEMU,
+/// which exits with `(value << 1) | 1`, never reports a guest exit as 1,
+/// its own status for failing to start.
+const DEBUG_EXIT_BASE: u8 = 0x10;
+/// Largest exit code whose QEMU status still fits in a byte (255).
+pub const MAX_EXIT_CODE: u8 = 0x6F;
+/// Exit code reported when the kernel panics in `test` mode.
+pub const PANIC_EXIT_CODE: u8 = MAX_EXIT_CODE;
+
+/// PM1 control register fields.
+const PM1_SCI_EN: u16 = 1 << 0;
+const PM1_SLP_TYP_SHIFT: u16 = 10;
+const PM1_SLP_TYP_MASK: u16 = 0b111 << PM1_SLP_TYP_SHIFT;
+const PM1_SLP_EN: u16 = 1 << 13;
+
+const RESET_CONTROL_PORT: u16 = 0xCF9;
+/// Reset control: request a reset, and make it a full reset through the
+/// power cycle rather than a CPU-only one.
+const RESET_CPU: u8 = 1 << 2;
+const RESET_SYSTEM: u8 = 1 << 1;
+const RESET_FULL: u8 = 1 << 3;
+
+const KBC_STATUS_PORT: u16 = 0x64;
+const KBC_INPUT_FULL: u8 = 1 << 1;
+const KBC_PULSE_RESET: u8 = 0xFE;
+
+/// Busy-wait iterations between a register write and the next fallback.
+const SETTLE_SPINS: usize = 10_000_000;
+
+/// What ACPI needs to enter S5, found in the FADT and DSDT at boot.
+#[derive(Debug, Clone, Copy)]
+pub struct AcpiPowerOff {
+    pub fadt: nitrogen::acpi::fadt::FadtInfo,
+    pub s5: nitrogen::acpi::fadt::SleepTypes,
+}
+
+static ACPI_POWER_OFF: Once<AcpiPowerOff> = Once::new();
+
+/// Record the ACPI S5 registers for [`shutdown`].
+pub fn configure_acpi(power_off: AcpiPowerOff) {
+    ACPI_POWER_OFF.call_once(|| power_off);
+}
+
+/// Power the machine off, reporting `code` (0 for success, at most
+/// [`MAX_EXIT_CODE`]) if it runs under QEMU with `isa-debug-exit`.
+pub fn shutdown(code: u8) -> ! {
+    log::info!("power: shutting down (exit code {})", code);
+    prepare();
+    exit_qemu(code);
+    if let Some(power_off) = ACPI_POWER_OFF.get() {
+        unsafe { enter_s5(power_off) };
+        settle();
+        petroleum::serial::serial_log(format_args!("power: ACPI S5 did not take effect\n"));
+    }
+    triple_fault()
+}
+
+/// Reset the machine.
+pub fn reboot() -> ! {
+    log::info!("power: rebooting");
+    prepare();
+    unsafe {
+        let mut reset = Port::<u8>::new(RESET_CONTROL_PORT);
+        let value = reset.read() & !(RESET_CPU | RESET_SYSTEM | RESET_FULL);
+        // The reset happens on the 0 -> 1 edge of RESET_CPU.
+        reset.write(value | RESET_SYSTEM | RESET_FULL);
+        reset.write(value | RESET_SYSTEM | RESET_FULL | RESET_CPU);
+    }
+    settle();
+    unsafe {
+        let mut status = Port::<u8>::new(KBC_STATUS_PORT);
+        while status.read() & KBC_INPUT_FULL != 0 {
+            core::hint::spin_loop();
+        }
+        status.write(KBC_PULSE_RESET);
+    }
+    settle();
+    triple_fault()
+}
+
+/// Save what is worth keeping and stop taking interrupts.
+fn prepare() {
+    let _ = crate::klog::flush_to_vfs();
+    x86_64::instructions::interrupts::disable();
+}
+
+fn settle() {
+    for _ in 0..SETTLE_SPINS {
+        core::hint::spin_loop();
+    }
+}
+
+/// Exit QEMU with `code` through `isa-debug-exit`.  Returns if the device
+/// is absent; safe to call from the panic handler.
+pub fn exit_qemu(code: u8) {
+    if debug_exit_present() {
+        let value = DEBUG_EXIT_BASE + code.min(MAX_EXIT_CODE);
+        unsafe { PortWriteOnly::<u32>::new(DEBUG_EXIT_PORT).write(u32::from(value)) };
+    }
+}
+
+/// Whether QEMU's `isa-debug-exit` device answers at [`DEBUG_EXIT_PORT`].
+///
+/// The device reads as 0, while an unclaimed port reads as all ones both
+/// in QEMU and on the ISA bus of real machines.
+fn debug_exit_present() -> bool {
+    unsafe { Port::<u8>::new(DEBUG_EXIT_PORT).read() == 0 }
+}
+
+/// Write `SLP_TYPx | SLP_EN` to the PM1 control registers, switching the
+/// chipset into ACPI mode first if the firmware left it in legacy mode.
+unsafe fn enter_s5(power_off: &AcpiPowerOff) {
+    let fadt = &power_off.fadt;
+    unsafe {
+        let mut pm1a = Port::<u16>::new(fadt.pm1a_control);
+        if pm1a.read() & PM1_SCI_EN == 0 && fadt.smi_cmd != 0 && fadt.acpi_enable != 0 {
+            PortWriteOnly::<u8>::new(fadt.smi_cmd).write(fadt.acpi_enable);
+            for _ in 0..SETTLE_SPINS {
+                if pm1a.read() & PM1_SCI_EN != 0 {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+        }
+        let sleep = |port: &mut Port<u16>, slp_typ: u8| {
+            let value = port.read() & !PM1_SLP_TYP_MASK;
+            port.write(value | u16::from(slp_typ) << PM1_SLP_TYP_SHIFT | PM1_SLP_EN);
+        };
+        sleep(&mut pm1a, power_off.s5.a);
+        if fadt.pm1b_control != 0 {
+            sleep(&mut Port::new(fadt.pm1b_control), power_off.s5.b);
+        }
+    }
+}
+
+/// Reset the CPU by taking an exception with an empty IDT.
+fn triple_fault() -> ! {
+    use x86_64::structures::DescriptorTablePointer;
+    let empty = DescriptorTablePointer {
+        limit: 0,
+        base: x86_64::VirtAddr::zero(),
+    };
+    unsafe {
+        x86_64::instructions::tables::lidt(&empty);
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
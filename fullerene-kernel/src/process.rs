@@ -6,6 +6,7 @@
 //! `SCHEDULER`.
 
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::alloc::Layout;
 use petroleum::mem_debug;
@@ -13,6 +14,7 @@ use petroleum::page_table::PageTableHelper as _;
 use x86_64::{PhysAddr, VirtAddr};
 
 use crate::linux::runtime::DispatchMode;
+use crate::memory_management::ProcessMemoryManagerImpl;
 use crate::vdso::{VdsoPageRef, create_vdso_page};
 
 use crate::syscall::{Handle, HandlePerms, KernelObject};
@@ -20,6 +22,13 @@ use crate::syscall::{Handle, HandlePerms, KernelObject};
 /// Maximum number of processes managed by the system
 pub const MAX_PROCESSES: usize = 64;
 
+/// Scheduling priority of the idle loop; every other process outranks it.
+pub const IDLE_PRIORITY: u8 = 0;
+/// Scheduling priority given to newly created processes.
+pub const DEFAULT_PRIORITY: u8 = 16;
+/// Highest scheduling priority a process may request.
+pub const MAX_PRIORITY: u8 = 31;
+
 /// Process ID type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ProcessId(pub u64);
@@ -30,6 +39,63 @@ impl core::fmt::Display for ProcessId {
     }
 }
 
+/// Bytes kept of a process name; longer names are truncated.
+pub const PROCESS_NAME_CAPACITY: usize = 32;
+
+/// Process name stored inline, so naming a process never allocates.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ProcessName {
+    bytes: [u8; PROCESS_NAME_CAPACITY],
+    len: u8,
+}
+
+impl ProcessName {
+    /// `name`, cut at the last character boundary that fits.
+    pub fn new(name: &str) -> Self {
+        let mut len = name.len().min(PROCESS_NAME_CAPACITY);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; PROCESS_NAME_CAPACITY];
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self {
+            bytes,
+            len: len as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // `new` only stores whole characters of a `str`.
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl core::ops::Deref for ProcessName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<&str> for ProcessName {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl core::fmt::Display for ProcessName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Debug for ProcessName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
 /// Process states
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProcessState {
@@ -39,10 +105,51 @@ pub enum ProcessState {
     Running,
     /// Process is waiting for I/O or other event
     Blocked,
+    /// Process has exited but its parent has not yet collected the exit
+    /// code (kept in [`Process::exit_code`]) with `waitpid`
+    Zombie,
     /// Process has terminated
     Terminated,
 }
 
+impl ProcessState {
+    /// Name shown by `ps`; stable, so scripts may match on it.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ready => "ready",
+            Self::Running => "running",
+            Self::Blocked => "blocked",
+            Self::Zombie => "zombie",
+            Self::Terminated => "terminated",
+        }
+    }
+
+    /// Value reported in [`fullerene_abi::ProcessInfo::state`].
+    pub const fn abi_code(self) -> u32 {
+        use fullerene_abi::process_states;
+        match self {
+            Self::Ready => process_states::READY,
+            Self::Running => process_states::RUNNING,
+            Self::Blocked => process_states::BLOCKED,
+            Self::Zombie => process_states::ZOMBIE,
+            Self::Terminated => process_states::TERMINATED,
+        }
+    }
+
+    /// Inverse of [`abi_code`](Self::abi_code).
+    pub const fn from_abi_code(code: u32) -> Option<Self> {
+        use fullerene_abi::process_states;
+        match code {
+            process_states::READY => Some(Self::Ready),
+            process_states::RUNNING => Some(Self::Running),
+            process_states::BLOCKED => Some(Self::Blocked),
+            process_states::ZOMBIE => Some(Self::Zombie),
+            process_states::TERMINATED => Some(Self::Terminated),
+            _ => None,
+        }
+    }
+}
+
 /// Process context for context switching
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy)]
@@ -59,6 +166,25 @@ pub struct ProcessContext {
     pub(crate) tss: u64,
     /// Whether the process runs in user mode (Ring 3)
     pub(crate) is_user: bool,
+    /// x87/SSE registers, saved and restored by `switch_context`
+    pub(crate) fpu: crate::context_switch::FpuState,
+    /// GS base of the user program, parked in KERNEL_GS_BASE while the
+    /// process is in the kernel (see [`crate::percpu`])
+    pub(crate) user_gs_base: u64,
+    /// The process's syscall in progress, if it blocked inside one (see
+    /// [`crate::interrupts::syscall::switch_syscall_stack`])
+    pub(crate) syscall_frame: u64,
+}
+
+/// Slots for every process's [`ProcessContext`], which is created on each
+/// spawn, fork and thread start.
+pub static CONTEXT_CACHE: petroleum::SlabCache<ProcessContext> = petroleum::SlabCache::new();
+
+impl ProcessContext {
+    /// Move `self` into [`CONTEXT_CACHE`].
+    pub fn boxed(self) -> petroleum::SlabBox<ProcessContext> {
+        CONTEXT_CACHE.boxed(self)
+    }
 }
 
 impl Default for ProcessContext {
@@ -78,23 +204,61 @@ impl Default for ProcessContext {
             ],
             tss: 0,
             is_user: false,
+            fpu: Default::default(),
+            user_gs_base: 0,
+            syscall_frame: 0,
+        }
+    }
+}
+
+/// Largest number of fds one process may have open.
+pub const MAX_FDS: usize = 256;
+
+/// The object an fd refers to.
+#[derive(Clone)]
+pub enum FdObject {
+    /// Keyboard and serial input through [`crate::tty`]; output to COM1.
+    Console,
+    /// Shared, with its offset, by every fd duplicated from the one `open`
+    /// returned; see [`FdObject::close`].
+    File(Arc<spin::Mutex<crate::fs::FileDesc>>),
+    /// Pipe ends count their own clones, so the pipe sees its last reader
+    /// or writer go when the last fd on that end closes.
+    Pipe(crate::syscall::pipe::PipeEnd),
+}
+
+impl FdObject {
+    pub fn file(file_desc: crate::fs::FileDesc) -> Self {
+        Self::File(Arc::new(spin::Mutex::new(file_desc)))
+    }
+
+    /// Release one fd's reference, closing the file once no fd refers to
+    /// it any more.
+    pub fn close(self) -> Result<(), crate::fs::FsError> {
+        match self {
+            Self::File(file) => match Arc::into_inner(file) {
+                Some(file_desc) => crate::fs::close_file(file_desc.into_inner()),
+                None => Ok(()),
+            },
+            Self::Console | Self::Pipe(_) => Ok(()),
         }
     }
 }
 
 /// Per-process file descriptor table.
 pub struct FdSlotMap {
-    slots: Vec<Option<crate::fs::FileDesc>>,
+    slots: Vec<Option<FdObject>>,
 }
 
 impl FdSlotMap {
+    /// A table with fds 0-2 open on the console.
     fn new() -> Self {
         let mut slots = Vec::new();
-        slots.resize_with(3, || None);
+        slots.resize_with(3, || Some(FdObject::Console));
         Self { slots }
     }
 
-    pub fn insert(&mut self, fd: u32, value: crate::fs::FileDesc) -> Option<crate::fs::FileDesc> {
+    pub fn insert(&mut self, fd: u32, value: FdObject) -> Option<FdObject> {
         let index = fd as usize;
         if self.slots.len() <= index {
             self.slots.resize_with(index + 1, || None);
@@ -102,11 +266,15 @@ impl FdSlotMap {
         self.slots[index].replace(value)
     }
 
-    pub fn get_mut(&mut self, fd: &u32) -> Option<&mut crate::fs::FileDesc> {
+    pub fn get(&self, fd: &u32) -> Option<&FdObject> {
+        self.slots.get(*fd as usize)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, fd: &u32) -> Option<&mut FdObject> {
         self.slots.get_mut(*fd as usize)?.as_mut()
     }
 
-    pub fn remove(&mut self, fd: &u32) -> Option<crate::fs::FileDesc> {
+    pub fn remove(&mut self, fd: &u32) -> Option<FdObject> {
         self.slots.get_mut(*fd as usize)?.take()
     }
 
@@ -114,18 +282,27 @@ impl FdSlotMap {
         self.slots.get(*fd as usize).is_some_and(Option::is_some)
     }
 
-    pub fn clear(&mut self) {
-        for slot in self.slots.iter_mut().skip(3) {
-            *slot = None;
+    /// Close every fd, standard streams included, through
+    /// [`FdObject::close`].  All are closed even if some fail; the first
+    /// failure is returned.
+    pub fn clear(&mut self) -> Result<(), crate::fs::FsError> {
+        let mut result = Ok(());
+        for object in self.slots.drain(..).flatten() {
+            let closed = object.close();
+            if result.is_ok() {
+                result = closed;
+            }
         }
+        result
     }
 
-    fn first_free_from(&self, start: u32) -> Option<u32> {
-        self.slots
+    fn first_free(&self) -> Option<u32> {
+        let index = self
+            .slots
             .iter()
-            .enumerate()
-            .skip(start as usize)
-            .find_map(|(index, slot)| slot.is_none().then_some(index as u32))
+            .position(Option::is_none)
+            .unwrap_or(self.slots.len());
+        (index < MAX_FDS).then_some(index as u32)
     }
 }
 
@@ -140,15 +317,34 @@ impl FdTable {
         }
     }
 
-    pub fn alloc(&mut self, file_desc: crate::fs::FileDesc) -> Result<u32, ()> {
-        let fd = self
-            .entries
-            .first_free_from(3)
-            .map(Ok)
-            .unwrap_or_else(|| u32::try_from(self.entries.slots.len()).map_err(|_| ()))?;
-        self.entries.insert(fd, file_desc);
+    /// Install `object` at the lowest free fd.  Once [`MAX_FDS`] are open,
+    /// `object` is handed back.
+    pub fn alloc(&mut self, object: FdObject) -> Result<u32, FdObject> {
+        let Some(fd) = self.entries.first_free() else {
+            return Err(object);
+        };
+        self.entries.insert(fd, object);
         Ok(fd)
     }
+
+    /// Fds 0-2, for a child that keeps its parent's streams.
+    pub fn standard_streams(&self) -> [Option<FdObject>; 3] {
+        core::array::from_fn(|fd| self.entries.get(&(fd as u32)).cloned())
+    }
+
+    /// Replace fds 0-2; `None` closes the stream.
+    pub fn set_standard_streams(&mut self, streams: [Option<FdObject>; 3]) {
+        for (fd, stream) in streams.into_iter().enumerate() {
+            match stream {
+                Some(object) => {
+                    self.entries.insert(fd as u32, object);
+                }
+                None => {
+                    self.entries.remove(&(fd as u32));
+                }
+            }
+        }
+    }
 }
 
 /// A slot entry in the per-process handle table.
@@ -309,6 +505,8 @@ pub struct ProcessResources {
     pub handle_table: spin::Mutex<HandleTable>,
     /// Registered event subscriptions: (event_type, event_handle)
     pub subscriptions: spin::Mutex<alloc::vec::Vec<(u64, u64)>>,
+    /// Heap and program-break bookkeeping, created by the first `brk`
+    pub memory: spin::Mutex<Option<ProcessMemoryManagerImpl>>,
 }
 
 impl ProcessResources {
@@ -317,6 +515,7 @@ impl ProcessResources {
             fd_table: spin::Mutex::new(FdTable::new()),
             handle_table: spin::Mutex::new(HandleTable::new()),
             subscriptions: spin::Mutex::new(alloc::vec::Vec::new()),
+            memory: spin::Mutex::new(None),
         }
     }
 
@@ -363,10 +562,10 @@ impl ProcessResources {
         }
         drop(ht);
 
-        // Clear fd table
-        let mut ft = self.fd_table.lock();
-        ft.entries.clear();
-        drop(ft);
+        // Close every fd, files and pipe ends alike.
+        if let Err(error) = self.fd_table.lock().entries.clear() {
+            log::warn!("Closing fds of an exiting process failed: {:?}", error);
+        }
 
         to_unblock
     }
@@ -376,12 +575,23 @@ impl ProcessResources {
 pub struct Process {
     /// Unique process ID
     pub id: ProcessId,
-    /// Process name
-    pub name: &'static str,
+    /// Process name, shown by `ps` and in scheduler logs
+    pub name: ProcessName,
     /// Current state
     pub state: ProcessState,
+    /// Timer tick at which a sleeping process becomes ready again
+    pub wake_tick: Option<u64>,
+    /// Base scheduling priority (higher runs first)
+    pub priority: u8,
+    /// Timer tick since which the process has been waiting in `Ready`,
+    /// used to age its effective priority
+    pub ready_since: u64,
+    /// Microseconds of timer time during which this process was on the CPU
+    pub cpu_time_us: u64,
+    /// Signals sent to the process and not yet acted on, as `1 << signal`
+    pub pending_signals: u64,
     /// CPU context for context switching
-    pub context: Box<ProcessContext>,
+    pub context: petroleum::SlabBox<ProcessContext>,
     /// Process page table (physical address of level 4 page table)
     pub page_table_phys_addr: PhysAddr,
     /// Process page table mapper
@@ -394,6 +604,9 @@ pub struct Process {
     pub entry_point: VirtAddr,
     /// Whether the process runs in user mode (Ring 3)
     pub is_user: bool,
+    /// Whether the process may power off or reset the machine: kernel
+    /// tasks, and what a privileged process forks or spawns
+    pub privileged: bool,
     /// Exit code - used for signaling ChildProcessExited signal
     pub exit_code: Option<i32>,
     /// Parent process ID (for wait() and signal propagation)
@@ -409,21 +622,32 @@ pub struct Process {
 }
 
 impl Process {
+    /// CPU time used so far, in 1 ms ticks.
+    pub fn cpu_ticks(&self) -> u64 {
+        self.cpu_time_us / 1000
+    }
+
     /// Create a new process
-    pub fn new(name: &'static str, entry_point: VirtAddr, is_user: bool) -> Self {
+    pub fn new(name: &str, entry_point: VirtAddr, is_user: bool) -> Self {
         let id = SCHEDULER.allocate_pid();
 
         Self {
             id,
-            name,
+            name: ProcessName::new(name),
             state: ProcessState::Ready,
-            context: Box::new(ProcessContext::default()),
+            wake_tick: None,
+            priority: DEFAULT_PRIORITY,
+            ready_since: crate::scheduler::get_system_tick(),
+            cpu_time_us: 0,
+            pending_signals: 0,
+            context: ProcessContext::default().boxed(),
             page_table_phys_addr: PhysAddr::new(0), // Will be set when allocated
             page_table: None,
             kernel_stack: VirtAddr::new(0), // Will be set when allocated
             user_stack: VirtAddr::new(0),   // Will be set when allocated
             entry_point,
             is_user,
+            privileged: !is_user,
             exit_code: None,
             parent_id: None, // Will be set by fork
             task_data: 0,
@@ -433,10 +657,38 @@ impl Process {
         }
     }
 
+    /// Free the kernel stack and the address space of an exited process.
+    ///
+    /// The process runs on both until it switches away for the last time,
+    /// so [`terminate_process`] leaves them to the scheduler, which calls
+    /// this once the process is off the CPU.  Calling it again is a no-op.
+    pub(crate) fn release_memory(&mut self) {
+        if let Some(kernel_stack_base) = self
+            .kernel_stack
+            .as_u64()
+            .checked_sub(crate::heap::KERNEL_STACK_SIZE as u64)
+            .filter(|&base| base != 0)
+        {
+            let layout = Layout::from_size_align(crate::heap::KERNEL_STACK_SIZE, 16).unwrap();
+            unsafe {
+                petroleum::common::memory::deallocate_layout(kernel_stack_base as *mut u8, layout)
+            };
+        }
+        self.kernel_stack = VirtAddr::zero();
+
+        // Threads run on their process's table and own none.
+        if let Some(page_table) = self.page_table.take() {
+            if let Some(pml4_frame) = page_table.pml4_frame() {
+                drop(page_table);
+                crate::memory_management::deallocate_process_page_table(pml4_frame);
+            }
+        }
+    }
+
     /// Initialize process context for first execution
     pub fn init_context(&mut self, kernel_stack_top: VirtAddr) {
         petroleum::mem_debug!("Process: init_context for ");
-        petroleum::mem_debug!(self.name);
+        petroleum::mem_debug!(self.name.as_str());
         petroleum::mem_debug!("\n");
 
         if self.is_user {
@@ -513,19 +765,28 @@ pub fn init(heap_start: usize, heap_end: usize) {
         ],
         tss: 0,
         is_user: false,
+        fpu: Default::default(),
+        user_gs_base: 0,
+        syscall_frame: 0,
     };
 
     let idle = Box::new(Process {
         id: pid,
-        name: "idle",
+        name: ProcessName::new("idle"),
         state: ProcessState::Running,
-        context: Box::new(ctx),
+        wake_tick: None,
+        priority: IDLE_PRIORITY,
+        ready_since: 0,
+        cpu_time_us: 0,
+        pending_signals: 0,
+        context: ctx.boxed(),
         page_table_phys_addr: PhysAddr::new(0),
         page_table: None,
         kernel_stack: VirtAddr::new(0),
         user_stack: VirtAddr::new(0),
         entry_point: idle_addr,
         is_user: false,
+        privileged: true,
         exit_code: None,
         parent_id: None,
         task_data: 0,
@@ -534,6 +795,7 @@ pub fn init(heap_start: usize, heap_end: usize) {
         resources: ProcessResources::new(),
     });
 
+    SCHEDULER.set_idle_pid(pid);
     SCHEDULER.add(idle).expect("Failed to add idle process");
 
     IDLE_INIT.store(true, core::sync::atomic::Ordering::Release);
@@ -544,7 +806,7 @@ pub fn init(heap_start: usize, heap_end: usize) {
 
 /// Create a new process and add it to the process list
 pub fn create_process(
-    name: &'static str,
+    name: &str,
     entry_point_address: VirtAddr,
     is_user: bool,
 ) -> Result<ProcessId, petroleum::common::logging::SystemError> {
@@ -558,26 +820,18 @@ pub fn create_process(
     let kernel_stack_top = VirtAddr::new(stack_ptr as u64 + crate::heap::KERNEL_STACK_SIZE as u64);
 
     if is_user {
-        // Allocate user stack for the process
-        let user_stack_layout =
-            Layout::from_size_align(crate::heap::KERNEL_STACK_SIZE, 16).unwrap();
-        let user_stack_ptr = petroleum::common::memory::allocate_layout(user_stack_layout)
-            .map_err(|e| {
-                unsafe { petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout) };
-                e
-            })?;
-        process.user_stack =
-            VirtAddr::new(user_stack_ptr as u64 + crate::heap::KERNEL_STACK_SIZE as u64);
+        // RSP0 while the process runs, so interrupts from ring 3 (and a
+        // preempted process's parked timer frame) land on its own stack.
+        // Kernel processes leave it unset: they run on that stack, and
+        // exiting must not free it under them.
+        process.kernel_stack = kernel_stack_top;
 
         // Create VDSO page after page table creation
         let page_table = match crate::memory_management::create_process_page_table() {
             Ok(pt) => pt,
             Err(e) => {
                 log::error!("Failed to create process page table: {:?}", e);
-                unsafe {
-                    petroleum::common::memory::deallocate_layout(user_stack_ptr, user_stack_layout);
-                    petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout);
-                }
+                unsafe { petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout) };
                 return Err(e);
             }
         };
@@ -585,35 +839,27 @@ pub fn create_process(
         process.page_table_phys_addr = PhysAddr::new(page_table_phys);
         process.page_table = Some(Box::new(page_table));
 
-        let mut fa_lock = crate::heap::FRAME_ALLOCATOR.lock();
-        let fa = fa_lock.as_mut().ok_or_else(|| {
-            unsafe {
-                petroleum::common::memory::deallocate_layout(user_stack_ptr, user_stack_layout);
-                petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout);
-            }
-            if let Some(ref page_table) = process.page_table {
-                if let Some(pml4_frame) = page_table.pml4_frame() {
-                    crate::memory_management::deallocate_process_page_table(pml4_frame);
-                }
-            }
-            petroleum::common::logging::SystemError::InternalError
-        })?;
         let pt: &mut petroleum::page_table::process::ProcessPageTable =
             process.page_table.as_mut().unwrap();
-        let vdso_ref = create_vdso_page(pt, fa, process.id.0).map_err(|_| {
-            unsafe {
-                petroleum::common::memory::deallocate_layout(user_stack_ptr, user_stack_layout);
-                petroleum::common::memory::deallocate_layout(stack_ptr, stack_layout);
+        let id = process.id.0;
+        let mapped = map_user_stack(pt, &process.resources, id).and_then(|stack_top| {
+            let vdso_ref = petroleum::page_table::constants::with_frame_allocator(|fa| {
+                create_vdso_page(pt, fa, id)
+            })
+            .map_err(|_| petroleum::common::logging::SystemError::FrameAllocationFailed)?;
+            Ok((stack_top, vdso_ref))
+        });
+        match mapped {
+            Ok((stack_top, vdso_ref)) => {
+                process.user_stack = stack_top;
+                process.vdso_page = Some(vdso_ref);
             }
-            if let Some(ref page_table) = process.page_table {
-                if let Some(pml4_frame) = page_table.pml4_frame() {
-                    crate::memory_management::deallocate_process_page_table(pml4_frame);
-                }
+            Err(e) => {
+                // Frees the kernel stack and every frame mapped so far.
+                process.release_memory();
+                return Err(e);
             }
-            petroleum::common::logging::SystemError::FrameAllocationFailed
-        })?;
-        drop(fa_lock);
-        process.vdso_page = Some(vdso_ref);
+        }
     } else {
         // Create page table for the process (kernel process, no user stack)
         let page_table = match crate::memory_management::create_process_page_table() {
@@ -632,12 +878,42 @@ pub fn create_process(
     process.init_context(kernel_stack_top);
 
     let pid = process.id;
+    let name = process.name;
     SCHEDULER.add(Box::new(process))?;
+    log::info!("Process {} ({}) created", pid, name);
 
     mem_debug!("Process: create_process done\n");
     Ok(pid)
 }
 
+/// Size of the stack a new user process starts on.
+pub const USER_STACK_SIZE: usize = 64 * 1024;
+
+/// Map a fresh user stack into `page_table` and return its top.
+///
+/// The stack is carved out of the process's memory bookkeeping, which
+/// reserves a guard page directly below it.  The guard is left unmapped, so
+/// overflowing the stack faults instead of running into the next mapping.
+fn map_user_stack(
+    page_table: &mut petroleum::page_table::process::ProcessPageTable,
+    resources: &ProcessResources,
+    id: u64,
+) -> Result<VirtAddr, petroleum::common::logging::SystemError> {
+    let mut memory = resources.memory.lock();
+    let memory = memory.get_or_insert_with(|| ProcessMemoryManagerImpl::new(id as usize));
+    let base = memory.allocate_stack(USER_STACK_SIZE)?;
+    let flags = crate::syscall::memory::user_page_flags(
+        fullerene_abi::memory_protection::READ | fullerene_abi::memory_protection::WRITE,
+    );
+    if crate::syscall::memory::map_user_pages(page_table, base, base + USER_STACK_SIZE, flags)
+        .is_err()
+    {
+        let _ = memory.free_stack(base, USER_STACK_SIZE);
+        return Err(petroleum::common::logging::SystemError::MemOutOfMemory);
+    }
+    Ok(VirtAddr::new((base + USER_STACK_SIZE) as u64))
+}
+
 /// Unblock parent processes that are waiting for this child process
 fn unblock_waiting_parents(child_pid: ProcessId) {
     let parent_to_unblock = SCHEDULER.with_list(|list| {
@@ -657,50 +933,58 @@ fn unblock_waiting_parents(child_pid: ProcessId) {
 }
 
 /// Terminate a process
+///
+/// A process whose parent is still alive becomes a [`ProcessState::Zombie`]
+/// until the parent reaps it; orphans go straight to `Terminated`.
 pub fn terminate_process(pid: ProcessId, exit_code: i32) {
-    let to_unblock = SCHEDULER
+    let has_live_parent = SCHEDULER.with_list(|list| {
+        let parent = list
+            .iter()
+            .find(|(id, _)| *id == pid)
+            .and_then(|(_, proc)| proc.parent_id);
+        parent.is_some_and(|parent_id| {
+            list.iter().any(|(id, proc)| {
+                *id == parent_id
+                    && !matches!(proc.state, ProcessState::Zombie | ProcessState::Terminated)
+            })
+        })
+    });
+
+    let (to_unblock, exited) = SCHEDULER
         .with_process(pid, |process| {
             // The idle task owns neither an allocated stack nor a replacement task.
             // It is a scheduler invariant, not a terminable user process.
-            if process.name == "idle" {
-                return Vec::new();
+            if SCHEDULER.is_idle(pid) {
+                return (Vec::new(), None);
             }
-            process.state = ProcessState::Terminated;
+            process.state = if has_live_parent {
+                ProcessState::Zombie
+            } else {
+                ProcessState::Terminated
+            };
             process.exit_code = Some(exit_code);
 
             // Clean up per-process resources (fd table, handle table)
             // Collects waiters to unblock outside the process-manager lock.
+            // The kernel stack and page table may be the ones this runs on;
+            // `release_memory` frees them after the final switch.
             let waiters = process.resources.cleanup();
 
-            // Free resources
-            if let Some(kernel_stack_base) = process
-                .kernel_stack
-                .as_u64()
-                .checked_sub(crate::heap::KERNEL_STACK_SIZE as u64)
-                .filter(|&base| base != 0)
-            {
-                let layout = Layout::from_size_align(crate::heap::KERNEL_STACK_SIZE, 16).unwrap();
-                unsafe {
-                    petroleum::common::memory::deallocate_layout(
-                        kernel_stack_base as *mut u8,
-                        layout,
-                    )
-                };
-            }
-
-            // Properly free page table frames recursively
-            if let Some(page_table) = process.page_table.take() {
-                if let Some(pml4_frame) = page_table.pml4_frame() {
-                    drop(page_table);
-                    crate::memory_management::deallocate_process_page_table(pml4_frame);
-                }
-            }
-
-            process.page_table = None;
-
-            waiters
+            (waiters, Some(process.name))
         })
         .unwrap_or_default();
+    if let Some(name) = exited {
+        log::info!("Process {} ({}) exited with code {}", pid, name, exit_code);
+        crate::syscall::shm::release_process(pid);
+        crate::syscall::sem::release_process(pid);
+    }
+
+    // Nobody is left to reap this process's own zombie children.
+    SCHEDULER.for_each_process_mut(|child| {
+        if child.parent_id == Some(pid) && child.state == ProcessState::Zombie {
+            child.state = ProcessState::Terminated;
+        }
+    });
 
     // Unblock waiters (handles, parent) outside the process-manager lock.
     for waiter in to_unblock {
@@ -726,7 +1010,35 @@ fn idle_loop() {
     }
 }
 
-/// Schedule next process (round-robin)
+/// Terminate every process other than the current one that has a pending
+/// `SIGKILL`, reporting [`signals::KILLED_EXIT_CODE`] as its exit code.
+///
+/// Runs at the start of each scheduling pass, so a killed process is never
+/// dispatched again.
+///
+/// [`signals::KILLED_EXIT_CODE`]: fullerene_abi::signals::KILLED_EXIT_CODE
+pub fn deliver_pending_kills() {
+    use fullerene_abi::signals;
+    let current = current_pid();
+    let killed: Vec<ProcessId> = SCHEDULER.with_list(|list| {
+        list.iter()
+            .filter(|(id, process)| {
+                Some(*id) != current
+                    && process.pending_signals & (1 << signals::SIGKILL) != 0
+                    && !matches!(
+                        process.state,
+                        ProcessState::Zombie | ProcessState::Terminated
+                    )
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    });
+    for pid in killed {
+        terminate_process(pid, signals::KILLED_EXIT_CODE);
+    }
+}
+
+/// Schedule next process (highest priority first, round-robin among equals)
 pub fn schedule_next() {
     SCHEDULER.schedule_next();
 }
@@ -751,11 +1063,38 @@ pub fn yield_current() {
     }
 }
 
+/// Yield to the other ready processes of the caller's priority
+pub fn yield_turn() {
+    SCHEDULER.yield_turn();
+}
+
 /// Perform context switch between two processes
 pub unsafe fn context_switch(old_pid: Option<ProcessId>, new_pid: ProcessId) {
     unsafe { SCHEDULER.context_switch(old_pid, new_pid) };
 }
 
+/// Snapshot every process as an ABI record, lowest pid first.
+pub fn process_infos() -> Vec<fullerene_abi::ProcessInfo> {
+    SCHEDULER.update_cpu_time();
+    let mut infos: Vec<_> = SCHEDULER.with_list(|list| {
+        list.iter()
+            .map(|(pid, process)| {
+                let mut name = [0u8; PROCESS_NAME_CAPACITY];
+                name[..process.name.len()].copy_from_slice(process.name.as_bytes());
+                fullerene_abi::ProcessInfo {
+                    pid: pid.0,
+                    state: process.state.abi_code(),
+                    priority: process.priority as u32,
+                    cpu_ticks: process.cpu_ticks(),
+                    name,
+                }
+            })
+            .collect()
+    });
+    infos.sort_unstable_by_key(|info| info.pid);
+    infos
+}
+
 /// Block current process
 pub fn block_current() {
     SCHEDULER.block_current();
@@ -763,11 +1102,7 @@ pub fn block_current() {
 
 /// Unblock a process
 pub fn unblock_process(pid: ProcessId) {
-    SCHEDULER.with_process(pid, |process| {
-        if process.state == ProcessState::Blocked {
-            process.state = ProcessState::Ready;
-        }
-    });
+    SCHEDULER.unblock_process(pid);
 }
 
 #[cfg(test)]
@@ -812,6 +1147,60 @@ mod tests {
         assert_eq!(proc.state, ProcessState::Ready);
     }
 
+    #[test]
+    fn long_names_are_truncated_on_a_char_boundary() {
+        let name = ProcessName::new("a-rather-long-process-name-that-keeps-going");
+        assert_eq!(name.len(), PROCESS_NAME_CAPACITY);
+        assert_eq!(name, "a-rather-long-process-name-that-");
+        // 31 ASCII bytes leave no room for the two-byte 'é'.
+        let name = ProcessName::new("abcdefghijklmnopqrstuvwxyzabcdeé");
+        assert_eq!(name.len(), 31);
+        assert_eq!(ProcessName::new("shell"), "shell");
+    }
+
+    #[test]
+    fn pending_sigkill_terminates_the_target() {
+        init(0, 0);
+        let looper = Box::new(Process::new("looper", VirtAddr::new(0), false));
+        let pid = looper.id;
+        SCHEDULER.add(looper).unwrap();
+
+        deliver_pending_kills();
+        assert_eq!(
+            SCHEDULER.with_process(pid, |p| p.state),
+            Some(ProcessState::Ready)
+        );
+
+        SCHEDULER.with_process(pid, |p| {
+            p.pending_signals |= 1 << fullerene_abi::signals::SIGKILL;
+        });
+        deliver_pending_kills();
+        assert_eq!(
+            SCHEDULER.with_process(pid, |p| (p.state, p.exit_code)),
+            Some((
+                ProcessState::Terminated,
+                Some(fullerene_abi::signals::KILLED_EXIT_CODE)
+            ))
+        );
+    }
+
+    #[test]
+    fn state_codes_and_names_are_stable() {
+        let states = [
+            ProcessState::Ready,
+            ProcessState::Running,
+            ProcessState::Blocked,
+            ProcessState::Zombie,
+            ProcessState::Terminated,
+        ];
+        for (code, state) in states.into_iter().enumerate() {
+            assert_eq!(state.abi_code(), code as u32);
+            assert_eq!(ProcessState::from_abi_code(code as u32), Some(state));
+        }
+        assert_eq!(ProcessState::Zombie.as_str(), "zombie");
+        assert_eq!(ProcessState::from_abi_code(99), None);
+    }
+
     #[test]
     fn test_process_counting() {
         // Initialize the process management system with dummy heap range
@@ -827,12 +1216,12 @@ mod tests {
 
         first.fd_table.lock().entries.insert(
             3,
-            crate::fs::FileDesc {
+            FdObject::file(crate::fs::FileDesc {
                 fd: 3,
                 ino: 11,
                 offset: 7,
                 flags: 0,
-            },
+            }),
         );
         let first_handle = first
             .handle_table
@@ -847,22 +1236,109 @@ mod tests {
 
     #[test]
     fn fd_slots_reuse_holes_without_overwriting_later_entries() {
-        fn file_desc(ino: u64) -> crate::fs::FileDesc {
-            crate::fs::FileDesc {
+        fn file_desc(ino: u64) -> FdObject {
+            FdObject::file(crate::fs::FileDesc {
                 fd: 0,
                 ino,
                 offset: 0,
                 flags: 0,
+            })
+        }
+        fn ino(object: Option<&FdObject>) -> Option<u64> {
+            match object {
+                Some(FdObject::File(file)) => Some(file.lock().ino),
+                _ => None,
             }
         }
 
         let mut table = FdTable::new();
-        assert_eq!(table.alloc(file_desc(30)), Ok(3));
-        assert_eq!(table.alloc(file_desc(40)), Ok(4));
-        assert_eq!(table.entries.remove(&3).map(|entry| entry.ino), Some(30));
-        assert_eq!(table.alloc(file_desc(31)), Ok(3));
-        assert_eq!(table.alloc(file_desc(50)), Ok(5));
-        assert_eq!(table.entries.get_mut(&4).map(|entry| entry.ino), Some(40));
+        assert_eq!(table.alloc(file_desc(30)).ok(), Some(3));
+        assert_eq!(table.alloc(file_desc(40)).ok(), Some(4));
+        assert_eq!(ino(table.entries.remove(&3).as_ref()), Some(30));
+        assert_eq!(table.alloc(file_desc(31)).ok(), Some(3));
+        assert_eq!(table.alloc(file_desc(50)).ok(), Some(5));
+        assert_eq!(ino(table.entries.get(&4)), Some(40));
+    }
+
+    #[test]
+    fn standard_fds_start_on_the_console_and_can_be_reused() {
+        let mut table = FdTable::new();
+        assert!((0..3).all(|fd| matches!(table.entries.get(&fd), Some(FdObject::Console))));
+        assert!(table.entries.remove(&1).is_some());
+        assert!(table.entries.remove(&1).is_none());
+        assert_eq!(table.alloc(FdObject::Console).ok(), Some(1));
+
+        let (read_end, _write_end) = crate::syscall::pipe::pipe();
+        table.entries.insert(0, FdObject::Pipe(read_end));
+        let streams = table.standard_streams();
+        assert!(matches!(streams[0], Some(FdObject::Pipe(_))));
+        assert!(matches!(streams[2], Some(FdObject::Console)));
+    }
+
+    #[test]
+    fn duplicated_fds_share_one_open_file() {
+        let mut table = FdTable::new();
+        let file = FdObject::file(crate::fs::FileDesc {
+            fd: 9,
+            ino: 12,
+            offset: 0,
+            flags: 0,
+        });
+        assert_eq!(table.alloc(file).ok(), Some(3));
+        let duplicate = table.entries.get(&3).cloned().unwrap();
+        assert_eq!(table.alloc(duplicate).ok(), Some(4));
+
+        let Some(FdObject::File(first)) = table.entries.get(&3) else {
+            panic!("fd 3 is not a file");
+        };
+        first.lock().offset = 40;
+        // Closing one fd leaves the file open, offset and all, on the other.
+        assert!(table.entries.remove(&3).unwrap().close().is_ok());
+        let Some(FdObject::File(second)) = table.entries.get(&4) else {
+            panic!("fd 4 is not a file");
+        };
+        assert_eq!(second.lock().offset, 40);
+        assert_eq!(Arc::strong_count(second), 1);
+    }
+
+    #[test]
+    fn clearing_the_table_closes_every_fd() {
+        let mut table = FdTable::new();
+        let file = FdObject::file(crate::fs::FileDesc {
+            fd: 9,
+            ino: 12,
+            offset: 0,
+            flags: 0,
+        });
+        let FdObject::File(shared) = &file else {
+            unreachable!();
+        };
+        let shared = Arc::clone(shared);
+        assert_eq!(table.alloc(file).ok(), Some(3));
+        let (read_end, write_end) = crate::syscall::pipe::pipe();
+        assert_eq!(table.alloc(FdObject::Pipe(write_end)).ok(), Some(4));
+
+        // The file is still held here, so closing its fd only drops a
+        // reference and cannot fail.
+        assert!(table.entries.clear().is_ok());
+        assert!((0..5).all(|fd| !table.entries.contains_key(&fd)));
+        assert_eq!(Arc::strong_count(&shared), 1);
+        let mut out = [0u8; 4];
+        assert_eq!(read_end.read(&mut out), Ok(0));
+    }
+
+    #[test]
+    fn fd_allocation_stops_at_the_limit() {
+        let mut table = FdTable::new();
+        for fd in 3..MAX_FDS {
+            assert_eq!(table.alloc(FdObject::Console).ok(), Some(fd as u32));
+        }
+        assert!(matches!(
+            table.alloc(FdObject::Console),
+            Err(FdObject::Console)
+        ));
+        table.entries.remove(&7);
+        assert_eq!(table.alloc(FdObject::Console).ok(), Some(7));
     }
 
     #[test]
@@ -9,15 +9,18 @@
 //! ```text
 //! scheduler_loop()
 //!   ├── update_vdso_all()       — publish time to every process's VDSO page
+//!   ├── wake_sleepers()         — ready processes whose sleep deadline passed
+//!   ├── yield_current()         — run ready processes until they yield back
 //!   ├── solvent::poll_*()       — poll input devices (no interrupt path)
 //!   ├── gui::runtime_tick()     — solvent tick_core + framebuffer render
 //!   ├── shell launch check      — via KERNEL lock (independent of SCHEDULER)
 //!   ├── advance_tick()
-//!   └── hlt()
+//!   └── hlt()                   — skipped while input is pending
 //! ```
 
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use x86_64::VirtAddr;
+use x86_64::structures::idt::InterruptStackFrame;
 
 use crate::gui;
 use crate::scheduler_context::SCHEDULER;
@@ -63,6 +66,21 @@ fn read_rtc_us() -> Option<u64> {
     Some((total_seconds as u64) * 1_000_000)
 }
 
+/// Milliseconds of APIC timer time since the timer was started.
+///
+/// This is the clock used for tick-based sleeps; unlike the scheduler's
+/// loop counter it keeps advancing while a process other than the idle
+/// loop owns the CPU.  One tick is 1 ms whatever the current timer rate,
+/// but while idle it only advances in 10 ms steps.
+pub fn get_system_tick() -> u64 {
+    crate::interrupts::TICK_COUNTER.load(Ordering::Relaxed)
+}
+
+/// Base scheduling priority of `pid`, or `None` if no such process exists.
+pub fn get_process_priority(pid: crate::process::ProcessId) -> Option<u8> {
+    SCHEDULER.priority(pid)
+}
+
 /// NMI recovery dedicated stack (writable, 16-byte aligned).
 /// Must be mutable so recovery pushes can write to it without faulting.
 #[repr(align(16))]
@@ -115,6 +133,23 @@ pub fn scheduler_loop() -> ! {
         VirtAddr::from_ptr(mmio_recovery_restart as *const ()),
     );
 
+    // Init is over; long boot steps can no longer trip the watchdog.
+    SCHEDULER_RUNNING.store(true, Ordering::Release);
+    if let Some(timeout) = crate::boot::cmdline_param("watchdog").and_then(|ms| ms.parse().ok())
+        && enable_watchdog(timeout).is_ok()
+    {
+        log::info!("Scheduler watchdog armed: {} ms", timeout);
+    }
+    if let Some(ticks) = crate::boot::cmdline_param("timeslice").and_then(|ms| ms.parse().ok()) {
+        SCHEDULER.set_time_slice(ticks);
+    }
+    // `flasks --test` boots with `test`: reaching the scheduler is the
+    // pass condition.
+    if crate::boot::cmdline_param("test").is_some() {
+        log::info!("test: boot reached the scheduler, shutting down");
+        crate::power::shutdown(0);
+    }
+
     // Idle loop: drive runtime ticks.
     // Shell and other apps are launched via AppGrid or context menu.
     loop {
@@ -124,7 +159,7 @@ pub fn scheduler_loop() -> ! {
             let tsc = unsafe { core::arch::x86_64::_rdtsc() };
             (tsc as u128 * 1000 / solvent::get_tsc_per_ms() as u128) as u64
         } else {
-            crate::interrupts::TICK_COUNTER.load(Ordering::Relaxed)
+            get_system_tick() * 1000
         };
 
         // Obtain wall-clock time from RTC; fallback to uptime if RTC unavailable
@@ -132,6 +167,11 @@ pub fn scheduler_loop() -> ! {
 
         SCHEDULER.update_vdso_all(uptime_us, wall_us);
 
+        // Wake expired sleepers and give any ready process its turn; the
+        // idle loop is resumed once they yield, block, or exit.
+        SCHEDULER.wake_sleepers(get_system_tick());
+        SCHEDULER.yield_current();
+
         // Poll input devices before the runtime tick so that even
         // without interrupt delivery (some firmware / VM configs) the
         // desktop remains responsive and doesn't hang after the first
@@ -151,8 +191,147 @@ pub fn scheduler_loop() -> ! {
             petroleum::serial::_print(format_args!("Shell exited, back to idle\n"));
         }
 
+        // Run the timer fast while processes compete for the CPU and let it
+        // slow down while only the idle loop has work.
+        crate::interrupts::apic::set_timer_hz(if SCHEDULER.has_ready_work() {
+            crate::interrupts::apic::ACTIVE_TIMER_HZ
+        } else {
+            crate::interrupts::apic::IDLE_TIMER_HZ
+        });
+
         SCHEDULER.advance_tick();
-        x86_64::instructions::hlt();
+        // Interrupts stay masked between the check and the halt, so input
+        // arriving in between wakes the `hlt` instead of waiting out a tick.
+        x86_64::instructions::interrupts::disable();
+        if crate::interrupts::input::take_input_event() {
+            x86_64::instructions::interrupts::enable();
+        } else {
+            x86_64::instructions::interrupts::enable_and_hlt();
+        }
+    }
+}
+
+// ── Watchdog ──────────────────────────────────────────────────────
+
+/// Set once [`scheduler_loop`] is about to start idling.
+static SCHEDULER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+static WATCHDOG: Watchdog = Watchdog::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogError {
+    /// Kernel init has not finished; its long steps would trip the watchdog.
+    SchedulerNotRunning,
+}
+
+/// Report over serial when no scheduling point is passed for
+/// `timeout_ticks` ticks (1 ms each).
+///
+/// Scheduling points are calls to [`SchedulerContext::schedule_next`],
+/// which the idle loop makes on each iteration and processes make whenever
+/// they yield, sleep or block.  A stall therefore means the idle loop, a driver it
+/// polls, or a process that never yields has kept the CPU.  The report
+/// names the current pid and the interrupted RIP and is made once per
+/// stall; the process is not preempted.  A timeout of 0 disarms the
+/// watchdog.
+///
+/// [`SchedulerContext::schedule_next`]: crate::scheduler_context::SchedulerContext::schedule_next
+pub fn enable_watchdog(timeout_ticks: u64) -> Result<(), WatchdogError> {
+    if !SCHEDULER_RUNNING.load(Ordering::Acquire) {
+        return Err(WatchdogError::SchedulerNotRunning);
+    }
+    WATCHDOG.arm(timeout_ticks, get_system_tick(), SCHEDULER.heartbeat());
+    Ok(())
+}
+
+/// Check for a stall; called from the timer interrupt.
+pub(crate) fn watchdog_tick(frame: &InterruptStackFrame) {
+    // Serial output is lock-free, so the report gets out even if the
+    // stuck code holds a lock.
+    match WATCHDOG.check(get_system_tick(), SCHEDULER.heartbeat()) {
+        WatchdogEvent::Quiet => {}
+        WatchdogEvent::Stalled { ticks } => {
+            petroleum::serial::serial_log(format_args!(
+                "[watchdog] no scheduling point for {} ms: pid {} at rip {:#x} (ring {})\n",
+                ticks,
+                SCHEDULER.current_pid(),
+                frame.instruction_pointer.as_u64(),
+                frame.code_segment.0 & 3,
+            ));
+        }
+        WatchdogEvent::Recovered { ticks } => {
+            petroleum::serial::serial_log(format_args!(
+                "[watchdog] scheduling resumed after {} ms\n",
+                ticks
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatchdogEvent {
+    Quiet,
+    /// The heartbeat has not moved for `ticks`; reported once per stall.
+    Stalled {
+        ticks: u64,
+    },
+    /// The heartbeat moved again after a reported stall of `ticks`.
+    Recovered {
+        ticks: u64,
+    },
+}
+
+/// Stall detector fed with the tick count and the scheduler heartbeat.
+///
+/// Only the timer interrupt calls [`Watchdog::check`], so the atomics just
+/// make the state shareable with [`enable_watchdog`].
+struct Watchdog {
+    /// Ticks without a heartbeat that count as a stall; 0 when disarmed.
+    timeout: AtomicU64,
+    last_beat: AtomicU64,
+    /// Tick at which `last_beat` was seen to change.
+    beat_tick: AtomicU64,
+    reported: AtomicBool,
+}
+
+impl Watchdog {
+    const fn new() -> Self {
+        Self {
+            timeout: AtomicU64::new(0),
+            last_beat: AtomicU64::new(0),
+            beat_tick: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    fn arm(&self, timeout: u64, now: u64, beat: u64) {
+        self.timeout.store(0, Ordering::Release);
+        self.last_beat.store(beat, Ordering::Relaxed);
+        self.beat_tick.store(now, Ordering::Relaxed);
+        self.reported.store(false, Ordering::Relaxed);
+        self.timeout.store(timeout, Ordering::Release);
+    }
+
+    fn check(&self, now: u64, beat: u64) -> WatchdogEvent {
+        let timeout = self.timeout.load(Ordering::Acquire);
+        if timeout == 0 {
+            return WatchdogEvent::Quiet;
+        }
+        let since = self.beat_tick.load(Ordering::Relaxed);
+        if self.last_beat.swap(beat, Ordering::Relaxed) != beat {
+            self.beat_tick.store(now, Ordering::Relaxed);
+            if self.reported.swap(false, Ordering::Relaxed) {
+                return WatchdogEvent::Recovered {
+                    ticks: now.saturating_sub(since),
+                };
+            }
+            return WatchdogEvent::Quiet;
+        }
+        let ticks = now.saturating_sub(since);
+        if ticks >= timeout && !self.reported.swap(true, Ordering::Relaxed) {
+            return WatchdogEvent::Stalled { ticks };
+        }
+        WatchdogEvent::Quiet
     }
 }
 
@@ -179,3 +358,33 @@ pub extern "C" fn mmio_recovery_restart() -> ! {
     nitrogen::iwlwifi::force_init_failed();
     scheduler_loop()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_reports_a_stall_once_and_then_the_recovery() {
+        let watchdog = Watchdog::new();
+        assert_eq!(watchdog.check(0, 0), WatchdogEvent::Quiet);
+
+        watchdog.arm(100, 1_000, 7);
+        // Heartbeats keep it quiet however much time passes.
+        for (now, beat) in [(1_050, 8), (1_200, 9), (1_299, 9)] {
+            assert_eq!(watchdog.check(now, beat), WatchdogEvent::Quiet);
+        }
+        assert_eq!(
+            watchdog.check(1_300, 9),
+            WatchdogEvent::Stalled { ticks: 100 }
+        );
+        assert_eq!(watchdog.check(1_500, 9), WatchdogEvent::Quiet);
+        assert_eq!(
+            watchdog.check(1_510, 10),
+            WatchdogEvent::Recovered { ticks: 310 }
+        );
+        assert_eq!(watchdog.check(1_520, 10), WatchdogEvent::Quiet);
+
+        watchdog.arm(0, 2_000, 10);
+        assert_eq!(watchdog.check(9_000, 10), WatchdogEvent::Quiet);
+    }
+}
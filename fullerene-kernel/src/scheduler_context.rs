@@ -31,12 +31,32 @@ use x86_64::registers::control::Cr3;
 use x86_64::structures::paging::PhysFrame;
 
 use crate::context_switch::switch_context;
-use crate::process::{MAX_PROCESSES, Process, ProcessContext, ProcessId, ProcessState};
+use crate::process::{
+    MAX_PRIORITY, MAX_PROCESSES, Process, ProcessContext, ProcessId, ProcessState,
+};
 use crate::vdso;
 
 /// Scheduler tick interval in nanoseconds (for future use).
 const _TICK_NANOS: u64 = 2_250_000; // ~2.25 ms ≈ 1 PIT tick
 
+/// Ticks a ready process must wait before its effective priority is raised by one.
+const PRIORITY_AGING_TICKS: u64 = 50;
+
+/// Ticks a user process runs before the timer preempts it, unless changed
+/// with [`SchedulerContext::set_time_slice`].
+pub const DEFAULT_TIME_SLICE_TICKS: u64 = 10;
+
+/// Outcome of [`SchedulerContext::reap_child`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReapResult {
+    /// A zombie child was removed from the process list.
+    Exited { pid: ProcessId, exit_code: i32 },
+    /// A matching child exists but has not exited yet.
+    Alive,
+    /// The caller has no matching child.
+    NoChild,
+}
+
 /// ── Global singleton ──────────────────────────────────────────────
 
 pub static SCHEDULER: SchedulerContext = SchedulerContext::new();
@@ -49,12 +69,28 @@ pub struct SchedulerContext {
 
     // ── Schedule state (lock‑free atomics) ──────────────────
     next_pid: AtomicUsize,
+    /// The idle process, which runs when nothing else can; 0 until
+    /// [`crate::process::init`] creates it.
+    idle_pid: AtomicU64,
     schedule_index: AtomicUsize,
-    current_pid: AtomicUsize,
 
     // ── Scheduler loop state ────────────────────────────────
     tsc_per_ms: AtomicU64,
     tick_counter: AtomicU64,
+    /// Scheduling passes made, bumped on every [`Self::schedule_next`];
+    /// the watchdog in [`crate::scheduler`] watches it for progress.
+    heartbeat: AtomicU64,
+
+    // ── CPU time accounting ─────────────────────────────────
+    /// Timer time not yet credited to the current process.
+    uncredited_cpu_us: AtomicU64,
+    /// Timer time spent with only the idle loop on the CPU.
+    idle_cpu_us: AtomicU64,
+    /// Timer time since the last scheduling pass.
+    slice_used_us: AtomicU64,
+    /// Timer time a user process may run before the timer preempts it;
+    /// 0 leaves user processes to yield on their own.
+    time_slice_us: AtomicU64,
 
     // ── NMI recovery target ─────────────────────────────────
     recovery_rsp: AtomicU64,
@@ -67,10 +103,15 @@ impl SchedulerContext {
         Self {
             processes: spin::Mutex::new(HeaplessVec::new()),
             next_pid: AtomicUsize::new(1),
+            idle_pid: AtomicU64::new(0),
             schedule_index: AtomicUsize::new(0),
-            current_pid: AtomicUsize::new(0),
             tsc_per_ms: AtomicU64::new(0),
             tick_counter: AtomicU64::new(0),
+            heartbeat: AtomicU64::new(0),
+            uncredited_cpu_us: AtomicU64::new(0),
+            idle_cpu_us: AtomicU64::new(0),
+            slice_used_us: AtomicU64::new(0),
+            time_slice_us: AtomicU64::new(DEFAULT_TIME_SLICE_TICKS * 1000),
             recovery_rsp: AtomicU64::new(0),
             recovery_rip: AtomicU64::new(0),
         }
@@ -92,6 +133,52 @@ impl SchedulerContext {
     pub fn current_tick(&self) -> u64 {
         self.tick_counter.load(Ordering::Relaxed)
     }
+    pub fn heartbeat(&self) -> u64 {
+        self.heartbeat.load(Ordering::Relaxed)
+    }
+
+    // ── CPU time accounting ─────────────────────────────────
+
+    /// Charge one timer period to whichever process is on the CPU.
+    ///
+    /// Called from the timer interrupt, which must not take the process
+    /// lock; the time is credited by the next scheduling pass or
+    /// [`Self::update_cpu_time`].
+    pub fn charge_timer_tick(&self, period_us: u64) {
+        self.uncredited_cpu_us
+            .fetch_add(period_us, Ordering::Relaxed);
+        self.slice_used_us.fetch_add(period_us, Ordering::Relaxed);
+    }
+
+    /// Credit the time charged since the last call to the current process.
+    ///
+    /// Must run before `current_pid` changes so that every timer period
+    /// lands on the process that was actually running.
+    fn credit_cpu_time(&self, list: &mut HeaplessVec<(ProcessId, Box<Process>), MAX_PROCESSES>) {
+        let elapsed = self.uncredited_cpu_us.swap(0, Ordering::Relaxed);
+        if elapsed == 0 {
+            return;
+        }
+        let current = ProcessId(self.current_pid() as u64);
+        if let Some((_, process)) = list.iter_mut().find(|(id, _)| *id == current) {
+            process.cpu_time_us += elapsed;
+            if !self.is_idle(current) {
+                return;
+            }
+        }
+        self.idle_cpu_us.fetch_add(elapsed, Ordering::Relaxed);
+    }
+
+    /// Bring the CPU time of the current process up to date.
+    pub fn update_cpu_time(&self) {
+        self.with_list(|list| self.credit_cpu_time(list));
+    }
+
+    /// Idle time so far, in 1 ms ticks, including time before the idle
+    /// process existed.
+    pub fn idle_ticks(&self) -> u64 {
+        self.idle_cpu_us.load(Ordering::Relaxed) / 1000
+    }
 
     // ── PID allocation ──────────────────────────────────────
 
@@ -99,6 +186,17 @@ impl SchedulerContext {
         ProcessId(self.next_pid.fetch_add(1, Ordering::Relaxed) as u64)
     }
 
+    /// Record which process is the idle process.
+    pub fn set_idle_pid(&self, pid: ProcessId) {
+        self.idle_pid.store(pid.0, Ordering::Relaxed);
+    }
+
+    /// Whether `pid` is the idle process.  By pid rather than by name, which
+    /// any process can take.
+    pub fn is_idle(&self, pid: ProcessId) -> bool {
+        pid.0 != 0 && pid.0 == self.idle_pid.load(Ordering::Relaxed)
+    }
+
     // ── Process list access ──────────────────────────────────
 
     /// Add a new process to the list.
@@ -174,20 +272,96 @@ impl SchedulerContext {
             .count()
     }
 
-    /// Remove terminated processes.
+    /// Whether any process other than the idle process is ready or running.
+    pub fn has_ready_work(&self) -> bool {
+        self.processes.lock().iter().any(|(id, p)| {
+            !self.is_idle(*id) && matches!(p.state, ProcessState::Ready | ProcessState::Running)
+        })
+    }
+
+    /// Reap an exited child of `parent`: `child == None` matches any child.
+    ///
+    /// A matching zombie is removed from the list and its exit code returned.
+    pub fn reap_child(&self, parent: ProcessId, child: Option<ProcessId>) -> ReapResult {
+        let mut procs = self.processes.lock();
+        let mut alive = false;
+        let mut zombie = None;
+        for (idx, (id, p)) in procs.iter().enumerate() {
+            if p.parent_id != Some(parent) || child.is_some_and(|c| c != *id) {
+                continue;
+            }
+            if p.state == ProcessState::Zombie {
+                zombie = Some(idx);
+                break;
+            }
+            if p.state != ProcessState::Terminated {
+                alive = true;
+            }
+        }
+
+        let Some(idx) = zombie else {
+            return if alive {
+                ReapResult::Alive
+            } else {
+                ReapResult::NoChild
+            };
+        };
+        let (pid, mut process) = procs.remove(idx);
+        // Keep the schedule index on the same process after the shift.
+        let current = self.schedule_index();
+        if idx < current {
+            self.set_schedule_index(current - 1);
+        }
+        drop(procs);
+        // A zombie has switched away for good.
+        process.release_memory();
+        ReapResult::Exited {
+            pid,
+            exit_code: process.exit_code.unwrap_or(0),
+        }
+    }
+
+    /// Free the memory of exited processes and remove the terminated ones.
+    ///
+    /// The running process is skipped: it may be exiting on its own kernel
+    /// stack and page table, which stay in use until it switches away.
     pub fn cleanup(&self) {
+        let current = ProcessId(self.current_pid() as u64);
         let mut procs = self.processes.lock();
-        procs.retain(|(_, p)| !matches!(p.state, ProcessState::Terminated));
+        for (id, p) in procs.iter_mut() {
+            if *id != current && matches!(p.state, ProcessState::Zombie | ProcessState::Terminated)
+            {
+                p.release_memory();
+            }
+        }
+        let mut idx = 0;
+        while idx < procs.len() {
+            let (id, p) = &procs[idx];
+            if *id == current || p.state != ProcessState::Terminated {
+                idx += 1;
+                continue;
+            }
+            procs.remove(idx);
+            // Keep the schedule index on the same process after the shift.
+            let scheduled = self.schedule_index();
+            if idx < scheduled {
+                self.set_schedule_index(scheduled - 1);
+            }
+        }
     }
 
-    // ── Current PID ─────────────────────────────────────────
+    // ── Current PID (per CPU) ───────────────────────────────
 
     pub fn current_pid(&self) -> usize {
-        self.current_pid.load(Ordering::SeqCst)
+        crate::percpu::this_cpu()
+            .current_process
+            .load(Ordering::SeqCst)
     }
 
     pub fn set_current_pid(&self, pid: usize) {
-        self.current_pid.store(pid, Ordering::SeqCst);
+        crate::percpu::this_cpu()
+            .current_process
+            .store(pid, Ordering::SeqCst);
     }
 
     pub fn schedule_index(&self) -> usize {
@@ -198,12 +372,20 @@ impl SchedulerContext {
         self.schedule_index.store(idx, Ordering::SeqCst);
     }
 
-    // ── Scheduling (round‑robin) ────────────────────────────
+    // ── Scheduling (priority, round‑robin within a level) ───
 
     /// Select the next ready process and update global state.
-    /// Returns `(old_pid, new_pid)`.
+    ///
+    /// The ready process with the highest effective priority wins; ties
+    /// are broken round‑robin by scanning from the slot after the current
+    /// one.  Returns `(old_pid, new_pid)`.
     pub fn schedule_next(&self) -> (Option<ProcessId>, ProcessId) {
         petroleum::scheduler_log!("Starting process scheduling");
+        self.heartbeat.fetch_add(1, Ordering::Relaxed);
+        self.slice_used_us.store(0, Ordering::Relaxed);
+
+        // Killed processes must not be picked below.
+        crate::process::deliver_pending_kills();
 
         let (old_pid, new_pid) = self.with_list(|list| {
             if list.is_empty() {
@@ -211,25 +393,34 @@ impl SchedulerContext {
                 return (None, ProcessId(0));
             }
 
+            let now = crate::scheduler::get_system_tick();
+            wake_expired_sleepers(list, now);
+
             // Clamp the schedule index to the valid range in case the process list has shrunk.
             let current_idx = self.schedule_index().min(list.len().saturating_sub(1));
-            let start_idx = current_idx;
-            let mut next_idx = current_idx;
-
-            // Round‑robin scan
-            loop {
-                next_idx = (next_idx + 1) % list.len();
-                if list[next_idx].1.state == ProcessState::Ready {
-                    break;
+
+            // Priority scan; only a strictly higher priority replaces the
+            // first candidate, so equal priorities rotate.
+            let mut best: Option<(usize, u8)> = None;
+            for offset in 1..=list.len() {
+                let idx = (current_idx + offset) % list.len();
+                let candidate = &list[idx].1;
+                if candidate.state != ProcessState::Ready {
+                    continue;
                 }
-                if next_idx == start_idx {
-                    // All blocked → fall back to idle
-                    if let Some(idle) = list.iter().position(|(_, p)| p.name == "idle") {
-                        next_idx = idle;
-                    }
-                    break;
+                let priority = effective_priority(candidate, now, !self.is_idle(list[idx].0));
+                if best.is_none_or(|(_, best_priority)| priority > best_priority) {
+                    best = Some((idx, priority));
                 }
             }
+            let next_idx = match best {
+                Some((idx, _)) => idx,
+                // All blocked → fall back to idle
+                None => list
+                    .iter()
+                    .position(|(id, _)| self.is_idle(*id))
+                    .unwrap_or(current_idx),
+            };
 
             let old = if current_idx < list.len() {
                 let pid = list[current_idx].0;
@@ -239,6 +430,7 @@ impl SchedulerContext {
             };
             let new = list[next_idx].0;
 
+            self.credit_cpu_time(list);
             self.set_schedule_index(next_idx);
             self.set_current_pid(new.0 as usize);
 
@@ -246,6 +438,7 @@ impl SchedulerContext {
                 if let Some((_, cur)) = list.get_mut(current_idx) {
                     if cur.state == ProcessState::Running {
                         cur.state = ProcessState::Ready;
+                        cur.ready_since = now;
                     }
                 }
                 if let Some((_, nxt)) = list.get_mut(next_idx) {
@@ -261,28 +454,78 @@ impl SchedulerContext {
 
     /// Block the current process and switch to the next.
     pub fn block_current(&self) {
-        let pid = ProcessId(self.current_pid.load(Ordering::SeqCst) as u64);
+        let pid = ProcessId(self.current_pid() as u64);
         if pid.0 == 0 {
             return;
         }
         self.with_process(pid, |p| p.state = ProcessState::Blocked);
-        let (old, new) = self.schedule_next();
-        if let (Some(o), n) = (old, new) {
-            if o != n {
-                unsafe { self.context_switch(Some(o), n) };
+        match self.schedule_next() {
+            (Some(old), new) if old != new => unsafe { self.context_switch(Some(old), new) },
+            // Nothing else can run: pause instead of letting the caller's
+            // wait loop spin at full speed.
+            _ => petroleum::cpu_pause(),
+        }
+    }
+
+    /// Block the current process until the system tick reaches `wake_tick`.
+    ///
+    /// The sleeper is skipped by every scheduling pass until its deadline
+    /// has passed; [`Self::schedule_next`] then moves it back to `Ready`.
+    /// Re-checks the deadline after each wakeup so an early unblock (or a
+    /// fallback to the same process) never shortens the sleep.
+    pub fn sleep_current_until(&self, wake_tick: u64) {
+        let pid = ProcessId(self.current_pid() as u64);
+        if pid.0 == 0 {
+            return;
+        }
+        while crate::scheduler::get_system_tick() < wake_tick {
+            self.with_process(pid, |p| {
+                p.state = ProcessState::Blocked;
+                p.wake_tick = Some(wake_tick);
+            });
+            match self.schedule_next() {
+                (Some(old), new) if old != new => unsafe { self.context_switch(Some(old), new) },
+                // Nothing else can run: wait for the next timer interrupt.
+                _ => petroleum::cpu_pause(),
             }
         }
+        self.with_process(pid, |p| {
+            p.wake_tick = None;
+            if p.state == ProcessState::Blocked {
+                p.state = ProcessState::Running;
+            }
+        });
+    }
+
+    /// Move every sleeper whose wakeup tick has passed back to `Ready`.
+    pub fn wake_sleepers(&self, now: u64) {
+        self.with_list(|list| wake_expired_sleepers(list, now));
     }
 
     /// Unblock a process (set it back to Ready).
     pub fn unblock_process(&self, pid: ProcessId) {
+        let now = crate::scheduler::get_system_tick();
         self.with_process(pid, |p| {
             if p.state == ProcessState::Blocked {
                 p.state = ProcessState::Ready;
+                p.wake_tick = None;
+                p.ready_since = now;
             }
         });
     }
 
+    /// Base scheduling priority of `pid`, if it exists.
+    pub fn priority(&self, pid: ProcessId) -> Option<u8> {
+        self.with_process(pid, |p| p.priority)
+    }
+
+    /// Set the base scheduling priority of `pid`, clamped to [`MAX_PRIORITY`].
+    /// Returns `false` if the process does not exist.
+    pub fn set_priority(&self, pid: ProcessId, priority: u8) -> bool {
+        self.with_process(pid, |p| p.priority = priority.min(MAX_PRIORITY))
+            .is_some()
+    }
+
     /// Yield the current process.
     pub fn yield_current(&self) {
         let old_pid_val = self.current_pid();
@@ -297,20 +540,95 @@ impl SchedulerContext {
         }
     }
 
+    /// Put the current process at the back of its priority level and run
+    /// the next ready process, returning when the caller's turn comes again.
+    ///
+    /// Unlike [`Self::yield_current`], the caller stays a candidate: it is
+    /// made `Ready` as if it had just started waiting, and the round-robin
+    /// scan reaches its own slot last, so every other ready process of the
+    /// same effective priority runs first.  With none, the caller keeps the
+    /// CPU instead of falling back to a lower priority.
+    pub fn yield_turn(&self) {
+        let pid = ProcessId(self.current_pid() as u64);
+        if pid.0 == 0 {
+            return;
+        }
+        let now = crate::scheduler::get_system_tick();
+        self.with_process(pid, |p| {
+            if p.state == ProcessState::Running {
+                p.state = ProcessState::Ready;
+                p.ready_since = now;
+            }
+        });
+        match self.schedule_next() {
+            (Some(old), new) if old != new => unsafe { self.context_switch(Some(old), new) },
+            // Picked again straight away: the pass made no transition.
+            _ => {
+                self.with_process(pid, |p| {
+                    if p.state == ProcessState::Ready {
+                        p.state = ProcessState::Running;
+                    }
+                });
+            }
+        }
+    }
+
+    // ── Preemption ──────────────────────────────────────────
+
+    /// Set the time slice in 1 ms ticks; 0 turns preemption off.
+    pub fn set_time_slice(&self, ticks: u64) {
+        self.time_slice_us
+            .store(ticks.saturating_mul(1000), Ordering::Relaxed);
+    }
+
+    /// Whether the running process has used up its time slice.
+    pub fn slice_expired(&self) -> bool {
+        let slice = self.time_slice_us.load(Ordering::Relaxed);
+        slice != 0 && self.slice_used_us.load(Ordering::Relaxed) >= slice
+    }
+
+    /// Switch away from the current process if its time slice has run out.
+    ///
+    /// Called by the timer interrupt, after the EOI, and only when it
+    /// interrupted ring 3.  User code holds no kernel locks, and the CPU
+    /// entered the handler on the process's own kernel stack (RSP0), so the
+    /// handler's frame can stay parked there: the switch saves the kernel
+    /// context inside the handler, and when the process is picked again the
+    /// handler returns and `iretq` resumes the user code.  A process still
+    /// on the shared default kernel stack is left alone, since a second
+    /// process parked there would overwrite it.
+    ///
+    /// The switch goes through [`Self::yield_turn`], so the preempted
+    /// process stays a candidate and only gives way to a ready process of
+    /// equal or higher effective priority; with none it starts a new slice.
+    pub fn preempt_current(&self) {
+        if !self.slice_expired() {
+            return;
+        }
+        let current = ProcessId(self.current_pid() as u64);
+        let own_stack = self
+            .with_process(current, |p| !p.kernel_stack.is_null())
+            .unwrap_or(false);
+        if own_stack {
+            self.yield_turn();
+        }
+    }
+
     /// Raw context switch — updates CR3 when needed.
     ///
     /// # Safety
     ///
     /// Raw context pointers are extracted while holding the process-list
     /// spinlock, then dereferenced after the lock is released.  This is safe
-    /// **only** because the kernel is currently single-core (UP) and uses
-    /// cooperative scheduling:
+    /// **only** because the kernel is currently single-core (UP) and kernel
+    /// code is scheduled cooperatively:
     ///
     ///   * No other core can concurrently terminate/clean up a process.
-    ///   * Interrupt handlers (interrupt‑gate, IF=0) never touch the process
-    ///     list, so they cannot race with the pointer window.
-    ///   * Cooperative scheduling means no preemption can occur between the
-    ///     lock drop and `switch_context`.
+    ///   * The only interrupt handler that touches the process list is the
+    ///     timer's preemption, and it only acts when it interrupted ring 3,
+    ///     never this window.
+    ///   * Kernel code is never preempted, so nothing else can run between
+    ///     the lock drop and `switch_context`.
     ///
     /// For future SMP support the `ProcessContext` must be ref‑counted
     /// (e.g. `Arc<Mutex<ProcessContext>>`) so that the data stays alive
@@ -320,6 +638,8 @@ impl SchedulerContext {
         if old_pid == Some(new_pid) {
             return;
         }
+        // Processes that exited before this switch are off the CPU by now.
+        self.cleanup();
 
         let mut guard = self.processes.lock();
         let list = &mut *guard;
@@ -333,6 +653,11 @@ impl SchedulerContext {
             .find(|(id, _)| *id == new_pid)
             .map(|(_, p)| p.page_table_phys_addr)
             .unwrap_or(x86_64::PhysAddr::new(0));
+        let kernel_stack = list
+            .iter()
+            .find(|(id, _)| *id == new_pid)
+            .map(|(_, p)| p.kernel_stack)
+            .unwrap_or(VirtAddr::zero());
         let old_ctx = old_pid
             .and_then(|pid| list.iter_mut().find(|(id, _)| *id == pid))
             .map(|(_, p)| &mut *p.context as *mut ProcessContext);
@@ -348,6 +673,23 @@ impl SchedulerContext {
                     }
                 }
             }
+            // Interrupts taken while the new process runs in ring 3, and its
+            // syscalls, land on its own kernel stack; the old process may be
+            // parked in a syscall on its own.
+            crate::gdt::set_kernel_stack(kernel_stack);
+            // The user GS bases of both processes sit in KERNEL_GS_BASE
+            // while they are in the kernel.
+            unsafe {
+                let old_frame = crate::interrupts::syscall::switch_syscall_stack(
+                    kernel_stack,
+                    (*new).syscall_frame,
+                );
+                if let Some(old) = old_ctx {
+                    (*old).user_gs_base = crate::percpu::user_gs_base();
+                    (*old).syscall_frame = old_frame;
+                }
+                crate::percpu::set_user_gs_base((*new).user_gs_base);
+            }
             let old_ref = old_ctx.map(|ptr| unsafe { &mut *ptr });
             unsafe { switch_context(old_ref, &*new) };
         }
@@ -400,3 +742,65 @@ impl SchedulerContext {
         }
     }
 }
+
+/// Priority a ready process is scheduled with: its base priority plus one
+/// level for every [`PRIORITY_AGING_TICKS`] it has waited, so low-priority
+/// work cannot be starved indefinitely.  Only when `ages`: the idle
+/// process is always ready, so aging would carry it up to [`MAX_PRIORITY`]
+/// and have it take turns from real work.
+fn effective_priority(p: &Process, now: u64, ages: bool) -> u8 {
+    if !ages {
+        return p.priority;
+    }
+    let aged = now.saturating_sub(p.ready_since) / PRIORITY_AGING_TICKS;
+    p.priority
+        .saturating_add(aged.min(MAX_PRIORITY as u64) as u8)
+        .min(MAX_PRIORITY)
+}
+
+/// Wake sleeping processes whose deadline is at or before `now`.
+fn wake_expired_sleepers(
+    list: &mut HeaplessVec<(ProcessId, Box<Process>), MAX_PROCESSES>,
+    now: u64,
+) {
+    for (_, p) in list.iter_mut() {
+        if p.state == ProcessState::Blocked && p.wake_tick.is_some_and(|tick| tick <= now) {
+            p.state = ProcessState::Ready;
+            p.wake_tick = None;
+            p.ready_since = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_slices_are_charged_by_the_timer_and_reset_by_scheduling() {
+        let scheduler = SchedulerContext::new();
+        scheduler.set_time_slice(4);
+        scheduler.charge_timer_tick(3_000);
+        assert!(!scheduler.slice_expired());
+        scheduler.charge_timer_tick(1_000);
+        assert!(scheduler.slice_expired());
+
+        scheduler.schedule_next();
+        assert!(!scheduler.slice_expired());
+
+        scheduler.charge_timer_tick(60_000);
+        scheduler.set_time_slice(0);
+        assert!(!scheduler.slice_expired());
+    }
+
+    #[test]
+    fn waiting_raises_priority_except_for_idle() {
+        let mut p = Process::new("worker", x86_64::VirtAddr::new(0), false);
+        p.priority = 0;
+        p.ready_since = 0;
+        let long_wait = PRIORITY_AGING_TICKS * (MAX_PRIORITY as u64 + 1);
+        assert_eq!(effective_priority(&p, PRIORITY_AGING_TICKS * 3, true), 3);
+        assert_eq!(effective_priority(&p, long_wait, true), MAX_PRIORITY);
+        assert_eq!(effective_priority(&p, long_wait, false), 0);
+    }
+}
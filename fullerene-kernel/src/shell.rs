@@ -66,11 +66,10 @@ fn wasm_read_stdin() -> Option<u8> {
         // The GUI shell reads from the PS/2 queue directly.  The kernel
         // read syscall is for user processes and must not be called while a
         // WASI module is running synchronously inside shell_main.
-        return nitrogen::ps2::keyboard::read_char();
+        return nitrogen::ps2::keyboard::try_read();
     }
-    let mut byte = 0u8;
-    let res = kernel_syscall(3, 0, &mut byte as *mut u8 as u64, 1);
-    if res > 0 { Some(byte) } else { None }
+    // Raw bytes: reading fd 0 would go through the echoing line discipline.
+    crate::interrupts::input::try_read_console()
 }
 
 fn wasm_yield_now() {
@@ -151,6 +150,160 @@ fn read_entire_file(path: &str) -> Result<alloc::vec::Vec<u8>, genome::FsError>
     crate::fs::read_entire_file(path)
 }
 
+/// Wait `ms` milliseconds of TSC time, yielding every ~10 ms so other
+/// tasks are not starved.
+fn wait_ms(ms: u64) {
+    let tsc_per_ms = solvent::get_tsc_per_ms();
+    let total_ticks = tsc_per_ms.saturating_mul(ms);
+    let start = unsafe { core::arch::x86_64::_rdtsc() };
+    let mut last_yield = start;
+    let yield_interval = tsc_per_ms.saturating_mul(10);
+    loop {
+        let now = unsafe { core::arch::x86_64::_rdtsc() };
+        if now.wrapping_sub(start) >= total_ticks {
+            break;
+        }
+        if now.wrapping_sub(last_yield) >= yield_interval {
+            crate::syscall::kernel_syscall(22, 0, 0, 0);
+            last_yield = now;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Sample CPU time over `window_ms` and print each process's share,
+/// busiest first.
+fn print_top(terminal: &mut dyn nozzle::Terminal, window_ms: u64) {
+    let before = crate::process::process_infos();
+    let idle_before = crate::process::SCHEDULER.idle_ticks();
+    let start = crate::scheduler::get_system_tick();
+    wait_ms(window_ms);
+    let after = crate::process::process_infos();
+    let idle = crate::process::SCHEDULER.idle_ticks() - idle_before;
+    let elapsed = (crate::scheduler::get_system_tick() - start).max(1);
+
+    let percent = |ticks: u64| (ticks * 100 / elapsed).min(100);
+    tline!(
+        terminal,
+        "CPU over {} ms: {}% busy, {}% idle",
+        elapsed,
+        100 - percent(idle),
+        percent(idle)
+    );
+    let mut usage: alloc::vec::Vec<_> = after
+        .iter()
+        .map(|info| {
+            let previous = before
+                .iter()
+                .find(|earlier| earlier.pid == info.pid)
+                .map_or(0, |earlier| earlier.cpu_ticks);
+            (info, info.cpu_ticks - previous)
+        })
+        .collect();
+    usage.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.pid.cmp(&b.0.pid)));
+    tline!(terminal, "PID   NAME              STATE       CPU%");
+    for (info, ticks) in usage {
+        let state = crate::process::ProcessState::from_abi_code(info.state)
+            .map_or("?", |state| state.as_str());
+        tline!(
+            terminal,
+            "{:<4}  {:<16}  {:<10}  {:>3}%",
+            info.pid,
+            String::from_utf8_lossy(info.name_bytes()),
+            state,
+            percent(ticks)
+        );
+    }
+}
+
+/// Run the stages of `pipeline` as processes, each one's stdout feeding the
+/// next one's stdin through a kernel pipe and the last one's going to the
+/// redirection target, if any.
+///
+/// Bare names are looked up in `/bin`.  Stage arguments are not passed on
+/// yet: `load_program` starts processes without an `argv`.
+fn spawn_pipeline(terminal: &mut dyn nozzle::Terminal, pipeline: &nozzle::Pipeline) {
+    let stages = &pipeline.commands;
+    let mut images = alloc::vec::Vec::with_capacity(stages.len());
+    for stage in stages {
+        let path = if stage.name.contains('/') {
+            stage.name.clone()
+        } else {
+            format!("/bin/{}", stage.name)
+        };
+        match read_entire_file(&path) {
+            Ok(image) => images.push(image),
+            Err(_) => {
+                tline!(terminal, "{}: command not found", stage.name);
+                return;
+            }
+        }
+    }
+
+    // The shell made the target ready already; open it for appending so
+    // the output lands after anything written there since.
+    let redirect = match &pipeline.stdout {
+        Some(redirect) => match crate::fs::open_for_output(&redirect.path, true) {
+            Ok(desc) => Some(crate::process::FdObject::file(desc)),
+            Err(error) => {
+                tline!(terminal, "nozzle: {}: {}", redirect.path, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut launched = alloc::vec::Vec::with_capacity(stages.len());
+    let mut stdin = None;
+    for (index, (stage, image)) in stages.iter().zip(&images).enumerate() {
+        let (next_stdin, stdout) = if index + 1 < stages.len() {
+            let (read_end, write_end) = crate::syscall::pipe::pipe();
+            (
+                Some(read_end),
+                Some(crate::process::FdObject::Pipe(write_end)),
+            )
+        } else {
+            (None, redirect.clone())
+        };
+        // Keep the new process off the CPU until its streams are redirected.
+        let loaded = x86_64::instructions::interrupts::without_interrupts(|| {
+            let pid = crate::loader::load_program(image, &stage.name)?;
+            crate::process::SCHEDULER.with_process(pid, |p| {
+                let mut table = p.resources.fd_table.lock();
+                if let Some(read_end) = stdin.take() {
+                    table
+                        .entries
+                        .insert(0, crate::process::FdObject::Pipe(read_end));
+                }
+                if let Some(stdout) = stdout {
+                    table.entries.insert(1, stdout);
+                }
+            });
+            Ok::<_, crate::loader::LoadError>(pid)
+        });
+        match loaded {
+            Ok(pid) => launched.push(pid),
+            Err(error) => {
+                tline!(terminal, "{}: {:?}", stage.name, error);
+                for pid in launched {
+                    crate::process::terminate_process(pid, -1);
+                }
+                if let Some(file) = redirect {
+                    let _ = file.close();
+                }
+                return;
+            }
+        }
+        stdin = next_stdin;
+    }
+    // The last stage holds its own reference to the file now.
+    if let Some(file) = redirect {
+        let _ = file.close();
+    }
+    let pids: alloc::vec::Vec<String> = launched.iter().map(|pid| format!("{}", pid.0)).collect();
+    tline!(terminal, "Started pipeline (PIDs {})", pids.join(", "));
+}
+
 /// Initialize the shell subsystem (formerly keyboard init, etc.)
 pub fn init() {
     nitrogen::ps2::keyboard::init_keyboard();
@@ -169,7 +322,12 @@ fn nozzle_services() -> nozzle::ShellServices {
                 "."
             };
             let long_format = ctx.args.contains(&"-l");
-            match crate::contexts::vfs::readdir(path) {
+            // `ls FILE` lists the file itself, whichever mount it lives on.
+            let listing = match crate::fs::vfs::stat(path) {
+                Ok(node) if !node.is_dir => Ok(alloc::vec![node]),
+                _ => crate::contexts::vfs::readdir(path),
+            };
+            match listing {
                 Ok(entries) => {
                     for ent in entries {
                         if long_format {
@@ -357,6 +515,42 @@ fn nozzle_services() -> nozzle::ShellServices {
                 }
             }
         }),
+        entries: Some(|path| {
+            crate::contexts::vfs::readdir(path).ok().map(|entries| {
+                entries
+                    .into_iter()
+                    .map(|entry| nozzle::fs_hooks::DirEntry {
+                        name: entry.name,
+                        is_dir: entry.is_dir,
+                    })
+                    .collect()
+            })
+        }),
+        open_output: Some(|path, append| {
+            crate::fs::open_for_output(path, append)
+                .and_then(crate::fs::close_file)
+                .map_err(|e| format!("{}", e))
+        }),
+        append_output: Some(|path, data| {
+            let mut fd = crate::fs::open_for_output(path, true).map_err(|e| format!("{}", e))?;
+            let mut remaining = data;
+            let mut result = Ok(());
+            while !remaining.is_empty() {
+                match crate::fs::write_file(&mut fd, remaining) {
+                    Ok(0) => {
+                        result = Err(String::from("short write"));
+                        break;
+                    }
+                    Ok(written) => remaining = &remaining[written..],
+                    Err(e) => {
+                        result = Err(format!("{}", e));
+                        break;
+                    }
+                }
+            }
+            let _ = crate::fs::close_file(fd);
+            result
+        }),
     };
 
     let mount: Option<fn(&mut nozzle::CommandContext)> =
@@ -388,6 +582,7 @@ fn nozzle_services() -> nozzle::ShellServices {
         });
 
     let sys = nozzle::sys_hooks::SysHooks {
+        spawn: Some(spawn_pipeline),
         info: Some(|ctx, cmd| match cmd {
             "mem" => {
                 let (heap_start, heap_end) = petroleum::common::memory::get_heap_range();
@@ -401,7 +596,48 @@ fn nozzle_services() -> nozzle::ShellServices {
                     total, heap_start, heap_end
                 );
                 ctx.terminal.write_str(&msg);
+                let frames = crate::memory_management::get_memory_manager()
+                    .lock()
+                    .as_ref()
+                    .map(|manager| {
+                        let frames = manager.frame_allocator();
+                        (
+                            frames.available_frames(),
+                            frames.largest_free_run(),
+                            frames.fragmentation_ratio(),
+                        )
+                    });
+                if let Some((free, largest_run, fragmentation)) = frames {
+                    let msg = format!(
+                        "Frames: {} free, largest free run {}, fragmentation {:.0}%\n",
+                        free,
+                        largest_run,
+                        fragmentation * 100.0
+                    );
+                    ctx.terminal.write_str(&msg);
+                }
             }
+            "free" => match crate::memory_management::mem_info() {
+                Some(info) => {
+                    tline!(
+                        ctx.terminal,
+                        "{:>10} {:>10} {:>10} {:>10}",
+                        "total",
+                        "used",
+                        "free",
+                        "largest"
+                    );
+                    tline!(
+                        ctx.terminal,
+                        "{:>10} {:>10} {:>10} {:>10}",
+                        format_bytes(info.total),
+                        format_bytes(info.used),
+                        format_bytes(info.free),
+                        format_bytes(info.largest_free_run)
+                    );
+                }
+                None => tstr!(ctx.terminal, "free: memory manager not initialized"),
+            },
             "metrics" => {
                 ctx.terminal.write_str(&crate::metrics::format_snapshot());
             }
@@ -412,6 +648,53 @@ fn nozzle_services() -> nozzle::ShellServices {
                 let list = crate::task::TASK_MANAGER.format_task_list();
                 ctx.terminal.write_str(&list);
             }
+            "ps" => {
+                ctx.terminal
+                    .write_str("PID   NAME              STATE       PRIO  TIME\n");
+                for info in crate::process::process_infos() {
+                    let state = crate::process::ProcessState::from_abi_code(info.state)
+                        .map_or("?", |state| state.as_str());
+                    let line = format!(
+                        "{:<4}  {:<16}  {:<10}  {:<4}  {}.{:03}s\n",
+                        info.pid,
+                        String::from_utf8_lossy(info.name_bytes()),
+                        state,
+                        info.priority,
+                        info.cpu_ticks / 1000,
+                        info.cpu_ticks % 1000
+                    );
+                    ctx.terminal.write_str(&line);
+                }
+            }
+            "top" => {
+                let window_ms = match ctx.args.get(1).map(|arg| arg.parse::<u64>()) {
+                    None => 1000,
+                    Some(Ok(secs)) if secs > 0 => secs.saturating_mul(1000),
+                    Some(_) => return tstr!(ctx.terminal, "top: invalid number of seconds"),
+                };
+                print_top(ctx.terminal, window_ms);
+            }
+            "kill" => {
+                use fullerene_abi::signals::{SIGKILL, SIGTERM};
+                let (signal, pid_arg) = match ctx.args.get(1..) {
+                    Some([flag, pid]) if flag.starts_with('-') => {
+                        let signal = match &flag[1..] {
+                            "9" | "KILL" => SIGKILL,
+                            "15" | "TERM" => SIGTERM,
+                            _ => return tline!(ctx.terminal, "kill: unknown signal {}", flag),
+                        };
+                        (signal, *pid)
+                    }
+                    Some([pid]) => (SIGTERM, *pid),
+                    _ => return tstr!(ctx.terminal, "Usage: kill [-9|-15|-KILL|-TERM] <pid>"),
+                };
+                let Ok(pid) = pid_arg.parse::<u64>() else {
+                    return tline!(ctx.terminal, "kill: invalid pid {}", pid_arg);
+                };
+                if let Err(e) = crate::syscall::process::syscall_kill(pid, signal) {
+                    tline!(ctx.terminal, "kill: {}: {:?}", pid, e);
+                }
+            }
             "taskmon" => {
                 let list = crate::task::TASK_MANAGER.format_task_list();
                 ctx.terminal.write_str(&list);
@@ -476,6 +759,23 @@ fn nozzle_services() -> nozzle::ShellServices {
                 ctx.terminal
                 .write_str("Usage: wallpaper solid | grid | gradient | beach | mountain | city | fullerene | fullerene-sharp\n");
             }
+            "keymap" => {
+                use nitrogen::ps2::{keyboard, layouts};
+                if let Some(&name) = ctx.args.get(1) {
+                    match layouts::by_name(name) {
+                        Some(layout) => {
+                            keyboard::set_layout(layout);
+                            tline!(ctx.terminal, "Keyboard layout: {}", layout.name());
+                        }
+                        None => tline!(ctx.terminal, "keymap: unknown layout '{}'", name),
+                    }
+                } else {
+                    tline!(ctx.terminal, "Keyboard layout: {}", keyboard::layout_name());
+                    let names: alloc::vec::Vec<_> =
+                        layouts::LAYOUTS.iter().map(|l| l.name()).collect();
+                    tline!(ctx.terminal, "Usage: keymap ( {} )", names.join(" | "));
+                }
+            }
             "windows" => {
                 if solvent::is_initialized() {
                     ctx.terminal
@@ -486,6 +786,22 @@ fn nozzle_services() -> nozzle::ShellServices {
                     ctx.terminal.write_str("Windowing system not active.\n");
                 }
             }
+            "loglevel" => {
+                use petroleum::common::logging;
+                match ctx.args.get(1).map(|arg| arg.parse::<log::LevelFilter>()) {
+                    None => tline!(ctx.terminal, "Log level: {}", logging::max_level()),
+                    Some(Ok(level)) => {
+                        logging::set_max_level(level);
+                        tline!(ctx.terminal, "Log level: {}", level);
+                    }
+                    Some(Err(_)) => {
+                        tstr!(
+                            ctx.terminal,
+                            "Usage: loglevel [trace|debug|info|warn|error|off]"
+                        )
+                    }
+                }
+            }
             "dmesg" => {
                 let klog_len = crate::klog::len();
                 if klog_len > 0 {
@@ -732,29 +1048,43 @@ fn nozzle_services() -> nozzle::ShellServices {
             "sleep" => {
                 if ctx.args.len() > 1 {
                     if let Ok(secs) = ctx.args[1].parse::<u64>() {
-                        let tsc_per_ms = solvent::get_tsc_per_ms();
-                        let total_ticks = tsc_per_ms.saturating_mul(secs.saturating_mul(1000));
-                        let start = unsafe { core::arch::x86_64::_rdtsc() };
-                        // Yield via HLT-hinted syscall periodically to avoid
-                        // starving other tasks during the wait.
-                        let mut last_yield = start;
-                        let yield_interval = tsc_per_ms.saturating_mul(10); // every ~10 ms
-                        loop {
-                            let now = unsafe { core::arch::x86_64::_rdtsc() };
-                            if now.wrapping_sub(start) >= total_ticks {
-                                break;
-                            }
-                            if now.wrapping_sub(last_yield) >= yield_interval {
-                                crate::syscall::kernel_syscall(22, 0, 0, 0);
-                                last_yield = now;
-                            }
-                            core::hint::spin_loop();
-                        }
+                        wait_ms(secs.saturating_mul(1000));
                     } else {
                         ctx.terminal.write_str("sleep: invalid number of seconds\n");
                     }
                 }
             }
+            "clockcheck" => {
+                use petroleum::hardware::tsc;
+                let ticks = match ctx.args.get(1).map(|arg| arg.parse::<u64>()) {
+                    None => 100,
+                    Some(Ok(ticks)) if (1..=10_000).contains(&ticks) => ticks,
+                    Some(_) => return tstr!(ctx.terminal, "Usage: clockcheck [ticks 1-10000]"),
+                };
+                let start = tsc::read_tsc();
+                if let Err(e) = crate::syscall::time::syscall_sleep_ticks(ticks) {
+                    return tline!(ctx.terminal, "clockcheck: sleep failed: {:?}", e);
+                }
+                let elapsed_us = tsc::tsc_to_nanos(tsc::read_tsc().wrapping_sub(start)) / 1000;
+                // Ticks are milliseconds.  The sleep may start just before a
+                // tick and end a little after its deadline once rescheduled;
+                // beyond that allow 10% for calibration error.
+                let expected_us = ticks * 1000;
+                let low = (expected_us - 1000) * 9 / 10;
+                let high = expected_us * 11 / 10 + 20_000;
+                tline!(
+                    ctx.terminal,
+                    "{} ticks took {} us by the TSC ({} kHz): {}",
+                    ticks,
+                    elapsed_us,
+                    tsc::tsc_hz() / 1000,
+                    if (low..=high).contains(&elapsed_us) {
+                        "ok"
+                    } else {
+                        "OUT OF RANGE"
+                    }
+                );
+            }
             "grep" => {
                 if ctx.args.len() < 3 {
                     return tstr!(ctx.terminal, "grep: pattern and file required");
@@ -899,32 +1229,11 @@ fn nozzle_services() -> nozzle::ShellServices {
             }
             "reboot" => {
                 petroleum::serial::serial_log(format_args!("Reboot requested via shell\n"));
-                unsafe {
-                    let port: u16 = 0x64;
-                    while x86_64::instructions::port::PortReadOnly::<u8>::new(port).read() & 0x02
-                        != 0
-                    {}
-                    x86_64::instructions::port::PortWriteOnly::<u8>::new(port).write(0xFEu8);
-                }
+                crate::power::reboot();
             }
             "shutdown" => {
                 petroleum::serial::serial_log(format_args!("Shutdown requested via shell\n"));
-                unsafe {
-                    x86_64::instructions::port::PortWriteOnly::<u16>::new(0x604).write(0x2000u16);
-                }
-                unsafe {
-                    let shutdown_str = b"Shutdown";
-                    let mut port = x86_64::instructions::port::PortWriteOnly::<u8>::new(0xB004);
-                    for &byte in shutdown_str {
-                        port.write(byte);
-                    }
-                }
-                unsafe {
-                    x86_64::instructions::port::PortWriteOnly::<u16>::new(0x4004).write(0x3400u16);
-                }
-                loop {
-                    x86_64::instructions::hlt();
-                }
+                crate::power::shutdown(0);
             }
             _ if cmd.starts_with("app_install ") => {
                 let rest = &cmd[12..];
@@ -1018,18 +1327,21 @@ impl nozzle::Terminal for KernelTerminal {
     }
 
     fn read_byte(&mut self) -> Option<u8> {
+        // The line editor does its own echo and editing, so it takes raw
+        // bytes rather than reading fd 0 through the line discipline.
         loop {
-            let mut byte = 0u8;
-            let res = kernel_syscall(3, 0, &mut byte as *mut u8 as u64, 1);
-            if res > 0 {
+            if let Some(byte) = crate::interrupts::input::try_read_console() {
                 return Some(byte);
             }
+            // Let ready processes run, then sleep until input arrives or
+            // the next tick.
             kernel_syscall(22, 0, 0, 0);
+            crate::interrupts::input::wait_for_console_input();
         }
     }
 
     fn input_available(&self) -> bool {
-        nitrogen::ps2::keyboard::input_available()
+        crate::interrupts::input::console_input_available()
     }
 
     fn record_history(&mut self, line: &str) {
@@ -1047,6 +1359,23 @@ impl nozzle::Terminal for KernelTerminal {
     }
 }
 
+// ── Size formatting helper ─────────────────────────────────────────
+
+/// `bytes` in the largest binary unit that keeps the value at least 1, to
+/// one decimal place.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut unit = 0;
+    while unit + 1 < UNITS.len() && bytes >= 1u64 << (10 * (unit + 1)) {
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{} B", bytes);
+    }
+    let tenths = u128::from(bytes) * 10 / (1u128 << (10 * unit));
+    format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+}
+
 // ── PCI device description helper ────────────────────────────────
 
 fn pci_device_description(class: u8, subclass: u8) -> &'static str {
@@ -46,7 +46,7 @@ impl SlabPage {
         if m.safe_map_page(
             virt,
             phys,
-            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
         )
         .is_err()
         {
@@ -1,12 +1,38 @@
 //! Multiprocessor topology and application-processor bring-up state.
+//!
+//! The BSP starts each AP the MADT lists with INIT-SIPI-SIPI through the
+//! real-mode [`trampoline`].  An AP loads its own GDT, TSS and per-CPU block
+//! and the shared IDT, reports its APIC ID and then halts; nothing is
+//! scheduled on it yet.
 
+mod trampoline;
+
+use alloc::boxed::Box;
 use alloc::vec::Vec;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use nitrogen::DriverError;
 use spin::Mutex;
+use x86_64::instructions::tables::load_tss;
+use x86_64::registers::control::{Cr0, Cr3, Cr4};
+use x86_64::registers::model_specific::Efer;
+use x86_64::registers::segmentation::{CS, DS, ES, SS, Segment};
+use x86_64::structures::gdt::{GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::{PhysAddr, VirtAddr};
+
+use self::trampoline::TrampolineParams;
+
+/// Stack an AP runs on once it leaves the trampoline.
+const AP_STACK_SIZE: usize = 16 * 1024;
+/// How long an AP gets to report in after its startup IPIs.
+const AP_STARTUP_TIMEOUT_US: u64 = 100_000;
 
 static PROCESSORS: Mutex<Vec<nitrogen::acpi::madt::Processor>> = Mutex::new(Vec::new());
 static ONLINE_APS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
 static ONLINE_PROCESSORS: AtomicUsize = AtomicUsize::new(1);
+/// APIC ID of the last AP to reach [`ap_entry`]; `u32::MAX` while the BSP
+/// waits for the next one.
+static AP_ALIVE: AtomicU32 = AtomicU32::new(u32::MAX);
 
 pub fn configure(topology: nitrogen::acpi::madt::MadtInfo) {
     let mut processors = topology.processors;
@@ -80,3 +106,165 @@ pub fn format_topology() -> alloc::string::String {
     );
     output
 }
+
+/// Descriptor tables and per-CPU block an AP loads in [`ap_entry`].
+/// Leaked: they must stay put for as long as the AP runs.
+struct ApDescriptors {
+    gdt: GlobalDescriptorTable,
+    code: SegmentSelector,
+    data: SegmentSelector,
+    tss: SegmentSelector,
+    percpu: &'static crate::percpu::PerCpu,
+}
+
+/// Initial APIC ID of the running processor, from CPUID; needs no APIC.
+pub(crate) fn current_apic_id() -> u32 {
+    core::arch::x86_64::__cpuid(1).ebx >> 24
+}
+
+/// Start every enabled AP in the topology and park it.
+///
+/// APs share one trampoline page, so they are started one at a time, each
+/// only after the previous one reported in.  An AP that misses its deadline
+/// may still wake up later and read that page, so bring-up stops there
+/// rather than hand its parameters to the next AP.
+pub fn start_application_processors() {
+    let bsp = current_apic_id();
+    let targets: Vec<u32> = topology()
+        .iter()
+        .filter(|processor| processor.enabled && processor.apic_id != bsp)
+        .map(|processor| processor.apic_id)
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let Some(page) = install_trampoline() else {
+        log::warn!("SMP: no free page below 1 MiB for the AP trampoline");
+        return;
+    };
+    for apic_id in targets {
+        if let Err(error) = start_processor(page, apic_id) {
+            log::warn!("SMP: AP {} did not start: {:?}", apic_id, error);
+            break;
+        }
+        log::info!("SMP: AP {} is online", apic_id);
+    }
+    log::info!(
+        "SMP: {} of {} processors online",
+        online_count(),
+        discovered_count()
+    );
+}
+
+/// Copy the trampoline into a free page below 1 MiB and return the page's
+/// physical address.  The page is never freed.
+fn install_trampoline() -> Option<u64> {
+    petroleum::page_table::constants::with_frame_allocator(|allocator| {
+        let frame = allocator.allocate_frame_low()?;
+        let phys = frame.start_address().as_u64();
+        let image = trampoline::image();
+        let virt = petroleum::common::memory::physical_to_virtual(phys as usize);
+        unsafe { core::ptr::copy_nonoverlapping(image.as_ptr(), virt as *mut u8, image.len()) };
+
+        // The AP keeps executing the page at its identity address once paging
+        // is on, and the identity window is no-execute: make the page
+        // read-execute.  It only writes the page in real mode, and the BSP
+        // fills in the parameters through the direct map.
+        let offset = VirtAddr::new(petroleum::common::memory::get_physical_memory_offset() as u64);
+        let identity = VirtAddr::new(phys);
+        unsafe {
+            petroleum::page_table::kernel::init::map_page_4k_l1(
+                petroleum::page_table::active_level_4_table(offset),
+                identity,
+                PhysAddr::new(phys),
+                PageTableFlags::PRESENT,
+                allocator,
+                offset,
+            )
+        }
+        .ok()?;
+        x86_64::instructions::tlb::flush(identity);
+        Some(phys)
+    })
+}
+
+/// Start the AP with `apic_id` through the trampoline at `page` and wait
+/// until it is running kernel code.
+fn start_processor(page: u64, apic_id: u32) -> Result<(), DriverError> {
+    // Startup IPIs address the target with an 8-bit xAPIC ID.
+    let target = u8::try_from(apic_id).map_err(|_| DriverError::NotSupported)?;
+    // The trampoline loads CR3 before it has 64-bit registers.
+    let cr3 = Cr3::read().0.start_address().as_u64();
+    if cr3 > u64::from(u32::MAX) {
+        return Err(DriverError::NotSupported);
+    }
+
+    let stack = Box::leak(alloc::vec![0u8; AP_STACK_SIZE].into_boxed_slice());
+    let stack_top = (stack.as_ptr() as u64 + AP_STACK_SIZE as u64) & !0xF;
+    let ist = Box::leak(alloc::vec![0u8; crate::gdt::GDT_INIT_OVERHEAD].into_boxed_slice());
+    let tss = Box::leak(Box::new(
+        crate::gdt::TssStacks::from_base(VirtAddr::from_ptr(ist.as_ptr())).tss(),
+    ));
+    let (gdt, code, data, tss_selector, _, _) = unsafe { crate::gdt::build_gdt(tss) };
+    let descriptors: &'static ApDescriptors = Box::leak(Box::new(ApDescriptors {
+        gdt,
+        code,
+        data,
+        tss: tss_selector,
+        percpu: crate::percpu::allocate(apic_id),
+    }));
+
+    let params = TrampolineParams {
+        cr3,
+        efer: Efer::read_raw(),
+        cr0: Cr0::read_raw(),
+        cr4: Cr4::read_raw(),
+        stack_top,
+        entry: ap_entry as *const () as u64,
+        arg: descriptors as *const ApDescriptors as u64,
+    };
+    let params_virt =
+        petroleum::common::memory::physical_to_virtual(page as usize + trampoline::params_offset());
+    unsafe { core::ptr::write_volatile(params_virt as *mut TrampolineParams, params) };
+
+    AP_ALIVE.store(u32::MAX, Ordering::Release);
+    crate::interrupts::apic::APIC_CONTROLLER
+        .lock()
+        .as_ref()
+        .ok_or(DriverError::NotReady)?
+        .start_application_processor(target, (page >> 12) as u8)?;
+    nitrogen::timing::wait_timeout_us(AP_STARTUP_TIMEOUT_US, || {
+        AP_ALIVE.load(Ordering::Acquire) == apic_id
+    })
+    .map_err(|_| DriverError::TimedOut)?;
+
+    mark_processor_online(apic_id);
+    Ok(())
+}
+
+/// Long-mode entry point of an AP, called by the trampoline on the stack
+/// the BSP gave it.
+extern "sysv64" fn ap_entry(descriptors: &'static ApDescriptors) -> ! {
+    descriptors.gdt.load();
+    unsafe {
+        CS::set_reg(descriptors.code);
+        DS::set_reg(descriptors.data);
+        ES::set_reg(descriptors.data);
+        SS::set_reg(descriptors.data);
+        load_tss(descriptors.tss);
+    }
+    crate::percpu::init_ap(descriptors.percpu);
+    crate::interrupts::idt::load();
+
+    let apic_id = current_apic_id();
+    petroleum::serial::serial_log(format_args!("SMP: AP {} alive, parking\n", apic_id));
+    // The trampoline page and parameters are not touched past this point,
+    // so the BSP may reuse them for the next AP.
+    AP_ALIVE.store(apic_id, Ordering::Release);
+
+    loop {
+        x86_64::instructions::interrupts::disable();
+        x86_64::instructions::hlt();
+    }
+}
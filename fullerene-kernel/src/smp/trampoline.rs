@@ -0,0 +1,144 @@
+//! Real-mode entry code for application processors.
+//!
+//! A startup IPI starts an AP in real mode at `page << 12`, so the code below
+//! is copied into a page under 1 MiB before any SIPI is sent.  It is written
+//! to run at whatever page it lands on: the 16-bit prologue derives its load
+//! address from CS and patches its own GDT pointer and far-jump targets
+//! before leaving real mode.  The BSP fills in [`TrampolineParams`], at the
+//! end of the copy, with the state the AP needs to join the kernel's address
+//! space in long mode and call into Rust.
+
+use core::mem::{offset_of, size_of};
+
+/// Values the BSP hands to an AP through the trampoline page.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct TrampolineParams {
+    /// Kernel page table root; loaded while still in protected mode, so it
+    /// must lie below 4 GiB.
+    pub cr3: u64,
+    /// BSP's EFER, for LME and the NXE/SCE bits the kernel relies on.
+    pub efer: u64,
+    pub cr0: u64,
+    pub cr4: u64,
+    pub stack_top: u64,
+    /// `extern "sysv64" fn(u64) -> !` to call in long mode.
+    pub entry: u64,
+    /// Passed to `entry` in RDI.
+    pub arg: u64,
+}
+
+core::arch::global_asm!(
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_end",
+    ".code16",
+    "ap_trampoline_start:",
+    "cli",
+    "cld",
+    "mov ax, cs",
+    "mov ds, ax",
+    "movzx ebx, ax",
+    "shl ebx, 4",
+    // Point the GDT descriptor and both far jumps at this copy.
+    "lea eax, [ebx + TRAMPOLINE_GDT]",
+    "mov dword ptr [TRAMPOLINE_GDTR + 2], eax",
+    "lea eax, [ebx + TRAMPOLINE_PROTECTED]",
+    "mov dword ptr [TRAMPOLINE_PROTECTED_PTR], eax",
+    "lea eax, [ebx + TRAMPOLINE_LONG]",
+    "mov dword ptr [TRAMPOLINE_LONG_PTR], eax",
+    "lgdt [TRAMPOLINE_GDTR]",
+    "mov eax, cr0",
+    "or eax, 1",
+    "mov cr0, eax",
+    "jmp fword ptr [TRAMPOLINE_PROTECTED_PTR]",
+    ".code32",
+    "ap_trampoline_protected:",
+    "mov ax, 0x10",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    // PAE, then the kernel page table, then EFER.LME (LMA is read-only).
+    "mov eax, cr4",
+    "or eax, 0x20",
+    "mov cr4, eax",
+    "mov eax, dword ptr [ebx + TRAMPOLINE_PARAMS + {cr3}]",
+    "mov cr3, eax",
+    "mov ecx, 0xC0000080",
+    "mov eax, dword ptr [ebx + TRAMPOLINE_PARAMS + {efer}]",
+    "mov edx, dword ptr [ebx + TRAMPOLINE_PARAMS + {efer} + 4]",
+    "and eax, 0xFFFFFBFF",
+    "wrmsr",
+    "mov eax, cr0",
+    "or eax, 0x80000001",
+    "mov cr0, eax",
+    "jmp fword ptr [ebx + TRAMPOLINE_LONG_PTR]",
+    ".code64",
+    "ap_trampoline_long:",
+    // The upper half of RBX is undefined after the mode switch.
+    "mov ebx, ebx",
+    "mov rax, qword ptr [rbx + TRAMPOLINE_PARAMS + {cr4}]",
+    "mov cr4, rax",
+    "mov rax, qword ptr [rbx + TRAMPOLINE_PARAMS + {cr0}]",
+    "mov cr0, rax",
+    "mov rsp, qword ptr [rbx + TRAMPOLINE_PARAMS + {stack_top}]",
+    "mov rdi, qword ptr [rbx + TRAMPOLINE_PARAMS + {arg}]",
+    "call qword ptr [rbx + TRAMPOLINE_PARAMS + {entry}]",
+    "2:",
+    "cli",
+    "hlt",
+    "jmp 2b",
+    ".balign 8",
+    "ap_trampoline_gdt:",
+    ".quad 0",
+    ".quad 0x00CF9A000000FFFF", // 0x08: 32-bit code
+    ".quad 0x00CF92000000FFFF", // 0x10: data
+    ".quad 0x00AF9A000000FFFF", // 0x18: 64-bit code
+    "ap_trampoline_gdtr:",
+    ".word 31",
+    ".long 0",
+    "ap_trampoline_protected_ptr:",
+    ".long 0",
+    ".word 0x08",
+    "ap_trampoline_long_ptr:",
+    ".long 0",
+    ".word 0x18",
+    ".balign 8",
+    "ap_trampoline_params:",
+    ".skip {params_size}",
+    "ap_trampoline_end:",
+    ".set TRAMPOLINE_GDT, ap_trampoline_gdt - ap_trampoline_start",
+    ".set TRAMPOLINE_PROTECTED, ap_trampoline_protected - ap_trampoline_start",
+    ".set TRAMPOLINE_LONG, ap_trampoline_long - ap_trampoline_start",
+    ".set TRAMPOLINE_GDTR, ap_trampoline_gdtr - ap_trampoline_start",
+    ".set TRAMPOLINE_PROTECTED_PTR, ap_trampoline_protected_ptr - ap_trampoline_start",
+    ".set TRAMPOLINE_LONG_PTR, ap_trampoline_long_ptr - ap_trampoline_start",
+    ".set TRAMPOLINE_PARAMS, ap_trampoline_params - ap_trampoline_start",
+    cr3 = const offset_of!(TrampolineParams, cr3),
+    efer = const offset_of!(TrampolineParams, efer),
+    cr0 = const offset_of!(TrampolineParams, cr0),
+    cr4 = const offset_of!(TrampolineParams, cr4),
+    stack_top = const offset_of!(TrampolineParams, stack_top),
+    entry = const offset_of!(TrampolineParams, entry),
+    arg = const offset_of!(TrampolineParams, arg),
+    params_size = const size_of::<TrampolineParams>(),
+);
+
+unsafe extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+}
+
+/// The trampoline code and its parameter block, as assembled into the
+/// kernel image.
+pub(super) fn image() -> &'static [u8] {
+    unsafe {
+        let start = &raw const ap_trampoline_start;
+        let end = &raw const ap_trampoline_end;
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+/// Offset of the parameter block within [`image`].
+pub(super) fn params_offset() -> usize {
+    image().len() - size_of::<TrampolineParams>()
+}
@@ -0,0 +1,202 @@
+//! Kernel symbol table for backtraces (`debug_symbols` feature).
+//!
+//! With the feature on, the kernel reserves a `.ksyms` section of
+//! [`CAPACITY`] bytes holding an empty table, and build.rs has the linker
+//! write a map file next to the image.  `flasks --debug-symbols` reads the
+//! function symbols from the map and patches the table into the section of
+//! a copy of the image, so the kernel finds its own symbols at run time
+//! without a second link.  Without the feature, or when the image was not
+//! patched, [`resolve`] finds nothing and backtraces stay raw addresses.
+//!
+//! The table is little-endian: a header of [`MAGIC`], the entry count and
+//! the RVA of `.ksyms` itself, which gives the image's load address; then
+//! the entries, sorted by start RVA, each `start`, `len`, `name_offset` and
+//! `name_len`; then the names, which the offsets index.
+
+use core::fmt;
+
+pub const MAGIC: [u8; 4] = *b"KSYM";
+const HEADER_LEN: usize = 16;
+const ENTRY_LEN: usize = 16;
+
+/// Bytes reserved for the table; flasks refuses a table that does not fit.
+#[cfg(feature = "debug_symbols")]
+pub const CAPACITY: usize = 4 << 20;
+
+/// The table flasks patches in.  The magic also keeps the section out of
+/// zero-initialised data, so it has room in the image file.
+#[cfg(feature = "debug_symbols")]
+#[used]
+#[unsafe(link_section = ".ksyms")]
+static TABLE: [u8; CAPACITY] = {
+    let mut table = [0u8; CAPACITY];
+    table[0] = MAGIC[0];
+    table[1] = MAGIC[1];
+    table[2] = MAGIC[2];
+    table[3] = MAGIC[3];
+    table
+};
+
+/// A parsed view of a symbol table.
+struct SymbolTable<'a> {
+    entries: &'a [u8],
+    names: &'a [u8],
+    section_rva: u32,
+}
+
+impl<'a> SymbolTable<'a> {
+    /// Check the header of `bytes` and split it into entries and names.
+    fn parse(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.get(..4)? != MAGIC {
+            return None;
+        }
+        let count = read_u32(bytes, 4)? as usize;
+        let section_rva = read_u32(bytes, 8)?;
+        let names_start = HEADER_LEN.checked_add(count.checked_mul(ENTRY_LEN)?)?;
+        Some(Self {
+            entries: bytes.get(HEADER_LEN..names_start)?,
+            names: &bytes[names_start..],
+            section_rva,
+        })
+    }
+
+    fn entry(&self, index: usize) -> (u32, u32, usize, usize) {
+        let at = index * ENTRY_LEN;
+        let field = |n: usize| read_u32(self.entries, at + 4 * n).unwrap_or(0);
+        (field(0), field(1), field(2) as usize, field(3) as usize)
+    }
+
+    /// The function covering `rva` and `rva`'s offset into it.
+    fn lookup(&self, rva: u32) -> Option<(&'a str, u32)> {
+        let count = self.entries.len() / ENTRY_LEN;
+        // Binary search for the last entry starting at or below `rva`.
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entry(mid).0 <= rva {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let (start, len, name_offset, name_len) = self.entry(low.checked_sub(1)?);
+        let offset = rva - start;
+        if offset >= len {
+            return None;
+        }
+        let name = self
+            .names
+            .get(name_offset..name_offset.checked_add(name_len)?)?;
+        Some((core::str::from_utf8(name).ok()?, offset))
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    let field = bytes.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+/// The function containing `addr` and `addr`'s offset into it, if the
+/// kernel was built with `debug_symbols` and its table was patched in.
+pub fn resolve(addr: usize) -> Option<(&'static str, usize)> {
+    let bytes = table_bytes();
+    let table = SymbolTable::parse(bytes)?;
+    let base = (bytes.as_ptr() as usize).checked_sub(table.section_rva as usize)?;
+    let rva = u32::try_from(addr.checked_sub(base)?).ok()?;
+    table
+        .lookup(rva)
+        .map(|(name, offset)| (name, offset as usize))
+}
+
+#[cfg(feature = "debug_symbols")]
+fn table_bytes() -> &'static [u8] {
+    // Hide where the pointer comes from, so that reads are not folded into
+    // the empty table the compiler saw.
+    unsafe { &*core::hint::black_box(&raw const TABLE) }
+}
+
+#[cfg(not(feature = "debug_symbols"))]
+fn table_bytes() -> &'static [u8] {
+    &[]
+}
+
+/// A return address from a backtrace, shown as `0x... function+0xoff`
+/// when [`resolve`] knows it.
+///
+/// The address is looked up less one: a call that never returns may be
+/// the last instruction of its function, leaving the return address at
+/// the start of the next one.
+pub struct ReturnAddress(pub u64);
+
+impl fmt::Display for ReturnAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)?;
+        match resolve((self.0 as usize).wrapping_sub(1)) {
+            Some((name, offset)) => write!(f, " {}+{:#x}", name, offset + 1),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A table of `symbols` as (start, len, name), with `.ksyms` at RVA
+    /// 0x9000.
+    fn table(symbols: &[(u32, u32, &str)]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&0x9000u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        let mut names = Vec::new();
+        for &(start, len, name) in symbols {
+            for field in [start, len, names.len() as u32, name.len() as u32] {
+                bytes.extend_from_slice(&field.to_le_bytes());
+            }
+            names.extend_from_slice(name.as_bytes());
+        }
+        bytes.extend_from_slice(&names);
+        bytes
+    }
+
+    #[test]
+    fn lookup_finds_the_covering_function_and_offset() {
+        let bytes = table(&[
+            (0x1000, 0x40, "fullerene_kernel::main"),
+            (0x1040, 0x20, "petroleum::serial::_print"),
+            (0x1100, 0x10, "core::panicking::panic"),
+        ]);
+        let table = SymbolTable::parse(&bytes).unwrap();
+        assert_eq!(table.section_rva, 0x9000);
+        assert_eq!(table.lookup(0x1000), Some(("fullerene_kernel::main", 0)));
+        assert_eq!(
+            table.lookup(0x105F),
+            Some(("petroleum::serial::_print", 0x1F))
+        );
+        assert_eq!(table.lookup(0x110F), Some(("core::panicking::panic", 0xF)));
+        // Before the first symbol, in a gap, and past the last.
+        assert_eq!(table.lookup(0xFFF), None);
+        assert_eq!(table.lookup(0x1060), None);
+        assert_eq!(table.lookup(0x1110), None);
+    }
+
+    #[test]
+    fn parse_rejects_empty_and_truncated_tables() {
+        let mut bytes = table(&[(0x1000, 0x40, "main")]);
+        assert!(SymbolTable::parse(&bytes[..HEADER_LEN + ENTRY_LEN - 1]).is_none());
+        assert!(SymbolTable::parse(&[]).is_none());
+
+        // A name running past the table is not returned.
+        bytes.truncate(bytes.len() - 1);
+        assert_eq!(SymbolTable::parse(&bytes).unwrap().lookup(0x1000), None);
+
+        // The table as the kernel is linked, before flasks fills it in.
+        let mut empty = [0u8; 64];
+        empty[..4].copy_from_slice(&MAGIC);
+        assert_eq!(SymbolTable::parse(&empty).unwrap().lookup(0x1000), None);
+        bytes[0] = b'X';
+        assert!(SymbolTable::parse(&bytes).is_none());
+    }
+}
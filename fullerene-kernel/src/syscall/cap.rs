@@ -57,10 +57,7 @@ pub(crate) fn syscall_handle_duplicate(handle: u64) -> SyscallResult {
                 window_id: w.window_id,
                 pid: w.pid,
             }),
-            KernelObject::Pipe(p) => KernelObject::Pipe(PipeState {
-                buffer: Arc::clone(&p.buffer),
-                is_read_end: p.is_read_end,
-            }),
+            KernelObject::Pipe(end) => KernelObject::Pipe(end.clone()),
             _ => return Err(SyscallError::NotSupported),
         };
         Ok(new_obj)
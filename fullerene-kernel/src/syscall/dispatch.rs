@@ -5,10 +5,15 @@ use super::cap;
 use super::device;
 use super::event;
 use super::fs;
+use super::futex;
 use super::interface::SyscallError;
 use super::ipc;
+use super::klog;
 use super::memory;
+use super::power;
 use super::process;
+use super::sem;
+use super::shm;
 use super::thread;
 use super::time;
 use super::window;
@@ -67,9 +72,12 @@ pub unsafe extern "C" fn handle_syscall(
 
         Ok(SyscallNumber::Exit) => process::syscall_exit(arg1 as i32),
         Ok(SyscallNumber::Fork) => process::syscall_fork(),
-        Ok(SyscallNumber::Read) => {
-            fs::syscall_read(arg1 as core::ffi::c_int, arg2 as *mut u8, arg3 as usize)
-        }
+        Ok(SyscallNumber::Read) => fs::syscall_read(
+            arg1 as core::ffi::c_int,
+            arg2 as *mut u8,
+            arg3 as usize,
+            arg4,
+        ),
         Ok(SyscallNumber::Write) => {
             fs::syscall_write(arg1 as core::ffi::c_int, arg2 as *const u8, arg3 as usize)
         }
@@ -77,18 +85,33 @@ pub unsafe extern "C" fn handle_syscall(
             fs::syscall_open(arg1 as *const u8, arg2 as core::ffi::c_int, arg3 as u32)
         }
         Ok(SyscallNumber::Close) => fs::syscall_close(arg1 as core::ffi::c_int),
+        Ok(SyscallNumber::Dup) => fs::syscall_dup(arg1 as core::ffi::c_int),
+        Ok(SyscallNumber::Dup2) => {
+            fs::syscall_dup2(arg1 as core::ffi::c_int, arg2 as core::ffi::c_int)
+        }
         Ok(SyscallNumber::Wait) => process::syscall_wait(arg1),
+        Ok(SyscallNumber::WaitPid) => process::syscall_waitpid(arg1),
         Ok(SyscallNumber::GetPid) => process::syscall_getpid(),
         Ok(SyscallNumber::GetProcessName) => {
             process::syscall_get_process_name(arg1 as *mut u8, arg2 as usize)
         }
         Ok(SyscallNumber::Yield) => process::syscall_yield(),
+        Ok(SyscallNumber::SetPriority) => process::syscall_set_priority(arg1, arg2),
         Ok(SyscallNumber::Spawn) => process::syscall_spawn(
             arg1 as *const u8,
             arg2 as usize,
             arg3 as *const u8,
             arg4 as usize,
         ),
+        Ok(SyscallNumber::Exec) => {
+            process::syscall_exec(arg1 as *const u8, arg2 as *const u8, arg3 as usize)
+        }
+        Ok(SyscallNumber::ListProcesses) => {
+            process::syscall_list_processes(arg1 as *mut u8, arg2 as usize)
+        }
+        Ok(SyscallNumber::SetName) => process::syscall_set_name(arg1 as *const u8, arg2 as usize),
+        Ok(SyscallNumber::Kill) => process::syscall_kill(arg1, arg2),
+        Ok(SyscallNumber::TakeSignals) => process::syscall_take_signals(),
 
         Ok(SyscallNumber::MapMemory) => memory::syscall_map_memory(arg1, arg2, arg3),
         Ok(SyscallNumber::UnmapMemory) => memory::syscall_unmap_memory(arg1, arg2),
@@ -96,16 +119,30 @@ pub unsafe extern "C" fn handle_syscall(
         Ok(SyscallNumber::QueryMemory) => {
             memory::syscall_query_memory(arg1 as *mut u8, arg2 as usize)
         }
+        Ok(SyscallNumber::Brk) => memory::syscall_brk(arg1),
+        Ok(SyscallNumber::Mmap) => memory::syscall_mmap(arg1, arg2),
+        Ok(SyscallNumber::Munmap) => memory::syscall_munmap(arg1, arg2),
+        Ok(SyscallNumber::ShmCreate) => shm::syscall_shm_create(arg1),
+        Ok(SyscallNumber::ShmAttach) => shm::syscall_shm_attach(arg1),
+        Ok(SyscallNumber::ShmDetach) => shm::syscall_shm_detach(arg1),
 
         Ok(SyscallNumber::CreateEvent) => event::syscall_create_event(arg1),
         Ok(SyscallNumber::WaitEvent) => event::syscall_wait_event(arg1, arg2),
         Ok(SyscallNumber::SignalEvent) => event::syscall_signal_event(arg1),
         Ok(SyscallNumber::SubscribeEvent) => event::syscall_subscribe_event(arg1, arg2),
+        Ok(SyscallNumber::FutexWait) => futex::syscall_futex_wait(arg1, arg2),
+        Ok(SyscallNumber::FutexWake) => futex::syscall_futex_wake(arg1, arg2),
+        Ok(SyscallNumber::MemInfo) => memory::syscall_mem_info(arg1 as *mut u8),
+        Ok(SyscallNumber::ReadKlog) => klog::syscall_read_klog(arg1 as *mut u8, arg2 as usize),
 
         Ok(SyscallNumber::CreateThread) => thread::syscall_create_thread(arg1, arg2, arg3),
         Ok(SyscallNumber::JoinThread) => thread::syscall_join_thread(arg1),
         Ok(SyscallNumber::DetachThread) => thread::syscall_detach_thread(arg1),
         Ok(SyscallNumber::ExitThread) => thread::syscall_exit_thread(arg1 as i32),
+        Ok(SyscallNumber::SemCreate) => sem::syscall_sem_create(arg1),
+        Ok(SyscallNumber::SemWait) => sem::syscall_sem_wait(arg1),
+        Ok(SyscallNumber::SemPost) => sem::syscall_sem_post(arg1),
+        Ok(SyscallNumber::SemDestroy) => sem::syscall_sem_destroy(arg1),
 
         Ok(SyscallNumber::CreateWindow) => {
             window::syscall_create_window(arg1 as i32, arg2 as i32, arg3 as u32, arg4 as u32, arg5)
@@ -138,6 +175,12 @@ pub unsafe extern "C" fn handle_syscall(
         Ok(SyscallNumber::TimerCreate) => time::syscall_timer_create(arg1, arg2, arg3),
         Ok(SyscallNumber::Sleep) => time::syscall_sleep(arg1),
         Ok(SyscallNumber::Uptime) => time::syscall_uptime(arg1 as *mut u8),
+        Ok(SyscallNumber::SleepTicks) => time::syscall_sleep_ticks(arg1),
+        Ok(SyscallNumber::GetTimeOfDay) => time::syscall_gettimeofday(),
+        Ok(SyscallNumber::ClockMonotonic) => time::syscall_clock_monotonic(),
+
+        Ok(SyscallNumber::Shutdown) => power::syscall_shutdown(arg1),
+        Ok(SyscallNumber::Reboot) => power::syscall_reboot(),
 
         Ok(_) => Err(SyscallError::InvalidSyscall),
         Err(()) => Err(SyscallError::InvalidSyscall),
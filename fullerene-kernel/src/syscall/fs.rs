@@ -1,18 +1,28 @@
 //! Native filesystem and terminal I/O syscalls.
 
+use alloc::sync::Arc;
 use alloc::vec;
 use core::ffi::c_int;
 
 use petroleum::common::memory::UserSlice;
+use spin::Mutex;
 
 use super::interface::{SyscallError, SyscallResult, copy_user_string};
+use super::pipe::PipeEnd;
 use super::process::with_current_fd_table;
+use super::user::copy_from_user;
 use crate::linux::{O_APPEND, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY};
+use crate::process::{FdObject, MAX_FDS};
 
 const MAX_IO_BYTES: usize = 65_536;
-const MAX_PATH_BYTES: usize = 256;
+pub(crate) const MAX_PATH_BYTES: usize = 256;
 
-pub(crate) fn syscall_read(fd: c_int, buffer: *mut u8, count: usize) -> SyscallResult {
+/// Read from `fd`.  Console fds go through the line discipline in
+/// [`crate::tty`]; `flags` takes [`fullerene_abi::read_flags`] bits.
+pub(crate) fn syscall_read(fd: c_int, buffer: *mut u8, count: usize, flags: u64) -> SyscallResult {
+    if flags & !fullerene_abi::read_flags::NONBLOCK != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
     let count = count.min(MAX_IO_BYTES);
     if count == 0 {
         return Ok(0);
@@ -21,42 +31,19 @@ pub(crate) fn syscall_read(fd: c_int, buffer: *mut u8, count: usize) -> SyscallR
     let slice = UserSlice::new(buffer, count, true).map_err(|_| SyscallError::InvalidArgument)?;
     petroleum::validate_syscall_fd(fd)?;
 
-    if fd == 0 {
-        if count == 1 {
-            if let Some(ch) = nitrogen::ps2::keyboard::read_char() {
-                let kernel_buf = [ch];
-                unsafe { slice.copy_to_user(&kernel_buf) }
-                    .map_err(|_| SyscallError::InvalidArgument)?;
-                Ok(1)
-            } else {
-                Ok(0)
-            }
-        } else {
-            let mut kernel_buf = vec![0u8; count];
-            let bytes_read = nitrogen::ps2::keyboard::drain_line_buffer(&mut kernel_buf);
-            unsafe { slice.copy_to_user(&kernel_buf[..bytes_read]) }
-                .map_err(|_| SyscallError::InvalidArgument)?;
-            Ok(bytes_read as u64)
-        }
-    } else {
-        if fd < 0 {
-            return Err(SyscallError::BadFileDescriptor);
+    let mut kernel_buf = vec![0u8; count];
+    let bytes_read = match stream(fd)? {
+        Stream::Console => {
+            let nonblocking = flags & fullerene_abi::read_flags::NONBLOCK != 0;
+            crate::tty::read_console(&mut kernel_buf, nonblocking)?
         }
-        with_current_fd_table(|table| match table.entries.get_mut(&(fd as u32)) {
-            Some(file_desc) => {
-                let mut kernel_buf = vec![0u8; count];
-                match crate::fs::read_file(file_desc, &mut kernel_buf) {
-                    Ok(bytes_read) => {
-                        unsafe { slice.copy_to_user(&kernel_buf[..bytes_read]) }
-                            .map_err(|_| SyscallError::InvalidArgument)?;
-                        Ok(bytes_read as u64)
-                    }
-                    Err(_) => Err(SyscallError::BadFileDescriptor),
-                }
-            }
-            None => Err(SyscallError::BadFileDescriptor),
-        })
-    }
+        Stream::Pipe(pipe) => pipe.read(&mut kernel_buf)?,
+        Stream::File(file) => crate::fs::read_file(&mut file.lock(), &mut kernel_buf)
+            .map_err(|_| SyscallError::BadFileDescriptor)?,
+    };
+    unsafe { slice.copy_to_user(&kernel_buf[..bytes_read]) }
+        .map_err(|_| SyscallError::InvalidArgument)?;
+    Ok(bytes_read as u64)
 }
 
 pub(crate) fn syscall_write(fd: c_int, buffer: *const u8, count: usize) -> SyscallResult {
@@ -66,17 +53,48 @@ pub(crate) fn syscall_write(fd: c_int, buffer: *const u8, count: usize) -> Sysca
         return Ok(0);
     }
 
-    let slice = UserSlice::new(buffer as *mut u8, count, false)
-        .map_err(|_| SyscallError::InvalidArgument)?;
+    let kernel_buf = unsafe { copy_from_user(buffer, count)? };
 
-    let mut kernel_buf = vec![0u8; count];
-    unsafe { slice.copy_from_user(&mut kernel_buf) }.map_err(|_| SyscallError::InvalidArgument)?;
+    match stream(fd)? {
+        Stream::Console => {
+            petroleum::write_serial_bytes(0x3F8, 0x3FD, &kernel_buf);
+            Ok(count as u64)
+        }
+        Stream::Pipe(pipe) => pipe.write(&kernel_buf).map(|written| written as u64),
+        Stream::File(file) => {
+            let mut file = file.lock();
+            // `open` hands out read-only files; only the shell opens files
+            // for writing, to redirect a program's output.
+            if file.flags & 0x3 == O_RDONLY as u32 {
+                return Err(SyscallError::BadFileDescriptor);
+            }
+            crate::fs::write_file(&mut file, &kernel_buf)
+                .map(|written| written as u64)
+                .map_err(|_| SyscallError::BadFileDescriptor)
+        }
+    }
+}
 
-    if fd == 1 || fd == 2 {
-        petroleum::write_serial_bytes(0x3F8, 0x3FD, &kernel_buf);
-        Ok(count as u64)
-    } else {
-        Err(SyscallError::BadFileDescriptor)
+/// The object behind an fd, cloned out of the table so the caller can block
+/// on it without holding the table.
+enum Stream {
+    Console,
+    Pipe(PipeEnd),
+    File(Arc<Mutex<crate::fs::FileDesc>>),
+}
+
+fn stream(fd: c_int) -> Result<Stream, SyscallError> {
+    let index = u32::try_from(fd).map_err(|_| SyscallError::BadFileDescriptor)?;
+    let stream = with_current_fd_table(|table| match table.entries.get(&index) {
+        Some(FdObject::Console) => Ok(Stream::Console),
+        Some(FdObject::Pipe(end)) => Ok(Stream::Pipe(end.clone())),
+        Some(FdObject::File(file)) => Ok(Stream::File(Arc::clone(file))),
+        None => Err(SyscallError::BadFileDescriptor),
+    });
+    match stream {
+        // Kernel callers without a process table use the console.
+        Err(SyscallError::NoSuchProcess) if index < 3 => Ok(Stream::Console),
+        result => result,
     }
 }
 
@@ -100,25 +118,70 @@ pub(crate) fn syscall_open(filename: *const u8, flags: c_int, _mode: u32) -> Sys
 
     match crate::fs::open_file(&filename) {
         Ok(file_desc) => with_current_fd_table(|table| {
-            let fd = table
-                .alloc(file_desc)
-                .map_err(|_| SyscallError::OutOfMemory)?;
-            Ok(fd as u64)
+            table
+                .alloc(FdObject::file(file_desc))
+                .map(u64::from)
+                .map_err(|object| {
+                    // Every fd is in use.
+                    let _ = object.close();
+                    SyscallError::OutOfMemory
+                })
         }),
         Err(crate::fs::FsError::FileNotFound) => Err(SyscallError::FileNotFound),
         Err(_) => Err(SyscallError::PermissionDenied),
     }
 }
 
+/// Close `fd`, standard streams included, freeing it for the next `open`.
+/// Closing an fd that is not open, e.g. a second time, is `InvalidArgument`.
 pub(crate) fn syscall_close(fd: c_int) -> SyscallResult {
-    if fd <= 2 {
-        return Err(SyscallError::InvalidArgument);
-    }
+    petroleum::validate_syscall_fd(fd)?;
     with_current_fd_table(|table| match table.entries.remove(&(fd as u32)) {
-        Some(file_desc) => match crate::fs::close_file(file_desc) {
-            Ok(_) => Ok(0),
-            Err(_) => Err(SyscallError::BadFileDescriptor),
-        },
-        None => Err(SyscallError::BadFileDescriptor),
+        Some(object) => object
+            .close()
+            .map(|()| 0)
+            .map_err(|_| SyscallError::BadFileDescriptor),
+        None => Err(SyscallError::InvalidArgument),
+    })
+}
+
+/// Open the lowest free fd on the object behind `fd`.
+pub(crate) fn syscall_dup(fd: c_int) -> SyscallResult {
+    petroleum::validate_syscall_fd(fd)?;
+    with_current_fd_table(|table| {
+        let object = table
+            .entries
+            .get(&(fd as u32))
+            .cloned()
+            .ok_or(SyscallError::BadFileDescriptor)?;
+        table
+            .alloc(object)
+            .map(u64::from)
+            .map_err(|_| SyscallError::OutOfMemory)
+    })
+}
+
+/// Make `new_fd` refer to the object behind `old_fd`, closing whatever
+/// `new_fd` referred to before.  `dup2(fd, fd)` only checks that `fd` is open.
+pub(crate) fn syscall_dup2(old_fd: c_int, new_fd: c_int) -> SyscallResult {
+    petroleum::validate_syscall_fd(old_fd)?;
+    petroleum::validate_syscall_fd(new_fd)?;
+    if new_fd as usize >= MAX_FDS {
+        return Err(SyscallError::BadFileDescriptor);
+    }
+    with_current_fd_table(|table| {
+        let object = table
+            .entries
+            .get(&(old_fd as u32))
+            .cloned()
+            .ok_or(SyscallError::BadFileDescriptor)?;
+        if old_fd != new_fd
+            && let Some(previous) = table.entries.insert(new_fd as u32, object)
+        {
+            // As with dup2(2), a failure to close the old object is not
+            // reported.
+            let _ = previous.close();
+        }
+        Ok(new_fd as u64)
     })
 }
@@ -0,0 +1,191 @@
+//! Futexes: blocking on a 32-bit word of user memory.
+//!
+//! Waiters are queued by the physical address of the word, so processes that
+//! map the same page (a shared-memory segment, say) wait on the same queue
+//! even when it sits at different virtual addresses, and threads key the
+//! same word as their process does.
+//!
+//! `FutexWait` compares the word with the expected value and queues the
+//! caller under the futex lock, and wakers take that lock after changing
+//! the word, so a change between the caller's own check and the syscall is
+//! seen as a mismatch rather than a lost wakeup.  Callers must still
+//! re-check the word after every return: a wait can also end without a
+//! matching wake.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use petroleum::common::memory::UserSlice;
+use spin::Mutex;
+use x86_64::VirtAddr;
+use x86_64::registers::control::Cr3;
+
+use super::interface::{SyscallError, SyscallResult};
+use crate::process::{self, ProcessId, ProcessState};
+
+/// Waiters per futex, oldest first, keyed by physical address.
+pub(crate) struct FutexTable {
+    queues: BTreeMap<u64, VecDeque<ProcessId>>,
+}
+
+impl FutexTable {
+    pub const fn new() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+        }
+    }
+
+    pub fn enqueue(&mut self, key: u64, pid: ProcessId) {
+        self.queues.entry(key).or_default().push_back(pid);
+    }
+
+    /// Take `pid` off the queue for `key`, returning whether it was queued.
+    pub fn remove(&mut self, key: u64, pid: ProcessId) -> bool {
+        let Some(queue) = self.queues.get_mut(&key) else {
+            return false;
+        };
+        let Some(position) = queue.iter().position(|&waiter| waiter == pid) else {
+            return false;
+        };
+        queue.remove(position);
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+        true
+    }
+
+    /// Dequeue up to `count` waiters on `key` for which `is_waiting` holds,
+    /// oldest first.  Waiters that fail it, such as killed processes, are
+    /// dropped without counting against `count`.
+    pub fn take(
+        &mut self,
+        key: u64,
+        count: usize,
+        is_waiting: impl Fn(ProcessId) -> bool,
+    ) -> Vec<ProcessId> {
+        let mut woken = Vec::new();
+        let Some(queue) = self.queues.get_mut(&key) else {
+            return woken;
+        };
+        while woken.len() < count {
+            let Some(pid) = queue.pop_front() else {
+                break;
+            };
+            if is_waiting(pid) {
+                woken.push(pid);
+            }
+        }
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+        woken
+    }
+}
+
+static FUTEXES: Mutex<FutexTable> = Mutex::new(FutexTable::new());
+
+/// Queue key of the futex word at `addr` in the caller's address space:
+/// its physical address, looked up in the page table the caller runs on.
+///
+/// Threads own no page table object but run on their process's, and tasks
+/// without an address space of their own on the kernel's.
+fn futex_key(pid: ProcessId, addr: u64) -> Result<u64, SyscallError> {
+    if addr == 0 || addr % 4 != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let virt = VirtAddr::try_new(addr).map_err(|_| SyscallError::AddressFault)?;
+    let pml4 = process::SCHEDULER
+        .with_process(pid, |p| p.page_table_phys_addr)
+        .ok_or(SyscallError::NoSuchProcess)?;
+    let pml4 = if pml4.is_null() {
+        Cr3::read().0.start_address()
+    } else {
+        pml4
+    };
+    crate::memory_management::translate_in(pml4, virt)
+        .map(|phys| phys.as_u64())
+        .ok_or(SyscallError::AddressFault)
+}
+
+fn read_futex_word(addr: u64) -> Result<u32, SyscallError> {
+    let mut bytes = [0u8; 4];
+    let user = UserSlice::new(addr as *mut u8, bytes.len(), false)
+        .map_err(|_| SyscallError::AddressFault)?;
+    unsafe { user.copy_from_user(&mut bytes) }.map_err(|_| SyscallError::AddressFault)?;
+    Ok(u32::from_ne_bytes(bytes))
+}
+
+/// Block until a `FutexWake` on `addr`, provided the word there still holds
+/// `expected`; fails with [`SyscallError::Again`] if it does not.
+pub(crate) fn syscall_futex_wait(addr: u64, expected: u64) -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let key = futex_key(pid, addr)?;
+    {
+        let mut futexes = FUTEXES.lock();
+        if u64::from(read_futex_word(addr)?) != expected {
+            return Err(SyscallError::Again);
+        }
+        futexes.enqueue(key, pid);
+    }
+
+    // Nothing can run between releasing the lock and blocking: the kernel
+    // is single-core and only switches tasks at explicit scheduling points.
+    process::block_current();
+
+    // Still queued means nobody woke us; the wait ended spuriously.
+    if FUTEXES.lock().remove(key, pid) {
+        process::unblock_process(pid);
+    }
+    Ok(0)
+}
+
+/// Wake up to `count` processes waiting on `addr` and return how many were
+/// woken.
+pub(crate) fn syscall_futex_wake(addr: u64, count: u64) -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let key = futex_key(pid, addr)?;
+    let count = usize::try_from(count).unwrap_or(usize::MAX);
+    let woken = FUTEXES.lock().take(key, count, |waiter| {
+        process::SCHEDULER.with_process(waiter, |p| p.state) == Some(ProcessState::Blocked)
+    });
+    for &waiter in &woken {
+        process::unblock_process(waiter);
+    }
+    Ok(woken.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wakes_oldest_waiters_first_up_to_count() {
+        let mut table = FutexTable::new();
+        for pid in 1..=3 {
+            table.enqueue(0x1000, ProcessId(pid));
+        }
+        table.enqueue(0x2000, ProcessId(9));
+
+        assert_eq!(
+            table.take(0x1000, 2, |_| true),
+            [ProcessId(1), ProcessId(2)]
+        );
+        assert_eq!(table.take(0x1000, 5, |_| true), [ProcessId(3)]);
+        assert!(table.take(0x1000, 1, |_| true).is_empty());
+        assert_eq!(table.take(0x2000, 1, |_| true), [ProcessId(9)]);
+    }
+
+    #[test]
+    fn departed_waiters_do_not_use_up_wakeups() {
+        let mut table = FutexTable::new();
+        for pid in 1..=3 {
+            table.enqueue(0x1000, ProcessId(pid));
+        }
+        assert!(table.remove(0x1000, ProcessId(2)));
+        assert!(!table.remove(0x1000, ProcessId(2)));
+
+        // pid 1 was killed while waiting.
+        let woken = table.take(0x1000, 1, |pid| pid != ProcessId(1));
+        assert_eq!(woken, [ProcessId(3)]);
+        assert!(table.queues.is_empty());
+    }
+}
@@ -51,6 +51,8 @@ pub enum SyscallError {
     IsADirectory = SyscallErrorCode::IsADirectory as i64,
     /// Storage capacity exhausted
     NoSpace = SyscallErrorCode::NoSpace as i64,
+    /// Write to a pipe with no reader left
+    BrokenPipe = SyscallErrorCode::BrokenPipe as i64,
     /// Directory must be empty
     DirectoryNotEmpty = SyscallErrorCode::DirectoryNotEmpty as i64,
     /// Numeric or address overflow
@@ -80,6 +82,7 @@ petroleum::error_chain!(SyscallError, petroleum::common::logging::SystemError,
     SyscallError::NotADirectory => petroleum::common::logging::SystemError::InvalidArgument,
     SyscallError::IsADirectory => petroleum::common::logging::SystemError::InvalidArgument,
     SyscallError::NoSpace => petroleum::common::logging::SystemError::DiskFull,
+    SyscallError::BrokenPipe => petroleum::common::logging::SystemError::BadFileDescriptor,
     SyscallError::DirectoryNotEmpty => petroleum::common::logging::SystemError::InvalidArgument,
     SyscallError::Overflow => petroleum::common::logging::SystemError::InvalidArgument,
     SyscallError::BadHandle => petroleum::common::logging::SystemError::BadHandle,
@@ -237,14 +240,18 @@ pub(crate) fn copy_versioned_dto_to_user(
 /// The caller must ensure the user pages are mapped.  Page faults during
 /// copy are caught by the kernel's page fault handler.
 pub unsafe fn copy_user_string(ptr: *const u8, max_len: usize) -> Result<String, SyscallError> {
-    unsafe { user_memory::copy_c_string(ptr, max_len) }.map_err(|error| match error {
+    unsafe { user_memory::copy_c_string(ptr, max_len) }.map_err(native_user_copy_error)
+}
+
+pub(super) fn native_user_copy_error(error: UserCopyError) -> SyscallError {
+    match error {
         UserCopyError::System(SystemError::MemOutOfMemory | SystemError::SyscallOutOfMemory) => {
             SyscallError::OutOfMemory
         }
         UserCopyError::System(_) | UserCopyError::InvalidUtf8 | UserCopyError::MissingNul => {
             SyscallError::InvalidArgument
         }
-    })
+    }
 }
 
 #[cfg(test)]
@@ -301,6 +308,7 @@ mod tests {
             syscall_errors::IS_A_DIRECTORY
         );
         assert_eq!(SyscallError::NoSpace as i64, syscall_errors::NO_SPACE);
+        assert_eq!(SyscallError::BrokenPipe as i64, syscall_errors::BROKEN_PIPE);
         assert_eq!(
             SyscallError::DirectoryNotEmpty as i64,
             syscall_errors::DIRECTORY_NOT_EMPTY
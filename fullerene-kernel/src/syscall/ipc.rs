@@ -92,16 +92,7 @@ pub(crate) fn syscall_pipe_create(buf: *mut u64) -> SyscallResult {
     }
     petroleum::validate_user_buffer(buf as usize, 16, false)?;
 
-    let shared_buffer = Arc::new(Mutex::new(Vec::with_capacity(4096)));
-
-    let read_end = PipeState {
-        buffer: Arc::clone(&shared_buffer),
-        is_read_end: true,
-    };
-    let write_end = PipeState {
-        buffer: shared_buffer,
-        is_read_end: false,
-    };
+    let (read_end, write_end) = super::pipe::pipe();
     let read_h = alloc_handle(KernelObject::Pipe(read_end))?;
     let write_h = match alloc_handle(KernelObject::Pipe(write_end)) {
         Ok(h) => h,
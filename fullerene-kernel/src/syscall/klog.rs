@@ -0,0 +1,22 @@
+//! Kernel log syscall implementation.
+
+use super::interface::{SyscallError, SyscallResult};
+use super::user::copy_to_user;
+
+/// Largest read a single call may request; the ring buffer is smaller.
+const MAX_READ: usize = 1 << 20;
+
+/// Copy the newest `size` bytes of the kernel log to `buffer`, oldest
+/// first, and return how many were copied.  A null `buffer` with a zero
+/// `size` returns the current length of the log instead.
+pub(crate) fn syscall_read_klog(buffer: *mut u8, size: usize) -> SyscallResult {
+    if buffer.is_null() && size == 0 {
+        return Ok(crate::klog::len() as u64);
+    }
+    if buffer.is_null() || size > MAX_READ {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let log = crate::klog::tail(size);
+    unsafe { copy_to_user(buffer, &log)? };
+    Ok(log.len() as u64)
+}
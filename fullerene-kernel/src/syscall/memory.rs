@@ -1,15 +1,37 @@
 use alloc::vec::Vec;
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use fullerene_abi::memory_protection::{EXEC as PROT_EXEC, READ as PROT_READ, WRITE as PROT_WRITE};
+use petroleum::page_table::constants::with_frame_allocator;
+use petroleum::page_table::process::ProcessPageTable;
 use petroleum::page_table::types::PageTableHelper;
 use x86_64::VirtAddr;
+use x86_64::structures::paging::{FrameAllocator, PageTableFlags, PhysFrame};
 
 use super::interface::{SyscallError, SyscallResult, copy_versioned_dto_to_user};
 use super::process::with_kernel_mut_result;
+use super::user::copy_to_user;
+use crate::memory_management::ProcessMemoryManagerImpl;
+use crate::process;
 
-const PROT_READ: u64 = 1;
-const PROT_WRITE: u64 = 2;
-const PROT_EXEC: u64 = 4;
+/// Largest mapping a single call may request.
+pub(super) const MAX_MAPPING_SIZE: usize = 128 << 20;
+
+/// Run `f` on the calling process's memory bookkeeping, created on first
+/// use, and on its page table (`None` for tasks on the kernel table).
+pub(super) fn with_current_memory<F>(f: F) -> SyscallResult
+where
+    F: FnOnce(&mut ProcessMemoryManagerImpl, Option<&mut ProcessPageTable>) -> SyscallResult,
+{
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    process::SCHEDULER
+        .with_process(pid, |p| {
+            let mut memory = p.resources.memory.lock();
+            let memory =
+                memory.get_or_insert_with(|| ProcessMemoryManagerImpl::new(pid.0 as usize));
+            f(memory, p.page_table.as_deref_mut())
+        })
+        .ok_or(SyscallError::NoSuchProcess)?
+}
 
 fn rollback_mapped_pages(memory: &mut crate::contexts::memory::MemoryContext, pages: &[usize]) {
     if let Some(mgr) = memory.manager.as_mut() {
@@ -21,7 +43,7 @@ fn rollback_mapped_pages(memory: &mut crate::contexts::memory::MemoryContext, pa
 
 pub(crate) fn syscall_map_memory(addr_hint: u64, length: u64, flags: u64) -> SyscallResult {
     let len = length as usize;
-    if len == 0 || len > (128 << 20) {
+    if len == 0 || len > MAX_MAPPING_SIZE {
         return Err(SyscallError::InvalidArgument);
     }
 
@@ -51,21 +73,22 @@ pub(crate) fn syscall_map_memory(addr_hint: u64, length: u64, flags: u64) -> Sys
     }
     pt_flags |= x86_64::structures::paging::PageTableFlags::USER_ACCESSIBLE;
 
-    with_kernel_mut_result(|k| -> SyscallResult {
-        let memory = &mut k.memory;
+    let num_pages = len.div_ceil(4096);
+    // Without a usable hint, place the mapping in the caller's own mapping
+    // region so it cannot land on another address space's mappings.
+    let reserved = if addr_hint != 0
+        && addr_hint % 4096 == 0
+        && petroleum::is_user_address(VirtAddr::new(addr_hint))
+    {
+        None
+    } else {
+        Some(with_current_memory(|memory, _| Ok(memory.reserve_mmap(num_pages)? as u64))? as usize)
+    };
 
-        let virt_base = if addr_hint != 0
-            && addr_hint % 4096 == 0
-            && petroleum::is_user_address(VirtAddr::new(addr_hint))
-        {
-            addr_hint as usize
-        } else {
-            static NEXT_VADDR: AtomicU64 = AtomicU64::new(0x100_0000_0000);
-            let aligned_len = (len + 4095) & !4095;
-            NEXT_VADDR.fetch_add(aligned_len as u64, Ordering::Relaxed) as usize
-        };
+    let result = with_kernel_mut_result(|k| -> SyscallResult {
+        let memory = &mut k.memory;
+        let virt_base = reserved.unwrap_or(addr_hint as usize);
 
-        let num_pages = (len + 4095) / 4096;
         let mut mapped_pages: Vec<usize> = Vec::with_capacity(num_pages);
         for i in 0..num_pages {
             let frame = memory.allocate_frame().map_err(|_| {
@@ -82,12 +105,20 @@ pub(crate) fn syscall_map_memory(addr_hint: u64, length: u64, flags: u64) -> Sys
         }
 
         Ok(virt_base as u64)
-    })
+    });
+    if let (Err(_), Some(base)) = (&result, reserved) {
+        let _ = with_current_memory(|memory, _| {
+            memory.release_mmap(base, num_pages)?;
+            Ok(0)
+        });
+    }
+    result
 }
 
-pub(crate) fn syscall_unmap_memory(addr: u64, length: u64) -> SyscallResult {
-    let len = length as usize;
-    if len == 0 || (addr % 4096) != 0 {
+/// Check that `length` bytes at the page-aligned `addr` are a non-empty
+/// range of user addresses and return its end.
+fn user_range_end(addr: u64, length: u64) -> Result<u64, SyscallError> {
+    if length == 0 || (addr % 4096) != 0 {
         return Err(SyscallError::InvalidArgument);
     }
     let end_vaddr = addr
@@ -98,11 +129,25 @@ pub(crate) fn syscall_unmap_memory(addr: u64, length: u64) -> SyscallResult {
     if !petroleum::is_user_address(start_addr) || !petroleum::is_user_address(end_addr) {
         return Err(SyscallError::PermissionDenied);
     }
+    Ok(end_vaddr)
+}
 
+pub(crate) fn syscall_unmap_memory(addr: u64, length: u64) -> SyscallResult {
+    user_range_end(addr, length)?;
+    let num_pages = (length as usize).div_ceil(4096);
+    // Settle the bookkeeping before any page goes, so a refused call
+    // changes neither.  Hinted mappings were never reserved and have
+    // nothing to release; a reserved one can only go as a whole.
+    with_current_memory(|memory, _| {
+        if memory.release_mmap(addr as usize, num_pages).is_err()
+            && memory.overlaps_mmap(addr as usize, num_pages)
+        {
+            return Err(SyscallError::InvalidArgument);
+        }
+        Ok(0)
+    })?;
     with_kernel_mut_result(|k| -> SyscallResult {
-        let memory = &mut k.memory;
-        let num_pages = (len + 4095) / 4096;
-        let mgr = memory.manager.as_mut().ok_or(SyscallError::OutOfMemory)?;
+        let mgr = k.memory.manager.as_mut().ok_or(SyscallError::OutOfMemory)?;
         for i in 0..num_pages {
             let vaddr = addr as usize + i * 4096;
             mgr.safe_unmap_page(vaddr)
@@ -112,6 +157,162 @@ pub(crate) fn syscall_unmap_memory(addr: u64, length: u64) -> SyscallResult {
     })
 }
 
+/// Page-table flags for a present user page with protection `prot`.
+pub(crate) fn user_page_flags(prot: u64) -> PageTableFlags {
+    let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+    if (prot & PROT_WRITE) != 0 {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if (prot & PROT_EXEC) == 0 {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+    flags
+}
+
+/// Map zeroed user pages over `[start, end)` in `page_table`.
+///
+/// Fails without leaving anything mapped if the range overlaps an existing
+/// mapping or memory runs out.
+pub(crate) fn map_user_pages(
+    page_table: &mut ProcessPageTable,
+    start: usize,
+    end: usize,
+    flags: PageTableFlags,
+) -> Result<(), SyscallError> {
+    if (start..end)
+        .step_by(4096)
+        .any(|vaddr| page_table.translate_address(vaddr).is_ok())
+    {
+        return Err(SyscallError::OutOfMemory);
+    }
+    for vaddr in (start..end).step_by(4096) {
+        let frame = with_frame_allocator(|allocator| allocator.allocate_frame());
+        let mapped = frame.ok_or(SyscallError::OutOfMemory).and_then(|frame| {
+            let frame_vaddr = petroleum::common::memory::physical_to_virtual(
+                frame.start_address().as_u64() as usize,
+            );
+            unsafe { core::ptr::write_bytes(frame_vaddr as *mut u8, 0, 4096) };
+            page_table
+                .map_page(
+                    vaddr,
+                    frame.start_address().as_u64() as usize,
+                    flags,
+                    unsafe { petroleum::page_table::constants::get_frame_allocator_mut() },
+                )
+                .map_err(|_| {
+                    free_user_frame(frame);
+                    SyscallError::OutOfMemory
+                })
+        });
+        if let Err(error) = mapped {
+            unmap_user_pages(page_table, start, vaddr);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Unmap `[start, end)` from `page_table` and free the backing frames.
+pub(super) fn unmap_user_pages(page_table: &mut ProcessPageTable, start: usize, end: usize) {
+    for vaddr in (start..end).step_by(4096) {
+        if let Ok(frame) = page_table.unmap_page(vaddr) {
+            free_user_frame(frame);
+        }
+    }
+}
+
+/// Drop this mapping's reference to `frame`; a frame still shared
+/// copy-on-write or through shared memory stays allocated.
+pub(super) fn free_user_frame(frame: PhysFrame) {
+    with_frame_allocator(|allocator| allocator.free_frame(frame));
+}
+
+/// Move the caller's program break to `new_break` and return the resulting
+/// break; `new_break == 0` only queries it.
+///
+/// Pages between the old and the new break are mapped or unmapped in the
+/// caller's own page table.  The break cannot move below where it started,
+/// and growth into the stack region or over existing mappings fails with
+/// `OutOfMemory`.
+pub(crate) fn syscall_brk(new_break: u64) -> SyscallResult {
+    with_current_memory(|memory, page_table| {
+        if new_break == 0 {
+            return Ok(memory.program_break() as u64);
+        }
+        let page_table = page_table.ok_or(SyscallError::NotSupported)?;
+
+        let old_break = memory.program_break();
+        let (old_end, new_end) = memory.set_program_break(new_break as usize)?;
+        if new_end > old_end {
+            let flags = user_page_flags(PROT_READ | PROT_WRITE);
+            if let Err(error) = map_user_pages(page_table, old_end, new_end, flags) {
+                memory.set_program_break(old_break)?;
+                return Err(error);
+            }
+        } else {
+            unmap_user_pages(page_table, new_end, old_end);
+        }
+        Ok(memory.program_break() as u64)
+    })
+}
+
+/// Map `pages` zeroed pages with protection `prot` at an address picked
+/// from the caller's mapping region and return it.
+pub(crate) fn syscall_mmap(pages: u64, prot: u64) -> SyscallResult {
+    let valid_prot = PROT_READ | PROT_WRITE | PROT_EXEC;
+    if pages == 0
+        || pages > (MAX_MAPPING_SIZE / 4096) as u64
+        || prot == 0
+        || (prot & !valid_prot) != 0
+    {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let pages = pages as usize;
+
+    with_current_memory(|memory, page_table| {
+        let page_table = page_table.ok_or(SyscallError::NotSupported)?;
+        let base = memory.reserve_mmap(pages)?;
+        if let Err(error) =
+            map_user_pages(page_table, base, base + pages * 4096, user_page_flags(prot))
+        {
+            memory.release_mmap(base, pages)?;
+            return Err(error);
+        }
+        Ok(base as u64)
+    })
+}
+
+/// Release a whole mapping made by [`syscall_mmap`].
+///
+/// The range has to be exactly one reservation of the caller's mapping
+/// region.  Shared-memory attachments live in the same region but are only
+/// released by `ShmDetach`, so a range with any shared page is refused.
+pub(crate) fn syscall_munmap(addr: u64, pages: u64) -> SyscallResult {
+    if pages > (MAX_MAPPING_SIZE / 4096) as u64 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let length = pages
+        .checked_mul(4096)
+        .ok_or(SyscallError::InvalidArgument)?;
+    let end = user_range_end(addr, length)? as usize;
+    let (start, pages) = (addr as usize, pages as usize);
+
+    with_current_memory(|memory, page_table| {
+        let page_table = page_table.ok_or(SyscallError::NotSupported)?;
+        if (start..end).step_by(4096).any(|vaddr| {
+            page_table
+                .get_page_flags(vaddr)
+                .is_ok_and(|flags| flags.contains(crate::memory_management::SHARED_FLAG))
+        }) {
+            return Err(SyscallError::InvalidArgument);
+        }
+        // Fails unless `[start, end)` is a whole reservation of its own.
+        memory.release_mmap(start, pages)?;
+        unmap_user_pages(page_table, start, end);
+        Ok(0)
+    })
+}
+
 pub(crate) fn syscall_protect_memory(addr: u64, length: u64, prot: u64) -> SyscallResult {
     let len = length as usize;
     if len == 0 || len > (128 << 20) || (addr % 4096) != 0 {
@@ -211,3 +412,15 @@ pub(crate) fn syscall_query_memory(info_buf: *mut u8, buf_size: usize) -> Syscal
         &bytes,
     )
 }
+
+/// Copy the system-wide [`fullerene_abi::MemInfo`] to `info_buf`.
+pub(crate) fn syscall_mem_info(info_buf: *mut u8) -> SyscallResult {
+    if info_buf.is_null() {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let bytes = crate::memory_management::mem_info()
+        .ok_or(SyscallError::NotSupported)?
+        .to_ne_bytes();
+    unsafe { copy_to_user(info_buf, &bytes)? };
+    Ok(0)
+}
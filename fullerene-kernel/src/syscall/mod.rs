@@ -4,9 +4,15 @@ pub mod device;
 pub mod dispatch;
 pub mod event;
 pub mod fs;
+pub mod futex;
 pub mod ipc;
+pub mod klog;
 pub mod memory;
+pub mod pipe;
+pub mod power;
 pub mod process;
+pub mod sem;
+pub mod shm;
 pub mod thread;
 pub mod time;
 pub mod types;
@@ -73,13 +79,13 @@ mod support_matrix {
             number: 2,
             name: "fork",
             support: Support::Full,
-            notes: "COW page tables",
+            notes: "copies the user address space",
         },
         SyscallInfo {
             number: 3,
             name: "read",
             support: Support::Full,
-            notes: "",
+            notes: "console stdin is line-buffered with echo; NONBLOCK flag",
         },
         SyscallInfo {
             number: 4,
@@ -103,7 +109,13 @@ mod support_matrix {
             number: 7,
             name: "wait",
             support: Support::Partial,
-            notes: "non-blocking only",
+            notes: "waitpid on a child; pid 0 only yields",
+        },
+        SyscallInfo {
+            number: 8,
+            name: "waitpid",
+            support: Support::Full,
+            notes: "reaps zombie children",
         },
         SyscallInfo {
             number: 20,
@@ -123,6 +135,42 @@ mod support_matrix {
             support: Support::Full,
             notes: "",
         },
+        SyscallInfo {
+            number: 24,
+            name: "set_priority",
+            support: Support::Full,
+            notes: "self or direct children only",
+        },
+        SyscallInfo {
+            number: 25,
+            name: "exec",
+            support: Support::Full,
+            notes: "replaces the image with a static ELF from the VFS",
+        },
+        SyscallInfo {
+            number: 26,
+            name: "list_processes",
+            support: Support::Full,
+            notes: "returns the total count when the buffer is too small",
+        },
+        SyscallInfo {
+            number: 27,
+            name: "set_name",
+            support: Support::Full,
+            notes: "names beyond 32 bytes are truncated",
+        },
+        SyscallInfo {
+            number: 28,
+            name: "kill",
+            support: Support::Full,
+            notes: "SIGKILL and SIGTERM only; SIGTERM is left pending",
+        },
+        SyscallInfo {
+            number: 29,
+            name: "take_signals",
+            support: Support::Full,
+            notes: "returns and clears the pending signal mask",
+        },
         SyscallInfo {
             number: 30,
             name: "map_memory",
@@ -147,6 +195,42 @@ mod support_matrix {
             support: Support::Stub,
             notes: "returns empty data",
         },
+        SyscallInfo {
+            number: 34,
+            name: "brk",
+            support: Support::Full,
+            notes: "grows or shrinks the heap; 0 queries the break",
+        },
+        SyscallInfo {
+            number: 35,
+            name: "mmap",
+            support: Support::Full,
+            notes: "anonymous pages at a kernel-chosen address",
+        },
+        SyscallInfo {
+            number: 36,
+            name: "munmap",
+            support: Support::Full,
+            notes: "whole mappings only",
+        },
+        SyscallInfo {
+            number: 37,
+            name: "shm_create",
+            support: Support::Full,
+            notes: "zeroed segment, rounded up to whole pages",
+        },
+        SyscallInfo {
+            number: 38,
+            name: "shm_attach",
+            support: Support::Full,
+            notes: "read-write, once per process; not inherited by fork",
+        },
+        SyscallInfo {
+            number: 39,
+            name: "shm_detach",
+            support: Support::Full,
+            notes: "the last detach frees the segment",
+        },
         SyscallInfo {
             number: 40,
             name: "create_event",
@@ -171,6 +255,42 @@ mod support_matrix {
             support: Support::Stub,
             notes: "returns NotSupported",
         },
+        SyscallInfo {
+            number: 44,
+            name: "futex_wait",
+            support: Support::Full,
+            notes: "keyed by physical address; no timeout",
+        },
+        SyscallInfo {
+            number: 45,
+            name: "futex_wake",
+            support: Support::Full,
+            notes: "",
+        },
+        SyscallInfo {
+            number: 46,
+            name: "mem_info",
+            support: Support::Full,
+            notes: "physical memory totals from the frame allocator",
+        },
+        SyscallInfo {
+            number: 47,
+            name: "read_klog",
+            support: Support::Full,
+            notes: "newest bytes of the kernel log ring buffer",
+        },
+        SyscallInfo {
+            number: 48,
+            name: "dup",
+            support: Support::Full,
+            notes: "lowest free fd; shares the file offset",
+        },
+        SyscallInfo {
+            number: 49,
+            name: "dup2",
+            support: Support::Full,
+            notes: "closes newfd first; dup2(fd, fd) returns fd",
+        },
         SyscallInfo {
             number: 50,
             name: "create_thread",
@@ -195,6 +315,30 @@ mod support_matrix {
             support: Support::Full,
             notes: "",
         },
+        SyscallInfo {
+            number: 54,
+            name: "sem_create",
+            support: Support::Full,
+            notes: "counting semaphore; lives until sem_destroy",
+        },
+        SyscallInfo {
+            number: 55,
+            name: "sem_wait",
+            support: Support::Full,
+            notes: "FIFO waiters; fails once the semaphore is destroyed",
+        },
+        SyscallInfo {
+            number: 56,
+            name: "sem_post",
+            support: Support::Full,
+            notes: "overflow past u32::MAX is an error",
+        },
+        SyscallInfo {
+            number: 57,
+            name: "sem_destroy",
+            support: Support::Full,
+            notes: "wakes every waiter with an error",
+        },
         SyscallInfo {
             number: 60,
             name: "create_window",
@@ -309,6 +453,36 @@ mod support_matrix {
             support: Support::Full,
             notes: "",
         },
+        SyscallInfo {
+            number: 104,
+            name: "sleep_ticks",
+            support: Support::Full,
+            notes: "blocks until the timer tick deadline",
+        },
+        SyscallInfo {
+            number: 105,
+            name: "get_time_of_day",
+            support: Support::Full,
+            notes: "Unix seconds from the CMOS RTC (UTC)",
+        },
+        SyscallInfo {
+            number: 106,
+            name: "clock_monotonic",
+            support: Support::Full,
+            notes: "nanoseconds from the PIT-calibrated TSC",
+        },
+        SyscallInfo {
+            number: 110,
+            name: "shutdown",
+            support: Support::Full,
+            notes: "QEMU isa-debug-exit with an exit code up to 111, then ACPI S5, then triple fault; privileged processes only",
+        },
+        SyscallInfo {
+            number: 111,
+            name: "reboot",
+            support: Support::Full,
+            notes: "0xCF9 reset, then keyboard controller, then triple fault; privileged processes only",
+        },
     ];
 
     #[test]
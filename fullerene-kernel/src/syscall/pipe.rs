@@ -0,0 +1,204 @@
+//! In-kernel pipes.
+//!
+//! A [`Pipe`] is a bounded byte ring shared by its [`PipeEnd`]s.  Every end
+//! counts itself in when created or cloned and out when dropped, so a reader
+//! can tell end-of-file (no writers left) from an empty pipe and a writer can
+//! tell a broken pipe (no readers left) from a full one.  Ends live in handle
+//! tables as [`KernelObject::Pipe`](super::types::KernelObject::Pipe) and in
+//! [`FdTable::stdio`](crate::process::FdTable::stdio) when a process's
+//! standard streams are redirected.
+//!
+//! Blocking reads and writes yield the CPU and retry, like console input,
+//! rather than queueing waiters: ends are dropped during process cleanup
+//! while the scheduler is locked, where waking a waiter is not possible.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+use super::interface::SyscallError;
+
+/// Bytes a pipe holds before writers block.
+pub const PIPE_CAPACITY: usize = 4096;
+
+pub struct Pipe {
+    buffer: VecDeque<u8>,
+    readers: usize,
+    writers: usize,
+}
+
+impl Pipe {
+    /// Move buffered bytes into `out`.
+    ///
+    /// Returns `Ok(0)` at end-of-file and [`SyscallError::WouldBlock`] when
+    /// the pipe is empty but still has a writer.
+    pub fn read(&mut self, out: &mut [u8]) -> Result<usize, SyscallError> {
+        if self.buffer.is_empty() {
+            return if self.writers == 0 {
+                Ok(0)
+            } else {
+                Err(SyscallError::WouldBlock)
+            };
+        }
+        let count = out.len().min(self.buffer.len());
+        for (slot, byte) in out.iter_mut().zip(self.buffer.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+
+    /// Append as much of `data` as fits.
+    ///
+    /// Fails with [`SyscallError::BrokenPipe`] once every reader is gone and
+    /// with [`SyscallError::WouldBlock`] while the pipe is full.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, SyscallError> {
+        if self.readers == 0 {
+            return Err(SyscallError::BrokenPipe);
+        }
+        let count = data.len().min(PIPE_CAPACITY - self.buffer.len());
+        if count == 0 && !data.is_empty() {
+            return Err(SyscallError::WouldBlock);
+        }
+        self.buffer.extend(&data[..count]);
+        Ok(count)
+    }
+}
+
+/// One end of a [`Pipe`].
+pub struct PipeEnd {
+    pipe: Arc<Mutex<Pipe>>,
+    is_read_end: bool,
+}
+
+/// Create a pipe, returning its read and write ends.
+pub fn pipe() -> (PipeEnd, PipeEnd) {
+    let pipe = Arc::new(Mutex::new(Pipe {
+        buffer: VecDeque::with_capacity(PIPE_CAPACITY),
+        readers: 0,
+        writers: 0,
+    }));
+    (
+        PipeEnd::attach(Arc::clone(&pipe), true),
+        PipeEnd::attach(pipe, false),
+    )
+}
+
+impl PipeEnd {
+    fn attach(pipe: Arc<Mutex<Pipe>>, is_read_end: bool) -> Self {
+        {
+            let mut state = pipe.lock();
+            if is_read_end {
+                state.readers += 1;
+            } else {
+                state.writers += 1;
+            }
+        }
+        Self { pipe, is_read_end }
+    }
+
+    pub fn is_read_end(&self) -> bool {
+        self.is_read_end
+    }
+
+    /// Read into `out`, yielding while the pipe is empty and has a writer.
+    pub fn read(&self, out: &mut [u8]) -> Result<usize, SyscallError> {
+        if !self.is_read_end {
+            return Err(SyscallError::BadFileDescriptor);
+        }
+        loop {
+            match self.pipe.lock().read(out) {
+                Err(SyscallError::WouldBlock) => {}
+                result => return result,
+            }
+            crate::process::yield_current();
+        }
+    }
+
+    /// Write all of `data`, yielding while the pipe is full.
+    ///
+    /// If the last reader goes away part-way, the bytes already written are
+    /// reported; [`SyscallError::BrokenPipe`] is only returned when nothing
+    /// was written.
+    pub fn write(&self, data: &[u8]) -> Result<usize, SyscallError> {
+        if self.is_read_end {
+            return Err(SyscallError::BadFileDescriptor);
+        }
+        let mut written = 0;
+        while written < data.len() {
+            match self.pipe.lock().write(&data[written..]) {
+                Ok(count) => {
+                    written += count;
+                    continue;
+                }
+                Err(SyscallError::WouldBlock) => {}
+                Err(_) if written > 0 => break,
+                Err(error) => return Err(error),
+            }
+            crate::process::yield_current();
+        }
+        Ok(written)
+    }
+}
+
+impl Clone for PipeEnd {
+    fn clone(&self) -> Self {
+        Self::attach(Arc::clone(&self.pipe), self.is_read_end)
+    }
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        let mut state = self.pipe.lock();
+        if self.is_read_end {
+            state.readers -= 1;
+        } else {
+            state.writers -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closed_ends_mean_eof_and_broken_pipe() {
+        let (reader, writer) = pipe();
+        let mut out = [0u8; 8];
+        assert_eq!(writer.write(b"abc"), Ok(3));
+        assert_eq!(reader.pipe.lock().read(&mut out), Ok(3));
+        assert_eq!(&out[..3], b"abc");
+        assert_eq!(
+            reader.pipe.lock().read(&mut out),
+            Err(SyscallError::WouldBlock)
+        );
+
+        // A clone keeps the write side open.
+        let second_writer = writer.clone();
+        drop(writer);
+        assert_eq!(
+            reader.pipe.lock().read(&mut out),
+            Err(SyscallError::WouldBlock)
+        );
+        drop(second_writer);
+        assert_eq!(reader.read(&mut out), Ok(0));
+
+        let (reader, writer) = pipe();
+        drop(reader);
+        assert_eq!(writer.write(b"x"), Err(SyscallError::BrokenPipe));
+    }
+
+    #[test]
+    fn a_full_pipe_accepts_only_what_fits() {
+        let (reader, writer) = pipe();
+        let data = [7u8; PIPE_CAPACITY + 10];
+        let mut pipe = writer.pipe.lock();
+        assert_eq!(pipe.write(&data), Ok(PIPE_CAPACITY));
+        assert_eq!(pipe.write(&data), Err(SyscallError::WouldBlock));
+        let mut out = [0u8; 10];
+        assert_eq!(pipe.read(&mut out), Ok(10));
+        assert_eq!(pipe.write(&data), Ok(10));
+        drop(pipe);
+        assert!(!writer.is_read_end() && reader.is_read_end());
+    }
+}
@@ -0,0 +1,33 @@
+//! Shutdown and reboot syscall implementations.  Neither returns on
+//! success; both are refused to processes that are not
+//! [`privileged`](crate::process::Process::privileged).
+
+use super::interface::{SyscallError, SyscallResult};
+use crate::process;
+
+fn require_privileged() -> Result<(), SyscallError> {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let privileged = process::SCHEDULER
+        .with_process(pid, |p| p.privileged)
+        .ok_or(SyscallError::NoSuchProcess)?;
+    if privileged {
+        Ok(())
+    } else {
+        Err(SyscallError::PermissionDenied)
+    }
+}
+
+/// Power off, reporting `code` to a test harness; see [`crate::power`].
+pub(crate) fn syscall_shutdown(code: u64) -> SyscallResult {
+    require_privileged()?;
+    let code = u8::try_from(code)
+        .ok()
+        .filter(|&code| code <= crate::power::MAX_EXIT_CODE)
+        .ok_or(SyscallError::InvalidArgument)?;
+    crate::power::shutdown(code)
+}
+
+pub(crate) fn syscall_reboot() -> SyscallResult {
+    require_privileged()?;
+    crate::power::reboot()
+}
@@ -1,17 +1,19 @@
 //! Native process lifecycle syscalls and per-process resource access.
 
 use alloc::boxed::Box;
-use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
 use core::alloc::Layout;
 
 use petroleum::common::memory::UserSlice;
-use petroleum::page_table::PageTableHelper;
-use x86_64::{PhysAddr, VirtAddr};
+use petroleum::page_table::constants::with_frame_allocator;
+use x86_64::VirtAddr;
 
 use super::interface::{SyscallError, SyscallResult};
 use super::types::{Handle, HandlePerms, KernelObject};
-use crate::process::{self, Process, ProcessState};
+use crate::memory_management::ProcessMemoryManagerImpl;
+use crate::process::{self, Process, ProcessName, ProcessState};
+use crate::scheduler_context::ReapResult;
 
 pub(crate) fn with_current_fd_table<F, R>(f: F) -> Result<R, SyscallError>
 where
@@ -107,92 +109,113 @@ pub(crate) fn syscall_exit(exit_code: i32) -> SyscallResult {
     Ok(0)
 }
 
+/// Duplicate the calling user process.
+///
+/// The child gets a private copy of the parent's user address space and
+/// resumes at the parent's syscall return site with the same stack pointer
+/// and callee-saved registers; it sees 0 in `rax`, the parent sees the
+/// child's pid.
 pub(crate) fn syscall_fork() -> SyscallResult {
     let current_pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
 
-    let (parent_page_table_phys_addr, parent_context, parent_user_stack, parent_entry_point) = {
+    let (
+        parent_name,
+        parent_page_table_phys_addr,
+        parent_context,
+        parent_user_stack,
+        parent_entry_point,
+        parent_priority,
+        parent_privileged,
+    ) = {
         process::SCHEDULER
             .with_process(current_pid, |process| {
                 (
+                    process.name,
                     process.page_table_phys_addr,
                     process.context.clone(),
                     process.user_stack,
                     process.entry_point,
+                    process.priority,
+                    process.privileged,
                 )
             })
             .ok_or(SyscallError::NoSuchProcess)?
     };
 
-    let cloned_table_addr = {
-        let mut manager_guard = crate::memory_management::get_memory_manager().lock();
-        let manager = manager_guard.as_mut().ok_or(SyscallError::OutOfMemory)?;
-
-        let page_table_manager = &mut manager.page_table_manager;
-        petroleum::page_table::constants::with_frame_allocator(|allocator| {
-            PageTableHelper::clone_page_table(
-                page_table_manager,
-                parent_page_table_phys_addr.as_u64() as usize,
-                allocator,
-            )
-        })?
-    };
-
-    let cloned_pml4_frame = x86_64::structures::paging::PhysFrame::containing_address(
-        PhysAddr::new(cloned_table_addr as u64),
-    );
+    // Kernel tasks do not enter through `syscall`, so there is no user
+    // return site to resume the child at.
+    if !parent_context.is_user {
+        return Err(SyscallError::NotSupported);
+    }
+    let frame =
+        crate::interrupts::syscall::current_syscall_frame().ok_or(SyscallError::InvalidArgument)?;
 
     let mut child_page_table =
-        petroleum::page_table::ProcessPageTable::new_with_frame(cloned_pml4_frame);
-    petroleum::initializer::Initializable::init(&mut child_page_table).map_err(|_| {
-        crate::memory_management::deallocate_process_page_table(cloned_pml4_frame);
-        SyscallError::InvalidArgument
-    })?;
+        crate::memory_management::fork_process_page_table(parent_page_table_phys_addr)?;
+    let child_pml4_frame = child_page_table
+        .pml4_frame
+        .ok_or(SyscallError::OutOfMemory)?;
 
     let (kernel_stack_ptr, kernel_stack_top) = alloc_kernel_stack().map_err(|error| {
-        crate::memory_management::deallocate_process_page_table(cloned_pml4_frame);
+        crate::memory_management::deallocate_process_page_table(child_pml4_frame);
         error
     })?;
 
-    let child_pid = process::SCHEDULER.allocate_pid().0 as usize;
-    let _ = child_page_table.unmap_page(petroleum::vdso::VDSO_USER_BASE as usize);
+    let child_pid = process::SCHEDULER.allocate_pid();
 
-    let child_vdso = if parent_context.is_user {
-        let mut allocator_guard = crate::heap::FRAME_ALLOCATOR.lock();
-        let allocator = match allocator_guard.as_mut() {
-            Some(allocator) => allocator,
-            None => {
-                drop(allocator_guard);
-                free_kernel_stack(kernel_stack_ptr);
-                crate::memory_management::deallocate_process_page_table(cloned_pml4_frame);
-                return Err(SyscallError::OutOfMemory);
-            }
-        };
-        let vdso =
-            crate::vdso::create_vdso_page(&mut child_page_table, allocator, child_pid as u64);
-        drop(allocator_guard);
+    let child_vdso = {
+        let vdso = with_frame_allocator(|allocator| {
+            crate::vdso::create_vdso_page(&mut child_page_table, allocator, child_pid.0)
+        });
         match vdso {
             Ok(vdso) => Some(vdso),
             Err(_) => {
                 free_kernel_stack(kernel_stack_ptr);
-                crate::memory_management::deallocate_process_page_table(cloned_pml4_frame);
+                crate::memory_management::deallocate_process_page_table(child_pml4_frame);
                 return Err(SyscallError::OutOfMemory);
             }
         }
-    } else {
-        None
     };
 
-    let mut child_process = Process {
-        id: process::ProcessId(child_pid as u64),
-        name: "child",
+    // Resume exactly where the parent's `syscall` returns to. The syscall
+    // ABI only preserves the callee-saved registers; RCX/R11 hold RIP and
+    // RFLAGS as SYSRET would leave them.
+    let mut child_context = parent_context.clone();
+    child_context.regs = [0; 16];
+    child_context.regs[1] = frame.rbx;
+    child_context.regs[2] = frame.rip;
+    child_context.regs[6] = frame.rbp;
+    child_context.regs[7] = frame.rsp;
+    child_context.regs[11] = frame.rflags;
+    child_context.regs[12] = frame.r12;
+    child_context.regs[13] = frame.r13;
+    child_context.regs[14] = frame.r14;
+    child_context.regs[15] = frame.r15;
+    child_context.rip = frame.rip;
+    child_context.rflags = frame.rflags;
+    // The parent's saved context predates this syscall; its live GS base is
+    // the one in KERNEL_GS_BASE.
+    child_context.user_gs_base = crate::percpu::user_gs_base();
+    // The child starts in ring 3, not inside the parent's syscall.
+    child_context.syscall_frame = 0;
+
+    let child_process = Process {
+        id: child_pid,
+        name: parent_name,
         state: ProcessState::Ready,
-        context: parent_context.clone(),
-        page_table_phys_addr: PhysAddr::new(cloned_table_addr as u64),
+        wake_tick: None,
+        priority: parent_priority,
+        ready_since: crate::scheduler::get_system_tick(),
+        cpu_time_us: 0,
+        pending_signals: 0,
+        context: child_context,
+        page_table_phys_addr: child_pml4_frame.start_address(),
         page_table: Some(Box::new(child_page_table)),
         kernel_stack: kernel_stack_top,
         user_stack: parent_user_stack,
         entry_point: parent_entry_point,
-        is_user: parent_context.is_user,
+        is_user: true,
+        privileged: parent_privileged,
         task_data: 0,
         exit_code: None,
         parent_id: Some(current_pid),
@@ -201,42 +224,65 @@ pub(crate) fn syscall_fork() -> SyscallResult {
         resources: process::ProcessResources::new(),
     };
 
-    child_process.context.regs[0] = 0;
-    child_process.context.regs[7] = child_process.user_stack.as_u64();
+    // The child inherits the parent's heap pages, so its break starts there too.
+    let child_memory = process::SCHEDULER
+        .with_process(current_pid, |p| {
+            p.resources
+                .memory
+                .lock()
+                .as_ref()
+                .map(|memory| memory.fork(child_pid.0 as usize))
+        })
+        .flatten();
+    *child_process.resources.memory.lock() = child_memory;
+    // Redirected standard streams stay connected in the child.
+    if let Some(streams) = process::SCHEDULER.with_process(current_pid, |p| {
+        p.resources.fd_table.lock().standard_streams()
+    }) {
+        child_process
+            .resources
+            .fd_table
+            .lock()
+            .set_standard_streams(streams);
+    }
 
     process::SCHEDULER
         .add(Box::new(child_process))
         .map_err(|_| {
             free_kernel_stack(kernel_stack_ptr);
-            crate::memory_management::deallocate_process_page_table(cloned_pml4_frame);
+            crate::memory_management::deallocate_process_page_table(child_pml4_frame);
             SyscallError::OutOfMemory
         })?;
 
-    Ok(child_pid as u64)
+    Ok(child_pid.0)
 }
 
+/// Legacy wait.  `pid == 0` only yields; any other pid behaves as
+/// [`syscall_waitpid`], so it must name a child of the caller, which is
+/// reaped, and fails with `NoSuchProcess` otherwise.  It used to wait for
+/// any process and leave it in the process list.
 pub(crate) fn syscall_wait(pid: u64) -> SyscallResult {
     if pid == 0 {
         process::yield_current();
         return Ok(0);
     }
+    syscall_waitpid(pid)
+}
 
-    let process_id = process::ProcessId(pid);
+/// Block until child `pid` (or any child when `pid == 0`) has exited, reap
+/// it and return its exit code.
+pub(crate) fn syscall_waitpid(pid: u64) -> SyscallResult {
+    let caller = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let child = (pid != 0).then_some(process::ProcessId(pid));
     loop {
-        let state = process::SCHEDULER
-            .with_process(process_id, |process| (process.state, process.exit_code));
-
-        match state {
-            Some((ProcessState::Terminated, exit_code)) => {
-                return Ok(exit_code.unwrap_or(0) as u64);
-            }
-            Some(_) => {
-                // A sibling child can also wake this parent. Re-check the
-                // requested child after every wakeup instead of treating any
-                // parent wakeup as completion of this wait.
-                process::block_current();
-            }
-            None => return Err(SyscallError::NoSuchProcess),
+        match process::SCHEDULER.reap_child(caller, child) {
+            // Exit codes travel as their 32-bit pattern so they are never
+            // mistaken for a negated error code.
+            ReapResult::Exited { exit_code, .. } => return Ok(exit_code as u32 as u64),
+            // Any exiting child wakes the parent, so re-check after every
+            // wakeup instead of treating it as completion of this wait.
+            ReapResult::Alive => process::block_current(),
+            ReapResult::NoChild => return Err(SyscallError::NoSuchProcess),
         }
     }
 }
@@ -270,14 +316,158 @@ pub(crate) fn syscall_get_process_name(buffer: *mut u8, size: usize) -> SyscallR
         .ok_or(SyscallError::NoSuchProcess)?
 }
 
+/// Fill `buffer` with one [`fullerene_abi::ProcessInfo`] per process and
+/// return the number of processes.
+///
+/// A buffer too small for every record is filled as far as it goes, and the
+/// full count is still returned so the caller can retry with a larger one.
+/// A null buffer of size 0 only queries the count.
+pub(crate) fn syscall_list_processes(buffer: *mut u8, size: usize) -> SyscallResult {
+    let infos = process::process_infos();
+    if buffer.is_null() && size == 0 {
+        return Ok(infos.len() as u64);
+    }
+    if buffer.is_null() || size > (1 << 20) {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let fitting = infos
+        .len()
+        .min(size / fullerene_abi::ProcessInfo::BYTE_SIZE);
+    if fitting > 0 {
+        let length = fitting * fullerene_abi::ProcessInfo::BYTE_SIZE;
+        petroleum::validate_user_buffer(buffer as usize, length, false)?;
+        let kernel_buf: Vec<u8> = infos[..fitting]
+            .iter()
+            .flat_map(|info| info.to_ne_bytes())
+            .collect();
+        let slice =
+            UserSlice::new(buffer, length, true).map_err(|_| SyscallError::InvalidArgument)?;
+        unsafe { slice.copy_to_user(&kernel_buf) }.map_err(|_| SyscallError::InvalidArgument)?;
+    }
+    Ok(infos.len() as u64)
+}
+
+/// Send `signal` to `pid` (the caller itself when `pid == 0`).
+///
+/// `SIGKILL` aimed at the caller terminates it at once; aimed at another
+/// process it is delivered before the scheduler next dispatches anything.
+/// `SIGTERM` only stays pending until the target calls `take_signals`.
+/// Signal 0 just checks that the target exists.
+pub(crate) fn syscall_kill(pid: u64, signal: u64) -> SyscallResult {
+    use fullerene_abi::signals;
+    if !matches!(signal, 0 | signals::SIGKILL | signals::SIGTERM) {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let caller = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let target = if pid == 0 {
+        caller
+    } else {
+        process::ProcessId(pid)
+    };
+    process::SCHEDULER
+        .with_process(target, |p| {
+            if matches!(p.state, ProcessState::Zombie | ProcessState::Terminated) {
+                return Err(SyscallError::NoSuchProcess);
+            }
+            // The scheduler falls back to the idle task when nothing else
+            // can run, so it must outlive every signal.
+            if process::SCHEDULER.is_idle(target) {
+                return Err(SyscallError::PermissionDenied);
+            }
+            if signal != 0 {
+                p.pending_signals |= 1 << signal;
+            }
+            Ok(())
+        })
+        .ok_or(SyscallError::NoSuchProcess)??;
+
+    if target == caller && signal == signals::SIGKILL {
+        process::terminate_process(caller, signals::KILLED_EXIT_CODE);
+    }
+    Ok(0)
+}
+
+/// Return the caller's pending signals as `1 << signal` bits and clear them.
+pub(crate) fn syscall_take_signals() -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    process::SCHEDULER
+        .with_process(pid, |p| core::mem::take(&mut p.pending_signals))
+        .ok_or(SyscallError::NoSuchProcess)
+}
+
+/// Go to the back of the caller's priority level; see
+/// [`process::yield_turn`].
 pub(crate) fn syscall_yield() -> SyscallResult {
-    process::yield_current();
+    process::yield_turn();
     Ok(0)
 }
 
+/// Change the scheduling priority of the caller (`pid == 0` or its own pid)
+/// or of one of its direct children.
+pub(crate) fn syscall_set_priority(pid: u64, priority: u64) -> SyscallResult {
+    if priority > process::MAX_PRIORITY as u64 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let caller = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let target = if pid == 0 {
+        caller
+    } else {
+        process::ProcessId(pid)
+    };
+
+    if target != caller {
+        let parent = process::SCHEDULER
+            .with_process(target, |process| process.parent_id)
+            .ok_or(SyscallError::NoSuchProcess)?;
+        if parent != Some(caller) {
+            return Err(SyscallError::PermissionDenied);
+        }
+    }
+
+    if process::SCHEDULER.set_priority(target, priority as u8) {
+        Ok(0)
+    } else {
+        Err(SyscallError::NoSuchProcess)
+    }
+}
+
 const MAX_EXECUTABLE_BYTES: usize = 64 * 1024 * 1024;
 const MAX_PROCESS_NAME_BYTES: usize = 64;
 
+/// Copy a process name from the caller.
+///
+/// At most [`MAX_PROCESS_NAME_BYTES`] are read and [`ProcessName`] keeps
+/// fewer still, so long names are truncated rather than rejected.  Empty
+/// names and names containing control characters are invalid.
+fn copy_process_name(name_ptr: *const u8, name_len: usize) -> Result<ProcessName, SyscallError> {
+    if name_len == 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let read_len = name_len.min(MAX_PROCESS_NAME_BYTES);
+    let name_slice = UserSlice::new(name_ptr as *mut u8, read_len, false)
+        .map_err(|_| SyscallError::AddressFault)?;
+    let mut name_bytes = vec![0u8; read_len];
+    unsafe { name_slice.copy_from_user(&mut name_bytes) }
+        .map_err(|_| SyscallError::AddressFault)?;
+    let name = match core::str::from_utf8(&name_bytes) {
+        Ok(name) => name,
+        // Drop a character that the read limit split in two.
+        Err(error) if error.error_len().is_none() && read_len < name_len => {
+            core::str::from_utf8(&name_bytes[..error.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return Err(SyscallError::InvalidArgument),
+    }
+    .trim();
+    if name.is_empty()
+        || name
+            .bytes()
+            .any(|byte| byte == 0 || byte.is_ascii_control())
+    {
+        return Err(SyscallError::InvalidArgument);
+    }
+    Ok(ProcessName::new(name))
+}
+
 /// Copy an ELF image from the caller and start it in a new isolated process.
 pub(crate) fn syscall_spawn(
     image_ptr: *const u8,
@@ -285,51 +475,148 @@ pub(crate) fn syscall_spawn(
     name_ptr: *const u8,
     name_len: usize,
 ) -> SyscallResult {
-    if image_len == 0
-        || image_len > MAX_EXECUTABLE_BYTES
-        || name_len == 0
-        || name_len > MAX_PROCESS_NAME_BYTES
-    {
+    if image_len == 0 || image_len > MAX_EXECUTABLE_BYTES {
         return Err(SyscallError::InvalidArgument);
     }
+    let name = copy_process_name(name_ptr, name_len)?;
 
     let image_slice = UserSlice::new(image_ptr as *mut u8, image_len, false)
         .map_err(|_| SyscallError::AddressFault)?;
-    let name_slice = UserSlice::new(name_ptr as *mut u8, name_len, false)
-        .map_err(|_| SyscallError::AddressFault)?;
     let mut image = vec![0u8; image_len];
-    let mut name_bytes = vec![0u8; name_len];
     unsafe {
         image_slice
             .copy_from_user(&mut image)
             .map_err(|_| SyscallError::AddressFault)?;
-        name_slice
-            .copy_from_user(&mut name_bytes)
-            .map_err(|_| SyscallError::AddressFault)?;
     }
-    let name = core::str::from_utf8(&name_bytes)
-        .map_err(|_| SyscallError::InvalidArgument)?
-        .trim();
-    if name.is_empty()
-        || name
-            .bytes()
-            .any(|byte| byte == 0 || byte.is_ascii_control())
-    {
+
+    let caller = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let privileged = process::SCHEDULER
+        .with_process(caller, |p| p.privileged)
+        .ok_or(SyscallError::NoSuchProcess)?;
+    let pid = crate::loader::load_program(&image, &name).map_err(load_error)?;
+    process::SCHEDULER.with_process(pid, |p| p.privileged = privileged);
+    Ok(pid.0)
+}
+
+/// Rename the calling process; names longer than
+/// [`PROCESS_NAME_CAPACITY`](process::PROCESS_NAME_CAPACITY) are truncated.
+pub(crate) fn syscall_set_name(name_ptr: *const u8, name_len: usize) -> SyscallResult {
+    let name = copy_process_name(name_ptr, name_len)?;
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    process::SCHEDULER
+        .with_process(pid, |process| process.name = name)
+        .ok_or(SyscallError::NoSuchProcess)?;
+    Ok(0)
+}
+
+fn load_error(error: crate::loader::LoadError) -> SyscallError {
+    match error {
+        crate::loader::LoadError::OutOfMemory => SyscallError::OutOfMemory,
+        crate::loader::LoadError::FileNotFound => SyscallError::FileNotFound,
+        crate::loader::LoadError::InvalidFormat
+        | crate::loader::LoadError::NotExecutable
+        | crate::loader::LoadError::UnsupportedArchitecture
+        | crate::loader::LoadError::KernelAddress => SyscallError::InvalidArgument,
+        crate::loader::LoadError::MappingFailed
+        | crate::loader::LoadError::AddressAlreadyMapped => SyscallError::Io,
+    }
+}
+
+const MAX_EXEC_ARGS_BYTES: usize = 4096;
+
+/// Replace the calling process's image with the ELF executable at `path`.
+///
+/// `args` is a block of NUL-terminated strings that becomes `argv`; when it
+/// is empty, `argv` is just the path.  On success the syscall does not
+/// return to the old image: the caller resumes at the new entry point with
+/// `argc`, `argv`, an empty `envp` and an empty auxiliary vector on its
+/// stack.  Open files and handles are kept.
+pub(crate) fn syscall_exec(path: *const u8, args: *const u8, args_len: usize) -> SyscallResult {
+    let path = unsafe { super::interface::copy_user_string(path, super::fs::MAX_PATH_BYTES)? };
+    if args_len > MAX_EXEC_ARGS_BYTES {
         return Err(SyscallError::InvalidArgument);
     }
+    let mut args_block = vec![0u8; args_len];
+    if args_len > 0 {
+        let args_slice = UserSlice::new(args as *mut u8, args_len, false)
+            .map_err(|_| SyscallError::AddressFault)?;
+        unsafe { args_slice.copy_from_user(&mut args_block) }
+            .map_err(|_| SyscallError::AddressFault)?;
+    }
+    let argv: alloc::vec::Vec<&[u8]> = match args_block.strip_suffix(&[0]) {
+        Some(block) => block.split(|&byte| byte == 0).collect(),
+        None if args_block.is_empty() => vec![path.as_bytes()],
+        None => return Err(SyscallError::InvalidArgument),
+    };
+
+    let current_pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    let (is_user, old_page_table) = process::SCHEDULER
+        .with_process(current_pid, |p| (p.context.is_user, p.page_table_phys_addr))
+        .ok_or(SyscallError::NoSuchProcess)?;
+    // Like fork, exec needs a user return site to redirect.
+    if !is_user || crate::interrupts::syscall::current_syscall_frame().is_none() {
+        return Err(SyscallError::NotSupported);
+    }
+    // Threads run on this address space too; it cannot be torn down under them.
+    let shared = process::SCHEDULER.with_list(|list| {
+        list.iter()
+            .any(|(id, p)| *id != current_pid && p.page_table_phys_addr == old_page_table)
+    });
+    if shared {
+        return Err(SyscallError::Busy);
+    }
 
-    // Process names are currently stored for the lifetime of the kernel.
-    // The process table is bounded, so leaking this short label is bounded too.
-    let process_name: &'static str = Box::leak(String::from(name).into_boxed_str());
-    crate::loader::load_program(&image, process_name)
-        .map(|pid| pid.0)
-        .map_err(|error| match error {
-            crate::loader::LoadError::OutOfMemory => SyscallError::OutOfMemory,
-            crate::loader::LoadError::FileNotFound => SyscallError::FileNotFound,
-            crate::loader::LoadError::InvalidFormat
-            | crate::loader::LoadError::NotExecutable
-            | crate::loader::LoadError::UnsupportedArchitecture => SyscallError::InvalidArgument,
-            crate::loader::LoadError::MappingFailed
-            | crate::loader::LoadError::AddressAlreadyMapped => SyscallError::Io,
+    let image = crate::fs::read_entire_file(&path)?;
+    let mut loaded = crate::loader::exec_image(&image, &argv).map_err(load_error)?;
+    let pml4_frame = loaded
+        .page_table
+        .pml4_frame()
+        .ok_or(SyscallError::OutOfMemory)?;
+    let vdso = with_frame_allocator(|allocator| {
+        crate::vdso::create_vdso_page(&mut loaded.page_table, allocator, current_pid.0)
+    });
+    let vdso = match vdso {
+        Ok(vdso) => vdso,
+        Err(_) => {
+            drop(loaded);
+            crate::memory_management::deallocate_process_page_table(pml4_frame);
+            return Err(SyscallError::OutOfMemory);
+        }
+    };
+
+    let name = ProcessName::new(path.rsplit('/').next().unwrap_or(&path));
+    let (entry, stack_pointer) = (loaded.entry, loaded.stack_pointer);
+    let old = process::SCHEDULER
+        .with_process(current_pid, |p| {
+            p.name = name;
+            p.entry_point = entry;
+            p.user_stack = stack_pointer;
+            p.page_table_phys_addr = pml4_frame.start_address();
+            p.vdso_page = Some(vdso);
+            // The new image starts with an empty heap and the loader's stack.
+            let mut memory = ProcessMemoryManagerImpl::new(current_pid.0 as usize);
+            memory.adopt_stack(
+                (crate::loader::EXEC_STACK_TOP - crate::loader::EXEC_STACK_SIZE) as usize,
+                crate::loader::EXEC_STACK_SIZE as usize,
+            );
+            *p.resources.memory.get_mut() = Some(memory);
+            p.page_table.replace(Box::new(loaded.page_table))
         })
+        .ok_or(SyscallError::NoSuchProcess)?;
+    // We are on the kernel page table, so the old one can go right away.
+    if let Some(old_pml4) = old.and_then(|page_table| page_table.pml4_frame()) {
+        crate::memory_management::deallocate_process_page_table(old_pml4);
+    }
+    // Shared-memory attachments went with the old address space.
+    super::shm::detach_process(current_pid);
+
+    let redirected = unsafe {
+        crate::interrupts::syscall::redirect_syscall_return(
+            entry.as_u64(),
+            stack_pointer.as_u64(),
+            pml4_frame.start_address().as_u64(),
+        )
+    };
+    debug_assert!(redirected);
+    Ok(0)
 }
@@ -0,0 +1,272 @@
+//! Counting semaphores.
+//!
+//! A semaphore is a count in a global table, named by the id `SemCreate`
+//! returns, with a queue of the processes blocked in `SemWait` on it.  Any
+//! process that knows the id may use it, and it lives until `SemDestroy`.
+//!
+//! `SemPost` with a process waiting hands its unit straight to the oldest
+//! waiter instead of adding it to the count, so a process that calls
+//! `SemWait` in the meantime cannot take it first.  Each woken waiter finds
+//! why it woke in the table: it acquired the semaphore, or the semaphore
+//! was destroyed under it.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use super::interface::{SyscallError, SyscallResult};
+use crate::process::{self, ProcessId, ProcessState};
+
+struct Semaphore {
+    count: u32,
+    /// Blocked waiters, oldest first.
+    waiters: VecDeque<ProcessId>,
+}
+
+/// Why a waiter was woken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Wakeup {
+    /// A post handed it a unit of semaphore `id`.
+    Acquired(u64),
+    /// The semaphore went away while it waited.
+    Destroyed,
+}
+
+pub(crate) struct SemTable {
+    next_id: u64,
+    semaphores: BTreeMap<u64, Semaphore>,
+    /// Wakeups not yet collected by their waiter, by pid.
+    woken: BTreeMap<ProcessId, Wakeup>,
+}
+
+impl SemTable {
+    pub const fn new() -> Self {
+        Self {
+            next_id: 1,
+            semaphores: BTreeMap::new(),
+            woken: BTreeMap::new(),
+        }
+    }
+
+    /// Create a semaphore holding `initial` units and return its id.
+    pub fn create(&mut self, initial: u32) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.semaphores.insert(
+            id,
+            Semaphore {
+                count: initial,
+                waiters: VecDeque::new(),
+            },
+        );
+        id
+    }
+
+    /// Take a unit of `id` for `pid` if one is free, or else queue `pid`.
+    /// Returns whether it got the unit.
+    pub fn wait(&mut self, id: u64, pid: ProcessId) -> Result<bool, SyscallError> {
+        let semaphore = self
+            .semaphores
+            .get_mut(&id)
+            .ok_or(SyscallError::InvalidArgument)?;
+        if semaphore.count > 0 {
+            semaphore.count -= 1;
+            return Ok(true);
+        }
+        semaphore.waiters.push_back(pid);
+        Ok(false)
+    }
+
+    /// Release a unit of `id`: to the oldest waiter for which `is_alive`
+    /// holds, returned for the caller to unblock, or else to the count.
+    /// Fails with [`SyscallError::Overflow`] if the count is at `u32::MAX`.
+    pub fn post(
+        &mut self,
+        id: u64,
+        is_alive: impl Fn(ProcessId) -> bool,
+    ) -> Result<Option<ProcessId>, SyscallError> {
+        let semaphore = self
+            .semaphores
+            .get_mut(&id)
+            .ok_or(SyscallError::InvalidArgument)?;
+        while let Some(pid) = semaphore.waiters.pop_front() {
+            if is_alive(pid) {
+                self.woken.insert(pid, Wakeup::Acquired(id));
+                return Ok(Some(pid));
+            }
+        }
+        semaphore.count = semaphore
+            .count
+            .checked_add(1)
+            .ok_or(SyscallError::Overflow)?;
+        Ok(None)
+    }
+
+    /// Remove `id` and return its waiters, each now due to fail.
+    pub fn destroy(&mut self, id: u64) -> Result<Vec<ProcessId>, SyscallError> {
+        let semaphore = self
+            .semaphores
+            .remove(&id)
+            .ok_or(SyscallError::InvalidArgument)?;
+        let waiters: Vec<ProcessId> = semaphore.waiters.into();
+        for &pid in &waiters {
+            self.woken.insert(pid, Wakeup::Destroyed);
+        }
+        Ok(waiters)
+    }
+
+    /// Collect the wakeup of `pid`, or `None` if it is still waiting.
+    pub fn take_wakeup(&mut self, pid: ProcessId) -> Option<Wakeup> {
+        self.woken.remove(&pid)
+    }
+
+    /// Forget `pid`, which exited.  A unit handed to it that it never
+    /// collected is posted again; returns the waiter that then gets it.
+    pub fn release_process(&mut self, pid: ProcessId) -> Option<ProcessId> {
+        for semaphore in self.semaphores.values_mut() {
+            semaphore.waiters.retain(|&waiter| waiter != pid);
+        }
+        match self.woken.remove(&pid) {
+            Some(Wakeup::Acquired(id)) => self.post(id, |_| true).ok().flatten(),
+            _ => None,
+        }
+    }
+}
+
+static SEMAPHORES: Mutex<SemTable> = Mutex::new(SemTable::new());
+
+/// Drop everything an exiting process has pending on semaphores.
+pub(crate) fn release_process(pid: ProcessId) {
+    let next = SEMAPHORES.lock().release_process(pid);
+    if let Some(next) = next {
+        process::unblock_process(next);
+    }
+}
+
+/// Create a semaphore holding `initial` units and return its id.
+pub(crate) fn syscall_sem_create(initial: u64) -> SyscallResult {
+    let initial = u32::try_from(initial).map_err(|_| SyscallError::InvalidArgument)?;
+    Ok(SEMAPHORES.lock().create(initial))
+}
+
+/// Block until a unit of semaphore `id` is free and take it.  Fails with
+/// [`SyscallError::InvalidArgument`] if there is no such semaphore, or if
+/// it is destroyed while the caller waits.
+pub(crate) fn syscall_sem_wait(id: u64) -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    if SEMAPHORES.lock().wait(id, pid)? {
+        return Ok(0);
+    }
+    loop {
+        // Nothing can run between releasing the lock and blocking: the
+        // kernel is single-core and only switches tasks at explicit
+        // scheduling points.
+        process::block_current();
+        match SEMAPHORES.lock().take_wakeup(pid) {
+            Some(Wakeup::Acquired(_)) => return Ok(0),
+            Some(Wakeup::Destroyed) => return Err(SyscallError::InvalidArgument),
+            // Woken by something else; still queued.
+            None => {}
+        }
+    }
+}
+
+/// Release a unit of semaphore `id`, waking its oldest waiter if it has
+/// one.  Returns the number of processes woken.
+pub(crate) fn syscall_sem_post(id: u64) -> SyscallResult {
+    let woken = SEMAPHORES.lock().post(id, |waiter| {
+        process::SCHEDULER
+            .with_process(waiter, |p| {
+                !matches!(p.state, ProcessState::Zombie | ProcessState::Terminated)
+            })
+            .unwrap_or(false)
+    })?;
+    match woken {
+        Some(waiter) => {
+            process::unblock_process(waiter);
+            Ok(1)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Destroy semaphore `id`; every process waiting on it fails its wait.
+/// Returns the number of waiters woken.
+pub(crate) fn syscall_sem_destroy(id: u64) -> SyscallResult {
+    let waiters = SEMAPHORES.lock().destroy(id)?;
+    for &waiter in &waiters {
+        process::unblock_process(waiter);
+    }
+    Ok(waiters.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_go_to_the_oldest_live_waiter_before_the_count() {
+        let mut table = SemTable::new();
+        let id = table.create(1);
+        assert_eq!(table.wait(id, ProcessId(1)), Ok(true));
+        assert_eq!(table.wait(id, ProcessId(2)), Ok(false));
+        assert_eq!(table.wait(id, ProcessId(3)), Ok(false));
+        assert_eq!(table.wait(id, ProcessId(4)), Ok(false));
+
+        // pid 2 was killed while waiting.
+        let alive = |pid| pid != ProcessId(2);
+        assert_eq!(table.post(id, alive), Ok(Some(ProcessId(3))));
+        assert_eq!(table.take_wakeup(ProcessId(3)), Some(Wakeup::Acquired(id)));
+        assert_eq!(table.take_wakeup(ProcessId(3)), None);
+        assert_eq!(table.post(id, alive), Ok(Some(ProcessId(4))));
+        assert_eq!(table.post(id, alive), Ok(None));
+        // The unit went to the count, so the next wait takes it.
+        assert_eq!(table.wait(id, ProcessId(5)), Ok(true));
+        assert_eq!(
+            table.wait(7, ProcessId(5)),
+            Err(SyscallError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn the_count_does_not_wrap() {
+        let mut table = SemTable::new();
+        let id = table.create(u32::MAX - 1);
+        assert_eq!(table.post(id, |_| true), Ok(None));
+        assert_eq!(table.post(id, |_| true), Err(SyscallError::Overflow));
+        assert_eq!(table.semaphores[&id].count, u32::MAX);
+    }
+
+    #[test]
+    fn destroying_fails_every_waiter() {
+        let mut table = SemTable::new();
+        let id = table.create(0);
+        table.wait(id, ProcessId(1)).unwrap();
+        table.wait(id, ProcessId(2)).unwrap();
+        assert_eq!(
+            table.destroy(id),
+            Ok(alloc::vec![ProcessId(1), ProcessId(2)])
+        );
+        assert_eq!(table.take_wakeup(ProcessId(1)), Some(Wakeup::Destroyed));
+        assert_eq!(table.take_wakeup(ProcessId(2)), Some(Wakeup::Destroyed));
+        assert_eq!(table.destroy(id), Err(SyscallError::InvalidArgument));
+        assert_eq!(table.post(id, |_| true), Err(SyscallError::InvalidArgument));
+    }
+
+    #[test]
+    fn a_unit_left_by_an_exiting_waiter_is_passed_on() {
+        let mut table = SemTable::new();
+        let id = table.create(0);
+        table.wait(id, ProcessId(1)).unwrap();
+        table.wait(id, ProcessId(2)).unwrap();
+        table.wait(id, ProcessId(3)).unwrap();
+        assert_eq!(table.post(id, |_| true), Ok(Some(ProcessId(1))));
+
+        // pid 3 leaves the queue; pid 1 exits before collecting its unit.
+        assert_eq!(table.release_process(ProcessId(3)), None);
+        assert_eq!(table.release_process(ProcessId(1)), Some(ProcessId(2)));
+        assert_eq!(table.take_wakeup(ProcessId(2)), Some(Wakeup::Acquired(id)));
+        assert_eq!(table.semaphores[&id].count, 0);
+        assert!(table.semaphores[&id].waiters.is_empty());
+    }
+}
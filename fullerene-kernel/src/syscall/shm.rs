@@ -0,0 +1,342 @@
+//! Shared-memory segments.
+//!
+//! A segment is a fixed set of zeroed frames that any process can map into
+//! its mapping region with `ShmAttach`, so processes can exchange data
+//! without copying it through the kernel.  The segment table holds one
+//! reference on every frame and each attachment holds another, taken with
+//! the frame allocator's reference counts.  The table's reference is dropped,
+//! and the frames with it, once the last attacher detaches or exits; a
+//! segment nobody has attached yet lives as long as its creator.
+//!
+//! Attached pages carry [`SHARED_FLAG`] so a fork leaves them out of the
+//! child instead of sharing them copy-on-write.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::PhysAddr;
+use x86_64::structures::paging::{FrameAllocator, PhysFrame};
+
+use fullerene_abi::memory_protection::{READ as PROT_READ, WRITE as PROT_WRITE};
+use petroleum::page_table::constants::with_frame_allocator;
+use petroleum::page_table::types::PageTableHelper;
+
+use super::interface::{SyscallError, SyscallResult};
+use super::memory::{
+    MAX_MAPPING_SIZE, free_user_frame, unmap_user_pages, user_page_flags, with_current_memory,
+};
+use crate::memory_management::SHARED_FLAG;
+use crate::process;
+
+struct Segment {
+    /// Physical address of every page, in mapping order.
+    frames: Vec<u64>,
+    creator: u64,
+    /// Base address of the mapping in each attached process, by pid.
+    attachments: BTreeMap<u64, usize>,
+}
+
+/// A finished attachment: where it was mapped, and the frames whose table
+/// reference must be dropped because the segment went away with it.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Detached {
+    pub base: usize,
+    pub pages: usize,
+    pub freed: Option<Vec<u64>>,
+}
+
+/// Bookkeeping for every shared-memory segment.  Frame references are
+/// taken and dropped by the callers; the table only says which ones.
+pub(crate) struct ShmTable {
+    next_id: u64,
+    segments: BTreeMap<u64, Segment>,
+}
+
+impl ShmTable {
+    pub const fn new() -> Self {
+        Self {
+            next_id: 1,
+            segments: BTreeMap::new(),
+        }
+    }
+
+    /// Register a segment backed by `frames` and return its id.
+    pub fn create(&mut self, creator: u64, frames: Vec<u64>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.segments.insert(
+            id,
+            Segment {
+                frames,
+                creator,
+                attachments: BTreeMap::new(),
+            },
+        );
+        id
+    }
+
+    /// The frames of segment `id`, or [`SyscallError::InvalidArgument`] if
+    /// there is no such segment.
+    pub fn frames(&self, id: u64) -> Result<&[u64], SyscallError> {
+        self.segments
+            .get(&id)
+            .map(|segment| segment.frames.as_slice())
+            .ok_or(SyscallError::InvalidArgument)
+    }
+
+    /// Record that `pid` mapped segment `id` at `base`.
+    ///
+    /// A process attaches a segment at most once.
+    pub fn attach(&mut self, id: u64, pid: u64, base: usize) -> Result<(), SyscallError> {
+        let segment = self
+            .segments
+            .get_mut(&id)
+            .ok_or(SyscallError::InvalidArgument)?;
+        if segment.attachments.contains_key(&pid) {
+            return Err(SyscallError::AlreadyExists);
+        }
+        segment.attachments.insert(pid, base);
+        Ok(())
+    }
+
+    /// Forget `pid`'s attachment to segment `id`, removing the segment when
+    /// it was the last one.
+    pub fn detach(&mut self, id: u64, pid: u64) -> Result<Detached, SyscallError> {
+        let segment = self
+            .segments
+            .get_mut(&id)
+            .ok_or(SyscallError::InvalidArgument)?;
+        let base = segment
+            .attachments
+            .remove(&pid)
+            .ok_or(SyscallError::InvalidArgument)?;
+        let pages = segment.frames.len();
+        let freed = if segment.attachments.is_empty() {
+            self.segments.remove(&id).map(|segment| segment.frames)
+        } else {
+            None
+        };
+        Ok(Detached { base, pages, freed })
+    }
+
+    /// Drop every attachment of `pid`.  Returns one entry per frame
+    /// reference to drop.
+    pub fn detach_process(&mut self, pid: u64) -> Vec<u64> {
+        let attached: Vec<u64> = self
+            .segments
+            .iter()
+            .filter(|(_, segment)| segment.attachments.contains_key(&pid))
+            .map(|(&id, _)| id)
+            .collect();
+        let mut references = Vec::new();
+        for id in attached {
+            let Ok(frames) = self.frames(id).map(<[u64]>::to_vec) else {
+                continue;
+            };
+            references.extend(frames);
+            if let Ok(Detached {
+                freed: Some(freed), ..
+            }) = self.detach(id, pid)
+            {
+                references.extend(freed);
+            }
+        }
+        references
+    }
+
+    /// Drop everything `pid` holds: its attachments, and the segments it
+    /// created that nobody has attached.  Returns one entry per frame
+    /// reference to drop, attachments first.
+    pub fn release_process(&mut self, pid: u64) -> Vec<u64> {
+        let mut references = self.detach_process(pid);
+        let orphaned: Vec<u64> = self
+            .segments
+            .iter()
+            .filter(|(_, segment)| segment.creator == pid && segment.attachments.is_empty())
+            .map(|(&id, _)| id)
+            .collect();
+        for id in orphaned {
+            if let Some(segment) = self.segments.remove(&id) {
+                references.extend(segment.frames);
+            }
+        }
+        references
+    }
+}
+
+static SEGMENTS: Mutex<ShmTable> = Mutex::new(ShmTable::new());
+
+fn drop_frame_references(frames: &[u64]) {
+    for &phys in frames {
+        free_user_frame(PhysFrame::containing_address(PhysAddr::new(phys)));
+    }
+}
+
+/// Drop the segment attachments of a process replacing its image.  The old
+/// page table is gone, so the mappings themselves are not touched.
+pub(crate) fn detach_process(pid: process::ProcessId) {
+    let references = SEGMENTS.lock().detach_process(pid.0);
+    drop_frame_references(&references);
+}
+
+/// Drop the attachments of an exiting process, and the segments it created
+/// that nobody attached.
+pub(crate) fn release_process(pid: process::ProcessId) {
+    let references = SEGMENTS.lock().release_process(pid.0);
+    drop_frame_references(&references);
+}
+
+/// Create a segment of `size` bytes, rounded up to whole pages, and return
+/// its id.  The segment starts out zeroed and unattached.
+pub(crate) fn syscall_shm_create(size: u64) -> SyscallResult {
+    let size = size as usize;
+    if size == 0 || size > MAX_MAPPING_SIZE {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+
+    let pages = size.div_ceil(4096);
+    let mut frames = Vec::with_capacity(pages);
+    for _ in 0..pages {
+        let frame = with_frame_allocator(|allocator| allocator.allocate_frame());
+        let Some(frame) = frame else {
+            drop_frame_references(&frames);
+            return Err(SyscallError::OutOfMemory);
+        };
+        let phys = frame.start_address().as_u64();
+        let frame_vaddr = petroleum::common::memory::physical_to_virtual(phys as usize);
+        unsafe { core::ptr::write_bytes(frame_vaddr as *mut u8, 0, 4096) };
+        frames.push(phys);
+    }
+    Ok(SEGMENTS.lock().create(pid.0, frames))
+}
+
+/// Map segment `id` read-write into the caller's mapping region and return
+/// the address.
+pub(crate) fn syscall_shm_attach(id: u64) -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    with_current_memory(|memory, page_table| {
+        let page_table = page_table.ok_or(SyscallError::NotSupported)?;
+        let mut segments = SEGMENTS.lock();
+        let frames = segments.frames(id)?.to_vec();
+        let base = memory.reserve_mmap(frames.len())?;
+        let flags = user_page_flags(PROT_READ | PROT_WRITE) | SHARED_FLAG;
+
+        for (index, &phys) in frames.iter().enumerate() {
+            let vaddr = base + index * 4096;
+            let referenced = with_frame_allocator(|allocator| allocator.inc_ref(phys))
+                .map_err(SyscallError::from);
+            let mapped = referenced.and_then(|_| {
+                page_table
+                    .map_page(vaddr, phys as usize, flags, unsafe {
+                        petroleum::page_table::constants::get_frame_allocator_mut()
+                    })
+                    .map_err(|_| {
+                        drop_frame_references(&[phys]);
+                        SyscallError::OutOfMemory
+                    })
+            });
+            if let Err(error) = mapped {
+                unmap_user_pages(page_table, base, vaddr);
+                memory.release_mmap(base, frames.len())?;
+                return Err(error);
+            }
+        }
+
+        if let Err(error) = segments.attach(id, pid.0, base) {
+            unmap_user_pages(page_table, base, base + frames.len() * 4096);
+            memory.release_mmap(base, frames.len())?;
+            return Err(error);
+        }
+        Ok(base as u64)
+    })
+}
+
+/// Unmap segment `id` from the caller, freeing the segment once nobody is
+/// attached to it any more.
+pub(crate) fn syscall_shm_detach(id: u64) -> SyscallResult {
+    let pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
+    with_current_memory(|memory, page_table| {
+        let page_table = page_table.ok_or(SyscallError::NotSupported)?;
+        let detached = SEGMENTS.lock().detach(id, pid.0)?;
+        unmap_user_pages(
+            page_table,
+            detached.base,
+            detached.base + detached.pages * 4096,
+        );
+        memory.release_mmap(detached.base, detached.pages)?;
+        if let Some(frames) = detached.freed {
+            drop_frame_references(&frames);
+        }
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attaching_an_unknown_segment_fails() {
+        let mut table = ShmTable::new();
+        assert_eq!(
+            table.attach(7, 1, 0x1000),
+            Err(SyscallError::InvalidArgument)
+        );
+        assert!(table.frames(7).is_err());
+        assert_eq!(table.detach(7, 1), Err(SyscallError::InvalidArgument));
+    }
+
+    #[test]
+    fn last_detach_frees_the_segment() {
+        let mut table = ShmTable::new();
+        let id = table.create(1, alloc::vec![0x10_000, 0x11_000]);
+        table.attach(id, 1, 0xA000).unwrap();
+        table.attach(id, 2, 0xB000).unwrap();
+        assert_eq!(
+            table.attach(id, 2, 0xC000),
+            Err(SyscallError::AlreadyExists)
+        );
+
+        assert_eq!(
+            table.detach(id, 1),
+            Ok(Detached {
+                base: 0xA000,
+                pages: 2,
+                freed: None
+            })
+        );
+        assert_eq!(table.detach(id, 1), Err(SyscallError::InvalidArgument));
+        assert_eq!(
+            table.detach(id, 2),
+            Ok(Detached {
+                base: 0xB000,
+                pages: 2,
+                freed: Some(alloc::vec![0x10_000, 0x11_000])
+            })
+        );
+        assert!(table.frames(id).is_err());
+    }
+
+    #[test]
+    fn exiting_processes_release_their_references() {
+        let mut table = ShmTable::new();
+        let shared = table.create(1, alloc::vec![0x10_000]);
+        let unattached = table.create(1, alloc::vec![0x20_000]);
+        let foreign = table.create(2, alloc::vec![0x30_000]);
+        table.attach(shared, 1, 0xA000).unwrap();
+        table.attach(shared, 2, 0xA000).unwrap();
+
+        // The attachment's reference, then the unattached segment's.
+        assert_eq!(table.release_process(1), alloc::vec![0x10_000, 0x20_000]);
+        assert!(table.frames(unattached).is_err());
+        assert!(table.frames(foreign).is_ok());
+
+        // The last attacher drops its own reference and the table's.
+        assert_eq!(
+            table.release_process(2),
+            alloc::vec![0x10_000, 0x10_000, 0x30_000]
+        );
+        assert!(table.frames(shared).is_err());
+    }
+}
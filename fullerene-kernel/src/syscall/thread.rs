@@ -9,7 +9,7 @@ use crate::map_handle;
 use super::interface::{SyscallError, SyscallResult};
 use super::process::{alloc_handle, alloc_kernel_stack, free_kernel_stack, with_handle_mut};
 use super::types::*;
-use crate::process::{self, Process, ProcessState};
+use crate::process::{self, Process, ProcessName, ProcessState};
 
 pub(crate) fn syscall_create_thread(entry: u64, stack: u64, _flags: u64) -> SyscallResult {
     let entry_point = VirtAddr::try_new(entry).map_err(|_| SyscallError::InvalidArgument)?;
@@ -25,9 +25,16 @@ pub(crate) fn syscall_create_thread(entry: u64, stack: u64, _flags: u64) -> Sysc
 
     let current_pid = process::current_pid().ok_or(SyscallError::NoSuchProcess)?;
 
-    let (parent_pt_phys, parent_context) = {
+    let (parent_pt_phys, parent_context, parent_priority, parent_privileged) = {
         crate::process::SCHEDULER
-            .with_process(current_pid, |p| (p.page_table_phys_addr, p.context.clone()))
+            .with_process(current_pid, |p| {
+                (
+                    p.page_table_phys_addr,
+                    p.context.clone(),
+                    p.priority,
+                    p.privileged,
+                )
+            })
             .ok_or(SyscallError::NoSuchProcess)?
     };
 
@@ -37,8 +44,13 @@ pub(crate) fn syscall_create_thread(entry: u64, stack: u64, _flags: u64) -> Sysc
 
     let mut thread_process = Process {
         id: child_pid,
-        name: "thread",
+        name: ProcessName::new("thread"),
         state: ProcessState::Ready,
+        wake_tick: None,
+        priority: parent_priority,
+        ready_since: crate::scheduler::get_system_tick(),
+        cpu_time_us: 0,
+        pending_signals: 0,
         context: parent_context.clone(),
         page_table_phys_addr: parent_pt_phys,
         page_table: None,
@@ -46,6 +58,7 @@ pub(crate) fn syscall_create_thread(entry: u64, stack: u64, _flags: u64) -> Sysc
         user_stack,
         entry_point,
         is_user: true,
+        privileged: parent_privileged,
         task_data: 0,
         exit_code: None,
         parent_id: Some(current_pid),
@@ -57,6 +70,8 @@ pub(crate) fn syscall_create_thread(entry: u64, stack: u64, _flags: u64) -> Sysc
     thread_process.context.regs[0] = 0;
     thread_process.context.regs[7] = thread_process.user_stack.as_u64();
     thread_process.context.rip = entry;
+    thread_process.context.user_gs_base = crate::percpu::user_gs_base();
+    thread_process.context.syscall_frame = 0;
 
     let thread_box = Box::new(thread_process);
     crate::process::SCHEDULER.add(thread_box).map_err(|_| {
@@ -152,5 +167,8 @@ pub(crate) fn syscall_exit_thread(exit_code: i32) -> SyscallResult {
     }
 
     process::terminate_process(pid, exit_code);
+    // The exit code was delivered through the thread handle; don't leave a
+    // zombie behind for `waitpid`.
+    process::SCHEDULER.with_process(pid, |p| p.state = ProcessState::Terminated);
     Ok(0)
 }
@@ -75,7 +75,7 @@ pub(crate) fn syscall_clock_gettime(clock_id: u64, timespec_buf: *mut u8) -> Sys
             let us = uptime_us();
             (us / 1_000_000, ((us % 1_000_000) * 1000))
         }
-        1 => (0, 0),
+        1 => (unix_time()?, 0),
         _ => return Err(SyscallError::InvalidArgument),
     };
 
@@ -128,6 +128,51 @@ pub(crate) fn syscall_sleep(us: u64) -> SyscallResult {
     }
 }
 
+/// Upper bound for a single tick sleep: one hour of 1 ms ticks.
+const MAX_SLEEP_TICKS: u64 = 60 * 60 * 1000;
+
+/// Block the caller for `ticks` timer ticks.
+///
+/// The argument is interpreted as signed so that a negative duration from a
+/// C-style caller is rejected instead of turning into a near-infinite sleep.
+/// Absurdly long requests are clamped to [`MAX_SLEEP_TICKS`].
+pub(crate) fn syscall_sleep_ticks(ticks: u64) -> SyscallResult {
+    if (ticks as i64) < 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if ticks == 0 {
+        process::yield_current();
+        return Ok(0);
+    }
+    let wake_tick = crate::scheduler::get_system_tick().saturating_add(ticks.min(MAX_SLEEP_TICKS));
+    process::SCHEDULER.sleep_current_until(wake_tick);
+    Ok(0)
+}
+
+/// Wall-clock seconds since the Unix epoch, read from the CMOS RTC.
+///
+/// The RTC is assumed to hold UTC; see [`petroleum::hardware::rtc`].
+fn unix_time() -> Result<u64, SyscallError> {
+    petroleum::hardware::rtc::read_datetime()
+        .unix_timestamp()
+        .ok_or(SyscallError::Io)
+}
+
+/// Return the current time as seconds since 1970-01-01 00:00:00 UTC.
+///
+/// Fails with [`SyscallError::Io`] if the RTC reports an invalid date.
+pub(crate) fn syscall_gettimeofday() -> SyscallResult {
+    unix_time()
+}
+
+/// Nanoseconds from the TSC, for timing intervals shorter than a tick.
+///
+/// Counts from the last TSC reset rather than from boot; only differences
+/// between two readings are meaningful.
+pub(crate) fn syscall_clock_monotonic() -> SyscallResult {
+    Ok(petroleum::hardware::tsc::monotonic_nanos())
+}
+
 pub(crate) fn syscall_uptime(buf: *mut u8) -> SyscallResult {
     if buf.is_null() {
         return Err(SyscallError::InvalidArgument);
@@ -121,7 +121,7 @@ pub enum KernelObject {
     Window(WindowState),
     Device(DeviceState),
     Channel(ChannelState),
-    Pipe(PipeState),
+    Pipe(super::pipe::PipeEnd),
     Timer(TimerState),
 }
 
@@ -163,11 +163,6 @@ pub struct ChannelState {
     pub inner: Arc<Mutex<ChannelInner>>,
 }
 
-pub struct PipeState {
-    pub buffer: Arc<Mutex<Vec<u8>>>,
-    pub is_read_end: bool,
-}
-
 pub struct TimerState {
     pub deadline_ns: u64,
     pub event_handle: Handle,
@@ -1,3 +1,40 @@
+//! Copying syscall buffers between user space and the kernel.
+//!
+//! Handlers take a user pointer and length; nothing is dereferenced until
+//! every page of the range has been checked present and `USER_ACCESSIBLE`
+//! (and writable for a destination) in the current page table.
+
+use alloc::vec::Vec;
+
+use super::interface::{SyscallError, native_user_copy_error};
+use crate::user_memory;
+
+/// Copy `len` bytes at `ptr` in the calling process into a kernel buffer.
+///
+/// A range with any page unmapped or kernel-only, including one that runs
+/// off the end of its mapping, fails as a whole with `InvalidArgument`
+/// rather than yielding a partial copy.
+///
+/// # Safety
+///
+/// The current process address space must remain stable while the buffer is
+/// validated and copied.
+pub(crate) unsafe fn copy_from_user(ptr: *const u8, len: usize) -> Result<Vec<u8>, SyscallError> {
+    unsafe { user_memory::copy_bytes_from_user(ptr, len) }.map_err(native_user_copy_error)
+}
+
+/// Copy `bytes` to `ptr` in the calling process, after checking that every
+/// page of the destination is mapped writable and `USER_ACCESSIBLE`;
+/// `InvalidArgument` otherwise.
+///
+/// # Safety
+///
+/// The current process address space must remain stable while the
+/// destination is validated and written.
+pub(crate) unsafe fn copy_to_user(ptr: *mut u8, bytes: &[u8]) -> Result<(), SyscallError> {
+    unsafe { user_memory::copy_bytes_to_user(ptr, bytes) }.map_err(native_user_copy_error)
+}
+
 /// Syscall helper macros for user space (would be in user-space library)
 #[cfg(feature = "user_space")]
 pub mod user {
@@ -68,3 +105,28 @@ pub mod user {
         unsafe { syscall(SyscallNumber::GetPid, 0, 0, 0, 0, 0, 0) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_copies_reject_null_and_kernel_buffers() {
+        assert_eq!(
+            unsafe { copy_from_user(core::ptr::null(), 8) },
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            unsafe { copy_to_user(core::ptr::null_mut(), b"data") },
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            unsafe { copy_from_user(0xFFFF_8000_0000_0000 as *const u8, 8) },
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            unsafe { copy_from_user(core::ptr::null(), 0) },
+            Ok(Vec::new())
+        );
+    }
+}
@@ -96,7 +96,7 @@ impl TaskHandle {
     pub fn join(self) {
         loop {
             let terminated = process::SCHEDULER.with_process(ProcessId(self.pid), |p| {
-                matches!(p.state, ProcessState::Terminated)
+                matches!(p.state, ProcessState::Zombie | ProcessState::Terminated)
             });
             if terminated.unwrap_or(true) {
                 return;
@@ -183,7 +183,7 @@ pub fn block_on<F: Future>(mut future: F) -> F::Output {
 #[derive(Debug, Clone)]
 pub struct TrackedTask {
     pub pid: u64,
-    pub name: &'static str,
+    pub name: crate::process::ProcessName,
     pub state: &'static str,
     pub is_user: bool,
 }
@@ -201,10 +201,10 @@ impl TaskManager {
     }
 
     /// Register a task in the monitor.
-    pub fn register(&self, pid: u64, name: &'static str, is_user: bool) {
+    pub fn register(&self, pid: u64, name: &str, is_user: bool) {
         self.entries.lock().push(TrackedTask {
             pid,
-            name,
+            name: crate::process::ProcessName::new(name),
             state: "ready",
             is_user,
         });
@@ -229,12 +229,25 @@ impl TaskManager {
 
     /// Get a formatted task list for the shell.
     pub fn format_task_list(&self) -> alloc::string::String {
-        let entries = self.entries.lock();
-        let mut out = alloc::string::String::from("PID   NAME             STATE     TYPE\n");
-        out.push_str("----  ----------------  --------  ----\n");
+        // Snapshot first: priorities come from the scheduler, whose lock is
+        // taken before ours in `init_task_manager`.
+        let entries = self.snapshot();
+        let mut out = alloc::string::String::from("PID   NAME             STATE     PRIO  TYPE\n");
+        out.push_str("----  ----------------  --------  ----  ----\n");
         for e in entries.iter() {
             let ttype = if e.is_user { "user" } else { "kern" };
-            let line = alloc::format!("{:<4}  {:<16}  {:<8}  {}\n", e.pid, e.name, e.state, ttype);
+            let prio = crate::scheduler::get_process_priority(crate::process::ProcessId(e.pid))
+                .map_or(alloc::string::String::from("-"), |p| {
+                    alloc::format!("{}", p)
+                });
+            let line = alloc::format!(
+                "{:<4}  {:<16}  {:<8}  {:<4}  {}\n",
+                e.pid,
+                e.name,
+                e.state,
+                prio,
+                ttype
+            );
             out.push_str(&line);
         }
         out
@@ -249,7 +262,7 @@ pub static TASK_MANAGER: TaskManager = TaskManager::new();
 pub fn init_task_manager() {
     crate::process::SCHEDULER.with_list(|list| {
         for (_, proc) in list.iter() {
-            TASK_MANAGER.register(proc.id.0, proc.name, proc.is_user);
+            TASK_MANAGER.register(proc.id.0, &proc.name, proc.is_user);
         }
     });
 }
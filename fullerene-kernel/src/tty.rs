@@ -0,0 +1,181 @@
+//! Line discipline for console input read through fd 0.
+//!
+//! Bytes from the PS/2 keyboard and COM1 are collected into a line, echoed
+//! to the serial console and edited with backspace until Enter hands the
+//! line to a reader.  Ctrl-D hands over a partial line without its newline,
+//! or reports end of file when the line is empty.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::syscall::interface::SyscallError;
+
+const BACKSPACE: u8 = 0x08;
+const END_OF_FILE: u8 = 0x04;
+/// Longest line kept while editing; further bytes are dropped unechoed.
+const MAX_LINE_BYTES: usize = 4096;
+
+static CONSOLE: Mutex<LineDiscipline> = Mutex::new(LineDiscipline::new());
+
+/// Line editing state for one input stream.
+pub struct LineDiscipline {
+    /// The line being edited.
+    line: Vec<u8>,
+    /// Completed input not yet read.
+    ready: VecDeque<u8>,
+    /// Ctrl-D on an empty line, reported to the next reader as a 0-byte
+    /// read once `ready` is drained.
+    eof: bool,
+}
+
+impl LineDiscipline {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            ready: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    /// Process one input byte, passing anything to echo to `echo`.
+    pub fn feed(&mut self, byte: u8, echo: &mut impl FnMut(&[u8])) {
+        match byte {
+            b'\n' => {
+                self.line.push(b'\n');
+                self.ready.extend(self.line.drain(..));
+                echo(b"\n");
+            }
+            BACKSPACE if !self.line.is_empty() => {
+                // Remove a whole UTF-8 character, continuation bytes first.
+                while self.line.pop().is_some_and(|byte| byte & 0xC0 == 0x80) {}
+                echo(b"\x08 \x08");
+            }
+            END_OF_FILE if self.line.is_empty() => self.eof = true,
+            END_OF_FILE => self.ready.extend(self.line.drain(..)),
+            b'\t' | 0x20..=0x7E | 0x80.. if self.line.len() < MAX_LINE_BYTES => {
+                self.line.push(byte);
+                echo(&[byte]);
+            }
+            _ => {}
+        }
+    }
+
+    /// Move input to `out`: completed lines first, then an end of file, then
+    /// the line being edited once it alone would fill `out`.  `None` means
+    /// the reader has to wait for more input.
+    pub fn read(&mut self, out: &mut [u8]) -> Option<usize> {
+        if !self.ready.is_empty() {
+            let count = out.len().min(self.ready.len());
+            for (slot, byte) in out.iter_mut().zip(self.ready.drain(..count)) {
+                *slot = byte;
+            }
+            return Some(count);
+        }
+        if self.eof {
+            self.eof = false;
+            return Some(0);
+        }
+        if !out.is_empty() && self.line.len() >= out.len() {
+            let count = out.len();
+            out.copy_from_slice(&self.line[..count]);
+            self.line.drain(..count);
+            return Some(count);
+        }
+        None
+    }
+}
+
+impl Default for LineDiscipline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read console input into `out`, waiting for a line unless `nonblocking`
+/// is set, in which case [`SyscallError::WouldBlock`] is returned instead.
+pub fn read_console(out: &mut [u8], nonblocking: bool) -> Result<usize, SyscallError> {
+    loop {
+        if let Some(count) = poll_console(out) {
+            return Ok(count);
+        }
+        if nonblocking {
+            return Err(SyscallError::WouldBlock);
+        }
+        crate::process::yield_current();
+    }
+}
+
+fn poll_console(out: &mut [u8]) -> Option<usize> {
+    let mut console = CONSOLE.lock();
+    while let Some(byte) = crate::interrupts::input::try_read_console() {
+        console.feed(byte, &mut |bytes| {
+            petroleum::write_serial_bytes(0x3F8, 0x3FD, bytes)
+        });
+    }
+    console.read(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(discipline: &mut LineDiscipline, input: &[u8]) -> Vec<u8> {
+        let mut echoed = Vec::new();
+        for &byte in input {
+            discipline.feed(byte, &mut |bytes| echoed.extend_from_slice(bytes));
+        }
+        echoed
+    }
+
+    #[test]
+    fn lines_are_edited_before_they_are_read() {
+        let mut discipline = LineDiscipline::new();
+        let mut out = [0u8; 16];
+        let echoed = feed_all(&mut discipline, b"lx\x08s");
+        assert_eq!(echoed, b"lx\x08 \x08s");
+        assert_eq!(discipline.read(&mut out), None);
+
+        feed_all(&mut discipline, b"\n");
+        assert_eq!(discipline.read(&mut out), Some(3));
+        assert_eq!(&out[..3], b"ls\n");
+        assert_eq!(discipline.read(&mut out), None);
+    }
+
+    #[test]
+    fn backspace_removes_whole_characters_and_stops_at_line_start() {
+        let mut discipline = LineDiscipline::new();
+        let echoed = feed_all(&mut discipline, "a\u{e9}\x08\x08\x08b\n".as_bytes());
+        assert_eq!(echoed, b"a\xc3\xa9\x08 \x08\x08 \x08b\n");
+        let mut out = [0u8; 8];
+        assert_eq!(discipline.read(&mut out), Some(2));
+        assert_eq!(&out[..2], b"b\n");
+    }
+
+    #[test]
+    fn ctrl_d_ends_a_partial_line_or_reports_end_of_file() {
+        let mut discipline = LineDiscipline::new();
+        let mut out = [0u8; 8];
+        feed_all(&mut discipline, b"ab\x04");
+        assert_eq!(discipline.read(&mut out), Some(2));
+        assert_eq!(&out[..2], b"ab");
+
+        feed_all(&mut discipline, b"\x04");
+        assert_eq!(discipline.read(&mut out), Some(0));
+        assert_eq!(discipline.read(&mut out), None);
+    }
+
+    #[test]
+    fn a_full_buffer_returns_before_enter() {
+        let mut discipline = LineDiscipline::new();
+        let mut out = [0u8; 2];
+        feed_all(&mut discipline, b"a");
+        assert_eq!(discipline.read(&mut out), None);
+        feed_all(&mut discipline, b"bc");
+        assert_eq!(discipline.read(&mut out), Some(2));
+        assert_eq!(out, *b"ab");
+        feed_all(&mut discipline, b"\n");
+        assert_eq!(discipline.read(&mut out), Some(2));
+        assert_eq!(out, *b"c\n");
+    }
+}
@@ -102,11 +102,32 @@ pub trait FileSystem: Send {
     fn size(&mut self, _fd: u32) -> Result<u64, FsError> {
         Err(FsError::NotSupported)
     }
+    /// Cut or zero-extend the file open on `fd` to `len` bytes.
+    fn truncate(&mut self, _fd: u32, _len: u64) -> Result<(), FsError> {
+        Err(FsError::NotSupported)
+    }
     fn create(&mut self, path: &str, kind: InodeType) -> Option<u64>;
     fn mkdir(&mut self, path: &str) -> Result<(), FsError>;
     fn unlink(&mut self, path: &str) -> Result<(), FsError>;
     fn readdir(&mut self, path: &str) -> Result<Vec<VNode>, FsError>;
     fn exists(&mut self, path: &str) -> bool;
+    /// Metadata for `path`.  The default looks the final component up in its
+    /// parent directory; the filesystem root is always a directory.
+    fn stat(&mut self, path: &str) -> Result<VNode, FsError> {
+        let path = path.trim_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() {
+            return Ok(VNode {
+                name: String::new(),
+                size: 0,
+                is_dir: true,
+            });
+        }
+        self.readdir(parent)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or(FsError::FileNotFound)
+    }
 }
 
 // ── MemFileSystem ─────────────────────────────────────────────
@@ -302,6 +323,22 @@ impl FileSystem for MemFileSystem {
             .ok_or(FsError::FileNotFound)
     }
 
+    fn truncate(&mut self, fd: u32, len: u64) -> Result<(), FsError> {
+        let descriptor = self.fds.get(&fd).ok_or(FsError::InvalidFileDescriptor)?;
+        let inode = self
+            .inodes
+            .get_mut(&descriptor.ino)
+            .ok_or(FsError::FileNotFound)?;
+        if inode.kind != InodeType::File {
+            return Err(FsError::IsADirectory);
+        }
+        inode
+            .data
+            .resize(usize::try_from(len).map_err(|_| FsError::InvalidInput)?, 0);
+        inode.size = len;
+        Ok(())
+    }
+
     fn create(&mut self, path: &str, kind: InodeType) -> Option<u64> {
         if self.lookup(path).is_some() {
             return None;
@@ -589,6 +626,14 @@ impl Vfs {
             .size(fd)
     }
 
+    pub fn truncate_at(&mut self, mount_idx: usize, fd: u32, len: u64) -> Result<(), FsError> {
+        self.mounts
+            .get_mut(mount_idx)
+            .ok_or(FsError::InvalidFileDescriptor)?
+            .fs
+            .truncate(fd, len)
+    }
+
     /// Open a file directly on the VFS and expose it as a Genome stream.
     pub fn open_reader<'a>(&'a mut self, path: &str) -> Result<VfsFile<'a>, FsError> {
         let mount_index = self.find_fs_index(path).ok_or(FsError::FileNotFound)?;
@@ -621,6 +666,10 @@ impl Vfs {
         self.resolve_and_find(path)
             .is_some_and(|(fs, p)| fs.exists(&p))
     }
+
+    pub fn stat(&mut self, path: &str) -> Result<VNode, FsError> {
+        self.with_fs_result(path, |fs, p| fs.stat(p))
+    }
 }
 
 /// A scoped reader for a file opened through [`Vfs`].
@@ -826,6 +875,36 @@ mod tests {
         assert!(fs.exists(&relative_path));
     }
 
+    #[test]
+    fn sibling_mounts_share_one_namespace() {
+        let mut root = MemFileSystem::new();
+        root.mkdir("/ram").unwrap();
+        root.mkdir("/boot").unwrap();
+        let mut ram = MemFileSystem::new();
+        ram.mkdir("/b").unwrap();
+        let mut boot = MemFileSystem::new();
+        let ino = boot.create("/kernel.elf", InodeType::File).unwrap();
+        let fd = boot.open("/kernel.elf", 0).unwrap().fd;
+        boot.write(fd, b"\x7fELF").unwrap();
+        boot.close(fd).unwrap();
+        let mut vfs = Vfs::new(Box::new(root));
+        vfs.mount("/ram", Box::new(ram)).unwrap();
+        vfs.mount("/boot", Box::new(boot)).unwrap();
+
+        let boot_entry = vfs.stat("/boot/./kernel.elf").unwrap();
+        assert_eq!((boot_entry.size, boot_entry.is_dir), (4, false));
+        assert!(vfs.stat("/ram/./a/../b/").unwrap().is_dir);
+        assert!(vfs.stat("/boot/").unwrap().is_dir);
+        assert_eq!(
+            vfs.stat("/ram/kernel.elf").unwrap_err(),
+            FsError::FileNotFound
+        );
+        assert_eq!(vfs.open("/boot/kernel.elf", 0).unwrap().ino, ino);
+
+        vfs.change_directory("/ram/b").unwrap();
+        assert!(vfs.stat("../../boot/kernel.elf").is_ok());
+    }
+
     #[test]
     fn path_normalization_stays_within_the_root() {
         assert_eq!(normalize_path("/a/./b/../c"), "/a/c");
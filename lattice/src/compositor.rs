@@ -16,6 +16,10 @@ pub struct Compositor;
 
 pub const TITLE_BAR_HEIGHT: u32 = 28;
 pub const WINDOW_BORDER: u32 = 1;
+/// Drop shadow under decorated windows: offset down and right, then
+/// blurred this many pixels in every direction.
+pub const WINDOW_SHADOW_OFFSET: i32 = 2;
+pub const WINDOW_SHADOW_BLUR: u32 = 3;
 
 // UI padding constants
 pub const WINDOW_PADDING: u32 = 8;
@@ -36,6 +40,59 @@ pub const COLOR_TITLE_INACTIVE: u32 = 0x444444;
 pub const COLOR_ACCENT: u32 = 0xE6A817;
 pub const COLOR_DANGER: u32 = 0xD94A4A;
 
+/// Screen area `(x0, y0, x1, y1)`, end-exclusive, whose every pixel
+/// drawing `win` overwrites: the decorated box, whose rounded corners are
+/// filled with the background colour, or the bare surface.
+pub(crate) fn window_opaque_area(win: &Window) -> (i32, i32, i32, i32) {
+    if win.title.is_none() {
+        return (
+            win.x,
+            win.y,
+            win.x + win.width as i32,
+            win.y + win.height as i32,
+        );
+    }
+    let border = WINDOW_BORDER as i32;
+    (
+        win.x - border,
+        win.y - border,
+        win.x + win.width as i32 + border,
+        win.y + (TITLE_BAR_HEIGHT + win.height) as i32 + border,
+    )
+}
+
+/// Screen area `(x0, y0, x1, y1)`, end-exclusive, that drawing `win` may
+/// touch: its opaque area plus the drop shadow.
+pub(crate) fn window_footprint(win: &Window) -> (i32, i32, i32, i32) {
+    let (x0, y0, x1, y1) = window_opaque_area(win);
+    if win.title.is_none() {
+        return (x0, y0, x1, y1);
+    }
+    let reach = WINDOW_SHADOW_OFFSET + WINDOW_SHADOW_BLUR as i32;
+    let back = (WINDOW_SHADOW_BLUR as i32 - WINDOW_SHADOW_OFFSET).max(0);
+    (x0 - back, y0 - back, x1 + reach, y1 + reach)
+}
+
+/// Whether `windows[index]` can be left undrawn within `clip`: the part of
+/// it inside `clip` is either empty or lies wholly under the opaque area of
+/// one visible window above it.  Windows hidden only by several windows
+/// together are still drawn.
+pub(crate) fn is_occluded(windows: &[Window], index: usize, clip: DirtyRect) -> bool {
+    let (x0, y0, x1, y1) = window_footprint(&windows[index]);
+    let x0 = x0.max(clip.x as i32);
+    let y0 = y0.max(clip.y as i32);
+    let x1 = x1.min((clip.x + clip.width) as i32);
+    let y1 = y1.min((clip.y + clip.height) as i32);
+    if x0 >= x1 || y0 >= y1 {
+        return true;
+    }
+    windows[index + 1..]
+        .iter()
+        .filter(|above| !above.minimized)
+        .map(window_opaque_area)
+        .any(|(ax0, ay0, ax1, ay1)| ax0 <= x0 && ay0 <= y0 && ax1 >= x1 && ay1 >= y1)
+}
+
 // ── Dim lookup table ────────────────────────────────────────
 /// Pre‑computed dim table: `(v * 2) / 5` for each 0..=255 channel value.
 static DIM_TABLE: [u32; 256] = {
@@ -369,9 +426,11 @@ impl Compositor {
         }
 
         // ── Layer 1: Windows ─────────────────────────────
-        for window in scene.windows {
-            // Skip minimized windows
-            if window.minimized {
+        let clip = DirtyRect::new(dx, dy, dw, dh);
+        for (index, window) in scene.windows.iter().enumerate() {
+            // Skip minimized windows, and ones a window above would paint
+            // over anyway.
+            if window.minimized || is_occluded(scene.windows, index, clip) {
                 continue;
             }
             Self::draw_window_clipped(framebuffer, fb_width, fb_height, window, dx, dy, dw, dh);
@@ -630,7 +689,16 @@ impl Compositor {
         p.clip_rect(cx as i32, cy as i32, cw, ch);
 
         // Shadow via painter
-        p.draw_shadow(wx, wy, ww_u, wh_u, radius, 2, 3, 0x000000);
+        p.draw_shadow(
+            wx,
+            wy,
+            ww_u,
+            wh_u,
+            radius,
+            WINDOW_SHADOW_OFFSET,
+            WINDOW_SHADOW_BLUR,
+            0x000000,
+        );
 
         // Pre-fill corner squares with background so outside-the-arc pixels
         // don't stay black.
@@ -704,3 +772,41 @@ impl Compositor {
         p.draw_text(tx, ty, title, colors.text, 15.0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::WindowId;
+    use alloc::vec::Vec;
+
+    fn titled(id: u64, x: i32, y: i32, width: u32, height: u32) -> Window {
+        Window::new_with_title(WindowId(id), x, y, width, height, 0x336699, "w")
+    }
+
+    #[test]
+    fn windows_under_one_opaque_window_are_occluded() {
+        let windows = [titled(1, 100, 100, 50, 50), titled(2, 50, 50, 200, 200)];
+        let screen = DirtyRect::full(640, 480);
+        assert!(is_occluded(&windows, 0, screen));
+        assert!(!is_occluded(&windows, 1, screen));
+
+        // Minimizing the cover exposes the window beneath.
+        let mut windows = windows;
+        windows[1].minimized = true;
+        assert!(!is_occluded(&windows, 0, screen));
+    }
+
+    #[test]
+    fn shadows_and_partial_cover_keep_windows_drawn() {
+        // The cover spans the lower window's box but not its shadow.
+        let lower = titled(1, 100, 100, 50, 50);
+        let (x0, y0, x1, y1) = window_opaque_area(&lower);
+        let cover = Window::new(WindowId(2), x0, y0, (x1 - x0) as u32, (y1 - y0) as u32, 0);
+        let windows: Vec<Window> = [lower, cover].into();
+        assert!(!is_occluded(&windows, 0, DirtyRect::full(640, 480)));
+        // A dirty rect that only reaches the covered part leaves it hidden.
+        assert!(is_occluded(&windows, 0, DirtyRect::new(110, 110, 10, 10)));
+        // Nothing of the window inside the dirty rect: nothing to draw.
+        assert!(is_occluded(&windows, 0, DirtyRect::new(400, 400, 10, 10)));
+    }
+}
@@ -241,6 +241,13 @@ impl Desktop {
     ///
     /// `fb_width` / `fb_height` are required for maximize toggle.
     pub fn mouse_down(&mut self, fb_width: u32, fb_height: u32) {
+        let (work_width, work_height) = self.work_area(fb_width, fb_height);
+        self.wm.set_bounds(DirtyRect::new(
+            0,
+            self.top_panel_offset(),
+            work_width,
+            work_height,
+        ));
         let cx = self.cursor.x;
         let cy = self.cursor.y;
 
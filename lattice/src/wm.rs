@@ -55,23 +55,47 @@ pub struct WindowManager {
     floating_restore: alloc::collections::BTreeMap<WindowId, (i32, i32, u32, u32)>,
     /// Cached work area dimensions (width, height) for retiling.
     work_area: Option<(u32, u32)>,
+    /// Area that dragged windows are kept inside, once known.
+    bounds: Option<DirtyRect>,
 }
 
 const RESIZE_HANDLE_SIZE: u32 = 16;
 const MIN_WINDOW_W: u32 = 80;
 const MIN_WINDOW_H: u32 = 40;
 
-/// TITLE_BAR_HEIGHT from compositor — kept in sync manually.
-const TITLE_BAR_H: u32 = 20;
-const BORDER: u32 = 2;
-
-/// Build a dirty rect for the full decorated area of a window.
+/// Build a dirty rect for everything drawing a window touches: the
+/// decorated area and its drop shadow, cut off at the top-left screen edge.
 pub(crate) fn window_dirty_rect(w: &Window) -> DirtyRect {
-    let x0 = w.x.saturating_sub(BORDER as i32).max(0) as u32;
-    let y0 = w.y.saturating_sub(BORDER as i32).max(0) as u32;
-    let ww = w.width + BORDER * 2;
-    let wh = w.height + TITLE_BAR_H + BORDER * 2;
-    DirtyRect::new(x0, y0, ww, wh)
+    let (x0, y0, x1, y1) = crate::compositor::window_footprint(w);
+    let (x0, y0) = (x0.max(0), y0.max(0));
+    DirtyRect::new(
+        x0 as u32,
+        y0 as u32,
+        (x1 - x0).max(0) as u32,
+        (y1 - y0).max(0) as u32,
+    )
+}
+
+/// Move `w` the least distance that puts its decorated box inside
+/// `bounds`.  A window larger than `bounds` is pinned to its top-left
+/// corner, keeping the title bar reachable.
+fn clamp_to_bounds(w: &mut Window, bounds: DirtyRect) {
+    let (x0, y0, x1, y1) = crate::compositor::window_opaque_area(w);
+    let (left, top) = (bounds.x as i32, bounds.y as i32);
+    let right = left + bounds.width as i32;
+    let bottom = top + bounds.height as i32;
+    let dx = if x0 < left {
+        left - x0
+    } else {
+        (right - x1).min(0).max(left - x0)
+    };
+    let dy = if y0 < top {
+        top - y0
+    } else {
+        (bottom - y1).min(0).max(top - y0)
+    };
+    w.x += dx;
+    w.y += dy;
 }
 
 impl WindowManager {
@@ -86,6 +110,7 @@ impl WindowManager {
             tiling_mode: TilingMode::Floating,
             floating_restore: alloc::collections::BTreeMap::new(),
             work_area: None,
+            bounds: None,
         }
     }
 
@@ -94,6 +119,12 @@ impl WindowManager {
         self.work_area = Some((width, height));
     }
 
+    /// Keep windows moved by dragging inside `bounds`, e.g. the screen
+    /// less its panels.
+    pub fn set_bounds(&mut self, bounds: DirtyRect) {
+        self.bounds = Some(bounds);
+    }
+
     pub fn windows(&self) -> &[Window] {
         &self.windows
     }
@@ -314,6 +345,9 @@ impl WindowManager {
                 if let Some(w) = self.windows.iter_mut().find(|w| w.id == window) {
                     w.x = x - offset_x;
                     w.y = y - offset_y;
+                    if let Some(bounds) = self.bounds {
+                        clamp_to_bounds(w, bounds);
+                    }
                 }
                 let dirty_after = {
                     self.windows
@@ -725,6 +759,57 @@ mod tests {
                 .focused
         );
     }
+    #[test]
+    fn dragged_windows_stay_inside_the_bounds() {
+        let mut wm = WindowManager::new();
+        wm.set_bounds(DirtyRect::new(0, 30, 640, 400));
+        wm.create_titled_window(100, 100, 200, 100, 0xFF0000, "Test");
+        // Grab the title bar 10 px right of and 5 px below the window origin.
+        wm.on_mouse_down(110, 105);
+        let window = |wm: &WindowManager| {
+            let w = &wm.windows[0];
+            (w.x, w.y)
+        };
+        let border = crate::compositor::WINDOW_BORDER as i32;
+        let height = (100 + crate::compositor::TITLE_BAR_HEIGHT) as i32;
+
+        wm.on_mouse_move(-50, -50);
+        assert_eq!(window(&wm), (border, 30 + border));
+        wm.on_mouse_move(2000, 2000);
+        assert_eq!(window(&wm), (640 - 200 - border, 430 - height - border));
+        wm.on_mouse_move(300, 200);
+        assert_eq!(window(&wm), (290, 195));
+        wm.on_mouse_up();
+    }
+
+    #[test]
+    fn windows_larger_than_the_bounds_keep_their_title_bar_visible() {
+        let mut wm = WindowManager::new();
+        wm.set_bounds(DirtyRect::new(0, 0, 320, 200));
+        wm.create_titled_window(10, 10, 400, 300, 0xFF0000, "Big");
+        wm.on_mouse_down(20, 15);
+        wm.on_mouse_move(300, 300);
+        let w = &wm.windows[0];
+        assert_eq!((w.x, w.y), (1, 1));
+    }
+
+    #[test]
+    fn moving_a_window_dirties_its_shadow_at_both_positions() {
+        let mut wm = WindowManager::new();
+        wm.create_titled_window(100, 100, 50, 50, 0xFF0000, "Test");
+        wm.consume_dirty_rects();
+        wm.on_mouse_down(110, 105);
+        wm.consume_dirty_rects();
+        wm.on_mouse_move(210, 105);
+        let rects = wm.consume_dirty_rects();
+        assert_eq!(rects.len(), 2);
+        let (_, _, x1, y1) = crate::compositor::window_footprint(&wm.windows[0]);
+        let after = rects[1];
+        assert_eq!(after.x + after.width, x1 as u32);
+        assert_eq!(after.y + after.height, y1 as u32);
+        assert_eq!(rects[0].x + 100, after.x);
+    }
+
     #[test]
     fn test_dirty_rects_accumulated() {
         let mut wm = WindowManager::new();
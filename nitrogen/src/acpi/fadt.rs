@@ -0,0 +1,218 @@
+//! Fixed ACPI Description Table (FADT) parsing and the `\_S5` sleep types
+//! from the DSDT, which together say how to power the machine off.
+
+const SDT_HEADER_LEN: usize = 36;
+/// Shortest FADT that reaches the PM1 control blocks (ACPI 1.0).
+const FADT_MIN_LEN: usize = 76;
+/// Length from which the 64-bit `X_DSDT` field is present.
+const FADT_X_DSDT_END: usize = 148;
+/// Length from which the `X_PM1a/b_CNT_BLK` address structures are present.
+const FADT_X_PM1_CNT_END: usize = 196;
+/// Generic address structure space ID for system I/O ports.
+const GAS_SYSTEM_IO: u8 = 1;
+
+const NAME_OP: u8 = 0x08;
+const ROOT_PREFIX: u8 = b'\\';
+const PACKAGE_OP: u8 = 0x12;
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const BYTE_PREFIX: u8 = 0x0A;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FadtInfo {
+    /// Physical address of the DSDT, preferring `X_DSDT`.
+    pub dsdt: u64,
+    /// Port that switches the chipset into ACPI mode, 0 if it always is.
+    pub smi_cmd: u16,
+    /// Value written to `smi_cmd` to enter ACPI mode.
+    pub acpi_enable: u8,
+    pub pm1a_control: u16,
+    /// 0 when the chipset has only one PM1 control block.
+    pub pm1b_control: u16,
+}
+
+/// `SLP_TYPa` and `SLP_TYPb` for one sleep state, from the DSDT's `\_Sx`
+/// package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepTypes {
+    pub a: u8,
+    pub b: u8,
+}
+
+pub fn parse(bytes: &[u8]) -> Option<FadtInfo> {
+    if bytes.len() < FADT_MIN_LEN || bytes.get(..4) != Some(b"FACP") {
+        return None;
+    }
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let u64_at = |offset: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(
+            bytes.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+    // Port of an `X_PM1x_CNT_BLK` address structure, for firmware that
+    // leaves the 32-bit block field zero.
+    let x_port = |offset: usize| -> u16 {
+        if bytes.len() < FADT_X_PM1_CNT_END || bytes[offset] != GAS_SYSTEM_IO {
+            return 0;
+        }
+        u64_at(offset + 4)
+            .and_then(|port| u16::try_from(port).ok())
+            .unwrap_or(0)
+    };
+    let x_dsdt = if bytes.len() >= FADT_X_DSDT_END {
+        u64_at(140)?
+    } else {
+        0
+    };
+    let dsdt = match x_dsdt {
+        0 => u64::from(u32_at(40)?),
+        x_dsdt => x_dsdt,
+    };
+    let pm1_control = |block: usize, x_block: usize| -> Option<u16> {
+        match u16::try_from(u32_at(block)?).unwrap_or(0) {
+            0 => Some(x_port(x_block)),
+            port => Some(port),
+        }
+    };
+    Some(FadtInfo {
+        dsdt,
+        smi_cmd: u16::try_from(u32_at(48)?).unwrap_or(0),
+        acpi_enable: bytes[52],
+        pm1a_control: pm1_control(64, 172)?,
+        pm1b_control: pm1_control(68, 184)?,
+    })
+}
+
+/// The sleep types of the `\_S5` (soft-off) package in a DSDT.
+///
+/// This is a byte scan for `Name (_S5, Package () { a, b, ... })` rather
+/// than an AML interpreter, which is what firmware emits in practice; an
+/// `_S5` computed by a method is not found.
+pub fn s5_sleep_types(dsdt: &[u8]) -> Option<SleepTypes> {
+    let body = dsdt.get(SDT_HEADER_LEN..)?;
+    body.windows(4)
+        .enumerate()
+        .filter(|&(_, name)| name == b"_S5_")
+        .find_map(|(at, _)| {
+            if !matches!(body[..at], [.., NAME_OP] | [.., NAME_OP, ROOT_PREFIX]) {
+                return None;
+            }
+            let rest = body.get(at + 4..)?;
+            if rest.first() != Some(&PACKAGE_OP) {
+                return None;
+            }
+            // PkgLength: bits 7:6 of the lead byte count the bytes after it.
+            let pkg_length_len = 1 + usize::from(*rest.get(1)? >> 6);
+            // Skip the opcode, the PkgLength and NumElements.
+            let elements = rest.get(1 + pkg_length_len + 1..)?;
+            let (a, elements) = aml_byte_integer(elements)?;
+            let (b, _) = aml_byte_integer(elements)?;
+            Some(SleepTypes { a, b })
+        })
+}
+
+/// A small integer constant at the start of `aml` and the bytes after it.
+fn aml_byte_integer(aml: &[u8]) -> Option<(u8, &[u8])> {
+    match *aml.first()? {
+        ZERO_OP => Some((0, &aml[1..])),
+        ONE_OP => Some((1, &aml[1..])),
+        BYTE_PREFIX => Some((*aml.get(1)?, &aml[2..])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn dsdt(aml: &[u8]) -> Vec<u8> {
+        let mut table = alloc::vec![0u8; SDT_HEADER_LEN];
+        table[..4].copy_from_slice(b"DSDT");
+        table.extend_from_slice(aml);
+        table
+    }
+
+    #[test]
+    fn reads_pm1_blocks_and_the_dsdt() {
+        let mut fadt = alloc::vec![0u8; 244];
+        fadt[..4].copy_from_slice(b"FACP");
+        fadt[40..44].copy_from_slice(&0x7fe0_0000u32.to_le_bytes());
+        fadt[48..52].copy_from_slice(&0xb2u32.to_le_bytes());
+        fadt[52] = 0xf1;
+        fadt[64..68].copy_from_slice(&0x604u32.to_le_bytes());
+        // PM1b only through its extended address structure.
+        fadt[184] = GAS_SYSTEM_IO;
+        fadt[188..196].copy_from_slice(&0x640u64.to_le_bytes());
+
+        let info = parse(&fadt).unwrap();
+        assert_eq!(
+            info,
+            FadtInfo {
+                dsdt: 0x7fe0_0000,
+                smi_cmd: 0xb2,
+                acpi_enable: 0xf1,
+                pm1a_control: 0x604,
+                pm1b_control: 0x640,
+            }
+        );
+
+        fadt[140..148].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        assert_eq!(parse(&fadt).unwrap().dsdt, 0x1_0000_0000);
+        assert!(parse(&fadt[..FADT_MIN_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn finds_s5_sleep_types() {
+        // Name (_S5, Package (0x04) { 0x05, One, Zero, Zero })
+        let table = dsdt(&[
+            0x10,
+            0x0c,
+            b'_',
+            b'S',
+            b'5',
+            b'_', // a Scope mentioning the name
+            NAME_OP,
+            b'_',
+            b'S',
+            b'5',
+            b'_',
+            PACKAGE_OP,
+            0x07,
+            0x04,
+            BYTE_PREFIX,
+            0x05,
+            ONE_OP,
+            ZERO_OP,
+            ZERO_OP,
+        ]);
+        assert_eq!(s5_sleep_types(&table), Some(SleepTypes { a: 5, b: 1 }));
+
+        // Name (\_S5, Package () { Zero, Zero }) with a two-byte PkgLength.
+        let table = dsdt(&[
+            NAME_OP,
+            ROOT_PREFIX,
+            b'_',
+            b'S',
+            b'5',
+            b'_',
+            PACKAGE_OP,
+            0x40,
+            0x00,
+            0x02,
+            ZERO_OP,
+            ZERO_OP,
+        ]);
+        assert_eq!(s5_sleep_types(&table), Some(SleepTypes { a: 0, b: 0 }));
+
+        assert_eq!(s5_sleep_types(&dsdt(b"_S4_")), None);
+        assert_eq!(
+            s5_sleep_types(&dsdt(&[NAME_OP, b'_', b'S', b'5', b'_'])),
+            None
+        );
+    }
+}
@@ -1,10 +1,13 @@
-//! Multiple APIC Description Table (MADT) processor topology parsing.
+//! Multiple APIC Description Table (MADT) parsing: processor topology and
+//! the Local APIC and I/O APIC base addresses.
 
 use alloc::vec::Vec;
 
 const SDT_HEADER_LEN: usize = 36;
 const MADT_FIXED_LEN: usize = SDT_HEADER_LEN + 8;
 const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_LOCAL_APIC_OVERRIDE: u8 = 5;
 const ENTRY_LOCAL_X2APIC: u8 = 9;
 const CPU_ENABLED: u32 = 1;
 const CPU_ONLINE_CAPABLE: u32 = 2;
@@ -17,17 +20,47 @@ pub struct Processor {
     pub online_capable: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApic {
+    pub id: u8,
+    pub address: u32,
+    /// First global system interrupt this I/O APIC handles.
+    pub gsi_base: u32,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MadtInfo {
     pub local_apic_address: u32,
+    /// 64-bit Local APIC address from an address override entry.
+    pub local_apic_address_override: Option<u64>,
+    pub io_apics: Vec<IoApic>,
     pub processors: Vec<Processor>,
 }
 
+impl MadtInfo {
+    /// Physical Local APIC base, preferring the 64-bit override entry.
+    pub fn local_apic_base(&self) -> u64 {
+        self.local_apic_address_override
+            .unwrap_or(self.local_apic_address as u64)
+    }
+
+    /// Physical base of the I/O APIC that receives the legacy ISA IRQs,
+    /// i.e. the one whose interrupts start at GSI 0.
+    pub fn legacy_io_apic_base(&self) -> Option<u64> {
+        self.io_apics
+            .iter()
+            .min_by_key(|io_apic| io_apic.gsi_base)
+            .map(|io_apic| io_apic.address as u64)
+    }
+}
+
 pub fn parse(bytes: &[u8]) -> Option<MadtInfo> {
     if bytes.len() < MADT_FIXED_LEN || bytes.get(..4) != Some(b"APIC") {
         return None;
     }
     let local_apic_address = u32::from_le_bytes(bytes[36..40].try_into().ok()?);
+    let mut local_apic_address_override = None;
+    let mut io_apics = Vec::new();
     let mut processors = Vec::new();
     let mut offset = MADT_FIXED_LEN;
     while offset < bytes.len() {
@@ -47,6 +80,17 @@ pub fn parse(bytes: &[u8]) -> Option<MadtInfo> {
                     online_capable: flags & CPU_ONLINE_CAPABLE != 0,
                 });
             }
+            ENTRY_IO_APIC if entry_len >= 12 => {
+                io_apics.push(IoApic {
+                    id: entry[2],
+                    address: u32::from_le_bytes(entry[4..8].try_into().ok()?),
+                    gsi_base: u32::from_le_bytes(entry[8..12].try_into().ok()?),
+                });
+            }
+            ENTRY_LOCAL_APIC_OVERRIDE if entry_len >= 12 => {
+                local_apic_address_override =
+                    Some(u64::from_le_bytes(entry[4..12].try_into().ok()?));
+            }
             ENTRY_LOCAL_X2APIC if entry_len >= 16 => {
                 let apic_id = u32::from_le_bytes(entry[4..8].try_into().ok()?);
                 let flags = u32::from_le_bytes(entry[8..12].try_into().ok()?);
@@ -66,6 +110,8 @@ pub fn parse(bytes: &[u8]) -> Option<MadtInfo> {
     processors.dedup_by_key(|processor| processor.apic_id);
     Some(MadtInfo {
         local_apic_address,
+        local_apic_address_override,
+        io_apics,
         processors,
     })
 }
@@ -103,6 +149,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reports_io_apics_and_the_local_apic_override() {
+        let mut madt = alloc::vec![0u8; MADT_FIXED_LEN];
+        madt[..4].copy_from_slice(b"APIC");
+        madt[36..40].copy_from_slice(&0xfee0_0000u32.to_le_bytes());
+        let info = parse(&madt).unwrap();
+        assert_eq!(info.local_apic_base(), 0xfee0_0000);
+        assert_eq!(info.legacy_io_apic_base(), None);
+
+        // A second I/O APIC for GSIs 24.. listed before the legacy one.
+        madt.extend_from_slice(&[1, 12, 3, 0, 0x00, 0x10, 0xc0, 0xfe, 24, 0, 0, 0]);
+        madt.extend_from_slice(&[1, 12, 2, 0, 0x00, 0x00, 0xc0, 0xfe, 0, 0, 0, 0]);
+        madt.extend_from_slice(&[5, 12, 0, 0, 0x00, 0x00, 0xd0, 0xfe, 0, 0, 0, 0]);
+
+        let info = parse(&madt).unwrap();
+        assert_eq!(
+            info.io_apics,
+            alloc::vec![
+                IoApic {
+                    id: 3,
+                    address: 0xfec0_1000,
+                    gsi_base: 24,
+                },
+                IoApic {
+                    id: 2,
+                    address: 0xfec0_0000,
+                    gsi_base: 0,
+                },
+            ]
+        );
+        assert_eq!(info.legacy_io_apic_base(), Some(0xfec0_0000));
+        assert_eq!(info.local_apic_base(), 0xfed0_0000);
+    }
+
     #[test]
     fn rejects_truncated_entries() {
         let mut madt = alloc::vec![0u8; MADT_FIXED_LEN];
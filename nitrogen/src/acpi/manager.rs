@@ -91,4 +91,18 @@ impl AcpiManager {
         let table_phys = self.find_table(b"APIC")?;
         crate::acpi::madt::parse(self.table_bytes(table_phys)?)
     }
+
+    /// Parse the FADT (signature `FACP`) for the power-management registers.
+    pub fn parse_fadt(&self) -> Option<crate::acpi::fadt::FadtInfo> {
+        let table_phys = self.find_table(b"FACP")?;
+        crate::acpi::fadt::parse(self.table_bytes(table_phys)?)
+    }
+
+    /// Sleep types for soft-off (S5), from the DSDT named by `fadt`.
+    pub fn parse_s5(
+        &self,
+        fadt: &crate::acpi::fadt::FadtInfo,
+    ) -> Option<crate::acpi::fadt::SleepTypes> {
+        crate::acpi::fadt::s5_sleep_types(self.table_bytes(fadt.dsdt)?)
+    }
 }
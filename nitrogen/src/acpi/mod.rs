@@ -1,4 +1,5 @@
 pub mod dmar;
+pub mod fadt;
 pub mod madt;
 pub mod manager;
 pub mod mcfg;
@@ -67,10 +68,20 @@ fn find_rsdp_at(phys: u64) -> Option<u64> {
     if &sig != b"RSD PTR " {
         return None;
     }
+    // The ACPI 1.0 checksum covers the first 20 bytes; revision 2 adds an
+    // extended checksum over the whole 36-byte structure.
     let rev = unsafe { core::ptr::read_unaligned(addr_of!((*ptr).revision)) };
-    let cksum_len = if rev >= 2 { 36 } else { 20 };
-    let data = unsafe { core::slice::from_raw_parts(ptr as *const u8, cksum_len) };
-    if checksum(data) { Some(phys) } else { None }
+    let v1 = unsafe { core::slice::from_raw_parts(ptr as *const u8, 20) };
+    if !checksum(v1) {
+        return None;
+    }
+    if rev >= 2 {
+        let v2 = unsafe { core::slice::from_raw_parts(ptr as *const u8, 36) };
+        if !checksum(v2) {
+            return None;
+        }
+    }
+    Some(phys)
 }
 
 fn find_rsdp_in_range(start: u64, len: u64) -> Option<u64> {
@@ -112,21 +123,18 @@ pub fn find_table(rsdp_phys: u64, signature: &[u8; 4]) -> Option<u64> {
         return None;
     }
 
+    // ACPI 2.0+ firmware may still leave the XSDT out; use the RSDT then.
     let rev = unsafe { core::ptr::read_unaligned(addr_of!((*rsdp_ptr).revision)) };
-    let (sdt_phys, entry_size): (u64, usize) = if rev >= 2 {
-        let xsdt = unsafe { core::ptr::read_unaligned(addr_of!((*rsdp_ptr).xsdt_address)) };
-        if xsdt != 0 {
-            (xsdt, 8)
-        } else {
-            return None;
-        }
+    let xsdt = if rev >= 2 {
+        unsafe { core::ptr::read_unaligned(addr_of!((*rsdp_ptr).xsdt_address)) }
     } else {
-        let rsdt = unsafe { core::ptr::read_unaligned(addr_of!((*rsdp_ptr).rsdt_address)) } as u64;
-        if rsdt != 0 {
-            (rsdt, 4)
-        } else {
-            return None;
-        }
+        0
+    };
+    let rsdt = unsafe { core::ptr::read_unaligned(addr_of!((*rsdp_ptr).rsdt_address)) } as u64;
+    let (sdt_phys, entry_size): (u64, usize) = match (xsdt, rsdt) {
+        (0, 0) => return None,
+        (0, rsdt) => (rsdt, 4),
+        (xsdt, _) => (xsdt, 8),
     };
 
     let sdt_ptr = phys_to_virt::<SdtHeader>(sdt_phys);
@@ -184,3 +192,99 @@ pub fn get_table_bytes(phys: u64) -> Option<&'static [u8]> {
     }
     Some(bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn fix_checksum(bytes: &mut [u8], at: usize, len: usize) {
+        bytes[at] = 0;
+        let sum = bytes[..len].iter().fold(0u8, |a, b| a.wrapping_add(*b));
+        bytes[at] = sum.wrapping_neg();
+    }
+
+    fn sdt(signature: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut table = alloc::vec![0u8; 36];
+        table[..4].copy_from_slice(signature);
+        table.extend_from_slice(body);
+        let length = table.len() as u32;
+        table[4..8].copy_from_slice(&length.to_le_bytes());
+        let len = table.len();
+        fix_checksum(&mut table, 9, len);
+        table
+    }
+
+    fn rsdp(revision: u8, rsdt: u32, xsdt: u64) -> Vec<u8> {
+        let mut rsdp = alloc::vec![0u8; 36];
+        rsdp[..8].copy_from_slice(b"RSD PTR ");
+        rsdp[15] = revision;
+        rsdp[16..20].copy_from_slice(&rsdt.to_le_bytes());
+        rsdp[20..24].copy_from_slice(&36u32.to_le_bytes());
+        rsdp[24..32].copy_from_slice(&xsdt.to_le_bytes());
+        fix_checksum(&mut rsdp, 8, 20);
+        fix_checksum(&mut rsdp, 32, 36);
+        rsdp
+    }
+
+    /// Lay the given structures out at their "physical" offsets in one
+    /// buffer and point the physical-to-virtual translation at it.
+    fn firmware(parts: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let mut memory = alloc::vec![0u8; 0x1000];
+        for (at, bytes) in parts {
+            memory[*at..*at + bytes.len()].copy_from_slice(bytes);
+        }
+        set_phys_to_virt_offset(memory.as_ptr() as u64);
+        memory
+    }
+
+    // One test, because the translation offset is global.
+    #[test]
+    fn walks_the_rsdt_and_the_xsdt() {
+        let apic = sdt(b"APIC", &[0; 8]);
+        let rsdt = sdt(b"RSDT", &0x400u32.to_le_bytes());
+        let xsdt = sdt(b"XSDT", &0x400u64.to_le_bytes());
+
+        // ACPI 1.0: RSDT only.
+        let memory = firmware(&[
+            (0x100, rsdp(0, 0x200, 0)),
+            (0x200, rsdt.clone()),
+            (0x400, apic.clone()),
+        ]);
+        assert_eq!(find_table(0x100, b"APIC"), Some(0x400));
+        assert_eq!(find_table(0x100, b"MCFG"), None);
+        assert_eq!(
+            get_table_bytes(0x400),
+            Some(&memory[0x400..0x400 + apic.len()])
+        );
+
+        // ACPI 2.0 prefers the XSDT, but falls back to the RSDT without one.
+        let _memory = firmware(&[
+            (0x100, rsdp(2, 0, 0x300)),
+            (0x300, xsdt),
+            (0x400, apic.clone()),
+        ]);
+        assert_eq!(find_table(0x100, b"APIC"), Some(0x400));
+        let _memory = firmware(&[
+            (0x100, rsdp(2, 0x200, 0)),
+            (0x200, rsdt.clone()),
+            (0x400, apic.clone()),
+        ]);
+        assert_eq!(find_table(0x100, b"APIC"), Some(0x400));
+
+        // Either RSDP checksum failing rejects it, as does a bad table.
+        let mut bad_extended = rsdp(2, 0x200, 0);
+        bad_extended[30] ^= 1;
+        let _memory = firmware(&[(0x100, bad_extended), (0x200, rsdt.clone())]);
+        assert!(!find_rsdp_from_addr(0x100));
+        let mut bad_table = apic;
+        bad_table[20] ^= 1;
+        let _memory = firmware(&[
+            (0x100, rsdp(0, 0x200, 0)),
+            (0x200, rsdt),
+            (0x400, bad_table),
+        ]);
+        assert!(find_rsdp_from_addr(0x100));
+        assert_eq!(get_table_bytes(0x400), None);
+    }
+}
@@ -28,6 +28,19 @@ const PIC_SLAVE_DATA: u16 = 0xA1;
 const ICW1_INIT: u8 = 0x10;
 const ICW4_8086: u8 = 0x01;
 
+// ── Legacy PIT channel 2 (used only as a calibration reference) ──
+
+const PIT_CHANNEL2_DATA: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+/// NMI status/control port: bit 0 gates channel 2, bit 1 drives the
+/// speaker, bit 5 reflects the channel 2 output.
+const PIT_GATE_PORT: u16 = 0x61;
+/// Channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count).
+const PIT_CHANNEL2_ONESHOT: u8 = 0b1011_0000;
+
+/// Input clock of the 8254 PIT in Hz.
+pub const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+
 // ── I/O APIC register offsets ──
 
 const IOAPIC_REG_WINDOW: u64 = 0x10;
@@ -282,6 +295,27 @@ impl ApicController {
         }
     }
 
+    /// Route an additional legacy ISA IRQ to `vector` on this CPU
+    /// (edge-triggered, active high, unmasked).
+    pub fn route_legacy_irq(&self, irq: u8, vector: u8) {
+        if irq > self.max_redirection_entry {
+            return;
+        }
+        let rte =
+            IoApicRedirectionEntry::new(vector, 0, false, false, false, false, self.local_apic_id);
+        self.write_rte(irq, rte);
+    }
+
+    /// Virtual base address of the Local APIC registers.
+    pub fn lapic_base(&self) -> u64 {
+        self.lapic_base
+    }
+
+    /// Virtual base address of the I/O APIC registers.
+    pub fn ioapic_base(&self) -> u64 {
+        self.ioapic_base
+    }
+
     /// Return the cached I/O APIC version register.
     pub fn ioapic_version(&self) -> u32 {
         self.ioapic_version
@@ -321,6 +355,39 @@ impl ApicController {
             Port::<u8>::new(PIC_SLAVE_DATA).write(0xFFu8);
         }
     }
+
+    // ── Legacy PIT ─────────────────────────────────────────────────
+
+    /// Busy-wait `microseconds` (at most ~54 ms) on PIT channel 2.
+    ///
+    /// Channel 2 is gated through port 0x61 and is not wired to an IRQ, so
+    /// this works with the legacy PIC disabled.  Returns `false` if the
+    /// channel output never went high, e.g. on machines without a PIT; the
+    /// caller should then fall back to another reference clock.
+    pub fn pit_wait_us(microseconds: u32) -> bool {
+        let count = (PIT_FREQUENCY_HZ * u64::from(microseconds) / 1_000_000).clamp(1, 0xFFFF);
+        let mut gate = Port::<u8>::new(PIT_GATE_PORT);
+        let mut data = Port::<u8>::new(PIT_CHANNEL2_DATA);
+        unsafe {
+            // Gate low and speaker off while the channel is programmed.
+            let saved = gate.read();
+            gate.write(saved & !0x03);
+            Port::<u8>::new(PIT_COMMAND).write(PIT_CHANNEL2_ONESHOT);
+            data.write(count as u8);
+            data.write((count >> 8) as u8);
+            // Raising the gate starts the countdown.
+            gate.write((saved & !0x02) | 0x01);
+
+            // Allow generous slack: the TSC rate behind the timeout may only
+            // be an estimate.
+            let fired = crate::timing::wait_timeout_us(u64::from(microseconds) * 4 + 1_000, || {
+                gate.read() & 0x20 != 0
+            })
+            .is_ok();
+            gate.write(saved);
+            fired
+        }
+    }
 }
 
 // ── Helper: compute virtual address from physical address ──────────────
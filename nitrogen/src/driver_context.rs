@@ -131,7 +131,7 @@ pub trait DriverContext: Send + Sync {
 pub struct PageFlags {
     /// Page is writable.
     pub writable: bool,
-    /// Page uses write-combining caching (WC) instead of write-back.
+    /// Page uses write-combining caching (WC) instead of being uncached.
     pub write_combining: bool,
     /// Page is executable.
     pub executable: bool,
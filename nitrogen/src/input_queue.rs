@@ -0,0 +1,208 @@
+//! Lock-free queue between an input interrupt handler and its reader.
+
+use core::sync::atomic::{AtomicU8, AtomicU16, AtomicUsize, Ordering};
+
+/// Atomic storage for one queued item.
+pub trait QueueSlot {
+    type Item: Copy;
+    const EMPTY: Self;
+    fn get(&self) -> Self::Item;
+    fn set(&self, item: Self::Item);
+}
+
+impl QueueSlot for AtomicU8 {
+    type Item = u8;
+    const EMPTY: Self = AtomicU8::new(0);
+    fn get(&self) -> u8 {
+        self.load(Ordering::Relaxed)
+    }
+    fn set(&self, item: u8) {
+        self.store(item, Ordering::Relaxed)
+    }
+}
+
+impl QueueSlot for AtomicU16 {
+    type Item = u16;
+    const EMPTY: Self = AtomicU16::new(0);
+    fn get(&self) -> u16 {
+        self.load(Ordering::Relaxed)
+    }
+    fn set(&self, item: u16) {
+        self.store(item, Ordering::Relaxed)
+    }
+}
+
+/// Fixed-capacity queue with one producer and one consumer, of bytes
+/// unless `S` says otherwise.
+///
+/// The producer is an interrupt handler and the consumer normal kernel code.
+/// Each side only stores to its own index, so neither ever waits for the
+/// other: `push` never spins on a lock the code it interrupted is holding,
+/// and `pop` runs with interrupts enabled.  The slots are atomics too, so a
+/// second concurrent producer or consumer can lose or repeat items but
+/// never cause undefined behaviour; callers keep to one of each.
+pub struct InputQueue<S: QueueSlot = AtomicU8> {
+    buf: [S; INPUT_QUEUE_CAPACITY],
+    /// Count of items popped, wrapping; written only by the consumer.
+    head: AtomicUsize,
+    /// Count of items pushed, wrapping; written only by the producer.
+    tail: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+/// A power of two, so the wrapping counts stay consistent modulo it.
+pub const INPUT_QUEUE_CAPACITY: usize = 256;
+
+impl<S: QueueSlot> InputQueue<S> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [const { S::EMPTY }; INPUT_QUEUE_CAPACITY],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append an item; returns `false`, dropping and counting it, when the
+    /// queue is full.  Producer side.
+    pub fn push(&self, item: S::Item) -> bool {
+        if self.free() == 0 {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let tail = self.tail.load(Ordering::Relaxed);
+        self.buf[tail % INPUT_QUEUE_CAPACITY].set(item);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Room left for items.  Exact on the producer side; the consumer can
+    /// only make it grow.
+    pub fn free(&self) -> usize {
+        // Acquire pairs with the consumer's release of `head`, so a slot is
+        // not overwritten before it has been read.
+        let used = self
+            .tail
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.head.load(Ordering::Acquire));
+        INPUT_QUEUE_CAPACITY - used
+    }
+
+    /// Remove the oldest item.  Consumer side.
+    pub fn pop(&self) -> Option<S::Item> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let item = self.buf[head % INPUT_QUEUE_CAPACITY].get();
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+
+    /// Discard everything queued so far.  Consumer side.
+    pub fn clear(&self) {
+        self.head
+            .store(self.tail.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+
+    /// Count of items pushed so far, wrapping.  Comparing two readings
+    /// tells the producer whether anything was queued in between.
+    pub fn pushed(&self) -> usize {
+        self.tail.load(Ordering::Acquire)
+    }
+
+    /// Items dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: QueueSlot> Default for InputQueue<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{AtomicU16, INPUT_QUEUE_CAPACITY, InputQueue, Ordering};
+
+    #[test]
+    fn input_queue_is_fifo_and_counts_drops_when_full() {
+        let queue: InputQueue = InputQueue::new();
+        for i in 0..INPUT_QUEUE_CAPACITY {
+            assert!(queue.push(i as u8));
+        }
+        assert_eq!(queue.free(), 0);
+        assert!(!queue.push(0xFF));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop(), Some(0));
+        assert!(queue.push(0xAA));
+        for i in 1..INPUT_QUEUE_CAPACITY {
+            assert_eq!(queue.pop(), Some(i as u8));
+        }
+        assert_eq!(queue.pop(), Some(0xAA));
+        assert_eq!(queue.pushed(), INPUT_QUEUE_CAPACITY + 1);
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn clear_drops_only_what_was_queued() {
+        let queue: InputQueue<AtomicU16> = InputQueue::new();
+        queue.push(0x1FF);
+        queue.push(0x002);
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.free(), INPUT_QUEUE_CAPACITY);
+        queue.push(0x103);
+        assert_eq!(queue.pop(), Some(0x103));
+    }
+
+    #[test]
+    fn input_queue_keeps_order_under_concurrent_push_and_pop() {
+        use std::sync::Arc;
+        use std::sync::atomic::AtomicUsize;
+
+        const ITEMS: usize = 100_000;
+        let queue: Arc<InputQueue> = Arc::new(InputQueue::new());
+        let refused = Arc::new(AtomicUsize::new(0));
+        let producer = {
+            let queue = Arc::clone(&queue);
+            let refused = Arc::clone(&refused);
+            std::thread::spawn(move || {
+                for i in 0..ITEMS {
+                    // Retry until there is room, counting the refusals, so
+                    // every item goes in exactly once.
+                    while !queue.push(i as u8) {
+                        refused.fetch_add(1, Ordering::Relaxed);
+                        std::thread::yield_now();
+                    }
+                }
+            })
+        };
+
+        // Anything lost, repeated or reordered shows up as a mismatch
+        // against the expected sequence.
+        let mut received = 0;
+        while received < ITEMS {
+            match queue.pop() {
+                Some(byte) => {
+                    assert_eq!(byte, received as u8, "item {received}");
+                    received += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), refused.load(Ordering::Relaxed));
+    }
+}
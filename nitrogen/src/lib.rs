@@ -32,11 +32,13 @@ pub mod debug;
 pub mod driver_api;
 pub mod driver_context;
 pub mod error;
+pub mod input_queue;
 pub mod metrics;
 pub mod mmio;
 pub mod pci;
 pub mod pci_error;
 pub mod pci_health;
+pub mod pci_msi;
 pub mod port;
 
 // ── Excludable drivers (gated by .driverignore) ──────────────
@@ -6,6 +6,8 @@
 
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
+use crate::driver_context::DriverContext;
+use crate::error::DriverError;
 use crate::port::PortWriter;
 
 // ── ECAM (Enhanced Configuration Access Mechanism) ──────────────
@@ -621,7 +623,23 @@ impl PciDevice {
         if bar_index >= self.max_bars() {
             return 0;
         }
-        let offset = 0x10 + (bar_index * 4);
+        let size_mask = self.probe_bar_dword(0x10 + (bar_index * 4));
+
+        if size_mask == 0 || size_mask == 0xFFFFFFFF {
+            return 0;
+        }
+
+        if (size_mask & 0x1) != 0 {
+            !(size_mask & 0xFFFFFFFC) + 1
+        } else {
+            !(size_mask & 0xFFFFFFF0) + 1
+        }
+    }
+
+    /// Write all ones to the BAR dword at `offset` and return what reads
+    /// back, restoring the original value afterwards.  Memory and I/O
+    /// decoding are disabled for the duration of the probe.
+    fn probe_bar_dword(&self, offset: u8) -> u32 {
         pci_config_lock_acquire();
         let original_value = PciConfigSpace::read_config_dword_unlocked(
             self.bus,
@@ -667,19 +685,71 @@ impl PciDevice {
             command,
         );
         pci_config_lock_release();
+        size_mask
+    }
 
-        if size_mask == 0 || size_mask == 0xFFFFFFFF {
-            return 0;
+    /// Map memory BAR `index` into the kernel address space and return the
+    /// virtual address of its first byte.
+    ///
+    /// The BAR is sized by probing, including the upper half of a 64-bit
+    /// BAR, and mapped uncached and non-executable through
+    /// [`DriverContext::map_mmio_region`].  I/O-space BARs have nothing to
+    /// map and fail with [`DriverError::NotSupported`]; their port base is
+    /// the `address` from [`read_bar_info`](Self::read_bar_info).  A BAR the
+    /// firmware or allocator has not assigned fails with
+    /// [`DriverError::NotReady`].
+    pub fn map_bar(&self, index: u8, ctx: &dyn DriverContext) -> Result<usize, DriverError> {
+        let bar = self
+            .read_bar_info(index)
+            .ok_or(DriverError::InvalidArgument)?;
+        if bar.is_io {
+            return Err(DriverError::NotSupported);
         }
-
-        if (size_mask & 0x1) != 0 {
-            !(size_mask & 0xFFFFFFFC) + 1
-        } else {
-            !(size_mask & 0xFFFFFFF0) + 1
+        // The upper half of a 64-bit BAR occupies the next slot.
+        if bar.is_64bit && index + 1 >= self.max_bars() {
+            return Err(DriverError::InvalidArgument);
         }
+        let offset = 0x10 + (index * 4);
+        let low_mask = self.probe_bar_dword(offset);
+        let high_mask = bar.is_64bit.then(|| self.probe_bar_dword(offset + 4));
+        let size = memory_bar_size(low_mask, high_mask).ok_or(DriverError::InvalidArgument)?;
+        if bar.address == 0 {
+            return Err(DriverError::NotReady);
+        }
+
+        let (phys, len) = page_span(bar.address, size).ok_or(DriverError::InvalidArgument)?;
+        let virt = ctx.phys_to_virt(phys);
+        let len = usize::try_from(len).map_err(|_| DriverError::InvalidArgument)?;
+        ctx.map_mmio_region(phys as usize, virt, len)?;
+        Ok(virt + (bar.address - phys) as usize)
     }
 }
 
+/// Size of a memory BAR from the value read back after writing all ones to
+/// its low dword and, for a 64-bit BAR, its high dword.  `None` if the BAR
+/// is not implemented.
+fn memory_bar_size(low_mask: u32, high_mask: Option<u32>) -> Option<u64> {
+    let low = u64::from(low_mask & 0xFFFFFFF0);
+    let mask = match high_mask {
+        Some(high) => u64::from(high) << 32 | low,
+        // A 32-bit BAR decodes nothing above 4 GiB.
+        None => 0xFFFF_FFFF_0000_0000 | low,
+    };
+    if low == 0 && high_mask.is_none_or(|high| high == 0) {
+        return None;
+    }
+    Some((!mask).wrapping_add(1))
+}
+
+/// The page-aligned physical start and length that cover `size` bytes at
+/// `address`.
+fn page_span(address: u64, size: u64) -> Option<(u64, u64)> {
+    const PAGE: u64 = 4096;
+    let start = address & !(PAGE - 1);
+    let end = address.checked_add(size)?.checked_next_multiple_of(PAGE)?;
+    Some((start, end - start))
+}
+
 struct PrivatePciDevice {
     bus: u8,
     device: u8,
@@ -891,3 +961,31 @@ impl PciScanner {
         &self.devices
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_memory_bars_from_probe_masks() {
+        // 32-bit, 16 KiB, type and prefetch bits set in the read-back.
+        assert_eq!(memory_bar_size(0xFFFF_C008, None), Some(0x4000));
+        // 64-bit, 16 KiB: the upper dword reads back all ones.
+        assert_eq!(memory_bar_size(0xFFFF_C00C, Some(u32::MAX)), Some(0x4000));
+        // 64-bit, 8 GiB: no address bits decoded in the lower dword.
+        assert_eq!(
+            memory_bar_size(0x0000_000C, Some(0xFFFF_FFFE)),
+            Some(1 << 33)
+        );
+        assert_eq!(memory_bar_size(0, None), None);
+        assert_eq!(memory_bar_size(0x4, Some(0)), None);
+    }
+
+    #[test]
+    fn page_span_covers_unaligned_bars() {
+        assert_eq!(page_span(0xFEB0_0000, 0x4000), Some((0xFEB0_0000, 0x4000)));
+        assert_eq!(page_span(0xFEB0_1010, 0x10), Some((0xFEB0_1000, 0x1000)));
+        assert_eq!(page_span(0xFEB0_1FF0, 0x20), Some((0xFEB0_1000, 0x2000)));
+        assert_eq!(page_span(u64::MAX - 0xF, 0x20), None);
+    }
+}
@@ -0,0 +1,445 @@
+//! MSI and MSI-X interrupt delivery for PCI devices.
+//!
+//! Message Signaled Interrupts replace the legacy INTx pins: the device
+//! writes `data` to `address`, and an address in the `0xFEE0_0000` window
+//! is delivered by the Local APIC as the vector encoded in `data`.  Both
+//! capability layouts are handled:
+//!
+//! - **MSI** (capability 0x05) keeps the message in config space, with a
+//!   32-bit or 64-bit address field depending on bit 7 of Message Control.
+//! - **MSI-X** (capability 0x11) keeps one 16-byte entry per vector in a
+//!   table inside one of the device's memory BARs.  The BAR is mapped
+//!   through the caller's [`DriverContext`], like any other driver MMIO.
+//!
+//! Vectors come from a small pool starting at [`MSI_VECTOR_BASE`]; the
+//! kernel is expected to route that range in its IDT.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::driver_context::{DriverContext, DriverContextError};
+use crate::pci::{PciConfigSpace, PciDevice};
+
+pub const PCI_CAP_ID_MSI: u8 = 0x05;
+pub const PCI_CAP_ID_MSIX: u8 = 0x11;
+
+/// First IDT vector handed out by [`allocate_vector`], directly above the
+/// 16 vectors the legacy IRQs are remapped to.
+pub const MSI_VECTOR_BASE: u8 = 0x30;
+/// Number of vectors in the MSI pool.
+pub const MSI_VECTOR_COUNT: u8 = 32;
+
+/// Local APIC message address window (Intel SDM Vol. 3, 11.11.1).
+const LAPIC_MSI_ADDRESS: u64 = 0xFEE0_0000;
+
+/// Message Control: MSI / MSI-X enable.
+const MSI_CTRL_ENABLE: u16 = 1 << 0;
+/// Message Control: Multiple Message Enable, bits [6:4].
+const MSI_CTRL_MME_MASK: u16 = 0x7 << 4;
+/// Message Control: 64-bit address capable.
+const MSI_CTRL_64BIT: u16 = 1 << 7;
+/// Message Control: per-vector masking capable.
+const MSI_CTRL_PVM: u16 = 1 << 8;
+/// MSI-X Message Control: function mask.
+const MSIX_CTRL_FUNCTION_MASK: u16 = 1 << 14;
+/// MSI-X Message Control: enable.
+const MSIX_CTRL_ENABLE: u16 = 1 << 15;
+/// MSI-X table entry size and vector-control mask bit.
+const MSIX_ENTRY_SIZE: usize = 16;
+const MSIX_ENTRY_MASKED: u32 = 1 << 0;
+
+/// PCI Command: INTx disable.
+const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+/// PCI Status: capabilities list present.
+const STATUS_CAP_LIST: u16 = 1 << 4;
+
+static VECTOR_POOL: AtomicU32 = AtomicU32::new(0);
+
+/// Reserve a free vector from the MSI pool.
+pub fn allocate_vector() -> Option<u8> {
+    let mut pool = VECTOR_POOL.load(Ordering::Relaxed);
+    loop {
+        let free = (!pool).trailing_zeros();
+        if free >= MSI_VECTOR_COUNT as u32 {
+            return None;
+        }
+        match VECTOR_POOL.compare_exchange_weak(
+            pool,
+            pool | (1 << free),
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => return Some(MSI_VECTOR_BASE + free as u8),
+            Err(current) => pool = current,
+        }
+    }
+}
+
+/// Return a vector obtained from [`allocate_vector`] to the pool.
+pub fn release_vector(vector: u8) {
+    if let Some(bit) = vector
+        .checked_sub(MSI_VECTOR_BASE)
+        .filter(|&bit| bit < MSI_VECTOR_COUNT)
+    {
+        VECTOR_POOL.fetch_and(!(1 << bit), Ordering::AcqRel);
+    }
+}
+
+/// Address/data pair a device writes to raise an interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiMessage {
+    pub address: u64,
+    pub data: u32,
+}
+
+impl MsiMessage {
+    /// Fixed delivery of `vector` to the Local APIC `apic_id`, edge
+    /// triggered, physical destination mode.
+    pub fn for_lapic(apic_id: u8, vector: u8) -> Self {
+        Self {
+            address: LAPIC_MSI_ADDRESS | ((apic_id as u64) << 12),
+            data: vector as u32,
+        }
+    }
+}
+
+/// MSI capability (ID 0x05).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiCapability {
+    /// Offset of the capability in config space.
+    pub offset: u8,
+    /// Message Address is 64 bits wide (adds an upper-address dword).
+    pub is_64bit: bool,
+    /// Mask and Pending registers follow the Message Data field.
+    pub per_vector_masking: bool,
+    /// Number of vectors the device can request (1..=32).
+    pub max_vectors: u8,
+}
+
+impl MsiCapability {
+    fn parse(offset: u8, control: u16) -> Option<Self> {
+        let cap = Self {
+            offset,
+            is_64bit: control & MSI_CTRL_64BIT != 0,
+            per_vector_masking: control & MSI_CTRL_PVM != 0,
+            max_vectors: 1 << ((control >> 1) & 0x7).min(5),
+        };
+        // The whole structure must fit below the end of config space.
+        (offset as usize + cap.len() <= 0x100).then_some(cap)
+    }
+
+    /// Size in bytes: 32-bit layout, plus the upper address dword, plus the
+    /// Mask and Pending registers.
+    fn len(&self) -> usize {
+        0x0C + 4 * self.is_64bit as usize + 8 * self.per_vector_masking as usize
+    }
+
+    /// Offset of the Message Data register.
+    pub fn data_offset(&self) -> u8 {
+        self.offset + if self.is_64bit { 0x0C } else { 0x08 }
+    }
+
+    /// Offset of the Mask Bits register, if per-vector masking exists.
+    pub fn mask_offset(&self) -> Option<u8> {
+        self.per_vector_masking
+            .then(|| self.offset + if self.is_64bit { 0x10 } else { 0x0C })
+    }
+}
+
+/// MSI-X capability (ID 0x11).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiXCapability {
+    pub offset: u8,
+    /// Number of table entries.
+    pub table_size: u16,
+    /// BAR index (BIR) holding the vector table.
+    pub table_bar: u8,
+    /// Offset of the table inside `table_bar`.
+    pub table_offset: u32,
+    pub pba_bar: u8,
+    pub pba_offset: u32,
+}
+
+impl MsiXCapability {
+    fn parse(offset: u8, control: u16, table: u32, pba: u32) -> Option<Self> {
+        (offset as usize + 12 <= 0x100).then_some(Self {
+            offset,
+            table_size: (control & 0x7FF) + 1,
+            table_bar: (table & 0x7) as u8,
+            table_offset: table & !0x7,
+            pba_bar: (pba & 0x7) as u8,
+            pba_offset: pba & !0x7,
+        })
+    }
+}
+
+/// Which mechanism [`PciDevice::enable_msi`] programmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsiMode {
+    Msi,
+    MsiX,
+}
+
+/// An enabled message-signaled interrupt.  `vector` is what the IDT must
+/// route to the driver's handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiInterrupt {
+    pub vector: u8,
+    pub mode: MsiMode,
+    pub message: MsiMessage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsiError {
+    /// The device has neither an MSI nor an MSI-X capability.
+    Unsupported,
+    /// `vector` is an exception vector (below 32).
+    InvalidVector,
+    /// The MSI-X table BAR is missing or in I/O space.
+    TableBarUnavailable,
+    /// Mapping the MSI-X table failed.
+    Mapping(DriverContextError),
+}
+
+impl fmt::Display for MsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("device has no MSI or MSI-X capability"),
+            Self::InvalidVector => f.write_str("vector is reserved for exceptions"),
+            Self::TableBarUnavailable => f.write_str("MSI-X table BAR is not usable"),
+            Self::Mapping(err) => write!(f, "MSI-X table mapping failed: {}", err),
+        }
+    }
+}
+
+/// Walk the standard capability list for the MSI and MSI-X capabilities.
+fn find_msi_capabilities(dev: &PciDevice) -> (Option<MsiCapability>, Option<MsiXCapability>) {
+    let (bus, device, function) = (dev.bus, dev.device, dev.function);
+    let mut msi = None;
+    let mut msix = None;
+    if PciConfigSpace::read_config_word(bus, device, function, 6) & STATUS_CAP_LIST == 0 {
+        return (msi, msix);
+    }
+    let mut off = PciConfigSpace::read_config_byte(bus, device, function, 0x34) & !0x3;
+    let mut visited = [false; 256];
+    for _ in 0..48 {
+        if !(0x40..=0xFC).contains(&off) || visited[off as usize] {
+            break;
+        }
+        visited[off as usize] = true;
+        let header = PciConfigSpace::read_config_dword(bus, device, function, off);
+        let control = (header >> 16) as u16;
+        match header as u8 {
+            PCI_CAP_ID_MSI => msi = MsiCapability::parse(off, control),
+            PCI_CAP_ID_MSIX => {
+                let table = PciConfigSpace::read_config_dword(bus, device, function, off + 4);
+                let pba = PciConfigSpace::read_config_dword(bus, device, function, off + 8);
+                msix = MsiXCapability::parse(off, control, table, pba);
+            }
+            _ => {}
+        }
+        off = (header >> 8) as u8 & !0x3;
+    }
+    (msi, msix)
+}
+
+/// APIC ID of the executing CPU, from CPUID leaf 1.
+fn current_apic_id() -> u8 {
+    (core::arch::x86_64::__cpuid(1).ebx >> 24) as u8
+}
+
+impl PciDevice {
+    /// The device's MSI capability, if it has one.
+    pub fn msi_capability(&self) -> Option<MsiCapability> {
+        find_msi_capabilities(self).0
+    }
+
+    /// The device's MSI-X capability, if it has one.
+    pub fn msix_capability(&self) -> Option<MsiXCapability> {
+        find_msi_capabilities(self).1
+    }
+
+    /// Deliver this device's interrupts as `vector` to the Local APIC of
+    /// the calling CPU, and disable its legacy INTx pin.
+    ///
+    /// MSI-X is preferred when present: entry 0 of the vector table is
+    /// programmed and unmasked and every other entry is masked.  The table
+    /// BAR is mapped through `ctx`.  Otherwise single-message MSI is used.
+    pub fn enable_msi(
+        &self,
+        vector: u8,
+        ctx: &dyn DriverContext,
+    ) -> Result<MsiInterrupt, MsiError> {
+        if vector < 32 {
+            return Err(MsiError::InvalidVector);
+        }
+        let message = MsiMessage::for_lapic(current_apic_id(), vector);
+        let mode = match find_msi_capabilities(self) {
+            (_, Some(msix)) => {
+                self.program_msix(&msix, message, ctx)?;
+                MsiMode::MsiX
+            }
+            (Some(msi), None) => {
+                self.program_msi(&msi, message);
+                MsiMode::Msi
+            }
+            (None, None) => return Err(MsiError::Unsupported),
+        };
+
+        let command = PciConfigSpace::read_config_word(self.bus, self.device, self.function, 4);
+        PciConfigSpace::write_config_word_raw(
+            self.bus,
+            self.device,
+            self.function,
+            4,
+            command | COMMAND_INTX_DISABLE,
+        );
+        log::info!(
+            "PCI: {:02x}:{:02x}.{} {:?} -> vector {:#x}",
+            self.bus,
+            self.device,
+            self.function,
+            mode,
+            vector
+        );
+        Ok(MsiInterrupt {
+            vector,
+            mode,
+            message,
+        })
+    }
+
+    fn program_msi(&self, cap: &MsiCapability, message: MsiMessage) {
+        let (bus, device, function) = (self.bus, self.device, self.function);
+        let control_offset = cap.offset + 2;
+        let control = PciConfigSpace::read_config_word(bus, device, function, control_offset);
+        // Disable while the message is half written.
+        PciConfigSpace::write_config_word_raw(
+            bus,
+            device,
+            function,
+            control_offset,
+            control & !MSI_CTRL_ENABLE,
+        );
+
+        PciConfigSpace::write_config_dword_raw(
+            bus,
+            device,
+            function,
+            cap.offset + 4,
+            message.address as u32,
+        );
+        if cap.is_64bit {
+            PciConfigSpace::write_config_dword_raw(
+                bus,
+                device,
+                function,
+                cap.offset + 8,
+                (message.address >> 32) as u32,
+            );
+        }
+        PciConfigSpace::write_config_word_raw(
+            bus,
+            device,
+            function,
+            cap.data_offset(),
+            message.data as u16,
+        );
+        if let Some(mask_offset) = cap.mask_offset() {
+            let mask = PciConfigSpace::read_config_dword(bus, device, function, mask_offset);
+            PciConfigSpace::write_config_dword_raw(bus, device, function, mask_offset, mask & !1);
+        }
+
+        // One message, enabled.
+        let control = (control & !MSI_CTRL_MME_MASK) | MSI_CTRL_ENABLE;
+        PciConfigSpace::write_config_word_raw(bus, device, function, control_offset, control);
+    }
+
+    fn program_msix(
+        &self,
+        cap: &MsiXCapability,
+        message: MsiMessage,
+        ctx: &dyn DriverContext,
+    ) -> Result<(), MsiError> {
+        let (bus, device, function) = (self.bus, self.device, self.function);
+        let bar = self
+            .read_bar_info(cap.table_bar)
+            .filter(|bar| !bar.is_io && bar.address != 0)
+            .ok_or(MsiError::TableBarUnavailable)?;
+
+        let table_phys = bar.address + cap.table_offset as u64;
+        let table_len = cap.table_size as usize * MSIX_ENTRY_SIZE;
+        let map_phys = table_phys & !0xFFF;
+        let map_len = ((table_phys - map_phys) as usize + table_len).div_ceil(4096) * 4096;
+        let map_virt = ctx.phys_to_virt(map_phys);
+        ctx.map_mmio_region(map_phys as usize, map_virt, map_len)
+            .map_err(MsiError::Mapping)?;
+        let table = (map_virt + (table_phys - map_phys) as usize) as *mut u32;
+
+        // Enable MSI-X with the whole function masked while entries change.
+        let control_offset = cap.offset + 2;
+        let control = PciConfigSpace::read_config_word(bus, device, function, control_offset);
+        PciConfigSpace::write_config_word_raw(
+            bus,
+            device,
+            function,
+            control_offset,
+            control | MSIX_CTRL_ENABLE | MSIX_CTRL_FUNCTION_MASK,
+        );
+
+        unsafe {
+            for entry in 1..cap.table_size as usize {
+                let vector_control = table.add(entry * 4 + 3);
+                vector_control.write_volatile(vector_control.read_volatile() | MSIX_ENTRY_MASKED);
+            }
+            table.write_volatile(message.address as u32);
+            table.add(1).write_volatile((message.address >> 32) as u32);
+            table.add(2).write_volatile(message.data);
+            let vector_control = table.add(3);
+            vector_control.write_volatile(vector_control.read_volatile() & !MSIX_ENTRY_MASKED);
+        }
+
+        PciConfigSpace::write_config_word_raw(
+            bus,
+            device,
+            function,
+            control_offset,
+            (control | MSIX_CTRL_ENABLE) & !MSIX_CTRL_FUNCTION_MASK,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msi_layouts_follow_the_address_width() {
+        let narrow = MsiCapability::parse(0x50, 0x0000).unwrap();
+        assert!(!narrow.is_64bit);
+        assert_eq!((narrow.data_offset(), narrow.mask_offset()), (0x58, None));
+
+        let wide = MsiCapability::parse(0x50, MSI_CTRL_64BIT | MSI_CTRL_PVM | (3 << 1)).unwrap();
+        assert_eq!(wide.max_vectors, 8);
+        assert_eq!((wide.data_offset(), wide.mask_offset()), (0x5C, Some(0x60)));
+
+        let masked = MsiCapability::parse(0x50, MSI_CTRL_PVM).unwrap();
+        assert_eq!(masked.mask_offset(), Some(0x5C));
+
+        // A 64-bit capability with masking does not fit at the very end.
+        assert!(MsiCapability::parse(0xF0, MSI_CTRL_64BIT | MSI_CTRL_PVM).is_none());
+    }
+
+    #[test]
+    fn msix_table_location_and_message_encoding() {
+        let cap = MsiXCapability::parse(0x70, 0x0003, 0x0000_2004, 0x0000_3004).unwrap();
+        assert_eq!(cap.table_size, 4);
+        assert_eq!((cap.table_bar, cap.table_offset), (4, 0x2000));
+        assert_eq!((cap.pba_bar, cap.pba_offset), (4, 0x3000));
+
+        let message = MsiMessage::for_lapic(2, 0x51);
+        assert_eq!(message.address, 0xFEE0_2000);
+        assert_eq!(message.data, 0x51);
+    }
+}
@@ -1,17 +1,31 @@
 //! PS/2 Keyboard Driver
 //!
-//! Scancode set 1 to ASCII conversion with input buffering, modifier tracking,
-//! key repeat support, and Super (Windows) key handling.
-
-use alloc::collections::VecDeque;
+//! Scancode set 1 to character conversion through the active keyboard layout,
+//! with input buffering, modifier tracking, key repeat support, and Super
+//! (Windows) key handling.  Untranslated set 2 input is decoded into set 1
+//! codes first; every make and break is also reported as a [`KeyEvent`].
+
+use super::keymap::{self, KeyCode, ScancodeSet};
+use super::layouts::{KeyboardLayout, UsQwerty};
+use crate::input_queue::InputQueue;
 use alloc::string::String;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use spin::Mutex;
+use x86_64::instructions::port::Port;
 
-static INPUT_BUFFER: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+/// Terminal input bytes.  The interrupt handler pushes without locking, so
+/// readers need not mask interrupts.
+static INPUT_BUFFER: InputQueue = InputQueue::new();
 static INPUT_STRING_BUFFER: Mutex<String> = Mutex::new(String::new());
+/// Bytes [`INPUT_STRING_BUFFER`] holds at most; reserved up front so the
+/// interrupt handler never allocates.
+const LINE_CAPACITY: usize = 256;
+
+/// Keeps the readers of the input queues to one consumer at a time.  The
+/// handler never takes it.
+static QUEUE_READER: Mutex<()> = Mutex::new(());
 
-/// When `false`, `read_char()` / `drain_line_buffer()` return no data so that
+/// When `false`, `try_read()` / `drain_line_buffer()` return no data so that
 /// background terminal processes do not steal keystrokes from GUI windows.
 /// Set by the GUI layer (`solvent::input_loop::poll_keyboard`) on focus change.
 pub static TERMINAL_INPUT_ALLOWED: AtomicBool = AtomicBool::new(true);
@@ -19,22 +33,44 @@ pub static TERMINAL_INPUT_ALLOWED: AtomicBool = AtomicBool::new(true);
 pub fn set_terminal_input_allowed(allowed: bool) {
     TERMINAL_INPUT_ALLOWED.store(allowed, Ordering::Release);
     if !allowed {
-        interrupt_free(|| {
-            let mut ib = INPUT_BUFFER.lock();
-            if !ib.is_empty() {
-                ib.clear();
-            }
-            let mut isb = INPUT_STRING_BUFFER.lock();
-            if !isb.is_empty() {
-                isb.clear();
-            }
-        });
+        let _reader = QUEUE_READER.lock();
+        INPUT_BUFFER.clear();
+        interrupt_free(|| INPUT_STRING_BUFFER.lock().clear());
     }
 }
 
-/// Raw key event buffer for non-ASCII key events (e.g. Super, arrows).
-/// Each entry is a (scancode, pressed) tuple.
-pub static RAW_KEY_QUEUE: Mutex<VecDeque<(u8, bool)>> = Mutex::new(VecDeque::new());
+/// Raw key event buffer for non-ASCII key events (e.g. Super, arrows), as
+/// [`encode_key`] packs them.
+static RAW_KEY_QUEUE: InputQueue<AtomicU16> = InputQueue::new();
+
+/// A key going down or up, after prefix decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+}
+
+/// Every key press and release, packed like [`RAW_KEY_QUEUE`] and turned
+/// into a [`KeyEvent`] by the reader.
+static EVENT_QUEUE: InputQueue<AtomicU16> = InputQueue::new();
+
+/// Pack a set 1 code, with bit 7 set for the `0xE0` prefix, and whether
+/// the key went down into one queue entry.
+fn encode_key(code: u8, pressed: bool) -> u16 {
+    u16::from(code) | u16::from(pressed) << 8
+}
+
+fn decode_key(entry: u16) -> (u8, bool) {
+    (entry as u8, entry & 0x100 != 0)
+}
+
+/// Active layout for character translation (US QWERTY until changed).
+static LAYOUT: Mutex<&'static dyn KeyboardLayout> = Mutex::new(&UsQwerty);
+
+/// Scancode set arriving on the data port.  Set 1 while the controller's
+/// translation bit is on (the default after `init_ps2_controller`).
+static SCANCODE_SET: Mutex<ScancodeSet> = Mutex::new(ScancodeSet::Set1);
+static SET2_DECODER: Mutex<Set2Decoder> = Mutex::new(Set2Decoder::new());
 
 /// Keyboard modifiers state
 #[derive(Debug, Clone, Copy, Default)]
@@ -103,83 +139,41 @@ impl KeyRepeatState {
     }
 }
 
-// Scancode set 1 tables
-const NUMBERS_BASE: &[u8] = b"1234567890";
-const NUMBERS_SHIFT: &[u8] = b"!@#$%^&*()";
-const QWERTY_BASE: &[u8] = b"qwertyuiop";
-const ASDF_BASE: &[u8] = b"asdfghjkl";
-const ZXCV_BASE: &[u8] = b"zxcvbnm";
-
-const PUNCTUATION: &[(u8, u8, u8)] = &[
-    (0x0C, b'-', b'_'),
-    (0x0D, b'=', b'+'),
-    (0x1A, b'[', b'{'),
-    (0x1B, b']', b'}'),
-    (0x27, b';', b':'),
-    (0x28, b'\'', b'"'),
-    (0x29, b'`', b'~'),
-    (0x2B, b'\\', b'|'),
-    (0x33, b',', b'<'),
-    (0x34, b'.', b'>'),
-    (0x35, b'/', b'?'),
-];
-
-const SPECIAL_KEYS: &[(u8, u8)] = &[
-    (0x1C, b'\n'),
-    (0x0E, 0x08),
-    (0x0F, b'\t'),
-    (0x01, 27),
-    (0x39, b' '),
-];
-
 /// Super key scancodes (Set 1, extended prefix 0xE0)
 pub const SC_LSUPER: u8 = 0x5B;
 pub const SC_RSUPER: u8 = 0x5C;
 
-fn process_alphabetic(base: u8, modifiers: &KeyboardModifiers) -> u8 {
-    let shift_pressed = modifiers.lshift || modifiers.rshift;
-    let ctrl_pressed = modifiers.lctrl || modifiers.rctrl;
-    let mut ch = base;
-    if shift_pressed ^ modifiers.caps_lock {
-        ch = ch.to_ascii_uppercase();
+/// Character for `scancode` under the active layout.
+fn translate(scancode: u8, modifiers: &KeyboardModifiers) -> Option<char> {
+    LAYOUT.lock().translate(scancode, modifiers)
+}
+
+/// Queue a translated character for terminal input.
+fn push_char(c: char) {
+    let mut utf8 = [0u8; 4];
+    let bytes = c.encode_utf8(&mut utf8).as_bytes();
+    // Whole characters only, so a reader never sees half of one.
+    if INPUT_BUFFER.free() >= bytes.len() {
+        for &byte in bytes {
+            INPUT_BUFFER.push(byte);
+        }
     }
-    if ctrl_pressed {
-        ch &= 0x1F;
+    let mut sb = INPUT_STRING_BUFFER.lock();
+    if c == '\x08' {
+        sb.pop();
+    } else if sb.len() + c.len_utf8() <= LINE_CAPACITY {
+        sb.push(c);
     }
-    ch
 }
 
-fn scancode_to_ascii(scancode: u8, modifiers: &KeyboardModifiers) -> Option<u8> {
-    let shift = modifiers.lshift || modifiers.rshift;
-    match scancode {
-        0x02..=0x0B => Some(if shift {
-            NUMBERS_SHIFT[(scancode - 0x02) as usize]
-        } else {
-            NUMBERS_BASE[(scancode - 0x02) as usize]
-        }),
-        0x10..=0x19 => Some(process_alphabetic(
-            QWERTY_BASE[(scancode - 0x10) as usize],
-            modifiers,
-        )),
-        0x1E..=0x26 => Some(process_alphabetic(
-            ASDF_BASE[(scancode - 0x1E) as usize],
-            modifiers,
-        )),
-        0x2C..=0x32 => Some(process_alphabetic(
-            ZXCV_BASE[(scancode - 0x2C) as usize],
-            modifiers,
-        )),
-        _ => PUNCTUATION
-            .iter()
-            .find(|&&(c, _, _)| c == scancode)
-            .map(|&(_, b, s)| if shift { s } else { b })
-            .or_else(|| {
-                SPECIAL_KEYS
-                    .iter()
-                    .find(|&&(c, _)| c == scancode)
-                    .map(|&(_, a)| a)
-            }),
-    }
+/// Select the layout used to translate key presses.
+pub fn set_layout(layout: &'static dyn KeyboardLayout) {
+    interrupt_free(|| *LAYOUT.lock() = layout);
+}
+
+/// Name of the active layout.
+pub fn layout_name() -> &'static str {
+    interrupt_free(|| LAYOUT.lock().name())
 }
 
 /// Check if a scancode is a Super (Windows) key.
@@ -193,25 +187,147 @@ pub fn super_held() -> bool {
     mods.lsuper || mods.rsuper
 }
 
-pub fn handle_keyboard_scancode(scancode: u8) {
+/// Decoder for scancode set 2 byte streams.
+///
+/// Set 2 signals a release with an `0xF0` prefix instead of bit 7, and
+/// extended keys with `0xE0` (which may be followed by `0xF0`).  Each
+/// complete sequence is translated to its set 1 equivalent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Set2Decoder {
+    extended: bool,
+    release: bool,
+    /// Remaining bytes of a Pause sequence (`E1 14 77 E1 F0 14 F0 77`).
+    pause_skip: u8,
+}
+
+impl Set2Decoder {
+    pub const fn new() -> Self {
+        Self {
+            extended: false,
+            release: false,
+            pause_skip: 0,
+        }
+    }
+
+    /// Feed one byte; returns `(set1_code, extended, pressed)` once a
+    /// sequence is complete.
+    pub fn feed(&mut self, byte: u8) -> Option<(u8, bool, bool)> {
+        if self.pause_skip > 0 {
+            self.pause_skip -= 1;
+            return None;
+        }
+        match byte {
+            0xE0 => self.extended = true,
+            0xF0 => self.release = true,
+            0xE1 => self.pause_skip = 7,
+            // Controller/device responses, not keys.
+            0x00 | 0xAA | 0xEE | 0xFA | 0xFE | 0xFF => {}
+            code => {
+                let extended = core::mem::take(&mut self.extended);
+                let pressed = !core::mem::take(&mut self.release);
+                return keymap::set2_to_set1(code, extended).map(|sc| (sc, extended, pressed));
+            }
+        }
+        None
+    }
+}
+
+/// Select which scancode set the data port delivers.
+pub fn set_scancode_set(set: ScancodeSet) {
+    interrupt_free(|| {
+        *SCANCODE_SET.lock() = set;
+        *SET2_DECODER.lock() = Set2Decoder::new();
+        *EXTENDED_SCANCODE.lock() = false;
+    });
+}
+
+/// Set 1 while the controller translates; otherwise the set the keyboard
+/// reported, or its power-on default, set 2, when it did not answer.  Set 3
+/// is decoded as set 2, the closest match.
+fn scancode_set_for(config: u8, reported: Option<u8>) -> ScancodeSet {
+    if config & super::CFG_FIRST_PORT_TRANSLATION != 0 {
+        return ScancodeSet::Set1;
+    }
+    match reported {
+        Some(1) => ScancodeSet::Set1,
+        _ => ScancodeSet::Set2,
+    }
+}
+
+/// Ask the keyboard which scancode set it sends (command `F0 00`).
+fn query_keyboard_set(data_port: &mut Port<u8>, status_port: &mut Port<u8>) -> Option<u8> {
+    for byte in [0xF0, 0x00] {
+        if !super::write_data(data_port, status_port, byte)
+            || super::read_data(data_port, status_port) != Some(0xFA)
+        {
+            return None;
+        }
+    }
+    super::read_data(data_port, status_port)
+}
+
+/// Work out which scancode set the data port delivers and decode with it.
+///
+/// [`super::init_ps2_controller`] turns translation on, but not every
+/// controller honours it.  When it is off the keyboard is asked directly,
+/// with its interrupt masked so the handler does not take the reply for a
+/// key.  Returns the set selected.
+pub fn detect_scancode_set() -> ScancodeSet {
+    let mut command_port: Port<u8> = Port::new(super::PS2_COMMAND_PORT);
+    let mut data_port: Port<u8> = Port::new(super::PS2_DATA_PORT);
+    let mut status_port: Port<u8> = Port::new(super::PS2_STATUS_PORT);
+    let Some(config) = super::read_config_byte(&mut command_port, &mut data_port, &mut status_port)
+    else {
+        return *SCANCODE_SET.lock();
+    };
+    let mut reported = None;
+    if config & super::CFG_FIRST_PORT_TRANSLATION == 0 {
+        let masked = config & !super::CFG_FIRST_PORT_INTERRUPT;
+        super::write_config_byte(&mut command_port, &mut data_port, &mut status_port, masked);
+        reported = query_keyboard_set(&mut data_port, &mut status_port);
+        super::write_config_byte(&mut command_port, &mut data_port, &mut status_port, config);
+    }
+    let set = scancode_set_for(config, reported);
+    set_scancode_set(set);
+    log::info!(
+        "[ps2] Keyboard scancode {:?} (reported {:?})",
+        set,
+        reported
+    );
+    set
+}
+
+/// Feed one byte from the data port to the driver.  Returns whether it
+/// queued bytes for [`try_read`]; prefixes, releases and modifier keys
+/// queue none.
+pub fn handle_keyboard_scancode(scancode: u8) -> bool {
+    let before = INPUT_BUFFER.pushed();
+    let set = *SCANCODE_SET.lock();
+    if set == ScancodeSet::Set2 {
+        if let Some((base, is_ext, pressed)) = SET2_DECODER.lock().feed(scancode) {
+            handle_key(base, is_ext, pressed);
+        }
+        return INPUT_BUFFER.pushed() != before;
+    }
+
     let mut ext = EXTENDED_SCANCODE.lock();
     if scancode == 0xE0 {
         *ext = true;
-        return;
+        return false;
     }
     let is_ext = *ext;
     *ext = false;
     drop(ext);
 
+    handle_key(scancode & 0x7F, is_ext, scancode & 0x80 == 0);
+    INPUT_BUFFER.pushed() != before
+}
+
+fn handle_key(base: u8, is_ext: bool, pressed: bool) {
     // Always push raw key events for non‑ASCII handling (shell, etc.)
-    let pressed = scancode & 0x80 == 0;
-    let base = scancode & 0x7F;
-    {
-        let mut raw = RAW_KEY_QUEUE.lock();
-        if raw.len() < 64 {
-            raw.push_back((if is_ext { base | 0x80 } else { base }, pressed));
-        }
-    }
+    let entry = encode_key(if is_ext { base | 0x80 } else { base }, pressed);
+    RAW_KEY_QUEUE.push(entry);
+    EVENT_QUEUE.push(entry);
 
     let mut mods = MODIFIERS.lock();
 
@@ -239,17 +355,8 @@ fn handle_press(scancode: u8, mods: &mut KeyboardModifiers) {
         0x46 => mods.scroll_lock = !mods.scroll_lock,
         _ => {
             track_repeat(scancode);
-            if let Some(ascii) = scancode_to_ascii(scancode, mods) {
-                let mut buf = INPUT_BUFFER.lock();
-                if buf.len() < 256 {
-                    buf.push_back(ascii);
-                }
-                let mut sb = INPUT_STRING_BUFFER.lock();
-                if ascii == 0x08 {
-                    sb.pop();
-                } else if sb.len() < 256 {
-                    sb.push(ascii as char);
-                }
+            if let Some(c) = translate(scancode, mods) {
+                push_char(c);
             }
         }
     }
@@ -272,10 +379,29 @@ fn handle_ext_press(scancode: u8, mods: &mut KeyboardModifiers) {
         0x38 => mods.ralt = true,
         SC_LSUPER => mods.lsuper = true,
         SC_RSUPER => mods.rsuper = true,
-        _ => {}
+        _ => {
+            if let Some(sequence) = cursor_key_sequence(scancode) {
+                sequence.chars().for_each(push_char);
+            }
+        }
     }
 }
 
+/// ANSI escape sequence queued for an extended cursor key, so terminal
+/// readers see the same input as from a serial console.
+fn cursor_key_sequence(scancode: u8) -> Option<&'static str> {
+    Some(match scancode {
+        0x48 => "\x1b[A",  // Up
+        0x50 => "\x1b[B",  // Down
+        0x4D => "\x1b[C",  // Right
+        0x4B => "\x1b[D",  // Left
+        0x47 => "\x1b[H",  // Home
+        0x4F => "\x1b[F",  // End
+        0x53 => "\x1b[3~", // Delete
+        _ => return None,
+    })
+}
+
 fn handle_ext_release(scancode: u8, mods: &mut KeyboardModifiers) {
     match scancode {
         0x1D => mods.rctrl = false,
@@ -304,41 +430,58 @@ fn clear_repeat(scancode: u8) {
     }
 }
 
-pub fn read_char() -> Option<u8> {
+/// Pop the next byte of terminal input without waiting.
+pub fn try_read() -> Option<u8> {
+    let _reader = QUEUE_READER.lock();
     if !TERMINAL_INPUT_ALLOWED.load(Ordering::Acquire) {
-        interrupt_free(|| INPUT_BUFFER.lock().clear());
+        INPUT_BUFFER.clear();
         return None;
     }
-    interrupt_free(|| INPUT_BUFFER.lock().pop_front())
+    INPUT_BUFFER.pop()
 }
 
 /// Pop a raw key event (scancode, pressed) from the queue.
 pub fn pop_raw_key() -> Option<(u8, bool)> {
-    interrupt_free(|| RAW_KEY_QUEUE.lock().pop_front())
+    let _reader = QUEUE_READER.lock();
+    RAW_KEY_QUEUE.pop().map(decode_key)
+}
+
+/// Pop the next key press or release.
+pub fn poll_event() -> Option<KeyEvent> {
+    let _reader = QUEUE_READER.lock();
+    EVENT_QUEUE
+        .pop()
+        .map(decode_key)
+        .map(|(code, pressed)| KeyEvent {
+            code: keymap::set1_keycode(code & 0x7F, code & 0x80 != 0),
+            pressed,
+        })
 }
 
 pub fn input_available() -> bool {
     if !TERMINAL_INPUT_ALLOWED.load(Ordering::Acquire) {
-        interrupt_free(|| INPUT_BUFFER.lock().clear());
+        let _reader = QUEUE_READER.lock();
+        INPUT_BUFFER.clear();
         return false;
     }
-    interrupt_free(|| !INPUT_BUFFER.lock().is_empty())
+    !INPUT_BUFFER.is_empty()
 }
 
 pub fn raw_key_available() -> bool {
-    interrupt_free(|| !RAW_KEY_QUEUE.lock().is_empty())
+    !RAW_KEY_QUEUE.is_empty()
 }
 
 pub fn flush_input() {
-    interrupt_free(|| {
-        INPUT_BUFFER.lock().clear();
-        INPUT_STRING_BUFFER.lock().clear();
-        RAW_KEY_QUEUE.lock().clear();
-    });
+    {
+        let _reader = QUEUE_READER.lock();
+        INPUT_BUFFER.clear();
+        RAW_KEY_QUEUE.clear();
+        EVENT_QUEUE.clear();
+    }
+    interrupt_free(|| INPUT_STRING_BUFFER.lock().clear());
 }
 
 pub fn poll_key_hit() -> bool {
-    use x86_64::instructions::port::Port;
     let mut status: Port<u8> = Port::new(0x64);
     let st: u8 = unsafe { status.read() };
     // Bit 0 (0x01) = OBF (Output Buffer Full), Bit 5 (0x20) = AUXOBF (mouse data)
@@ -358,19 +501,23 @@ pub fn get_keyboard_status() -> KeyboardModifiers {
     *MODIFIERS.lock()
 }
 
+/// Take up to `buffer.len()` bytes of the line typed so far.  The interrupt
+/// handler appends under the same lock, so this masks interrupts while it
+/// holds it.
 pub fn drain_line_buffer(buffer: &mut [u8]) -> usize {
-    if !TERMINAL_INPUT_ALLOWED.load(Ordering::Acquire) {
+    interrupt_free(|| {
         let mut sb = INPUT_STRING_BUFFER.lock();
-        sb.clear();
-        return 0;
-    }
-    let mut sb = INPUT_STRING_BUFFER.lock();
-    let n = sb.len().min(buffer.len());
-    if n > 0 {
-        buffer[..n].copy_from_slice(&sb.as_bytes()[..n]);
-        sb.drain(..n);
-    }
-    n
+        if !TERMINAL_INPUT_ALLOWED.load(Ordering::Acquire) {
+            sb.clear();
+            return 0;
+        }
+        let n = sb.len().min(buffer.len());
+        if n > 0 {
+            buffer[..n].copy_from_slice(&sb.as_bytes()[..n]);
+            sb.drain(..n);
+        }
+        n
+    })
 }
 
 /// Update system tick for key repeat timing.
@@ -399,22 +546,14 @@ pub fn process_key_repeat() {
     let sc = r.last_scancode;
     drop(r);
     let mods = MODIFIERS.lock();
-    if let Some(ascii) = scancode_to_ascii(sc, &mods) {
-        let mut buf = INPUT_BUFFER.lock();
-        if buf.len() < 256 {
-            buf.push_back(ascii);
-        }
-        let mut sb = INPUT_STRING_BUFFER.lock();
-        if ascii == 0x08 {
-            sb.pop();
-        } else if sb.len() < 256 {
-            sb.push(ascii as char);
-        }
+    if let Some(c) = translate(sc, &mods) {
+        push_char(c);
     }
 }
 
 pub fn init_keyboard() {
     flush_input();
+    interrupt_free(|| INPUT_STRING_BUFFER.lock().reserve(LINE_CAPACITY));
     log::info!("PS/2 keyboard driver initialized");
 }
 
@@ -424,17 +563,70 @@ mod tests {
     #[test]
     fn test_scancode_conversion() {
         let m = KeyboardModifiers::default();
-        assert_eq!(scancode_to_ascii(0x1E, &m), Some(b'a'));
-        assert_eq!(scancode_to_ascii(0x10, &m), Some(b'q'));
-        assert_eq!(scancode_to_ascii(0x39, &m), Some(b' '));
-        assert_eq!(scancode_to_ascii(0x1C, &m), Some(b'\n'));
+        assert_eq!(UsQwerty.translate(0x1E, &m), Some('a'));
+        assert_eq!(UsQwerty.translate(0x10, &m), Some('q'));
+        assert_eq!(UsQwerty.translate(0x39, &m), Some(' '));
+        assert_eq!(UsQwerty.translate(0x1C, &m), Some('\n'));
     }
     #[test]
     fn test_buffer_operations() {
         init_keyboard();
-        assert_eq!(read_char(), None);
-        INPUT_BUFFER.lock().push_back(b't');
+        assert_eq!(try_read(), None);
+        INPUT_BUFFER.push(b't');
         assert!(input_available());
-        assert_eq!(read_char(), Some(b't'));
+        assert_eq!(try_read(), Some(b't'));
+    }
+    #[test]
+    fn queued_keys_keep_their_prefix_and_direction() {
+        assert_eq!(decode_key(encode_key(SC_LSUPER | 0x80, true)), (0xDB, true));
+        assert_eq!(decode_key(encode_key(0x1E, false)), (0x1E, false));
+    }
+    #[test]
+    fn set2_break_prefix_marks_release() {
+        let mut d = Set2Decoder::new();
+        assert_eq!(d.feed(0x1C), Some((0x1E, false, true)));
+        assert_eq!(d.feed(0xF0), None);
+        assert_eq!(d.feed(0x1C), Some((0x1E, false, false)));
+    }
+    #[test]
+    fn set2_extended_prefix_is_not_a_base_code() {
+        let mut d = Set2Decoder::new();
+        // E0 75 is Up, not keypad 8; E0 14 is right Ctrl, not left.
+        assert_eq!(d.feed(0xE0), None);
+        let (sc, ext, pressed) = d.feed(0x75).unwrap();
+        assert!(pressed);
+        assert_eq!(keymap::set1_keycode(sc, ext), KeyCode::Up);
+        assert_eq!(d.feed(0xE0), None);
+        assert_eq!(d.feed(0xF0), None);
+        let (sc, ext, pressed) = d.feed(0x14).unwrap();
+        assert!(!pressed);
+        assert_eq!(keymap::set1_keycode(sc, ext), KeyCode::RCtrl);
+        // The prefix does not leak into the next key.
+        let (sc, ext, _) = d.feed(0x14).unwrap();
+        assert_eq!(keymap::set1_keycode(sc, ext), KeyCode::LCtrl);
+    }
+    #[test]
+    fn extended_cursor_keys_become_escape_sequences() {
+        assert_eq!(cursor_key_sequence(0x48), Some("\x1b[A"));
+        assert_eq!(cursor_key_sequence(0x50), Some("\x1b[B"));
+        assert_eq!(cursor_key_sequence(0x53), Some("\x1b[3~"));
+        // Right Ctrl is a modifier, not a cursor key.
+        assert_eq!(cursor_key_sequence(0x1D), None);
+    }
+    #[test]
+    fn scancode_set_follows_translation_then_the_keyboard() {
+        let translated = super::super::CFG_FIRST_PORT_TRANSLATION;
+        assert_eq!(scancode_set_for(translated, None), ScancodeSet::Set1);
+        assert_eq!(scancode_set_for(0, Some(1)), ScancodeSet::Set1);
+        assert_eq!(scancode_set_for(0, Some(2)), ScancodeSet::Set2);
+        assert_eq!(scancode_set_for(0, None), ScancodeSet::Set2);
+    }
+    #[test]
+    fn set2_pause_sequence_is_swallowed() {
+        let mut d = Set2Decoder::new();
+        for b in [0xE1, 0x14, 0x77, 0xE1, 0xF0, 0x14, 0xF0, 0x77] {
+            assert_eq!(d.feed(b), None);
+        }
+        assert_eq!(d.feed(0x29), Some((0x39, false, true)));
     }
 }
@@ -84,6 +84,7 @@ pub enum KeyCode {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ScancodeSet {
     Set1,
+    Set2,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -285,6 +286,150 @@ fn us_set1_keycode(scancode: u8) -> Option<KeyCode> {
     }
 }
 
+/// Key code for a set 1 make code, taking the `0xE0` prefix into account so
+/// that e.g. the arrow keys and right-hand modifiers are not confused with
+/// the keypad and left-hand keys that share their base code.
+pub fn set1_keycode(scancode: u8, extended: bool) -> KeyCode {
+    use KeyCode::*;
+    if !extended {
+        return us_set1_keycode(scancode).unwrap_or(Unknown(scancode));
+    }
+    match scancode {
+        0x1C => Enter,
+        0x1D => RCtrl,
+        0x38 => RAlt,
+        0x47 => Home,
+        0x48 => Up,
+        0x49 => PageUp,
+        0x4B => Left,
+        0x4D => Right,
+        0x4F => End,
+        0x50 => Down,
+        0x51 => PageDown,
+        0x5B => LSuper,
+        0x5C => RSuper,
+        _ => Unknown(scancode | 0x80),
+    }
+}
+
+/// Translate a set 2 make code into the equivalent set 1 make code, as the
+/// i8042 does when translation is enabled.  `extended` is whether the code
+/// followed an `0xE0` prefix; the result keeps the same prefix.
+pub fn set2_to_set1(code: u8, extended: bool) -> Option<u8> {
+    if extended {
+        return Some(match code {
+            0x11 => 0x38,
+            0x12 => 0x2A,
+            0x14 => 0x1D,
+            0x1F => 0x5B,
+            0x27 => 0x5C,
+            0x2F => 0x5D,
+            0x4A => 0x35,
+            0x5A => 0x1C,
+            0x69 => 0x4F,
+            0x6B => 0x4B,
+            0x6C => 0x47,
+            0x70 => 0x52,
+            0x71 => 0x53,
+            0x72 => 0x50,
+            0x74 => 0x4D,
+            0x75 => 0x48,
+            0x7A => 0x51,
+            0x7C => 0x37,
+            0x7D => 0x49,
+            _ => return None,
+        });
+    }
+    Some(match code {
+        0x01 => 0x43,
+        0x03 => 0x3F,
+        0x04 => 0x3D,
+        0x05 => 0x3B,
+        0x06 => 0x3C,
+        0x07 => 0x58,
+        0x09 => 0x44,
+        0x0A => 0x42,
+        0x0B => 0x40,
+        0x0C => 0x3E,
+        0x0D => 0x0F,
+        0x0E => 0x29,
+        0x11 => 0x38,
+        0x12 => 0x2A,
+        0x14 => 0x1D,
+        0x15 => 0x10,
+        0x16 => 0x02,
+        0x1A => 0x2C,
+        0x1B => 0x1F,
+        0x1C => 0x1E,
+        0x1D => 0x11,
+        0x1E => 0x03,
+        0x21 => 0x2E,
+        0x22 => 0x2D,
+        0x23 => 0x20,
+        0x24 => 0x12,
+        0x25 => 0x05,
+        0x26 => 0x04,
+        0x29 => 0x39,
+        0x2A => 0x2F,
+        0x2B => 0x21,
+        0x2C => 0x14,
+        0x2D => 0x13,
+        0x2E => 0x06,
+        0x31 => 0x31,
+        0x32 => 0x30,
+        0x33 => 0x23,
+        0x34 => 0x22,
+        0x35 => 0x15,
+        0x36 => 0x07,
+        0x3A => 0x32,
+        0x3B => 0x24,
+        0x3C => 0x16,
+        0x3D => 0x08,
+        0x3E => 0x09,
+        0x41 => 0x33,
+        0x42 => 0x25,
+        0x43 => 0x17,
+        0x44 => 0x18,
+        0x45 => 0x0B,
+        0x46 => 0x0A,
+        0x49 => 0x34,
+        0x4A => 0x35,
+        0x4B => 0x26,
+        0x4C => 0x27,
+        0x4D => 0x19,
+        0x4E => 0x0C,
+        0x52 => 0x28,
+        0x54 => 0x1A,
+        0x55 => 0x0D,
+        0x58 => 0x3A,
+        0x59 => 0x36,
+        0x5A => 0x1C,
+        0x5B => 0x1B,
+        0x5D => 0x2B,
+        0x66 => 0x0E,
+        0x69 => 0x4F,
+        0x6B => 0x4B,
+        0x6C => 0x47,
+        0x70 => 0x52,
+        0x71 => 0x53,
+        0x72 => 0x50,
+        0x73 => 0x4C,
+        0x74 => 0x4D,
+        0x75 => 0x48,
+        0x76 => 0x01,
+        0x77 => 0x45,
+        0x78 => 0x57,
+        0x79 => 0x4E,
+        0x7A => 0x51,
+        0x7B => 0x4A,
+        0x7C => 0x37,
+        0x7D => 0x49,
+        0x7E => 0x46,
+        0x83 => 0x41,
+        _ => return None,
+    })
+}
+
 fn jp_set1_keycode(scancode: u8) -> Option<KeyCode> {
     match scancode {
         0x29 => Some(KeyCode::Kana),
@@ -0,0 +1,254 @@
+//! Keyboard layout maps.
+//!
+//! Each layout turns a set 1 make code plus the current modifier state into
+//! a character.  The driver consults the active layout (see
+//! [`super::keyboard::set_layout`]) for every key press that is not a
+//! modifier.
+
+use super::keyboard::KeyboardModifiers;
+
+/// Scancode-to-character translation for one physical layout.
+pub trait KeyboardLayout: Send + Sync {
+    /// Short name used to select the layout (e.g. `"us"`).
+    fn name(&self) -> &'static str;
+
+    /// Character produced by `scancode` (set 1) under `modifiers`, if any.
+    fn translate(&self, scancode: u8, modifiers: &KeyboardModifiers) -> Option<char>;
+}
+
+/// Unshifted/shifted characters for the printable rows of a layout, indexed
+/// from the first set 1 code of each row.
+struct Rows {
+    /// 0x02..=0x0D: digit row.
+    digits: (&'static str, &'static str),
+    /// 0x10..=0x1B: top letter row.
+    top: (&'static str, &'static str),
+    /// 0x1E..=0x29: home row plus the key left of `1`.
+    home: (&'static str, &'static str),
+    /// 0x2B: key next to Enter.
+    hash: (char, char),
+    /// 0x2C..=0x35: bottom letter row.
+    bottom: (&'static str, &'static str),
+    /// 0x56: extra ISO key left of the bottom row.
+    iso: (char, char),
+}
+
+impl Rows {
+    fn lookup(&self, scancode: u8) -> Option<(char, char)> {
+        fn nth(row: (&str, &str), index: u8) -> Option<(char, char)> {
+            let index = index as usize;
+            Some((row.0.chars().nth(index)?, row.1.chars().nth(index)?))
+        }
+        match scancode {
+            0x02..=0x0D => nth(self.digits, scancode - 0x02),
+            0x10..=0x1B => nth(self.top, scancode - 0x10),
+            0x1E..=0x29 => nth(self.home, scancode - 0x1E),
+            0x2B => Some(self.hash),
+            0x2C..=0x35 => nth(self.bottom, scancode - 0x2C),
+            0x56 => Some(self.iso),
+            _ => None,
+        }
+    }
+}
+
+/// Keys that produce the same control character on every layout.
+fn special_key(scancode: u8) -> Option<char> {
+    match scancode {
+        0x01 => Some('\x1b'),
+        0x0E => Some('\x08'),
+        0x0F => Some('\t'),
+        0x1C => Some('\n'),
+        0x39 => Some(' '),
+        _ => None,
+    }
+}
+
+/// Shared translation: AltGr table first, then the shift level, with Caps
+/// Lock applying to letters only and Ctrl folding letters to C0 controls.
+fn translate_with(
+    rows: &Rows,
+    altgr: fn(u8) -> Option<char>,
+    scancode: u8,
+    modifiers: &KeyboardModifiers,
+) -> Option<char> {
+    if let Some(c) = special_key(scancode) {
+        return Some(c);
+    }
+    if modifiers.ralt
+        && let Some(c) = altgr(scancode)
+    {
+        return Some(c);
+    }
+    let (lower, upper) = rows.lookup(scancode)?;
+    let shift = modifiers.lshift || modifiers.rshift;
+    let is_letter = lower.is_alphabetic() && upper.is_alphabetic();
+    let c = if shift ^ (is_letter && modifiers.caps_lock) {
+        upper
+    } else {
+        lower
+    };
+    if (modifiers.lctrl || modifiers.rctrl) && c.is_ascii_alphabetic() {
+        return Some(char::from(c as u8 & 0x1F));
+    }
+    Some(c)
+}
+
+fn no_altgr(_scancode: u8) -> Option<char> {
+    None
+}
+
+const US_ROWS: Rows = Rows {
+    digits: ("1234567890-=", "!@#$%^&*()_+"),
+    top: ("qwertyuiop[]", "QWERTYUIOP{}"),
+    home: ("asdfghjkl;'`", "ASDFGHJKL:\"~"),
+    hash: ('\\', '|'),
+    bottom: ("zxcvbnm,./", "ZXCVBNM<>?"),
+    iso: ('\\', '|'),
+};
+
+/// US QWERTY (the default).
+pub struct UsQwerty;
+
+impl KeyboardLayout for UsQwerty {
+    fn name(&self) -> &'static str {
+        "us"
+    }
+
+    fn translate(&self, scancode: u8, modifiers: &KeyboardModifiers) -> Option<char> {
+        translate_with(&US_ROWS, no_altgr, scancode, modifiers)
+    }
+}
+
+/// German QWERTZ.
+///
+/// The dead keys (`^`, `´`, `` ` ``) are not composed with the following
+/// key; they simply produce the accent character itself.
+pub struct German;
+
+const DE_ROWS: Rows = Rows {
+    digits: ("1234567890ß´", "!\"§$%&/()=?`"),
+    top: ("qwertzuiopü+", "QWERTZUIOPÜ*"),
+    home: ("asdfghjklöä^", "ASDFGHJKLÖÄ°"),
+    hash: ('#', '\''),
+    bottom: ("yxcvbnm,.-", "YXCVBNM;:_"),
+    iso: ('<', '>'),
+};
+
+fn de_altgr(scancode: u8) -> Option<char> {
+    match scancode {
+        0x03 => Some('²'),
+        0x04 => Some('³'),
+        0x08 => Some('{'),
+        0x09 => Some('['),
+        0x0A => Some(']'),
+        0x0B => Some('}'),
+        0x0C => Some('\\'),
+        0x10 => Some('@'),
+        0x12 => Some('€'),
+        0x1B => Some('~'),
+        0x32 => Some('µ'),
+        0x56 => Some('|'),
+        _ => None,
+    }
+}
+
+impl KeyboardLayout for German {
+    fn name(&self) -> &'static str {
+        "de"
+    }
+
+    fn translate(&self, scancode: u8, modifiers: &KeyboardModifiers) -> Option<char> {
+        translate_with(&DE_ROWS, de_altgr, scancode, modifiers)
+    }
+}
+
+/// Colemak on a US keyboard.
+pub struct Colemak;
+
+const COLEMAK_ROWS: Rows = Rows {
+    digits: ("1234567890-=", "!@#$%^&*()_+"),
+    top: ("qwfpgjluy;[]", "QWFPGJLUY:{}"),
+    home: ("arstdhneio'`", "ARSTDHNEIO\"~"),
+    hash: ('\\', '|'),
+    bottom: ("zxcvbkm,./", "ZXCVBKM<>?"),
+    iso: ('\\', '|'),
+};
+
+impl KeyboardLayout for Colemak {
+    fn name(&self) -> &'static str {
+        "colemak"
+    }
+
+    fn translate(&self, scancode: u8, modifiers: &KeyboardModifiers) -> Option<char> {
+        translate_with(&COLEMAK_ROWS, no_altgr, scancode, modifiers)
+    }
+}
+
+/// All built-in layouts, in the order they are listed to the user.
+pub static LAYOUTS: [&dyn KeyboardLayout; 3] = [&UsQwerty, &German, &Colemak];
+
+/// Look up a built-in layout by its [`KeyboardLayout::name`].
+pub fn by_name(name: &str) -> Option<&'static dyn KeyboardLayout> {
+    LAYOUTS
+        .iter()
+        .copied()
+        .find(|layout| layout.name().eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mods(f: impl FnOnce(&mut KeyboardModifiers)) -> KeyboardModifiers {
+        let mut m = KeyboardModifiers::default();
+        f(&mut m);
+        m
+    }
+
+    #[test]
+    fn layouts_differ_on_the_same_key() {
+        let none = KeyboardModifiers::default();
+        assert_eq!(UsQwerty.translate(0x15, &none), Some('y'));
+        assert_eq!(German.translate(0x15, &none), Some('z'));
+        assert_eq!(Colemak.translate(0x24, &none), Some('n'));
+        assert_eq!(
+            German.translate(0x2C, &mods(|m| m.lshift = true)),
+            Some('Y')
+        );
+        assert_eq!(
+            German.translate(0x27, &mods(|m| m.caps_lock = true)),
+            Some('Ö')
+        );
+        assert_eq!(
+            UsQwerty.translate(0x02, &mods(|m| m.caps_lock = true)),
+            Some('1')
+        );
+        assert_eq!(
+            UsQwerty.translate(0x2E, &mods(|m| m.lctrl = true)),
+            Some('\x03')
+        );
+    }
+
+    #[test]
+    fn german_altgr_and_dead_keys() {
+        let altgr = mods(|m| m.ralt = true);
+        assert_eq!(German.translate(0x10, &altgr), Some('@'));
+        assert_eq!(German.translate(0x12, &altgr), Some('€'));
+        // AltGr on a key without a third level falls back to the base level.
+        assert_eq!(German.translate(0x1E, &altgr), Some('a'));
+        let none = KeyboardModifiers::default();
+        assert_eq!(German.translate(0x29, &none), Some('^'));
+        assert_eq!(German.translate(0x0D, &none), Some('´'));
+        assert_eq!(
+            German.translate(0x0D, &mods(|m| m.rshift = true)),
+            Some('`')
+        );
+    }
+
+    #[test]
+    fn layouts_are_found_by_name() {
+        assert_eq!(by_name("DE").map(|l| l.name()), Some("de"));
+        assert_eq!(by_name("colemak").map(|l| l.name()), Some("colemak"));
+        assert!(by_name("dvorak").is_none());
+    }
+}
@@ -11,6 +11,7 @@ use x86_64::instructions::port::Port;
 
 pub mod keyboard;
 pub mod keymap;
+pub mod layouts;
 pub mod mouse;
 
 /// PS/2 I/O port addresses
@@ -1,28 +1,49 @@
 //! PS/2 mouse / touchpad driver backed by the external `ps2-mouse` crate.
 //!
-//! The external crate handles the low-level PS/2 protocol (including hardware
-//! initialisation quirks on real laptops), while the hand-rolled packet
-//! decoder serves as a well-tested fallback.  On native hardware the crate's
-//! `init()` is tried first; if it fails we fall through to the internal init
-//! so the system remains usable even with unusual or legacy controllers.
+//! The external crate handles device initialisation (including hardware
+//! quirks on real laptops).  On native hardware the crate's `init()` is tried
+//! first; if it fails we fall through to the internal init so the system
+//! remains usable even with unusual or legacy controllers.
+//!
+//! Either way, packets are assembled by the hand-rolled decoder: it
+//! resynchronises after a lost byte and understands the four-byte
+//! IntelliMouse packets that carry wheel motion, which the crate does not.
 
-use ps2_mouse::{Mouse as Ps2MouseInner, MouseState as Ps2MouseState};
+use ps2_mouse::Mouse as Ps2MouseInner;
 use spin::Mutex;
 use x86_64::instructions::port::Port;
 
 /// Global PS/2 mouse instance backed by the external crate.
 pub static MOUSE: Mutex<Option<Ps2MouseInner>> = Mutex::new(None);
 
-/// Relative movement accumulated since the previous poll.
+/// Relative movement accumulated since the previous poll, and the buttons
+/// held in the latest packet.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct MouseState {
     x: i16,
     y: i16,
+    wheel: i16,
+    left: bool,
+    right: bool,
+    middle: bool,
 }
 
 impl MouseState {
     pub const fn new() -> Self {
-        Self { x: 0, y: 0 }
+        Self::with_buttons(0)
+    }
+
+    /// No movement, with the buttons in `buttons` (the low three bits of a
+    /// status byte) held.
+    const fn with_buttons(buttons: u8) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            wheel: 0,
+            left: buttons & 0x01 != 0,
+            right: buttons & 0x02 != 0,
+            middle: buttons & 0x04 != 0,
+        }
     }
 
     pub const fn get_x(self) -> i16 {
@@ -32,41 +53,100 @@ impl MouseState {
     pub const fn get_y(self) -> i16 {
         self.y
     }
+
+    /// Wheel notches; positive when scrolled towards the user.  Always 0
+    /// unless the mouse accepted IntelliMouse mode.
+    pub const fn get_wheel(self) -> i16 {
+        self.wheel
+    }
+
+    pub const fn left_button_down(self) -> bool {
+        self.left
+    }
+
+    pub const fn right_button_down(self) -> bool {
+        self.right
+    }
+
+    pub const fn middle_button_down(self) -> bool {
+        self.middle
+    }
+
+    /// Button flags (bit 0 = left, bit 1 = right, bit 2 = middle).
+    pub const fn buttons(self) -> u8 {
+        self.left as u8 | (self.right as u8) << 1 | (self.middle as u8) << 2
+    }
+
+    /// Add the motion of `packet` and take its buttons.
+    fn accumulate(&mut self, packet: MouseState) {
+        self.x = self.x.saturating_add(packet.x);
+        self.y = self.y.saturating_add(packet.y);
+        self.wheel = self.wheel.saturating_add(packet.wheel);
+        self.left = packet.left;
+        self.right = packet.right;
+        self.middle = packet.middle;
+    }
 }
 
-/// Three-byte PS/2 packet decoder (kept as a portable fallback).
+/// PS/2 packet decoder for standard three-byte and IntelliMouse four-byte
+/// packets.
 #[derive(Debug, Clone, Copy)]
 struct PacketDecoder {
-    packet: [u8; 3],
+    packet: [u8; 4],
     index: usize,
+    len: usize,
 }
 
 impl PacketDecoder {
     const fn new() -> Self {
         Self {
-            packet: [0; 3],
+            packet: [0; 4],
             index: 0,
+            len: 3,
+        }
+    }
+
+    /// Decoder for a mouse in IntelliMouse mode.
+    const fn with_wheel() -> Self {
+        Self {
+            len: 4,
+            ..Self::new()
         }
     }
 
-    fn push(&mut self, byte: u8) -> Option<(MouseState, u8)> {
+    /// Add one byte, returning the motion and buttons once a packet is
+    /// complete.  Bytes are dropped until one carries the always-set bit 3
+    /// of a status byte, so a lost byte costs at most a packet or two.
+    fn push(&mut self, byte: u8) -> Option<MouseState> {
         if self.index == 0 && byte & 0x08 == 0 {
             return None;
         }
         self.packet[self.index] = byte;
         self.index += 1;
-        if self.index != self.packet.len() {
+        if self.index != self.len {
             return None;
         }
         self.index = 0;
 
         let status = self.packet[0];
+        let buttons = MouseState::with_buttons(status & 0x07);
         if status & 0xc0 != 0 {
-            return Some((MouseState::new(), status & 0x07));
+            return Some(buttons);
         }
         let x = decode_axis(self.packet[1], status & 0x10 != 0);
         let y = decode_axis(self.packet[2], status & 0x20 != 0);
-        Some((MouseState { x, y }, status & 0x07))
+        // The low nibble of the fourth byte is a signed wheel delta.
+        let wheel = if self.len == 4 {
+            i16::from(((self.packet[3] << 4) as i8) >> 4)
+        } else {
+            0
+        };
+        Some(MouseState {
+            x,
+            y,
+            wheel,
+            ..buttons
+        })
     }
 }
 
@@ -83,9 +163,7 @@ enum Backend {
 
 static DECODER: Mutex<PacketDecoder> = Mutex::new(PacketDecoder::new());
 static LATEST_STATE: Mutex<MouseState> = Mutex::new(MouseState::new());
-static LATEST_STATUS: Mutex<u8> = Mutex::new(0);
 static BACKEND: Mutex<Option<Backend>> = Mutex::new(None);
-static PACKET_IDX: Mutex<u8> = Mutex::new(0);
 
 fn mouse_port_present() -> bool {
     let mut status_port: Port<u8> = Port::new(super::PS2_STATUS_PORT);
@@ -107,6 +185,51 @@ fn send_mouse_command(
     matches!(super::read_data(data_port, status_port), Some(0xfa))
 }
 
+/// Try to switch the mouse into IntelliMouse mode, where a fourth packet
+/// byte reports wheel motion.  Streaming must be off.  Returns whether the
+/// device now identifies as a wheel mouse.
+fn enable_wheel(
+    command_port: &mut Port<u8>,
+    data_port: &mut Port<u8>,
+    status_port: &mut Port<u8>,
+) -> bool {
+    // The "magic knock": sample rates 200, 100, 80 in a row, then ask for
+    // the device ID.
+    let knock = [0xf3, 200, 0xf3, 100, 0xf3, 80, 0xf2];
+    if !knock
+        .iter()
+        .all(|&byte| send_mouse_command(command_port, data_port, status_port, byte))
+    {
+        return false;
+    }
+    let id = super::read_data(data_port, status_port);
+    // Put the sample rate back to the default.
+    for byte in [0xf3, 100] {
+        send_mouse_command(command_port, data_port, status_port, byte);
+    }
+    matches!(id, Some(3 | 4))
+}
+
+/// Enable IntelliMouse mode if available, then (re)start streaming, and
+/// reset the decoder for whichever packet size the mouse now sends.
+fn start_streaming() -> bool {
+    let mut command_port: Port<u8> = Port::new(super::PS2_COMMAND_PORT);
+    let mut data_port: Port<u8> = Port::new(super::PS2_DATA_PORT);
+    let mut status_port: Port<u8> = Port::new(super::PS2_STATUS_PORT);
+    let wheel = send_mouse_command(&mut command_port, &mut data_port, &mut status_port, 0xf5)
+        && enable_wheel(&mut command_port, &mut data_port, &mut status_port);
+    *DECODER.lock() = if wheel {
+        PacketDecoder::with_wheel()
+    } else {
+        PacketDecoder::new()
+    };
+    *LATEST_STATE.lock() = MouseState::new();
+    if wheel {
+        log::info!("[nitrogen] PS/2 mouse: wheel enabled");
+    }
+    send_mouse_command(&mut command_port, &mut data_port, &mut status_port, 0xf4)
+}
+
 /// Initialise the PS/2 mouse / touchpad.
 ///
 /// Tries the external `ps2-mouse` crate first.  If that fails we fall back to
@@ -118,16 +241,12 @@ pub fn init_mouse() -> Result<(), crate::DriverError> {
 
     // ── Attempt 1: external crate ──
     let mut mouse = Ps2MouseInner::new();
-    mouse.set_on_complete(|state: Ps2MouseState| {
-        let x = state.get_x();
-        let y = state.get_y();
-        let mut s = LATEST_STATE.lock();
-        s.x = s.x.saturating_add(x);
-        s.y = s.y.saturating_add(y);
-    });
     match mouse.init() {
         Ok(()) => {
             log::info!("[nitrogen] PS/2 mouse: external crate init succeeded");
+            if !start_streaming() {
+                log::warn!("[nitrogen] PS/2 mouse: restarting the stream failed");
+            }
             *MOUSE.lock() = Some(mouse);
             *BACKEND.lock() = Some(Backend::External);
             return Ok(());
@@ -149,14 +268,11 @@ pub fn init_mouse() -> Result<(), crate::DriverError> {
         &mut status_port,
         super::CMD_ENABLE_SECOND_PORT,
     ) || !send_mouse_command(&mut command_port, &mut data_port, &mut status_port, 0xf6)
-        || !send_mouse_command(&mut command_port, &mut data_port, &mut status_port, 0xf4)
+        || !start_streaming()
     {
         return Err(crate::DriverError::DeviceFault);
     }
 
-    *DECODER.lock() = PacketDecoder::new();
-    *LATEST_STATE.lock() = MouseState::new();
-    *LATEST_STATUS.lock() = 0;
     *BACKEND.lock() = Some(Backend::Internal);
     log::info!("[nitrogen] PS/2 mouse: hand-rolled fallback init succeeded");
     Ok(())
@@ -164,29 +280,11 @@ pub fn init_mouse() -> Result<(), crate::DriverError> {
 
 /// Feed one byte from IRQ12 into the mouse driver.
 pub fn handle_mouse_data(byte: u8) {
-    let backend = BACKEND.lock().clone();
-    match backend {
-        Some(Backend::External) => {
-            let mut idx = PACKET_IDX.lock();
-            if *idx == 0 {
-                *LATEST_STATUS.lock() = byte & 0x07;
-            }
-            *idx = (*idx + 1) % 3;
-            drop(idx);
-
-            if let Some(ref mut mouse) = *MOUSE.lock() {
-                mouse.process_packet(byte);
-            }
-        }
-        Some(Backend::Internal) => {
-            if let Some((delta, buttons)) = DECODER.lock().push(byte) {
-                let mut state = LATEST_STATE.lock();
-                state.x = state.x.saturating_add(delta.x);
-                state.y = state.y.saturating_add(delta.y);
-                *LATEST_STATUS.lock() = buttons;
-            }
-        }
-        None => {}
+    if BACKEND.lock().is_none() {
+        return;
+    }
+    if let Some(packet) = DECODER.lock().push(byte) {
+        LATEST_STATE.lock().accumulate(packet);
     }
 }
 
@@ -198,13 +296,15 @@ pub fn latest_state() -> MouseState {
 /// Drain accumulated movement while retaining the latest button state.
 pub fn consume_state() -> MouseState {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        core::mem::take(&mut *LATEST_STATE.lock())
+        let mut state = LATEST_STATE.lock();
+        let buttons = MouseState::with_buttons(state.buttons());
+        core::mem::replace(&mut *state, buttons)
     })
 }
 
 /// Return the latest button flags (bit 0 = left, bit 1 = right, bit 2 = middle).
 pub fn mouse_buttons() -> u8 {
-    x86_64::instructions::interrupts::without_interrupts(|| *LATEST_STATUS.lock())
+    latest_state().buttons()
 }
 
 #[cfg(test)]
@@ -216,7 +316,15 @@ mod tests {
         let mut decoder = PacketDecoder::new();
         assert_eq!(decoder.push(0x1b), None);
         assert_eq!(decoder.push(0xfe), None);
-        assert_eq!(decoder.push(0x05), Some((MouseState { x: -2, y: 5 }, 0x03)));
+        assert_eq!(
+            decoder.push(0x05),
+            Some(MouseState {
+                x: -2,
+                y: 5,
+                wheel: 0,
+                ..MouseState::with_buttons(0x03)
+            })
+        );
     }
 
     #[test]
@@ -224,13 +332,26 @@ mod tests {
         let mut decoder = PacketDecoder::new();
         decoder.push(0x08);
         decoder.push(0xff);
-        assert_eq!(decoder.push(0x80), Some((MouseState { x: 255, y: 128 }, 0)));
+        assert_eq!(
+            decoder.push(0x80),
+            Some(MouseState {
+                x: 255,
+                y: 128,
+                wheel: 0,
+                ..MouseState::with_buttons(0)
+            })
+        );
 
         decoder.push(0x38);
         decoder.push(0x00);
         assert_eq!(
             decoder.push(0x7f),
-            Some((MouseState { x: -256, y: -129 }, 0))
+            Some(MouseState {
+                x: -256,
+                y: -129,
+                wheel: 0,
+                ..MouseState::with_buttons(0)
+            })
         );
     }
 
@@ -240,7 +361,15 @@ mod tests {
         assert_eq!(decoder.push(0x01), None);
         assert_eq!(decoder.push(0x28), None);
         assert_eq!(decoder.push(0x01), None);
-        assert_eq!(decoder.push(0xff), Some((MouseState { x: 1, y: -1 }, 0)));
+        assert_eq!(
+            decoder.push(0xff),
+            Some(MouseState {
+                x: 1,
+                y: -1,
+                wheel: 0,
+                ..MouseState::with_buttons(0)
+            })
+        );
     }
 
     #[test]
@@ -248,6 +377,55 @@ mod tests {
         let mut decoder = PacketDecoder::new();
         decoder.push(0xc9);
         decoder.push(0x7f);
-        assert_eq!(decoder.push(0x7f), Some((MouseState::new(), 1)));
+        assert_eq!(decoder.push(0x7f), Some(MouseState::with_buttons(1)));
+    }
+
+    #[test]
+    fn motion_accumulates_and_buttons_follow_the_latest_packet() {
+        let mut decoder = PacketDecoder::new();
+        let mut state = MouseState::new();
+        for byte in [0x0d, 0x03, 0x01, 0x0a, 0x02, 0x01] {
+            if let Some(packet) = decoder.push(byte) {
+                state.accumulate(packet);
+            }
+        }
+        assert_eq!((state.get_x(), state.get_y()), (5, 2));
+        assert!(!state.left_button_down());
+        assert!(state.right_button_down());
+        assert!(!state.middle_button_down());
+        assert_eq!(state.buttons(), 0x02);
+    }
+
+    #[test]
+    fn wheel_packets_carry_a_signed_fourth_byte() {
+        let mut decoder = PacketDecoder::with_wheel();
+        decoder.push(0x09);
+        decoder.push(0x02);
+        assert_eq!(decoder.push(0x03), None);
+        assert_eq!(
+            decoder.push(0x0f),
+            Some(MouseState {
+                x: 2,
+                y: 3,
+                wheel: -1,
+                ..MouseState::with_buttons(1)
+            })
+        );
+
+        // A dropped status byte costs the packet, then decoding recovers.
+        decoder.push(0x01);
+        decoder.push(0x00);
+        decoder.push(0x08);
+        decoder.push(0x00);
+        decoder.push(0x00);
+        assert_eq!(
+            decoder.push(0x01),
+            Some(MouseState {
+                x: 0,
+                y: 0,
+                wheel: 1,
+                ..MouseState::with_buttons(0)
+            })
+        );
     }
 }
@@ -4,14 +4,21 @@
 //! HBA memory registers, resets ports, sends IDENTIFY DEVICE, and reads
 //! sectors via DMA.
 //!
+//! Every port issues one command at a time through command slot 0, whose
+//! single PRDT entry points at a page-sized bounce buffer, so each READ DMA
+//! EXT moves one sector.  Completion is polled rather than interrupt-driven.
+//!
 //! # References
 //! - Serial ATA AHCI 1.3.1 Specification
 //! - Serial ATA Revision 3.0
 
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::ptr;
+use core::sync::atomic::{Ordering, fence};
 use spin::Mutex;
 
+use crate::DriverError;
 use crate::driver_context::DriverContext;
 use crate::pci::{PciDevice, PciScanner};
 
@@ -35,8 +42,19 @@ const PXFBU: usize = 0x0C; // FIS Base Address Upper
 const PXIS: usize = 0x10; // Interrupt Status
 const PXIE: usize = 0x14; // Interrupt Enable
 const PXCMD: usize = 0x18; // Command and Status
+const PXTFD: usize = 0x20; // Task File Data
+const PXSIG: usize = 0x24; // Signature
 const PXSSTS: usize = 0x28; // SATA Status (SCR0: SStatus)
 const PXSERR: usize = 0x30; // SATA Error (SCR1: SError)
+const PXCI: usize = 0x38; // Command Issue
+
+// ── PxIS bits ────────────────────────────────────────────────────
+const PXIS_TFES: u32 = 1 << 30; // Task File Error Status
+
+// ── PxTFD status bits ────────────────────────────────────────────
+const TFD_ERR: u32 = 1 << 0;
+const TFD_DRQ: u32 = 1 << 3;
+const TFD_BSY: u32 = 1 << 7;
 
 // ── PxCMD bits ───────────────────────────────────────────────────
 const PXCMD_ST: u32 = 1 << 0; // Start DMA
@@ -48,6 +66,28 @@ const PXCMD_CR: u32 = 1 << 15; // Command List Running
 const SSTS_DET_MASK: u32 = 0x0F;
 const SSTS_DET_PHY_OK: u32 = 0x03;
 
+/// PxSIG of a plain ATA disk; ATAPI, port multipliers and enclosure
+/// bridges report other values.
+const SIG_ATA: u32 = 0x0000_0101;
+
+// ── ATA commands ─────────────────────────────────────────────────
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+const ATA_CMD_READ_DMA_EXT: u8 = 0x25;
+const ATA_CMD_IDENTIFY: u8 = 0xEC;
+/// Device register value selecting LBA addressing.
+const ATA_DEVICE_LBA: u8 = 1 << 6;
+
+/// Length of a Register H2D FIS in dwords, as stored in the command header.
+const H2D_FIS_DWORDS: u32 = 5;
+
+/// Bounce buffer per port; also the largest sector size supported.
+const DATA_BUFFER_BYTES: usize = 4096;
+
+/// Time allowed for a port to go idle before a command is issued.
+const PORT_IDLE_TIMEOUT_US: u64 = 1_000_000;
+/// Time allowed for a single command to complete.
+const COMMAND_TIMEOUT_US: u64 = 5_000_000;
+
 // ── Command Header ───────────────────────────────────────────────
 #[repr(C)]
 struct CommandHeader {
@@ -81,38 +121,50 @@ struct PrdtEntry {
 struct ReceivedFis {
     dsfis: [u8; 28],
     pad0: [u8; 4],
-    psfis: [u8; 24],
-    pad1: [u8; 8],
-    rfis: [u8; 24],
+    psfis: [u8; 20],
+    pad1: [u8; 12],
+    rfis: [u8; 20],
     pad2: [u8; 4],
     sdbfis: [u8; 8],
     ufis: [u8; 64],
     rsvd: [u8; 96],
 }
 
+// The HBA writes received FISes into this page at fixed offsets.
+const _: () = assert!(size_of::<ReceivedFis>() == 256);
+
 // ── Controller ───────────────────────────────────────────────────
 
-#[allow(dead_code)]
+/// A disk found by IDENTIFY DEVICE.
+#[derive(Debug, Clone)]
+pub struct DiskInfo {
+    /// Port the disk is attached to.
+    pub port: u8,
+    /// Model string, trimmed.
+    pub model: String,
+    /// Logical sector size in bytes.
+    pub sector_size: u32,
+    /// Addressable logical sectors.
+    pub total_sectors: u64,
+}
+
 struct AhciPort {
     index: u8,
-    hba_mmio: *mut u32,
     port_mmio: *mut u32,
     cmd_list: *mut CommandHeader,
-    cmd_list_phys: u64,
-    fis: *mut ReceivedFis,
-    fis_phys: u64,
     cmd_table: *mut CommandTable,
-    cmd_table_phys: u64,
+    data: *mut u8,
+    disk: Option<DiskInfo>,
 }
 
 pub struct AhciController {
     #[allow(dead_code)]
     device: PciDevice,
     hba_mmio: *mut u32,
-    #[allow(dead_code)]
-    hba_phys: u64,
     /// Number of implemented ports (0–31).
     num_ports: u32,
+    /// Ports with a link and command memory set up.
+    ports: Vec<AhciPort>,
 }
 
 // SAFETY: Single-threaded kernel — all device MMIO pointers are
@@ -126,21 +178,16 @@ impl AhciController {
     /// `ctx` provides memory allocation, MMIO mapping, and address
     /// translation services (typically the kernel's [`DriverContext`]).
     pub fn init(ctx: &dyn DriverContext, device: PciDevice) -> Option<Self> {
-        let bar5 = device.get_bar_info(5)?;
-        if bar5.is_io {
-            return None;
-        }
-        let hba_phys = bar5.address;
-        let hba_virt = ctx.phys_to_virt(hba_phys) as *mut u32;
-
-        ctx.map_mmio_region(hba_phys as usize, hba_virt as usize, bar5.size as usize)
-            .ok()?;
+        let hba_virt = device
+            .map_bar(5, ctx)
+            .inspect_err(|e| log::warn!("AHCI: cannot map ABAR: {}", e))
+            .ok()? as *mut u32;
 
         let mut ctrl = Self {
             device,
             hba_mmio: hba_virt,
-            hba_phys,
             num_ports: 0,
+            ports: Vec::new(),
         };
 
         let ghc = ctrl.r32(HBA_GHC);
@@ -150,6 +197,8 @@ impl AhciController {
             log::warn!("AHCI: HBA reset timed out — controller may be unresponsive");
             ctrl.w32(HBA_GHC, ctrl.r32(HBA_GHC) & !GHC_HR);
         }
+        // The reset clears GHC.AE on controllers that also support legacy mode.
+        ctrl.w32(HBA_GHC, ctrl.r32(HBA_GHC) | GHC_AE);
 
         let pi = ctrl.r32(HBA_PI);
         ctrl.num_ports = pi.count_ones() as u32;
@@ -166,13 +215,16 @@ impl AhciController {
                 log::info!("AHCI port {}: no PHY (SSTS={:#x}), skipping init", i, ssts);
                 continue;
             }
-            ctrl.init_port(ctx, i);
+            if let Some(mut port) = ctrl.init_port(ctx, i) {
+                port.disk = ctrl.identify(&port);
+                ctrl.ports.push(port);
+            }
         }
 
         Some(ctrl)
     }
 
-    fn init_port(&self, ctx: &dyn DriverContext, port: u8) {
+    fn init_port(&self, ctx: &dyn DriverContext, port: u8) -> Option<AhciPort> {
         let port_base = 0x100 + (port as usize) * 0x80;
         let port_mmio = unsafe { self.hba_mmio.add(port_base / 4) };
 
@@ -188,49 +240,28 @@ impl AhciController {
         let det = ssts & SSTS_DET_MASK;
         if det != SSTS_DET_PHY_OK {
             log::info!("AHCI port {}: no device (SSTS={:#x})", port, ssts);
-            return;
+            return None;
         }
 
-        let cmd_list_phys = match ctx.allocate_frame() {
+        // Command list, received FIS, command table and data buffer each
+        // take one page.
+        let frames = match ctx.allocate_contiguous_frames(4) {
             Ok(phys) => phys,
             Err(e) => {
-                log::error!(
-                    "AHCI port {}: failed to allocate cmd_list frame: {}",
-                    port,
-                    e
-                );
-                return;
+                log::error!("AHCI port {}: failed to allocate port memory: {}", port, e);
+                return None;
             }
         };
+        let cmd_list_phys = frames;
+        let fis_phys = frames + 0x1000;
+        let cmd_table_phys = frames + 0x2000;
+        let data_phys = frames + 0x3000;
         let cmd_list = ctx.phys_to_virt(cmd_list_phys) as *mut CommandHeader;
-        let fis_phys = match ctx.allocate_frame() {
-            Ok(phys) => phys,
-            Err(e) => {
-                log::error!("AHCI port {}: failed to allocate FIS frame: {}", port, e);
-                ctx.free_frame(cmd_list_phys);
-                return;
-            }
-        };
-        let fis = ctx.phys_to_virt(fis_phys) as *mut ReceivedFis;
-        let cmd_table_phys = match ctx.allocate_frame() {
-            Ok(phys) => phys,
-            Err(e) => {
-                log::error!(
-                    "AHCI port {}: failed to allocate cmd_table frame: {}",
-                    port,
-                    e
-                );
-                ctx.free_frame(cmd_list_phys);
-                ctx.free_frame(fis_phys);
-                return;
-            }
-        };
         let cmd_table = ctx.phys_to_virt(cmd_table_phys) as *mut CommandTable;
+        let data = ctx.phys_to_virt(data_phys) as *mut u8;
 
         unsafe {
-            ptr::write_bytes(cmd_list as *mut u8, 0, 4096);
-            ptr::write_bytes(fis as *mut u8, 0, 4096);
-            ptr::write_bytes(cmd_table as *mut u8, 0, 4096);
+            ptr::write_bytes(ctx.phys_to_virt(frames) as *mut u8, 0, 4 * 4096);
         }
 
         self.w32_port(port_mmio, PXCLB, cmd_list_phys as u32);
@@ -242,6 +273,8 @@ impl AhciController {
             (*cmd_list).ctba = cmd_table_phys as u32;
             (*cmd_list).ctbau = (cmd_table_phys >> 32) as u32;
             (*cmd_list).dword0 = 0;
+            (*cmd_table).prdt[0].dba = data_phys as u32;
+            (*cmd_table).prdt[0].dbau = (data_phys >> 32) as u32;
         }
 
         self.w32_port(port_mmio, PXSERR, 0xFFFFFFFF);
@@ -249,6 +282,134 @@ impl AhciController {
         self.w32_port(port_mmio, PXIE, 0);
 
         self.w32_port(port_mmio, PXCMD, cmd | PXCMD_FRE | PXCMD_ST);
+
+        Some(AhciPort {
+            index: port,
+            port_mmio,
+            cmd_list,
+            cmd_table,
+            data,
+            disk: None,
+        })
+    }
+
+    /// Send IDENTIFY DEVICE to the disk on `port`, if one is attached.
+    fn identify(&self, port: &AhciPort) -> Option<DiskInfo> {
+        let sig = self.r32_port(port.port_mmio, PXSIG);
+        if sig != SIG_ATA {
+            log::info!(
+                "AHCI port {}: signature {:#010x} is not an ATA disk",
+                port.index,
+                sig
+            );
+            return None;
+        }
+        let fis = h2d_fis(ATA_CMD_IDENTIFY, 0, 0);
+        if let Err(e) = self.issue(port, &fis, 512) {
+            log::warn!("AHCI port {}: IDENTIFY DEVICE failed: {}", port.index, e);
+            return None;
+        }
+        let mut words = [0u16; 256];
+        for (index, word) in words.iter_mut().enumerate() {
+            *word = unsafe { ptr::read_volatile((port.data as *const u16).add(index)) };
+        }
+        let disk = parse_identify(port.index, &words);
+        match &disk {
+            Some(disk) => log::info!(
+                "AHCI port {}: {} ({} sectors of {} bytes)",
+                port.index,
+                disk.model,
+                disk.total_sectors,
+                disk.sector_size
+            ),
+            None => log::warn!("AHCI port {}: unusable IDENTIFY data", port.index),
+        }
+        disk
+    }
+
+    /// Issue `fis` through command slot 0 with a `len`-byte read into the
+    /// port's data buffer, and wait for it to complete.
+    fn issue(&self, port: &AhciPort, fis: &[u8; 20], len: usize) -> Result<(), DriverError> {
+        let mmio = port.port_mmio;
+        crate::timing::wait_timeout_us(PORT_IDLE_TIMEOUT_US, || {
+            self.r32_port(mmio, PXTFD) & (TFD_BSY | TFD_DRQ) == 0
+        })
+        .map_err(|_| DriverError::Busy)?;
+
+        self.w32_port(mmio, PXIS, 0xFFFFFFFF);
+        unsafe {
+            let table = &mut *port.cmd_table;
+            table.cfis = [0; 64];
+            table.cfis[..fis.len()].copy_from_slice(fis);
+            table.prdt[0].dbc = (len as u32 - 1) & 0x003F_FFFF;
+            let header = &mut *port.cmd_list;
+            header.dword0 = H2D_FIS_DWORDS | (1 << 16);
+            header.prdbc = 0;
+        }
+        // The HBA reads the command header and table once CI is set.
+        fence(Ordering::SeqCst);
+        self.w32_port(mmio, PXCI, 1);
+
+        let done = crate::timing::poll_timeout_us(COMMAND_TIMEOUT_US, || {
+            if self.r32_port(mmio, PXIS) & PXIS_TFES != 0 {
+                Some(Err(DriverError::Io))
+            } else if self.r32_port(mmio, PXCI) & 1 == 0 {
+                Some(Ok(()))
+            } else {
+                None
+            }
+        });
+        fence(Ordering::SeqCst);
+        match done {
+            Some(Ok(())) if self.r32_port(mmio, PXTFD) & TFD_ERR == 0 => Ok(()),
+            Some(Ok(())) | Some(Err(_)) => {
+                log::warn!(
+                    "AHCI port {}: command failed (TFD={:#x}, IS={:#x})",
+                    port.index,
+                    self.r32_port(mmio, PXTFD),
+                    self.r32_port(mmio, PXIS)
+                );
+                Err(DriverError::Io)
+            }
+            None => Err(DriverError::TimedOut),
+        }
+    }
+
+    /// Read `count` sectors starting at `lba` from the first disk into
+    /// `buffer`, one sector per command.
+    pub fn read_sectors(&self, lba: u64, count: u16, buffer: &mut [u8]) -> Result<(), DriverError> {
+        let (port, disk) = self
+            .ports
+            .iter()
+            .find_map(|port| Some((port, port.disk.as_ref()?)))
+            .ok_or(DriverError::DeviceNotFound)?;
+        let sector_size = disk.sector_size as usize;
+        let bytes = usize::from(count)
+            .checked_mul(sector_size)
+            .ok_or(DriverError::InvalidArgument)?;
+        let destination = buffer
+            .get_mut(..bytes)
+            .ok_or(DriverError::InvalidArgument)?;
+        if lba
+            .checked_add(u64::from(count))
+            .is_none_or(|end| end > disk.total_sectors)
+        {
+            return Err(DriverError::InvalidArgument);
+        }
+        for (sector_lba, sector) in (lba..).zip(destination.chunks_exact_mut(sector_size)) {
+            self.issue(
+                port,
+                &h2d_fis(ATA_CMD_READ_DMA_EXT, sector_lba, 1),
+                sector_size,
+            )?;
+            unsafe { ptr::copy_nonoverlapping(port.data, sector.as_mut_ptr(), sector_size) };
+        }
+        Ok(())
+    }
+
+    /// The first disk found on the controller.
+    pub fn disk(&self) -> Option<&DiskInfo> {
+        self.ports.iter().find_map(|port| port.disk.as_ref())
     }
 
     fn r32(&self, off: usize) -> u32 {
@@ -277,8 +438,86 @@ impl AhciController {
     }
 }
 
+/// Build a Register Host-to-Device FIS for an LBA48 `command` on `count`
+/// sectors at `lba`.
+fn h2d_fis(command: u8, lba: u64, count: u16) -> [u8; 20] {
+    let lba = lba.to_le_bytes();
+    let count = count.to_le_bytes();
+    let mut fis = [0u8; 20];
+    fis[0] = FIS_TYPE_REG_H2D;
+    fis[1] = 1 << 7; // C: this FIS carries a command
+    fis[2] = command;
+    fis[4..7].copy_from_slice(&lba[..3]);
+    fis[7] = ATA_DEVICE_LBA;
+    fis[8..11].copy_from_slice(&lba[3..6]);
+    fis[12..14].copy_from_slice(&count);
+    fis
+}
+
+/// Decode the IDENTIFY DEVICE words of the disk on `port`.
+///
+/// `None` if the disk reports no capacity or a sector larger than the
+/// port's bounce buffer.
+fn parse_identify(port: u8, words: &[u16; 256]) -> Option<DiskInfo> {
+    let dword = |index: usize| u32::from(words[index]) | u32::from(words[index + 1]) << 16;
+    let lba48 = words[83] & (1 << 10) != 0;
+    let total_sectors = if lba48 {
+        u64::from(dword(100)) | u64::from(dword(102)) << 32
+    } else {
+        u64::from(dword(60))
+    };
+    // Word 106 is valid when bits 15:14 read 01; bit 12 then says that
+    // words 117–118 hold the logical sector size in 16-bit words.
+    let sector_size = if words[106] & 0xC000 == 0x4000 && words[106] & (1 << 12) != 0 {
+        dword(117).checked_mul(2)?
+    } else {
+        512
+    };
+    if total_sectors == 0 || sector_size == 0 || sector_size as usize > DATA_BUFFER_BYTES {
+        return None;
+    }
+    // The model string is stored with the bytes of each word swapped.
+    let model = words[27..47]
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .map(char::from)
+        .collect::<String>()
+        .trim()
+        .into();
+    Some(DiskInfo {
+        port,
+        model,
+        sector_size,
+        total_sectors,
+    })
+}
+
 // ── Globals ──────────────────────────────────────────────────────
 
+/// Initialise the AHCI controller `device` and add it to the global list.
+pub fn attach(ctx: &dyn DriverContext, device: &PciDevice) -> Result<(), DriverError> {
+    log::info!(
+        "AHCI: found device {:#06x}:{:#06x}",
+        device.vendor_id,
+        device.device_id
+    );
+    // Programming interface 01h is AHCI; 00h is a vendor-specific IDE mode.
+    if device.prog_if != 0x01 {
+        log::info!(
+            "AHCI: controller is not in AHCI mode (prog-if {:#04x})",
+            device.prog_if
+        );
+        return Err(DriverError::NotSupported);
+    }
+    if !device.prepare_mmio() {
+        return Err(DriverError::NotReady);
+    }
+    let ctrl = AhciController::init(ctx, device.clone()).ok_or(DriverError::MmioMappingFailed)?;
+    log::info!("AHCI: controller initialised ({} ports)", ctrl.num_ports);
+    CONTROLLERS.lock().push(ctrl);
+    Ok(())
+}
+
 /// Initialise all AHCI controllers found on the PCI bus.
 ///
 /// `ctx` provides memory allocation and MMIO mapping services.
@@ -287,19 +526,77 @@ pub fn init(ctx: &dyn DriverContext) {
     let _ = scanner.scan_all_buses();
     for dev in scanner.get_devices() {
         if dev.class_code == 0x01 && dev.subclass == 0x06 {
-            log::info!(
-                "AHCI: found device {:#06x}:{:#06x}",
-                dev.vendor_id,
-                dev.device_id
-            );
-            dev.enable_memory_access();
-            if let Some(ctrl) = AhciController::init(ctx, dev.clone()) {
-                log::info!("AHCI: controller initialised ({} ports)", ctrl.num_ports);
-                CONTROLLERS.lock().push(ctrl);
-            }
+            let _ = attach(ctx, dev);
         }
     }
     if CONTROLLERS.lock().is_empty() {
         log::info!("AHCI: no SATA controllers found");
     }
 }
+
+/// The first disk on any attached controller.
+pub fn disk_info() -> Option<DiskInfo> {
+    CONTROLLERS
+        .lock()
+        .iter()
+        .find_map(|ctrl| ctrl.disk().cloned())
+}
+
+/// Read from the disk reported by [`disk_info`].
+pub fn read_sectors(lba: u64, count: u16, buffer: &mut [u8]) -> Result<(), DriverError> {
+    CONTROLLERS
+        .lock()
+        .iter()
+        .find(|ctrl| ctrl.disk().is_some())
+        .ok_or(DriverError::DeviceNotFound)?
+        .read_sectors(lba, count, buffer)
+}
+
+pub fn is_present() -> bool {
+    !CONTROLLERS.lock().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_lba48_command_fis() {
+        let fis = h2d_fis(ATA_CMD_READ_DMA_EXT, 0x0605_0403_0201, 1);
+        assert_eq!(&fis[..4], [0x27, 0x80, 0x25, 0x00]);
+        assert_eq!(&fis[4..8], [0x01, 0x02, 0x03, 0x40]);
+        assert_eq!(&fis[8..11], [0x04, 0x05, 0x06]);
+        assert_eq!(&fis[12..14], [0x01, 0x00]);
+    }
+
+    fn identify_words(model: &str) -> [u16; 256] {
+        let mut words = [0u16; 256];
+        let padded = alloc::format!("{model:<40}");
+        for (word, pair) in words[27..47].iter_mut().zip(padded.as_bytes().chunks(2)) {
+            *word = u16::from_be_bytes([pair[0], pair[1]]);
+        }
+        words
+    }
+
+    #[test]
+    fn decodes_identify_data() {
+        let mut words = identify_words("QEMU HARDDISK");
+        words[60] = 0x0000;
+        words[61] = 0x0010; // 1 Mi sectors through the 28-bit field
+        let disk = parse_identify(0, &words).unwrap();
+        assert_eq!(disk.model, "QEMU HARDDISK");
+        assert_eq!((disk.total_sectors, disk.sector_size), (0x10_0000, 512));
+
+        // LBA48 capacity beyond 2 TiB and 4 KiB logical sectors.
+        words[83] = 1 << 10;
+        words[100..104].copy_from_slice(&[0x0000, 0x0000, 0x0001, 0x0000]);
+        words[106] = 0x4000 | (1 << 12);
+        words[117..119].copy_from_slice(&[2048, 0]);
+        let disk = parse_identify(3, &words).unwrap();
+        assert_eq!((disk.port, disk.total_sectors), (3, 1 << 32));
+        assert_eq!(disk.sector_size, 4096);
+
+        // No capacity means no usable disk.
+        assert!(parse_identify(0, &identify_words("EMPTY")).is_none());
+    }
+}
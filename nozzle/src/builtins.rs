@@ -75,11 +75,15 @@ macro_rules! sys_info_cmd {
 }
 
 sys_info_cmd!(cmd_mem, "mem");
+sys_info_cmd!(cmd_free, "free");
 sys_info_cmd!(cmd_metrics, "metrics");
 sys_info_cmd!(cmd_cpuinfo, "cpuinfo");
 sys_info_cmd!(cmd_tasks, "tasks");
+sys_info_cmd!(cmd_ps, "ps");
+sys_info_cmd!(cmd_top, "top");
 sys_info_cmd!(cmd_windows, "windows");
 sys_info_cmd!(cmd_dmesg, "dmesg");
+sys_info_cmd!(cmd_loglevel, "loglevel");
 
 /// `hexdump` — show hex dump of provided string
 pub fn cmd_hexdump(ctx: &mut CommandContext) -> bool {
@@ -150,6 +154,7 @@ pub fn cmd_run(ctx: &mut CommandContext) -> bool {
 
 sys_info_cmd!(cmd_taskmon, "taskmon");
 sys_info_cmd!(cmd_devices, "devices");
+sys_info_cmd!(cmd_keymap, "keymap");
 
 /// `theme` — show or change the desktop theme
 pub fn cmd_theme(ctx: &mut CommandContext) -> bool {
@@ -312,6 +317,7 @@ pub fn cmd_df(ctx: &mut CommandContext) -> bool {
 
 sys_info_cmd!(cmd_date, "date");
 sys_info_cmd!(cmd_uptime, "uptime");
+sys_info_cmd!(cmd_clockcheck, "clockcheck");
 
 /// `whoami` — print current user name
 pub fn cmd_whoami(ctx: &mut CommandContext) -> bool {
@@ -343,6 +349,17 @@ pub fn cmd_sleep(ctx: &mut CommandContext) -> bool {
     true
 }
 
+/// `kill` — send SIGTERM (or the given signal) to a process
+pub fn cmd_kill(ctx: &mut CommandContext) -> bool {
+    if ctx.args.len() < 2 {
+        ctx.terminal
+            .write_str("Usage: kill [-9|-15|-KILL|-TERM] <pid>\n");
+        return true;
+    }
+    crate::sys_hooks::call_sys_info_hook(ctx, "kill");
+    true
+}
+
 /// `grep` — search for a pattern in input (stdin or files)
 pub fn cmd_grep(ctx: &mut CommandContext) -> bool {
     if ctx.args.len() < 2 {
@@ -0,0 +1,153 @@
+//! Tab completion candidates for the Nozzle line editor.
+//!
+//! The first word on a line completes against the shell's command table;
+//! later words complete as paths, using the directory listing from
+//! [`FsHooks::entries`](crate::fs_hooks::FsHooks::entries).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use carrier::exec::Command;
+
+use crate::fs_hooks::ListDirFn;
+
+/// Where completion candidates come from.
+#[derive(Clone, Copy)]
+pub struct Completer<'a> {
+    commands: &'a [&'a dyn Command],
+    entries: Option<ListDirFn>,
+}
+
+impl<'a> Completer<'a> {
+    pub const fn new(commands: &'a [&'a dyn Command], entries: Option<ListDirFn>) -> Self {
+        Self { commands, entries }
+    }
+
+    /// Complete the last word of `line`.
+    ///
+    /// Returns the byte offset at which the completed text starts and the
+    /// sorted candidates that may replace `line[start..]`.  Directory
+    /// candidates end in `/`.
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let word_start = line.rfind(' ').map_or(0, |space| space + 1);
+        let word = &line[word_start..];
+        if !line[..word_start].trim().is_empty() {
+            return self.complete_path(word_start, word);
+        }
+        let mut candidates: Vec<String> = self
+            .commands
+            .iter()
+            .map(|command| command.name())
+            .filter(|name| name.starts_with(word))
+            .map(String::from)
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        (word_start, candidates)
+    }
+
+    fn complete_path(&self, word_start: usize, word: &str) -> (usize, Vec<String>) {
+        let (directory, name) = match word.rfind('/') {
+            Some(0) => ("/", &word[1..]),
+            Some(slash) => (&word[..slash], &word[slash + 1..]),
+            None => (".", word),
+        };
+        let start = word_start + word.len() - name.len();
+        let Some(entries) = self.entries.and_then(|entries| entries(directory)) else {
+            return (start, Vec::new());
+        };
+        let mut candidates: Vec<String> = entries
+            .into_iter()
+            .filter(|entry| entry.name.starts_with(name))
+            // Hidden entries only complete once a dot is typed.
+            .filter(|entry| name.starts_with('.') || !entry.name.starts_with('.'))
+            .map(|entry| {
+                let mut candidate = entry.name;
+                if entry.is_dir {
+                    candidate.push('/');
+                }
+                candidate
+            })
+            .collect();
+        candidates.sort_unstable();
+        (start, candidates)
+    }
+}
+
+/// Longest prefix shared by every candidate.
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let Some((first, rest)) = candidates.split_first() else {
+        return "";
+    };
+    let mut prefix = first.as_str();
+    for candidate in rest {
+        while !candidate.starts_with(prefix) {
+            let last = prefix.chars().next_back().map_or(0, char::len_utf8);
+            prefix = &prefix[..prefix.len() - last];
+        }
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_hooks::DirEntry;
+    use carrier::exec::NamedCommand;
+
+    fn ok(_: &mut carrier::exec::CommandContext) -> bool {
+        true
+    }
+
+    const fn command(name: &'static str) -> NamedCommand {
+        NamedCommand {
+            name,
+            description: "",
+            func: ok,
+        }
+    }
+
+    static COMMANDS: [NamedCommand; 3] = [command("cat"), command("cd"), command("clear")];
+
+    fn entries(directory: &str) -> Option<Vec<DirEntry>> {
+        let names: &[(&str, bool)] = match directory {
+            "." => &[("docs", true), ("data.txt", false), (".hidden", false)],
+            "docs" => &[("readme.md", false)],
+            _ => return None,
+        };
+        Some(
+            names
+                .iter()
+                .map(|&(name, is_dir)| DirEntry {
+                    name: String::from(name),
+                    is_dir,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn first_word_completes_commands() {
+        let commands: Vec<&dyn Command> = COMMANDS.iter().map(|c| c as &dyn Command).collect();
+        let completer = Completer::new(&commands, Some(entries));
+        let (start, candidates) = completer.complete("c");
+        assert_eq!(start, 0);
+        assert_eq!(candidates, ["cat", "cd", "clear"]);
+        assert_eq!(common_prefix(&candidates), "c");
+        assert_eq!(completer.complete("cl").1, ["clear"]);
+    }
+
+    #[test]
+    fn later_words_complete_paths() {
+        let completer = Completer::new(&[], Some(entries));
+        let (start, candidates) = completer.complete("cat d");
+        assert_eq!(start, 4);
+        assert_eq!(candidates, ["data.txt", "docs/"]);
+        assert_eq!(common_prefix(&candidates), "d");
+
+        let (start, candidates) = completer.complete("cat docs/r");
+        assert_eq!(start, 9);
+        assert_eq!(candidates, ["readme.md"]);
+        assert_eq!(completer.complete("cat .").1, [".hidden"]);
+        assert!(completer.complete("cat missing/x").1.is_empty());
+    }
+}
@@ -3,13 +3,26 @@
 //! Nozzle has no direct knowledge of the kernel's VFS.  These hooks
 //! allow the kernel to register callbacks which the `ls`, `cat`,
 //! `pwd`, `cd`, `tree`, `find`, `cp`, `mv`, and `write` commands
-//! call into.
+//! call into, which Tab completion uses to list directories, and which
+//! `>` / `>>` redirection writes through.
 //!
 //! All function pointers are bundled into a single [`FsHooks`] value which is
 //! constructor-injected into a shell session.
 
+use alloc::string::String;
+use alloc::vec::Vec;
 use carrier::exec::CommandContext;
 
+/// One entry of a directory listing used for path completion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Directory listing callback, see [`FsHooks::entries`].
+pub type ListDirFn = fn(&str) -> Option<Vec<DirEntry>>;
+
 /// Aggregated filesystem hooks for shell built‑in commands.
 ///
 /// Construct this value at the integration boundary and pass it through
@@ -29,8 +42,22 @@ pub struct FsHooks {
     pub mkdir: Option<fn(&mut CommandContext, &str)>,
     pub touch: Option<fn(&mut CommandContext, &str)>,
     pub df: Option<fn(&mut CommandContext)>,
+    /// List a directory (relative to the working directory) for Tab
+    /// completion; `None` if it cannot be read.
+    pub entries: Option<ListDirFn>,
+    /// Make a path ready for redirected output before the command runs:
+    /// truncate or create it, or with `append` create it if missing.
+    pub open_output: Option<OpenOutputFn>,
+    /// Append a command's redirected output to a path made ready by
+    /// [`FsHooks::open_output`].
+    pub append_output: Option<AppendOutputFn>,
 }
 
+/// Redirection target setup, see [`FsHooks::open_output`].
+pub type OpenOutputFn = fn(&str, bool) -> Result<(), String>;
+/// Redirected output sink, see [`FsHooks::append_output`].
+pub type AppendOutputFn = fn(&str, &[u8]) -> Result<(), String>;
+
 impl FsHooks {
     /// Build a no‑op set of hooks (every field is `None`).
     pub const fn none() -> Self {
@@ -48,6 +75,9 @@ impl FsHooks {
             mkdir: None,
             touch: None,
             df: None,
+            entries: None,
+            open_output: None,
+            append_output: None,
         }
     }
 }
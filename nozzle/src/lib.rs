@@ -2,6 +2,7 @@
 extern crate alloc;
 
 pub mod builtins;
+pub mod completion;
 pub mod fs_hooks;
 pub mod line_editor;
 pub mod prompt;
@@ -13,9 +14,10 @@ use alloc::string::String;
 
 // Re-export carrier types so existing consumers still work
 pub use carrier::exec::{Command, CommandContext, NamedCommand};
-pub use carrier::pipeline::ParsedCommand;
+pub use carrier::pipeline::{ParsedCommand, Pipeline, Redirect};
 pub use carrier::terminal::Terminal;
 
+pub use completion::Completer;
 pub use line_editor::LineEditor;
 pub use prompt::Prompt;
 
@@ -62,10 +64,12 @@ impl<'a> Shell<'a> {
         commands: &'a [&'a dyn Command],
         services: ShellServices,
     ) -> Self {
+        let mut editor = LineEditor::new();
+        editor.set_prompt(DEFAULT_PROMPT);
         Self {
             terminal,
             commands,
-            editor: LineEditor::new(),
+            editor,
             prompt: Prompt::new(DEFAULT_PROMPT),
             welcome_shown: false,
             services,
@@ -74,6 +78,7 @@ impl<'a> Shell<'a> {
 
     pub fn set_prompt(&mut self, text: impl Into<String>) {
         self.prompt.set_text(text);
+        self.editor.set_prompt(self.prompt.as_str());
     }
 
     pub fn run(&mut self) {
@@ -100,10 +105,11 @@ impl<'a> Shell<'a> {
             return;
         }
 
+        let completer = Completer::new(self.commands, self.services.fs.entries);
         loop {
             self.terminal.write_str(self.prompt.as_str());
 
-            let line = match self.editor.read_line(&mut *self.terminal) {
+            let line = match self.editor.read_line(&mut *self.terminal, &completer) {
                 Some(l) => l,
                 None => {
                     continue;
@@ -128,12 +134,64 @@ impl<'a> Shell<'a> {
         if trimmed.is_empty() {
             return true;
         }
-        carrier::exec::dispatch_with_services(
+        let pipeline = carrier::pipeline::Pipeline::parse(trimmed);
+        if pipeline.commands.is_empty() {
+            return true;
+        }
+        // The target is opened before anything runs, so a bad path stops
+        // the command instead of letting its output reach the console.
+        if let Some(redirect) = &pipeline.stdout
+            && let Err(error) = self.open_output(redirect)
+        {
+            self.terminal.write_str(&error);
+            return true;
+        }
+        // Lines made only of program names run as processes joined by
+        // kernel pipes; built-ins keep the in-shell pipeline.
+        if let Some(spawn) = self.services.sys.spawn
+            && pipeline
+                .commands
+                .iter()
+                .all(|stage| !self.is_builtin(&stage.name))
+        {
+            spawn(&mut *self.terminal, &pipeline);
+            return true;
+        }
+        let keep_running = carrier::exec::dispatch_pipeline_with_services(
             self.commands,
             &mut *self.terminal,
-            trimmed,
+            &pipeline,
             &self.services,
-        )
+        );
+        if let Some(redirect) = &pipeline.stdout {
+            let output = self.terminal.take_stdout().unwrap_or_default();
+            if let Some(append) = self.services.fs.append_output
+                && let Err(error) = append(&redirect.path, output.as_bytes())
+            {
+                self.terminal
+                    .write_str(&alloc::format!("nozzle: {}: {}\n", redirect.path, error));
+            }
+        }
+        keep_running
+    }
+
+    fn open_output(&self, redirect: &carrier::pipeline::Redirect) -> Result<(), String> {
+        if !redirect.is_valid() {
+            return Err(String::from(
+                "nozzle: expected one file name after > or >>\n",
+            ));
+        }
+        let open = self
+            .services
+            .fs
+            .open_output
+            .ok_or_else(|| String::from("nozzle: redirection needs a filesystem\n"))?;
+        open(&redirect.path, redirect.append)
+            .map_err(|error| alloc::format!("nozzle: {}: {}\n", redirect.path, error))
+    }
+
+    fn is_builtin(&self, name: &str) -> bool {
+        name == "help" || self.commands.iter().any(|command| command.name() == name)
     }
 
     fn show_welcome(&mut self) {
@@ -158,6 +216,7 @@ pub fn default_commands() -> &'static [&'static dyn Command] {
         ("cat", "Print file contents", builtins::cmd_cat),
         ("pwd", "Print working directory", builtins::cmd_pwd),
         ("mem", "Show memory information", builtins::cmd_mem),
+        ("free", "Show physical memory usage", builtins::cmd_free),
         (
             "metrics",
             "Show boot/frame/heap/DMA metrics",
@@ -169,8 +228,24 @@ pub fn default_commands() -> &'static [&'static dyn Command] {
             builtins::cmd_cpuinfo
         ),
         ("tasks", "List processes", builtins::cmd_tasks),
+        (
+            "ps",
+            "List processes with state and priority",
+            builtins::cmd_ps
+        ),
+        (
+            "top",
+            "Show CPU usage per process over N seconds",
+            builtins::cmd_top
+        ),
+        ("kill", "Send a signal to a process", builtins::cmd_kill),
         ("windows", "List windows", builtins::cmd_windows),
         ("dmesg", "Show kernel messages", builtins::cmd_dmesg),
+        (
+            "loglevel",
+            "Show or change kernel log verbosity",
+            builtins::cmd_loglevel
+        ),
         ("hexdump", "Hex dump of text", builtins::cmd_hexdump),
         ("version", "Show version info", builtins::cmd_version),
         ("reboot", "Reboot the system", builtins::cmd_reboot),
@@ -194,6 +269,11 @@ pub fn default_commands() -> &'static [&'static dyn Command] {
             builtins::cmd_wallpaper
         ),
         ("pci", "List PCI devices", builtins::cmd_pci),
+        (
+            "keymap",
+            "Show or change keyboard layout",
+            builtins::cmd_keymap
+        ),
         (
             "badapple",
             "Play Bad Apple!! animation",
@@ -216,6 +296,11 @@ pub fn default_commands() -> &'static [&'static dyn Command] {
         ("df", "Show disk usage", builtins::cmd_df),
         ("date", "Show current date and time", builtins::cmd_date),
         ("uptime", "Show system uptime", builtins::cmd_uptime),
+        (
+            "clockcheck",
+            "Compare a timer sleep against the TSC",
+            builtins::cmd_clockcheck
+        ),
         ("whoami", "Print current user name", builtins::cmd_whoami),
         ("history", "Show command history", builtins::cmd_history),
         ("sleep", "Pause for N seconds", builtins::cmd_sleep),
@@ -288,6 +373,74 @@ mod tests {
         assert!(terminal.output.contains("echo hello\n"));
         assert!(terminal.output.contains("hello\n"));
     }
+
+    #[derive(Default)]
+    struct CapturingTerminal {
+        output: String,
+        captured: Option<String>,
+    }
+
+    impl Terminal for CapturingTerminal {
+        fn write_str(&mut self, s: &str) {
+            match &mut self.captured {
+                Some(captured) => captured.push_str(s),
+                None => self.output.push_str(s),
+            }
+        }
+
+        fn read_byte(&mut self) -> Option<u8> {
+            None
+        }
+
+        fn arm_pipe_stdout(&mut self) {
+            self.captured = Some(String::new());
+        }
+
+        fn take_stdout(&mut self) -> Option<String> {
+            self.captured.take()
+        }
+    }
+
+    static REDIRECTED: spin::Mutex<String> = spin::Mutex::new(String::new());
+
+    fn redirect_services() -> ShellServices {
+        let mut fs = fs_hooks::FsHooks::none();
+        fs.open_output = Some(|path, append| match path {
+            "/ram/test.txt" => {
+                if !append {
+                    REDIRECTED.lock().clear();
+                }
+                Ok(())
+            }
+            _ => Err(String::from("no such directory")),
+        });
+        fs.append_output = Some(|_, data| {
+            REDIRECTED
+                .lock()
+                .push_str(core::str::from_utf8(data).unwrap());
+            Ok(())
+        });
+        ShellServices::new(fs, sys_hooks::SysHooks::none(), None)
+    }
+
+    #[test]
+    fn redirection_writes_output_to_the_file_and_not_the_console() {
+        let mut terminal = CapturingTerminal::default();
+        let mut shell = Shell::new(&mut terminal, default_commands(), redirect_services());
+        assert!(shell.execute_line("echo hello > /ram/test.txt"));
+        assert!(shell.execute_line("echo again >> /ram/test.txt"));
+        assert_eq!(*REDIRECTED.lock(), "hello\nagain\n");
+
+        // A target that cannot be opened stops the command.
+        assert!(shell.execute_line("echo lost > /nope/test.txt"));
+        assert!(shell.execute_line("echo lost >"));
+        assert_eq!(*REDIRECTED.lock(), "hello\nagain\n");
+        assert_eq!(
+            terminal.output,
+            "nozzle: /nope/test.txt: no such directory\n\
+             nozzle: expected one file name after > or >>\n"
+        );
+    }
 }
 
 pub fn get_completions(prefix: &str) -> alloc::vec::Vec<alloc::string::String> {
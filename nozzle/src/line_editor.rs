@@ -5,7 +5,11 @@ use alloc::string::String;
 use alloc::string::ToString;
 use carrier::terminal::Terminal;
 
+use crate::completion::{Completer, common_prefix};
+
 const HISTORY_MAX: usize = 128;
+/// Above this many completion candidates, ask before listing them.
+const COMPLETION_QUERY_ITEMS: usize = 32;
 
 pub struct LineEditor {
     buffer: alloc::vec::Vec<u8>,
@@ -14,6 +18,7 @@ pub struct LineEditor {
     browsing: Option<usize>,
     saved_line: String,
     max_len: usize,
+    prompt: String,
 }
 
 impl LineEditor {
@@ -29,10 +34,20 @@ impl LineEditor {
             browsing: None,
             saved_line: String::new(),
             max_len,
+            prompt: String::from("> "),
         }
     }
 
-    pub fn read_line(&mut self, term: &mut dyn Terminal) -> Option<String> {
+    /// Prompt redrawn after completion candidates are listed.
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.into();
+    }
+
+    pub fn read_line(
+        &mut self,
+        term: &mut dyn Terminal,
+        completer: &Completer<'_>,
+    ) -> Option<String> {
         self.buffer.clear();
         self.cursor = 0;
         self.browsing = None;
@@ -68,7 +83,7 @@ impl LineEditor {
                     }
                 }
                 Some(0x09) => {
-                    self.do_tab_complete(term);
+                    self.do_tab_complete(term, completer);
                 }
                 _ => {}
             }
@@ -78,45 +93,70 @@ impl LineEditor {
         Some(line)
     }
 
-    fn do_tab_complete(&mut self, term: &mut dyn Terminal) {
-        let line = String::from_utf8_lossy(&self.buffer).to_string();
-        // Extract only the current word prefix (before cursor), up to the
-        // last space, so completions are scoped to the current argument.
-        let word_start = line[..self.cursor].rfind(' ').map(|i| i + 1).unwrap_or(0);
-        let prefix = &line[word_start..self.cursor];
-        let completions = crate::get_completions(prefix);
-        if completions.is_empty() {
+    fn do_tab_complete(&mut self, term: &mut dyn Terminal, completer: &Completer<'_>) {
+        // Completing mid-line would splice text into the middle of a word.
+        if self.cursor != self.buffer.len() {
             return;
         }
-        if completions.len() == 1 {
-            // Replace current word with completion
-            let prefix = &line[word_start..self.cursor];
-            let completion = &completions[0];
-            let suffix = &completion[prefix.len()..];
-            for &ch in suffix.as_bytes() {
-                self.do_insert(ch, term);
+        let line = String::from_utf8_lossy(&self.buffer).to_string();
+        let (start, candidates) = completer.complete(&line);
+        let typed = line.len() - start;
+        match candidates.as_slice() {
+            [] => {}
+            [only] => {
+                if self.append_str(&only[typed..], term) && !only.ends_with('/') {
+                    self.append_str(" ", term);
+                }
             }
-        } else {
-            // List possible completions
-            term.write_str("\n");
-            for c in &completions {
-                term.write_str(c);
-                term.write_str("  ");
+            _ => {
+                let prefix = common_prefix(&candidates);
+                self.append_str(&prefix[typed.min(prefix.len())..], term);
+                self.list_candidates(&candidates, term);
             }
+        }
+    }
+
+    /// Print `candidates` below the line, then redraw the prompt and line.
+    /// Long lists need a `y` first; any other key cancels.
+    fn list_candidates(&mut self, candidates: &[String], term: &mut dyn Terminal) {
+        term.write_str("\n");
+        if candidates.len() > COMPLETION_QUERY_ITEMS {
+            term.write_str(&alloc::format!(
+                "Display all {} possibilities? (y or n)",
+                candidates.len()
+            ));
+            let answer = term.read_byte();
             term.write_str("\n");
-            // Redraw prompt
-            term.write_str("> ");
-            let s = String::from_utf8_lossy(&self.buffer);
-            term.write_str(&s);
-            // Move cursor back to end by counting remaining chars
-            let remaining = self.buffer.len().saturating_sub(self.cursor);
-            for _ in 0..remaining {
-                term.write_str("\x1b[D");
+            if !matches!(answer, Some(b'y' | b'Y')) {
+                self.redraw(term);
+                return;
             }
         }
+        for candidate in candidates {
+            term.write_str(candidate);
+            term.write_str("  ");
+        }
+        term.write_str("\n");
+        self.redraw(term);
+    }
+
+    fn redraw(&self, term: &mut dyn Terminal) {
+        term.write_str(&self.prompt);
+        term.write_str(&String::from_utf8_lossy(&self.buffer));
     }
 
     // ── editing primitives ──────────────────────────────────────────
+    /// Append `text` at the end of the line, or nothing if it would not fit.
+    fn append_str(&mut self, text: &str, term: &mut dyn Terminal) -> bool {
+        if self.buffer.len() + text.len() > self.max_len {
+            return false;
+        }
+        self.buffer.extend_from_slice(text.as_bytes());
+        self.cursor = self.buffer.len();
+        term.write_str(text);
+        true
+    }
+
     fn do_insert(&mut self, ch: u8, term: &mut dyn Terminal) {
         if self.cursor >= self.buffer.len() {
             self.buffer.push(ch);
@@ -322,3 +362,142 @@ impl LineEditor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::fs_hooks::DirEntry;
+
+    const UP: &[u8] = b"\x1b[A";
+    const DOWN: &[u8] = b"\x1b[B";
+    const LEFT: &[u8] = b"\x1b[D";
+    const NO_COMPLETION: Completer<'static> = Completer::new(&[], None);
+
+    struct ScriptedTerminal {
+        input: VecDeque<u8>,
+        output: String,
+    }
+
+    impl ScriptedTerminal {
+        fn typing(keys: &[&[u8]]) -> Self {
+            Self {
+                input: keys.iter().flat_map(|keys| keys.iter().copied()).collect(),
+                output: String::new(),
+            }
+        }
+    }
+
+    impl Terminal for ScriptedTerminal {
+        fn write_str(&mut self, s: &str) {
+            self.output.push_str(s);
+        }
+
+        fn read_byte(&mut self) -> Option<u8> {
+            self.input.pop_front()
+        }
+
+        fn input_available(&self) -> bool {
+            !self.input.is_empty()
+        }
+    }
+
+    fn editor_with_history(lines: &[&str]) -> LineEditor {
+        let mut editor = LineEditor::new();
+        for line in lines {
+            let mut term = ScriptedTerminal::typing(&[line.as_bytes(), b"\n"]);
+            editor.read_line(&mut term, &NO_COMPLETION);
+        }
+        editor
+    }
+
+    fn history(editor: &LineEditor) -> Vec<&str> {
+        editor.history.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn recall_stops_at_the_oldest_entry() {
+        let mut editor = editor_with_history(&["first", "second"]);
+        let mut term = ScriptedTerminal::typing(&[UP, UP, UP, UP, b"\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &NO_COMPLETION).as_deref(),
+            Some("first")
+        );
+
+        let mut term = ScriptedTerminal::typing(&[b"new", UP, DOWN, b"\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &NO_COMPLETION).as_deref(),
+            Some("new")
+        );
+    }
+
+    #[test]
+    fn editing_a_recalled_line_keeps_history_intact() {
+        let mut editor = editor_with_history(&["ls", "cat a"]);
+        let mut term = ScriptedTerminal::typing(&[UP, b"\x08x", UP, DOWN, b"\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &NO_COMPLETION).as_deref(),
+            Some("cat a")
+        );
+        assert_eq!(history(&editor), ["cat a", "ls"]);
+
+        let mut term = ScriptedTerminal::typing(&[UP, b"\x08b", b"\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &NO_COMPLETION).as_deref(),
+            Some("cat b")
+        );
+        assert_eq!(history(&editor), ["cat b", "cat a", "ls"]);
+    }
+
+    fn entries(directory: &str) -> Option<Vec<DirEntry>> {
+        let count = match directory {
+            "." => 2,
+            "many" => COMPLETION_QUERY_ITEMS + 1,
+            _ => return None,
+        };
+        Some(
+            (0..count)
+                .map(|i| DirEntry {
+                    name: alloc::format!("file{i}"),
+                    is_dir: i == 0,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn tab_completes_paths_at_end_of_line_only() {
+        let completer = Completer::new(&[], Some(entries));
+        let mut editor = LineEditor::new();
+        let mut term = ScriptedTerminal::typing(&[b"cat f\t1\t\n"]);
+        // Both candidates share "file"; the listing shows them.
+        assert_eq!(
+            editor.read_line(&mut term, &completer).as_deref(),
+            Some("cat file1 ")
+        );
+        assert!(term.output.contains("file0/  file1  \n> cat file"));
+
+        let mut term = ScriptedTerminal::typing(&[b"cat file0", LEFT, b"\t\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &completer).as_deref(),
+            Some("cat file0")
+        );
+    }
+
+    #[test]
+    fn long_candidate_lists_can_be_cancelled() {
+        let completer = Completer::new(&[], Some(entries));
+        let mut editor = LineEditor::new();
+        let mut term = ScriptedTerminal::typing(&[b"cat many/\tn\n"]);
+        assert_eq!(
+            editor.read_line(&mut term, &completer).as_deref(),
+            Some("cat many/file")
+        );
+        assert!(
+            term.output
+                .contains("possibilities? (y or n)\n> cat many/file")
+        );
+        assert!(!term.output.contains("file1  "));
+    }
+}
@@ -2,18 +2,25 @@
 //!
 //! These hooks let the kernel register callbacks for system information
 //! commands (`mem`, `tasks`, `windows`, `dmesg`) and system control
-//! commands (`reboot`, `shutdown`).
+//! commands (`reboot`, `shutdown`), and to run command lines that name
+//! programs rather than built-ins.
 //!
 //! Hooks are bundled into a single immutable [`SysHooks`] value and injected
 //! into each shell session.
 
 use carrier::exec::CommandContext;
+use carrier::pipeline::Pipeline;
+use carrier::terminal::Terminal;
 
 /// Aggregated system hooks.
 #[derive(Clone, Copy)]
 pub struct SysHooks {
     pub info: Option<fn(&mut CommandContext, &str)>,
     pub ctl: Option<fn(&str)>,
+    /// Launch each stage as a program, with every stage's stdout piped
+    /// into the next stage's stdin and the last stage's into
+    /// `pipeline.stdout` when it is redirected.
+    pub spawn: Option<fn(&mut dyn Terminal, &Pipeline)>,
 }
 
 impl SysHooks {
@@ -21,6 +28,7 @@ impl SysHooks {
         Self {
             info: None,
             ctl: None,
+            spawn: None,
         }
     }
 }
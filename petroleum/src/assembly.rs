@@ -26,6 +26,9 @@ pub struct KernelArgs {
     pub fb_bpp: u32,
     pub fb_stride: u32,
     pub fb_pixel_format: u32,
+    /// Physical address of the [`BootParams`](crate::common::boot_params::BootParams),
+    /// or 0 if the bootloader did not provide any.
+    pub boot_params: u64,
 }
 
 #[repr(C)]
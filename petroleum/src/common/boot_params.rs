@@ -0,0 +1,204 @@
+//! Boot parameters handed from the bootloader to the kernel.
+//!
+//! Bellows writes one [`BootParams`] into the block that holds
+//! [`KernelArgs`](crate::assembly::KernelArgs) and stores its physical
+//! address in `KernelArgs::boot_params`.  A zero address or a bad magic
+//! means the bootloader predates boot parameters, and the kernel treats
+//! the command line as empty.
+//!
+//! The initrd range points at loader-data pages.  Bellows keeps them out of
+//! the frame allocator it builds the kernel's page tables from, and the
+//! kernel only allocates from conventional memory, so the archive stays in
+//! place for the kernel to mount.
+
+use super::uefi::FullereneFramebufferConfig;
+
+/// `"FULBOOTP"` in little-endian byte order.
+pub const BOOT_PARAMS_MAGIC: u64 = u64::from_le_bytes(*b"FULBOOTP");
+/// Maximum command line length in bytes; longer lines are truncated.
+pub const BOOT_CMDLINE_MAX: usize = 256;
+
+/// Kernel command line and framebuffer configuration chosen at boot.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BootParams {
+    pub magic: u64,
+    pub cmdline_len: u32,
+    /// Non-zero when `framebuffer` holds a valid configuration.
+    pub has_framebuffer: u32,
+    pub cmdline: [u8; BOOT_CMDLINE_MAX],
+    pub framebuffer: FullereneFramebufferConfig,
+    /// Physical address of the initrd archive, page aligned.
+    pub initrd_base: u64,
+    /// Length of the initrd archive in bytes, 0 when there is none.
+    pub initrd_len: u64,
+}
+
+impl BootParams {
+    /// Build boot parameters, truncating `cmdline` to [`BOOT_CMDLINE_MAX`]
+    /// bytes on a character boundary.
+    pub fn new(cmdline: &str, framebuffer: Option<FullereneFramebufferConfig>) -> Self {
+        let mut len = cmdline.len().min(BOOT_CMDLINE_MAX);
+        while !cmdline.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0u8; BOOT_CMDLINE_MAX];
+        bytes[..len].copy_from_slice(&cmdline.as_bytes()[..len]);
+        Self {
+            magic: BOOT_PARAMS_MAGIC,
+            cmdline_len: len as u32,
+            has_framebuffer: framebuffer.is_some() as u32,
+            cmdline: bytes,
+            framebuffer: framebuffer.unwrap_or(FullereneFramebufferConfig {
+                address: 0,
+                width: 0,
+                height: 0,
+                pixel_format: super::uefi::EfiGraphicsPixelFormat::PixelFormatMax,
+                bpp: 0,
+                stride: 0,
+            }),
+            initrd_base: 0,
+            initrd_len: 0,
+        }
+    }
+
+    /// Record the initrd archive loaded at physical address `base`.
+    pub fn with_initrd(mut self, base: u64, len: u64) -> Self {
+        self.initrd_base = base;
+        self.initrd_len = len;
+        self
+    }
+
+    /// The command line, or `None` if these parameters are not valid.
+    pub fn cmdline(&self) -> Option<&str> {
+        if self.magic != BOOT_PARAMS_MAGIC {
+            return None;
+        }
+        let len = (self.cmdline_len as usize).min(BOOT_CMDLINE_MAX);
+        core::str::from_utf8(&self.cmdline[..len]).ok()
+    }
+
+    /// The framebuffer configuration, if the bootloader found one.
+    pub fn framebuffer(&self) -> Option<FullereneFramebufferConfig> {
+        (self.magic == BOOT_PARAMS_MAGIC && self.has_framebuffer != 0).then_some(self.framebuffer)
+    }
+
+    /// Physical address and length of the initrd, if the bootloader loaded
+    /// one.
+    pub fn initrd(&self) -> Option<(u64, u64)> {
+        (self.magic == BOOT_PARAMS_MAGIC && self.initrd_len != 0)
+            .then_some((self.initrd_base, self.initrd_len))
+    }
+}
+
+/// Convert UEFI `LoadOptions` (UCS-2, as set by the shell or a boot entry)
+/// into an ASCII command line in `out`, returning its length.
+///
+/// Stops at the first NUL.  The UEFI shell passes the image name as the
+/// first word, so a leading `*.efi` word is dropped.  Returns 0 if the
+/// options are not printable ASCII text, e.g. binary boot-entry data.
+pub fn cmdline_from_load_options(units: &[u16], out: &mut [u8; BOOT_CMDLINE_MAX]) -> usize {
+    let units = match units.iter().position(|&unit| unit == 0) {
+        Some(end) => &units[..end],
+        None => units,
+    };
+    if !units.iter().all(|&unit| (0x20..0x7F).contains(&unit)) {
+        return 0;
+    }
+    let mut words = units.split(|&unit| unit == u16::from(b' ')).peekable();
+    if let Some(first) = words.peek() {
+        let is_image = first.len() > 4
+            && first[first.len() - 4..]
+                .iter()
+                .map(|&unit| (unit as u8).to_ascii_lowercase())
+                .eq(*b".efi");
+        if is_image {
+            words.next();
+        }
+    }
+
+    let mut len = 0;
+    for word in words.filter(|word| !word.is_empty()) {
+        let needed = word.len() + usize::from(len > 0);
+        if len + needed > BOOT_CMDLINE_MAX {
+            break;
+        }
+        if len > 0 {
+            out[len] = b' ';
+            len += 1;
+        }
+        for &unit in word {
+            out[len] = unit as u8;
+            len += 1;
+        }
+    }
+    len
+}
+
+/// Look up `key` on a space-separated command line.
+///
+/// Returns the text after `key=` for `key=value` options and `""` for a
+/// bare `key` flag.  When an option is repeated, the last one wins.
+pub fn cmdline_param<'a>(cmdline: &'a str, key: &str) -> Option<&'a str> {
+    cmdline
+        .split_ascii_whitespace()
+        .filter_map(|option| match option.split_once('=') {
+            Some((name, value)) if name == key => Some(value),
+            None if option == key => Some(""),
+            _ => None,
+        })
+        .next_back()
+}
+
+/// Parse a `WxH` resolution such as `1024x768`, as given by the
+/// `resolution=` option.
+pub fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ucs2(text: &str) -> alloc::vec::Vec<u16> {
+        text.encode_utf16().chain(core::iter::once(0)).collect()
+    }
+
+    #[test]
+    fn load_options_become_the_command_line() {
+        let mut out = [0u8; BOOT_CMDLINE_MAX];
+        let len = cmdline_from_load_options(&ucs2("BOOTX64.EFI  loglevel=debug novga"), &mut out);
+        assert_eq!(&out[..len], b"loglevel=debug novga");
+
+        let len = cmdline_from_load_options(&ucs2("novga"), &mut out);
+        assert_eq!(&out[..len], b"novga");
+
+        // Binary optional data from a boot entry is not a command line.
+        assert_eq!(cmdline_from_load_options(&[0x0001, 0xFFFF], &mut out), 0);
+    }
+
+    #[test]
+    fn params_round_trip_and_parse() {
+        let params = BootParams::new("loglevel=debug novga loglevel=warn", None);
+        let cmdline = params.cmdline().unwrap();
+        assert_eq!(cmdline_param(cmdline, "loglevel"), Some("warn"));
+        assert_eq!(cmdline_param(cmdline, "novga"), Some(""));
+        assert_eq!(cmdline_param(cmdline, "nosmp"), None);
+        assert_eq!(parse_resolution("1280x720"), Some((1280, 720)));
+        assert_eq!(parse_resolution("1280x0"), None);
+        assert_eq!(parse_resolution("1280"), None);
+        assert!(params.framebuffer().is_none());
+        assert_eq!(params.initrd(), None);
+        assert_eq!(
+            params.with_initrd(0x4000_0000, 512).initrd(),
+            Some((0x4000_0000, 512))
+        );
+
+        let mut stale = params.with_initrd(0x4000_0000, 512);
+        stale.magic = 0;
+        assert_eq!(stale.cmdline(), None);
+        assert_eq!(stale.initrd(), None);
+    }
+}
@@ -6,6 +6,8 @@
 // Note: log crate dependency removed to avoid std pull-in.
 // Re-export serial functions for logging.
 
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
 // ── EARLY-ONLY GLOBAL STATE ─────────────────────────────────────────
 // The logger is initialised during boot and its static state (`LOGGER`,
 // `LOGGER_INITIALIZED`) lives in the `.data` / `.bss` sections.
@@ -14,6 +16,8 @@
 // or `graphics::PRIMARY_RENDERER` for output instead.
 
 pub struct FullereneLogger {
+    /// Level installed by [`init_global_logger`]; [`set_max_level`] changes
+    /// the effective level afterwards.
     level: log::LevelFilter,
 }
 
@@ -27,7 +31,9 @@ impl FullereneLogger {
 
 impl log::Log for FullereneLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= self.level
+        // Filter on the global maximum rather than `self.level`, so that a
+        // level raised by `set_max_level` is not cut off again here.
+        metadata.level() <= log::max_level()
     }
 
     fn log(&self, record: &log::Record) {
@@ -48,11 +54,19 @@ impl log::Log for FullereneLogger {
                 writer.pos
             };
             let msg = core::str::from_utf8(&buf[..len]).unwrap_or("[log error]");
-            crate::serial::serial_log(format_args!("{}", msg));
+            CONSOLE.write(msg.as_bytes(), &mut |bytes| {
+                crate::write_serial_bytes(
+                    crate::serial::COM1_DATA_PORT,
+                    crate::serial::COM1_STATUS_PORT,
+                    bytes,
+                )
+            });
             // Forward to kernel log hook (dmesg) when registered.
             // Copy the function pointer out of the lock first to avoid
-            // deadlock if the callback itself triggers logging.
-            let hook = *LOG_HOOK.lock();
+            // deadlock if the callback itself triggers logging, and skip
+            // the hook rather than spin if the lock is taken — the record
+            // has already reached the serial port.
+            let hook = LOG_HOOK.try_lock().and_then(|hook| *hook);
             if let Some(hook) = hook {
                 hook(record.level(), msg);
             }
@@ -69,6 +83,153 @@ static LOGGER_INITIALIZED: spin::Once<()> = spin::Once::new();
 /// for in-OS display (e.g. dmesg).
 pub static LOG_HOOK: spin::Mutex<Option<fn(log::Level, &str)>> = spin::Mutex::new(None);
 
+/// Serial console that log records take turns on.
+static CONSOLE: LogConsole = LogConsole::new();
+
+/// Bytes of records held back while the console is in use.
+const DEFERRED_CAPACITY: usize = 4096;
+
+/// Serialises log records onto the serial port without ever waiting.
+///
+/// Whoever finds the console free writes its record straight through.  A
+/// record that arrives while the console is in use — from an interrupt
+/// handler that fired mid-record, or from another CPU — is queued instead,
+/// and the writer that owns the console flushes the queue before handing
+/// it back.  Interrupt handlers therefore neither spin on the console nor
+/// splice their output into the middle of someone else's line.
+struct LogConsole {
+    busy: AtomicBool,
+    deferred: spin::Mutex<DeferredLog>,
+    /// Records lost because the queue was full or being flushed by another
+    /// CPU; reported on the console with the next flush.
+    dropped: AtomicUsize,
+}
+
+impl LogConsole {
+    const fn new() -> Self {
+        Self {
+            busy: AtomicBool::new(false),
+            deferred: spin::Mutex::new(DeferredLog::new()),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Write `record` through `sink`, or queue it if the console is busy.
+    fn write(&self, record: &[u8], sink: &mut dyn FnMut(&[u8])) {
+        if self.busy.swap(true, Ordering::Acquire) {
+            let queued = with_interrupts_masked(|| {
+                self.deferred
+                    .try_lock()
+                    .is_some_and(|mut deferred| deferred.push(record))
+            });
+            if !queued {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        sink(record);
+        loop {
+            self.flush(sink);
+            self.busy.store(false, Ordering::Release);
+            // A record queued between the flush and the release would be
+            // stranded until the next write; take the console back for it.
+            let pending = with_interrupts_masked(|| {
+                self.deferred
+                    .try_lock()
+                    .is_some_and(|deferred| !deferred.is_empty())
+            });
+            if !pending || self.busy.swap(true, Ordering::Acquire) {
+                return;
+            }
+        }
+    }
+
+    /// Write out queued records.  Only called by the console's owner.
+    fn flush(&self, sink: &mut dyn FnMut(&[u8])) {
+        let mut chunk = [0u8; 256];
+        loop {
+            // Copy out under the lock with interrupts masked, so a handler
+            // on this CPU never finds the queue locked; the slow write to
+            // the port happens afterwards.
+            let len = with_interrupts_masked(|| self.deferred.lock().take(&mut chunk));
+            if len == 0 {
+                break;
+            }
+            sink(&chunk[..len]);
+        }
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped != 0 {
+            use core::fmt::Write;
+            let mut note = [0u8; 64];
+            let mut writer = StackWriter {
+                buf: &mut note[..],
+                pos: 0,
+            };
+            let _ = writeln!(writer, "[log] {} records dropped", dropped);
+            let len = writer.pos;
+            sink(&note[..len]);
+        }
+    }
+}
+
+/// FIFO of whole records, as raw bytes.
+struct DeferredLog {
+    buf: [u8; DEFERRED_CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl DeferredLog {
+    const fn new() -> Self {
+        Self {
+            buf: [0; DEFERRED_CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Queue `record`, or return `false` if it does not fit whole.
+    fn push(&mut self, record: &[u8]) -> bool {
+        if record.len() > DEFERRED_CAPACITY - self.len {
+            return false;
+        }
+        for &byte in record {
+            self.buf[(self.head + self.len) % DEFERRED_CAPACITY] = byte;
+            self.len += 1;
+        }
+        true
+    }
+
+    /// Move the oldest queued bytes into `out`, returning how many.
+    fn take(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.len);
+        for slot in &mut out[..count] {
+            *slot = self.buf[self.head];
+            self.head = (self.head + 1) % DEFERRED_CAPACITY;
+        }
+        self.len -= count;
+        count
+    }
+}
+
+/// Run `f` with interrupts disabled on this CPU.
+fn with_interrupts_masked<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(all(not(feature = "std"), not(test)))]
+    {
+        x86_64::instructions::interrupts::without_interrupts(f)
+    }
+    #[cfg(any(feature = "std", test))]
+    {
+        f()
+    }
+}
+
+/// Install the global logger.  The records [`record_early`] kept wait for
+/// [`replay_early_log`], so that the final level can be set first.
 pub fn init_global_logger() -> Result<(), log::SetLoggerError> {
     log::set_logger(&LOGGER)?;
     log::set_max_level(LOGGER.level);
@@ -84,6 +245,163 @@ pub fn is_logger_initialized() -> bool {
     LOGGER_INITIALIZED.is_completed()
 }
 
+/// Records kept from before the logger is up.
+const EARLY_RECORDS: usize = 64;
+/// Bytes of text kept per early record; the rest is cut off.
+const EARLY_RECORD_LEN: usize = 120;
+/// Least severe level kept from before the logger is up.
+const EARLY_MIN_LEVEL: log::Level = log::Level::Info;
+
+/// Records logged before [`init_global_logger`], for the hook to see once
+/// it is installed.  Fixed size, since the heap may not exist yet.
+static EARLY_LOG: spin::Mutex<EarlyLog> = spin::Mutex::new(EarlyLog::new());
+/// Early records lost because the buffer was locked, by an interrupt
+/// handler or another CPU.
+static EARLY_LOST: AtomicUsize = AtomicUsize::new(0);
+
+/// Keep a record logged before the logger is up, to be replayed by
+/// [`replay_early_log`].  The record must already have been written to the
+/// serial port; replay does not repeat it there.  Does nothing once the
+/// logger is up, and drops records below `Info`, so that a burst of debug
+/// output cannot push the ones that matter out of the buffer.
+pub fn record_early(level: log::Level, args: core::fmt::Arguments) {
+    if is_logger_initialized() || level > EARLY_MIN_LEVEL {
+        return;
+    }
+    use core::fmt::Write;
+    let mut buf = [0u8; 256];
+    let mut writer = StackWriter {
+        buf: &mut buf[..],
+        pos: 0,
+    };
+    let _ = writer.write_fmt(args);
+    let len = writer.pos;
+    let kept = with_interrupts_masked(|| {
+        EARLY_LOG
+            .try_lock()
+            .map(|mut early| early.push(level, &buf[..len]))
+            .is_some()
+    });
+    if !kept {
+        EARLY_LOST.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Pass the early records to [`LOG_HOOK`] as [`FullereneLogger`] formats
+/// them, dropping those above the current level, then log how many were
+/// lost.  Call once the hook is installed and the level from the command
+/// line applied.
+pub fn replay_early_log() {
+    let hook = LOG_HOOK.try_lock().and_then(|hook| *hook);
+    let lost = with_interrupts_masked(|| {
+        let mut early = EARLY_LOG.lock();
+        if let Some(hook) = hook {
+            early.for_each(|level, text| {
+                if level > log::max_level() {
+                    return;
+                }
+                use core::fmt::Write;
+                let mut buf = [0u8; EARLY_RECORD_LEN + 16];
+                let mut writer = StackWriter {
+                    buf: &mut buf[..],
+                    pos: 0,
+                };
+                let _ = writeln!(writer, "[{}] {}", level, text);
+                let len = writer.pos;
+                hook(
+                    level,
+                    core::str::from_utf8(&buf[..len]).unwrap_or("[log error]"),
+                );
+            });
+        }
+        let lost = early.overwritten;
+        *early = EarlyLog::new();
+        lost
+    }) + EARLY_LOST.swap(0, Ordering::Relaxed);
+    if lost != 0 {
+        log::warn!("{} early boot log records lost", lost);
+    }
+}
+
+struct EarlyRecord {
+    level: log::Level,
+    len: usize,
+    text: [u8; EARLY_RECORD_LEN],
+}
+
+/// Ring of the newest [`EARLY_RECORDS`] early records.
+struct EarlyLog {
+    records: [EarlyRecord; EARLY_RECORDS],
+    head: usize,
+    len: usize,
+    /// Records pushed out by newer ones.
+    overwritten: usize,
+}
+
+impl EarlyLog {
+    const fn new() -> Self {
+        Self {
+            records: [const {
+                EarlyRecord {
+                    level: log::Level::Info,
+                    len: 0,
+                    text: [0; EARLY_RECORD_LEN],
+                }
+            }; EARLY_RECORDS],
+            head: 0,
+            len: 0,
+            overwritten: 0,
+        }
+    }
+
+    /// Keep `text`, cut to [`EARLY_RECORD_LEN`] bytes and stripped of its
+    /// trailing newline, overwriting the oldest record once full.
+    fn push(&mut self, level: log::Level, text: &[u8]) {
+        let text = text.strip_suffix(b"\n").unwrap_or(text);
+        let len = text.len().min(EARLY_RECORD_LEN);
+        let slot = if self.len < EARLY_RECORDS {
+            self.len += 1;
+            (self.head + self.len - 1) % EARLY_RECORDS
+        } else {
+            let oldest = self.head;
+            self.head = (self.head + 1) % EARLY_RECORDS;
+            self.overwritten += 1;
+            oldest
+        };
+        let record = &mut self.records[slot];
+        record.level = level;
+        record.len = len;
+        record.text[..len].copy_from_slice(&text[..len]);
+    }
+
+    /// Visit the records oldest first.  Text cut mid-character loses the
+    /// partial character.
+    fn for_each(&self, mut f: impl FnMut(log::Level, &str)) {
+        for i in 0..self.len {
+            let record = &self.records[(self.head + i) % EARLY_RECORDS];
+            let text = &record.text[..record.len];
+            let text = match core::str::from_utf8(text) {
+                Ok(text) => text,
+                Err(error) => core::str::from_utf8(&text[..error.valid_up_to()]).unwrap_or(""),
+            };
+            f(record.level, text);
+        }
+    }
+}
+
+/// Change the verbosity of the kernel log.
+///
+/// Takes effect for the next `log::` call on any CPU: the `log` macros and
+/// [`FullereneLogger`] both read the one global maximum.
+pub fn set_max_level(level: log::LevelFilter) {
+    log::set_max_level(level);
+}
+
+/// The level set by [`init_global_logger`] or the last [`set_max_level`].
+pub fn max_level() -> log::LevelFilter {
+    log::max_level()
+}
+
 /// Unified result type for system operations
 pub type SystemResult<T> = Result<T, SystemError>;
 
@@ -122,6 +440,53 @@ pub enum SystemError {
     WouldBlock = 140,
 }
 
+impl SystemError {
+    /// A short description of the error, fixed for each variant so that log
+    /// lines can be matched on it.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvalidSyscall => "invalid system call",
+            Self::BadFileDescriptor => "bad file descriptor",
+            Self::PermissionDenied => "permission denied",
+            Self::FileNotFound => "file not found",
+            Self::NoSuchProcess => "no such process",
+            Self::InvalidArgument => "invalid argument",
+            Self::SyscallOutOfMemory => "out of memory",
+            Self::FileExists => "file exists",
+            Self::InvalidSeek => "invalid seek",
+            Self::DiskFull => "disk full",
+            Self::MappingFailed => "page mapping failed",
+            Self::UnmappingFailed => "page unmapping failed",
+            Self::FrameAllocationFailed => "frame allocation failed",
+            Self::MemOutOfMemory => "out of physical memory",
+            Self::InvalidFormat => "invalid format",
+            Self::LoadFailed => "load failed",
+            Self::DeviceNotFound => "device not found",
+            Self::DeviceError => "device error",
+            Self::PortError => "port error",
+            Self::NotImplemented => "not implemented",
+            Self::NotSupported => "not supported",
+            Self::InternalError => "internal error",
+            Self::UnknownError => "unknown error",
+            Self::FsInvalidFileDescriptor => "invalid filesystem descriptor",
+            Self::TooManyProcesses => "too many processes",
+            Self::OperationAgain => "resource temporarily unavailable",
+            Self::OperationTimedOut => "operation timed out",
+            Self::NoSuchDevice => "no such device",
+            Self::BadHandle => "bad handle",
+            Self::WouldBlock => "operation would block",
+        }
+    }
+}
+
+/// Formats as the description followed by the numeric code, e.g.
+/// `file not found (2)`.
+impl core::fmt::Display for SystemError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({})", self.as_str(), *self as u32)
+    }
+}
+
 /// Logging trait for system errors with context — used by initializer's HardwareDevice.
 pub trait ErrorLogging {
     fn log_error(&self, error: &SystemError, context: &'static str);
@@ -134,7 +499,7 @@ pub trait ErrorLogging {
 pub struct ErrorLogger;
 impl ErrorLogging for ErrorLogger {
     fn log_error(&self, error: &SystemError, context: &'static str) {
-        log::error!("{}: {}", *error as u64, context);
+        log::error!("{}: {}", context, error);
     }
     fn log_warning(&self, message: &'static str) {
         log::warn!("{}", message);
@@ -161,6 +526,7 @@ macro_rules! info_log {
             log::info!("{}", format_args!($($arg)*));
         } else {
             $crate::serial::_print(format_args!("[INFO] {}\n", format_args!($($arg)*)));
+            $crate::common::logging::record_early(log::Level::Info, format_args!($($arg)*));
         }
     };
 }
@@ -172,6 +538,7 @@ macro_rules! error_log {
             log::error!("{}", format_args!($($arg)*));
         } else {
             $crate::serial::_print(format_args!("[ERROR] {}\n", format_args!($($arg)*)));
+            $crate::common::logging::record_early(log::Level::Error, format_args!($($arg)*));
         }
     };
 }
@@ -183,6 +550,7 @@ macro_rules! warn_log {
             log::warn!("{}", format_args!($($arg)*));
         } else {
             $crate::serial::_print(format_args!("[WARN] {}\n", format_args!($($arg)*)));
+            $crate::common::logging::record_early(log::Level::Warn, format_args!($($arg)*));
         }
     };
 }
@@ -203,7 +571,7 @@ macro_rules! debug_log {
 #[macro_export]
 macro_rules! log_error {
     ($error:expr, $context:expr) => {{
-        log::error!("{}: {}", *$error as u64, $context);
+        log::error!("{}: {}", $context, $error);
     }};
 }
 
@@ -258,3 +626,142 @@ macro_rules! log {
         $crate::serial::_print(format_args!(concat!($prefix, ": ", $format, "\n"), $($args)*));
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use log::{Level, LevelFilter, Log, Metadata};
+
+    fn enabled(level: Level) -> bool {
+        LOGGER.enabled(&Metadata::builder().level(level).build())
+    }
+
+    #[test]
+    fn every_error_has_its_own_description() {
+        use SystemError::*;
+        let all = [
+            InvalidSyscall,
+            BadFileDescriptor,
+            PermissionDenied,
+            FileNotFound,
+            NoSuchProcess,
+            InvalidArgument,
+            SyscallOutOfMemory,
+            FileExists,
+            InvalidSeek,
+            DiskFull,
+            MappingFailed,
+            UnmappingFailed,
+            FrameAllocationFailed,
+            MemOutOfMemory,
+            InvalidFormat,
+            LoadFailed,
+            DeviceNotFound,
+            DeviceError,
+            PortError,
+            NotImplemented,
+            NotSupported,
+            InternalError,
+            UnknownError,
+            FsInvalidFileDescriptor,
+            TooManyProcesses,
+            OperationAgain,
+            OperationTimedOut,
+            NoSuchDevice,
+            BadHandle,
+            WouldBlock,
+        ];
+        for (i, a) in all.iter().enumerate() {
+            assert!(!a.as_str().is_empty());
+            for b in &all[i + 1..] {
+                assert_ne!(a.as_str(), b.as_str(), "{a:?} and {b:?}");
+            }
+        }
+        assert_eq!(alloc::format!("{FileNotFound}"), "file not found (2)");
+    }
+
+    #[test]
+    fn level_changes_apply_to_the_next_record() {
+        set_max_level(LevelFilter::Trace);
+        assert!(enabled(Level::Trace));
+        set_max_level(LevelFilter::Warn);
+        assert_eq!(max_level(), LevelFilter::Warn);
+        assert!(!enabled(Level::Info));
+        assert!(enabled(Level::Error));
+        set_max_level(LevelFilter::Off);
+        assert!(!enabled(Level::Error));
+    }
+
+    #[test]
+    fn a_record_logged_mid_write_waits_for_the_one_in_progress() {
+        let console = LogConsole::new();
+        let mut out = Vec::new();
+        let mut interrupted = false;
+        console.write(b"[INFO] outer\n", &mut |bytes| {
+            // An interrupt handler logging while the outer record is on
+            // its way to the port must neither spin nor write.
+            if !interrupted {
+                interrupted = true;
+                console.write(b"[WARN] handler\n", &mut |_| panic!("console taken twice"));
+            }
+            out.extend_from_slice(bytes);
+        });
+        assert_eq!(out, b"[INFO] outer\n[WARN] handler\n");
+        assert!(!console.busy.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn records_that_do_not_fit_are_counted_and_reported() {
+        let console = LogConsole::new();
+        let record = [b'x'; 1000];
+        console.busy.store(true, Ordering::Relaxed);
+        for _ in 0..5 {
+            console.write(&record, &mut |_| panic!("console is busy"));
+        }
+        console.busy.store(false, Ordering::Relaxed);
+
+        let mut out = Vec::new();
+        console.write(b"next\n", &mut |bytes| out.extend_from_slice(bytes));
+        assert_eq!(&out[..5], b"next\n");
+        assert_eq!(out[5..4005], [b'x'; 4000]);
+        assert_eq!(&out[4005..], b"[log] 1 records dropped\n");
+        assert!(console.deferred.lock().is_empty());
+    }
+
+    fn early_records(early: &EarlyLog) -> Vec<(Level, alloc::string::String)> {
+        let mut records = Vec::new();
+        early.for_each(|level, text| records.push((level, text.into())));
+        records
+    }
+
+    #[test]
+    fn early_records_keep_the_newest_and_count_the_rest() {
+        let mut early = EarlyLog::new();
+        for i in 0..EARLY_RECORDS + 3 {
+            early.push(Level::Debug, alloc::format!("record {i}\n").as_bytes());
+        }
+        let records = early_records(&early);
+        assert_eq!(records.len(), EARLY_RECORDS);
+        assert_eq!(records[0], (Level::Debug, "record 3".into()));
+        assert_eq!(
+            records[EARLY_RECORDS - 1].1,
+            alloc::format!("record {}", EARLY_RECORDS + 2)
+        );
+        assert_eq!(early.overwritten, 3);
+    }
+
+    #[test]
+    fn long_early_records_are_cut_on_a_character_boundary() {
+        let mut early = EarlyLog::new();
+        let mut text = alloc::string::String::from("x");
+        while text.len() < EARLY_RECORD_LEN + 4 {
+            text.push('é');
+        }
+        early.push(Level::Warn, text.as_bytes());
+        let records = early_records(&early);
+        // 'x' plus two-byte characters leaves half a character at the cut.
+        assert_eq!(records[0].1.len(), EARLY_RECORD_LEN - 1);
+        assert!(text.starts_with(records[0].1.as_str()));
+    }
+}
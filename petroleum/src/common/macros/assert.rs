@@ -0,0 +1,59 @@
+//! Kernel assertion macros for Fullerene OS
+
+/// Like `assert!`, but a failure prints the condition's source text, the
+/// optional formatted message and the call site straight to serial, then
+/// halts, instead of going through the panic handler.  Usable before the
+/// logger and the heap are up.
+///
+/// With petroleum's `release_fast` feature the check compiles to nothing
+/// and the condition is not evaluated.
+#[macro_export]
+macro_rules! kassert {
+    ($cond:expr $(,)?) => {
+        if $crate::debug::KASSERT_ENABLED && !$cond {
+            $crate::debug::kassert_failed(stringify!($cond), None);
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if $crate::debug::KASSERT_ENABLED && !$cond {
+            $crate::debug::kassert_failed(stringify!($cond), Some(format_args!($($arg)+)));
+        }
+    };
+}
+
+/// Like `assert_eq!`, failing as [`kassert!`] does, with both values.
+#[macro_export]
+macro_rules! kassert_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        if $crate::debug::KASSERT_ENABLED {
+            match (&$left, &$right) {
+                (left, right) => {
+                    if !(*left == *right) {
+                        $crate::debug::kassert_eq_failed(
+                            concat!(stringify!($left), " == ", stringify!($right)),
+                            &*left,
+                            &*right,
+                            None,
+                        );
+                    }
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        if $crate::debug::KASSERT_ENABLED {
+            match (&$left, &$right) {
+                (left, right) => {
+                    if !(*left == *right) {
+                        $crate::debug::kassert_eq_failed(
+                            concat!(stringify!($left), " == ", stringify!($right)),
+                            &*left,
+                            &*right,
+                            Some(format_args!($($arg)+)),
+                        );
+                    }
+                }
+            }
+        }
+    };
+}
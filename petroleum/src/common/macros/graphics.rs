@@ -251,6 +251,10 @@ macro_rules! impl_text_buffer_operations {
             self.$color_field
         }
 
+        fn set_color_code(&mut self, color_code: ColorCode) {
+            self.$color_field = color_code;
+        }
+
         fn get_position(&self) -> (usize, usize) {
             (self.$row_pos, self.$col_pos)
         }
@@ -425,6 +429,10 @@ macro_rules! impl_mock_vga_buffer {
                 self.color_code
             }
 
+            fn set_color_code(&mut self, color_code: ColorCode) {
+                self.color_code = color_code;
+            }
+
             fn get_position(&self) -> (usize, usize) {
                 (self.row_position, self.column_position)
             }
@@ -16,6 +16,7 @@ macro_rules! unified_logging {
             log::info!($msg);
         } else {
             $crate::serial::_print(format_args!("{}\n", $msg));
+            $crate::common::logging::record_early(log::Level::Info, format_args!("{}", $msg));
         }
     };
     (verbose_print, args, $($arg:tt)*) => {
@@ -23,6 +24,7 @@ macro_rules! unified_logging {
             log::info!("{}", format_args!($($arg)*));
         } else {
             $crate::serial::_print(format_args!("{}\n", format_args!($($arg)*)));
+            $crate::common::logging::record_early(log::Level::Info, format_args!($($arg)*));
         }
     };
 }
@@ -33,6 +35,7 @@ macro_rules! debug_log_no_alloc {
         let args = format_args!($($arg)*);
         $crate::serial::_print(args);
         $crate::serial::debug_print_str_no_lock("\n");
+        $crate::common::logging::record_early(log::Level::Debug, args);
     }};
 }
 
@@ -110,6 +113,7 @@ macro_rules! println {
             log::info!("{}", format_args!($($arg)*));
         } else {
             $crate::serial::_print(format_args!("{}\n", format_args!($($arg)*)));
+            $crate::common::logging::record_early(log::Level::Info, format_args!($($arg)*));
         }
     };
 }
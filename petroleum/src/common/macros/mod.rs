@@ -1,5 +1,7 @@
 //! Unified macro definitions for Fullerene OS, split into functional modules
 
+#[macro_use]
+pub mod assert;
 #[macro_use]
 pub mod paging;
 #[macro_use]
@@ -4,9 +4,10 @@
 //! used by syscall handlers and memory management.
 use crate::common::logging::{SystemError, SystemResult};
 use core::alloc::Layout;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use x86_64::VirtAddr;
-use x86_64::registers::control::Cr3;
+use x86_64::registers::control::{Cr3, Cr4, Cr4Flags};
+use x86_64::registers::rflags::{self, RFlags};
 use x86_64::structures::paging::{PageTable, PageTableFlags};
 
 // ── RUNTIME GLOBAL STATE ──────────────────────────────────────────────
@@ -139,6 +140,17 @@ pub fn walk_page_table_for_flags(vaddr: VirtAddr) -> Option<PageTableFlags> {
 ///
 /// Walks the current page table (CR3) page by page.
 pub fn validate_user_range(addr: *const u8, len: usize, writable: bool) -> Result<(), SystemError> {
+    check_user_pages(addr, len, writable, walk_page_table_for_flags)
+}
+
+/// [`validate_user_range`] against `flags_of`, which gives the effective
+/// flags of the page at an address or `None` if it is not mapped.
+fn check_user_pages(
+    addr: *const u8,
+    len: usize,
+    writable: bool,
+    flags_of: impl Fn(VirtAddr) -> Option<PageTableFlags>,
+) -> Result<(), SystemError> {
     if len == 0 {
         return Ok(());
     }
@@ -162,7 +174,7 @@ pub fn validate_user_range(addr: *const u8, len: usize, writable: bool) -> Resul
 
     for i in 0..num_pages {
         let vaddr = page_start + (i * 4096);
-        let flags = walk_page_table_for_flags(vaddr).ok_or(SystemError::InvalidArgument)?;
+        let flags = flags_of(vaddr).ok_or(SystemError::InvalidArgument)?;
         if !flags.contains(PageTableFlags::PRESENT) {
             return Err(SystemError::InvalidArgument);
         }
@@ -206,6 +218,90 @@ pub unsafe fn deallocate_layout(ptr: *mut u8, layout: Layout) {
     unsafe { alloc::alloc::dealloc(ptr, layout) };
 }
 
+/// Whether SMAP is on.  `stac` and `clac` are invalid opcodes on a CPU
+/// without it, so [`UserAccess`] issues them only once this is set.
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The supervisor-mode protections [`enable_user_access_protection`] turned
+/// on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserAccessProtection {
+    /// SMEP: the kernel faults on executing a user page.
+    pub smep: bool,
+    /// SMAP: the kernel faults on touching a user page outside the copy
+    /// helpers of [`UserPtr`] and [`UserSlice`].
+    pub smap: bool,
+}
+
+/// Turn on SMEP and SMAP in CR4 where the CPU has them.  Application
+/// processors copy CR4 from the bootstrap processor, so this must run
+/// before they start.
+pub fn enable_user_access_protection() -> UserAccessProtection {
+    let features = crate::hardware::cpuid::features();
+    let protection = UserAccessProtection {
+        smep: features.smep,
+        smap: features.smap,
+    };
+    unsafe {
+        Cr4::update(|cr4| {
+            if protection.smep {
+                cr4.insert(Cr4Flags::SUPERVISOR_MODE_EXECUTION_PROTECTION);
+            }
+            if protection.smap {
+                cr4.insert(Cr4Flags::SUPERVISOR_MODE_ACCESS_PREVENTION);
+            }
+        });
+    }
+    SMAP_ENABLED.store(protection.smap, Ordering::Release);
+    protection
+}
+
+/// Whether SMAP is on, so that the kernel faults on touching a user page
+/// outside a user copy.
+pub fn smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Acquire)
+}
+
+/// Close whatever user-access window the interrupted code had open.
+/// Delivering an interrupt or exception leaves RFLAGS.AC alone, so a
+/// handler that interrupted a user copy would otherwise run with SMAP off;
+/// its `iretq` restores the interrupted RFLAGS, and with them the window.
+#[inline(always)]
+pub fn close_user_access() {
+    if SMAP_ENABLED.load(Ordering::Relaxed) {
+        unsafe { core::arch::asm!("clac", options(nostack)) };
+    }
+}
+
+/// A window in which the kernel may touch user pages under SMAP, held for
+/// the duration of one copy.  Opening it sets RFLAGS.AC (`stac`); dropping
+/// it puts AC back the way it was found rather than clearing it, so a copy
+/// nested inside another leaves the outer copy's window open.
+struct UserAccess {
+    /// AC was already set, by an enclosing window, when this one opened.
+    was_open: bool,
+}
+
+impl UserAccess {
+    fn open() -> Self {
+        if !SMAP_ENABLED.load(Ordering::Acquire) {
+            return Self { was_open: true };
+        }
+        let was_open = rflags::read().contains(RFlags::ALIGNMENT_CHECK);
+        // No `nomem`: the user accesses must not move out of the window.
+        unsafe { core::arch::asm!("stac", options(nostack)) };
+        Self { was_open }
+    }
+}
+
+impl Drop for UserAccess {
+    fn drop(&mut self) {
+        if !self.was_open {
+            unsafe { core::arch::asm!("clac", options(nostack)) };
+        }
+    }
+}
+
 /// Validated pointer to user-space memory.
 ///
 /// `UserPtr` represents a pointer that has been validated to point into
@@ -246,6 +342,7 @@ impl<T> UserPtr<T> {
     ///
     /// The caller must ensure that `T` is valid for the memory at the pointer.
     pub unsafe fn copy_from_user(&self) -> Result<T, SystemError> {
+        let _access = UserAccess::open();
         unsafe { Ok(core::ptr::read_unaligned(self.ptr)) }
     }
 
@@ -256,6 +353,7 @@ impl<T> UserPtr<T> {
     /// The caller must ensure that `T` is valid for the memory at the pointer
     /// and that the user buffer is writable.
     pub unsafe fn copy_to_user(&self, val: T) -> SystemResult<()> {
+        let _access = UserAccess::open();
         unsafe {
             core::ptr::write_unaligned(self.ptr as *mut T, val);
         }
@@ -330,6 +428,7 @@ impl UserSlice {
         if count == 0 {
             return Ok(());
         }
+        let _access = UserAccess::open();
         unsafe {
             core::ptr::copy_nonoverlapping(self.ptr, buf.as_mut_ptr(), count);
         }
@@ -347,6 +446,7 @@ impl UserSlice {
         if count == 0 {
             return Ok(());
         }
+        let _access = UserAccess::open();
         unsafe {
             core::ptr::copy_nonoverlapping(buf.as_ptr(), self.ptr, count);
         }
@@ -449,7 +549,8 @@ pub unsafe fn copy_to_user(ptr: *mut u8, buf: &[u8]) -> SystemResult<()> {
 /// Legacy: create a temporary user-space slice (borrows from user memory).
 ///
 /// NOTE: Prefer `copy_from_user` / `UserSlice` instead.  This returns a
-/// `'static` borrow that is unsound if the user buffer is deallocated.
+/// `'static` borrow that is unsound if the user buffer is deallocated, and
+/// with SMAP on, reading through it faults.
 ///
 /// # Safety
 ///
@@ -473,7 +574,8 @@ pub unsafe fn user_slice(
 /// Legacy: create a temporary mutable user-space slice.
 ///
 /// NOTE: Prefer `copy_to_user` / `UserSlice` instead.  This returns a
-/// `'static` mut borrow that is unsound if the user buffer is deallocated.
+/// `'static` mut borrow that is unsound if the user buffer is deallocated,
+/// and with SMAP on, accessing it faults.
 ///
 /// # Safety
 ///
@@ -511,3 +613,43 @@ pub fn create_framebuffer_config(
         stride,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: u64 = 0x40_0000;
+
+    /// One user page at `PAGE`, writable, with nothing mapped after it.
+    fn one_page(vaddr: VirtAddr) -> Option<PageTableFlags> {
+        (vaddr.as_u64() == PAGE).then_some(
+            PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::WRITABLE,
+        )
+    }
+
+    #[test]
+    fn range_straddling_an_unmapped_page_is_rejected() {
+        let last_bytes = (PAGE + 4096 - 16) as *const u8;
+        assert_eq!(check_user_pages(last_bytes, 16, true, one_page), Ok(()));
+        assert_eq!(
+            check_user_pages(last_bytes, 17, false, one_page),
+            Err(SystemError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn kernel_only_and_read_only_pages_are_refused() {
+        let kernel_only = |_| Some(PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+        let read_only = |_| Some(PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE);
+        let ptr = PAGE as *const u8;
+        assert_eq!(
+            check_user_pages(ptr, 8, false, kernel_only),
+            Err(SystemError::PermissionDenied)
+        );
+        assert_eq!(check_user_pages(ptr, 8, false, read_only), Ok(()));
+        assert_eq!(
+            check_user_pages(ptr, 8, true, read_only),
+            Err(SystemError::PermissionDenied)
+        );
+    }
+}
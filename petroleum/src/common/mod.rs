@@ -6,6 +6,7 @@ pub enum BellowsError {
     Efi { status: uefi::EfiStatus },
     FileIo(&'static str),
     PeParse(&'static str),
+    ElfParse(&'static str),
     AllocationFailed(&'static str),
     InvalidState(&'static str),
     ProtocolNotFound(&'static str),
@@ -19,10 +20,12 @@ impl From<uefi::EfiStatus> for BellowsError {
 
 pub type Result<T> = core::result::Result<T, BellowsError>;
 
+pub mod boot_params;
 pub mod logging;
 #[macro_use]
 pub mod macros;
 pub mod memory;
+pub mod multiboot2;
 pub mod syscall;
 pub mod uefi;
 pub mod utils;
@@ -0,0 +1,431 @@
+//! Multiboot2 header and boot information.
+//!
+//! [`Header`] is what a Multiboot2 loader such as GRUB's `multiboot2`
+//! command looks for in the first 32 KiB of the kernel image.
+//! [`BootInformation`] walks the tags the loader passes back and translates
+//! them into what bellows hands over on the UEFI path: UEFI memory
+//! descriptors, a [`FullereneFramebufferConfig`], the command line and the
+//! ACPI RSDP, so that nothing after the entry point needs to know which
+//! loader ran.
+
+use super::uefi::{EfiGraphicsPixelFormat, EfiMemoryType, FullereneFramebufferConfig};
+use crate::page_table::memory_map::EfiMemoryDescriptor;
+
+/// Value of `eax` when a Multiboot2 loader enters the kernel.
+pub const BOOTLOADER_MAGIC: u32 = 0x36D7_6289;
+pub const HEADER_MAGIC: u32 = 0xE852_50D6;
+/// Protected-mode i386, the only architecture GRUB loads for x86.
+const ARCHITECTURE_I386: u32 = 0;
+
+const HEADER_TAG_END: u16 = 0;
+const HEADER_TAG_FRAMEBUFFER: u16 = 5;
+/// The loader may boot the kernel even if it cannot set the mode.
+const HEADER_TAG_OPTIONAL: u16 = 1;
+
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_MEMORY_MAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ACPI_OLD: u32 = 14;
+const TAG_ACPI_NEW: u32 = 15;
+
+const MEMORY_AVAILABLE: u32 = 1;
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+const PAGE_SIZE: u64 = 4096;
+
+/// Multiboot2 header asking for a linear framebuffer.
+#[repr(C, align(8))]
+pub struct Header {
+    magic: u32,
+    architecture: u32,
+    header_length: u32,
+    checksum: u32,
+    framebuffer_type: u16,
+    framebuffer_flags: u16,
+    framebuffer_size: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    // Tags start on 8-byte boundaries.
+    _padding: u32,
+    end_type: u16,
+    end_flags: u16,
+    end_size: u32,
+}
+
+impl Header {
+    /// A header asking for a `width`x`height` framebuffer of `depth` bits
+    /// per pixel; 0 leaves a dimension to the loader.
+    pub const fn new(width: u32, height: u32, depth: u32) -> Self {
+        let header_length = size_of::<Self>() as u32;
+        Self {
+            magic: HEADER_MAGIC,
+            architecture: ARCHITECTURE_I386,
+            header_length,
+            checksum: 0u32
+                .wrapping_sub(HEADER_MAGIC)
+                .wrapping_sub(ARCHITECTURE_I386)
+                .wrapping_sub(header_length),
+            framebuffer_type: HEADER_TAG_FRAMEBUFFER,
+            framebuffer_flags: HEADER_TAG_OPTIONAL,
+            framebuffer_size: 20,
+            width,
+            height,
+            depth,
+            _padding: 0,
+            end_type: HEADER_TAG_END,
+            end_flags: 0,
+            end_size: 8,
+        }
+    }
+}
+
+/// A region of the Multiboot2 memory map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub length: u64,
+    /// 1 for available RAM; 3 ACPI reclaimable, 4 ACPI NVS, 5 bad RAM and
+    /// anything else reserved.
+    pub kind: u32,
+}
+
+impl MemoryRegion {
+    /// The region as a UEFI descriptor: available RAM becomes conventional
+    /// memory shrunk to whole pages, everything else reserved memory grown
+    /// to whole pages.  `None` for available RAM smaller than a page.
+    pub fn to_efi_descriptor(&self) -> Option<EfiMemoryDescriptor> {
+        let end = self.base.saturating_add(self.length);
+        let (type_, start, end) = if self.kind == MEMORY_AVAILABLE {
+            (
+                EfiMemoryType::EfiConventionalMemory,
+                self.base.checked_next_multiple_of(PAGE_SIZE)?,
+                end & !(PAGE_SIZE - 1),
+            )
+        } else {
+            (
+                EfiMemoryType::EfiReservedMemoryType,
+                self.base & !(PAGE_SIZE - 1),
+                end.checked_next_multiple_of(PAGE_SIZE).unwrap_or(end),
+            )
+        };
+        (end > start).then_some(EfiMemoryDescriptor {
+            type_,
+            padding: 0,
+            physical_start: start,
+            virtual_start: 0,
+            number_of_pages: (end - start) / PAGE_SIZE,
+            attribute: 0,
+        })
+    }
+}
+
+/// Split `descriptor` around the pages `[start, end)` overlaps.  Of
+/// conventional memory, the parts below and above stay conventional and
+/// the part inside becomes loader data, which the frame allocator never
+/// hands out, as bellows' own image and boot data are on the UEFI path.
+/// Any other descriptor is returned whole.
+pub fn split_around(
+    descriptor: EfiMemoryDescriptor,
+    start: u64,
+    end: u64,
+) -> [Option<EfiMemoryDescriptor>; 3] {
+    let base = descriptor.physical_start;
+    let top = base + descriptor.number_of_pages * PAGE_SIZE;
+    let start = (start & !(PAGE_SIZE - 1)).clamp(base, top);
+    let end = end
+        .checked_next_multiple_of(PAGE_SIZE)
+        .unwrap_or(u64::MAX)
+        .clamp(base, top);
+    if descriptor.type_ != EfiMemoryType::EfiConventionalMemory || start >= end {
+        return [Some(descriptor), None, None];
+    }
+    let piece = |type_, from: u64, to: u64| {
+        (to > from).then_some(EfiMemoryDescriptor {
+            type_,
+            physical_start: from,
+            number_of_pages: (to - from) / PAGE_SIZE,
+            ..descriptor
+        })
+    };
+    [
+        piece(EfiMemoryType::EfiConventionalMemory, base, start),
+        piece(EfiMemoryType::EfiLoaderData, start, end),
+        piece(EfiMemoryType::EfiConventionalMemory, end, top),
+    ]
+}
+
+/// The boot information structure a Multiboot2 loader passes in `ebx`.
+#[derive(Clone, Copy)]
+pub struct BootInformation<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BootInformation<'a> {
+    /// Boot information in `bytes`, which must hold at least its
+    /// `total_size`.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let total_size = read_u32(bytes, 0)? as usize;
+        if total_size < 8 {
+            return None;
+        }
+        Some(Self {
+            bytes: bytes.get(..total_size)?,
+        })
+    }
+
+    /// Boot information at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a Multiboot2 boot information structure that
+    /// stays mapped and unchanged for `'a`.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Option<Self> {
+        let total_size = unsafe { ptr.cast::<u32>().read_unaligned() } as usize;
+        Self::new(unsafe { core::slice::from_raw_parts(ptr, total_size) })
+    }
+
+    /// Size in bytes of the whole structure, tags included.
+    pub fn total_size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Each tag's type and its bytes, including the 8-byte tag header.
+    fn tags(&self) -> impl Iterator<Item = (u32, &'a [u8])> + 'a {
+        let bytes = self.bytes;
+        let mut offset = 8;
+        core::iter::from_fn(move || {
+            let kind = read_u32(bytes, offset)?;
+            let size = read_u32(bytes, offset + 4)? as usize;
+            if kind == TAG_END || size < 8 {
+                return None;
+            }
+            let tag = bytes.get(offset..offset + size)?;
+            offset = (offset + size).next_multiple_of(8);
+            Some((kind, tag))
+        })
+    }
+
+    fn tag(&self, kind: u32) -> Option<&'a [u8]> {
+        self.tags()
+            .find(|&(tag_kind, _)| tag_kind == kind)
+            .map(|(_, tag)| tag)
+    }
+
+    /// The command line the loader was given.
+    pub fn cmdline(&self) -> Option<&'a str> {
+        let text = self.tag(TAG_CMDLINE)?.get(8..)?;
+        let len = text
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(text.len());
+        core::str::from_utf8(&text[..len]).ok()
+    }
+
+    /// The regions of the memory map.
+    pub fn memory_regions(&self) -> impl Iterator<Item = MemoryRegion> + 'a {
+        let tag = self.tag(TAG_MEMORY_MAP).unwrap_or(&[]);
+        let entry_size = read_u32(tag, 8).unwrap_or(0) as usize;
+        let entries = tag.get(16..).unwrap_or(&[]);
+        entries
+            .chunks_exact(entry_size.max(24))
+            .take_while(move |_| entry_size >= 24)
+            .filter_map(|entry| {
+                Some(MemoryRegion {
+                    base: read_u64(entry, 0)?,
+                    length: read_u64(entry, 8)?,
+                    kind: read_u32(entry, 16)?,
+                })
+            })
+    }
+
+    /// The framebuffer, if the loader set up a 32-bit RGB one.
+    pub fn framebuffer(&self) -> Option<FullereneFramebufferConfig> {
+        let tag = self.tag(TAG_FRAMEBUFFER)?;
+        let address = read_u64(tag, 8)?;
+        let pitch = read_u32(tag, 16)?;
+        let width = read_u32(tag, 20)?;
+        let height = read_u32(tag, 24)?;
+        let (bpp, kind) = (*tag.get(28)?, *tag.get(29)?);
+        if kind != FRAMEBUFFER_TYPE_RGB || bpp != 32 || address == 0 {
+            return None;
+        }
+        // Field positions and mask sizes of red, green and blue.
+        let pixel_format = match tag.get(32..38)? {
+            [0, 8, 8, 8, 16, 8] => EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor,
+            [16, 8, 8, 8, 0, 8] => EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor,
+            _ => return None,
+        };
+        Some(FullereneFramebufferConfig {
+            address,
+            width,
+            height,
+            pixel_format,
+            bpp: u32::from(bpp),
+            stride: pitch,
+        })
+    }
+
+    /// Offset within the boot information of the loader's copy of the ACPI
+    /// RSDP, preferring the ACPI 2.0 one.
+    pub fn rsdp_offset(&self) -> Option<usize> {
+        let start = self.bytes.as_ptr() as usize;
+        let tag = self.tag(TAG_ACPI_NEW).or_else(|| self.tag(TAG_ACPI_OLD))?;
+        Some(tag.as_ptr() as usize - start + 8)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn tag(info: &mut Vec<u8>, kind: u32, body: &[u8]) {
+        info.extend_from_slice(&kind.to_le_bytes());
+        info.extend_from_slice(&(8 + body.len() as u32).to_le_bytes());
+        info.extend_from_slice(body);
+        info.resize(info.len().next_multiple_of(8), 0);
+    }
+
+    fn boot_information() -> Vec<u8> {
+        let mut info = alloc::vec![0u8; 8];
+        tag(&mut info, TAG_CMDLINE, b"loglevel=debug novga\0");
+
+        let mut map = Vec::new();
+        map.extend_from_slice(&24u32.to_le_bytes());
+        map.extend_from_slice(&0u32.to_le_bytes());
+        for (base, length, kind) in [
+            (0u64, 0x9_FC00u64, MEMORY_AVAILABLE),
+            (0x9_FC00, 0x400, 2),
+            (0x10_0800, 0x7FE0_0000, MEMORY_AVAILABLE),
+        ] {
+            map.extend_from_slice(&base.to_le_bytes());
+            map.extend_from_slice(&length.to_le_bytes());
+            map.extend_from_slice(&kind.to_le_bytes());
+            map.extend_from_slice(&0u32.to_le_bytes());
+        }
+        tag(&mut info, TAG_MEMORY_MAP, &map);
+
+        let mut framebuffer = Vec::new();
+        framebuffer.extend_from_slice(&0xFD00_0000u64.to_le_bytes());
+        framebuffer.extend_from_slice(&(1280u32 * 4).to_le_bytes());
+        framebuffer.extend_from_slice(&1280u32.to_le_bytes());
+        framebuffer.extend_from_slice(&720u32.to_le_bytes());
+        framebuffer.extend_from_slice(&[32, FRAMEBUFFER_TYPE_RGB, 0, 0]);
+        framebuffer.extend_from_slice(&[16, 8, 8, 8, 0, 8]);
+        tag(&mut info, TAG_FRAMEBUFFER, &framebuffer);
+
+        tag(&mut info, TAG_ACPI_OLD, b"RSD PTR ");
+        tag(&mut info, TAG_END, &[]);
+        let total_size = info.len() as u32;
+        info[..4].copy_from_slice(&total_size.to_le_bytes());
+        info
+    }
+
+    #[test]
+    fn header_checksum_cancels_out() {
+        let header = Header::new(0, 0, 32);
+        assert_eq!(header.header_length as usize, 48);
+        assert_eq!(
+            header
+                .magic
+                .wrapping_add(header.architecture)
+                .wrapping_add(header.header_length)
+                .wrapping_add(header.checksum),
+            0
+        );
+    }
+
+    #[test]
+    fn split_around_keeps_the_image_out_of_conventional_memory() {
+        let ram = MemoryRegion {
+            base: 0x10_0000,
+            length: 0x40_0000,
+            kind: MEMORY_AVAILABLE,
+        }
+        .to_efi_descriptor()
+        .unwrap();
+        let pieces = |start, end| -> Vec<_> {
+            split_around(ram, start, end)
+                .into_iter()
+                .flatten()
+                .map(|desc| (desc.type_ as u32, desc.physical_start, desc.number_of_pages))
+                .collect()
+        };
+        let conventional = EfiMemoryType::EfiConventionalMemory as u32;
+        let loader = EfiMemoryType::EfiLoaderData as u32;
+
+        // Partial pages at either end of the hole are taken whole.
+        assert_eq!(
+            pieces(0x20_0800, 0x30_0001),
+            [
+                (conventional, 0x10_0000, 0x100),
+                (loader, 0x20_0000, 0x101),
+                (conventional, 0x30_1000, 0x1FF),
+            ]
+        );
+        // A hole over the start leaves only the tail; one elsewhere leaves
+        // the region whole.
+        assert_eq!(
+            pieces(0, 0x20_0000),
+            [(loader, 0x10_0000, 0x100), (conventional, 0x20_0000, 0x300)]
+        );
+        assert_eq!(
+            pieces(0x80_0000, 0x90_0000),
+            [(conventional, 0x10_0000, 0x400)]
+        );
+    }
+
+    #[test]
+    fn translates_the_boot_information() {
+        let bytes = boot_information();
+        let info = BootInformation::new(&bytes).unwrap();
+        assert_eq!(info.cmdline(), Some("loglevel=debug novga"));
+
+        let descriptors: Vec<_> = info
+            .memory_regions()
+            .filter_map(|region| region.to_efi_descriptor())
+            .map(|desc| (desc.type_ as u32, desc.physical_start, desc.number_of_pages))
+            .collect();
+        assert_eq!(
+            descriptors,
+            [
+                // Partial pages are dropped from RAM and kept in reserved
+                // regions.
+                (EfiMemoryType::EfiConventionalMemory as u32, 0, 0x9F),
+                (EfiMemoryType::EfiReservedMemoryType as u32, 0x9_F000, 1),
+                (
+                    EfiMemoryType::EfiConventionalMemory as u32,
+                    0x10_1000,
+                    0x7FDFF
+                ),
+            ]
+        );
+
+        let framebuffer = info.framebuffer().unwrap();
+        assert_eq!(framebuffer.address, 0xFD00_0000);
+        assert_eq!((framebuffer.width, framebuffer.height), (1280, 720));
+        assert_eq!(framebuffer.stride, 1280 * 4);
+        assert!(matches!(
+            framebuffer.pixel_format,
+            EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor
+        ));
+
+        let rsdp = info.rsdp_offset().unwrap();
+        assert_eq!(&bytes[rsdp..rsdp + 8], b"RSD PTR ");
+
+        assert!(BootInformation::new(&bytes[..bytes.len() - 8]).is_none());
+    }
+}
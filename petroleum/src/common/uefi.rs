@@ -333,7 +333,14 @@ pub struct EfiSimpleFileSystem {
 pub struct EfiLoadedImageProtocol {
     pub revision: u32,
     pub parent_handle: usize,
+    pub system_table: *mut EfiSystemTable,
     pub device_handle: usize,
+    pub file_path: *mut c_void,
+    pub reserved: *mut c_void,
+    /// Size of `load_options` in bytes
+    pub load_options_size: u32,
+    /// Command-line options (UCS-2) set by the shell or boot entry
+    pub load_options: *mut c_void,
     // more fields, but we only need these
 }
 
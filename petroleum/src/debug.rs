@@ -4,15 +4,127 @@
 //! and resolving addresses to file names and line numbers.
 
 use core::arch::asm;
-use core::fmt::Write;
+use core::fmt::{self, Write};
+use core::panic::Location;
+use x86_64::VirtAddr;
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::{PageTable, PageTableFlags};
+
+/// Frame pointers further than this above the stack pointer the walk started
+/// from are taken to be corrupt rather than belonging to the same stack.
+const MAX_STACK_SPAN: u64 = 1024 * 1024;
 
 /// Validate if an address is safe to dereference
 /// This is a basic check for stack frame pointers to prevent double faults
 /// during page fault handling when stacks might be corrupted.
 fn is_address_valid(addr: u64) -> bool {
-    // Basic checks: not within null page and reasonably aligned.
-    // The null pointer case is handled by the caller's loop.
-    addr >= 0x1000 && (addr as usize) % core::mem::size_of::<usize>() == 0
+    // Not within the null page, reasonably aligned, and backed by a present
+    // mapping in the active page table.
+    addr >= 0x1000
+        && (addr as usize).is_multiple_of(core::mem::size_of::<usize>())
+        && is_mapped(addr)
+}
+
+/// Whether `addr` is mapped in the page table CR3 points at.
+///
+/// Reads the tables through the physical memory offset, which covers them
+/// both under the firmware's identity map and after the kernel's own tables
+/// are loaded.
+fn is_mapped(addr: u64) -> bool {
+    let Ok(virt) = VirtAddr::try_new(addr) else {
+        return false;
+    };
+    let offset = crate::common::memory::get_physical_memory_offset() as u64;
+    let mut table_phys = Cr3::read().0.start_address().as_u64();
+    let indices = [
+        virt.p4_index(),
+        virt.p3_index(),
+        virt.p2_index(),
+        virt.p1_index(),
+    ];
+    for (level, index) in indices.into_iter().enumerate() {
+        let table = unsafe { &*((table_phys + offset) as *const PageTable) };
+        let entry = &table[index];
+        let flags = entry.flags();
+        if !flags.contains(PageTableFlags::PRESENT) {
+            return false;
+        }
+        // 1 GiB and 2 MiB pages end the walk early.
+        if level == 3 || (level > 0 && flags.contains(PageTableFlags::HUGE_PAGE)) {
+            return true;
+        }
+        table_phys = entry.addr().as_u64();
+    }
+    true
+}
+
+/// Control and stack registers captured for a crash report.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegisterSnapshot {
+    /// Address inside the function that took the snapshot.
+    pub rip: u64,
+    pub rsp: u64,
+    pub rbp: u64,
+    pub rflags: u64,
+    pub cr0: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+}
+
+impl RegisterSnapshot {
+    /// Read the registers as they are at the call site.
+    ///
+    /// Inlined so that RIP, RSP and RBP describe the caller's frame rather
+    /// than a frame of this function's own.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let mut regs = Self::default();
+        unsafe {
+            asm!(
+                "lea {rip}, [rip]",
+                "mov {rsp}, rsp",
+                "mov {rbp}, rbp",
+                rip = out(reg) regs.rip,
+                rsp = out(reg) regs.rsp,
+                rbp = out(reg) regs.rbp,
+                options(nomem, nostack, preserves_flags),
+            );
+            asm!(
+                "pushfq",
+                "pop {}",
+                out(reg) regs.rflags,
+                options(preserves_flags),
+            );
+            asm!(
+                "mov {cr0}, cr0",
+                "mov {cr2}, cr2",
+                "mov {cr3}, cr3",
+                "mov {cr4}, cr4",
+                cr0 = out(reg) regs.cr0,
+                cr2 = out(reg) regs.cr2,
+                cr3 = out(reg) regs.cr3,
+                cr4 = out(reg) regs.cr4,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        regs
+    }
+}
+
+impl fmt::Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  RIP={:#018x} RSP={:#018x} RBP={:#018x}",
+            self.rip, self.rsp, self.rbp
+        )?;
+        writeln!(
+            f,
+            "  RFLAGS={:#x} CR0={:#x} CR2={:#x} CR3={:#x} CR4={:#x}",
+            self.rflags, self.cr0, self.cr2, self.cr3, self.cr4
+        )
+    }
 }
 
 /// A simple backtrace entry
@@ -42,60 +154,75 @@ impl BacktraceCollector {
     /// This is a simplified implementation that works in no_std environments
     /// without full DWARF parsing. It relies on frame pointers being enabled.
     pub fn capture(&mut self) {
-        let mut rbp: *const usize;
+        let rbp: u64;
+        let rsp: u64;
         unsafe {
-            asm!("mov {}, rbp", out(reg) rbp);
+            asm!(
+                "mov {}, rbp",
+                "mov {}, rsp",
+                out(reg) rbp,
+                out(reg) rsp,
+                options(nomem, nostack, preserves_flags),
+            );
         }
+        self.capture_from(rbp, rsp);
+    }
+
+    /// Walk the frame-pointer chain starting at `rbp` on the stack that
+    /// `stack_pointer` points into.
+    ///
+    /// The walk stops at the first frame that is unmapped, misaligned, not
+    /// above the previous one, or more than [`MAX_STACK_SPAN`] above
+    /// `stack_pointer`, so a corrupt RBP ends the backtrace instead of
+    /// faulting.  The chain is only approximate on the UEFI target: its
+    /// Win64 prologues point RBP into the middle of a frame that saves other
+    /// registers or reserves more than 128 bytes, so such callers may be
+    /// missed or followed by a few bogus entries before the walk stops.
+    pub fn capture_from(&mut self, rbp: u64, stack_pointer: u64) {
+        self.walk(rbp, stack_pointer, is_address_valid);
+    }
 
+    fn walk(&mut self, rbp: u64, stack_pointer: u64, readable: impl Fn(u64) -> bool) {
+        let word = core::mem::size_of::<u64>() as u64;
         let mut frame = rbp;
         let mut i = 0;
 
-        while !frame.is_null() && i < self.entries.len() {
+        while frame != 0 && i < self.entries.len() {
+            let on_stack = frame >= stack_pointer && frame - stack_pointer < MAX_STACK_SPAN;
             // Validate pointers before dereferencing to prevent double faults during page handling
-            if !is_address_valid(frame as u64)
-                || !is_address_valid(
-                    (frame as usize).wrapping_add(core::mem::size_of::<usize>()) as u64
-                )
+            if !on_stack
+                || !frame.is_multiple_of(word)
+                || !readable(frame)
+                || !readable(frame + word)
             {
                 break;
             }
 
-            unsafe {
-                // Read return address from stack frame
-                let return_addr = *frame.offset(1);
-                let next_frame_ptr = *frame as *const usize;
-
-                if next_frame_ptr <= frame {
-                    // Stack grows downwards, so the next frame pointer should be at a higher address.
-                    // If not, the stack is likely corrupted or we've reached the end of the call chain.
-                    break;
-                }
-
-                if return_addr == 0 {
-                    break;
-                }
-
-                if let Some((symbol, file, line)) = resolve_address(return_addr as u64) {
-                    self.entries[i] = BacktraceEntry {
-                        ip: return_addr as u64,
-                        sp: (frame as usize + 16) as u64,
-                        symbol: Some(symbol),
-                        file: Some(file),
-                        line: Some(line),
-                    };
-                } else {
-                    self.entries[i] = BacktraceEntry {
-                        ip: return_addr as u64,
-                        sp: (frame as usize + 16) as u64,
-                        symbol: None,
-                        file: None,
-                        line: None,
-                    };
-                }
-
-                frame = next_frame_ptr;
-                i += 1;
+            // Saved RBP at [frame], return address just above it.
+            let (next_frame, return_addr) = unsafe {
+                let frame_ptr = frame as *const u64;
+                (frame_ptr.read(), frame_ptr.add(1).read())
+            };
+            if return_addr == 0 {
+                break;
             }
+
+            let resolved = resolve_address(return_addr);
+            self.entries[i] = BacktraceEntry {
+                ip: return_addr,
+                sp: frame + 2 * word,
+                symbol: resolved.map(|(symbol, _, _)| symbol),
+                file: resolved.map(|(_, file, _)| file),
+                line: resolved.map(|(_, _, line)| line),
+            };
+            i += 1;
+
+            if next_frame <= frame {
+                // Stack grows downwards, so the next frame pointer should be at a higher address.
+                // If not, the stack is likely corrupted or we've reached the end of the call chain.
+                break;
+            }
+            frame = next_frame;
         }
 
         self.count = i;
@@ -143,3 +270,184 @@ pub fn resolve_address(addr: u64) -> Option<(&'static str, &'static str, u32)> {
         Some(("unknown", "unknown", 0))
     }
 }
+
+/// Whether [`kassert!`](crate::kassert) and
+/// [`kassert_eq!`](crate::kassert_eq) check anything.  The `release_fast`
+/// feature turns them off; their conditions are then not evaluated, as with
+/// `debug_assert!`.
+pub const KASSERT_ENABLED: bool = !cfg!(feature = "release_fast");
+
+/// What a failed [`kassert!`](crate::kassert) or
+/// [`kassert_eq!`](crate::kassert_eq) prints.
+pub struct AssertionReport<'a> {
+    /// Source text of the condition.
+    pub condition: &'a str,
+    pub message: Option<fmt::Arguments<'a>>,
+    /// The two sides of a failed [`kassert_eq!`](crate::kassert_eq).
+    pub values: Option<(&'a dyn fmt::Debug, &'a dyn fmt::Debug)>,
+    pub location: &'a Location<'a>,
+}
+
+impl fmt::Display for AssertionReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\n====== KERNEL ASSERTION FAILED ======")?;
+        writeln!(
+            f,
+            "  at {}:{}:{}",
+            self.location.file(),
+            self.location.line(),
+            self.location.column()
+        )?;
+        writeln!(f, "  assertion `{}` failed", self.condition)?;
+        if let Some(message) = self.message {
+            writeln!(f, "  {}", message)?;
+        }
+        if let Some((left, right)) = self.values {
+            writeln!(f, "   left: {:?}", left)?;
+            writeln!(f, "  right: {:?}", right)?;
+        }
+        writeln!(f, "=====================================")
+    }
+}
+
+/// Report a failed [`kassert!`](crate::kassert) over serial and halt.
+///
+/// Writes straight to COM1, so it works before the logger and the heap are
+/// up.  Called by the macro; its location is the macro's call site.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub fn kassert_failed(condition: &str, message: Option<fmt::Arguments<'_>>) -> ! {
+    halt_with(&AssertionReport {
+        condition,
+        message,
+        values: None,
+        location: Location::caller(),
+    })
+}
+
+/// Report a failed [`kassert_eq!`](crate::kassert_eq) with both values over
+/// serial and halt.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub fn kassert_eq_failed(
+    condition: &str,
+    left: &dyn fmt::Debug,
+    right: &dyn fmt::Debug,
+    message: Option<fmt::Arguments<'_>>,
+) -> ! {
+    halt_with(&AssertionReport {
+        condition,
+        message,
+        values: Some((left, right)),
+        location: Location::caller(),
+    })
+}
+
+fn halt_with(report: &AssertionReport<'_>) -> ! {
+    x86_64::instructions::interrupts::disable();
+    crate::serial::_print(format_args!("{}", report));
+    crate::halt_loop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Walk `stack` as if it were the machine stack, treating only its own
+    /// words as readable.
+    fn walk_fake_stack(stack: &[u64], rbp: u64) -> BacktraceCollector {
+        let base = stack.as_ptr() as u64;
+        let end = base + core::mem::size_of_val(stack) as u64;
+        let mut collector = BacktraceCollector::new();
+        collector.walk(rbp, base, |addr| (base..end).contains(&addr));
+        collector
+    }
+
+    fn return_addresses(collector: &BacktraceCollector) -> alloc::vec::Vec<u64> {
+        collector.entries().iter().map(|entry| entry.ip).collect()
+    }
+
+    #[test]
+    fn follows_the_frame_chain_to_its_end() {
+        let mut stack = [0u64; 6];
+        let base = stack.as_ptr() as u64;
+        stack[0] = base + 16;
+        stack[1] = 0x1111;
+        stack[2] = base + 32;
+        stack[3] = 0x2222;
+        stack[4] = 0;
+        stack[5] = 0x3333;
+        let collector = walk_fake_stack(&stack, base);
+        assert_eq!(return_addresses(&collector), [0x1111, 0x2222, 0x3333]);
+        assert_eq!(collector.entries()[0].sp, base + 16);
+    }
+
+    #[test]
+    fn stops_at_a_corrupt_frame_pointer() {
+        let mut stack = [0u64; 4];
+        let base = stack.as_ptr() as u64;
+        // A frame that points back at itself.
+        stack[0] = base;
+        stack[1] = 0x1111;
+        assert_eq!(return_addresses(&walk_fake_stack(&stack, base)), [0x1111]);
+
+        // A frame that points off the stack.
+        stack[0] = base + 16;
+        stack[2] = 0xDEAD_BEEF_0000;
+        stack[3] = 0x2222;
+        assert_eq!(
+            return_addresses(&walk_fake_stack(&stack, base)),
+            [0x1111, 0x2222]
+        );
+
+        // A misaligned or out-of-range starting RBP yields nothing.
+        assert!(walk_fake_stack(&stack, base + 3).entries().is_empty());
+        assert!(walk_fake_stack(&stack, base - 16).entries().is_empty());
+    }
+
+    #[test]
+    fn assertion_report_shows_condition_message_values_and_location() {
+        #[track_caller]
+        fn here() -> &'static Location<'static> {
+            Location::caller()
+        }
+        let location = here();
+        let report = AssertionReport {
+            condition: "frames.len() == 4",
+            message: Some(format_args!("after {} pushes", 3)),
+            values: Some((&3usize, &4usize)),
+            location,
+        };
+        let text = alloc::format!("{}", report);
+        assert!(text.contains(&alloc::format!(
+            "at {}:{}:{}",
+            file!(),
+            location.line(),
+            location.column()
+        )));
+        assert!(text.contains("assertion `frames.len() == 4` failed\n  after 3 pushes\n"));
+        assert!(text.contains("   left: 3\n  right: 4\n"));
+
+        let report = AssertionReport {
+            condition: "ready",
+            message: None,
+            values: None,
+            location,
+        };
+        let text = alloc::format!("{}", report);
+        assert!(text.contains("assertion `ready` failed\n===="));
+    }
+
+    #[test]
+    fn caps_the_number_of_frames() {
+        let mut stack = [0u64; 80];
+        let base = stack.as_ptr() as u64;
+        for frame in (0..stack.len()).step_by(2) {
+            stack[frame] = base + (frame as u64 + 2) * 8;
+            stack[frame + 1] = 0x1000 + frame as u64;
+        }
+        assert_eq!(walk_fake_stack(&stack, base).entries().len(), 32);
+    }
+}
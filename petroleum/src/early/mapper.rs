@@ -102,7 +102,12 @@ impl EarlyMapper {
             let frame = PhysFrame::containing_address(phys);
             // SAFETY: The caller ensures safety.
             mapper
-                .map_to(page, frame, flags, frame_allocator)
+                .map_to(
+                    page,
+                    frame,
+                    crate::page_table::supported_flags(flags),
+                    frame_allocator,
+                )
                 .map_err(|_| crate::MemoryError::MappingFailed)?
                 .flush();
             Ok(())
@@ -146,7 +151,7 @@ impl EarlyMapper {
         frame_allocator: &mut EarlyFrameAllocator,
     ) -> Result<(), crate::MemoryError> {
         unsafe {
-            let flags_2mb = flags | PageTableFlags::HUGE_PAGE;
+            let flags_2mb = crate::page_table::supported_flags(flags | PageTableFlags::HUGE_PAGE);
             for i in 0..page_count {
                 let virt = VirtAddr::new(virt_start.as_u64() + i * 2 * 1024 * 1024);
                 let phys = PhysAddr::new(phys_start.as_u64() + i * 2 * 1024 * 1024);
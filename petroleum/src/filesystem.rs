@@ -1,5 +1,7 @@
 use crate::common::{
-    BellowsError, EFI_FILE_INFO_GUID, EfiBootServices, EfiFile, EfiFileInfo, EfiStatus,
+    BellowsError, EFI_FILE_INFO_GUID, EFI_LOADED_IMAGE_PROTOCOL_GUID,
+    EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID, EfiBootServices, EfiFile, EfiFileInfo,
+    EfiLoadedImageProtocol, EfiSimpleFileSystem, EfiStatus,
 };
 use core::ffi::c_void;
 use core::ptr;
@@ -7,13 +9,23 @@ use log;
 
 const EFI_FILE_MODE_READ: u64 = 0x1;
 const KERNEL_PATH: &str = r"EFI\BOOT\KERNEL.EFI";
+const INITRD_PATH: &str = r"EFI\BOOT\INITRD.CPIO";
 
 /// Fixed UTF-16 encode for KERNEL_PATH (no alloc).
 pub fn kernel_path_utf16() -> [u16; 32] {
+    path_utf16(KERNEL_PATH)
+}
+
+/// Fixed UTF-16 encode for INITRD_PATH (no alloc).
+pub fn initrd_path_utf16() -> [u16; 32] {
+    path_utf16(INITRD_PATH)
+}
+
+fn path_utf16(path: &str) -> [u16; 32] {
     let mut buf = [0u16; 32];
     let mut i = 0;
-    // KERNEL_PATH is a constant, so we can rely on its length being less than 31.
-    for c in KERNEL_PATH.encode_utf16() {
+    // The paths are constants, so we can rely on their length being less than 31.
+    for c in path.encode_utf16() {
         if i >= buf.len() - 1 {
             break; // Path too long, should not happen for a constant
         }
@@ -50,6 +62,47 @@ impl Drop for EfiFileWrapper {
     }
 }
 
+/// Open the root directory of the volume the image `image_handle` was
+/// loaded from, e.g. the ESP of the boot ISO.
+pub fn open_boot_volume(
+    bs: &EfiBootServices,
+    image_handle: usize,
+) -> crate::common::Result<EfiFileWrapper> {
+    let mut interface: *mut c_void = ptr::null_mut();
+    let status = (bs.handle_protocol)(
+        image_handle,
+        EFI_LOADED_IMAGE_PROTOCOL_GUID.as_ptr(),
+        &mut interface,
+    );
+    if EfiStatus::from(status) != EfiStatus::Success || interface.is_null() {
+        log::error!("File: Failed to get the loaded image protocol.");
+        return Err(BellowsError::FileIo(
+            "Failed to get the loaded image protocol.",
+        ));
+    }
+    let device_handle = unsafe { (*(interface as *const EfiLoadedImageProtocol)).device_handle };
+
+    let mut interface: *mut c_void = ptr::null_mut();
+    let status = (bs.handle_protocol)(
+        device_handle,
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID.as_ptr(),
+        &mut interface,
+    );
+    if EfiStatus::from(status) != EfiStatus::Success || interface.is_null() {
+        log::error!("File: Boot device has no file system.");
+        return Err(BellowsError::FileIo("Boot device has no file system."));
+    }
+    let file_system = interface as *mut EfiSimpleFileSystem;
+
+    let mut root: *mut EfiFile = ptr::null_mut();
+    let status = unsafe { ((*file_system).open_volume)(file_system, &mut root) };
+    if EfiStatus::from(status) != EfiStatus::Success || root.is_null() {
+        log::error!("File: Failed to open the boot volume.");
+        return Err(BellowsError::FileIo("Failed to open the boot volume."));
+    }
+    Ok(EfiFileWrapper::new(root))
+}
+
 /// Helper function to open a file from a directory handle.
 pub fn open_file(dir: &EfiFileWrapper, path: &[u16]) -> crate::common::Result<EfiFileWrapper> {
     let mut file_handle: *mut EfiFile = ptr::null_mut();
@@ -0,0 +1,262 @@
+//! ANSI escape sequences for the text consoles.
+//!
+//! [`AnsiParser`] splits a byte stream into printable bytes and CSI
+//! (`ESC [`) sequences.  [`TextBufferOperations::write_string`] applies the
+//! sequences the consoles understand — SGR colours, cursor positioning and
+//! erase-display — and drops everything else, so unsupported sequences never
+//! reach the screen as literal text.
+//!
+//! [`TextBufferOperations::write_string`]: super::text::TextBufferOperations::write_string
+
+use super::text::ColorCode;
+
+const ESC: u8 = 0x1b;
+/// Parameters kept per sequence; later ones are parsed but dropped.
+const MAX_PARAMS: usize = 8;
+
+/// VGA colour index for each of the eight ANSI colours (black, red, green,
+/// yellow, blue, magenta, cyan, white).
+const ANSI_TO_VGA: [u8; 8] = [0x0, 0x4, 0x2, 0x6, 0x1, 0x5, 0x3, 0x7];
+/// VGA attribute bit that selects the bright half of the palette.
+const BRIGHT: u8 = 0x8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// What one input byte amounts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiEvent {
+    /// A byte to print.
+    Print(u8),
+    /// A complete control sequence.
+    Csi(CsiSequence),
+}
+
+/// A parsed `ESC [ params final` sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsiSequence {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    /// Set by a `?`, `<`, `=` or `>` prefix or an intermediate byte; such
+    /// sequences are private extensions that the consoles ignore.
+    private: bool,
+    pub final_byte: u8,
+}
+
+impl CsiSequence {
+    /// The numeric parameters; an empty field reads as 0.
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    /// Parameter `index`, or 0 when it was not given.
+    pub fn param(&self, index: usize) -> u16 {
+        self.params().get(index).copied().unwrap_or(0)
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+}
+
+/// Byte-at-a-time escape sequence parser.
+///
+/// State carries over between calls, so a sequence split across two writes
+/// is still recognised.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnsiParser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    /// Fields started so far, including any beyond `MAX_PARAMS`.
+    fields: usize,
+    private: bool,
+}
+
+impl AnsiParser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            fields: 0,
+            private: false,
+        }
+    }
+
+    /// Feed one byte; returns what it completes, if anything.
+    pub fn feed(&mut self, byte: u8) -> Option<AnsiEvent> {
+        match self.state {
+            State::Ground if byte == ESC => {
+                self.state = State::Escape;
+                None
+            }
+            State::Ground => Some(AnsiEvent::Print(byte)),
+            State::Escape if byte == b'[' => {
+                *self = Self {
+                    state: State::Csi,
+                    fields: 1,
+                    ..Self::new()
+                };
+                None
+            }
+            // Two-byte escapes such as `ESC c` are consumed and ignored.
+            State::Escape => {
+                self.state = State::Ground;
+                None
+            }
+            State::Csi => self.feed_csi(byte),
+        }
+    }
+
+    fn feed_csi(&mut self, byte: u8) -> Option<AnsiEvent> {
+        match byte {
+            b'0'..=b'9' => {
+                if let Some(param) = self.params.get_mut(self.fields - 1) {
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add(u16::from(byte - b'0'));
+                }
+                None
+            }
+            b';' => {
+                self.fields = self.fields.saturating_add(1);
+                None
+            }
+            b'<'..=b'?' | 0x20..=0x2f => {
+                self.private = true;
+                None
+            }
+            0x40..=0x7e => {
+                self.state = State::Ground;
+                Some(AnsiEvent::Csi(CsiSequence {
+                    params: self.params,
+                    len: self.fields.min(MAX_PARAMS),
+                    private: self.private,
+                    final_byte: byte,
+                }))
+            }
+            // Anything else cannot be part of a sequence; drop what was
+            // collected rather than print half of it.
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+}
+
+/// `color` after the SGR (`ESC [ … m`) parameters `params`.
+///
+/// Handles reset (0), bold as the bright palette half (1, 22), the eight
+/// foreground and background colours (30–37, 40–47), their bright forms
+/// (90–97, 100–107) and the defaults (39, 49), which come from `default`.
+/// Other attributes are ignored.
+pub fn apply_sgr(color: ColorCode, default: ColorCode, params: &[u16]) -> ColorCode {
+    let (mut fg, mut bg) = (color.0 & 0x0F, color.0 >> 4);
+    for &param in params {
+        match param {
+            0 => (fg, bg) = (default.0 & 0x0F, default.0 >> 4),
+            1 => fg |= BRIGHT,
+            22 => fg &= !BRIGHT,
+            30..=37 => fg = ANSI_TO_VGA[usize::from(param - 30)] | (fg & BRIGHT),
+            39 => fg = default.0 & 0x0F,
+            40..=47 => bg = ANSI_TO_VGA[usize::from(param - 40)],
+            49 => bg = default.0 >> 4,
+            90..=97 => fg = ANSI_TO_VGA[usize::from(param - 90)] | BRIGHT,
+            100..=107 => bg = ANSI_TO_VGA[usize::from(param - 100)] | BRIGHT,
+            _ => {}
+        }
+    }
+    ColorCode(bg << 4 | fg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::text::Color;
+    use alloc::vec::Vec;
+
+    fn events(input: &[u8]) -> Vec<AnsiEvent> {
+        let mut parser = AnsiParser::new();
+        input.iter().filter_map(|&byte| parser.feed(byte)).collect()
+    }
+
+    fn csi(input: &[u8]) -> CsiSequence {
+        match events(input).as_slice() {
+            [AnsiEvent::Csi(sequence)] => *sequence,
+            other => panic!("expected one sequence, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn splits_text_from_sequences() {
+        let parsed = events(b"a\x1b[1;31mb");
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0], AnsiEvent::Print(b'a'));
+        assert_eq!(parsed[2], AnsiEvent::Print(b'b'));
+        let AnsiEvent::Csi(sequence) = parsed[1] else {
+            panic!("{parsed:?}");
+        };
+        assert_eq!(sequence.final_byte, b'm');
+        assert_eq!(sequence.params(), [1, 31]);
+    }
+
+    #[test]
+    fn parses_parameters() {
+        assert_eq!(csi(b"\x1b[H").params(), [0]);
+        assert_eq!(csi(b"\x1b[;7H").params(), [0, 7]);
+        assert_eq!(csi(b"\x1b[12;40H").param(1), 40);
+        assert_eq!(csi(b"\x1b[2J").param(5), 0);
+        assert!(csi(b"\x1b[?25l").is_private());
+        assert!(!csi(b"\x1b[2J").is_private());
+        // Oversized numbers saturate; extra fields are dropped.
+        assert_eq!(csi(b"\x1b[99999m").params(), [u16::MAX]);
+        assert_eq!(
+            csi(b"\x1b[1;2;3;4;5;6;7;8;9;10m").params().len(),
+            MAX_PARAMS
+        );
+    }
+
+    #[test]
+    fn consumes_unknown_and_broken_escapes() {
+        assert_eq!(events(b"\x1bcX"), [AnsiEvent::Print(b'X')]);
+        assert_eq!(events(b"\x1b[12\nX"), [AnsiEvent::Print(b'X')]);
+    }
+
+    #[test]
+    fn keeps_state_between_writes() {
+        let mut parser = AnsiParser::new();
+        assert!(b"\x1b[3".iter().all(|&byte| parser.feed(byte).is_none()));
+        let Some(AnsiEvent::Csi(sequence)) = parser.feed(b'J') else {
+            panic!();
+        };
+        assert_eq!(sequence.params(), [3]);
+        assert_eq!(parser.feed(b'x'), Some(AnsiEvent::Print(b'x')));
+    }
+
+    #[test]
+    fn maps_sgr_to_vga_attributes() {
+        let default = ColorCode::new(Color::LightGray, Color::Black);
+        let red = apply_sgr(default, default, &[31]);
+        assert_eq!(red.0, ColorCode::new(Color::Red, Color::Black).0);
+        let bold_yellow_on_blue = apply_sgr(default, default, &[1, 33, 44]);
+        assert_eq!(
+            bold_yellow_on_blue.0,
+            ColorCode::new(Color::Yellow, Color::Blue).0
+        );
+        // Bold outlives a colour change until 22 or a reset.
+        let bold_green = apply_sgr(bold_yellow_on_blue, default, &[32]);
+        assert_eq!(bold_green.0 & 0x0F, Color::LightGreen as u8);
+        assert_eq!(
+            apply_sgr(bold_green, default, &[22]).0 & 0x0F,
+            Color::Green as u8
+        );
+        assert_eq!(apply_sgr(bold_green, default, &[0]).0, default.0);
+        assert_eq!(apply_sgr(red, default, &[96, 101]).0, 0xCB);
+        assert_eq!(apply_sgr(red, default, &[39, 4]).0, default.0);
+    }
+}
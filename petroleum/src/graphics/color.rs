@@ -201,9 +201,13 @@ pub struct SimpleFramebuffer {
     pub height: usize,
     pub stride: usize, // bytes per row
     pub bytes_per_pixel: usize,
-    /// Controls byte ordering: `None` = VGA indexed, `Some(RGB)` → LE bytes [R,G,B,A],
-    /// `Some(BGR)` → LE bytes [B,G,R,A].
+    /// Controls byte ordering: `Some(RGB)` → bytes [R,G,B,A], `Some(BGR)` or
+    /// `None` → bytes [B,G,R,A]; 24 bpp drops the last byte, 16 bpp packs
+    /// the same order into 5:6:5 bits and 8 bpp (VGA) stores a palette index.
     pub pixel_format: Option<crate::common::EfiGraphicsPixelFormat>,
+    /// Heap back buffer (`width * bytes_per_pixel` bytes per row) that drawing
+    /// targets until [`Self::present`]; `None` draws straight to the hardware.
+    back_buffer: Option<alloc::vec::Vec<u8>>,
 }
 
 impl SimpleFramebuffer {
@@ -216,23 +220,79 @@ impl SimpleFramebuffer {
             stride: config.stride,
             bytes_per_pixel: config.bytes_per_pixel,
             pixel_format: config.pixel_format,
+            back_buffer: None,
+        }
+    }
+
+    /// Create a framebuffer that draws into a heap back buffer and only
+    /// touches the hardware on [`Self::present`].
+    ///
+    /// Falls back to direct drawing if the back buffer cannot be allocated.
+    pub fn with_double_buffer(config: SimpleFramebufferConfig) -> Self {
+        let mut fb = Self::new(config);
+        let len = fb.width * fb.bytes_per_pixel * fb.height;
+        let mut buffer = alloc::vec::Vec::new();
+        if len > 0 && buffer.try_reserve_exact(len).is_ok() {
+            buffer.resize(len, 0);
+            fb.back_buffer = Some(buffer);
+        }
+        fb
+    }
+
+    /// Whether drawing currently targets a back buffer.
+    pub fn is_double_buffered(&self) -> bool {
+        self.back_buffer.is_some()
+    }
+
+    /// Copy the back buffer to the hardware framebuffer.  No-op when drawing
+    /// directly.
+    pub fn present(&mut self) {
+        let Some(buffer) = self.back_buffer.as_ref() else {
+            return;
+        };
+        let row_bytes = self.width * self.bytes_per_pixel;
+        unsafe {
+            if row_bytes == self.stride {
+                core::ptr::copy_nonoverlapping(buffer.as_ptr(), self.base as *mut u8, buffer.len());
+            } else {
+                // Hardware rows are padded to `stride`; copy row by row.
+                for (y, row) in buffer.chunks_exact(row_bytes).enumerate() {
+                    core::ptr::copy_nonoverlapping(
+                        row.as_ptr(),
+                        (self.base + y * self.stride) as *mut u8,
+                        row_bytes,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Start address, row pitch and byte length of the surface drawing targets.
+    fn surface(&mut self) -> (usize, usize, usize) {
+        match self.back_buffer.as_mut() {
+            Some(buffer) => (
+                buffer.as_mut_ptr() as usize,
+                self.width * self.bytes_per_pixel,
+                buffer.len(),
+            ),
+            None => (self.base, self.stride, self.height * self.stride),
         }
     }
 
     /// Clear the entire framebuffer
     pub fn clear(&mut self, color: u32) {
-        let color_bytes = color.to_le_bytes();
+        let color_bytes = self.encode(color);
+        let (base, pitch, len) = self.surface();
         for y in 0..self.height {
-            let row_base = self.base + y * self.stride;
+            let row_base = base + y * pitch;
             for x in 0..self.width {
                 let offset = x * self.bytes_per_pixel;
                 let pixel_addr = (row_base + offset) as *mut u8;
 
                 // Check that the calculated pixel_addr is within the valid framebuffer memory region
                 let pixel_addr_usize = pixel_addr as usize;
-                if pixel_addr_usize < self.base
-                    || (pixel_addr_usize + self.bytes_per_pixel)
-                        > (self.base + self.height * self.stride)
+                if pixel_addr_usize < base
+                    || (pixel_addr_usize + self.bytes_per_pixel) > (base + len)
                 {
                     continue;
                 }
@@ -248,56 +308,98 @@ impl SimpleFramebuffer {
         }
     }
 
+    /// `color` (`0x00RRGGBB`) as the bytes of one pixel in the hardware
+    /// format; only the first `bytes_per_pixel` of them are stored.
+    ///
+    /// BGR framebuffers (and 32 bpp ones without a known format) take the
+    /// little-endian bytes of `color` as they are, RGB ones swap R and B, and
+    /// the 8 bpp VGA fallback gets the nearest VGA palette index.  16 bpp
+    /// keeps the top 5, 6 and 5 bits of each channel, blue lowest unless
+    /// the format is RGB.
+    fn encode(&self, color: u32) -> [u8; 4] {
+        let [b, g, r, reserved] = color.to_le_bytes();
+        let rgb = self.pixel_format
+            == Some(EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor);
+        match self.bytes_per_pixel {
+            1 => [vga_color_index(r, g, b) as u8, 0, 0, 0],
+            2 => {
+                let (low, high) = if rgb { (r, b) } else { (b, r) };
+                let packed =
+                    u16::from(high >> 3) << 11 | u16::from(g >> 2) << 5 | u16::from(low >> 3);
+                let [first, second] = packed.to_le_bytes();
+                [first, second, 0, 0]
+            }
+            _ if rgb => [r, g, b, reserved],
+            _ => [b, g, r, reserved],
+        }
+    }
+
     /// Draw a single pixel (orbclient-style)
     ///
-    /// `color` is expected as `0x00RRGGBB` (RGB order).  For BGRA framebuffers
-    /// the u32 is written as-is (LE bytes naturally produce B,G,R,A order).
-    /// For RGBA the R and B bytes are swapped.  For VGA 8-bit the low byte is used.
+    /// `color` is `0x00RRGGBB` and is converted to the framebuffer's pixel
+    /// format and depth (see [`Self::encode`]): 32, 24 and 16 bpp RGB or
+    /// BGR, or a palette index on the 8 bpp VGA fallback.
     pub fn draw_pixel(&mut self, x: usize, y: usize, color: u32) {
         if x >= self.width || y >= self.height {
             return;
         }
-        let row_base = self.base + y * self.stride;
+        let (base, pitch, len) = self.surface();
+        let row_base = base + y * pitch;
         let offset = x * self.bytes_per_pixel;
         let pixel_addr = (row_base + offset) as *mut u8;
 
         // Check that the calculated pixel_addr is within the valid framebuffer memory region
         let pixel_addr_usize = pixel_addr as usize;
-        if pixel_addr_usize < self.base
-            || (pixel_addr_usize + self.bytes_per_pixel) > (self.base + self.height * self.stride)
-        {
+        if pixel_addr_usize < base || (pixel_addr_usize + self.bytes_per_pixel) > (base + len) {
             return;
         }
 
+        let bytes = self.encode(color);
         unsafe {
-            match (self.bytes_per_pixel, self.pixel_format) {
-                // 32 bpp BGRA → LE bytes naturally [B, G, R, A] from `0x00RRGGBB`
-                (4, Some(crate::common::EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor))
-                | (4, None) => {
-                    write_volatile(pixel_addr as *mut u32, color);
-                }
-                // 32 bpp RGBA → LE should be [R, G, B, A]; swap R↔B in the u32 value
-                (4, Some(crate::common::EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor)) => {
-                    let swapped = (color & 0xFF00FF00)
-                        | ((color & 0x00FF0000) >> 16)
-                        | ((color & 0x000000FF) << 16);
-                    write_volatile(pixel_addr as *mut u32, swapped);
-                }
-                // 8 bpp VGA (indexed) – use low byte
-                (1, _) => {
-                    write_volatile(pixel_addr, (color & 0xFF) as u8);
-                }
-                // fallback: byte-by-byte (type-agnostic)
-                _ => {
-                    let color_bytes = color.to_le_bytes();
-                    for i in 0..self.bytes_per_pixel.min(4) {
-                        write_volatile(pixel_addr.add(i), color_bytes[i]);
-                    }
+            if self.bytes_per_pixel == 4 {
+                // One 32-bit store, so a pixel is never seen half written.
+                write_volatile(pixel_addr as *mut u32, u32::from_le_bytes(bytes));
+            } else {
+                for (i, &byte) in bytes.iter().take(self.bytes_per_pixel).enumerate() {
+                    write_volatile(pixel_addr.add(i), byte);
                 }
             }
         }
     }
 
+    /// Draw `argb` (`0xAARRGGBB`) over the existing pixel using its alpha.
+    ///
+    /// Alpha `0x00` leaves the pixel untouched and `0xFF` writes it as-is.
+    /// Blending needs 32 bpp, so this does nothing on the 8 bpp VGA fallback.
+    pub fn blend_pixel(&mut self, x: usize, y: usize, argb: u32) {
+        if self.bytes_per_pixel != 4 || x >= self.width || y >= self.height {
+            return;
+        }
+        let alpha = argb >> 24;
+        if alpha == 0x00 {
+            return;
+        }
+        if alpha == 0xFF {
+            self.draw_pixel(x, y, argb & 0x00FF_FFFF);
+            return;
+        }
+        let mut dst = self.get_pixel(x, y);
+        if self.pixel_format
+            == Some(crate::common::EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor)
+        {
+            // Stored as [R, G, B, A]; bring it back to `0x00RRGGBB`.
+            dst = (dst & 0xFF00FF00) | ((dst & 0x00FF0000) >> 16) | ((dst & 0x000000FF) << 16);
+        }
+        let mut blended = 0;
+        for shift in [0, 8, 16] {
+            let src = (argb >> shift) & 0xFF;
+            let dst = (dst >> shift) & 0xFF;
+            let channel = (src * alpha + dst * (0xFF - alpha) + 0x7F) / 0xFF;
+            blended |= channel << shift;
+        }
+        self.draw_pixel(x, y, blended);
+    }
+
     /// Draw a filled rectangle (orbclient-style)
     pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
         for dy in 0..height {
@@ -313,15 +415,120 @@ impl SimpleFramebuffer {
         }
     }
 
-    /// Read a pixel (for reference, though not used in Redox)
+    /// Draw a line between two points (integer Bresenham)
+    ///
+    /// Any slope and either endpoint order is accepted.  The segment is
+    /// clipped to the framebuffer first, so off-screen endpoints are fine.
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u32) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let Some((x0, y0, x1, y1)) = clip_line(
+            (x0 as i64, y0 as i64),
+            (x1 as i64, y1 as i64),
+            self.width as i64 - 1,
+            self.height as i64 - 1,
+        ) else {
+            return;
+        };
+
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            self.draw_pixel(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draw the outline of a circle (midpoint algorithm)
+    ///
+    /// A zero radius draws the centre pixel.  Points falling outside the
+    /// framebuffer are skipped, so the circle may hang off any edge.
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: u32, color: u32) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        for_each_octant_point(radius, |x, y| {
+            for (px, py) in [
+                (cx + x, cy + y),
+                (cx - x, cy + y),
+                (cx + x, cy - y),
+                (cx - x, cy - y),
+                (cx + y, cy + x),
+                (cx - y, cy + x),
+                (cx + y, cy - x),
+                (cx - y, cy - x),
+            ] {
+                self.plot(px, py, color);
+            }
+        });
+    }
+
+    /// Draw a filled circle, clipped like [`Self::draw_circle`].
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, radius: u32, color: u32) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        for_each_octant_point(radius, |x, y| {
+            self.fill_span(cx - x, cx + x, cy + y, color);
+            self.fill_span(cx - x, cx + x, cy - y, color);
+            self.fill_span(cx - y, cx + y, cy + x, color);
+            self.fill_span(cx - y, cx + y, cy - x, color);
+        });
+    }
+
+    /// `draw_pixel` for signed coordinates that may lie off-screen.
+    fn plot(&mut self, x: i64, y: i64, color: u32) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.draw_pixel(x, y, color);
+        }
+    }
+
+    /// Fill row `y` from `x0` to `x1` inclusive, clipped to the framebuffer.
+    fn fill_span(&mut self, x0: i64, x1: i64, y: i64, color: u32) {
+        if y < 0 || y >= self.height as i64 {
+            return;
+        }
+        let x0 = x0.max(0);
+        let x1 = x1.min(self.width as i64 - 1);
+        for x in x0..=x1 {
+            self.draw_pixel(x as usize, y as usize, color);
+        }
+    }
+
+    /// Read a pixel back as stored, in the hardware format: the
+    /// little-endian value of its `bytes_per_pixel` bytes.
     pub fn get_pixel(&self, x: usize, y: usize) -> u32 {
         if x >= self.width || y >= self.height {
             return 0;
         }
-        let row_base = self.base + y * self.stride;
+        let (base, pitch) = match self.back_buffer.as_ref() {
+            Some(buffer) => (buffer.as_ptr() as usize, self.width * self.bytes_per_pixel),
+            None => (self.base, self.stride),
+        };
         let offset = x * self.bytes_per_pixel;
-        let pixel_addr = (row_base + offset) as *const u32;
-        unsafe { read_volatile(pixel_addr) }
+        let pixel_addr = (base + y * pitch + offset) as *const u8;
+        unsafe {
+            if self.bytes_per_pixel == 4 {
+                return read_volatile(pixel_addr as *const u32);
+            }
+            let mut bytes = [0u8; 4];
+            for (i, byte) in bytes.iter_mut().take(self.bytes_per_pixel).enumerate() {
+                *byte = read_volatile(pixel_addr.add(i));
+            }
+            u32::from_le_bytes(bytes)
+        }
     }
 
     /// Get framebuffer dimensions
@@ -330,6 +537,78 @@ impl SimpleFramebuffer {
     }
 }
 
+/// Walk the first octant of a circle of `radius` around the origin, from
+/// `(radius, 0)` until `x < y`, calling `f(x, y)` for each point.
+fn for_each_octant_point(radius: u32, mut f: impl FnMut(i64, i64)) {
+    let (mut x, mut y) = (radius as i64, 0i64);
+    let mut err = 1 - x;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+/// Cohen–Sutherland clip of the segment `a`–`b` to `[0, max_x] × [0, max_y]`.
+/// Returns `None` when the segment lies entirely outside.
+fn clip_line(a: (i64, i64), b: (i64, i64), max_x: i64, max_y: i64) -> Option<(i64, i64, i64, i64)> {
+    const LEFT: u8 = 1;
+    const RIGHT: u8 = 2;
+    const TOP: u8 = 4;
+    const BOTTOM: u8 = 8;
+    let outcode = |(x, y): (i64, i64)| {
+        let mut code = 0;
+        if x < 0 {
+            code |= LEFT;
+        } else if x > max_x {
+            code |= RIGHT;
+        }
+        if y < 0 {
+            code |= TOP;
+        } else if y > max_y {
+            code |= BOTTOM;
+        }
+        code
+    };
+
+    let (mut a, mut b) = (a, b);
+    let (mut code_a, mut code_b) = (outcode(a), outcode(b));
+    loop {
+        if code_a | code_b == 0 {
+            return Some((a.0, a.1, b.0, b.1));
+        }
+        if code_a & code_b != 0 {
+            return None;
+        }
+        // Move the outside endpoint onto the boundary it violates. The
+        // divisors are non-zero: a zero span would put both ends outside
+        // the same edge, which returned above.
+        let code = if code_a != 0 { code_a } else { code_b };
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let point = if code & TOP != 0 {
+            (a.0 + dx * -a.1 / dy, 0)
+        } else if code & BOTTOM != 0 {
+            (a.0 + dx * (max_y - a.1) / dy, max_y)
+        } else if code & RIGHT != 0 {
+            (max_x, a.1 + dy * (max_x - a.0) / dx)
+        } else {
+            (0, a.1 + dy * -a.0 / dx)
+        };
+        if code == code_a {
+            a = point;
+            code_a = outcode(a);
+        } else {
+            b = point;
+            code_b = outcode(b);
+        }
+    }
+}
+
 // --- Button and Drawing Macros ---
 // UI Color constants for desktop graphics
 pub const COLOR_LIGHT_GRAY: u32 = 0xE0E0E0;
@@ -397,7 +676,7 @@ impl Button {
             self.height,
             self.bg_color,
         );
-        let text_width = crate::calc_text_width(&self.text);
+        let text_width = super::text::measure(&self.text, super::text::Font::Font6x10) as i32;
         let text_x = self.x as i32 + (self.width as i32 / 2) - (text_width / 2);
         renderer.draw_text(
             text_x,
@@ -475,7 +754,7 @@ impl Window {
         title_rect.into_styled(title_style).draw(writer).ok();
 
         let title_text_style = MonoTextStyle::new(&FONT_6X10, u32_to_rgb888(COLOR_BLACK));
-        let title_width = calc_text_width(&self.title);
+        let title_width = super::text::measure(&self.title, super::text::Font::Font6x10) as i32;
         let title_x = self.x as i32 + ((self.width as i32 / 2) - (title_width as i32 / 2));
         Text::new(
             &self.title,
@@ -507,11 +786,6 @@ impl Icon {
     }
 }
 
-// Text width calculation for monospaced font
-pub fn calc_text_width(text: &str) -> i32 {
-    (text.len() * 6) as i32
-}
-
 pub fn grayscale_intensity(color: Rgb888) -> u32 {
     ((color.r() as u32 * 77 + color.g() as u32 * 150 + color.b() as u32 * 29) / 256).min(255)
 }
@@ -590,8 +864,226 @@ pub fn draw_centered_text<
     };
 
     let style = MonoTextStyle::new(&FONT_6X10, u32_to_rgb888(color));
-    let text_width = calc_text_width(text);
+    let text_width = super::text::measure(text, super::text::Font::Font6x10) as i32;
     let text_x = x + (width as i32 / 2) - (text_width as i32 / 2);
     let text_obj = Text::new(text, Point::new(text_x, y), style);
     text_obj.draw(writer).ok();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 16;
+    const H: usize = 12;
+
+    fn config(pixels: &mut [u32], stride: usize) -> SimpleFramebufferConfig {
+        SimpleFramebufferConfig {
+            base_addr: pixels.as_mut_ptr() as usize,
+            width: W,
+            height: H,
+            stride,
+            bytes_per_pixel: 4,
+            pixel_format: None,
+        }
+    }
+
+    fn framebuffer(pixels: &mut [u32]) -> SimpleFramebuffer {
+        SimpleFramebuffer::new(config(pixels, W * 4))
+    }
+
+    #[test]
+    fn draw_line_covers_endpoints_and_midpoint() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = framebuffer(&mut pixels);
+        // shallow, steep and reversed (right-to-left, bottom-to-top)
+        fb.draw_line(0, 0, 10, 4, 1);
+        fb.draw_line(2, 1, 4, 11, 2);
+        fb.draw_line(15, 11, 5, 6, 3);
+        assert_eq!(fb.get_pixel(0, 0), 1);
+        assert_eq!(fb.get_pixel(5, 2), 1);
+        assert_eq!(fb.get_pixel(10, 4), 1);
+        assert_eq!(fb.get_pixel(2, 1), 2);
+        assert_eq!(fb.get_pixel(3, 6), 2);
+        assert_eq!(fb.get_pixel(4, 11), 2);
+        assert_eq!(fb.get_pixel(15, 11), 3);
+        assert_eq!(fb.get_pixel(10, 8), 3);
+        assert_eq!(fb.get_pixel(5, 6), 3);
+    }
+
+    #[test]
+    fn draw_line_draws_one_pixel_per_major_step() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = framebuffer(&mut pixels);
+        fb.draw_line(1, 10, 11, 5, 7);
+        for x in 1..=11 {
+            let hits = (0..H).filter(|&y| fb.get_pixel(x, y) == 7).count();
+            assert_eq!(hits, 1, "column {x}");
+        }
+    }
+
+    #[test]
+    fn draw_line_clips_to_bounds() {
+        let mut pixels = [0u32; W * H + 1];
+        pixels[W * H] = 0xDEAD;
+        let mut fb = framebuffer(&mut pixels[..W * H]);
+        fb.draw_line(-20, -20, 100, 100, 9);
+        fb.draw_line(-5, 3, 40, 3, 9);
+        fb.draw_line(100, -1, 200, 50, 9);
+        assert_eq!(fb.get_pixel(0, 0), 9);
+        assert_eq!(fb.get_pixel(11, 11), 9);
+        assert_eq!(fb.get_pixel(0, 3), 9);
+        assert_eq!(fb.get_pixel(W - 1, 3), 9);
+        assert_eq!(pixels[W * H], 0xDEAD);
+    }
+
+    #[test]
+    fn circles_hit_their_boundary_pixels() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = framebuffer(&mut pixels);
+        fb.draw_circle(8, 6, 4, 1);
+        for (x, y) in [(12, 6), (4, 6), (8, 2), (8, 10), (11, 9), (5, 3)] {
+            assert_eq!(fb.get_pixel(x, y), 1, "({x}, {y})");
+        }
+        assert_eq!(fb.get_pixel(8, 6), 0);
+        assert_eq!(fb.get_pixel(10, 4), 0);
+
+        fb.fill_circle(8, 6, 2, 2);
+        assert_eq!(fb.get_pixel(8, 6), 2);
+        assert_eq!(fb.get_pixel(10, 6), 2);
+        assert_eq!(fb.get_pixel(11, 6), 0);
+        assert_eq!(fb.get_pixel(12, 6), 1);
+
+        fb.draw_circle(1, 1, 0, 3);
+        assert_eq!(fb.get_pixel(1, 1), 3);
+        assert_eq!(fb.get_pixel(2, 1), 0);
+    }
+
+    #[test]
+    fn circles_clip_to_bounds() {
+        let mut pixels = [0u32; W * H + 1];
+        pixels[W * H] = 0xDEAD;
+        let mut fb = framebuffer(&mut pixels[..W * H]);
+        fb.fill_circle(0, 0, 3, 4);
+        fb.fill_circle(W as i32 - 1, H as i32 - 1, 5, 4);
+        fb.draw_circle(-50, -50, 200, 5);
+        fb.fill_circle(100, 100, 3, 6);
+        assert_eq!(fb.get_pixel(0, 0), 4);
+        assert_eq!(fb.get_pixel(3, 0), 4);
+        assert_eq!(fb.get_pixel(W - 1, H - 1), 4);
+        assert_eq!(pixels[W * H], 0xDEAD);
+    }
+
+    #[test]
+    fn blend_pixel_mixes_by_source_alpha() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = framebuffer(&mut pixels);
+        fb.blend_pixel(1, 1, 0x80FF_FFFF);
+        assert_eq!(fb.get_pixel(1, 1), 0x0080_8080);
+
+        fb.draw_pixel(2, 1, 0x0012_3456);
+        fb.blend_pixel(2, 1, 0x00FF_FFFF);
+        assert_eq!(fb.get_pixel(2, 1), 0x0012_3456);
+        fb.blend_pixel(2, 1, 0xFF00_FF00);
+        assert_eq!(fb.get_pixel(2, 1), 0x0000_FF00);
+
+        let mut bytes = [0u8; W * H];
+        let mut vga = SimpleFramebuffer::new(SimpleFramebufferConfig {
+            base_addr: bytes.as_mut_ptr() as usize,
+            width: W,
+            height: H,
+            stride: W,
+            bytes_per_pixel: 1,
+            pixel_format: None,
+        });
+        vga.blend_pixel(0, 0, 0xFFFF_FFFF);
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn draw_pixel_converts_to_the_hardware_format() {
+        use crate::common::EfiGraphicsPixelFormat::{
+            PixelBlueGreenRedReserved8BitPerColor as Bgr,
+            PixelRedGreenBlueReserved8BitPerColor as Rgb,
+        };
+        const RED: u32 = 0x00FF_0000;
+        let cases: [(Option<_>, usize, &[u8]); 8] = [
+            (Some(Bgr), 4, &[0x00, 0x00, 0xFF, 0x00]),
+            (Some(Rgb), 4, &[0xFF, 0x00, 0x00, 0x00]),
+            (None, 4, &[0x00, 0x00, 0xFF, 0x00]),
+            (Some(Bgr), 3, &[0x00, 0x00, 0xFF]),
+            (Some(Rgb), 3, &[0xFF, 0x00, 0x00]),
+            (Some(Bgr), 2, &[0x00, 0xF8]),
+            (Some(Rgb), 2, &[0x1F, 0x00]),
+            (None, 1, &[vga_color_index(0xFF, 0, 0) as u8]),
+        ];
+        for (pixel_format, bytes_per_pixel, expected) in cases {
+            // 2x2 pixels; u32 storage keeps 32 bpp writes aligned.
+            let mut pixels = [0u32; 4];
+            let mut fb = SimpleFramebuffer::new(SimpleFramebufferConfig {
+                base_addr: pixels.as_mut_ptr() as usize,
+                width: 2,
+                height: 2,
+                stride: 2 * bytes_per_pixel,
+                bytes_per_pixel,
+                pixel_format,
+            });
+            fb.draw_pixel(1, 1, RED);
+            let mut raw = [0u8; 4];
+            raw[..bytes_per_pixel].copy_from_slice(expected);
+            assert_eq!(fb.get_pixel(1, 1), u32::from_le_bytes(raw));
+            assert_eq!(fb.get_pixel(0, 1), 0, "{pixel_format:?} {bytes_per_pixel}");
+
+            let bytes = |pixels: &[u32; 4]| -> [u8; 16] {
+                let mut bytes = [0u8; 16];
+                for (chunk, pixel) in bytes.chunks_exact_mut(4).zip(pixels) {
+                    chunk.copy_from_slice(&pixel.to_ne_bytes());
+                }
+                bytes
+            };
+            let start = 3 * bytes_per_pixel;
+            let written = bytes(&pixels);
+            assert_eq!(&written[start..start + bytes_per_pixel], expected);
+            assert!(written[..start].iter().all(|&b| b == 0));
+            assert!(written[start + bytes_per_pixel..].iter().all(|&b| b == 0));
+
+            fb.clear(RED);
+            assert_eq!(&bytes(&pixels)[..bytes_per_pixel], expected);
+        }
+    }
+
+    #[test]
+    fn double_buffer_defers_hardware_writes_until_present() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = SimpleFramebuffer::with_double_buffer(config(&mut pixels, W * 4));
+        assert!(fb.is_double_buffered());
+        fb.clear(4);
+        fb.draw_rect(2, 2, 3, 3, 5);
+        assert_eq!(fb.get_pixel(3, 3), 5);
+        assert!(pixels.iter().all(|&p| p == 0));
+
+        fb.present();
+        assert_eq!(pixels[0], 4);
+        assert_eq!(pixels[3 * W + 3], 5);
+    }
+
+    #[test]
+    fn present_respects_padded_stride() {
+        // Two pixels of padding at the end of every hardware row.
+        const PITCH: usize = W + 2;
+        let mut pixels = [0xAAAA_AAAAu32; PITCH * H];
+        let mut fb = SimpleFramebuffer::with_double_buffer(config(&mut pixels, PITCH * 4));
+        fb.clear(1);
+        fb.draw_pixel(W - 1, 1, 2);
+        fb.present();
+        for y in 0..H {
+            assert!(
+                pixels[y * PITCH..y * PITCH + W]
+                    .iter()
+                    .all(|&p| p != 0xAAAA_AAAA)
+            );
+            assert_eq!(pixels[y * PITCH + W], 0xAAAA_AAAA, "padding in row {y}");
+        }
+        assert_eq!(pixels[PITCH + W - 1], 2);
+    }
+}
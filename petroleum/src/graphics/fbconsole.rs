@@ -0,0 +1,245 @@
+//! Text console drawn on a linear framebuffer.
+//!
+//! GOP systems have no VGA text buffer at 0xb8000.  [`FbConsole`] offers the
+//! same [`TextBufferOperations`] interface on top of a [`SimpleFramebuffer`],
+//! drawing every cell with [`draw_glyph`] and scrolling the pixels with
+//! [`scroll_buffer_pixels`].
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::ansi::AnsiParser;
+use super::color::{SimpleFramebuffer, SimpleFramebufferConfig};
+use super::framebuffer::scroll_buffer_pixels;
+use super::text::{Color, ColorCode, Font, ScreenChar, TextBufferOperations, draw_glyph};
+
+/// Grid the 8x16 font must still fit before it is preferred over 6x10.
+const MIN_COLS: usize = 80;
+const MIN_ROWS: usize = 25;
+
+/// `0x00RRGGBB` values of the 16 VGA text colours.
+const PALETTE: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, 0x555555,
+    0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+/// Character grid sized to the framebuffer resolution.
+pub struct FbConsole {
+    fb: SimpleFramebuffer,
+    font: Font,
+    cols: usize,
+    rows: usize,
+    /// Contents of every cell, so `get_char_at` needs no pixel readback.
+    cells: Vec<ScreenChar>,
+    color_code: ColorCode,
+    cursor_row: usize,
+    cursor_col: usize,
+    ansi: AnsiParser,
+}
+
+impl FbConsole {
+    /// Console covering the framebuffer described by `config`.
+    ///
+    /// The 8x16 font is used when it still gives an 80x25 grid, 6x10
+    /// otherwise.  Returns `None` unless the framebuffer is 32 bpp and fits
+    /// at least one cell.
+    pub fn new(config: SimpleFramebufferConfig) -> Option<Self> {
+        if config.bytes_per_pixel != 4 {
+            return None;
+        }
+        let grid = |font: Font| {
+            let (width, height) = font.cell_size();
+            (config.width / width, config.height / height)
+        };
+        let (large_cols, large_rows) = grid(Font::Font8x16);
+        let font = if large_cols >= MIN_COLS && large_rows >= MIN_ROWS {
+            Font::Font8x16
+        } else {
+            Font::Font6x10
+        };
+        let (cols, rows) = grid(font);
+        if cols == 0 || rows == 0 {
+            return None;
+        }
+        let color_code = ColorCode::new(Color::LightGray, Color::Black);
+        Some(Self {
+            fb: SimpleFramebuffer::new(config),
+            font,
+            cols,
+            rows,
+            cells: vec![blank(color_code); cols * rows],
+            color_code,
+            cursor_row: 0,
+            cursor_col: 0,
+            ansi: AnsiParser::new(),
+        })
+    }
+
+    pub fn font(&self) -> Font {
+        self.font
+    }
+
+    pub fn set_color(&mut self, foreground: Color, background: Color) {
+        self.color_code = ColorCode::new(foreground, background);
+    }
+
+    fn draw_cell(&mut self, row: usize, col: usize) {
+        let cell = self.cells[row * self.cols + col];
+        let (width, height) = self.font.cell_size();
+        draw_glyph(
+            &mut self.fb,
+            col * width,
+            row * height,
+            cell.ascii_character as char,
+            self.font,
+            PALETTE[(cell.color_code.0 & 0x0F) as usize],
+            PALETTE[(cell.color_code.0 >> 4) as usize],
+        );
+    }
+}
+
+fn blank(color_code: ColorCode) -> ScreenChar {
+    ScreenChar {
+        ascii_character: b' ',
+        color_code,
+    }
+}
+
+impl core::fmt::Write for FbConsole {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+impl TextBufferOperations for FbConsole {
+    fn get_width(&self) -> usize {
+        self.cols
+    }
+
+    fn get_height(&self) -> usize {
+        self.rows
+    }
+
+    fn get_color_code(&self) -> ColorCode {
+        self.color_code
+    }
+
+    fn set_color_code(&mut self, color_code: ColorCode) {
+        self.color_code = color_code;
+    }
+
+    fn ansi_parser(&mut self) -> Option<&mut AnsiParser> {
+        Some(&mut self.ansi)
+    }
+
+    fn get_position(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    fn set_position(&mut self, row: usize, col: usize) {
+        self.cursor_row = row;
+        self.cursor_col = col;
+    }
+
+    fn set_char_at(&mut self, row: usize, col: usize, chr: ScreenChar) {
+        if row < self.rows && col < self.cols {
+            self.cells[row * self.cols + col] = chr;
+            self.draw_cell(row, col);
+        }
+    }
+
+    fn get_char_at(&self, row: usize, col: usize) -> ScreenChar {
+        if row < self.rows && col < self.cols {
+            self.cells[row * self.cols + col]
+        } else {
+            blank(self.color_code)
+        }
+    }
+
+    fn scroll_up(&mut self) {
+        let (_, height) = self.font.cell_size();
+        // SAFETY: the grid lies within the framebuffer, whose mapping the
+        // config describes; `SimpleFramebuffer::new` draws straight to it.
+        unsafe {
+            scroll_buffer_pixels::<u32>(
+                self.fb.base as u64,
+                self.fb.stride as u32,
+                (self.rows * height) as u32,
+                height as u32,
+                0,
+            );
+        }
+        self.cells.copy_within(self.cols.., 0);
+        let last_row = self.rows - 1;
+        for col in 0..self.cols {
+            // Repaint the new row so it takes the background colour.
+            self.set_char_at(last_row, col, blank(self.color_code));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 48;
+    const H: usize = 32;
+
+    fn console(pixels: &mut [u32]) -> FbConsole {
+        FbConsole::new(SimpleFramebufferConfig {
+            base_addr: pixels.as_mut_ptr() as usize,
+            width: W,
+            height: H,
+            stride: W * 4,
+            bytes_per_pixel: 4,
+            pixel_format: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn grid_follows_the_resolution() {
+        let mut pixels = [0u32; W * H];
+        let fb = console(&mut pixels);
+        assert_eq!(fb.font(), Font::Font6x10);
+        assert_eq!((fb.get_width(), fb.get_height()), (8, 3));
+    }
+
+    #[test]
+    fn writing_past_the_last_row_scrolls_cells_and_pixels() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = console(&mut pixels);
+        fb.write_string("a\nb\nc\nd");
+        assert_eq!(fb.get_char_at(0, 0).ascii_character, b'b');
+        assert_eq!(fb.get_char_at(2, 0).ascii_character, b'd');
+        assert_eq!(fb.get_position(), (2, 1));
+        // The bottom row was repainted after the scroll and holds only 'd'.
+        let fg = PALETTE[Color::LightGray as usize];
+        let row_has_ink =
+            |row: usize| (row * 10..row * 10 + 10).any(|y| (0..W).any(|x| pixels[y * W + x] == fg));
+        assert!(row_has_ink(2));
+        assert!((20..30).all(|y| (6..W).all(|x| pixels[y * W + x] == 0)));
+    }
+
+    #[test]
+    fn escape_sequences_colour_and_move_the_cursor() {
+        let mut pixels = [0u32; W * H];
+        let mut fb = console(&mut pixels);
+        fb.write_string("\x1b[2;3H\x1b[31;44mx\x1b[0my");
+        assert_eq!(fb.get_char_at(1, 2).ascii_character, b'x');
+        assert_eq!(
+            fb.get_char_at(1, 2).color_code.0,
+            ColorCode::new(Color::Red, Color::Blue).0
+        );
+        assert_eq!(
+            fb.get_char_at(1, 3).color_code.0,
+            ColorCode::new(Color::LightGray, Color::Black).0
+        );
+        // A sequence split across writes is still consumed.
+        fb.write_string("\x1b[");
+        fb.write_string("2Jz");
+        assert_eq!(fb.get_char_at(1, 2).ascii_character, b' ');
+        assert_eq!(fb.get_char_at(1, 4).ascii_character, b'z');
+    }
+}
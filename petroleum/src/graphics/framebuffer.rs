@@ -1,3 +1,4 @@
+use crate::common::FullereneFramebufferConfig;
 use crate::graphics::color::{FramebufferInfo, PixelType, rgb_pixel};
 use embedded_graphics::{
     geometry::{Point, Size},
@@ -7,6 +8,26 @@ use embedded_graphics::{
     text::Text,
 };
 
+/// Framebuffer configuration bellows chose at boot, from GOP or ramfb.
+/// The kernel never records one: it takes its framebuffer from the boot
+/// arguments bellows passes on, so there this stays unset.
+///
+/// It is written once and never changed, so readers take no lock and
+/// cannot block on a writer that was interrupted part-way through.
+static CONFIG: spin::Once<FullereneFramebufferConfig> = spin::Once::new();
+
+/// Record the boot framebuffer configuration.  Only the first call has an
+/// effect, so installing the same mode twice is harmless.
+pub fn set_config(config: FullereneFramebufferConfig) {
+    CONFIG.call_once(|| config);
+}
+
+/// The boot framebuffer configuration, or `None` if there is no directly
+/// addressable framebuffer (or it has not been recorded yet).
+pub fn get_config() -> Option<FullereneFramebufferConfig> {
+    CONFIG.get().copied()
+}
+
 // Helper macro for delegate calls to reduce duplication
 macro_rules! delegate_call {
     ($self:expr, $method:ident $(, $args:expr)*) => {
@@ -460,6 +481,7 @@ impl<T: PixelType> FramebufferLike for FramebufferWriter<T> {
                 self.info.address,
                 self.info.width_or_stride(),
                 self.info.height,
+                FONT_6X10.character_size.height,
                 T::from_u32(self.info.colors.bg),
             );
         }
@@ -493,6 +515,7 @@ impl<T: PixelType> FramebufferLike for FramebufferWriter<T> {
                 self.info.address,
                 self.info.width_or_stride(),
                 self.info.height,
+                FONT_6X10.character_size.height,
                 T::from_u32(self.info.colors.bg),
             );
         }
@@ -522,14 +545,25 @@ pub unsafe fn clear_buffer_pixels<T: Copy>(address: u64, stride: u32, height: u3
 
 /// Generic framebuffer buffer scroll up operation.
 ///
-/// Shifts the entire framebuffer up by 8 scan lines using `T`-sized
+/// Shifts the first `height` scan lines up by `lines` using `T`-sized
 /// volatile accesses (much fewer operations than byte-by-byte).
-/// The last 8 scan lines are filled with `bg_color`.
-pub unsafe fn scroll_buffer_pixels<T: Copy>(address: u64, stride: u32, height: u32, bg_color: T) {
+/// The last `lines` scan lines are filled with `bg_color`.
+///
+/// # Safety
+/// `address` must point to a writable mapping of at least `height` scan
+/// lines of `stride` bytes.
+pub unsafe fn scroll_buffer_pixels<T: Copy>(
+    address: u64,
+    stride: u32,
+    height: u32,
+    lines: u32,
+    bg_color: T,
+) {
     unsafe {
         let bpp = core::mem::size_of::<T>() as u32;
         let pixels_per_line = (stride / bpp) as usize;
-        let shift_pixels = 10 * pixels_per_line;
+        let lines = lines.min(height);
+        let shift_pixels = lines as usize * pixels_per_line;
         let total_pixels = pixels_per_line * height as usize;
 
         let fb_ptr = address as *mut T;
@@ -541,9 +575,9 @@ pub unsafe fn scroll_buffer_pixels<T: Copy>(address: u64, stride: u32, height: u
             core::ptr::write_volatile(dst, core::ptr::read_volatile(src));
         }
 
-        // Clear last 8 lines
-        let clear_start = (height.saturating_sub(8) as usize) * pixels_per_line;
-        let clear_count = 8 * pixels_per_line;
+        // Clear the lines that scrolled in
+        let clear_start = ((height - lines) as usize) * pixels_per_line;
+        let clear_count = shift_pixels;
         for i in 0..clear_count {
             core::ptr::write_volatile(fb_ptr.add(clear_start + i), bg_color);
         }
@@ -18,15 +18,25 @@
 //! The returned virtual address is opaque to the caller — the caller
 //! only cares that reads/writes are safe and cache behaviour is correct.
 
+use x86_64::structures::paging::PageTableFlags;
+
 /// Cache mode for a mapped region.
 ///
-/// Corresponds to x86 PAT / PCD / PWT combinations:
+/// Each mode selects a PAT slot through the PCD and PWT bits of the page
+/// table entry; the PAT bit itself is never set.  The memory type a slot
+/// stands for is the one programmed by
+/// [`init_pat`](crate::page_table::pat::init_pat):
+///
+/// | Mode             | PCD | PWT | Slot | Type after `init_pat` | Power-on type |
+/// |------------------|-----|-----|------|-----------------------|---------------|
+/// | `Uncached`       |   1 |   1 |    3 | UC                    | UC            |
+/// | `WriteCombining` |   0 |   1 |    1 | WC                    | WT            |
+/// | `WriteBack`      |   0 |   0 |    0 | WB                    | WB            |
 ///
-/// | Mode           | PAT | PCD | PWT | x86 type |
-/// |----------------|-----|-----|-----|----------|
-/// | `Uncached`     | UC  |   1 |   – | UC       |
-/// | `WriteCombining` | WC |   – |   – | WC       |
-/// | `WriteBack`    | WB  |   0 |   0 | WB       |
+/// Without `init_pat` (a CPU without PAT), write-combining falls back to
+/// write-through, which is still coherent for scan-out but slower.  The
+/// MTRRs are combined with the PAT type as usual, so an MTRR that marks a
+/// range UC keeps it uncached whatever the mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CacheMode {
     /// Strong uncacheable — reads always go to device, writes are not
@@ -39,6 +49,22 @@ pub enum CacheMode {
     WriteBack,
 }
 
+impl CacheMode {
+    /// The page table bits that select a cache mode.
+    pub const FLAGS_MASK: PageTableFlags =
+        PageTableFlags::NO_CACHE.union(PageTableFlags::WRITE_THROUGH);
+
+    /// The PCD/PWT bits for this mode, to be combined with the other flags
+    /// of a leaf entry.
+    pub const fn page_table_flags(self) -> PageTableFlags {
+        match self {
+            Self::Uncached => Self::FLAGS_MASK,
+            Self::WriteCombining => PageTableFlags::WRITE_THROUGH,
+            Self::WriteBack => PageTableFlags::empty(),
+        }
+    }
+}
+
 /// Trait for mapping framebuffer / MMIO memory into virtual address space.
 ///
 /// # Contract
@@ -62,3 +88,25 @@ pub trait FramebufferMapper {
     /// `size` must match the value originally passed to `map_framebuffer`.
     fn unmap_framebuffer(&mut self, virt_addr: u64, size: usize);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_modes_select_their_pat_slots() {
+        // Slot index = PAT << 2 | PCD << 1 | PWT.
+        let slot = |mode: CacheMode| {
+            let flags = mode.page_table_flags();
+            assert!(CacheMode::FLAGS_MASK.contains(flags));
+            usize::from(flags.contains(PageTableFlags::NO_CACHE)) << 1
+                | usize::from(flags.contains(PageTableFlags::WRITE_THROUGH))
+        };
+        assert_eq!(slot(CacheMode::WriteBack), 0);
+        assert_eq!(slot(CacheMode::WriteCombining), 1);
+        assert_eq!(slot(CacheMode::Uncached), 3);
+        // The slots hold WB, WC and UC in the table written by init_pat.
+        let pat = crate::page_table::pat::PAT_VALUE.to_le_bytes();
+        assert_eq!([pat[0], pat[1], pat[3]], [0x06, 0x01, 0x00]);
+    }
+}
@@ -97,11 +97,14 @@ pub trait Console: core::fmt::Write {
     fn scroll(&mut self);
 }
 
+pub mod ansi;
 pub mod boot_screen;
 pub mod color;
 pub mod constants;
+pub mod fbconsole;
 pub mod framebuffer;
 pub mod framebuffer_mapper;
+pub mod ramfb;
 pub mod registers;
 pub mod setup;
 pub mod text;
@@ -113,7 +116,7 @@ pub use constants::*;
 pub use framebuffer::UefiFramebufferWriter;
 pub use framebuffer::*;
 pub use setup::{
-    detect_and_init_vga_graphics, detect_cirrus_vga, init_vga_graphics, init_vga_text_mode,
-    setup_cirrus_vga_mode,
+    CirrusDepth, detect_and_init_vga_graphics, detect_cirrus_vga, init_vga_graphics,
+    init_vga_text_mode, setup_cirrus_linear_mode, setup_cirrus_vga_mode,
 };
 pub use text::{Color, ColorCode, ScreenChar, TextBufferOperations};
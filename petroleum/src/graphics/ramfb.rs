@@ -0,0 +1,93 @@
+//! QEMU `ramfb` display device.
+//!
+//! `ramfb` scans out a framebuffer in guest RAM.  The guest picks the mode
+//! and the memory and tells the device by writing a [`RamfbConfig`] to the
+//! `etc/ramfb` fw_cfg file, which only the fw_cfg DMA interface can do (see
+//! [`crate::hardware::fw_cfg`]).
+
+use crate::common::{EfiGraphicsPixelFormat, FullereneFramebufferConfig};
+use crate::hardware::fw_cfg::{self, FwCfgError};
+
+const RAMFB_FILE: &str = "etc/ramfb";
+/// DRM fourcc `XR24`: 32 bpp, `0x00RRGGBB` little-endian.
+const DRM_FORMAT_XRGB8888: u32 = u32::from_le_bytes(*b"XR24");
+
+/// `RAMFBCfg`: the framebuffer as the device wants it, all big-endian.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct RamfbConfig {
+    address: u64,
+    fourcc: u32,
+    flags: u32,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+impl RamfbConfig {
+    /// An XRGB8888 framebuffer of `width`x`height` pixels at physical
+    /// `address`, `stride` bytes per row.
+    pub const fn new(address: u64, width: u32, height: u32, stride: u32) -> Self {
+        Self {
+            address: address.to_be(),
+            fourcc: DRM_FORMAT_XRGB8888.to_be(),
+            flags: 0,
+            width: width.to_be(),
+            height: height.to_be(),
+            stride: stride.to_be(),
+        }
+    }
+}
+
+/// Point `ramfb` at the `width`x`height` XRGB8888 framebuffer at physical
+/// `address`, which must hold `width * 4 * height` bytes and stay reserved
+/// for as long as the device scans it out.
+///
+/// Fails with [`FwCfgError::FileNotFound`] when QEMU was started without
+/// `-device ramfb`.
+///
+/// # Safety
+///
+/// Virtual addresses must equal physical addresses, as for
+/// [`fw_cfg::dma_write`].
+pub unsafe fn configure(
+    address: u64,
+    width: u32,
+    height: u32,
+) -> Result<FullereneFramebufferConfig, FwCfgError> {
+    let file = fw_cfg::find_file(RAMFB_FILE)?;
+    let stride = width * 4;
+    let config = RamfbConfig::new(address, width, height, stride);
+    unsafe {
+        fw_cfg::dma_write(
+            file.select,
+            (&raw const config) as u64,
+            size_of::<RamfbConfig>() as u32,
+        )?
+    };
+    Ok(FullereneFramebufferConfig {
+        address,
+        width,
+        height,
+        pixel_format: EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor,
+        bpp: 32,
+        stride,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramfb_config_is_big_endian_xrgb8888() {
+        let config = RamfbConfig::new(0x1234_5000, 1024, 768, 4096);
+        let bytes: [u8; 28] = unsafe { core::mem::transmute(config) };
+        assert_eq!(bytes[..8], 0x1234_5000u64.to_be_bytes());
+        assert_eq!(&bytes[8..12], b"42RX");
+        assert_eq!(bytes[12..16], [0; 4]);
+        assert_eq!(bytes[16..20], 1024u32.to_be_bytes());
+        assert_eq!(bytes[20..24], 768u32.to_be_bytes());
+        assert_eq!(bytes[24..28], 4096u32.to_be_bytes());
+    }
+}
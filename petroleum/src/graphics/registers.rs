@@ -149,3 +149,85 @@ pub const ATTRIBUTE_TEXT_CONFIG: &[RegisterConfig] = config!(
     (0x13, 0x00), // Horizontal pixel panning register
     (0x14, 0x00), // Color select register
 );
+
+// Cirrus Logic GD5446 registers for a 1024x768 linear framebuffer at 60 Hz
+// (VESA timing: 65 MHz pixel clock, 1344x806 total).  The extended registers
+// (SR7 and above, CR19 and above, GR9 and above) only take writes after SR6
+// has been unlocked.  SR7 (depth) and CR13/CR1B/CR1D (line offset) depend
+// on the depth and are written separately.
+
+// Cirrus sequencer registers for 1024x768
+pub const CIRRUS_SEQUENCER_CONFIG: &[RegisterConfig] = config!(
+    (0x01, 0x01), // Clocking mode register - 8 dot clocks
+    (0x02, 0x0F), // Map mask register - enable all planes
+    (0x03, 0x00), // Character map select register
+    (0x04, 0x0E), // Memory mode register - extended memory, chain 4
+    (0x0E, 0x3B), // VCLK3 numerator - 59
+    (0x1E, 0x1A), // VCLK3 denominator - 13, no post-divide: 14.318 MHz * 59 / 13 = 65 MHz
+);
+
+// Cirrus CRTC registers for 1024x768
+pub const CIRRUS_CRTC_CONFIG: &[RegisterConfig] = config!(
+    (0x11, 0x09), // End vertical retrace - clears the CR0-CR7 write protect bit
+    (0x00, 0xA3), // Horizontal total - 168 character clocks
+    (0x01, 0x7F), // Horizontal display enable end - 128 character clocks
+    (0x02, 0x80), // Start horizontal blanking
+    (0x03, 0x87), // End horizontal blanking
+    (0x04, 0x83), // Start horizontal retrace pulse
+    (0x05, 0x94), // End horizontal retrace (bit 7: bit 5 of blanking end)
+    (0x06, 0x24), // Vertical total - 806 lines (bits 8-9 in overflow)
+    (0x07, 0xFF), // Overflow
+    (0x08, 0x00), // Preset row scan
+    (0x09, 0x60), // Maximum scan line - one scan line per row
+    (0x0C, 0x00), // Start address high
+    (0x0D, 0x00), // Start address low
+    (0x10, 0x03), // Start vertical retrace - line 771
+    (0x12, 0xFF), // Vertical display enable end - line 767
+    (0x14, 0x00), // Underline location - byte addressing
+    (0x15, 0xFF), // Start vertical blanking
+    (0x16, 0x25), // End vertical blanking
+    (0x17, 0xE3), // CRTC mode control
+    (0x18, 0xFF), // Line compare - disabled
+    (0x1A, 0x00), // Miscellaneous control - no interlace
+);
+
+// Cirrus graphics controller registers for a packed-pixel mode
+pub const CIRRUS_GRAPHICS_CONFIG: &[RegisterConfig] = config!(
+    (0x00, 0x00), // Set/reset register
+    (0x01, 0x00), // Enable set/reset register
+    (0x02, 0x00), // Color compare register
+    (0x03, 0x00), // Data rotate register
+    (0x04, 0x00), // Read plane select register
+    (0x05, 0x40), // Graphics mode register - 256-color shift mode
+    (0x06, 0x01), // Miscellaneous register - graphics mode, A0000-BFFFF window
+    (0x07, 0x0F), // Color don't care register
+    (0x08, 0xFF), // Bit mask register
+    (0x09, 0x00), // Offset register 0 - bank window starts at video memory 0
+    (0x0A, 0x00), // Offset register 1
+    (0x0B, 0x20), // Graphics controller mode extensions - 16 KiB bank granularity
+);
+
+// Cirrus attribute controller registers for a packed-pixel mode
+pub const CIRRUS_ATTRIBUTE_CONFIG: &[RegisterConfig] = config!(
+    (0x00, 0x00), // Palette register 0
+    (0x01, 0x01), // Palette register 1
+    (0x02, 0x02), // Palette register 2
+    (0x03, 0x03), // Palette register 3
+    (0x04, 0x04), // Palette register 4
+    (0x05, 0x05), // Palette register 5
+    (0x06, 0x06), // Palette register 6
+    (0x07, 0x07), // Palette register 7
+    (0x08, 0x08), // Palette register 8
+    (0x09, 0x09), // Palette register 9
+    (0x0A, 0x0A), // Palette register A
+    (0x0B, 0x0B), // Palette register B
+    (0x0C, 0x0C), // Palette register C
+    (0x0D, 0x0D), // Palette register D
+    (0x0E, 0x0E), // Palette register E
+    (0x0F, 0x0F), // Palette register F
+    (0x10, 0x01), // Attr mode control register - graphics mode, no pixel doubling
+    (0x11, 0x00), // Overscan color register
+    (0x12, 0x0F), // Color plane enable register
+    (0x13, 0x00), // Horizontal pixel panning register
+    (0x14, 0x00), // Color select register
+);
@@ -1,9 +1,12 @@
 use super::registers::{
-    ATTRIBUTE_CONFIG, ATTRIBUTE_TEXT_CONFIG, CRTC_CONFIG, CRTC_TEXT_CONFIG, GRAPHICS_CONFIG,
-    GRAPHICS_TEXT_CONFIG, SEQUENCER_CONFIG, SEQUENCER_TEXT_CONFIG,
+    ATTRIBUTE_CONFIG, ATTRIBUTE_TEXT_CONFIG, CIRRUS_ATTRIBUTE_CONFIG, CIRRUS_CRTC_CONFIG,
+    CIRRUS_GRAPHICS_CONFIG, CIRRUS_SEQUENCER_CONFIG, CRTC_CONFIG, CRTC_TEXT_CONFIG,
+    GRAPHICS_CONFIG, GRAPHICS_TEXT_CONFIG, SEQUENCER_CONFIG, SEQUENCER_TEXT_CONFIG,
 };
+use crate::common::{EfiGraphicsPixelFormat, FullereneFramebufferConfig};
 use crate::io::{HardwarePorts, PortWriter, VgaPortOps};
 use crate::write_port_sequence;
+use alloc::vec::Vec;
 
 /// Write RGB triples for palette setup (DRY helper).
 fn write_rgb(writer: &mut PortWriter<u8>, val: u8) {
@@ -67,23 +70,265 @@ pub fn setup_cirrus_vga_mode() {
     log_serial("Cirrus VGA: Cirrus-specific initialization complete\n");
 }
 
-pub fn detect_and_init_vga_graphics() {
+/// Pixel depth for [`setup_cirrus_linear_mode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CirrusDepth {
+    /// 16 bpp, 5:6:5 RGB.
+    Rgb565,
+    /// 32 bpp, `0x00RRGGBB` little-endian.
+    Xrgb8888,
+}
+
+impl CirrusDepth {
+    fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Rgb565 => 2,
+            Self::Xrgb8888 => 4,
+        }
+    }
+
+    /// SR7: extended (SVGA) mode plus the pixel depth field.
+    fn sr7(self) -> u8 {
+        match self {
+            Self::Rgb565 => 0x07,
+            Self::Xrgb8888 => 0x09,
+        }
+    }
+
+    /// Hidden DAC: 5:6:5 direct color or 8:8:8 with an unused byte.
+    fn hidden_dac(self) -> u8 {
+        match self {
+            Self::Rgb565 => 0xC1,
+            Self::Xrgb8888 => 0xC5,
+        }
+    }
+
+    fn pixel_format(self) -> EfiGraphicsPixelFormat {
+        match self {
+            Self::Rgb565 => EfiGraphicsPixelFormat::PixelBitMask,
+            Self::Xrgb8888 => EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor,
+        }
+    }
+}
+
+const CIRRUS_WIDTH: u32 = 1024;
+const CIRRUS_HEIGHT: u32 = 768;
+/// SR6 value that unlocks the Cirrus extended registers; SR6 reads it back
+/// once they are unlocked and 0x0F while they are locked.
+const CIRRUS_UNLOCK: u8 = 0x12;
+
+/// Switch a Cirrus Logic GD5446 (QEMU's `-vga cirrus`) to 1024x768 at
+/// `depth` and return the framebuffer, which is the linear aperture at BAR0.
+///
+/// The extended registers ignore writes until SR6 is unlocked, so this
+/// fails with `None`, leaving the mode alone, when no Cirrus device is
+/// found or the unlock does not read back.  1024x768x32 needs the 4 MiB of
+/// video memory QEMU gives the device.
+pub fn setup_cirrus_linear_mode(depth: CirrusDepth) -> Option<FullereneFramebufferConfig> {
+    log_serial("Cirrus VGA: Starting 1024x768 linear mode setup\n");
+    let Some((bus, device)) = find_cirrus_vga() else {
+        log_serial("Cirrus VGA: No Cirrus VGA device found\n");
+        return None;
+    };
+    let aperture =
+        u64::from(crate::bare_metal_pci::pci_config_read_dword(bus, device, 0, 0x10) & !0xF);
+    if aperture == 0 {
+        log_serial("Cirrus VGA: Linear aperture BAR0 is not assigned\n");
+        return None;
+    }
+
+    write_reg(
+        HardwarePorts::SEQUENCER_INDEX,
+        HardwarePorts::SEQUENCER_DATA,
+        0x06,
+        CIRRUS_UNLOCK,
+    );
+    if PortWriter::<u8>::new(HardwarePorts::SEQUENCER_DATA).read_safe() != CIRRUS_UNLOCK {
+        log_serial("Cirrus VGA: Extended registers did not unlock\n");
+        return None;
+    }
+
+    for step in cirrus_linear_mode_steps(depth) {
+        step.run();
+    }
+    crate::serial::serial_log(format_args!(
+        "Cirrus VGA: 1024x768x{} linear framebuffer at {:#x}\n",
+        depth.bytes_per_pixel() * 8,
+        aperture
+    ));
+    Some(FullereneFramebufferConfig {
+        address: aperture,
+        width: CIRRUS_WIDTH,
+        height: CIRRUS_HEIGHT,
+        pixel_format: depth.pixel_format(),
+        bpp: depth.bytes_per_pixel() * 8,
+        stride: CIRRUS_WIDTH * depth.bytes_per_pixel(),
+    })
+}
+
+/// One port access of a mode switch, so that the sequence can be built
+/// apart from the hardware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PortStep {
+    /// Select `index` at `index_port`, then write `value` to `data_port`.
+    Register {
+        index_port: u16,
+        data_port: u16,
+        index: u8,
+        value: u8,
+    },
+    /// Write `value` to a port.
+    Write(u16, u8),
+    /// Read a port and drop the value.
+    Read(u16),
+}
+
+impl PortStep {
+    fn run(self) {
+        match self {
+            Self::Register {
+                index_port,
+                data_port,
+                index,
+                value,
+            } => write_reg(index_port, data_port, index, value),
+            Self::Write(port, value) => PortWriter::new(port).write_safe(value),
+            Self::Read(port) => {
+                let _ = PortWriter::<u8>::new(port).read_safe();
+            }
+        }
+    }
+}
+
+fn register_steps(
+    index_port: u16,
+    data_port: u16,
+    config: &[crate::io::RegisterConfig],
+) -> impl Iterator<Item = PortStep> + '_ {
+    config.iter().map(move |reg| PortStep::Register {
+        index_port,
+        data_port,
+        index: reg.index,
+        value: reg.value,
+    })
+}
+
+/// Port accesses that switch an unlocked Cirrus device to 1024x768 at
+/// `depth`.
+fn cirrus_linear_mode_steps(depth: CirrusDepth) -> Vec<PortStep> {
+    use HardwarePorts as P;
+
+    // CR13, bit 4 of CR1B and bit 7 of CR1D hold the line offset in units of
+    // 8 bytes; CR1B bit 1 enables the extended address wrap past 256 KiB.
+    let offset = CIRRUS_WIDTH * depth.bytes_per_pixel() / 8;
+    let line_offset = [
+        crate::io::RegisterConfig {
+            index: 0x13,
+            value: offset as u8,
+        },
+        crate::io::RegisterConfig {
+            index: 0x1B,
+            value: 0x22 | (((offset >> 8) & 1) << 4) as u8,
+        },
+        crate::io::RegisterConfig {
+            index: 0x1D,
+            value: (((offset >> 9) & 1) << 7) as u8,
+        },
+    ];
+    let sr7 = [crate::io::RegisterConfig {
+        index: 0x07,
+        value: depth.sr7(),
+    }];
+
+    let mut steps = Vec::new();
+    // Hold the sequencer in synchronous reset while the clock changes.
+    steps.extend(register_steps(
+        P::SEQUENCER_INDEX,
+        P::SEQUENCER_DATA,
+        &[crate::io::RegisterConfig {
+            index: 0x00,
+            value: 0x01,
+        }],
+    ));
+    // Negative syncs, VCLK3, colour I/O addresses.
+    steps.push(PortStep::Write(P::MISC_OUTPUT, 0xEF));
+    steps.extend(register_steps(
+        P::SEQUENCER_INDEX,
+        P::SEQUENCER_DATA,
+        CIRRUS_SEQUENCER_CONFIG,
+    ));
+    steps.extend(register_steps(P::SEQUENCER_INDEX, P::SEQUENCER_DATA, &sr7));
+    steps.extend(register_steps(
+        P::CRTC_INDEX,
+        P::CRTC_DATA,
+        CIRRUS_CRTC_CONFIG,
+    ));
+    steps.extend(register_steps(P::CRTC_INDEX, P::CRTC_DATA, &line_offset));
+    steps.extend(register_steps(
+        P::GRAPHICS_INDEX,
+        P::GRAPHICS_DATA,
+        CIRRUS_GRAPHICS_CONFIG,
+    ));
+    steps.extend(register_steps(
+        P::SEQUENCER_INDEX,
+        P::SEQUENCER_DATA,
+        &[crate::io::RegisterConfig {
+            index: 0x00,
+            value: 0x03,
+        }],
+    ));
+
+    // The hidden DAC register answers at the DAC mask port after four
+    // consecutive reads of it.
+    steps.extend([PortStep::Read(P::DAC_MASK); 4]);
+    steps.push(PortStep::Write(P::DAC_MASK, depth.hidden_dac()));
+
+    // Reading the status register resets the attribute flip-flop to index.
+    steps.push(PortStep::Read(P::STATUS));
+    steps.extend(register_steps(
+        P::ATTRIBUTE_INDEX,
+        P::ATTRIBUTE_INDEX,
+        CIRRUS_ATTRIBUTE_CONFIG,
+    ));
+    steps.push(PortStep::Write(P::ATTRIBUTE_INDEX, 0x20));
+    steps
+}
+
+/// Detect the VGA device and put it in a graphics mode.
+///
+/// A Cirrus device gets the 1024x768x32 linear mode, whose framebuffer is
+/// returned; if that cannot be set, or on any other device, this falls back
+/// to mode 13h and returns `None`.
+pub fn detect_and_init_vga_graphics() -> Option<FullereneFramebufferConfig> {
     log_serial("VGA Detection: Starting VGA device detection\n");
     if detect_cirrus_vga() {
         log_serial("VGA Detection: Cirrus VGA device detected, initializing\n");
+        if let Some(config) = setup_cirrus_linear_mode(CirrusDepth::Xrgb8888) {
+            return Some(config);
+        }
         setup_cirrus_vga_mode();
     } else {
         log_serial("VGA Detection: Standard VGA device detected, using standard mode\n");
         setup_vga_mode_13h();
     }
+    None
 }
 
 pub fn detect_cirrus_vga() -> bool {
-    const CIRRUS_VID: u16 = 0x1013;
     log_serial("VGA Detection: Checking for Cirrus VGA device\n");
+    if find_cirrus_vga().is_some() {
+        return true;
+    }
+    log_serial("VGA Detection: No Cirrus VGA device found, using standard VGA\n");
+    false
+}
+
+/// Bus and device number of the first Cirrus Logic display device.
+fn find_cirrus_vga() -> Option<(u8, u8)> {
+    const CIRRUS_VID: u16 = 0x1013;
     if crate::bare_metal_pci::pci_config_read_word(0, 2, 0, 0x00) == CIRRUS_VID {
         log_serial("VGA Detection: Cirrus VGA device found via PCI\n");
-        return true;
+        return Some((0, 2));
     }
     for bus in 0..2u8 {
         for device in 0..32u8 {
@@ -92,12 +337,11 @@ pub fn detect_cirrus_vga() -> bool {
                     "VGA Detection: Cirrus VGA device found at bus:device = {}:{}\n",
                     bus, device
                 ));
-                return true;
+                return Some((bus, device));
             }
         }
     }
-    log_serial("VGA Detection: No Cirrus VGA device found, using standard VGA\n");
-    false
+    None
 }
 
 pub fn setup_vga_text_mode() {
@@ -151,3 +395,96 @@ pub fn write_vga_registers(index_port: u16, data_port: u16, configs: &[(u8, u8)]
 pub fn setup_vga_attributes() {
     write_attr(ATTRIBUTE_TEXT_CONFIG);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value last written to `index` at `index_port` by `steps`.
+    fn last_write(steps: &[PortStep], port: u16, register: u8) -> Option<u8> {
+        steps.iter().rev().find_map(|step| match *step {
+            PortStep::Register {
+                index_port,
+                index,
+                value,
+                ..
+            } if index_port == port && index == register => Some(value),
+            _ => None,
+        })
+    }
+
+    fn sequencer_reset(value: u8) -> PortStep {
+        PortStep::Register {
+            index_port: HardwarePorts::SEQUENCER_INDEX,
+            data_port: HardwarePorts::SEQUENCER_DATA,
+            index: 0x00,
+            value,
+        }
+    }
+
+    #[test]
+    fn cirrus_mode_change_happens_under_sequencer_reset() {
+        let steps = cirrus_linear_mode_steps(CirrusDepth::Xrgb8888);
+        assert_eq!(steps[0], sequencer_reset(0x01));
+        assert_eq!(steps[1], PortStep::Write(HardwarePorts::MISC_OUTPUT, 0xEF));
+        let release = steps
+            .iter()
+            .position(|&step| step == sequencer_reset(0x03))
+            .unwrap();
+        // Every sequencer, CRTC and graphics register is set before the
+        // reset is released.
+        assert!(steps[release + 1..].iter().all(|step| !matches!(
+            step,
+            PortStep::Register { index_port, .. }
+                if *index_port != HardwarePorts::ATTRIBUTE_INDEX
+        )));
+    }
+
+    #[test]
+    fn cirrus_depth_selects_sr7_and_hidden_dac() {
+        for depth in [CirrusDepth::Rgb565, CirrusDepth::Xrgb8888] {
+            let steps = cirrus_linear_mode_steps(depth);
+            assert_eq!(
+                last_write(&steps, HardwarePorts::SEQUENCER_INDEX, 0x07),
+                Some(depth.sr7())
+            );
+            let dac = steps
+                .iter()
+                .position(|&step| {
+                    step == PortStep::Write(HardwarePorts::DAC_MASK, depth.hidden_dac())
+                })
+                .unwrap();
+            assert_eq!(
+                steps[dac - 4..dac],
+                [PortStep::Read(HardwarePorts::DAC_MASK); 4]
+            );
+        }
+    }
+
+    #[test]
+    fn cirrus_line_offset_covers_the_whole_stride() {
+        for depth in [CirrusDepth::Rgb565, CirrusDepth::Xrgb8888] {
+            let steps = cirrus_linear_mode_steps(depth);
+            let crtc = |index| last_write(&steps, HardwarePorts::CRTC_INDEX, index).unwrap();
+            let offset = u32::from(crtc(0x13))
+                | u32::from((crtc(0x1B) >> 4) & 1) << 8
+                | u32::from(crtc(0x1D) >> 7) << 9;
+            assert_eq!(offset * 8, CIRRUS_WIDTH * depth.bytes_per_pixel());
+            assert_eq!(crtc(0x1B) & 0x02, 0x02);
+        }
+    }
+
+    #[test]
+    fn cirrus_attributes_start_from_a_reset_flip_flop() {
+        let steps = cirrus_linear_mode_steps(CirrusDepth::Rgb565);
+        let status = steps
+            .iter()
+            .position(|&step| step == PortStep::Read(HardwarePorts::STATUS))
+            .unwrap();
+        assert_eq!(steps[status + 1..].len(), CIRRUS_ATTRIBUTE_CONFIG.len() + 1);
+        assert_eq!(
+            steps.last(),
+            Some(&PortStep::Write(HardwarePorts::ATTRIBUTE_INDEX, 0x20))
+        );
+    }
+}
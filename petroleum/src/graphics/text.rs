@@ -1,3 +1,5 @@
+use super::ansi::{AnsiEvent, AnsiParser, CsiSequence, apply_sgr};
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum Color {
@@ -41,6 +43,7 @@ pub trait TextBufferOperations {
     fn get_width(&self) -> usize;
     fn get_height(&self) -> usize;
     fn get_color_code(&self) -> ColorCode;
+    fn set_color_code(&mut self, color_code: ColorCode);
     fn get_position(&self) -> (usize, usize);
     fn set_position(&mut self, row: usize, col: usize);
     fn set_char_at(&mut self, row: usize, col: usize, chr: ScreenChar);
@@ -79,11 +82,65 @@ pub trait TextBufferOperations {
     }
 
     fn write_string(&mut self, s: &str) {
+        // Consoles without a parser of their own only see sequences that
+        // arrive whole within one string.
+        let mut local = AnsiParser::new();
         for byte in s.bytes() {
-            match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xfe),
+            let event = match self.ansi_parser() {
+                Some(parser) => parser.feed(byte),
+                None => local.feed(byte),
+            };
+            match event {
+                Some(AnsiEvent::Print(byte @ (0x20..=0x7e | b'\n'))) => self.write_byte(byte),
+                Some(AnsiEvent::Print(_)) => self.write_byte(0xfe),
+                Some(AnsiEvent::Csi(sequence)) => self.apply_csi(&sequence),
+                None => {}
+            }
+        }
+    }
+
+    /// Parser state kept between [`write_string`](Self::write_string)
+    /// calls, if the console has one.
+    fn ansi_parser(&mut self) -> Option<&mut AnsiParser> {
+        None
+    }
+
+    /// Colour that SGR resets (`ESC[0m`, 39, 49) return to.
+    fn default_color_code(&self) -> ColorCode {
+        ColorCode::new(Color::LightGray, Color::Black)
+    }
+
+    /// Carry out a control sequence: SGR colours (`m`), cursor position
+    /// (`H`, `f`; 1-based and clamped to the screen) and erase-display
+    /// (`2J`).  Everything else is ignored.
+    fn apply_csi(&mut self, sequence: &CsiSequence) {
+        if sequence.is_private() {
+            return;
+        }
+        match sequence.final_byte {
+            b'm' => {
+                let color = apply_sgr(
+                    self.get_color_code(),
+                    self.default_color_code(),
+                    sequence.params(),
+                );
+                self.set_color_code(color);
+            }
+            b'H' | b'f' => {
+                let row = usize::from(sequence.param(0).max(1)) - 1;
+                let col = usize::from(sequence.param(1).max(1)) - 1;
+                self.set_position(
+                    row.min(self.get_height() - 1),
+                    col.min(self.get_width() - 1),
+                );
+            }
+            b'J' if sequence.param(0) == 2 => {
+                // Erase-display leaves the cursor where it was.
+                let position = self.get_position();
+                self.clear_screen();
+                self.set_position(position.0, position.1);
             }
+            _ => {}
         }
     }
 
@@ -126,15 +183,106 @@ pub trait TextBufferOperations {
 const VGA_WIDTH: usize = 80;
 const VGA_HEIGHT: usize = 25;
 const DEFAULT_VGA_BUFFER_ADDR: usize = 0xb8000;
+/// Size of the text-mode window at 0xB8000, which bounds the cells any
+/// text mode can have.
+pub const VGA_TEXT_WINDOW_BYTES: usize = 0x8000;
+const VGA_TEXT_WINDOW_CELLS: usize = VGA_TEXT_WINDOW_BYTES / core::mem::size_of::<ScreenChar>();
+
+/// Layout of the text mode the VGA is programmed for, as read back from its
+/// CRTC and sequencer registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextModeGeometry {
+    pub rows: usize,
+    pub cols: usize,
+    /// Character cell width in pixels: 9 in the BIOS modes, 8 in some
+    /// others.
+    pub char_width: usize,
+    /// Character cell height in scan lines: 16 for 80x25, 8 for 80x50.
+    pub char_height: usize,
+}
+
+impl TextModeGeometry {
+    /// CRTC Horizontal Display End: last character column.
+    const CRTC_HORIZONTAL_DISPLAY_END: u8 = 0x01;
+    /// CRTC Overflow: bits 8 and 9 of Vertical Display End in bits 1 and 6.
+    const CRTC_OVERFLOW: u8 = 0x07;
+    /// CRTC Maximum Scan Line: cell height minus one in bits 0-4, scan
+    /// doubling in bit 7.
+    const CRTC_MAX_SCAN_LINE: u8 = 0x09;
+    /// CRTC Vertical Display End: low 8 bits of the last visible scan line.
+    const CRTC_VERTICAL_DISPLAY_END: u8 = 0x12;
+    /// Sequencer Clocking Mode: bit 0 selects 8-pixel instead of 9-pixel
+    /// characters.
+    const SEQUENCER_CLOCKING_MODE: u8 = 0x01;
+
+    /// Read the current mode's geometry, or `None` if the registers do not
+    /// describe a text mode that fits the text window (for instance
+    /// because there is no VGA).
+    pub fn detect() -> Option<Self> {
+        use crate::io::HardwarePorts;
+        let read = |index_port: u16, data_port: u16, index: u8| -> u8 {
+            crate::port_write!(index_port, index);
+            crate::port_read_u8!(data_port)
+        };
+        let crtc = |index| read(HardwarePorts::CRTC_INDEX, HardwarePorts::CRTC_DATA, index);
+        Self::from_registers(
+            crtc(Self::CRTC_HORIZONTAL_DISPLAY_END),
+            crtc(Self::CRTC_VERTICAL_DISPLAY_END),
+            crtc(Self::CRTC_OVERFLOW),
+            crtc(Self::CRTC_MAX_SCAN_LINE),
+            read(
+                HardwarePorts::SEQUENCER_INDEX,
+                HardwarePorts::SEQUENCER_DATA,
+                Self::SEQUENCER_CLOCKING_MODE,
+            ),
+        )
+    }
+
+    fn from_registers(
+        horizontal_display_end: u8,
+        vertical_display_end: u8,
+        overflow: u8,
+        max_scan_line: u8,
+        clocking_mode: u8,
+    ) -> Option<Self> {
+        let scan_lines = (usize::from(vertical_display_end)
+            | usize::from(overflow >> 1 & 1) << 8
+            | usize::from(overflow >> 6 & 1) << 9)
+            + 1;
+        let char_height = usize::from(max_scan_line & 0x1F) + 1;
+        let lines_per_row = if max_scan_line & 0x80 != 0 {
+            char_height * 2
+        } else {
+            char_height
+        };
+        let geometry = Self {
+            rows: scan_lines / lines_per_row,
+            cols: usize::from(horizontal_display_end) + 1,
+            char_width: if clocking_mode & 1 != 0 { 8 } else { 9 },
+            char_height,
+        };
+        VgaBuffer::fits(geometry.rows, geometry.cols).then_some(geometry)
+    }
+}
 
 #[derive(Clone)]
 /// VGA text mode buffer wrapper that implements TextBufferOperations
+///
+/// Starts out 80x25; [`reconfigure`](Self::reconfigure) adopts the size of
+/// another text mode, e.g. one found by [`TextModeGeometry::detect`].
 pub struct VgaBuffer {
     buffer_addr: usize,
     enabled: bool,
+    rows: usize,
+    cols: usize,
     color_code: ColorCode,
     cursor_row: usize,
     cursor_col: usize,
+    /// First and last row (inclusive) that scroll on a new line; rows
+    /// outside the region are left alone.
+    scroll_top: usize,
+    scroll_bottom: usize,
+    ansi: AnsiParser,
 }
 
 impl core::fmt::Write for VgaBuffer {
@@ -198,9 +346,94 @@ impl VgaBuffer {
         Self {
             buffer_addr: addr,
             enabled: false,
+            rows: VGA_HEIGHT,
+            cols: VGA_WIDTH,
             color_code: ColorCode::new(Color::Green, Color::Black),
             cursor_row: 0,
             cursor_col: 0,
+            scroll_top: 0,
+            scroll_bottom: VGA_HEIGHT - 1,
+            ansi: AnsiParser::new(),
+        }
+    }
+
+    /// Whether a `rows` by `cols` mode fits the text window.
+    fn fits(rows: usize, cols: usize) -> bool {
+        rows > 0 && cols > 0 && rows.saturating_mul(cols) <= VGA_TEXT_WINDOW_CELLS
+    }
+
+    /// Current size as `(rows, cols)`.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Take on the size of a newly set text mode: the scroll region is
+    /// reset to the whole screen, the screen is cleared if enabled, and the
+    /// cursor goes home.  The cells live in video memory, so nothing is
+    /// allocated; the whole text window must be mapped at the buffer's
+    /// address for modes larger than 80x25.
+    pub fn reconfigure(
+        &mut self,
+        rows: usize,
+        cols: usize,
+    ) -> crate::common::logging::SystemResult<()> {
+        if !Self::fits(rows, cols) {
+            return Err(crate::common::logging::SystemError::InvalidArgument);
+        }
+        self.rows = rows;
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.reset();
+        Ok(())
+    }
+
+    /// Restrict scrolling to rows `top..=bottom`, e.g. to keep a status
+    /// line fixed above or below the output area.  The cursor is moved to
+    /// the start of the region if it was outside it.
+    pub fn set_scroll_region(
+        &mut self,
+        top: usize,
+        bottom: usize,
+    ) -> crate::common::logging::SystemResult<()> {
+        if top >= bottom || bottom >= self.rows {
+            return Err(crate::common::logging::SystemError::InvalidArgument);
+        }
+        self.scroll_top = top;
+        self.scroll_bottom = bottom;
+        if !(top..=bottom).contains(&self.cursor_row) {
+            self.set_position(top, 0);
+        }
+        Ok(())
+    }
+
+    /// Current cursor position as `(row, col)`.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    /// Write `s` starting at `row`, `col` without moving the cursor or
+    /// scrolling.  Output stops at a newline or the right edge of the row.
+    pub fn write_string_at(&mut self, row: usize, col: usize, s: &str) {
+        let color_code = self.color_code;
+        for (offset, byte) in s.bytes().enumerate() {
+            let ascii_character = match byte {
+                b'\n' => break,
+                0x20..=0x7e => byte,
+                _ => 0xfe,
+            };
+            let col = col + offset;
+            if col >= self.cols {
+                break;
+            }
+            self.set_char_at(
+                row,
+                col,
+                ScreenChar {
+                    ascii_character,
+                    color_code,
+                },
+            );
         }
     }
 
@@ -209,13 +442,19 @@ impl VgaBuffer {
     }
 
     pub fn update_cursor(&mut self) {
-        let pos = self.cursor_row * VGA_WIDTH + self.cursor_col;
+        let pos = self.cursor_row * self.cols + self.cursor_col;
         crate::update_vga_cursor!(pos);
     }
 
-    pub fn get_buffer(&mut self) -> Option<&mut [[ScreenChar; VGA_WIDTH]; VGA_HEIGHT]> {
+    /// The cells, row by row.
+    pub fn get_buffer(&mut self) -> Option<&mut [ScreenChar]> {
         if self.enabled {
-            Some(unsafe { &mut *(self.buffer_addr as *mut [[ScreenChar; VGA_WIDTH]; VGA_HEIGHT]) })
+            Some(unsafe {
+                core::slice::from_raw_parts_mut(
+                    self.buffer_addr as *mut ScreenChar,
+                    self.rows * self.cols,
+                )
+            })
         } else {
             None
         }
@@ -245,17 +484,29 @@ impl VgaBuffer {
 
 impl TextBufferOperations for VgaBuffer {
     fn get_width(&self) -> usize {
-        VGA_WIDTH
+        self.cols
     }
 
     fn get_height(&self) -> usize {
-        VGA_HEIGHT
+        self.rows
     }
 
     fn get_color_code(&self) -> ColorCode {
         self.color_code
     }
 
+    fn set_color_code(&mut self, color_code: ColorCode) {
+        self.color_code = color_code;
+    }
+
+    fn default_color_code(&self) -> ColorCode {
+        ColorCode::new(Color::Green, Color::Black)
+    }
+
+    fn ansi_parser(&mut self) -> Option<&mut AnsiParser> {
+        Some(&mut self.ansi)
+    }
+
     fn get_position(&self) -> (usize, usize) {
         (self.cursor_row, self.cursor_col)
     }
@@ -266,19 +517,18 @@ impl TextBufferOperations for VgaBuffer {
     }
 
     fn set_char_at(&mut self, row: usize, col: usize, chr: ScreenChar) {
-        if self.enabled && row < VGA_HEIGHT && col < VGA_WIDTH {
+        if self.enabled && row < self.rows && col < self.cols {
+            let cols = self.cols;
             if let Some(buffer) = self.get_buffer() {
-                buffer[row][col] = chr;
+                buffer[row * cols + col] = chr;
             }
         }
     }
 
     fn get_char_at(&self, row: usize, col: usize) -> ScreenChar {
-        if self.enabled && row < VGA_HEIGHT && col < VGA_WIDTH {
-            // Get buffer directly for immutable access
-            let buffer =
-                unsafe { &*(self.buffer_addr as *const [[ScreenChar; VGA_WIDTH]; VGA_HEIGHT]) };
-            buffer[row][col]
+        if self.enabled && row < self.rows && col < self.cols {
+            // Read the cell directly for immutable access
+            unsafe { *(self.buffer_addr as *const ScreenChar).add(row * self.cols + col) }
         } else {
             ScreenChar {
                 ascii_character: b' ',
@@ -287,22 +537,25 @@ impl TextBufferOperations for VgaBuffer {
         }
     }
 
+    fn new_line(&mut self) {
+        if self.cursor_row == self.scroll_bottom {
+            self.scroll_up();
+            self.set_position(self.scroll_bottom, 0);
+        } else {
+            self.set_position((self.cursor_row + 1).min(self.rows - 1), 0);
+        }
+    }
+
     fn scroll_up(&mut self) {
         let blank_char = ScreenChar {
             ascii_character: b' ',
             color_code: self.get_color_code(),
         };
-        let height = self.get_height();
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
         let width = self.get_width();
-        if let Some(ref mut buffer) = self.get_buffer() {
-            for row in 1..height {
-                unsafe {
-                    let src = buffer[row].as_ptr();
-                    let dst = buffer[row - 1].as_mut_ptr();
-                    core::ptr::copy_nonoverlapping(src, dst, width);
-                }
-            }
-            buffer[height - 1].fill(blank_char);
+        if let Some(buffer) = self.get_buffer() {
+            buffer.copy_within((top + 1) * width..(bottom + 1) * width, top * width);
+            buffer[bottom * width..(bottom + 1) * width].fill(blank_char);
         }
     }
 }
@@ -324,7 +577,7 @@ impl crate::initializer::Initializable for VgaBuffer {
 
 impl crate::initializer::ErrorLogging for VgaBuffer {
     fn log_error(&self, error: &crate::common::logging::SystemError, context: &'static str) {
-        log::error!("{}: {:?}", context, error);
+        log::error!("{}: {}", context, error);
     }
 
     fn log_warning(&self, message: &'static str) {
@@ -376,12 +629,160 @@ impl crate::initializer::HardwareDevice for VgaBuffer {
     }
 }
 
+/// Fixed-size fonts for drawing text straight onto a
+/// [`SimpleFramebuffer`](crate::graphics::color::SimpleFramebuffer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    /// The 6x10 font the desktop already uses.
+    Font6x10,
+    /// 8x16 cells holding the 8x13 glyphs, padded to a VGA-like line height.
+    Font8x16,
+}
+
+impl Font {
+    /// Width and height of one character cell; the width is also the advance.
+    pub const fn cell_size(self) -> (usize, usize) {
+        match self {
+            Font::Font6x10 => (6, 10),
+            Font::Font8x16 => (8, 16),
+        }
+    }
+
+    /// Horizontal advance of a printable `c`: the cell width for these
+    /// monospaced fonts, including characters drawn with the replacement
+    /// glyph.  A proportional font would vary it per glyph.
+    pub const fn advance(self, c: char) -> usize {
+        let _ = c;
+        self.cell_size().0
+    }
+
+    fn glyphs(self) -> &'static embedded_graphics::mono_font::MonoFont<'static> {
+        match self {
+            Font::Font6x10 => &embedded_graphics::mono_font::ascii::FONT_6X10,
+            Font::Font8x16 => &embedded_graphics::mono_font::ascii::FONT_8X13,
+        }
+    }
+
+    /// Blank rows above the glyph inside its cell.
+    const fn top_padding(self) -> usize {
+        match self {
+            Font::Font6x10 => 0,
+            Font::Font8x16 => 2,
+        }
+    }
+
+    /// Whether pixel (`col`, `row`) of `c`'s cell is set.  Characters the
+    /// font lacks use its replacement glyph.
+    fn is_set(self, c: char, col: usize, row: usize) -> bool {
+        use embedded_graphics::image::GetPixel;
+        use embedded_graphics::pixelcolor::BinaryColor;
+        use embedded_graphics::prelude::{OriginDimensions, Point};
+
+        let font = self.glyphs();
+        let Some(row) = row.checked_sub(self.top_padding()) else {
+            return false;
+        };
+        let (width, height) = (
+            font.character_size.width as usize,
+            font.character_size.height as usize,
+        );
+        if col >= width || row >= height {
+            return false;
+        }
+        let per_row = font.image.size().width as usize / width;
+        let index = font.glyph_mapping.index(c);
+        let x = (index % per_row) * width + col;
+        let y = (index / per_row) * height + row;
+        font.image.pixel(Point::new(x as i32, y as i32)) == Some(BinaryColor::On)
+    }
+}
+
+/// Draw `c` with its cell's top-left corner at (`x`, `y`).
+///
+/// Set pixels get `fg` and the rest of the cell `bg`.  Any part of the cell
+/// past the right or bottom edge is clipped.
+pub fn draw_glyph(
+    fb: &mut crate::graphics::color::SimpleFramebuffer,
+    x: usize,
+    y: usize,
+    c: char,
+    font: Font,
+    fg: u32,
+    bg: u32,
+) {
+    let (width, height) = font.cell_size();
+    let cols = width.min(fb.width.saturating_sub(x));
+    let rows = height.min(fb.height.saturating_sub(y));
+    for row in 0..rows {
+        for col in 0..cols {
+            let color = if font.is_set(c, col, row) { fg } else { bg };
+            fb.draw_pixel(x + col, y + row, color);
+        }
+    }
+}
+
+/// Tab stops are this many spaces apart.
+const TAB_STOP_SPACES: usize = 4;
+
+/// Pen position after `c` when it comes at `pen`, `line_start` being where
+/// the line began.  A tab moves to the next tab stop, other control
+/// characters (newline included) do not move the pen, and everything else
+/// advances by its glyph.
+fn advance_pen(pen: usize, line_start: usize, c: char, font: Font) -> usize {
+    match c {
+        '\t' => {
+            let stop = TAB_STOP_SPACES * font.advance(' ');
+            line_start + ((pen - line_start) / stop + 1) * stop
+        }
+        c if c.is_control() => pen,
+        c => pen + font.advance(c),
+    }
+}
+
+/// Width in pixels of `text` drawn in `font`: the widest of its lines, as
+/// [`draw_text`] would lay each out.  Tabs count up to the next tab stop,
+/// every [`TAB_STOP_SPACES`] spaces from the start of the line, and other
+/// control characters count nothing.
+pub fn measure(text: &str, font: Font) -> u32 {
+    text.split('\n')
+        .map(|line| line.chars().fold(0, |pen, c| advance_pen(pen, 0, c, font)))
+        .max()
+        .unwrap_or(0) as u32
+}
+
+/// Draw `text` on one line starting at (`x`, `y`), advancing one cell per
+/// character; tabs and other control characters move the pen as
+/// [`measure`] counts them and draw nothing.  Returns the x coordinate after
+/// the last cell.
+pub fn draw_text(
+    fb: &mut crate::graphics::color::SimpleFramebuffer,
+    x: usize,
+    y: usize,
+    text: &str,
+    font: Font,
+    fg: u32,
+    bg: u32,
+) -> usize {
+    let mut cursor = x;
+    for c in text.chars() {
+        if cursor >= fb.width {
+            break;
+        }
+        if !c.is_control() {
+            draw_glyph(fb, cursor, y, c, font, fg, bg);
+        }
+        cursor = advance_pen(cursor, x, c, font);
+    }
+    cursor
+}
+
 // Re-export for backward compatibility and consolidation
 pub use self::VgaBuffer as KernelVgaBuffer;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::graphics::color::{SimpleFramebuffer, SimpleFramebufferConfig};
     use crate::initializer::{ErrorLogging, HardwareDevice, Initializable};
 
     #[test]
@@ -442,4 +843,170 @@ mod tests {
         let _ = device.enable();
         assert!(device.is_enabled());
     }
+
+    fn framebuffer(pixels: &mut [u32], width: usize, height: usize) -> SimpleFramebuffer {
+        SimpleFramebuffer::new(SimpleFramebufferConfig {
+            base_addr: pixels.as_mut_ptr() as usize,
+            width,
+            height,
+            stride: width * 4,
+            bytes_per_pixel: 4,
+            pixel_format: None,
+        })
+    }
+
+    #[test]
+    fn glyphs_fill_their_cell_with_fg_and_bg() {
+        const W: usize = 20;
+        const H: usize = 20;
+        let mut pixels = [0u32; W * H];
+        let mut fb = framebuffer(&mut pixels, W, H);
+        for font in [Font::Font6x10, Font::Font8x16] {
+            let (width, height) = font.cell_size();
+            fb.clear(0);
+            draw_glyph(&mut fb, 1, 1, 'H', font, 1, 2);
+            let cell: alloc::vec::Vec<u32> = (1..=height)
+                .flat_map(|y| (1..=width).map(move |x| (x, y)))
+                .map(|(x, y)| fb.get_pixel(x, y))
+                .collect();
+            assert!(cell.iter().all(|&p| p == 1 || p == 2), "{font:?}");
+            assert!(cell.contains(&1) && cell.contains(&2), "{font:?}");
+            assert_eq!(fb.get_pixel(width + 1, 1), 0, "{font:?}");
+            assert_eq!(fb.get_pixel(1, height + 1), 0, "{font:?}");
+        }
+        // The padded rows of the 8x16 cell stay background.
+        assert!((1..=8).all(|x| fb.get_pixel(x, 1) == 2));
+        assert!((1..=8).all(|x| fb.get_pixel(x, 16) == 2));
+    }
+
+    #[test]
+    fn text_clips_at_the_right_and_bottom_edges() {
+        const W: usize = 20;
+        const H: usize = 12;
+        let mut pixels = [0u32; W * H + 1];
+        pixels[W * H] = 0xDEAD;
+        let mut fb = framebuffer(&mut pixels[..W * H], W, H);
+        let end = draw_text(&mut fb, 4, 4, "abcdef", Font::Font8x16, 1, 2);
+        assert_eq!(end, 4 + 2 * 8);
+        assert_eq!(fb.get_pixel(W - 1, H - 1), 2);
+        assert_eq!(pixels[W * H], 0xDEAD);
+    }
+
+    #[test]
+    fn measure_matches_draw_text_and_defines_tabs_and_newlines() {
+        assert_eq!(measure("", Font::Font6x10), 0);
+        assert_eq!(measure("abc", Font::Font6x10), 18);
+        assert_eq!(measure("abc", Font::Font8x16), 24);
+        // Characters the font lacks still take a cell.
+        assert_eq!(measure("é", Font::Font8x16), 8);
+        // Tabs stop every four cells from the start of the line.
+        assert_eq!(measure("\t", Font::Font6x10), 24);
+        assert_eq!(measure("ab\tc", Font::Font6x10), 30);
+        assert_eq!(measure("abcd\t", Font::Font6x10), 48);
+        // The widest line counts; other control characters are free.
+        assert_eq!(measure("ab\nabcde\r\nabc", Font::Font6x10), 30);
+
+        const W: usize = 200;
+        let mut pixels = [0u32; W * 16];
+        let mut fb = framebuffer(&mut pixels, W, 16);
+        let text = "a\tb\x07c";
+        let end = draw_text(&mut fb, 5, 0, text, Font::Font8x16, 1, 2);
+        assert_eq!(end - 5, measure(text, Font::Font8x16) as usize);
+    }
+
+    /// A device over a whole text window's worth of cells, 80x25 to start.
+    fn screen() -> (alloc::vec::Vec<ScreenChar>, VgaBuffer) {
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: ColorCode(0),
+        };
+        let mut cells = alloc::vec![blank; VGA_TEXT_WINDOW_CELLS];
+        let mut device = VgaBuffer::with_address(cells.as_mut_ptr() as usize);
+        device.enable();
+        (cells, device)
+    }
+
+    #[test]
+    fn scroll_region_keeps_status_row_intact() {
+        let (_cells, mut device) = screen();
+        device.write_string_at(0, 0, "status");
+        device.set_scroll_region(1, VGA_HEIGHT - 1).unwrap();
+        assert_eq!(device.cursor_position(), (1, 0));
+
+        for _ in 0..VGA_HEIGHT {
+            device.write_string("line\n");
+        }
+        device.write_string("last");
+
+        assert_eq!(device.get_char_at(0, 0).ascii_character, b's');
+        assert_eq!(device.get_char_at(0, 5).ascii_character, b's');
+        assert_eq!(device.get_char_at(VGA_HEIGHT - 1, 0).ascii_character, b'l');
+        assert_eq!(device.get_char_at(VGA_HEIGHT - 1, 3).ascii_character, b't');
+        assert_eq!(device.cursor_position(), (VGA_HEIGHT - 1, 4));
+    }
+
+    #[test]
+    fn write_string_at_clips_and_keeps_cursor() {
+        let (_cells, mut device) = screen();
+        device.write_string("ab");
+        device.write_string_at(3, VGA_WIDTH - 2, "xyz");
+        assert_eq!(device.get_char_at(3, VGA_WIDTH - 1).ascii_character, b'y');
+        assert_eq!(device.cursor_position(), (0, 2));
+        assert!(device.set_scroll_region(5, 5).is_err());
+        assert!(device.set_scroll_region(0, VGA_HEIGHT).is_err());
+    }
+
+    #[test]
+    fn reconfigure_lays_rows_out_at_the_new_width() {
+        let (cells, mut device) = screen();
+        device.write_string("old");
+        device.reconfigure(50, 132).unwrap();
+        assert_eq!(device.dimensions(), (50, 132));
+        assert_eq!(device.cursor_position(), (0, 0));
+        assert_eq!(cells[0].ascii_character, b' ');
+
+        device.write_string_at(1, 131, "ab");
+        assert_eq!(cells[132 + 131].ascii_character, b'a');
+        assert_eq!(cells[2 * 132].ascii_character, b' ');
+        for _ in 0..50 {
+            device.write_string("\n");
+        }
+        device.write_string("end");
+        // Fifty newlines from the top scroll the row written above up by one.
+        assert_eq!(device.get_char_at(0, 131).ascii_character, b'a');
+        assert_eq!(device.get_char_at(49, 2).ascii_character, b'd');
+        assert!(device.set_scroll_region(1, 49).is_ok());
+
+        assert!(device.reconfigure(0, 80).is_err());
+        assert!(device.reconfigure(200, 132).is_err());
+        assert_eq!(device.dimensions(), (50, 132));
+    }
+
+    #[test]
+    fn text_mode_geometry_is_read_from_crtc_registers() {
+        // Mode 3: 400 scan lines of 16-line, 9-pixel cells.
+        assert_eq!(
+            TextModeGeometry::from_registers(0x4F, 0x8F, 0x1F, 0x4F, 0x00),
+            Some(TextModeGeometry {
+                rows: 25,
+                cols: 80,
+                char_width: 9,
+                char_height: 16,
+            })
+        );
+        // The same timing with the 8x8 font gives 80x50.
+        let geometry = TextModeGeometry::from_registers(0x4F, 0x8F, 0x1F, 0x47, 0x01).unwrap();
+        assert_eq!((geometry.rows, geometry.cols), (50, 80));
+        assert_eq!((geometry.char_width, geometry.char_height), (8, 8));
+        // Scan doubling halves the rows.
+        let geometry = TextModeGeometry::from_registers(0x4F, 0x8F, 0x1F, 0xC7, 0x01).unwrap();
+        assert_eq!(geometry.rows, 25);
+        // 132 columns; 1024 one-line rows of 256 columns overflow the window.
+        let geometry = TextModeGeometry::from_registers(0x83, 0x8F, 0x1F, 0x4F, 0x01).unwrap();
+        assert_eq!((geometry.rows, geometry.cols), (25, 132));
+        assert_eq!(
+            TextModeGeometry::from_registers(0xFF, 0xFF, 0xFF, 0x00, 0x00),
+            None
+        );
+    }
 }
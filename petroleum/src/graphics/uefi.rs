@@ -1,12 +1,12 @@
-//! UEFI Graphics Output Protocol discovery.
+//! UEFI Graphics Output Protocol discovery, with QEMU's `ramfb` as the
+//! fallback when there is no GOP.
 
 use crate::common::memory::create_framebuffer_config;
 use crate::common::{
     EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID, EfiGraphicsOutputProtocol, EfiGraphicsPixelFormat,
-    EfiStatus, EfiSystemTable, FullereneFramebufferConfig,
+    EfiMemoryType, EfiStatus, EfiSystemTable, FullereneFramebufferConfig,
 };
 use core::{ffi::c_void, ptr};
-use spin::Mutex;
 
 macro_rules! log_uefi {
     ($($arg:tt)*) => { crate::serial::_print(format_args!($($arg)*)) };
@@ -55,7 +55,7 @@ fn normalize_pixel_format(
 }
 
 fn install(config: FullereneFramebufferConfig) {
-    crate::FULLERENE_FRAMEBUFFER_CONFIG.call_once(|| Mutex::new(Some(config)));
+    crate::graphics::framebuffer::set_config(config);
     const GRAY: u32 = 0x0080_8080;
     let pixels = usize::try_from(config.stride / 4)
         .ok()
@@ -121,8 +121,52 @@ pub fn init_gop_framebuffer(system_table: &EfiSystemTable) -> Option<FullereneFr
     Some(config)
 }
 
+/// Mode given to QEMU's `ramfb`, which scans out whatever the guest asks for.
+const RAMFB_WIDTH: u32 = 1024;
+const RAMFB_HEIGHT: u32 = 768;
+
+/// Set up QEMU's `ramfb` device on a framebuffer allocated from boot
+/// services.  The pages are reserved memory, so neither bellows nor the
+/// kernel hands them out after boot services end.
+pub fn init_ramfb_framebuffer(system_table: &EfiSystemTable) -> Option<FullereneFramebufferConfig> {
+    let services = unsafe { system_table.boot_services.as_ref() }?;
+    let size = RAMFB_WIDTH as usize * 4 * RAMFB_HEIGHT as usize;
+    let mut address = 0usize;
+    let status = EfiStatus::from((services.allocate_pages)(
+        0,
+        EfiMemoryType::EfiReservedMemoryType,
+        size.div_ceil(4096),
+        &mut address,
+    ));
+    if status != EfiStatus::Success {
+        log_uefi!(
+            "ramfb: framebuffer allocation failed ({:#x})\n",
+            status as u32
+        );
+        return None;
+    }
+    // Boot services identity map memory, as `configure` requires.
+    match unsafe { super::ramfb::configure(address as u64, RAMFB_WIDTH, RAMFB_HEIGHT) } {
+        Ok(config) => {
+            install(config);
+            log_uefi!(
+                "ramfb: {}x{} base={:#x}\n",
+                config.width,
+                config.height,
+                config.address
+            );
+            Some(config)
+        }
+        Err(err) => {
+            log_uefi!("ramfb: unavailable ({:?})\n", err);
+            (services.free_pages)(address, size.div_ceil(4096));
+            None
+        }
+    }
+}
+
 pub fn init_graphics_protocols(
     system_table: &EfiSystemTable,
 ) -> Option<FullereneFramebufferConfig> {
-    init_gop_framebuffer(system_table)
+    init_gop_framebuffer(system_table).or_else(|| init_ramfb_framebuffer(system_table))
 }
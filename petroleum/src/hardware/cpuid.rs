@@ -0,0 +1,184 @@
+//! CPU feature detection.
+//!
+//! [`features`] reads CPUID once and caches the result, so code that turns
+//! on an optional feature can ask without re-executing CPUID, which traps to
+//! the hypervisor under virtualisation.  Everything that sets a CR4 or EFER
+//! bit, or executes an instruction outside the x86-64 baseline, should check
+//! here first: restricted QEMU CPU models such as `qemu64` lack SMEP, SMAP
+//! and the invariant TSC, and enabling an absent feature raises #GP or #UD.
+
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+use core::fmt;
+use spin::Once;
+
+const LEAF_FEATURES: u32 = 1;
+const LEAF_EXTENDED_FEATURES: u32 = 7;
+const LEAF_MAX_EXTENDED: u32 = 0x8000_0000;
+const LEAF_EXTENDED_INFO: u32 = 0x8000_0001;
+const LEAF_POWER_MANAGEMENT: u32 = 0x8000_0007;
+
+/// Features the kernel cares about, as reported by CPUID.
+///
+/// A feature being present means the CPU implements it, not that it is
+/// enabled: AVX in particular also needs XCR0 set up, which nothing does yet.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuFeatures {
+    /// `RDTSC` (leaf 1 EDX bit 4).
+    pub tsc: bool,
+    /// Page attribute table (leaf 1 EDX bit 16).
+    pub pat: bool,
+    /// `FXSAVE`/`FXRSTOR` (leaf 1 EDX bit 24).
+    pub fxsr: bool,
+    /// Leaf 1 EDX bit 25.
+    pub sse: bool,
+    /// Leaf 1 EDX bit 26.
+    pub sse2: bool,
+    /// Leaf 1 ECX bit 28.
+    pub avx: bool,
+    /// Supervisor-mode execution prevention (leaf 7 EBX bit 7).
+    pub smep: bool,
+    /// Supervisor-mode access prevention (leaf 7 EBX bit 20).
+    pub smap: bool,
+    /// No-execute page protection (leaf 0x8000_0001 EDX bit 20).
+    pub nx: bool,
+    /// TSC rate independent of P-, C- and T-states (leaf 0x8000_0007 EDX
+    /// bit 8).
+    pub invariant_tsc: bool,
+}
+
+impl CpuFeatures {
+    /// Query the running CPU.  Leaves above the maximum it reports are
+    /// treated as all zeroes, since reading them returns unrelated data.
+    pub fn detect() -> Self {
+        let max_basic = __cpuid(0).eax;
+        let max_extended = __cpuid(LEAF_MAX_EXTENDED).eax;
+        let leaf1 = __cpuid(LEAF_FEATURES);
+        let leaf7_ebx = if max_basic >= LEAF_EXTENDED_FEATURES {
+            __cpuid_count(LEAF_EXTENDED_FEATURES, 0).ebx
+        } else {
+            0
+        };
+        let extended_edx = |leaf| {
+            if max_extended >= leaf {
+                __cpuid(leaf).edx
+            } else {
+                0
+            }
+        };
+        Self::from_registers(
+            leaf1.ecx,
+            leaf1.edx,
+            leaf7_ebx,
+            extended_edx(LEAF_EXTENDED_INFO),
+            extended_edx(LEAF_POWER_MANAGEMENT),
+        )
+    }
+
+    /// Decode the feature bits of leaf 1 ECX/EDX, leaf 7 subleaf 0 EBX,
+    /// leaf 0x8000_0001 EDX and leaf 0x8000_0007 EDX.
+    fn from_registers(
+        leaf1_ecx: u32,
+        leaf1_edx: u32,
+        leaf7_ebx: u32,
+        extended_info_edx: u32,
+        power_management_edx: u32,
+    ) -> Self {
+        let bit = |register: u32, bit: u32| register & (1 << bit) != 0;
+        Self {
+            tsc: bit(leaf1_edx, 4),
+            pat: bit(leaf1_edx, 16),
+            fxsr: bit(leaf1_edx, 24),
+            sse: bit(leaf1_edx, 25),
+            sse2: bit(leaf1_edx, 26),
+            avx: bit(leaf1_ecx, 28),
+            smep: bit(leaf7_ebx, 7),
+            smap: bit(leaf7_ebx, 20),
+            nx: bit(extended_info_edx, 20),
+            invariant_tsc: bit(power_management_edx, 8),
+        }
+    }
+}
+
+/// Lists the features present, space separated.
+impl fmt::Display for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.tsc, "tsc"),
+            (self.pat, "pat"),
+            (self.fxsr, "fxsr"),
+            (self.sse, "sse"),
+            (self.sse2, "sse2"),
+            (self.avx, "avx"),
+            (self.smep, "smep"),
+            (self.smap, "smap"),
+            (self.nx, "nx"),
+            (self.invariant_tsc, "invtsc"),
+        ];
+        let mut present = names.iter().filter(|(has, _)| *has).map(|(_, name)| name);
+        match present.next() {
+            Some(first) => f.write_str(first)?,
+            None => return f.write_str("none"),
+        }
+        present.try_for_each(|name| write!(f, " {name}"))
+    }
+}
+
+static FEATURES: Once<CpuFeatures> = Once::new();
+
+/// Features of the bootstrap processor, detected on first use.  APs are
+/// assumed to match it.
+pub fn features() -> CpuFeatures {
+    *FEATURES.call_once(CpuFeatures::detect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn decodes_feature_bits_from_their_registers() {
+        // A Skylake-class CPU: everything present.
+        let features = CpuFeatures::from_registers(
+            1 << 28,
+            (1 << 4) | (1 << 16) | (1 << 24) | (1 << 25) | (1 << 26),
+            (1 << 7) | (1 << 20),
+            1 << 20,
+            1 << 8,
+        );
+        assert_eq!(
+            features,
+            CpuFeatures {
+                tsc: true,
+                pat: true,
+                fxsr: true,
+                sse: true,
+                sse2: true,
+                avx: true,
+                smep: true,
+                smap: true,
+                nx: true,
+                invariant_tsc: true,
+            }
+        );
+
+        // Neighbouring bits must not be mistaken for the ones decoded.
+        let features = CpuFeatures::from_registers(!(1 << 28), 0, !((1 << 7) | (1 << 20)), 0, 0);
+        assert_eq!(features, CpuFeatures::default());
+    }
+
+    #[test]
+    fn display_lists_only_present_features() {
+        let qemu64 = CpuFeatures {
+            tsc: true,
+            pat: true,
+            fxsr: true,
+            sse: true,
+            sse2: true,
+            nx: true,
+            ..CpuFeatures::default()
+        };
+        assert_eq!(qemu64.to_string(), "tsc pat fxsr sse sse2 nx");
+        assert_eq!(CpuFeatures::default().to_string(), "none");
+    }
+}
@@ -0,0 +1,257 @@
+//! QEMU firmware configuration (fw_cfg) interface.
+//!
+//! fw_cfg exposes items such as the kernel command line, the E820 map,
+//! SMBIOS tables and named files (`etc/ramfb`, `etc/e820`, ...) behind a
+//! 16-bit selector port and an 8-bit data port.  Selecting a key rewinds
+//! the item, and each data read returns its next byte.  Newer QEMU also
+//! offers a DMA interface, the only way to write an item.
+//!
+//! Real hardware has nothing at these ports, so everything that is not a
+//! raw port access checks for the `QEMU` signature first.
+
+use alloc::vec::Vec;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+/// Selector (16-bit) and data (8-bit) ports.
+const SELECTOR_PORT: u16 = 0x510;
+const DATA_PORT: u16 = 0x511;
+/// DMA address register: a big-endian 64-bit physical address, high half
+/// first; writing the low half starts the transfer.
+const DMA_HIGH_PORT: u16 = 0x514;
+const DMA_LOW_PORT: u16 = 0x518;
+
+/// Item holding the signature `QEMU`.
+pub const SIGNATURE: u16 = 0x0000;
+/// Item holding the little-endian feature bitmap.
+pub const ID: u16 = 0x0001;
+/// Item holding the file directory.
+pub const FILE_DIR: u16 = 0x0019;
+
+/// `ID` bit advertising the DMA interface.
+const ID_DMA: u32 = 1 << 1;
+
+const DMA_CTL_ERROR: u32 = 1 << 0;
+const DMA_CTL_READ: u32 = 1 << 1;
+const DMA_CTL_SELECT: u32 = 1 << 3;
+const DMA_CTL_WRITE: u32 = 1 << 4;
+
+/// Length of the NUL-padded name of a directory entry.
+pub const FILE_NAME_LEN: usize = 56;
+/// Size of a directory entry: size, selector, reserved and name.
+const FILE_ENTRY_LEN: usize = 8 + FILE_NAME_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FwCfgError {
+    /// Nothing answers with the `QEMU` signature: not running under QEMU.
+    NotPresent,
+    /// fw_cfg has no DMA interface.
+    NoDma,
+    /// No file of that name in the directory.
+    FileNotFound,
+    /// The device reported an error for a DMA transfer.
+    DmaFailed,
+}
+
+/// An entry of the fw_cfg file directory.
+#[derive(Clone, Copy)]
+pub struct FwCfgFile {
+    pub size: u32,
+    pub select: u16,
+    name: [u8; FILE_NAME_LEN],
+}
+
+impl FwCfgFile {
+    /// Decode a big-endian `FWCfgFile` directory entry.
+    fn from_bytes(entry: &[u8; FILE_ENTRY_LEN]) -> Self {
+        let mut name = [0u8; FILE_NAME_LEN];
+        name.copy_from_slice(&entry[8..]);
+        Self {
+            size: u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]),
+            select: u16::from_be_bytes([entry[4], entry[5]]),
+            name,
+        }
+    }
+
+    /// The file name, up to its first NUL; empty if it is not UTF-8.
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(FILE_NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+
+impl core::fmt::Debug for FwCfgFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FwCfgFile")
+            .field("size", &self.size)
+            .field("select", &self.select)
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// `FWCfgDmaAccess`, all big-endian.  The device clears `control` once the
+/// transfer is done, leaving [`DMA_CTL_ERROR`] set if it failed.
+#[repr(C)]
+struct DmaAccess {
+    control: u32,
+    length: u32,
+    address: u64,
+}
+
+/// Select the item `key`, rewinding it to its first byte.
+///
+/// Raw port access: only meaningful once [`is_present`] holds.
+pub fn select(key: u16) {
+    unsafe { PortWriteOnly::<u16>::new(SELECTOR_PORT).write(key) };
+}
+
+/// Read the next `buf.len()` bytes of the selected item.  Reads past its
+/// end return zeros.
+///
+/// Raw port access: only meaningful once [`is_present`] holds.
+pub fn read_bytes(buf: &mut [u8]) {
+    let mut data = Port::<u8>::new(DATA_PORT);
+    for byte in buf {
+        *byte = unsafe { data.read() };
+    }
+}
+
+/// Whether fw_cfg answers with its `QEMU` signature.  Absent ports read as
+/// all ones, which never matches.
+pub fn is_present() -> bool {
+    select(SIGNATURE);
+    let mut signature = [0u8; 4];
+    read_bytes(&mut signature);
+    &signature == b"QEMU"
+}
+
+/// Whether the DMA interface is available.
+pub fn dma_supported() -> bool {
+    if !is_present() {
+        return false;
+    }
+    select(ID);
+    let mut id = [0u8; 4];
+    read_bytes(&mut id);
+    u32::from_le_bytes(id) & ID_DMA != 0
+}
+
+/// Read the whole file directory.
+pub fn list_files() -> Result<Vec<FwCfgFile>, FwCfgError> {
+    let mut files = Vec::new();
+    for_each_file(|file| {
+        files.push(file);
+        false
+    })?;
+    Ok(files)
+}
+
+/// Look up the file `name` without allocating.
+pub fn find_file(name: &str) -> Result<FwCfgFile, FwCfgError> {
+    let mut found = None;
+    for_each_file(|file| {
+        let matches = file.name() == name;
+        if matches {
+            found = Some(file);
+        }
+        matches
+    })?;
+    found.ok_or(FwCfgError::FileNotFound)
+}
+
+/// Call `f` on each directory entry until it returns `true`.
+fn for_each_file(mut f: impl FnMut(FwCfgFile) -> bool) -> Result<(), FwCfgError> {
+    if !is_present() {
+        return Err(FwCfgError::NotPresent);
+    }
+    select(FILE_DIR);
+    let mut count = [0u8; 4];
+    read_bytes(&mut count);
+    for _ in 0..u32::from_be_bytes(count) {
+        let mut entry = [0u8; FILE_ENTRY_LEN];
+        read_bytes(&mut entry);
+        if f(FwCfgFile::from_bytes(&entry)) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read `length` bytes of the item `key` into physical `buffer` by DMA.
+///
+/// # Safety
+///
+/// `buffer` must be writable memory of at least `length` bytes, and virtual
+/// addresses must equal physical ones, as the DMA descriptor on the stack
+/// is passed to the device by address.
+pub unsafe fn dma_read(key: u16, buffer: u64, length: u32) -> Result<(), FwCfgError> {
+    unsafe { dma_transfer(key, DMA_CTL_READ, buffer, length) }
+}
+
+/// Write `length` bytes at physical `data` to the item `key` by DMA.
+///
+/// # Safety
+///
+/// As for [`dma_read`], with `data` readable for `length` bytes.
+pub unsafe fn dma_write(key: u16, data: u64, length: u32) -> Result<(), FwCfgError> {
+    unsafe { dma_transfer(key, DMA_CTL_WRITE, data, length) }
+}
+
+unsafe fn dma_transfer(
+    key: u16,
+    direction: u32,
+    address: u64,
+    length: u32,
+) -> Result<(), FwCfgError> {
+    if !dma_supported() {
+        return Err(FwCfgError::NoDma);
+    }
+    let mut access = DmaAccess {
+        control: ((u32::from(key) << 16) | DMA_CTL_SELECT | direction).to_be(),
+        length: length.to_be(),
+        address: address.to_be(),
+    };
+    let descriptor = (&raw mut access) as u64;
+    // QEMU completes the transfer before the port write returns; poll
+    // anyway, as the interface allows asynchronous completion.
+    unsafe {
+        PortWriteOnly::<u32>::new(DMA_HIGH_PORT).write(((descriptor >> 32) as u32).to_be());
+        PortWriteOnly::<u32>::new(DMA_LOW_PORT).write((descriptor as u32).to_be());
+    }
+    loop {
+        let control = u32::from_be(unsafe { core::ptr::read_volatile(&raw const access.control) });
+        if control & DMA_CTL_ERROR != 0 {
+            return Err(FwCfgError::DmaFailed);
+        }
+        if control == 0 {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_entries_decode_big_endian_fields_and_name() {
+        let mut entry = [0u8; FILE_ENTRY_LEN];
+        entry[..4].copy_from_slice(&28u32.to_be_bytes());
+        entry[4..6].copy_from_slice(&0x0027u16.to_be_bytes());
+        entry[8..17].copy_from_slice(b"etc/ramfb");
+        let file = FwCfgFile::from_bytes(&entry);
+        assert_eq!(file.size, 28);
+        assert_eq!(file.select, 0x0027);
+        assert_eq!(file.name(), "etc/ramfb");
+
+        entry[8..].fill(b'x');
+        assert_eq!(FwCfgFile::from_bytes(&entry).name().len(), FILE_NAME_LEN);
+        entry[8] = 0xFF;
+        assert_eq!(FwCfgFile::from_bytes(&entry).name(), "");
+    }
+}
@@ -0,0 +1,7 @@
+//! Drivers for legacy PC platform devices that the kernel needs before (or
+//! independently of) the nitrogen device stack.
+
+pub mod cpuid;
+pub mod fw_cfg;
+pub mod rtc;
+pub mod tsc;
@@ -0,0 +1,254 @@
+//! CMOS real-time clock.
+//!
+//! Reads the MC146818-compatible RTC through index/data ports 0x70/0x71.
+//! The RTC is assumed to hold UTC (flasks starts QEMU with `-rtc base=utc`),
+//! and [`DateTime::unix_timestamp`] counts seconds since the Unix epoch,
+//! 1970-01-01 00:00:00 UTC, ignoring leap seconds.
+
+use x86_64::instructions::port::{PortReadOnly, PortWriteOnly};
+
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+/// Century register at the offset most firmware reports in the FADT.
+const REG_CENTURY: u8 = 0x32;
+
+/// Status A: an update cycle is in progress and the time registers may be
+/// mid-change.
+const STATUS_A_UIP: u8 = 0x80;
+/// Status B: registers hold binary rather than BCD values.
+const STATUS_B_BINARY: u8 = 0x04;
+/// Status B: hours are in 24-hour format.
+const STATUS_B_24H: u8 = 0x02;
+/// Hour register PM flag in 12-hour mode.
+const HOUR_PM: u8 = 0x80;
+
+const UIP_SPIN_LIMIT: u32 = 10_000;
+const MAX_READ_ATTEMPTS: u32 = 5;
+
+/// Calendar date and time of day as reported by the RTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Whether every field is in range and the date is not before 1970.
+    pub fn is_valid(&self) -> bool {
+        self.year >= 1970
+            && (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Seconds since 1970-01-01 00:00:00 UTC, or `None` if the RTC returned
+    /// an invalid date.
+    pub fn unix_timestamp(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
+        }
+        let days = days_since_epoch(self.year, self.month, self.day);
+        Some(
+            days * 86_400 + self.hour as u64 * 3_600 + self.minute as u64 * 60 + self.second as u64,
+        )
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given date (year >= 1970).
+fn days_since_epoch(year: u16, month: u8, day: u8) -> u64 {
+    // Shift the year to start in March so the leap day is the last day.
+    let (y, m) = if month <= 2 {
+        (year as u64 - 1, month as u64 + 9)
+    } else {
+        (year as u64, month as u64 - 3)
+    };
+    let day_of_year = (153 * m + 2) / 5 + day as u64 - 1;
+    let days = y * 365 + y / 4 - y / 100 + y / 400 + day_of_year;
+    // Same formula evaluated at 1970-01-01.
+    days - 719_468
+}
+
+/// Raw register snapshot, before BCD and 12-hour decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    century: u8,
+}
+
+fn cmos_read(reg: u8) -> u8 {
+    unsafe {
+        PortWriteOnly::<u8>::new(CMOS_INDEX).write(reg);
+        PortReadOnly::<u8>::new(CMOS_DATA).read()
+    }
+}
+
+fn read_registers() -> RawTime {
+    let mut spins = 0;
+    while cmos_read(REG_STATUS_A) & STATUS_A_UIP != 0 && spins < UIP_SPIN_LIMIT {
+        spins += 1;
+        core::hint::spin_loop();
+    }
+    RawTime {
+        second: cmos_read(REG_SECONDS),
+        minute: cmos_read(REG_MINUTES),
+        hour: cmos_read(REG_HOURS),
+        day: cmos_read(REG_DAY),
+        month: cmos_read(REG_MONTH),
+        year: cmos_read(REG_YEAR),
+        century: cmos_read(REG_CENTURY),
+    }
+}
+
+fn bcd_to_bin(bcd: u8) -> u8 {
+    (bcd & 0x0F) + (bcd >> 4) * 10
+}
+
+/// Decode a register snapshot according to status register B.
+///
+/// A century register of 0 (absent) or outside 19..=99 is ignored and the
+/// two-digit year is taken to be in 2000..=2099.
+fn decode(raw: RawTime, status_b: u8) -> DateTime {
+    let bcd = status_b & STATUS_B_BINARY == 0;
+    let conv = |v: u8| if bcd { bcd_to_bin(v) } else { v };
+
+    let pm = status_b & STATUS_B_24H == 0 && raw.hour & HOUR_PM != 0;
+    let mut hour = conv(raw.hour & !HOUR_PM);
+    if status_b & STATUS_B_24H == 0 {
+        // 12 AM is midnight, 12 PM is noon.
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    let century = match conv(raw.century) {
+        c @ 19..=99 => c as u16,
+        _ => 20,
+    };
+
+    DateTime {
+        year: century * 100 + conv(raw.year) as u16,
+        month: conv(raw.month),
+        day: conv(raw.day),
+        hour,
+        minute: conv(raw.minute),
+        second: conv(raw.second),
+    }
+}
+
+/// Read the current date and time from the RTC.
+///
+/// Waits for any update cycle to finish, then re-reads until two
+/// consecutive snapshots agree so that a rollover between register reads
+/// (e.g. 23:59:59 -> 00:00:00) cannot produce a torn value.  Check
+/// [`DateTime::is_valid`] before trusting the result.
+pub fn read_datetime() -> DateTime {
+    let status_b = cmos_read(REG_STATUS_B);
+    let mut last = read_registers();
+    for _ in 0..MAX_READ_ATTEMPTS {
+        let next = read_registers();
+        if next == last {
+            break;
+        }
+        last = next;
+    }
+    decode(last, status_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(hour: u8, century: u8) -> RawTime {
+        RawTime {
+            second: 0x59,
+            minute: 0x30,
+            hour,
+            day: 0x29,
+            month: 0x02,
+            year: 0x24,
+            century,
+        }
+    }
+
+    #[test]
+    fn decodes_bcd_12_hour_and_century() {
+        let pm = decode(raw(HOUR_PM | 0x12, 0x20), 0);
+        assert_eq!((pm.year, pm.month, pm.day), (2024, 2, 29));
+        assert_eq!((pm.hour, pm.minute, pm.second), (12, 30, 59));
+        assert_eq!(decode(raw(0x12, 0x20), 0).hour, 0);
+        assert_eq!(decode(raw(HOUR_PM | 0x01, 0), 0).hour, 13);
+        // Missing century register falls back to 20xx.
+        assert_eq!(decode(raw(0x01, 0), STATUS_B_24H).year, 2024);
+    }
+
+    #[test]
+    fn decodes_binary_24_hour() {
+        let raw = RawTime {
+            second: 5,
+            minute: 4,
+            hour: 23,
+            day: 31,
+            month: 12,
+            year: 99,
+            century: 19,
+        };
+        let dt = decode(raw, STATUS_B_BINARY | STATUS_B_24H);
+        assert_eq!(dt.year, 1999);
+        assert_eq!(dt.hour, 23);
+    }
+
+    #[test]
+    fn unix_timestamps() {
+        let at = |year, month, day, hour, minute, second| DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        assert_eq!(at(1970, 1, 1, 0, 0, 0).unix_timestamp(), Some(0));
+        assert_eq!(at(2000, 3, 1, 0, 0, 0).unix_timestamp(), Some(951_868_800));
+        assert_eq!(
+            at(2024, 2, 29, 12, 30, 59).unix_timestamp(),
+            Some(1_709_209_859)
+        );
+        assert_eq!(at(2023, 2, 29, 0, 0, 0).unix_timestamp(), None);
+        assert_eq!(at(1969, 12, 31, 23, 59, 59).unix_timestamp(), None);
+    }
+}
@@ -0,0 +1,189 @@
+//! Time-stamp counter.
+//!
+//! [`read_tsc`] is a cycle counter with sub-microsecond resolution.
+//! [`calibrate_tsc_hz`] measures its rate against PIT channel 2 once at boot
+//! so that counts can be turned into nanoseconds with [`tsc_to_nanos`].
+//!
+//! The conversion assumes the TSC ticks at a constant rate.  That holds when
+//! the CPU advertises an invariant TSC ([`is_invariant`]; flasks asks QEMU
+//! for one with `+invtsc`).  Without it the TSC may follow frequency scaling,
+//! and intervals that span a P-state change are off by the ratio of the two
+//! frequencies.  Nothing corrects for that; such CPUs get coarse profiling
+//! numbers rather than wrong ordering, since the count still only grows.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+const PIT_CHANNEL2_DATA: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+/// System control port B: bit 0 gates channel 2, bit 1 drives the speaker.
+const PIT_GATE_PORT: u16 = 0x61;
+/// Channel 2, lobyte/hibyte access, mode 0 (count down once), binary.
+const PIT_CHANNEL2_ONESHOT: u8 = 0b1011_0000;
+/// Counter-latch command for channel 2.
+const PIT_CHANNEL2_LATCH: u8 = 0b1000_0000;
+
+/// PIT counts to measure over: 20 ms, well inside one 16-bit countdown.
+const CALIBRATION_PIT_COUNTS: u16 = 23_864;
+/// TSC ticks to wait for the PIT to start counting before concluding there
+/// is no PIT (0.5 ms at 1 GHz).
+const PIT_START_TSC_TICKS: u64 = 500_000;
+/// TSC ticks after which a calibration that has not finished is abandoned
+/// (1 s at 3 GHz).
+const CALIBRATION_TSC_TICKS: u64 = 3_000_000_000;
+/// Rates outside 100 MHz–10 GHz are measurement errors.
+const MIN_TSC_HZ: u64 = 100_000_000;
+const MAX_TSC_HZ: u64 = 10_000_000_000;
+
+/// Rate assumed until [`calibrate_tsc_hz`] has succeeded.
+pub const FALLBACK_TSC_HZ: u64 = 3_000_000_000;
+
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Current value of the time-stamp counter.
+#[inline]
+pub fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Whether the CPU reports an invariant TSC, one that ticks at a constant
+/// rate across P-, C- and T-state changes.
+pub fn is_invariant() -> bool {
+    super::cpuid::features().invariant_tsc
+}
+
+/// Measure the TSC rate against PIT channel 2 and record it for
+/// [`tsc_hz`] and [`tsc_to_nanos`].
+///
+/// Busy-waits about 20 ms.  Returns `None`, leaving any earlier result in
+/// place, if the CPU has no TSC, the PIT does not count or the result is
+/// implausible.
+pub fn calibrate_tsc_hz() -> Option<u64> {
+    if !super::cpuid::features().tsc {
+        return None;
+    }
+    let mut gate = Port::<u8>::new(PIT_GATE_PORT);
+    let saved = unsafe { gate.read() };
+    let measured = x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        // Gate low and speaker off while the channel is programmed; raising
+        // the gate starts the countdown from 0xFFFF.
+        gate.write(saved & !0x03);
+        PortWriteOnly::<u8>::new(PIT_COMMAND).write(PIT_CHANNEL2_ONESHOT);
+        let mut data = Port::<u8>::new(PIT_CHANNEL2_DATA);
+        data.write(0xFF);
+        data.write(0xFF);
+        gate.write((saved & !0x02) | 0x01);
+        let measured = measure_against_pit();
+        gate.write(saved);
+        measured
+    });
+
+    let hz = measured.filter(|hz| (MIN_TSC_HZ..=MAX_TSC_HZ).contains(hz))?;
+    TSC_HZ.store(hz, Ordering::Relaxed);
+    Some(hz)
+}
+
+fn pit_channel2_count() -> u16 {
+    unsafe {
+        PortWriteOnly::<u8>::new(PIT_COMMAND).write(PIT_CHANNEL2_LATCH);
+        let mut data = Port::<u8>::new(PIT_CHANNEL2_DATA);
+        let low = data.read();
+        let high = data.read();
+        u16::from_le_bytes([low, high])
+    }
+}
+
+/// TSC ticks per second over [`CALIBRATION_PIT_COUNTS`] of the running PIT
+/// countdown, or `None` if the PIT does not count.
+fn measure_against_pit() -> Option<u64> {
+    let wait_start = read_tsc();
+    let pit_start = pit_channel2_count();
+    let mut pit_now = pit_start;
+    // Start timing on a count edge so the partial first count is not lost.
+    while pit_now == pit_start {
+        if read_tsc().wrapping_sub(wait_start) > PIT_START_TSC_TICKS {
+            return None;
+        }
+        pit_now = pit_channel2_count();
+    }
+    let tsc_start = read_tsc();
+    let pit_start = pit_now;
+
+    loop {
+        let elapsed = pit_start.wrapping_sub(pit_channel2_count());
+        let tsc_now = read_tsc();
+        if elapsed >= CALIBRATION_PIT_COUNTS {
+            return Some(rate_from_pit_counts(
+                tsc_now.wrapping_sub(tsc_start),
+                elapsed,
+            ));
+        }
+        if tsc_now.wrapping_sub(tsc_start) > CALIBRATION_TSC_TICKS {
+            return None;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// TSC rate given `ticks` counted over `pit_counts` PIT clocks.
+fn rate_from_pit_counts(ticks: u64, pit_counts: u16) -> u64 {
+    (u128::from(ticks) * u128::from(PIT_FREQUENCY_HZ) / u128::from(pit_counts.max(1))) as u64
+}
+
+/// Whether [`calibrate_tsc_hz`] has succeeded.
+pub fn is_calibrated() -> bool {
+    TSC_HZ.load(Ordering::Relaxed) != 0
+}
+
+/// Calibrated TSC rate, or [`FALLBACK_TSC_HZ`] before calibration.
+pub fn tsc_hz() -> u64 {
+    match TSC_HZ.load(Ordering::Relaxed) {
+        0 => FALLBACK_TSC_HZ,
+        hz => hz,
+    }
+}
+
+/// Nanoseconds that `ticks` TSC ticks last at `hz`.
+pub fn ticks_to_nanos(ticks: u64, hz: u64) -> u64 {
+    let nanos = u128::from(ticks) * 1_000_000_000 / u128::from(hz.max(1));
+    u64::try_from(nanos).unwrap_or(u64::MAX)
+}
+
+/// Nanoseconds that `ticks` TSC ticks last at the rate from [`tsc_hz`].
+pub fn tsc_to_nanos(ticks: u64) -> u64 {
+    ticks_to_nanos(ticks, tsc_hz())
+}
+
+/// Nanoseconds since the TSC was last reset, normally at power-on.
+pub fn monotonic_nanos() -> u64 {
+    tsc_to_nanos(read_tsc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_ticks_to_nanoseconds() {
+        assert_eq!(ticks_to_nanos(3_000_000_000, 3_000_000_000), 1_000_000_000);
+        assert_eq!(ticks_to_nanos(3, 3_000_000_000), 1);
+        assert_eq!(ticks_to_nanos(2_500, 2_500_000_000), 1_000);
+        // Uptimes far beyond the ~6 s a naive u64 product allows at 3 GHz.
+        let year_of_ticks = 3_000_000_000 * 365 * 24 * 3600;
+        assert_eq!(
+            ticks_to_nanos(year_of_ticks, 3_000_000_000),
+            365 * 24 * 3600 * 1_000_000_000
+        );
+        assert_eq!(ticks_to_nanos(u64::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn derives_the_rate_from_pit_counts() {
+        // 20 ms of PIT counts at 2 GHz.
+        let rate = rate_from_pit_counts(40_000_000, CALIBRATION_PIT_COUNTS);
+        assert!(rate.abs_diff(2_000_000_000) < 2_000_000 / 20, "{rate}");
+        // A PIT that never advanced must not divide by zero.
+        assert_eq!(rate_from_pit_counts(1_000, 0), 1_000 * PIT_FREQUENCY_HZ);
+    }
+}
@@ -166,6 +166,19 @@ pub trait HardwareDevice: Initializable + ErrorLogging {
     }
 }
 
+/// Storage addressed in fixed-size logical blocks.
+///
+/// Out-of-range block numbers and buffers shorter than `block_size()` are
+/// rejected with `SystemError::InvalidArgument`.
+pub trait BlockDevice: Send + Sync {
+    /// Read block `lba` into the first `block_size()` bytes of `buf`.
+    fn read_block(&self, lba: u64, buf: &mut [u8]) -> SystemResult<()>;
+    /// Write the first `block_size()` bytes of `buf` to block `lba`.
+    fn write_block(&self, lba: u64, buf: &[u8]) -> SystemResult<()>;
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u64;
+}
+
 // Re-export ErrorLogging from the logging module to avoid duplication
 pub use crate::common::logging::ErrorLogging;
 
@@ -131,6 +131,7 @@ impl HardwarePorts {
     pub const CRTC_DATA: u16 = 0x3D5;
     pub const STATUS: u16 = 0x3DA;
     pub const ATTRIBUTE_INDEX: u16 = 0x3C0;
+    pub const DAC_MASK: u16 = 0x3C6;
     pub const DAC_INDEX: u16 = 0x3C8;
     pub const DAC_DATA: u16 = 0x3C9;
     pub const GRAPHICS_INDEX: u16 = 0x3CE;
@@ -13,6 +13,7 @@ pub mod debug;
 pub mod error;
 pub mod filesystem;
 pub mod graphics;
+pub mod hardware;
 pub mod initializer;
 pub mod io;
 pub mod page_table;
@@ -53,19 +54,18 @@ pub use page_table::allocator::{BitmapFrameAllocator, bitmap};
 #[cfg(not(feature = "std"))]
 pub use page_table::heap::ALLOCATOR;
 pub use page_table::heap::HeapStats;
+#[cfg(not(feature = "std"))]
+pub use page_table::heap::TrackingHeap;
 pub use page_table::heap::allocate_heap_from_map;
 pub use page_table::heap::extend_global_heap;
 pub use page_table::heap::heap_stats;
 pub use page_table::heap::heap_top;
 pub use page_table::heap::init_global_heap;
 pub use page_table::page_buf::PageBuf;
+pub use page_table::slab::{SlabBox, SlabCache};
 
 use crate::common::EfiSystemTable;
-use crate::common::uefi::FullereneFramebufferConfig;
-use spin::{Mutex, Once};
-
-pub static FULLERENE_FRAMEBUFFER_CONFIG: Once<Mutex<Option<FullereneFramebufferConfig>>> =
-    Once::new();
+use spin::Mutex;
 
 #[derive(Clone, Copy)]
 pub struct UefiSystemTablePtr(pub *mut EfiSystemTable);
@@ -141,8 +141,10 @@ macro_rules! define_alloc_error_handler {
         #[cfg(all(any(target_os = "none", target_os = "uefi"), not(test)))]
         #[alloc_error_handler]
         fn alloc_error_handler(layout: core::alloc::Layout) -> ! {
-            $crate::serial::_print(format_args!("ALLOC ERROR: {:?}\n", layout));
-            loop {}
+            $crate::page_table::heap::report_alloc_error(layout);
+            loop {
+                x86_64::instructions::hlt();
+            }
         }
     };
 }
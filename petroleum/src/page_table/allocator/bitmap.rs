@@ -1,13 +1,27 @@
 use crate::page_table::allocator::traits::{FrameAllocator, FrameAllocatorExt};
-use crate::page_table::memory_map::MemoryDescriptorValidator;
+use crate::page_table::memory_map::{MemoryDescriptorValidator, MemoryMap, PhysicalRegion};
 use crate::page_table::types::PhysFrame;
 use x86_64::structures::paging::{
     FrameAllocator as X86FrameAllocator, PhysFrame as X86PhysFrame, Size4KiB,
 };
 
+/// Frames below 16 MiB are never handed out for contiguous allocations:
+///   - IVT/BDA (0x00000-0x00FFF)
+///   - BIOS/bootloader data (0x05000-0x9FFFF)
+///   - VGA/ROM regions (0xA0000-0xFFFFF)
+///   - DMA-safe buffer must be in conventional RAM, not reserved/firmware areas
+///   - Some QEMU/UEFI configurations leave low memory for legacy compatibility
+const LOW_MEM_SKIP_FRAMES: usize = 16 * 1024 * 1024 / 4096; // 4096 frames = 16MB
+
+/// Most references a single frame can carry.
+pub const MAX_FRAME_REFS: u32 = u8::MAX as u32 + 1;
+
 pub struct BitmapFrameAllocator {
     bitmap: alloc::vec::Vec<u64>,
     total_frames: usize,
+    /// References held on each frame beyond its owner's, indexed like the
+    /// bitmap.  Only meaningful for used frames; 0 means a single owner.
+    extra_refs: alloc::vec::Vec<u8>,
 }
 
 impl BitmapFrameAllocator {
@@ -16,6 +30,7 @@ impl BitmapFrameAllocator {
         Self {
             bitmap: alloc::vec::Vec::with_capacity(bitmap_size),
             total_frames,
+            extra_refs: alloc::vec![0; total_frames],
         }
     }
 
@@ -26,59 +41,123 @@ impl BitmapFrameAllocator {
         }
     }
 
+    /// An allocator whose free frames are the conventional memory of
+    /// `memory_map`, less anything a reserved or runtime region overlaps.
     pub fn init_with_memory_map<T: MemoryDescriptorValidator>(memory_map: &[T]) -> Self {
-        let mut max_phys = 0u64;
-        for desc in memory_map {
-            let end = desc.get_physical_start() + desc.get_page_count() * 4096;
-            if end > max_phys {
-                max_phys = end;
-            }
-        }
-        let total_frames = ((max_phys + 4095) / 4096) as usize;
+        let map = MemoryMap::new(memory_map);
+        let max_phys = map.regions().map(|region| region.end).max().unwrap_or(0);
+        let total_frames = max_phys.div_ceil(4096) as usize;
         let mut allocator = Self::new(total_frames);
         allocator
             .bitmap
             .resize(allocator.bitmap.capacity(), u64::MAX);
 
-        for desc in memory_map {
-            if desc.get_type() == crate::common::EfiMemoryType::EfiConventionalMemory as u32 {
-                let start_frame = (desc.get_physical_start() / 4096) as usize;
-                let end_frame =
-                    ((desc.get_physical_start() + desc.get_page_count() * 4096) / 4096) as usize;
-                allocator.set_frame_range(start_frame, end_frame, false);
-            }
+        let frames = |region: PhysicalRegion| (region.start / 4096, region.end / 4096);
+        for (start, end) in map
+            .iter_usable()
+            .filter(|region| {
+                region.mem_type == crate::common::EfiMemoryType::EfiConventionalMemory as u32
+            })
+            .map(frames)
+        {
+            allocator.set_frame_range(start as usize, end as usize, false);
+        }
+        for (start, end) in map.iter_reserved().map(frames) {
+            allocator.set_frame_range(start as usize, end as usize, true);
         }
         allocator
     }
 
+    /// Allocate `pages` physically contiguous frames above 16 MiB.
+    ///
+    /// Uses best fit: the smallest free run that can hold the request is
+    /// taken, so large runs are not chipped away by small allocations.
     pub fn allocate_contiguous_frames(
         &mut self,
         pages: usize,
     ) -> crate::common::logging::SystemResult<u64> {
-        let mut count = 0;
-        // Skip low memory (<16MB) to avoid:
-        //   - IVT/BDA (0x00000-0x00FFF)
-        //   - BIOS/bootloader data (0x05000-0x9FFFF)
-        //   - VGA/ROM regions (0xA0000-0xFFFFF)
-        //   - DMA-safe buffer must be in conventional RAM, not reserved/firmware areas
-        //   - Some QEMU/UEFI configurations leave low memory for legacy compatibility
-        // Using 16MB boundary to ensure we're well above all low-memory regions.
-        const LOW_MEM_SKIP_FRAMES: usize = 16 * 1024 * 1024 / 4096; // 4096 frames = 16MB
-        let mut start = LOW_MEM_SKIP_FRAMES;
-        for i in LOW_MEM_SKIP_FRAMES..self.total_frames {
-            if !self.is_frame_available(i) {
-                count = 0;
-                start = i + 1;
-            } else if count + 1 == pages {
-                for j in start..=i {
-                    self.set_frame_used(j, true);
+        if pages == 0 {
+            return Err(crate::common::logging::SystemError::InvalidArgument);
+        }
+        let mut best: Option<(usize, usize)> = None;
+        self.for_each_free_run(LOW_MEM_SKIP_FRAMES, |start, len| {
+            if len >= pages && best.is_none_or(|(_, best_len)| len < best_len) {
+                best = Some((start, len));
+            }
+            // An exact fit cannot be beaten.
+            len != pages
+        });
+
+        match best {
+            Some((start, _)) => {
+                self.set_frame_range(start, start + pages, true);
+                Ok(start as u64 * 4096)
+            }
+            None => {
+                // Frames cannot be relocated, so all we can do is report how
+                // badly free memory is fragmented.
+                log::warn!(
+                    "frame allocator: no run of {} contiguous frames ({} free, largest run {}, fragmentation {:.2}); compaction needed",
+                    pages,
+                    self.available_frames(),
+                    self.largest_free_run(),
+                    self.fragmentation_ratio()
+                );
+                Err(crate::common::logging::SystemError::FrameAllocationFailed)
+            }
+        }
+    }
+
+    /// Length in frames of the longest run of free frames.
+    pub fn largest_free_run(&self) -> usize {
+        let mut largest = 0;
+        self.for_each_free_run(0, |_, len| {
+            largest = largest.max(len);
+            true
+        });
+        largest
+    }
+
+    /// Share of free memory outside the largest free run: `0.0` when all
+    /// free frames are contiguous (or none are free), approaching `1.0` as
+    /// free memory splinters into small runs.
+    pub fn fragmentation_ratio(&self) -> f32 {
+        let free = self.available_frames();
+        if free == 0 {
+            return 0.0;
+        }
+        1.0 - self.largest_free_run() as f32 / free as f32
+    }
+
+    /// Call `f(start, len)` for every maximal run of free frames at or after
+    /// `from`, stopping early when `f` returns `false`.
+    fn for_each_free_run(&self, from: usize, mut f: impl FnMut(usize, usize) -> bool) {
+        let mut frame = from;
+        let mut run_start = None;
+        while frame < self.total_frames {
+            // Skip fully used words while outside a run.
+            if run_start.is_none()
+                && frame.is_multiple_of(64)
+                && self.bitmap[frame / 64] == u64::MAX
+            {
+                frame += 64;
+                continue;
+            }
+            match (self.is_frame_available(frame), run_start) {
+                (true, None) => run_start = Some(frame),
+                (false, Some(start)) => {
+                    run_start = None;
+                    if !f(start, frame - start) {
+                        return;
+                    }
                 }
-                return Ok(start as u64 * 4096);
-            } else {
-                count += 1;
+                _ => {}
             }
+            frame += 1;
+        }
+        if let Some(start) = run_start {
+            f(start, self.total_frames - start);
         }
-        Err(crate::common::logging::SystemError::FrameAllocationFailed)
     }
 
     pub fn available_frames(&self) -> usize {
@@ -104,18 +183,68 @@ impl BitmapFrameAllocator {
         (self.bitmap[idx] & (1 << bit)) == 0
     }
 
+    /// Drop one reference to `frame`, returning it to the pool once the
+    /// last reference is gone.
     pub fn free_frame(&mut self, frame: X86PhysFrame) {
-        let phys_addr = frame.start_address().as_u64();
+        self.dec_ref(frame.start_address().as_u64());
+    }
+
+    /// Take an additional reference to the allocated frame at `phys_addr`
+    /// so it can be mapped into more than one address space, returning the
+    /// new count.
+    ///
+    /// Fails with [`SystemError::InvalidArgument`] if the frame is free or
+    /// out of range, and with [`SystemError::MemOutOfMemory`] once the
+    /// frame already has [`MAX_FRAME_REFS`] references.
+    ///
+    /// [`SystemError::InvalidArgument`]: crate::common::logging::SystemError::InvalidArgument
+    /// [`SystemError::MemOutOfMemory`]: crate::common::logging::SystemError::MemOutOfMemory
+    pub fn inc_ref(&mut self, phys_addr: u64) -> crate::common::logging::SystemResult<u32> {
         let frame_idx = (phys_addr / 4096) as usize;
-        if frame_idx < self.total_frames {
-            self.set_frame_used(frame_idx, false);
+        if frame_idx >= self.total_frames || self.is_frame_available(frame_idx) {
+            return Err(crate::common::logging::SystemError::InvalidArgument);
         }
+        let extra = &mut self.extra_refs[frame_idx];
+        *extra = extra
+            .checked_add(1)
+            .ok_or(crate::common::logging::SystemError::MemOutOfMemory)?;
+        Ok(*extra as u32 + 1)
     }
 
+    /// Drop one reference to the frame at `phys_addr`, returning the number
+    /// left.  The frame goes back to the pool when that reaches 0; free or
+    /// out-of-range frames are left alone and report 0.
+    pub fn dec_ref(&mut self, phys_addr: u64) -> u32 {
+        let frame_idx = (phys_addr / 4096) as usize;
+        if frame_idx >= self.total_frames || self.is_frame_available(frame_idx) {
+            return 0;
+        }
+        match self.extra_refs[frame_idx] {
+            0 => {
+                self.set_frame_used(frame_idx, false);
+                0
+            }
+            extra => {
+                self.extra_refs[frame_idx] = extra - 1;
+                extra as u32
+            }
+        }
+    }
+
+    /// Number of references held on `frame` (0 if it is free).
+    pub fn frame_refcount(&self, frame: X86PhysFrame) -> u32 {
+        let frame_idx = (frame.start_address().as_u64() / 4096) as usize;
+        if frame_idx >= self.total_frames || self.is_frame_available(frame_idx) {
+            return 0;
+        }
+        self.extra_refs[frame_idx] as u32 + 1
+    }
+
+    /// Drop one reference to each of `pages` frames starting at
+    /// `start_phys`.
     pub fn free_contiguous_frames(&mut self, start_phys: u64, pages: usize) {
-        let start_frame = (start_phys / 4096) as usize;
-        for i in 0..pages {
-            self.set_frame_used(start_frame + i, false);
+        for i in 0..pages as u64 {
+            self.dec_ref(start_phys + i * 4096);
         }
     }
 
@@ -189,10 +318,7 @@ impl FrameAllocator for BitmapFrameAllocator {
     }
 
     fn deallocate(&mut self, frame: PhysFrame) {
-        let frame_idx = (frame.start_address() / 4096) as usize;
-        if frame_idx < self.total_frames {
-            self.set_frame_used(frame_idx, false);
-        }
+        self.dec_ref(frame.start_address());
     }
 
     fn is_initialized(&self) -> bool {
@@ -223,6 +349,7 @@ impl FrameAllocatorExt for BitmapFrameAllocator {
             self.bitmap[idx] |= 1 << bit;
         } else {
             self.bitmap[idx] &= !(1 << bit);
+            self.extra_refs[frame] = 0;
         }
     }
 
@@ -258,3 +385,111 @@ unsafe impl X86FrameAllocator<Size4KiB> for BitmapFrameAllocator {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOTAL: usize = LOW_MEM_SKIP_FRAMES + 256;
+
+    fn allocator() -> BitmapFrameAllocator {
+        let mut allocator = BitmapFrameAllocator::new(TOTAL);
+        allocator.init(LOW_MEM_SKIP_FRAMES);
+        allocator
+    }
+
+    fn frame_of(phys: u64) -> usize {
+        (phys / 4096) as usize
+    }
+
+    #[test]
+    fn contiguous_allocation_prefers_the_smallest_fitting_run() {
+        let mut allocator = allocator();
+        let base = LOW_MEM_SKIP_FRAMES;
+        // Free runs: [base, base+8) and [base+16, base+20), rest of memory after base+24.
+        allocator.set_frame_range(base + 8, base + 16, true);
+        allocator.set_frame_range(base + 20, base + 24, true);
+
+        let phys = allocator.allocate_contiguous_frames(4).unwrap();
+        assert_eq!(frame_of(phys), base + 16);
+        let phys = allocator.allocate_contiguous_frames(6).unwrap();
+        assert_eq!(frame_of(phys), base);
+    }
+
+    #[test]
+    fn large_run_survives_interleaved_alloc_free_cycles() {
+        let mut allocator = allocator();
+        let mut blocks = alloc::vec::Vec::new();
+        for _ in 0..32 {
+            blocks.push(allocator.allocate_contiguous_frames(2).unwrap());
+        }
+        // Free every other block, leaving 2-frame holes.
+        for phys in blocks.iter().step_by(2) {
+            allocator.free_contiguous_frames(*phys, 2);
+        }
+        for _ in 0..16 {
+            allocator.allocate_contiguous_frames(2).unwrap();
+        }
+        // The small requests refilled the holes instead of eating the tail.
+        assert_eq!(allocator.largest_free_run(), 256 - 64);
+        assert!(allocator.allocate_contiguous_frames(128).is_ok());
+    }
+
+    #[test]
+    fn fragmentation_diagnostics() {
+        let mut allocator = allocator();
+        assert_eq!(allocator.largest_free_run(), 256);
+        assert_eq!(allocator.fragmentation_ratio(), 0.0);
+
+        // Punch a used frame every 8 frames: runs of 7 free frames.
+        for i in (LOW_MEM_SKIP_FRAMES..TOTAL).step_by(8) {
+            allocator.set_frame_used(i, true);
+        }
+        assert_eq!(allocator.largest_free_run(), 7);
+        assert_eq!(allocator.available_frames(), 224);
+        assert!(allocator.fragmentation_ratio() > 0.9);
+        assert!(allocator.allocate_contiguous_frames(8).is_err());
+        assert!(allocator.allocate_contiguous_frames(0).is_err());
+    }
+
+    #[test]
+    fn shared_frame_is_freed_only_by_its_last_reference() {
+        let mut allocator = allocator();
+        let frame = allocator.allocate_frame().unwrap();
+        assert_eq!(allocator.inc_ref(frame.start_address().as_u64()), Ok(2));
+        assert_eq!(allocator.frame_refcount(frame), 2);
+
+        allocator.free_frame(frame);
+        assert_eq!(allocator.frame_refcount(frame), 1);
+        assert!(!allocator.is_frame_available(frame_of(frame.start_address().as_u64())));
+
+        allocator.free_frame(frame);
+        assert_eq!(allocator.frame_refcount(frame), 0);
+        assert!(allocator.is_frame_available(frame_of(frame.start_address().as_u64())));
+    }
+
+    #[test]
+    fn refcounts_saturate_and_reject_free_frames() {
+        let mut allocator = allocator();
+        let phys = allocator.allocate_contiguous_frames(2).unwrap();
+        for expected in 2..=MAX_FRAME_REFS {
+            assert_eq!(allocator.inc_ref(phys), Ok(expected));
+        }
+        assert_eq!(
+            allocator.inc_ref(phys),
+            Err(crate::common::logging::SystemError::MemOutOfMemory)
+        );
+        assert_eq!(allocator.dec_ref(phys), MAX_FRAME_REFS - 1);
+
+        // Freeing the run drops one reference per frame: only the unshared
+        // second frame is released.
+        allocator.free_contiguous_frames(phys, 2);
+        assert!(!allocator.is_frame_available(frame_of(phys)));
+        assert!(allocator.is_frame_available(frame_of(phys) + 1));
+        assert_eq!(
+            allocator.inc_ref(phys + 4096),
+            Err(crate::common::logging::SystemError::InvalidArgument)
+        );
+        assert_eq!(allocator.dec_ref(phys + 4096), 0);
+    }
+}
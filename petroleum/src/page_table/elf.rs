@@ -0,0 +1,431 @@
+//! ELF64 kernel loading for bellows.
+//!
+//! The counterpart of [`super::pe::load_efi_image`] for kernels built for
+//! `x86_64-unknown-none`.  PT_LOAD segments are copied into one contiguous
+//! allocation laid out like their `p_vaddr`s, and the image runs at the
+//! higher-half alias of that allocation, exactly as a PE kernel does.  The
+//! load bias therefore comes from the same [`super::pe::load_bias`], and
+//! PIE kernels are fixed up through their `R_X86_64_RELATIVE` entries.
+
+use super::pe::{allocate_image, load_bias};
+use crate::common::{BellowsError, EfiSystemTable};
+use core::ffi::c_void;
+use x86_64::PhysAddr;
+
+pub const ELF_MAGIC: [u8; 4] = *b"\x7FELF";
+
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 62;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+
+const DT_NULL: i64 = 0;
+const DT_RELA: i64 = 7;
+const DT_RELASZ: i64 = 8;
+const DT_RELAENT: i64 = 9;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_RELATIVE: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Ehdr {
+    pub e_ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Phdr {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Elf64Dyn {
+    d_tag: i64,
+    d_val: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Elf64Rela {
+    r_offset: u64,
+    r_info: u64,
+    r_addend: i64,
+}
+
+/// Whether `file` starts with the ELF magic rather than `MZ`.
+pub fn is_elf(file: &[u8]) -> bool {
+    file.starts_with(&ELF_MAGIC)
+}
+
+/// Read a `T` at `offset` in `bytes`, if it fits.
+fn read<T: Copy>(bytes: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(core::mem::size_of::<T>())?;
+    if end > bytes.len() {
+        return None;
+    }
+    Some(unsafe { core::ptr::read_unaligned(bytes.as_ptr().add(offset) as *const T) })
+}
+
+/// Where the PT_LOAD segments of a validated kernel ELF want to live.
+#[derive(Debug, Clone, Copy)]
+struct ElfLayout {
+    header: Elf64Ehdr,
+    /// Lowest PT_LOAD address, rounded down to a page.
+    link_base: u64,
+    /// Bytes from `link_base` to the end of the highest segment, rounded up
+    /// to a whole page.
+    size: u64,
+}
+
+impl ElfLayout {
+    fn parse(file: &[u8]) -> Result<Self, BellowsError> {
+        let header: Elf64Ehdr =
+            read(file, 0).ok_or(BellowsError::ElfParse("ELF header truncated"))?;
+        if header.e_ident[..4] != ELF_MAGIC
+            || header.e_ident[4] != ELFCLASS64
+            || header.e_ident[5] != ELFDATA2LSB
+            || header.e_machine != EM_X86_64
+        {
+            return Err(BellowsError::ElfParse("Not an x86_64 ELF64 image"));
+        }
+        if header.e_type != ET_EXEC && header.e_type != ET_DYN {
+            return Err(BellowsError::ElfParse("ELF image is not executable"));
+        }
+        if (header.e_phentsize as usize) < core::mem::size_of::<Elf64Phdr>() {
+            return Err(BellowsError::ElfParse("Bad program header size"));
+        }
+
+        let mut low = u64::MAX;
+        let mut high = 0u64;
+        for phdr in program_headers(file, &header) {
+            let phdr = phdr?;
+            if phdr.p_type != PT_LOAD || phdr.p_memsz == 0 {
+                continue;
+            }
+            let file_end = phdr.p_offset.checked_add(phdr.p_filesz);
+            let mem_end = phdr.p_vaddr.checked_add(phdr.p_memsz);
+            if phdr.p_filesz > phdr.p_memsz
+                || file_end.is_none_or(|end| end > file.len() as u64)
+                || mem_end.is_none()
+            {
+                return Err(BellowsError::ElfParse("Segment data out of bounds."));
+            }
+            low = low.min(phdr.p_vaddr);
+            high = high.max(phdr.p_vaddr + phdr.p_memsz);
+        }
+        if low > high {
+            return Err(BellowsError::ElfParse("ELF image has no PT_LOAD segments"));
+        }
+
+        let link_base = low & !0xFFF;
+        let size = (high - link_base).div_ceil(4096) * 4096;
+        if !(link_base..link_base + size).contains(&header.e_entry) {
+            return Err(BellowsError::ElfParse(
+                "Entry point address is outside the loaded segments.",
+            ));
+        }
+        Ok(Self {
+            header,
+            link_base,
+            size,
+        })
+    }
+
+    /// Copy every PT_LOAD segment into `image` (which starts at
+    /// `link_base`) and zero its `.bss` tail.
+    fn place_segments(&self, file: &[u8], image: &mut [u8]) -> Result<(), BellowsError> {
+        for phdr in program_headers(file, &self.header) {
+            let phdr = phdr?;
+            if phdr.p_type != PT_LOAD || phdr.p_memsz == 0 {
+                continue;
+            }
+            let start = (phdr.p_vaddr - self.link_base) as usize;
+            let file_size = phdr.p_filesz as usize;
+            let mem_size = phdr.p_memsz as usize;
+            let src = phdr.p_offset as usize;
+            image[start..start + file_size].copy_from_slice(&file[src..src + file_size]);
+            image[start + file_size..start + mem_size].fill(0);
+        }
+        Ok(())
+    }
+
+    /// Apply the RELA table from PT_DYNAMIC to the placed `image`.
+    ///
+    /// RELA entries carry their addend, so the table is applied even for a
+    /// zero bias; the linker is free to leave the targets themselves blank.
+    fn relocate(&self, file: &[u8], image: &mut [u8], bias: i64) -> Result<(), BellowsError> {
+        let Some(dynamic) = program_headers(file, &self.header)
+            .filter_map(Result::ok)
+            .find(|phdr| phdr.p_type == PT_DYNAMIC)
+        else {
+            return Ok(());
+        };
+
+        let (mut rela, mut rela_size, mut rela_ent) = (None, 0u64, 0u64);
+        let mut offset = self.image_offset(dynamic.p_vaddr, image)?;
+        let end = offset.saturating_add(dynamic.p_memsz as usize);
+        while offset < end {
+            let Some(entry) = read::<Elf64Dyn>(image, offset) else {
+                break;
+            };
+            match entry.d_tag {
+                DT_NULL => break,
+                DT_RELA => rela = Some(entry.d_val),
+                DT_RELASZ => rela_size = entry.d_val,
+                DT_RELAENT => rela_ent = entry.d_val,
+                _ => {}
+            }
+            offset += core::mem::size_of::<Elf64Dyn>();
+        }
+        let Some(rela) = rela else {
+            return Ok(());
+        };
+        if (rela_ent as usize) < core::mem::size_of::<Elf64Rela>() {
+            return Err(BellowsError::ElfParse("Bad relocation entry size"));
+        }
+
+        let table = self.image_offset(rela, image)?;
+        for index in 0..(rela_size / rela_ent) as usize {
+            let entry: Elf64Rela = read(image, table + index * rela_ent as usize)
+                .ok_or(BellowsError::ElfParse("Relocation table out of bounds."))?;
+            match entry.r_info as u32 {
+                R_X86_64_NONE => {}
+                R_X86_64_RELATIVE => {
+                    let target = self.image_offset(entry.r_offset, image)?;
+                    let value = bias.wrapping_add(entry.r_addend) as u64;
+                    image
+                        .get_mut(target..target + 8)
+                        .ok_or(BellowsError::ElfParse("Relocation target out of bounds."))?
+                        .copy_from_slice(&value.to_le_bytes());
+                }
+                _ => {
+                    return Err(BellowsError::ElfParse(
+                        "Unsupported relocation type; only R_X86_64_RELATIVE is handled",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Offset into the placed image of the link-time address `vaddr`.
+    fn image_offset(&self, vaddr: u64, image: &[u8]) -> Result<usize, BellowsError> {
+        vaddr
+            .checked_sub(self.link_base)
+            .filter(|&offset| offset < image.len() as u64)
+            .map(|offset| offset as usize)
+            .ok_or(BellowsError::ElfParse(
+                "Address outside the loaded segments.",
+            ))
+    }
+}
+
+fn program_headers<'a>(
+    file: &'a [u8],
+    header: &Elf64Ehdr,
+) -> impl Iterator<Item = Result<Elf64Phdr, BellowsError>> + 'a {
+    let base = header.e_phoff as usize;
+    let stride = header.e_phentsize as usize;
+    (0..header.e_phnum as usize).map(move |index| {
+        base.checked_add(index * stride)
+            .and_then(|offset| read(file, offset))
+            .ok_or(BellowsError::ElfParse("Program header out of bounds."))
+    })
+}
+
+/// Load an ELF64 kernel from file data.
+///
+/// Returns the same triple as [`super::pe::load_efi_image`]: the physical
+/// base of the image, the physical entry point and the entry point at its
+/// higher-half address.  A non-PIE (`ET_EXEC`) kernel cannot be moved, so
+/// it is rejected unless it happens to land where it was linked.
+pub fn load_elf_image(
+    st: &EfiSystemTable,
+    file: &[u8],
+    phys_offset: usize,
+) -> Result<
+    (
+        PhysAddr,
+        u64,
+        extern "efiapi" fn(usize, *mut EfiSystemTable, *mut c_void, usize) -> !,
+    ),
+    BellowsError,
+> {
+    let bs = unsafe { &*st.boot_services };
+    let layout = ElfLayout::parse(file)?;
+    let pages_needed = (layout.size / 4096) as usize;
+
+    let phys_addr = allocate_image(bs, pages_needed, layout.link_base as usize)?;
+    let image =
+        unsafe { core::slice::from_raw_parts_mut(phys_addr as *mut u8, pages_needed * 4096) };
+
+    let bias = load_bias(phys_offset, phys_addr, layout.link_base);
+    let result = if layout.header.e_type == ET_EXEC && bias != 0 {
+        Err(BellowsError::ElfParse(
+            "Non-PIE ELF kernel cannot run away from its link address",
+        ))
+    } else {
+        layout
+            .place_segments(file, image)
+            .and_then(|()| layout.relocate(file, image, bias))
+    };
+    if let Err(err) = result {
+        (bs.free_pages)(phys_addr, pages_needed);
+        return Err(err);
+    }
+
+    let entry_point_phys = phys_addr + (layout.header.e_entry - layout.link_base) as usize;
+    let entry_point_virt = phys_offset + entry_point_phys;
+    let entry: extern "efiapi" fn(usize, *mut EfiSystemTable, *mut c_void, usize) -> ! =
+        unsafe { core::mem::transmute(entry_point_virt) };
+    Ok((
+        PhysAddr::new(phys_addr as u64),
+        entry_point_phys as u64,
+        entry,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn put<T: Copy>(buf: &mut Vec<u8>, offset: usize, value: T) {
+        let size = core::mem::size_of::<T>();
+        if buf.len() < offset + size {
+            buf.resize(offset + size, 0);
+        }
+        unsafe { core::ptr::write_unaligned(buf.as_mut_ptr().add(offset) as *mut T, value) };
+    }
+
+    /// A PIE linked at 0: one PT_LOAD covering the whole file plus 0x100
+    /// bytes of .bss, a PT_DYNAMIC at 0x200 and one RELATIVE slot at 0x300.
+    fn pie_image() -> Vec<u8> {
+        let mut file = vec![0u8; 0x400];
+        let mut ident = [0u8; 16];
+        ident[..4].copy_from_slice(&ELF_MAGIC);
+        ident[4] = ELFCLASS64;
+        ident[5] = ELFDATA2LSB;
+        put(
+            &mut file,
+            0,
+            Elf64Ehdr {
+                e_ident: ident,
+                e_type: ET_DYN,
+                e_machine: EM_X86_64,
+                e_version: 1,
+                e_entry: 0x80,
+                e_phoff: 64,
+                e_shoff: 0,
+                e_flags: 0,
+                e_ehsize: 64,
+                e_phentsize: 56,
+                e_phnum: 2,
+                e_shentsize: 0,
+                e_shnum: 0,
+                e_shstrndx: 0,
+            },
+        );
+        let phdr = |p_type, offset, size, memsz| Elf64Phdr {
+            p_type,
+            p_flags: 0,
+            p_offset: offset,
+            p_vaddr: offset,
+            p_paddr: offset,
+            p_filesz: size,
+            p_memsz: memsz,
+            p_align: 0x1000,
+        };
+        put(&mut file, 64, phdr(PT_LOAD, 0, 0x400, 0x500));
+        put(&mut file, 120, phdr(PT_DYNAMIC, 0x200, 0x40, 0x40));
+        let dynamic = [
+            (DT_RELA, 0x280),
+            (DT_RELASZ, 24),
+            (DT_RELAENT, 24),
+            (DT_NULL, 0),
+        ];
+        for (index, (d_tag, d_val)) in dynamic.into_iter().enumerate() {
+            put(&mut file, 0x200 + index * 16, Elf64Dyn { d_tag, d_val });
+        }
+        put(
+            &mut file,
+            0x280,
+            Elf64Rela {
+                r_offset: 0x300,
+                r_info: R_X86_64_RELATIVE as u64,
+                r_addend: 0x80,
+            },
+        );
+        file
+    }
+
+    #[test]
+    fn pie_segments_are_placed_and_relocated() {
+        let file = pie_image();
+        assert!(is_elf(&file));
+        let layout = ElfLayout::parse(&file).unwrap();
+        assert_eq!((layout.link_base, layout.size), (0, 0x1000));
+
+        let mut image = vec![0xAAu8; layout.size as usize];
+        layout.place_segments(&file, &mut image).unwrap();
+        assert_eq!(&image[..4], b"\x7FELF");
+        assert!(image[0x400..0x500].iter().all(|&byte| byte == 0));
+        // Past the segment is left to the allocator, which zeroes it.
+        assert_eq!(image[0x500], 0xAA);
+
+        let bias = load_bias(0xFFFF_8000_0000_0000, 0x20_0000, layout.link_base);
+        layout.relocate(&file, &mut image, bias).unwrap();
+        let slot = u64::from_le_bytes(image[0x300..0x308].try_into().unwrap());
+        assert_eq!(slot, 0xFFFF_8000_0020_0080);
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        let mut file = pie_image();
+        put(&mut file, 18, 183u16); // EM_AARCH64
+        assert!(ElfLayout::parse(&file).is_err());
+
+        let mut file = pie_image();
+        put(&mut file, 24, 0x2000u64); // entry beyond the segments
+        assert!(ElfLayout::parse(&file).is_err());
+
+        let mut file = pie_image();
+        put(&mut file, 64 + 32, 0x800u64); // p_filesz past the end of file
+        assert!(ElfLayout::parse(&file).is_err());
+
+        let file = pie_image();
+        let layout = ElfLayout::parse(&file).unwrap();
+        let mut image = vec![0u8; layout.size as usize];
+        layout.place_segments(&file, &mut image).unwrap();
+        let mut bad = image.clone();
+        bad[0x288..0x290].copy_from_slice(&1u64.to_le_bytes()); // R_X86_64_64
+        assert!(layout.relocate(&file, &mut bad, 0).is_err());
+    }
+}
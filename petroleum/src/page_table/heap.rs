@@ -5,7 +5,8 @@
 //! exit_boot_services.
 
 use crate::page_table::memory_map::descriptor::MemoryMapDescriptor;
-use core::sync::atomic::AtomicBool;
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use x86_64::PhysAddr;
 
 /// Maximum number of memory map descriptors
@@ -29,13 +30,108 @@ pub static HEAP_INITIALIZED: AtomicBool = AtomicBool::new(false);
 /// Global heap allocator instance
 #[cfg(all(not(feature = "std"), not(test)))]
 #[global_allocator]
-pub static ALLOCATOR: linked_list_allocator::LockedHeap =
-    linked_list_allocator::LockedHeap::empty();
+pub static ALLOCATOR: TrackingHeap = TrackingHeap::empty();
 
 /// Global heap allocator instance (test environment)
 #[cfg(all(not(feature = "std"), test))]
-pub static ALLOCATOR: linked_list_allocator::LockedHeap =
-    linked_list_allocator::LockedHeap::empty();
+pub static ALLOCATOR: TrackingHeap = TrackingHeap::empty();
+
+/// Running totals of requested bytes, kept with atomics so that counting
+/// adds no lock beyond the heap's own.
+struct HeapCounters {
+    allocated: AtomicU64,
+    freed: AtomicU64,
+    live: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl HeapCounters {
+    const fn new() -> Self {
+        Self {
+            allocated: AtomicU64::new(0),
+            freed: AtomicU64::new(0),
+            live: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    fn record_alloc(&self, size: usize) {
+        self.allocated.fetch_add(size as u64, Ordering::Relaxed);
+        let live = self.live.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn record_free(&self, size: usize) {
+        self.freed.fetch_add(size as u64, Ordering::Relaxed);
+        self.live.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+/// The global `linked_list_allocator` heap, counting what passes through it
+/// for [`heap_stats`].
+pub struct TrackingHeap {
+    heap: linked_list_allocator::LockedHeap,
+    counters: HeapCounters,
+}
+
+impl TrackingHeap {
+    pub const fn empty() -> Self {
+        Self {
+            heap: linked_list_allocator::LockedHeap::empty(),
+            counters: HeapCounters::new(),
+        }
+    }
+
+    /// Current usage of this heap.
+    pub fn stats(&self) -> HeapStats {
+        let heap = self.heap.lock();
+        self.stats_of(&heap)
+    }
+
+    /// [`Self::stats`], or `None` rather than waiting while the heap lock
+    /// is held, possibly by the caller itself.
+    pub fn try_stats(&self) -> Option<HeapStats> {
+        let heap = self.heap.try_lock()?;
+        Some(self.stats_of(&heap))
+    }
+
+    fn stats_of(&self, heap: &linked_list_allocator::Heap) -> HeapStats {
+        HeapStats {
+            total: heap.size(),
+            used: heap.used(),
+            free: heap.free(),
+            allocated: self.counters.allocated.load(Ordering::Relaxed),
+            freed: self.counters.freed.load(Ordering::Relaxed),
+            live: self.counters.live.load(Ordering::Relaxed),
+            peak: self.counters.peak.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Derefs to the underlying heap so it can be locked to initialise or
+/// extend it.
+impl core::ops::Deref for TrackingHeap {
+    type Target = linked_list_allocator::LockedHeap;
+
+    fn deref(&self) -> &Self::Target {
+        &self.heap
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.heap.alloc(layout) };
+        if !ptr.is_null() {
+            self.counters.record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.heap.dealloc(ptr, layout) };
+        self.counters.record_free(layout.size());
+    }
+}
 
 /// Check if the heap has been initialized
 ///
@@ -168,33 +264,130 @@ pub fn heap_top() -> *mut u8 {
 }
 
 /// Heap usage statistics.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct HeapStats {
     /// Total usable size of the heap in bytes.
     pub total: usize,
-    /// Currently allocated (used) bytes.
+    /// Currently allocated (used) bytes, including allocator overhead.
     pub used: usize,
     /// Currently free bytes.
     pub free: usize,
+    /// Bytes requested by every allocation since boot.
+    pub allocated: u64,
+    /// Bytes returned by every deallocation since boot.
+    pub freed: u64,
+    /// Requested bytes currently allocated (`allocated - freed`).
+    pub live: usize,
+    /// Highest `live` seen since boot.
+    pub peak: usize,
 }
 
 /// Query the current heap usage.
 pub fn heap_stats() -> HeapStats {
     #[cfg(all(not(feature = "std"), not(test)))]
     {
-        let alloc = ALLOCATOR.lock();
-        HeapStats {
-            total: alloc.size(),
-            used: alloc.used(),
-            free: alloc.free(),
-        }
+        ALLOCATOR.stats()
     }
     #[cfg(any(feature = "std", test))]
     {
-        HeapStats {
-            total: 0,
-            used: 0,
-            free: 0,
+        HeapStats::default()
+    }
+}
+
+/// [`heap_stats`], or `None` while the heap lock is held.
+pub fn try_heap_stats() -> Option<HeapStats> {
+    #[cfg(all(not(feature = "std"), not(test)))]
+    {
+        ALLOCATOR.try_stats()
+    }
+    #[cfg(any(feature = "std", test))]
+    {
+        Some(HeapStats::default())
+    }
+}
+
+/// Report a failed allocation of `layout` and the heap state over serial.
+///
+/// Comparing `live` against `peak` and the request size tells a leak (live
+/// close to the heap size) from a single oversized request.  The failure
+/// may come with the heap lock held, so the state is left out rather than
+/// waited for when it is.
+pub fn report_alloc_error(layout: Layout) {
+    let Some(stats) = try_heap_stats() else {
+        crate::serial::_print(format_args!(
+            "\n========== OUT OF MEMORY ==========\n\
+             \x20 request: {} bytes, align {}\n\
+             \x20 heap:    locked, state unavailable\n\
+             ===================================\n",
+            layout.size(),
+            layout.align(),
+        ));
+        return;
+    };
+    crate::serial::_print(format_args!(
+        "\n========== OUT OF MEMORY ==========\n\
+         \x20 request: {} bytes, align {}\n\
+         \x20 heap:    {} total, {} used, {} free\n\
+         \x20 live:    {} bytes (peak {})\n\
+         \x20 totals:  {} allocated, {} freed\n\
+         ===================================\n",
+        layout.size(),
+        layout.align(),
+        stats.total,
+        stats.used,
+        stats.free,
+        stats.live,
+        stats.peak,
+        stats.allocated,
+        stats.freed,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_requested_bytes_and_peak_usage() {
+        #[repr(align(16))]
+        struct Arena([u8; 4096]);
+        let mut arena = Arena([0; 4096]);
+        let heap = TrackingHeap::empty();
+        unsafe { heap.lock().init(arena.0.as_mut_ptr(), arena.0.len()) };
+
+        let big = Layout::from_size_align(100, 8).unwrap();
+        let small = Layout::from_size_align(50, 8).unwrap();
+        unsafe {
+            let first = heap.alloc(big);
+            let second = heap.alloc(small);
+            heap.dealloc(first, big);
+            let third = heap.alloc(Layout::from_size_align(20, 8).unwrap());
+            assert!(!second.is_null() && !third.is_null());
+            // A request the heap cannot satisfy is not counted.
+            assert!(
+                heap.alloc(Layout::from_size_align(8192, 8).unwrap())
+                    .is_null()
+            );
         }
+
+        let stats = heap.stats();
+        assert_eq!((stats.allocated, stats.freed), (170, 100));
+        assert_eq!((stats.live, stats.peak), (70, 150));
+        assert_eq!(stats.total, 4096);
+        assert!(stats.used >= 70);
+    }
+
+    #[test]
+    fn try_stats_does_not_wait_for_the_heap_lock() {
+        #[repr(align(16))]
+        struct Arena([u8; 4096]);
+        let mut arena = Arena([0; 4096]);
+        let heap = TrackingHeap::empty();
+        unsafe { heap.lock().init(arena.0.as_mut_ptr(), arena.0.len()) };
+
+        let held = heap.lock();
+        assert!(heap.try_stats().is_none());
+        drop(held);
+        assert_eq!(heap.try_stats().map(|stats| stats.total), Some(4096));
     }
 }
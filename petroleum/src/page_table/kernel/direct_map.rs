@@ -54,9 +54,12 @@ pub fn init_direct_physical_mapping(
                             .map_to(
                                 page,
                                 frame,
-                                PageTableFlags::PRESENT
-                                    | PageTableFlags::WRITABLE
-                                    | PageTableFlags::HUGE_PAGE,
+                                crate::page_table::supported_flags(
+                                    PageTableFlags::PRESENT
+                                        | PageTableFlags::WRITABLE
+                                        | PageTableFlags::NO_EXECUTE
+                                        | PageTableFlags::HUGE_PAGE,
+                                ),
                                 allocator,
                             )
                             .map_err(|_| crate::MemoryError::MappingFailed)?
@@ -73,7 +76,11 @@ pub fn init_direct_physical_mapping(
                             .map_to(
                                 page,
                                 frame,
-                                PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                                crate::page_table::supported_flags(
+                                    PageTableFlags::PRESENT
+                                        | PageTableFlags::WRITABLE
+                                        | PageTableFlags::NO_EXECUTE,
+                                ),
                                 allocator,
                             )
                             .map_err(|_| crate::MemoryError::MappingFailed)?
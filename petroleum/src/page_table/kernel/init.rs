@@ -1,10 +1,14 @@
 //! Page table initialization and kernel jump logic.
 
 use crate::page_table::allocator::bitmap::BitmapFrameAllocator;
+use crate::page_table::pe::{PeParser, PeSection, image_page_flags, shares_code_page};
 use core::sync::atomic::{AtomicBool, Ordering};
 use x86_64::{
     PhysAddr, VirtAddr,
-    registers::control::Cr3,
+    registers::{
+        control::Cr3,
+        model_specific::{Efer, EferFlags},
+    },
     structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PageTableFlags, Size4KiB},
 };
 
@@ -16,6 +20,8 @@ const PAGE_SIZE_4K: u64 = 4096;
 const PAGE_SIZE_2M: u64 = 2 * 1024 * 1024;
 const ENTRIES_PER_TABLE: u64 = 512;
 const IDENTITY_MAP_2MB_PAGES: u64 = 32768; // 64GB / 2MB
+/// Pages mapped for a kernel image whose headers cannot be read.
+const FALLBACK_KERNEL_PAGES: u64 = 8 * 1024 * 1024 / PAGE_SIZE_4K;
 
 #[derive(Clone, Copy)]
 struct PageTableIndices {
@@ -36,6 +42,14 @@ impl PageTableIndices {
     }
 }
 
+/// Flags for an entry that points at a lower-level table.  Write and
+/// execute permissions are decided by the leaf: a parent that withheld
+/// either would withhold it from every page below, including pages mapped
+/// through it later.
+fn table_flags(leaf: PageTableFlags) -> PageTableFlags {
+    PageTableFlags::PRESENT | PageTableFlags::WRITABLE | (leaf & PageTableFlags::USER_ACCESSIBLE)
+}
+
 /// Stateful low-level page-table editor for kernel bootstrap memory work.
 ///
 /// This intentionally gathers the raw pointer arithmetic, frame allocation,
@@ -121,7 +135,7 @@ impl<'a> KernelMemoryOperations<'a> {
                     page_table_access_offset,
                     crate::MemoryError::FrameAllocationFailed,
                 )?;
-                l4[indices.l4].set_addr(addr, flags | PageTableFlags::PRESENT);
+                l4[indices.l4].set_addr(addr, table_flags(flags));
                 &mut *((l4[indices.l4].addr().as_u64() + offset) as *mut PageTable)
             } else {
                 &mut *((l4[indices.l4].addr().as_u64() + offset) as *mut PageTable)
@@ -142,9 +156,7 @@ impl<'a> KernelMemoryOperations<'a> {
                         orig_flags | PageTableFlags::PRESENT | PageTableFlags::HUGE_PAGE,
                     );
                 }
-                let mut new_flags = orig_flags;
-                new_flags.remove(PageTableFlags::HUGE_PAGE);
-                l3[indices.l3].set_addr(l2_phys, new_flags | PageTableFlags::PRESENT);
+                l3[indices.l3].set_addr(l2_phys, table_flags(orig_flags));
             }
 
             let l2 = if l3[indices.l3].is_unused() {
@@ -153,7 +165,7 @@ impl<'a> KernelMemoryOperations<'a> {
                     page_table_access_offset,
                     crate::MemoryError::FrameAllocationFailed,
                 )?;
-                l3[indices.l3].set_addr(addr, flags | PageTableFlags::PRESENT);
+                l3[indices.l3].set_addr(addr, table_flags(flags));
                 &mut *((l3[indices.l3].addr().as_u64() + offset) as *mut PageTable)
             } else {
                 &mut *((l3[indices.l3].addr().as_u64() + offset) as *mut PageTable)
@@ -165,7 +177,7 @@ impl<'a> KernelMemoryOperations<'a> {
                     page_table_access_offset,
                     crate::MemoryError::FrameAllocationFailed,
                 )?;
-                l2[indices.l2].set_addr(l1_phys, flags | PageTableFlags::PRESENT);
+                l2[indices.l2].set_addr(l1_phys, table_flags(flags));
             } else if l2[indices.l2].flags().contains(PageTableFlags::HUGE_PAGE) {
                 let huge_page_phys_base = l2[indices.l2].addr().as_u64();
                 let mut split_flags = l2[indices.l2].flags();
@@ -182,11 +194,11 @@ impl<'a> KernelMemoryOperations<'a> {
                         split_flags | PageTableFlags::PRESENT,
                     );
                 }
-                l2[indices.l2].set_addr(l1_phys, flags | PageTableFlags::PRESENT);
+                l2[indices.l2].set_addr(l1_phys, table_flags(split_flags | flags));
             }
 
             let l1 = &mut *((l2[indices.l2].addr().as_u64() + offset) as *mut PageTable);
-            l1[indices.l1].set_addr(phys, flags);
+            l1[indices.l1].set_addr(phys, crate::page_table::supported_flags(flags));
             Ok(())
         }
     }
@@ -207,6 +219,30 @@ impl<'a> KernelMemoryOperations<'a> {
         Ok(())
     }
 
+    /// Map the 4 KiB pages of a PE image loaded at `phys_start`, each with
+    /// the permissions of the sections it holds (see [`image_page_flags`]).
+    /// Pages no section touches, the headers among them, get the default
+    /// flags.
+    pub unsafe fn map_image_4k(
+        &mut self,
+        virt_start: VirtAddr,
+        phys_start: PhysAddr,
+        page_count: u64,
+        sections: &[PeSection],
+    ) -> Result<(), crate::MemoryError> {
+        if shares_code_page(sections) {
+            crate::debug_log_no_alloc!(
+                "PE image has data on a code page; writes there will fault (link with /ALIGN:4096)"
+            );
+        }
+        for i in 0..page_count {
+            let offset = i * PAGE_SIZE_4K;
+            let flags = image_page_flags(sections, offset).unwrap_or(self.default_flags);
+            unsafe { self.map_page_4k(virt_start + offset, phys_start + offset, flags)? };
+        }
+        Ok(())
+    }
+
     /// Map a range using 2 MiB huge pages.
     pub unsafe fn map_range_2mb_huge(
         &mut self,
@@ -232,7 +268,7 @@ impl<'a> KernelMemoryOperations<'a> {
                         page_table_access_offset,
                         crate::MemoryError::FrameAllocationFailed,
                     )?;
-                    l4[indices.l4].set_addr(addr, flags | PageTableFlags::PRESENT);
+                    l4[indices.l4].set_addr(addr, table_flags(flags));
                 }
 
                 let l3 = &mut *((l4[indices.l4].addr().as_u64() + offset) as *mut PageTable);
@@ -243,11 +279,14 @@ impl<'a> KernelMemoryOperations<'a> {
                         page_table_access_offset,
                         crate::MemoryError::FrameAllocationFailed,
                     )?;
-                    l3[indices.l3].set_addr(addr, flags | PageTableFlags::PRESENT);
+                    l3[indices.l3].set_addr(addr, table_flags(flags));
                 }
 
                 let l2 = &mut *((l3[indices.l3].addr().as_u64() + offset) as *mut PageTable);
-                l2[indices.l2].set_addr(phys, flags_2mb | PageTableFlags::PRESENT);
+                l2[indices.l2].set_addr(
+                    phys,
+                    crate::page_table::supported_flags(flags_2mb | PageTableFlags::PRESENT),
+                );
             }
             Ok(())
         }
@@ -368,7 +407,9 @@ pub unsafe extern "C" fn init_and_jump(
 
         crate::serial::_print(format_args!("IAJ: Initializing L4 table...\n"));
 
-        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        // Everything but code is mapped writable and no-execute; the two
+        // images below get per-section permissions.
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
 
         // 1. Use the pre-allocated L4 table provided by the bootloader
         let l4_phys = l4_phys_addr;
@@ -431,36 +472,52 @@ pub unsafe extern "C" fn init_and_jump(
         // The map_page_4k_l1 function handles HUGE_PAGE splitting automatically.
 
         // === Kernel mapping (higher-half + identity) ===
+        // Each page gets the permissions of the sections it holds, so code is
+        // read-only and data is not executable.  An image whose headers cannot
+        // be read is mapped writable and executable, as before, for the
+        // kernel's W^X audit to report.
         crate::serial::_print(format_args!("IAJ: Mapping kernel...\n"));
-        let kernel_size = 8 * 1024 * 1024u64; // Increase to 8MB
-        let kernel_pages = kernel_size / 4096;
 
         // In init_and_jump, we are still running under UEFI's page table (before CR3 switch).
         // All page table structure accesses must use identity mapping (phys_offset = 0)
         // because the UEFI page table does NOT have higher-half mappings.
         // The new page table at `l4_phys` has both identity and higher-half mappings
         // (created by map_range_2mb_huge above), but we access it through identity mapping here.
+        let kernel_image = PeParser::new(kernel_phys_start as *const u8)
+            .filter(|pe| pe.pe_base as u64 == kernel_phys_start);
+        let kernel_sections = kernel_image.as_ref().and_then(|pe| pe.sections());
+        let kernel_pages = kernel_image
+            .as_ref()
+            .and_then(|pe| pe.size_of_image())
+            .map_or(FALLBACK_KERNEL_PAGES, |size| size.div_ceil(PAGE_SIZE_4K));
+        if kernel_sections.is_none() {
+            crate::serial::_print(format_args!(
+                "IAJ: WARNING: no PE headers at {:#x}, kernel mapped W+X\n",
+                kernel_phys_start
+            ));
+        }
 
-        // higher-half kernel mapping (splits higher-half huge pages)
-        let kernel_virt = VirtAddr::new(physical_memory_offset.as_u64() + kernel_phys_start);
-        memory_ops
-            .map_range_4k(
-                kernel_virt,
-                PhysAddr::new(kernel_phys_start),
-                kernel_pages,
-                flags,
-            )
-            .expect("kernel higher map");
-
+        // higher-half kernel mapping (splits higher-half huge pages), then
         // identity kernel mapping (splits identity huge pages)
-        memory_ops
-            .map_range_4k(
-                VirtAddr::new(kernel_phys_start),
-                PhysAddr::new(kernel_phys_start),
-                kernel_pages,
-                flags,
-            )
-            .expect("kernel identity");
+        for kernel_virt in [
+            physical_memory_offset.as_u64() + kernel_phys_start,
+            kernel_phys_start,
+        ] {
+            let kernel_virt = VirtAddr::new(kernel_virt);
+            let kernel_phys = PhysAddr::new(kernel_phys_start);
+            match &kernel_sections {
+                Some(sections) => {
+                    memory_ops.map_image_4k(kernel_virt, kernel_phys, kernel_pages, sections)
+                }
+                None => memory_ops.map_range_4k(
+                    kernel_virt,
+                    kernel_phys,
+                    kernel_pages,
+                    PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                ),
+            }
+            .expect("kernel map");
+        }
         crate::serial::_print(format_args!("IAJ: Kernel mapped\n"));
 
         // === Stack mapping (identity + higher-half) ===
@@ -518,6 +575,44 @@ pub unsafe extern "C" fn init_and_jump(
             )
             .expect("map higher");
 
+        // === Bootloader image (identity) ===
+        // This function and the splash below keep running from the
+        // bootloader's identity-mapped image after the switch, so its code
+        // must stay executable inside a no-execute window.  Mapped last so a
+        // kernel or stack page sharing its range cannot take that away.
+        let loader_image = PeParser::new(this_func_addr as *const u8)
+            .and_then(|pe| Some((pe.pe_base as u64, pe.size_of_image()?, pe.sections()?)));
+        match loader_image {
+            Some((base, size, sections)) => memory_ops
+                .map_image_4k(
+                    VirtAddr::new(base),
+                    PhysAddr::new(base),
+                    size.div_ceil(PAGE_SIZE_4K),
+                    &sections,
+                )
+                .expect("loader map"),
+            None => {
+                crate::serial::_print(format_args!(
+                    "IAJ: WARNING: no bootloader PE headers, mapping its code W+X\n"
+                ));
+                let code = this_func_addr as u64 & !(PAGE_SIZE_2M - 1);
+                memory_ops
+                    .map_range_4k(
+                        VirtAddr::new(code),
+                        PhysAddr::new(code),
+                        PAGE_SIZE_2M / PAGE_SIZE_4K,
+                        PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                    )
+                    .expect("loader map");
+            }
+        }
+
+        // The new tables mark data no-execute, a reserved bit unless
+        // EFER.NXE is set; without NX `supported_flags` left it out.
+        if crate::hardware::cpuid::features().nx {
+            Efer::update(|efer| efer.insert(EferFlags::NO_EXECUTE_ENABLE));
+        }
+
         // Store state BEFORE switching CR3, since after the switch we can't safely reference
         // Rust statics (they may be in unmapped higher-half addresses)
         PAGE_TABLE_INITIALIZED.store(true, Ordering::SeqCst);
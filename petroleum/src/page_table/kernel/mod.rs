@@ -5,6 +5,7 @@
 pub mod direct_map;
 pub mod init;
 pub mod mapper;
+pub mod wx;
 
 // Re-export the main types
 pub use mapper::{MapError, Mapper, RegionBuilder};
@@ -0,0 +1,209 @@
+//! W^X audit of a live page table.
+//!
+//! Walks every present leaf and reports the ones that are both writable and
+//! executable.  Permissions combine across levels: a page is writable only
+//! if every entry on its path allows writes, and executable only if none of
+//! them sets `NO_EXECUTE`.
+
+use x86_64::VirtAddr;
+use x86_64::structures::paging::{PageTable, PageTableFlags};
+
+/// A run of virtual memory that is both writable and executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WxRegion {
+    pub start: VirtAddr,
+    pub size: u64,
+}
+
+/// Walk `l4`, whose tables are reachable at `phys + table_offset`, and pass
+/// each maximal run of W+X pages to `report` in address order.  Returns the
+/// number of runs reported.
+///
+/// # Safety
+/// `l4` and every table it references must be valid page tables, mapped at
+/// their physical address plus `table_offset`.
+pub unsafe fn for_each_wx_region(
+    l4: &PageTable,
+    table_offset: VirtAddr,
+    mut report: impl FnMut(WxRegion),
+) -> usize {
+    let mut walk = Walk {
+        table_offset,
+        run: None,
+        runs: 0,
+        report: &mut report,
+    };
+    unsafe { walk.table(l4, 4, 0, true, true) };
+    walk.flush();
+    walk.runs
+}
+
+struct Walk<'a, F> {
+    table_offset: VirtAddr,
+    run: Option<WxRegion>,
+    runs: usize,
+    report: &'a mut F,
+}
+
+impl<F: FnMut(WxRegion)> Walk<'_, F> {
+    /// Visit `table`, which maps `base` onwards at `level`.  `writable` and
+    /// `executable` are what the entries above it allow.
+    unsafe fn table(
+        &mut self,
+        table: &PageTable,
+        level: u8,
+        base: u64,
+        writable: bool,
+        executable: bool,
+    ) {
+        let span = 1u64 << (12 + 9 * (u32::from(level) - 1));
+        for (index, entry) in table.iter().enumerate() {
+            let flags = entry.flags();
+            if !flags.contains(PageTableFlags::PRESENT) {
+                continue;
+            }
+            let start = VirtAddr::new_truncate(base + index as u64 * span).as_u64();
+            let writable = writable && flags.contains(PageTableFlags::WRITABLE);
+            let executable = executable && !flags.contains(PageTableFlags::NO_EXECUTE);
+            let leaf = level == 1 || (level < 4 && flags.contains(PageTableFlags::HUGE_PAGE));
+            if !leaf {
+                let next = (entry.addr().as_u64() + self.table_offset.as_u64()) as *const PageTable;
+                unsafe { self.table(&*next, level - 1, start, writable, executable) };
+            } else if writable && executable {
+                self.leaf(start, span);
+            }
+        }
+    }
+
+    fn leaf(&mut self, start: u64, size: u64) {
+        if let Some(run) = self.run.as_mut()
+            && run.start.as_u64() + run.size == start
+        {
+            run.size += size;
+            return;
+        }
+        self.flush();
+        self.run = Some(WxRegion {
+            start: VirtAddr::new(start),
+            size,
+        });
+    }
+
+    fn flush(&mut self) {
+        if let Some(run) = self.run.take() {
+            (self.report)(run);
+            self.runs += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use x86_64::PhysAddr;
+
+    const TABLE: PageTableFlags = PageTableFlags::PRESENT.union(PageTableFlags::WRITABLE);
+    const DATA: PageTableFlags = TABLE.union(PageTableFlags::NO_EXECUTE);
+    const CODE: PageTableFlags = PageTableFlags::PRESENT;
+
+    /// Page tables addressed by their host address, so `table_offset` is 0.
+    #[derive(Default)]
+    struct Tables(Vec<Box<PageTable>>);
+
+    impl Tables {
+        fn new_table(&mut self) -> *mut PageTable {
+            let mut table = Box::new(PageTable::new());
+            let ptr = &mut *table as *mut PageTable;
+            self.0.push(table);
+            ptr
+        }
+
+        /// Link a fresh table below `parent[index]` with `flags`.
+        fn link(
+            &mut self,
+            parent: *mut PageTable,
+            index: usize,
+            flags: PageTableFlags,
+        ) -> *mut PageTable {
+            let child = self.new_table();
+            unsafe { (&mut *parent)[index].set_addr(PhysAddr::new(child as u64), flags) };
+            child
+        }
+    }
+
+    fn audit(l4: *mut PageTable) -> Vec<WxRegion> {
+        let mut regions = Vec::new();
+        let count =
+            unsafe { for_each_wx_region(&*l4, VirtAddr::zero(), |region| regions.push(region)) };
+        assert_eq!(count, regions.len());
+        regions
+    }
+
+    #[test]
+    fn adjacent_wx_pages_are_reported_as_one_run() {
+        let mut tables = Tables::default();
+        let l4 = tables.new_table();
+        let l3 = tables.link(l4, 0, TABLE);
+        let l2 = tables.link(l3, 0, TABLE);
+        let l1 = tables.link(l2, 0, TABLE);
+        let l1 = unsafe { &mut *l1 };
+        let frame = PhysAddr::new(0x10_0000);
+        l1[1].set_addr(frame, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+        l1[2].set_addr(frame, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+        l1[3].set_addr(frame, CODE);
+        l1[4].set_addr(frame, DATA);
+        l1[6].set_addr(frame, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+
+        assert_eq!(
+            audit(l4),
+            [
+                WxRegion {
+                    start: VirtAddr::new(0x1000),
+                    size: 0x2000,
+                },
+                WxRegion {
+                    start: VirtAddr::new(0x6000),
+                    size: 0x1000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parent_entries_restrict_their_leaves() {
+        let mut tables = Tables::default();
+        let l4 = tables.new_table();
+        let l3 = tables.link(l4, 0, TABLE);
+        let read_only = tables.link(l3, 0, PageTableFlags::PRESENT);
+        let no_execute = tables.link(l3, 1, DATA);
+        let wx = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::HUGE_PAGE;
+        unsafe {
+            (&mut *read_only)[0].set_addr(PhysAddr::new(0), wx);
+            (&mut *no_execute)[0].set_addr(PhysAddr::new(0), wx);
+        }
+
+        assert!(audit(l4).is_empty());
+    }
+
+    #[test]
+    fn higher_half_huge_pages_are_reported_at_canonical_addresses() {
+        let mut tables = Tables::default();
+        let l4 = tables.new_table();
+        let l3 = tables.link(l4, 256, TABLE);
+        let l2 = tables.link(l3, 0, TABLE);
+        let huge = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::HUGE_PAGE;
+        unsafe {
+            (&mut *l2)[0].set_addr(PhysAddr::new(0), huge);
+            (&mut *l2)[1].set_addr(PhysAddr::new(0x20_0000), huge | PageTableFlags::NO_EXECUTE);
+        }
+
+        assert_eq!(
+            audit(l4),
+            [WxRegion {
+                start: VirtAddr::new(0xFFFF_8000_0000_0000),
+                size: 0x20_0000,
+            }]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Typed view of a memory map.
+//!
+//! [`MemoryMap`] wraps a descriptor slice and yields only the descriptors
+//! that pass [`MemoryDescriptorValidator::is_valid`], as [`PhysicalRegion`]s
+//! already classified by [`RegionKind`].  Firmware maps may overlap; where
+//! they do, a reserved region wins over a usable one, so an address is only
+//! reported usable if nothing else claims it.
+
+use super::validator::MemoryDescriptorValidator;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// How the kernel may treat a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// RAM free once boot services are gone: conventional memory, loader
+    /// and boot services code and data, and reclaimable ACPI tables.
+    Usable,
+    /// UEFI runtime services code and data, which must stay mapped.
+    Runtime,
+    /// Everything else: firmware-reserved RAM, ACPI NVS, MMIO and unusable
+    /// memory.
+    Reserved,
+}
+
+impl RegionKind {
+    /// Classify the UEFI memory type `mem_type`.
+    pub fn of(mem_type: u32) -> Self {
+        match mem_type {
+            // Loader code/data, boot services code/data, conventional
+            // memory, ACPI reclaim memory.
+            1..=4 | 7 | 9 => Self::Usable,
+            // Runtime services code/data.
+            5 | 6 => Self::Runtime,
+            _ => Self::Reserved,
+        }
+    }
+}
+
+/// A validated descriptor: `[start, end)` in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalRegion {
+    pub start: u64,
+    pub end: u64,
+    pub mem_type: u32,
+    pub kind: RegionKind,
+}
+
+impl PhysicalRegion {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        (self.start..self.end).contains(&addr)
+    }
+}
+
+/// A memory map: any slice of descriptors.
+#[derive(Clone, Copy)]
+pub struct MemoryMap<'a, T> {
+    descriptors: &'a [T],
+}
+
+impl<'a, T: MemoryDescriptorValidator> MemoryMap<'a, T> {
+    pub fn new(descriptors: &'a [T]) -> Self {
+        Self { descriptors }
+    }
+
+    /// Every valid descriptor, in map order.  Descriptors with no pages,
+    /// an unaligned start or an out-of-range type are skipped.
+    pub fn regions(&self) -> impl Iterator<Item = PhysicalRegion> + 'a {
+        self.descriptors
+            .iter()
+            .filter(|descriptor| descriptor.is_valid())
+            .map(|descriptor| {
+                let start = descriptor.get_physical_start();
+                let mem_type = descriptor.get_type();
+                PhysicalRegion {
+                    start,
+                    end: start + descriptor.get_page_count() * PAGE_SIZE,
+                    mem_type,
+                    kind: RegionKind::of(mem_type),
+                }
+            })
+    }
+
+    pub fn iter_usable(&self) -> impl Iterator<Item = PhysicalRegion> + 'a {
+        self.regions()
+            .filter(|region| region.kind == RegionKind::Usable)
+    }
+
+    /// Regions the kernel must not allocate from, runtime services
+    /// included.
+    pub fn iter_reserved(&self) -> impl Iterator<Item = PhysicalRegion> + 'a {
+        self.regions()
+            .filter(|region| region.kind != RegionKind::Usable)
+    }
+
+    /// Bytes for which [`Self::is_usable`] holds: the union of the usable
+    /// regions, less whatever a reserved or runtime region overlaps.
+    pub fn total_usable_bytes(&self) -> u64 {
+        let mut total = 0;
+        self.for_each_run(
+            |region| region.kind == RegionKind::Usable,
+            0,
+            u64::MAX,
+            |start, end| {
+                let mut claimed = 0;
+                self.for_each_run(
+                    |region| region.kind != RegionKind::Usable,
+                    start,
+                    end,
+                    |start, end| claimed += end - start,
+                );
+                total += end - start - claimed;
+            },
+        );
+        total
+    }
+
+    /// Call `f(start, end)` for each maximal run of the union of the
+    /// regions matching `include`, clipped to `[lo, hi)`, from the bottom
+    /// up.
+    fn for_each_run(
+        &self,
+        include: impl Fn(&PhysicalRegion) -> bool,
+        lo: u64,
+        hi: u64,
+        mut f: impl FnMut(u64, u64),
+    ) {
+        let regions = || {
+            self.regions()
+                .filter(|region| include(region) && region.start < hi && region.end > lo)
+        };
+        let mut cursor = lo;
+        while cursor < hi {
+            // The lowest covered byte at or above `cursor`...
+            let Some(start) = regions()
+                .filter(|region| region.end > cursor)
+                .map(|region| region.start.max(cursor))
+                .min()
+            else {
+                break;
+            };
+            // ...and as far as regions starting inside the run extend it.
+            let mut end = start;
+            while let Some(next) = regions()
+                .filter(|region| region.start <= end && region.end > end)
+                .map(|region| region.end)
+                .max()
+            {
+                end = next;
+            }
+            let end = end.min(hi);
+            f(start, end);
+            cursor = end;
+        }
+    }
+
+    /// The region holding physical address `addr`.  When regions overlap,
+    /// a reserved or runtime one is returned in preference to a usable one.
+    pub fn find_region_containing(&self, addr: u64) -> Option<PhysicalRegion> {
+        let mut found = None;
+        for region in self.regions().filter(|region| region.contains(addr)) {
+            if region.kind != RegionKind::Usable {
+                return Some(region);
+            }
+            found.get_or_insert(region);
+        }
+        found
+    }
+
+    /// Whether `addr` is RAM the kernel may use.
+    pub fn is_usable(&self, addr: u64) -> bool {
+        self.find_region_containing(addr)
+            .is_some_and(|region| region.kind == RegionKind::Usable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::EfiMemoryType;
+    use crate::page_table::memory_map::EfiMemoryDescriptor;
+
+    fn descriptor(type_: EfiMemoryType, start: u64, pages: u64) -> EfiMemoryDescriptor {
+        EfiMemoryDescriptor {
+            type_,
+            padding: 0,
+            physical_start: start,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: 0,
+        }
+    }
+
+    fn map() -> [EfiMemoryDescriptor; 6] {
+        [
+            descriptor(EfiMemoryType::EfiConventionalMemory, 0x1000, 0x9E),
+            descriptor(EfiMemoryType::EfiReservedMemoryType, 0xA0000, 0x60),
+            descriptor(EfiMemoryType::EfiConventionalMemory, 0x10_0000, 0x100),
+            // Overlaps the top half of the previous region.
+            descriptor(EfiMemoryType::EfiBootServicesData, 0x18_0000, 0x100),
+            descriptor(EfiMemoryType::EfiRuntimeServicesData, 0x1F_0000, 0x10),
+            descriptor(EfiMemoryType::EfiConventionalMemory, 0x30_0000, 0),
+        ]
+    }
+
+    #[test]
+    fn classifies_and_skips_empty_descriptors() {
+        let descriptors = map();
+        let map = MemoryMap::new(&descriptors);
+        assert_eq!(map.regions().count(), 5);
+        assert_eq!(map.iter_usable().count(), 3);
+        let reserved: [RegionKind; 2] = [RegionKind::Reserved, RegionKind::Runtime];
+        assert!(map.iter_reserved().map(|region| region.kind).eq(reserved));
+        // ACPI reclaim memory and ACPI NVS.
+        assert_eq!(RegionKind::of(9), RegionKind::Usable);
+        assert_eq!(RegionKind::of(10), RegionKind::Reserved);
+    }
+
+    #[test]
+    fn total_usable_bytes_counts_overlaps_once_and_skips_reserved_ones() {
+        let descriptors = map();
+        let map = MemoryMap::new(&descriptors);
+        // 0x1000..0x9F000, then 0x100000..0x280000 as one run less the
+        // runtime region at 0x1F0000..0x200000.
+        assert_eq!(map.total_usable_bytes(), 0x9E000 + 0x18_0000 - 0x1_0000);
+        assert_eq!(
+            MemoryMap::<EfiMemoryDescriptor>::new(&[]).total_usable_bytes(),
+            0
+        );
+    }
+
+    #[test]
+    fn lookups_prefer_reserved_regions_over_usable_ones() {
+        let descriptors = map();
+        let map = MemoryMap::new(&descriptors);
+        assert!(map.is_usable(0x1000));
+        assert!(!map.is_usable(0x0FFF));
+        assert!(!map.is_usable(0x9F000));
+        assert!(!map.is_usable(0xA0000));
+        assert!(map.is_usable(0x27_FFFF));
+        assert!(!map.is_usable(0x28_0000));
+        assert!(!map.is_usable(0x30_0000));
+
+        let region = map.find_region_containing(0x1F_8000).unwrap();
+        assert_eq!(region.kind, RegionKind::Runtime);
+        assert_eq!((region.start, region.len()), (0x1F_0000, 0x1_0000));
+        assert!(!map.is_usable(0x1F_8000));
+        assert_eq!(
+            map.find_region_containing(0x18_0000).unwrap().mem_type,
+            EfiMemoryType::EfiConventionalMemory as u32
+        );
+    }
+}
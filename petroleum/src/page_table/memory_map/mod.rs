@@ -1,10 +1,12 @@
 //! UEFI memory map processing.
 
 pub mod descriptor;
+pub mod map;
 pub mod processor;
 pub mod validator;
 
 // Re-export commonly used items for backward compatibility
 pub use descriptor::*;
+pub use map::{MemoryMap, PhysicalRegion, RegionKind};
 pub use processor::*;
 pub use validator::MemoryDescriptorValidator;
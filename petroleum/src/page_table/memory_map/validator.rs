@@ -13,8 +13,6 @@ pub trait MemoryDescriptorValidator {
     fn get_physical_start(&self) -> u64;
     /// Get the number of pages.
     fn get_page_count(&self) -> u64;
-    /// Check if the memory is available for use.
-    fn is_memory_available(&self) -> bool;
 }
 
 impl MemoryDescriptorValidator for MemoryMapDescriptor {
@@ -33,18 +31,6 @@ impl MemoryDescriptorValidator for MemoryMapDescriptor {
     fn is_valid(&self) -> bool {
         is_valid_memory_descriptor(self)
     }
-
-    fn is_memory_available(&self) -> bool {
-        let mem_type = self.get_type();
-        // Available memory types according to UEFI spec:
-        // 1: Loader Code
-        // 2: Loader Data
-        // 3: Boot Services Code
-        // 4: Boot Services Data
-        // 7: Conventional Memory
-        // 9: ACPI Reclaimed Memory
-        matches!(mem_type, 7u32 | 3u32 | 4u32 | 1u32 | 2u32 | 9u32)
-    }
 }
 
 impl MemoryDescriptorValidator for EfiMemoryDescriptor {
@@ -67,19 +53,6 @@ impl MemoryDescriptorValidator for EfiMemoryDescriptor {
 
         validate_descriptor_common(mem_type, phys, pages)
     }
-
-    fn is_memory_available(&self) -> bool {
-        let mem_type = self.get_type();
-        // Available memory types according to UEFI spec:
-        // 1: Conventional Memory
-        // 2: Boot Services Code
-        // 3: Boot Services Data
-        // 4: Loader Code
-        // 5: Loader Data
-        // 9: ACPI Reclaimed Memory
-        // 11: ACPI Memory
-        matches!(mem_type, 7u32 | 3u32 | 4u32 | 1u32 | 2u32 | 9u32)
-    }
 }
 
 /// Helper function to validate memory descriptor properties common to both descriptor types
@@ -2,6 +2,7 @@
 
 pub mod allocator;
 pub mod constants;
+pub mod elf;
 pub mod heap;
 pub mod kernel;
 pub mod memory_map;
@@ -10,6 +11,7 @@ pub mod pat;
 pub mod pe;
 pub mod process;
 pub mod raw;
+pub mod slab;
 pub mod types;
 pub mod virtual_memory;
 
@@ -33,8 +35,24 @@ pub use raw::huge::map_range_with_huge_pages;
 pub use raw::utils::{map_identity_range, map_range_4kiB, map_to_higher_half_with_log};
 pub use types::PageTableEntry as Pte;
 pub use types::*;
+use x86_64::structures::paging::PageTableFlags;
 pub type EfiMemoryDescriptor = memory_map::MemoryMapDescriptor;
 
+/// `flags` as this CPU accepts them in a leaf entry.  Without NX the
+/// `NO_EXECUTE` bit is reserved and any access through the entry faults,
+/// so it is dropped there.
+pub fn supported_flags(flags: PageTableFlags) -> PageTableFlags {
+    without_unsupported_nx(flags, crate::hardware::cpuid::features().nx)
+}
+
+pub(crate) fn without_unsupported_nx(flags: PageTableFlags, nx: bool) -> PageTableFlags {
+    if nx {
+        flags
+    } else {
+        flags - PageTableFlags::NO_EXECUTE
+    }
+}
+
 pub fn init_kernel_mapper() {}
 pub fn find_free_virtual_address(_size: u64) -> Option<u64> {
     None
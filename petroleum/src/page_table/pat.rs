@@ -29,16 +29,17 @@ use x86_64::registers::model_specific::Msr;
 /// MSR address for IA32_CR_PAT.
 pub const MSR_IA32_CR_PAT: u32 = 0x0277;
 
+/// The OS-defined PAT: `PAT_VALUE(WB, WC, UC_MINUS, UC, WB, WP, UC_MINUS, WT)`,
+/// the same value Linux uses for modern CPUs with full PAT support.
+pub const PAT_VALUE: u64 = 0x0407_0506_0007_0106;
+
 /// Write the PAT MSR with the OS-defined value that enables WC on PAT[1].
 ///
 /// # Safety
 /// Must be called once per CPU during boot.  Writes to an MSR that affects
 /// all subsequent memory type determinations.
 pub unsafe fn init_pat() {
-    // Same value Linux uses for modern CPUs with full PAT support.
-    // PAT_VALUE(WB, WC, UC_MINUS, UC, WB, WP, UC_MINUS, WT)
-    let pat_value: u64 = 0x0407_0506_0007_0106;
     unsafe {
-        Msr::new(MSR_IA32_CR_PAT).write(pat_value);
+        Msr::new(MSR_IA32_CR_PAT).write(PAT_VALUE);
     }
 }
@@ -1,4 +1,4 @@
-use crate::common::{BellowsError, EfiMemoryType, EfiStatus, EfiSystemTable};
+use crate::common::{BellowsError, EfiBootServices, EfiMemoryType, EfiStatus, EfiSystemTable};
 use core::ffi::c_void;
 use goblin::pe::PE;
 use x86_64::{PhysAddr, structures::paging::PageTableFlags};
@@ -136,22 +136,70 @@ pub unsafe fn find_pe_base(start_ptr: *const u8) -> Option<*const u8> {
     None
 }
 
+/// `IMAGE_SCN_MEM_EXECUTE`: the section holds code.
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+/// `IMAGE_SCN_MEM_WRITE`: the section may be written.
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// Page flags for a section with `characteristics`: writable only if the
+/// section is, executable only if it is.  A section that asks for both
+/// keeps execute and loses write, so no page comes out W+X.
 pub fn derive_pe_flags(characteristics: u32) -> PageTableFlags {
     use x86_64::structures::paging::PageTableFlags as Flags;
     let mut flags = Flags::PRESENT;
-
-    // Ensure data sections are writable.
-    // In early boot, we prefer over-permissioning to avoid triple faults.
-    if (characteristics & 0x8000_0000) != 0 || (characteristics & 0x2000_0000) == 0 {
-        flags |= Flags::WRITABLE;
+    if characteristics & SCN_MEM_EXECUTE != 0 {
+        return flags;
     }
-
-    if (characteristics & 0x2000_0000) == 0 {
-        flags |= Flags::NO_EXECUTE;
+    flags |= Flags::NO_EXECUTE;
+    if characteristics & SCN_MEM_WRITE != 0 {
+        flags |= Flags::WRITABLE;
     }
     flags
 }
 
+/// Flags for the 4 KiB page at `rva` in an image with `sections`, or `None`
+/// if no section touches the page.  Sections sharing a page merge their
+/// characteristics before [`derive_pe_flags`], so the page is executable if
+/// any of them is and otherwise writable if any of them is.  Data sharing a
+/// page with code is therefore read-only; the kernel is linked with
+/// page-aligned sections (`/ALIGN:4096` in its build script) so that never
+/// happens, and [`shares_code_page`] catches images where it does.
+pub fn image_page_flags(sections: &[PeSection], rva: u64) -> Option<PageTableFlags> {
+    let page = rva & !0xFFF;
+    let characteristics = sections
+        .iter()
+        .filter(|section| {
+            let start = u64::from(section.virtual_address);
+            let size = u64::from(section.virtual_size.max(section.size_of_raw_data));
+            size != 0 && start < page + 0x1000 && page < start + size
+        })
+        .map(|section| section.characteristics)
+        .reduce(|a, b| a | b)?;
+    Some(derive_pe_flags(characteristics))
+}
+
+/// Whether a writable section shares a 4 KiB page with an executable one,
+/// which [`image_page_flags`] maps read-only so writes there fault.
+pub fn shares_code_page(sections: &[PeSection]) -> bool {
+    let pages = |section: &PeSection| {
+        let start = u64::from(section.virtual_address) & !0xFFF;
+        let size = u64::from(section.virtual_size.max(section.size_of_raw_data));
+        let end = (u64::from(section.virtual_address) + size).div_ceil(0x1000) * 0x1000;
+        (size != 0).then_some(start..end)
+    };
+    sections
+        .iter()
+        .filter(|section| section.characteristics & SCN_MEM_WRITE != 0)
+        .filter_map(pages)
+        .any(|data| {
+            sections
+                .iter()
+                .filter(|section| section.characteristics & SCN_MEM_EXECUTE != 0)
+                .filter_map(pages)
+                .any(|code| code.start < data.end && data.start < code.end)
+        })
+}
+
 pub unsafe fn calculate_kernel_memory_size(kernel_phys_start: PhysAddr) -> u64 {
     unsafe {
         log_page_table_op!(
@@ -199,35 +247,17 @@ pub unsafe fn calculate_kernel_memory_size(kernel_phys_start: PhysAddr) -> u64 {
     }
 }
 
-/// Load and parse EFI PE image from file data
-pub fn load_efi_image(
-    st: &EfiSystemTable,
-    file: &[u8],
-    phys_offset: usize,
-) -> Result<
-    (
-        PhysAddr,
-        u64,
-        extern "efiapi" fn(usize, *mut EfiSystemTable, *mut c_void, usize) -> !,
-    ),
-    BellowsError,
-> {
-    let bs = unsafe { &*st.boot_services };
-
-    let pe = PE::parse(file).map_err(|_| BellowsError::PeParse("Failed to parse PE image"))?;
-
-    let optional_header = pe
-        .header
-        .optional_header
-        .as_ref()
-        .ok_or(BellowsError::PeParse("Missing optional header"))?;
-    let address_of_entry_point = optional_header.standard_fields.address_of_entry_point as usize;
-    let image_size = optional_header.windows_fields.size_of_image as u64;
-
-    let pages_needed =
-        (image_size.max(address_of_entry_point as u64 + 4096)).div_ceil(4096) as usize;
-    let preferred_base = optional_header.windows_fields.image_base as usize;
-
+/// Allocate and zero `pages_needed` pages for a kernel image whose link
+/// base is `preferred_base`.
+///
+/// Shared by the PE and ELF loaders.  The image always goes wherever the
+/// firmware has room and [`load_bias`] says how far it moved, so zeroing
+/// here also covers `.bss` and any gaps between sections.
+pub(crate) fn allocate_image(
+    bs: &EfiBootServices,
+    pages_needed: usize,
+    preferred_base: usize,
+) -> Result<usize, BellowsError> {
     let mut phys_addr: usize = 0;
     // Use AllocateAnyPages (0) instead of AllocateAddress (2).
     // InsydeH2O rejects AllocateAddress with addr=0.
@@ -255,7 +285,7 @@ pub fn load_efi_image(
 
     if EfiStatus::from(status) != EfiStatus::Success {
         return Err(BellowsError::AllocationFailed(
-            "Failed to allocate memory for PE image.",
+            "Failed to allocate memory for kernel image.",
         ));
     }
 
@@ -264,6 +294,46 @@ pub fn load_efi_image(
         core::ptr::write_bytes(phys_addr as *mut u8, 0, pages_needed * 4096);
     }
 
+    Ok(phys_addr)
+}
+
+/// Difference between where an image linked at `link_base` will run (the
+/// higher-half alias of `phys_addr`) and where it was linked.
+pub(crate) fn load_bias(phys_offset: usize, phys_addr: usize, link_base: u64) -> i64 {
+    ((phys_offset + phys_addr) as i64).wrapping_sub(link_base as i64)
+}
+
+/// Load and parse EFI PE image from file data
+pub fn load_efi_image(
+    st: &EfiSystemTable,
+    file: &[u8],
+    phys_offset: usize,
+) -> Result<
+    (
+        PhysAddr,
+        u64,
+        extern "efiapi" fn(usize, *mut EfiSystemTable, *mut c_void, usize) -> !,
+    ),
+    BellowsError,
+> {
+    let bs = unsafe { &*st.boot_services };
+
+    let pe = PE::parse(file).map_err(|_| BellowsError::PeParse("Failed to parse PE image"))?;
+
+    let optional_header = pe
+        .header
+        .optional_header
+        .as_ref()
+        .ok_or(BellowsError::PeParse("Missing optional header"))?;
+    let address_of_entry_point = optional_header.standard_fields.address_of_entry_point as usize;
+    let image_size = optional_header.windows_fields.size_of_image as u64;
+
+    let pages_needed =
+        (image_size.max(address_of_entry_point as u64 + 4096)).div_ceil(4096) as usize;
+    let preferred_base = optional_header.windows_fields.image_base as usize;
+
+    let phys_addr = allocate_image(bs, pages_needed, preferred_base)?;
+
     // Copy headers
     let size_of_headers = optional_header.windows_fields.size_of_headers as usize;
     unsafe {
@@ -290,9 +360,7 @@ pub fn load_efi_image(
     }
 
     // Relocations
-    let image_base = optional_header.windows_fields.image_base as usize;
-    let virtual_base = phys_offset + phys_addr;
-    let image_base_delta = (virtual_base as i64) - (image_base as i64);
+    let image_base_delta = load_bias(phys_offset, phys_addr, preferred_base as u64);
 
     if image_base_delta != 0 {
         // Use DataDirectory to find the base relocation table
@@ -357,32 +425,64 @@ pub fn load_efi_image(
     ))
 }
 
-#[repr(C)]
-pub struct Elf64Ehdr {
-    pub e_ident: [u8; 16],
-    pub e_type: u16,
-    pub e_machine: u16,
-    pub e_version: u32,
-    pub e_entry: u64,
-    pub e_phoff: u64,
-    pub e_shoff: u64,
-    pub e_flags: u32,
-    pub e_ehsize: u16,
-    pub e_phentsize: u16,
-    pub e_phnum: u16,
-    pub e_shentsize: u16,
-    pub e_shnum: u16,
-    pub e_shstrndx: u16,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x86_64::structures::paging::PageTableFlags as Flags;
 
-#[repr(C)]
-pub struct Elf64Phdr {
-    pub p_type: u32,
-    pub p_flags: u32,
-    pub p_offset: u64,
-    pub p_vaddr: u64,
-    pub p_paddr: u64,
-    pub p_filesz: u64,
-    pub p_memsz: u64,
-    pub p_align: u64,
+    const TEXT: u32 = 0x6000_0020;
+    const RDATA: u32 = 0x4000_0040;
+    const DATA: u32 = 0xC000_0040;
+
+    fn section(virtual_address: u32, virtual_size: u32, characteristics: u32) -> PeSection {
+        PeSection {
+            name: [0; 8],
+            virtual_size,
+            virtual_address,
+            size_of_raw_data: 0,
+            pointer_to_raw_data: 0,
+            characteristics,
+        }
+    }
+
+    #[test]
+    fn section_flags_are_never_writable_and_executable() {
+        assert_eq!(derive_pe_flags(TEXT), Flags::PRESENT);
+        assert_eq!(derive_pe_flags(RDATA), Flags::PRESENT | Flags::NO_EXECUTE);
+        assert_eq!(
+            derive_pe_flags(DATA),
+            Flags::PRESENT | Flags::WRITABLE | Flags::NO_EXECUTE
+        );
+        assert_eq!(derive_pe_flags(TEXT | DATA), Flags::PRESENT);
+    }
+
+    #[test]
+    fn pages_take_the_flags_of_the_sections_they_hold() {
+        let sections = [
+            section(0x1000, 0x1800, TEXT),
+            section(0x2800, 0x800, RDATA),
+            section(0x3000, 0x2000, DATA),
+            section(0, 0, 0),
+        ];
+        assert_eq!(image_page_flags(&sections, 0), None);
+        assert_eq!(image_page_flags(&sections, 0x1000), Some(Flags::PRESENT));
+        // .text and .rdata share a page, which stays executable.
+        assert_eq!(image_page_flags(&sections, 0x2fff), Some(Flags::PRESENT));
+        assert_eq!(
+            image_page_flags(&sections, 0x4000),
+            Some(Flags::PRESENT | Flags::WRITABLE | Flags::NO_EXECUTE)
+        );
+        assert_eq!(image_page_flags(&sections, 0x5000), None);
+        assert!(!shares_code_page(&sections));
+    }
+
+    #[test]
+    fn data_on_a_code_page_is_detected() {
+        let packed = [section(0x1000, 0x800, TEXT), section(0x1800, 0x800, DATA)];
+        assert!(shares_code_page(&packed));
+        // The shared page stays executable, so those data writes fault.
+        assert_eq!(image_page_flags(&packed, 0x1800), Some(Flags::PRESENT));
+        let aligned = [section(0x1000, 0x800, TEXT), section(0x2000, 0x800, DATA)];
+        assert!(!shares_code_page(&aligned));
+    }
 }
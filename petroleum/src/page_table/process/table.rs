@@ -1,3 +1,4 @@
+use crate::graphics::framebuffer_mapper::CacheMode;
 use crate::page_table::allocator::traits::FrameAllocatorExt;
 use crate::page_table::constants::BootInfoFrameAllocator;
 use crate::page_table::types::PageTableHelper;
@@ -7,8 +8,9 @@ use x86_64::{
     PhysAddr, VirtAddr,
     registers::control::Cr3,
     structures::paging::{
-        FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame,
-        Size4KiB, Translate, mapper::TranslateResult,
+        FrameAllocator, Mapper, OffsetPageTable, Page, PageSize, PageTable, PageTableFlags,
+        PhysFrame, Size1GiB, Size2MiB, Size4KiB, Translate,
+        mapper::{MappedFrame, TranslateResult},
     },
 };
 
@@ -104,6 +106,80 @@ impl ProcessPageTable {
     pub fn init_paging(&mut self) -> crate::common::logging::SystemResult<()> {
         Ok(())
     }
+
+    /// Switch the existing mapping of `size` bytes at `virtual_addr` to
+    /// `cache` by rewriting the PCD/PWT bits of each leaf entry.
+    ///
+    /// A 2 MiB or 1 GiB page lying wholly inside the range is changed in
+    /// place; one that only partly overlaps it is first split down to
+    /// 4 KiB pages, so nothing outside the range changes.  Fails if part of
+    /// the range is not mapped.
+    pub fn set_cache_mode(
+        &mut self,
+        virtual_addr: usize,
+        size: usize,
+        cache: CacheMode,
+    ) -> crate::common::logging::SystemResult<()> {
+        use crate::common::logging::SystemError;
+
+        if !self.initialized {
+            return Err(SystemError::InternalError);
+        }
+        let mapper = self.mapper.as_mut().unwrap();
+        let end = virtual_addr
+            .checked_add(size)
+            .ok_or(SystemError::InvalidArgument)? as u64;
+        let mut addr = virtual_addr as u64;
+        while addr < end {
+            let TranslateResult::Mapped { frame, flags, .. } =
+                mapper.translate(VirtAddr::new(addr))
+            else {
+                return Err(SystemError::InvalidArgument);
+            };
+            let new_flags = (flags - CacheMode::FLAGS_MASK) | cache.page_table_flags();
+            let start = VirtAddr::new(addr).align_down(frame.size());
+            let covered =
+                start.as_u64() >= virtual_addr as u64 && start.as_u64() + frame.size() <= end;
+            if new_flags != flags && !covered && !matches!(frame, MappedFrame::Size4KiB(_)) {
+                // Rewriting this page's own entry splits the huge page
+                // around it with unchanged flags; the next pass then sees
+                // 4 KiB leaves.
+                let page = VirtAddr::new(addr).align_down(Size4KiB::SIZE);
+                let phys = frame.start_address() + (page - start);
+                let phys_offset = mapper.phys_offset();
+                unsafe {
+                    crate::page_table::kernel::init::map_page_4k_l1(
+                        mapper.level_4_table_mut(),
+                        page,
+                        phys,
+                        flags - PageTableFlags::HUGE_PAGE,
+                        crate::page_table::constants::get_frame_allocator_mut(),
+                        phys_offset,
+                    )
+                }
+                .map_err(|_| SystemError::MappingFailed)?;
+                continue;
+            }
+            if new_flags != flags {
+                let result = unsafe {
+                    match frame {
+                        MappedFrame::Size4KiB(_) => mapper
+                            .update_flags(Page::<Size4KiB>::containing_address(start), new_flags)
+                            .map(|flush| flush.flush()),
+                        MappedFrame::Size2MiB(_) => mapper
+                            .update_flags(Page::<Size2MiB>::containing_address(start), new_flags)
+                            .map(|flush| flush.flush()),
+                        MappedFrame::Size1GiB(_) => mapper
+                            .update_flags(Page::<Size1GiB>::containing_address(start), new_flags)
+                            .map(|flush| flush.flush()),
+                    }
+                };
+                result.map_err(|_| SystemError::MappingFailed)?;
+            }
+            addr = start.as_u64() + frame.size();
+        }
+        Ok(())
+    }
 }
 
 impl PageTableHelper for ProcessPageTable {
@@ -118,6 +194,7 @@ impl PageTableHelper for ProcessPageTable {
             return Err(crate::common::logging::SystemError::InternalError);
         }
 
+        let flags = crate::page_table::supported_flags(flags);
         let mapper = self.mapper.as_mut().unwrap();
         let virtual_addr = x86_64::VirtAddr::new(virtual_addr as u64);
         let physical_addr = x86_64::PhysAddr::new(physical_addr as u64);
@@ -219,6 +296,7 @@ impl PageTableHelper for ProcessPageTable {
             return Err(crate::common::logging::SystemError::InternalError);
         }
 
+        let flags = crate::page_table::supported_flags(flags);
         let mapper = self.mapper.as_mut().unwrap();
         let page = x86_64::structures::paging::Page::<Size4KiB>::containing_address(
             x86_64::VirtAddr::new(virtual_addr as u64),
@@ -166,6 +166,7 @@ pub unsafe fn map_range_with_huge_pages<
     behavior: &str,
 ) -> Result<(), x86_64::structures::paging::mapper::MapToError<x86_64::structures::paging::Size4KiB>>
 {
+    let flags = crate::page_table::supported_flags(flags);
     unsafe {
         use x86_64::structures::paging::{Mapper, Page, PhysFrame, Size2MiB, Size4KiB};
 
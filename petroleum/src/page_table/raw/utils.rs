@@ -87,6 +87,7 @@ pub unsafe fn map_range_4kiB<A: FrameAllocator<Size4KiB>>(
     flags: PageTableFlags,
     behavior: &str,
 ) -> Result<(), x86_64::structures::paging::mapper::MapToError<Size4KiB>> {
+    let flags = crate::page_table::supported_flags(flags);
     unsafe {
         for i in 0..pages {
             let p_addr = phys + i * 4096;
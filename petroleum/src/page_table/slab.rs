@@ -0,0 +1,245 @@
+//! Fixed-size object caches.
+//!
+//! A [`SlabCache`] takes slabs of at least a page from the global heap and
+//! carves them into equal slots for one type.  Free slots are threaded into
+//! a list through their own storage, so allocating and freeing are a pointer
+//! swap under a short lock, and objects of the type no longer fragment the
+//! general heap.  Slabs are kept once carved and reused for later objects.
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use spin::Mutex;
+
+/// Smallest slab taken from the heap.
+const SLAB_BYTES: usize = 4096;
+
+/// A free slot, linked through the slot's own bytes.
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>,
+}
+
+struct Slabs {
+    free: Option<NonNull<FreeSlot>>,
+    slabs: Vec<NonNull<u8>>,
+    in_use: usize,
+}
+
+/// A cache of equal-sized slots for values of type `T`.
+pub struct SlabCache<T> {
+    slabs: Mutex<Slabs>,
+    _marker: PhantomData<T>,
+}
+
+// The raw pointers only ever point into slabs owned by the cache, and values
+// move in and out by value.
+unsafe impl<T: Send> Send for SlabCache<T> {}
+unsafe impl<T: Send> Sync for SlabCache<T> {}
+
+impl<T> SlabCache<T> {
+    const SLOT_ALIGN: usize = max(align_of::<T>(), align_of::<FreeSlot>());
+    const SLOT_SIZE: usize =
+        max(size_of::<T>(), size_of::<FreeSlot>()).next_multiple_of(Self::SLOT_ALIGN);
+    const SLAB_SIZE: usize = max(SLAB_BYTES, Self::SLOT_SIZE);
+    const SLOTS_PER_SLAB: usize = Self::SLAB_SIZE / Self::SLOT_SIZE;
+
+    pub const fn new() -> Self {
+        Self {
+            slabs: Mutex::new(Slabs {
+                free: None,
+                slabs: Vec::new(),
+                in_use: 0,
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Move `value` into a free slot, carving a new slab if there is none.
+    ///
+    /// Aborts through [`handle_alloc_error`] if the heap cannot supply a
+    /// slab.  The slot stays allocated until it is passed to
+    /// [`SlabCache::free`].
+    pub fn alloc(&self, value: T) -> NonNull<T> {
+        let slot = {
+            let mut slabs = self.slabs.lock();
+            let slot = match slabs.free {
+                Some(slot) => slot,
+                None => Self::grow(&mut slabs),
+            };
+            slabs.free = unsafe { slot.as_ref().next };
+            slabs.in_use += 1;
+            slot
+        };
+        let ptr = slot.cast::<T>();
+        unsafe { ptr.write(value) };
+        ptr
+    }
+
+    /// Drop the value at `ptr` and put its slot back on the free list.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from [`SlabCache::alloc`] on this cache and not
+    /// have been freed since.
+    pub unsafe fn free(&self, ptr: NonNull<T>) {
+        unsafe { ptr.drop_in_place() };
+        let slot = ptr.cast::<FreeSlot>();
+        let mut slabs = self.slabs.lock();
+        unsafe { slot.write(FreeSlot { next: slabs.free }) };
+        slabs.free = Some(slot);
+        slabs.in_use -= 1;
+    }
+
+    /// Move `value` into the cache, freeing the slot again when the returned
+    /// box is dropped.
+    pub fn boxed(&'static self, value: T) -> SlabBox<T> {
+        SlabBox {
+            ptr: self.alloc(value),
+            cache: self,
+        }
+    }
+
+    /// Slots currently holding a value.
+    pub fn in_use(&self) -> usize {
+        self.slabs.lock().in_use
+    }
+
+    /// Slots carved so far, in use or free.
+    pub fn capacity(&self) -> usize {
+        self.slabs.lock().slabs.len() * Self::SLOTS_PER_SLAB
+    }
+
+    fn slab_layout() -> Layout {
+        Layout::from_size_align(Self::SLAB_SIZE, Self::SLOT_ALIGN).unwrap()
+    }
+
+    /// Carve a new slab onto the free list and return its first slot, now
+    /// the head of the list.
+    fn grow(slabs: &mut Slabs) -> NonNull<FreeSlot> {
+        let layout = Self::slab_layout();
+        let Some(base) = NonNull::new(unsafe { alloc(layout) }) else {
+            handle_alloc_error(layout);
+        };
+        slabs.slabs.push(base);
+        for index in (0..Self::SLOTS_PER_SLAB).rev() {
+            let slot = unsafe { base.add(index * Self::SLOT_SIZE) }.cast::<FreeSlot>();
+            unsafe { slot.write(FreeSlot { next: slabs.free }) };
+            slabs.free = Some(slot);
+        }
+        base.cast()
+    }
+}
+
+impl<T> Default for SlabCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SlabCache<T> {
+    /// Return the slabs to the heap.  Values still in them are not dropped.
+    fn drop(&mut self) {
+        let layout = Self::slab_layout();
+        for slab in self.slabs.get_mut().slabs.drain(..) {
+            unsafe { dealloc(slab.as_ptr(), layout) };
+        }
+    }
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+/// An owned value in a [`SlabCache`] slot, like a `Box` from the cache.
+pub struct SlabBox<T: 'static> {
+    ptr: NonNull<T>,
+    cache: &'static SlabCache<T>,
+}
+
+unsafe impl<T: Send> Send for SlabBox<T> {}
+unsafe impl<T: Sync> Sync for SlabBox<T> {}
+
+impl<T> Deref for SlabBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for SlabBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for SlabBox<T> {
+    fn drop(&mut self) {
+        unsafe { self.cache.free(self.ptr) };
+    }
+}
+
+impl<T: Clone> Clone for SlabBox<T> {
+    fn clone(&self) -> Self {
+        self.cache.boxed((**self).clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SlabBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    #[test]
+    fn slots_are_never_handed_out_twice() {
+        let cache = SlabCache::<[u64; 5]>::new();
+        let mut live = BTreeMap::new();
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for round in 0..20_000u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Allocate more often than free so that the cache keeps growing.
+            if live.is_empty() || state % 5 < 3 {
+                let ptr = cache.alloc([round; 5]);
+                assert_eq!(ptr.as_ptr() as usize % align_of::<[u64; 5]>(), 0);
+                assert!(live.insert(ptr.as_ptr() as usize, round).is_none());
+            } else {
+                let key = *live.keys().nth(state as usize % live.len()).unwrap();
+                let round = live.remove(&key).unwrap();
+                let ptr = NonNull::new(key as *mut [u64; 5]).unwrap();
+                // Neighbouring slots never overlapped this one.
+                assert_eq!(unsafe { *ptr.as_ptr() }, [round; 5]);
+                unsafe { cache.free(ptr) };
+            }
+            assert_eq!(cache.in_use(), live.len());
+        }
+        assert!(cache.capacity() >= live.len());
+        for &key in live.keys() {
+            unsafe { cache.free(NonNull::new(key as *mut [u64; 5]).unwrap()) };
+        }
+        assert_eq!(cache.in_use(), 0);
+    }
+
+    #[test]
+    fn freed_slots_are_reused_before_growing() {
+        static CACHE: SlabCache<u128> = SlabCache::new();
+        let first = CACHE.boxed(1);
+        let address = &*first as *const u128;
+        drop(first);
+        let second = CACHE.boxed(2);
+        assert_eq!(&*second as *const u128, address);
+        assert_eq!(*second.clone(), 2);
+        assert_eq!(CACHE.capacity(), SLAB_BYTES / size_of::<u128>());
+    }
+}
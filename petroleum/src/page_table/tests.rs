@@ -61,3 +61,16 @@ fn test_alignment() {
     assert!(is_aligned(4096, 4096));
     assert!(!is_aligned(4097, 4096));
 }
+
+#[test]
+fn no_execute_is_dropped_only_without_nx() {
+    use super::without_unsupported_nx;
+    use x86_64::structures::paging::PageTableFlags as F;
+    let data = F::PRESENT | F::WRITABLE | F::NO_EXECUTE;
+    assert_eq!(without_unsupported_nx(data, true), data);
+    assert_eq!(
+        without_unsupported_nx(data, false),
+        F::PRESENT | F::WRITABLE
+    );
+    assert_eq!(without_unsupported_nx(F::PRESENT, false), F::PRESENT);
+}
@@ -428,7 +428,7 @@ pub mod Flags {
 
     /// Common flag combinations
     pub const KERNEL_DATA: u64 = PRESENT | WRITABLE | NO_EXECUTE;
-    pub const KERNEL_CODE: u64 = PRESENT | NO_EXECUTE;
+    pub const KERNEL_CODE: u64 = PRESENT;
     pub const USER_DATA: u64 = PRESENT | WRITABLE | USER_ACCESSIBLE | NO_EXECUTE;
     pub const USER_CODE: u64 = PRESENT | USER_ACCESSIBLE;
     pub const DEVICE_MMIO: u64 = PRESENT | WRITABLE | NO_EXECUTE | NO_CACHE | WRITE_THROUGH;
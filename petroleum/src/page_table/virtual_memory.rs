@@ -19,6 +19,7 @@ use x86_64::{
     },
 };
 
+use crate::graphics::framebuffer_mapper::CacheMode;
 use crate::page_table::allocator::bitmap::BitmapFrameAllocator;
 
 // ── Mapping metadata ──────────────────────────────────────────
@@ -101,18 +102,19 @@ impl VirtualMemoryContext {
 
     // ── High-level mapping ───────────────────────────────────
 
-    /// Map a framebuffer (MMIO, write-combining).
+    /// Map a framebuffer (MMIO) with the given cache mode, normally
+    /// [`CacheMode::WriteCombining`].
     ///
     /// Returns the virtual address.
     ///
-    /// Uses WRITE_THROUGH (PWT=1, PCD=0) which targets PAT slot 1.
-    /// After `init_pat()` has set PAT[1]=WC, this gives write-combining
-    /// semantics — essential for PCI MMIO framebuffer performance.
+    /// Write-combining selects PAT slot 1 (PWT=1, PCD=0), which is WC only
+    /// once `init_pat()` has run; see [`CacheMode`] for the full table.
     pub fn map_framebuffer(
         &mut self,
         phys: u64,
         size_bytes: u64,
         preferred_virt: Option<u64>,
+        cache: CacheMode,
     ) -> Result<u64, crate::MemoryError> {
         let va = preferred_virt.unwrap_or(phys + self.physical_offset);
 
@@ -125,7 +127,7 @@ impl VirtualMemoryContext {
             flags: PageTableFlags::PRESENT
                 | PageTableFlags::WRITABLE
                 | PageTableFlags::NO_EXECUTE
-                | PageTableFlags::WRITE_THROUGH,
+                | cache.page_table_flags(),
             owned: false,
         });
 
@@ -159,7 +161,12 @@ impl VirtualMemoryContext {
             let frame = PhysFrame::<Size2MiB>::containing_address(PhysAddr::new(p));
             unsafe {
                 mapper
-                    .map_to(page, frame, flags, &mut self.frame_allocator)
+                    .map_to(
+                        page,
+                        frame,
+                        crate::page_table::supported_flags(flags),
+                        &mut self.frame_allocator,
+                    )
                     .map_err(|_| crate::MemoryError::MappingFailed)?
                     .flush();
             }
@@ -248,7 +255,9 @@ impl VirtualMemoryContext {
                 phys_start: phys,
                 virt_start: va,
                 size_bytes: size,
-                flags: PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                flags: PageTableFlags::PRESENT
+                    | PageTableFlags::WRITABLE
+                    | PageTableFlags::NO_EXECUTE,
                 owned: false,
             });
         }
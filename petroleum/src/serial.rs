@@ -23,6 +23,7 @@ pub unsafe fn write_serial_bytes(port_addr: u16, status_port_addr: u16, bytes: &
     }
 }
 
+use crate::common::logging::{SystemError, SystemResult};
 use crate::common::{EfiSimpleTextOutput, EfiStatus};
 use core::fmt;
 use spin::Mutex;
@@ -38,15 +39,147 @@ pub trait SerialPortOps {
     fn line_status_port(&self) -> Port<u8>;
 }
 
+/// Parity mode for [`SerialPort::configure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    /// Parity bit always 1.
+    Mark,
+    /// Parity bit always 0.
+    Space,
+}
+
+impl Parity {
+    /// Line control register bits 3..=5 for this parity mode.
+    const fn lcr_bits(self) -> u8 {
+        match self {
+            Parity::None => 0x00,
+            Parity::Odd => 0x08,
+            Parity::Even => 0x18,
+            Parity::Mark => 0x28,
+            Parity::Space => 0x38,
+        }
+    }
+}
+
+/// Baud rate at divisor 1 (the 1.8432 MHz UART clock divided by 16).
+pub const UART_BASE_BAUD: u32 = 115_200;
+
+/// Line control register: divisor latch access bit.
+const LCR_DLAB: u8 = 0x80;
+/// Line status register: a received byte is waiting in the RBR.
+const LSR_DATA_READY: u8 = 0x01;
+/// Interrupt enable register: received data available.
+const IER_RX_AVAILABLE: u8 = 0x01;
+/// Modem control register: DTR, RTS and OUT2 (OUT2 gates the IRQ line).
+const MCR_DTR_RTS_OUT2: u8 = 0x0B;
+
+/// Divisor programmed by [`SerialPort::init`].
+const DEFAULT_DIVISOR: u16 = 3;
+/// Line control programmed by [`SerialPort::init`]: 8 data bits, no parity,
+/// one stop bit.
+const DEFAULT_LINE_CTRL: u8 = 0x03;
+
+/// Line control register value for the given framing.
+fn line_ctrl_bits(data_bits: u8, parity: Parity, stop_bits: u8) -> SystemResult<u8> {
+    if !(5..=8).contains(&data_bits) {
+        return Err(SystemError::InvalidArgument);
+    }
+    let stop = match stop_bits {
+        1 => 0x00,
+        2 => 0x04,
+        _ => return Err(SystemError::InvalidArgument),
+    };
+    Ok((data_bits - 5) | stop | parity.lcr_bits())
+}
+
+/// Divisor latch value for `baud`, which must divide [`UART_BASE_BAUD`].
+fn divisor_for(baud: u32) -> SystemResult<u16> {
+    if baud == 0 || !UART_BASE_BAUD.is_multiple_of(baud) {
+        return Err(SystemError::InvalidArgument);
+    }
+    Ok((UART_BASE_BAUD / baud) as u16)
+}
+
+/// The UART registers [`SerialPort::configure`] programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UartRegister {
+    Data,
+    IrqEnable,
+    LineCtrl,
+}
+
+/// Byte access to a UART's registers, so that programming them can be
+/// tested against a model of the chip.
+trait UartRegisters {
+    fn read(&self, reg: UartRegister) -> u8;
+    fn write(&self, reg: UartRegister, value: u8);
+}
+
+impl<S: SerialPortOps> UartRegisters for S {
+    fn read(&self, reg: UartRegister) -> u8 {
+        unsafe { uart_port(self, reg).read() }
+    }
+
+    fn write(&self, reg: UartRegister, value: u8) {
+        unsafe { uart_port(self, reg).write(value) }
+    }
+}
+
+fn uart_port<S: SerialPortOps>(ops: &S, reg: UartRegister) -> Port<u8> {
+    match reg {
+        UartRegister::Data => ops.data_port(),
+        UartRegister::IrqEnable => ops.irq_enable_port(),
+        UartRegister::LineCtrl => ops.line_ctrl_port(),
+    }
+}
+
+/// Program the divisor latch and line control.  With DLAB set, the data
+/// and IRQ-enable registers address the low and high divisor bytes.
+#[cfg_attr(feature = "std", allow(dead_code))]
+fn program_uart(uart: &impl UartRegisters, divisor: u16, line_ctrl: u8) {
+    uart.write(UartRegister::LineCtrl, LCR_DLAB);
+    uart.write(UartRegister::Data, divisor as u8);
+    uart.write(UartRegister::IrqEnable, (divisor >> 8) as u8);
+    uart.write(UartRegister::LineCtrl, line_ctrl);
+}
+
+/// The line control register, without DLAB.
+#[cfg_attr(feature = "std", allow(dead_code))]
+fn read_line_ctrl(uart: &impl UartRegisters) -> u8 {
+    uart.read(UartRegister::LineCtrl) & !LCR_DLAB
+}
+
+/// The divisor latch, read with DLAB briefly set.
+#[cfg_attr(feature = "std", allow(dead_code))]
+fn read_divisor(uart: &impl UartRegisters) -> u16 {
+    let saved = uart.read(UartRegister::LineCtrl);
+    uart.write(UartRegister::LineCtrl, saved | LCR_DLAB);
+    let low = uart.read(UartRegister::Data) as u16;
+    let high = uart.read(UartRegister::IrqEnable) as u16;
+    uart.write(UartRegister::LineCtrl, saved);
+    (high << 8) | low
+}
+
 /// Represents a serial port for communication.
 pub struct SerialPort<S: SerialPortOps> {
     ops: S,
+    /// Divisor latch value last programmed into the UART.
+    divisor: u16,
+    /// Line control value (without DLAB) last programmed into the UART.
+    line_ctrl: u8,
 }
 
 impl<S: SerialPortOps> SerialPort<S> {
     /// Creates a new instance of the SerialPort.
     pub const fn new(ops: S) -> SerialPort<S> {
-        SerialPort { ops }
+        SerialPort {
+            ops,
+            divisor: DEFAULT_DIVISOR,
+            line_ctrl: DEFAULT_LINE_CTRL,
+        }
     }
 
     /// Initializes the serial port.
@@ -57,13 +190,63 @@ impl<S: SerialPortOps> SerialPort<S> {
             self.ops.irq_enable_port(),
             self.ops.fifo_ctrl_port(),
             self.ops.modem_ctrl_port(),
-            0x80,
-            0x03,
+            LCR_DLAB,
+            DEFAULT_DIVISOR as u8,
             0x00,
-            0x03,
+            DEFAULT_LINE_CTRL,
             0xC7,
-            0x0B
+            MCR_DTR_RTS_OUT2
         );
+        self.divisor = DEFAULT_DIVISOR;
+        self.line_ctrl = DEFAULT_LINE_CTRL;
+    }
+
+    /// Reprograms the baud rate and framing.
+    ///
+    /// `baud` must divide [`UART_BASE_BAUD`] evenly, `data_bits` must be
+    /// 5..=8 and `stop_bits` 1 or 2; otherwise the port is left untouched and
+    /// `InvalidArgument` is returned.  With 5 data bits, 2 stop bits means
+    /// 1.5 on a 16550.
+    pub fn configure(
+        &mut self,
+        baud: u32,
+        data_bits: u8,
+        parity: Parity,
+        stop_bits: u8,
+    ) -> SystemResult<()> {
+        let divisor = divisor_for(baud)?;
+        let line_ctrl = line_ctrl_bits(data_bits, parity, stop_bits)?;
+
+        #[cfg(all(not(feature = "std"), not(test)))]
+        program_uart(&self.ops, divisor, line_ctrl);
+
+        self.divisor = divisor;
+        self.line_ctrl = line_ctrl;
+        Ok(())
+    }
+
+    /// Reads back the line control register (DLAB cleared).
+    pub fn line_control(&self) -> u8 {
+        #[cfg(all(not(feature = "std"), not(test)))]
+        {
+            read_line_ctrl(&self.ops)
+        }
+        #[cfg(any(feature = "std", test))]
+        {
+            self.line_ctrl
+        }
+    }
+
+    /// Reads back the divisor latch.
+    pub fn divisor(&self) -> u16 {
+        #[cfg(all(not(feature = "std"), not(test)))]
+        {
+            read_divisor(&self.ops)
+        }
+        #[cfg(any(feature = "std", test))]
+        {
+            self.divisor
+        }
     }
 
     /// Writes a single byte to the serial port.
@@ -88,6 +271,39 @@ impl<S: SerialPortOps> SerialPort<S> {
             self.write_byte(b);
         }
     }
+
+    /// Reads one received byte, if the line status register reports data
+    /// ready.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        #[cfg(all(not(feature = "std"), not(test)))]
+        unsafe {
+            if self.ops.line_status_port().read() & LSR_DATA_READY != 0 {
+                Some(self.ops.data_port().read())
+            } else {
+                None
+            }
+        }
+        #[cfg(any(feature = "std", test))]
+        {
+            None
+        }
+    }
+
+    /// Enables the received-data-available interrupt.
+    ///
+    /// Only the receive interrupt is turned on; transmission keeps polling
+    /// the line status register and never raises an interrupt.
+    pub fn enable_receive_interrupt(&mut self) {
+        #[cfg(all(not(feature = "std"), not(test)))]
+        unsafe {
+            let mut lcr = self.ops.line_ctrl_port();
+            let saved = lcr.read();
+            lcr.write(saved & !LCR_DLAB);
+            self.ops.irq_enable_port().write(IER_RX_AVAILABLE);
+            lcr.write(saved);
+            self.ops.modem_ctrl_port().write(MCR_DTR_RTS_OUT2);
+        }
+    }
 }
 
 /// COM1 implementation
@@ -139,6 +355,18 @@ impl SerialManager {
         self.serial_port.init();
     }
 
+    /// Reconfigures COM1; see [`SerialPort::configure`].
+    pub fn configure_serial(
+        &mut self,
+        baud: u32,
+        data_bits: u8,
+        parity: Parity,
+        stop_bits: u8,
+    ) -> SystemResult<()> {
+        self.serial_port
+            .configure(baud, data_bits, parity, stop_bits)
+    }
+
     pub fn init_uefi(&mut self, con_out: *mut EfiSimpleTextOutput) {
         self.uefi_writer.init(con_out);
     }
@@ -272,6 +500,16 @@ pub fn _print(args: fmt::Arguments) {
     let _ = args;
 }
 
+/// Reads one received byte from COM1 without a SerialManager (polled).
+pub fn com1_read_byte() -> Option<u8> {
+    SerialPort::new(Com1Ports).read_byte()
+}
+
+/// Enables the COM1 receive interrupt (IRQ 4) without a SerialManager.
+pub fn com1_enable_receive_interrupt() {
+    SerialPort::new(Com1Ports).enable_receive_interrupt();
+}
+
 /// Initializes the serial port and returns a SerialManager capability.
 pub fn serial_init() -> SerialManager {
     let mut manager = SerialManager::new();
@@ -405,9 +643,80 @@ pub fn debug_print_no_lock<T: DebugNoLock>(value: T) {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use core::cell::Cell;
+
     #[test]
     fn test_uefi_writer_new() {
         let writer = super::UefiWriter::new();
         assert!(writer.con_out.is_null());
     }
+
+    /// A 16550's divisor latch and line control, with the data and
+    /// IRQ-enable registers switched to the latch while DLAB is set.
+    #[derive(Default)]
+    struct FakeUart {
+        line_ctrl: Cell<u8>,
+        divisor_low: Cell<u8>,
+        divisor_high: Cell<u8>,
+        data: Cell<u8>,
+        irq_enable: Cell<u8>,
+    }
+
+    impl FakeUart {
+        fn register(&self, reg: UartRegister) -> &Cell<u8> {
+            let dlab = self.line_ctrl.get() & LCR_DLAB != 0;
+            match reg {
+                UartRegister::LineCtrl => &self.line_ctrl,
+                UartRegister::Data if dlab => &self.divisor_low,
+                UartRegister::IrqEnable if dlab => &self.divisor_high,
+                UartRegister::Data => &self.data,
+                UartRegister::IrqEnable => &self.irq_enable,
+            }
+        }
+    }
+
+    impl UartRegisters for FakeUart {
+        fn read(&self, reg: UartRegister) -> u8 {
+            self.register(reg).get()
+        }
+
+        fn write(&self, reg: UartRegister, value: u8) {
+            self.register(reg).set(value)
+        }
+    }
+
+    #[test]
+    fn test_configure_9600_8n1() {
+        let uart = FakeUart::default();
+        uart.irq_enable.set(0x01);
+        let line_ctrl = line_ctrl_bits(8, Parity::None, 1).unwrap();
+        program_uart(&uart, divisor_for(9600).unwrap(), line_ctrl);
+        assert_eq!((uart.divisor_low.get(), uart.divisor_high.get()), (12, 0));
+        assert_eq!(uart.line_ctrl.get(), 0x03);
+        // DLAB is clear again, so the IRQ enables survived.
+        assert_eq!(uart.irq_enable.get(), 0x01);
+        assert_eq!((read_divisor(&uart), read_line_ctrl(&uart)), (12, 0x03));
+
+        let line_ctrl = line_ctrl_bits(7, Parity::Even, 2).unwrap();
+        program_uart(&uart, divisor_for(50).unwrap(), line_ctrl);
+        assert_eq!(
+            (uart.divisor_low.get(), uart.divisor_high.get()),
+            (0x00, 0x09)
+        );
+        assert_eq!((read_divisor(&uart), read_line_ctrl(&uart)), (2304, 0x1E));
+    }
+
+    #[test]
+    fn test_configure_rejects_invalid_settings() {
+        let mut port = SerialPort::new(Com1Ports);
+        for (baud, data_bits, stop_bits) in [(7000, 8, 1), (0, 8, 1), (9600, 9, 1), (9600, 8, 3)] {
+            assert_eq!(
+                port.configure(baud, data_bits, Parity::None, stop_bits),
+                Err(SystemError::InvalidArgument)
+            );
+        }
+        assert_eq!(port.divisor(), DEFAULT_DIVISOR);
+        assert_eq!(port.line_control(), DEFAULT_LINE_CTRL);
+    }
 }
@@ -216,18 +216,19 @@ pub fn find_heap_start(descriptors: &[impl MemoryDescriptorValidator]) -> PhysAd
     // Find the lowest suitable memory region within first 64MB from EfiConventionalMemory with sufficient size for heap
     // This ensures heap is within the identity-mapped range during page table reinitialization
     const HEAP_PAGES: u64 = 256; // approx 1MB for heap + structures
-    for (i, desc) in descriptors.iter().enumerate() {
-        crate::debug_log_no_alloc!("find_heap_start: Checking descriptor {}", i);
-        let start = desc.get_physical_start();
-        if desc.get_type() == crate::common::EfiMemoryType::EfiConventionalMemory as u32
-            && desc.get_page_count() >= HEAP_PAGES
-            && start >= 0x100000 // Avoid first 1MB reserved region
-            && start < 0x4000000 // within first 64MB
-            && crate::common::utils::calculate_region_end(start, desc.get_page_count()) <= 0x4000000
-        // ensure entire region fits
+    let map = crate::page_table::memory_map::MemoryMap::new(descriptors);
+    for region in map.iter_usable() {
+        if region.mem_type == crate::common::EfiMemoryType::EfiConventionalMemory as u32
+            && region.len() >= HEAP_PAGES * 4096
+            && region.start >= 0x100000 // Avoid first 1MB reserved region
+            && region.end <= 0x4000000
+        // ensure entire region fits within first 64MB
         {
-            crate::debug_log_no_alloc!("find_heap_start: Found suitable region at 0x{:x}", start);
-            return PhysAddr::new(start);
+            crate::debug_log_no_alloc!(
+                "find_heap_start: Found suitable region at 0x{:x}",
+                region.start
+            );
+            return PhysAddr::new(region.start);
         }
     }
     // Fallback if no suitable memory found within first 64MB
@@ -45,6 +45,15 @@ mod macro_tests {
         assert!(true);
     }
 
+    #[test]
+    fn test_kassert_passes_without_halting() {
+        let frames = [1, 2, 3];
+        petroleum::kassert!(frames.len() == 3);
+        petroleum::kassert!(frames[0] == 1, "first frame is {}", frames[0]);
+        petroleum::kassert_eq!(frames.len(), 3);
+        petroleum::kassert_eq!(frames[2], 3, "last of {} frames", frames.len(),);
+    }
+
     // Future: If macro testing becomes possible, the original tests covered:
     // - ensure!(condition, error) for early return on error
     // - ensure_with_msg!(condition, error, message) for early return with context
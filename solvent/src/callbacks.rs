@@ -41,6 +41,12 @@ pub struct SolventCallbacks {
     pub device_list: Option<fn() -> Vec<DeviceEntry>>,
     pub mounted_drive_list: Option<MountedDriveListCallback>,
     pub usb_poll: Option<fn() -> bool>,
+    /// Pop one byte received on the serial console.
+    pub serial_read: Option<fn() -> Option<u8>>,
+    pub serial_available: Option<fn() -> bool>,
+    /// Sleep until console input may have arrived, returning at the latest
+    /// on the next timer tick.
+    pub wait_input: Option<fn()>,
     pub settings_save: Option<fn()>,
     pub kernel_log: Option<fn() -> String>,
     pub metrics: Option<fn() -> String>,
@@ -69,6 +75,9 @@ impl SolventCallbacks {
             device_list: None,
             mounted_drive_list: None,
             usb_poll: None,
+            serial_read: None,
+            serial_available: None,
+            wait_input: None,
             settings_save: None,
             kernel_log: None,
             metrics: None,
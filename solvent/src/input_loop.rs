@@ -7,7 +7,7 @@ use spin::Mutex;
 use alloc::string::String;
 
 use crate::{
-    MOUSE_SENSITIVITY, PREV_MOUSE_BUTTONS, RUNTIME_CONTEXT, RuntimeState, editor_bridge,
+    FB_DIMS, MOUSE_SENSITIVITY, PREV_MOUSE_BUTTONS, RUNTIME_CONTEXT, RuntimeState, editor_bridge,
     network_manager, settings_bridge,
 };
 
@@ -35,19 +35,30 @@ macro_rules! mouse_edge {
     };
 }
 
+/// Move a cursor coordinate by `delta`, keeping it on a screen `limit`
+/// pixels wide (or tall).
+fn move_axis(position: i16, delta: i16, limit: u32) -> i16 {
+    let max = i16::try_from(limit.saturating_sub(1)).unwrap_or(i16::MAX);
+    position.saturating_add(delta).clamp(0, max)
+}
+
 pub fn poll_mouse_state() {
     let ps2_state = nitrogen::ps2::mouse::consume_state();
     let dx = ps2_state.get_x();
     let dy = ps2_state.get_y();
-    let buttons = nitrogen::ps2::mouse::mouse_buttons();
+    let buttons = ps2_state.buttons();
     let mut mouse = MOUSE_STATE.lock();
     let old_x = mouse.x;
     let old_y = mouse.y;
     let sensitivity = MOUSE_SENSITIVITY.load(core::sync::atomic::Ordering::Relaxed);
-    mouse.x = mouse.x.wrapping_add(dx.wrapping_mul(sensitivity));
-    mouse.y = mouse
-        .y
-        .wrapping_add(dy.wrapping_mul(sensitivity).wrapping_neg());
+    let (width, height, _) = *FB_DIMS.lock();
+    mouse.x = move_axis(mouse.x, dx.saturating_mul(sensitivity), width);
+    // PS/2 reports y growing upwards.
+    mouse.y = move_axis(
+        mouse.y,
+        dy.saturating_mul(sensitivity).saturating_neg(),
+        height,
+    );
     mouse.buttons = buttons;
     let cursor_x = mouse.x as i32;
     let cursor_y = mouse.y as i32;
@@ -61,6 +72,16 @@ pub fn poll_mouse_state() {
         }));
     }
 
+    let wheel = ps2_state.get_wheel();
+    if wheel != 0
+        && let Some(queue) = RUNTIME_CONTEXT.event_queue().as_mut()
+    {
+        queue.push(Event::Input(InputEvent::MouseWheel {
+            dx: 0,
+            dy: i32::from(wheel),
+        }));
+    }
+
     let mut previous_buttons = PREV_MOUSE_BUTTONS.lock();
     let previous = *previous_buttons;
     if buttons != previous
@@ -335,3 +356,17 @@ fn explorer_handle_key(runtime: &mut RuntimeState, scancode: u8, pressed: bool)
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::move_axis;
+
+    #[test]
+    fn cursor_position_stays_on_screen() {
+        assert_eq!(move_axis(512, 20, 1024), 532);
+        assert_eq!(move_axis(5, -20, 1024), 0);
+        assert_eq!(move_axis(1020, 20, 1024), 1023);
+        assert_eq!(move_axis(i16::MAX - 1, i16::MAX, u32::MAX), i16::MAX);
+        assert_eq!(move_axis(3, 1, 0), 0);
+    }
+}
@@ -178,15 +178,28 @@ impl carrier::terminal::Terminal for LatticeTerminal {
         }
     }
     fn read_byte(&mut self) -> Option<u8> {
+        let callbacks = crate::RUNTIME_CONTEXT.callback_snapshot();
         loop {
-            if let Some(ch) = nitrogen::ps2::keyboard::read_char() {
+            if let Some(ch) = nitrogen::ps2::keyboard::try_read() {
+                return Some(ch);
+            }
+            if let Some(ch) = callbacks.serial_read.and_then(|read| read()) {
                 return Some(ch);
             }
             crate::runtime_tick_no_fb();
+            // Between frames, sleep until input arrives or the next tick
+            // rather than spinning.
+            if let Some(wait) = callbacks.wait_input {
+                wait();
+            }
         }
     }
     fn input_available(&self) -> bool {
         nitrogen::ps2::keyboard::input_available()
+            || crate::RUNTIME_CONTEXT
+                .callback_snapshot()
+                .serial_available
+                .is_some_and(|available| available())
     }
     fn set_stdin(&mut self, data: String) {
         *crate::PIPE_STDIN.lock() = Some(data);
@@ -3,7 +3,13 @@
 
 //! User space system call wrappers for toluene
 
-use toluene::sys::{current_pid, exit_process, write, yield_now};
+use core::sync::atomic::{AtomicU32, Ordering};
+use fullerene_abi::memory_protection;
+use toluene::sys::{
+    create_thread, current_pid, exit_process, exit_thread, fork, futex_wait, futex_wake,
+    join_thread, mem_info, mmap, munmap, sem_create, sem_destroy, sem_post, sem_wait, shm_attach,
+    shm_create, shm_detach, sleep_ticks, uptime_ticks, waitpid, write, yield_now,
+};
 
 petroleum::define_panic_handler!();
 
@@ -14,26 +20,366 @@ macro_rules! safe_print {
     };
 }
 
+/// Print `label` followed by the caller's PID.
+fn print_pid(label: &[u8]) {
+    let mut pid_buffer = [0u8; 20];
+    let len = petroleum::serial::format_dec_to_buffer(current_pid(), &mut pid_buffer);
+    safe_print!(1, label);
+    safe_print!(1, &pid_buffer[..len]);
+    safe_print!(1, b"\n");
+}
+
+/// Fork `count` children that exit at once and reap each, returning how
+/// many bytes less are free afterwards.
+fn fork_and_reap(count: usize) -> Option<u64> {
+    let mut free_before = 0;
+    // The first child also warms up anything allocated once, so count from
+    // after it.
+    for round in 0..=count {
+        if round == 1 {
+            free_before = mem_info().ok()?.free;
+        }
+        match fork().ok()? {
+            0 => exit_process(0),
+            child => {
+                waitpid(child).ok()?;
+            }
+        }
+    }
+    Some(free_before.saturating_sub(mem_info().ok()?.free))
+}
+
+/// Fork a child and have it and the parent each print `rounds` lines,
+/// yielding after every one.  A turn counter in shared memory checks that
+/// the two strictly alternate; returns whether they did.
+fn ping_pong(rounds: u32) -> Option<bool> {
+    let id = shm_create(4096).ok()?;
+    let counter = shm_attach(id).ok()?;
+    let child = fork().ok()?;
+    if child == 0 {
+        // Attachments are not inherited.
+        let in_turn = shm_attach(id).is_ok_and(|counter| take_turns(counter, 1, b"pong\n", rounds));
+        exit_process(if in_turn { 0 } else { 1 });
+    }
+    let in_turn = take_turns(counter, 0, b"ping\n", rounds);
+    let child_in_turn = waitpid(child).ok()? == 0;
+    let _ = shm_detach(id);
+    Some(in_turn && child_in_turn)
+}
+
+/// Take `rounds` turns at printing `label`, each only when the counter at
+/// `counter` has parity `side`, and yield after each.
+fn take_turns(counter: usize, side: u32, label: &[u8], rounds: u32) -> bool {
+    let turn = unsafe { &*(counter as *const AtomicU32) };
+    for _ in 0..rounds {
+        if turn.load(Ordering::Acquire) % 2 != side {
+            return false;
+        }
+        safe_print!(1, label);
+        turn.fetch_add(1, Ordering::Release);
+        yield_now();
+    }
+    true
+}
+
+/// Slots in the bounded buffer of [`producer_consumer`].
+const BUFFER_SLOTS: usize = 4;
+
+/// Fork a consumer and pass it 1..=`items` through a ring buffer of
+/// [`BUFFER_SLOTS`] in shared memory, with an `empty` semaphore counting
+/// free slots and a `full` one counting filled slots.  Returns whether the
+/// consumer saw every item, in order.
+fn producer_consumer(items: u32) -> Option<bool> {
+    let id = shm_create(4096).ok()?;
+    let buffer = shm_attach(id).ok()?;
+    let empty = sem_create(BUFFER_SLOTS as u32).ok()?;
+    let full = sem_create(0).ok()?;
+    let consumer = fork().ok()?;
+    if consumer == 0 {
+        // Attachments are not inherited.
+        let in_order = shm_attach(id).is_ok_and(|buffer| consume(buffer, empty, full, items));
+        exit_process(if in_order { 0 } else { 1 });
+    }
+    let slots = buffer as *mut u32;
+    let mut produced = true;
+    for item in 1..=items {
+        if sem_wait(empty).is_err() {
+            produced = false;
+            break;
+        }
+        let slot = (item as usize - 1) % BUFFER_SLOTS;
+        unsafe { slots.add(slot).write_volatile(item) };
+        if sem_post(full).is_err() {
+            produced = false;
+            break;
+        }
+    }
+    let consumed = waitpid(consumer).ok()? == 0;
+    let _ = sem_destroy(empty);
+    let _ = sem_destroy(full);
+    let _ = shm_detach(id);
+    Some(produced && consumed)
+}
+
+/// Take `items` items out of the ring buffer at `buffer`, checking that
+/// they count up from 1.
+fn consume(buffer: usize, empty: u64, full: u64, items: u32) -> bool {
+    let slots = buffer as *const u32;
+    for expected in 1..=items {
+        if sem_wait(full).is_err() {
+            return false;
+        }
+        let slot = (expected as usize - 1) % BUFFER_SLOTS;
+        let item = unsafe { slots.add(slot).read_volatile() };
+        if item != expected || sem_post(empty).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Futex word the thread of [`thread_futex`] waits on until it is nonzero.
+static GO: AtomicU32 = AtomicU32::new(0);
+/// Set by that thread once it is about to wait, and again once it is woken.
+static THREAD_STAGE: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn futex_waiter() -> ! {
+    THREAD_STAGE.store(1, Ordering::Release);
+    while GO.load(Ordering::Acquire) == 0 {
+        if futex_wait(&GO, 0).is_err() && GO.load(Ordering::Acquire) == 0 {
+            exit_thread(1);
+        }
+    }
+    THREAD_STAGE.store(2, Ordering::Release);
+    exit_thread(0);
+}
+
+/// Start a thread that blocks on a futex word, then wake it from this, the
+/// process's main thread.  Both must key the word alike for the wake to
+/// find the waiter; returns whether it did and the thread finished.
+fn thread_futex() -> Option<bool> {
+    const STACK_PAGES: usize = 4;
+    let stack = mmap(
+        STACK_PAGES,
+        memory_protection::READ | memory_protection::WRITE,
+    )
+    .ok()?;
+    // Entered as if called: the return address slot is taken.
+    let thread = create_thread(futex_waiter, stack + STACK_PAGES * 4096 - 8).ok()?;
+    while THREAD_STAGE.load(Ordering::Acquire) == 0 {
+        yield_now();
+    }
+    // Let it get from the flag into the wait.
+    sleep_ticks(2).ok()?;
+    GO.store(1, Ordering::Release);
+    let woken = futex_wake(&GO, 1).ok()?;
+    let joined = join_thread(thread).ok()? == 0;
+    let _ = munmap(stack, STACK_PAGES);
+    Some(woken == 1 && joined && THREAD_STAGE.load(Ordering::Acquire) == 2)
+}
+
+/// Point GS at user data, then take timer interrupts in ring 3, a context
+/// switch and a few syscalls.  The kernel finds its per-CPU block through
+/// GS, so this only survives if entering the kernel swaps the user GS base
+/// out; returns whether the kernel still knew who we were afterwards.
+fn user_gs_load(spin_us: u64) -> Option<bool> {
+    let pid = current_pid();
+    // SAFETY: the user data selector is the one SS already holds; loading it
+    // into GS zeroes the user GS base, which this program never uses.
+    unsafe {
+        core::arch::asm!("mov {0:x}, ss", "mov gs, {0:x}", out(reg) _, options(nostack));
+    }
+    let start = uptime_ticks()?;
+    while uptime_ticks()? < start + spin_us {
+        core::hint::spin_loop();
+    }
+    yield_now();
+    sleep_ticks(1).ok()?;
+    Some(current_pid() == pid)
+}
+
+/// Fork a child that reads through the kernel's physical-memory window,
+/// which is mapped without `USER_ACCESSIBLE`.  From ring 3 the read must
+/// fault and the kernel end the child with a nonzero exit before it can
+/// report success; returns whether it did.
+fn kernel_read() -> Option<bool> {
+    match fork().ok()? {
+        0 => {
+            let kernel_page = petroleum::common::uefi::PHYSICAL_MEMORY_OFFSET_BASE as *const u64;
+            core::hint::black_box(unsafe { core::ptr::read_volatile(kernel_page) });
+            exit_process(0);
+        }
+        child => Some(waitpid(child).ok()? != 0),
+    }
+}
+
+/// Fork a child that recurses until it runs off the bottom of its stack.
+/// The guard page below the stack must stop it with a fault, which the
+/// kernel reports as a nonzero exit; returns whether it did.
+fn stack_overflow() -> Option<bool> {
+    #[allow(unconditional_recursion)]
+    fn recurse(depth: u64) -> u64 {
+        let frame = core::hint::black_box([depth; 64]);
+        recurse(depth + 1) + frame[0]
+    }
+    match fork().ok()? {
+        0 => {
+            core::hint::black_box(recurse(0));
+            exit_process(0);
+        }
+        child => Some(waitpid(child).ok()? != 0),
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn main() -> ! {
     // Write initial message to stdout
     safe_print!(1, b"Hello from toluene user program!\n");
 
+    // Checks that went wrong; any fails the run
+    let mut failures = 0;
+
     // Get our PID and display it
-    let pid = current_pid();
-    let mut pid_buffer = [0u8; 20];
-    let len = petroleum::serial::format_dec_to_buffer(pid, &mut pid_buffer);
-    let pid_msg = &pid_buffer[..len];
-    safe_print!(1, b"My PID is: ");
-    safe_print!(1, pid_msg);
-    safe_print!(1, b"\n");
+    print_pid(b"My PID is: ");
 
-    // Sleep a bit to simulate work
-    for _ in 0..10 {
-        yield_now();
+    // Fork: both branches report their own PID
+    match fork() {
+        Ok(0) => {
+            print_pid(b"Forked child, PID: ");
+            exit_process(0);
+        }
+        Ok(child) => {
+            print_pid(b"Fork parent, PID: ");
+            if waitpid(child).is_err() {
+                safe_print!(1, b"wait failed\n");
+                failures += 1;
+            }
+        }
+        Err(_) => {
+            safe_print!(1, b"fork failed\n");
+            failures += 1;
+        }
+    }
+
+    // Exiting children must give back every frame they took
+    safe_print!(1, b"Forking and reaping 64 children...\n");
+    match fork_and_reap(64) {
+        Some(0) => {
+            safe_print!(1, b"Free memory unchanged.\n");
+        }
+        Some(leaked) => {
+            let mut buffer = [0u8; 20];
+            let len = petroleum::serial::format_dec_to_buffer(leaked as usize, &mut buffer);
+            safe_print!(1, b"LEAK: free memory short by ");
+            safe_print!(1, &buffer[..len]);
+            safe_print!(1, b" bytes\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"fork/mem_info failed\n");
+            failures += 1;
+        }
+    }
+
+    // Yield hands the CPU to the other process and back, turn by turn
+    match ping_pong(5) {
+        Some(true) => {
+            safe_print!(1, b"Yield alternated strictly.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"Yield ran a process OUT OF TURN\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"ping-pong setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // Semaphores keep a producer and a consumer in step over a small buffer
+    match producer_consumer(32) {
+        Some(true) => {
+            safe_print!(1, b"Consumer got all 32 items in order.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"Bounded buffer LOST OR REORDERED items\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"producer/consumer setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // A futex wake from the main thread reaches a waiting thread
+    match thread_futex() {
+        Some(true) => {
+            safe_print!(1, b"Futex woke the waiting thread.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"Futex wake MISSED the waiting thread\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"thread/futex setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // A user GS load must not move the kernel's per-CPU block
+    match user_gs_load(50_000) {
+        Some(true) => {
+            safe_print!(1, b"User GS load left the kernel's GS alone.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"User GS load REDIRECTED the kernel's per-CPU data\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"GS test setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // Kernel-only pages stay out of reach of ring 3
+    match kernel_read() {
+        Some(true) => {
+            safe_print!(1, b"Kernel page read faulted in user mode.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"Kernel page READABLE from user mode\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"kernel read test setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // Running off the stack hits the guard page, not whatever lies below
+    match stack_overflow() {
+        Some(true) => {
+            safe_print!(1, b"Stack overflow stopped at the guard page.\n");
+        }
+        Some(false) => {
+            safe_print!(1, b"Stack overflow went UNDETECTED\n");
+            failures += 1;
+        }
+        None => {
+            safe_print!(1, b"stack overflow test setup failed\n");
+            failures += 1;
+        }
+    }
+
+    // Sleep for a fixed number of timer ticks to exercise timed wakeup
+    safe_print!(1, b"Sleeping for 100 ticks...\n");
+    if sleep_ticks(100).is_err() {
+        safe_print!(1, b"sleep_ticks failed\n");
+        failures += 1;
     }
+    safe_print!(1, b"Woke up after sleep.\n");
 
     // Write final message and exit
     safe_print!(1, b"Toluene program finished executing.\n");
-    exit_process(0);
+    exit_process(if failures == 0 { 0 } else { 1 });
 }
@@ -1,6 +1,8 @@
 //! Typed system-call wrappers for the Toluene SDK.
 
-use fullerene_abi::{AbiInfo, AbiVersion, SyscallErrorCode, SyscallNumber};
+use core::sync::atomic::AtomicU32;
+
+use fullerene_abi::{AbiInfo, AbiVersion, MemInfo, ProcessInfo, SyscallErrorCode, SyscallNumber};
 
 #[inline]
 unsafe fn raw_syscall(
@@ -55,13 +57,141 @@ pub fn current_pid() -> usize {
     unsafe { raw_syscall(SyscallNumber::GetPid, 0, 0, 0, 0, 0, 0) as usize }
 }
 
-/// Yield the CPU to the scheduler.
+/// Let every other ready process of the caller's priority run, then
+/// continue; returns at once if there is none.
 pub fn yield_now() {
     unsafe {
         raw_syscall(SyscallNumber::Yield, 0, 0, 0, 0, 0, 0);
     }
 }
 
+/// Block the calling process for `ticks` timer ticks.
+pub fn sleep_ticks(ticks: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::SleepTicks, ticks, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// Block until [`futex_wake`] is called on `word`, unless it no longer holds
+/// `expected`.  Returns `Ok` on any wakeup, including spurious ones, so
+/// callers re-check `word` in a loop.
+pub fn futex_wait(word: &AtomicU32, expected: u32) -> Result<(), i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::FutexWait,
+            word.as_ptr() as u64,
+            expected as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|_| ())
+}
+
+/// Wake up to `count` processes blocked in [`futex_wait`] on `word` and
+/// return how many were woken.
+pub fn futex_wake(word: &AtomicU32, count: usize) -> Result<usize, i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::FutexWake,
+            word.as_ptr() as u64,
+            count as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|woken| woken as usize)
+}
+
+/// Create a counting semaphore holding `initial` units and return its id.
+pub fn sem_create(initial: u32) -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::SemCreate, initial as u64, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Block until a unit of semaphore `id` is free and take it.  Fails if the
+/// semaphore is destroyed while waiting.
+pub fn sem_wait(id: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::SemWait, id, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// Release a unit of semaphore `id`, waking its oldest waiter if any.
+pub fn sem_post(id: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::SemPost, id, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// Destroy semaphore `id`, failing the waits of everyone blocked on it.
+pub fn sem_destroy(id: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::SemDestroy, id, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// System-wide physical memory totals, all taken at the same instant.
+pub fn mem_info() -> Result<MemInfo, i64> {
+    let mut info = MemInfo::default();
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::MemInfo,
+            &mut info as *mut MemInfo as u64,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|_| info)
+}
+
+/// Fill `out` with the newest bytes of the kernel log, oldest first, and
+/// return how many were written.
+pub fn read_klog(out: &mut [u8]) -> Result<usize, i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::ReadKlog,
+            out.as_mut_ptr() as u64,
+            out.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|count| count as usize)
+}
+
+/// Current length of the kernel log in bytes.
+pub fn klog_len() -> Result<usize, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::ReadKlog, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|len| len as usize)
+}
+
+/// Current wall-clock time in seconds since the Unix epoch (UTC).
+pub fn get_time_of_day() -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::GetTimeOfDay, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Nanoseconds from the kernel's TSC clock.  Only the difference between
+/// two readings is meaningful; use it to time intervals shorter than a tick.
+pub fn clock_monotonic() -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::ClockMonotonic, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Set the scheduling priority (0..=31, higher runs first) of the caller
+/// (`pid == 0`) or of one of its children.
+pub fn set_priority(pid: u64, priority: u8) -> Result<(), i64> {
+    let value =
+        unsafe { raw_syscall(SyscallNumber::SetPriority, pid, priority as u64, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
 /// Terminate the process with an exit code.
 pub fn exit_process(code: i32) -> ! {
     unsafe {
@@ -72,6 +202,22 @@ pub fn exit_process(code: i32) -> ! {
     }
 }
 
+/// Power the machine off.  Under QEMU with flasks' `isa-debug-exit`
+/// device, QEMU exits with status `33 + 2 * code`, which `flasks --test`
+/// turns back into `code`.  Only returns if the call failed (a code above
+/// 111, or a caller that is not privileged), with the error.
+pub fn shutdown(code: u8) -> i64 {
+    let value = unsafe { raw_syscall(SyscallNumber::Shutdown, code as u64, 0, 0, 0, 0, 0) };
+    syscall_result(value).err().unwrap_or(0)
+}
+
+/// Reset the machine.  Only returns if the call failed (a caller that is
+/// not privileged), with the error.
+pub fn reboot() -> i64 {
+    let value = unsafe { raw_syscall(SyscallNumber::Reboot, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value).err().unwrap_or(0)
+}
+
 /// Write raw bytes to a file descriptor.
 pub fn write(fd: i32, data: &[u8]) -> Result<usize, i64> {
     let value = unsafe {
@@ -108,14 +254,28 @@ pub fn open_read(path: &str) -> Result<i32, i64> {
 }
 
 /// Read bytes from a file descriptor.
+///
+/// On the console, fd 0 waits for Enter (or for `data` to fill) and returns
+/// the line with its newline; `Ok(0)` means Ctrl-D was pressed on an empty
+/// line.
 pub fn read(fd: i32, data: &mut [u8]) -> Result<usize, i64> {
+    read_with_flags(fd, data, 0)
+}
+
+/// Like [`read`], but fails with `WOULD_BLOCK` instead of waiting when no
+/// console input is ready.
+pub fn try_read(fd: i32, data: &mut [u8]) -> Result<usize, i64> {
+    read_with_flags(fd, data, fullerene_abi::read_flags::NONBLOCK)
+}
+
+fn read_with_flags(fd: i32, data: &mut [u8], flags: u64) -> Result<usize, i64> {
     let value = unsafe {
         raw_syscall(
             SyscallNumber::Read,
             fd as u64,
             data.as_mut_ptr() as u64,
             data.len() as u64,
-            0,
+            flags,
             0,
             0,
         )
@@ -129,6 +289,29 @@ pub fn close(fd: i32) -> Result<(), i64> {
     syscall_result(value).map(|_| ())
 }
 
+/// Open the lowest free file descriptor on the same file, pipe or console
+/// as `fd`.
+pub fn dup(fd: i32) -> Result<i32, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Dup, fd as u64, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|fd| fd as i32)
+}
+
+/// Make `new_fd` refer to what `old_fd` does, closing `new_fd` first.
+pub fn dup2(old_fd: i32, new_fd: i32) -> Result<i32, i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::Dup2,
+            old_fd as u64,
+            new_fd as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|fd| fd as i32)
+}
+
 /// Start an ELF image in a new isolated process.
 pub fn spawn_image(image: &[u8], name: &str) -> Result<u64, i64> {
     let value = unsafe {
@@ -145,6 +328,143 @@ pub fn spawn_image(image: &[u8], name: &str) -> Result<u64, i64> {
     syscall_result(value)
 }
 
+/// Replace the calling process with the ELF executable at `path`, passing
+/// `args` as its `argv`. Only returns if the exec failed, with the error.
+pub fn exec(path: &str, args: &[&str]) -> i64 {
+    let mut nul_terminated = alloc::vec::Vec::with_capacity(path.len() + 1);
+    nul_terminated.extend_from_slice(path.as_bytes());
+    nul_terminated.push(0);
+    let mut block = alloc::vec::Vec::new();
+    for arg in args {
+        block.extend_from_slice(arg.as_bytes());
+        block.push(0);
+    }
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::Exec,
+            nul_terminated.as_ptr() as u64,
+            block.as_ptr() as u64,
+            block.len() as u64,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).err().unwrap_or(0)
+}
+
+/// Duplicate the calling process. Returns `Ok(0)` in the child and the
+/// child's pid in the parent.
+pub fn fork() -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Fork, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Block until child `pid` exits, reap it and return its exit code.
+///
+/// `Wait` with a nonzero pid behaves as [`waitpid`]; with `pid == 0` it
+/// only yields and returns 0.
+pub fn wait(pid: u64) -> Result<i32, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Wait, pid, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|code| code as u32 as i32)
+}
+
+/// Block until child `pid` (any child when `pid == 0`) exits, reap it and
+/// return its exit code.
+pub fn waitpid(pid: u64) -> Result<i32, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::WaitPid, pid, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|code| code as u32 as i32)
+}
+
+/// Start a thread of this process at `entry` on the stack whose top is
+/// `stack`, and return a handle for [`join_thread`].  The thread shares the
+/// address space; it must end with [`exit_thread`] rather than return.
+pub fn create_thread(entry: extern "C" fn() -> !, stack: usize) -> Result<u64, i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::CreateThread,
+            entry as usize as u64,
+            stack as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value)
+}
+
+/// Block until the thread behind `handle` exits and return its exit code.
+pub fn join_thread(handle: u64) -> Result<i32, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::JoinThread, handle, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|code| code as u32 as i32)
+}
+
+/// End the calling thread with an exit code for [`join_thread`].
+pub fn exit_thread(code: i32) -> ! {
+    unsafe {
+        raw_syscall(SyscallNumber::ExitThread, code as u64, 0, 0, 0, 0, 0);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Move the program break to `addr` and return the new break.
+/// `addr == 0` returns the current break.
+pub fn brk(addr: usize) -> Result<usize, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Brk, addr as u64, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|brk| brk as usize)
+}
+
+/// Grow (or with a negative `delta`, shrink) the heap by `delta` bytes and
+/// return the previous break, i.e. the start of the new memory.
+pub fn sbrk(delta: isize) -> Result<usize, i64> {
+    let old = brk(0)?;
+    if delta != 0 {
+        let new = old
+            .checked_add_signed(delta)
+            .ok_or(-SyscallErrorCode::InvalidArgument.as_i64())?;
+        brk(new)?;
+    }
+    Ok(old)
+}
+
+/// Map `pages` zeroed pages with protection `prot` (a combination of
+/// [`abi::memory_protection`](fullerene_abi::memory_protection) bits) and
+/// return their address.
+pub fn mmap(pages: usize, prot: u64) -> Result<usize, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Mmap, pages as u64, prot, 0, 0, 0, 0) };
+    syscall_result(value).map(|addr| addr as usize)
+}
+
+/// Release a mapping returned by [`mmap`]; `pages` must match.
+pub fn munmap(addr: usize, pages: usize) -> Result<(), i64> {
+    let value =
+        unsafe { raw_syscall(SyscallNumber::Munmap, addr as u64, pages as u64, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// Create a zeroed shared-memory segment of at least `size` bytes and
+/// return its id, which other processes pass to [`shm_attach`].
+pub fn shm_create(size: usize) -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::ShmCreate, size as u64, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Map shared-memory segment `id` read-write and return its address.
+pub fn shm_attach(id: u64) -> Result<usize, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::ShmAttach, id, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|addr| addr as usize)
+}
+
+/// Unmap shared-memory segment `id`; the segment is freed once every
+/// process has detached.
+pub fn shm_detach(id: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::ShmDetach, id, 0, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
 /// Write raw bytes to stdout (fd 1).
 pub fn stdout_write(data: &[u8]) -> Result<usize, i64> {
     write(1, data)
@@ -161,9 +481,58 @@ pub fn print(s: &str) {
     let _ = stdout_write(s.as_bytes());
 }
 
-/// Get the number of active processes, if supported by the kernel.
+/// Rename the calling process as shown by `ps`; long names are truncated.
+pub fn set_name(name: &str) -> Result<(), i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::SetName,
+            name.as_ptr() as u64,
+            name.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|_| ())
+}
+
+/// Send `signal` (see [`fullerene_abi::signals`]) to `pid`; pid 0 is the
+/// caller.  Signal 0 only checks that the process exists.
+pub fn kill(pid: u64, signal: u64) -> Result<(), i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::Kill, pid, signal, 0, 0, 0, 0) };
+    syscall_result(value).map(|_| ())
+}
+
+/// Return the signals sent to this process since the last call, as
+/// `1 << signal` bits.  A set `SIGTERM` bit is a request to exit.
+pub fn take_signals() -> Result<u64, i64> {
+    let value = unsafe { raw_syscall(SyscallNumber::TakeSignals, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value)
+}
+
+/// Fill `out` with the kernel's process records and return how many
+/// processes exist.  A result larger than `out.len()` means the list was cut
+/// short; retry with a buffer at least that long.
+pub fn list_processes(out: &mut [ProcessInfo]) -> Result<usize, i64> {
+    let value = unsafe {
+        raw_syscall(
+            SyscallNumber::ListProcesses,
+            out.as_mut_ptr() as u64,
+            core::mem::size_of_val(out) as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    syscall_result(value).map(|count| count as usize)
+}
+
+/// Get the number of processes, if supported by the kernel.
 pub fn process_count() -> Option<usize> {
-    None
+    let value = unsafe { raw_syscall(SyscallNumber::ListProcesses, 0, 0, 0, 0, 0, 0) };
+    syscall_result(value).ok().map(|count| count as usize)
 }
 
 /// Get system uptime in microseconds.